@@ -0,0 +1,144 @@
+//! Heading anchors and intra-document links
+//!
+//! Headings get GitHub-compatible anchors (see [`slugify`](crate::slugify)),
+//! which the HTML export emits as `id` attributes. Links of the form
+//! `[text](#anchor)` can then be checked against the headings that exist.
+
+use crate::{Document, InlineNode};
+use serde::Serialize;
+
+/// A link pointing at an anchor within the same document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InternalLink {
+    /// Index of the top-level node containing the link
+    pub node_index: usize,
+    /// The anchor, without the leading `#`
+    pub anchor: String,
+    /// Plain text of the link
+    pub text: String,
+    /// Index of the heading the anchor resolves to, if any
+    pub target: Option<usize>,
+}
+
+impl InternalLink {
+    /// Returns true if no heading has the link's anchor
+    pub fn is_broken(&self) -> bool {
+        self.target.is_none()
+    }
+}
+
+/// Every intra-document link, resolved against the heading anchors
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InternalLinkReport {
+    /// The links, in document order
+    pub links: Vec<InternalLink>,
+}
+
+impl InternalLinkReport {
+    /// Returns true if every link resolves to a heading
+    pub fn is_ok(&self) -> bool {
+        self.links.iter().all(|link| !link.is_broken())
+    }
+
+    /// The links whose anchor matches no heading
+    pub fn broken(&self) -> impl Iterator<Item = &InternalLink> {
+        self.links.iter().filter(|link| link.is_broken())
+    }
+}
+
+impl Document {
+    /// Returns the anchor of the heading at `node_index`
+    ///
+    /// Repeated heading texts get numbered anchors (`intro`, `intro-1`, ...)
+    /// in document order, as on GitHub.
+    pub fn heading_anchor(&self, node_index: usize) -> Option<String> {
+        self.heading_anchors()
+            .into_iter()
+            .find(|(index, _)| *index == node_index)
+            .map(|(_, anchor)| anchor)
+    }
+
+    /// Returns the index of the heading with the given anchor
+    ///
+    /// A leading `#` is ignored.
+    pub fn resolve_anchor(&self, anchor: &str) -> Option<usize> {
+        let anchor = anchor.strip_prefix('#').unwrap_or(anchor);
+        self.heading_anchors()
+            .into_iter()
+            .find(|(_, candidate)| candidate == anchor)
+            .map(|(index, _)| index)
+    }
+
+    /// Finds every `#anchor` link and checks that it points at a heading
+    pub fn check_internal_links(&self) -> InternalLinkReport {
+        let anchors = self.heading_anchors();
+        let mut links = Vec::new();
+
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            node.walk(&mut |block| {
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        let InlineNode::Link { url, children, .. } = inline else {
+                            return;
+                        };
+                        let Some(anchor) = url.strip_prefix('#') else {
+                            return;
+                        };
+                        links.push(InternalLink {
+                            node_index,
+                            anchor: anchor.to_string(),
+                            text: InlineNode::plain_text_of(children),
+                            target: anchors
+                                .iter()
+                                .find(|(_, candidate)| candidate == anchor)
+                                .map(|(index, _)| *index),
+                        });
+                    });
+                }
+            });
+        }
+
+        InternalLinkReport { links }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    const SOURCE: &str = "# Getting Started\n\n\
+                          See [setup](#setup) and [the FAQ](#faq).\n\n\
+                          ## Setup\n\n\
+                          > Back to [top](#getting-started)\n\n\
+                          ## Setup\n\n\
+                          Visit [the site](https://example.com).\n";
+
+    #[test]
+    fn test_heading_anchor() {
+        let doc = Document::try_from(Text::<Markdown>::new(SOURCE)).unwrap();
+        assert_eq!(doc.heading_anchor(0).as_deref(), Some("getting-started"));
+        assert_eq!(doc.heading_anchor(4).as_deref(), Some("setup-1"));
+        assert_eq!(doc.heading_anchor(1), None);
+
+        assert_eq!(doc.resolve_anchor("#setup"), Some(2));
+        assert_eq!(doc.resolve_anchor("setup-1"), Some(4));
+        assert_eq!(doc.resolve_anchor("missing"), None);
+    }
+
+    #[test]
+    fn test_check_internal_links() {
+        let doc = Document::try_from(Text::<Markdown>::new(SOURCE)).unwrap();
+        let report = doc.check_internal_links();
+
+        let anchors: Vec<&str> = report.links.iter().map(|l| l.anchor.as_str()).collect();
+        assert_eq!(anchors, ["setup", "faq", "getting-started"]);
+        assert_eq!(report.links[0].target, Some(2));
+        assert_eq!(report.links[2].node_index, 3);
+
+        let broken: Vec<&InternalLink> = report.broken().collect();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].text, "the FAQ");
+        assert!(!report.is_ok());
+    }
+}
@@ -0,0 +1,350 @@
+//! Content-addressed embedded assets
+//!
+//! Images pasted into a document have no URL of their own. Rather than
+//! inlining them as `data:` URLs, which bloat every save and diff, their
+//! bytes go into an [`AssetStore`] under the SHA-256 of their content and
+//! the image refers to them as `asset:<hash>`. The same image pasted twice
+//! is stored once. On export, [`Document::embed_assets`] turns the asset
+//! URLs back into `data:` URLs, or [`Document::rewrite_asset_urls`] points
+//! them at wherever the assets were written.
+
+use crate::{Document, EditError, Editor, InlineNode, Node};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// URL scheme of embedded assets
+pub const ASSET_SCHEME: &str = "asset";
+
+/// Bytes of an embedded asset and their media type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    /// Media type, e.g. `image/png`
+    pub mime_type: String,
+    /// Content of the asset
+    pub bytes: Vec<u8>,
+}
+
+impl Asset {
+    /// Creates an asset
+    pub fn new(mime_type: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            bytes: bytes.into(),
+        }
+    }
+
+    /// The asset's id: the SHA-256 of its bytes, as 64 hex digits
+    pub fn id(&self) -> String {
+        Sha256::digest(&self.bytes)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// The `asset:` URL images refer to the asset with
+    pub fn url(&self) -> String {
+        asset_url(&self.id())
+    }
+
+    /// The asset as a base64 `data:` URL
+    pub fn to_data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime_type,
+            STANDARD.encode(&self.bytes)
+        )
+    }
+
+    /// Decodes a `data:` URL, or returns `None` if the URL isn't one or
+    /// its content is malformed
+    ///
+    /// Content without `;base64` is taken as is, and the media type
+    /// defaults to `text/plain` as in browsers.
+    pub fn from_data_url(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.trim().split_once(':')?;
+        if !scheme.eq_ignore_ascii_case("data") {
+            return None;
+        }
+        let (header, content) = rest.split_once(',')?;
+        let (mime_type, base64) = match header.strip_suffix(";base64") {
+            Some(mime_type) => (mime_type, true),
+            None => (header, false),
+        };
+        let bytes = match base64 {
+            true => STANDARD.decode(content.trim()).ok()?,
+            false => content.as_bytes().to_vec(),
+        };
+        let mime_type = match mime_type.split(';').next().unwrap_or_default() {
+            "" => "text/plain",
+            mime_type => mime_type,
+        };
+        Some(Self::new(mime_type.to_ascii_lowercase(), bytes))
+    }
+}
+
+/// The `asset:` URL of an asset id
+pub fn asset_url(id: &str) -> String {
+    format!("{ASSET_SCHEME}:{id}")
+}
+
+/// The asset id an `asset:` URL refers to, or `None` for other URLs
+pub fn asset_id(url: &str) -> Option<&str> {
+    let (scheme, id) = url.split_once(':')?;
+    (scheme.eq_ignore_ascii_case(ASSET_SCHEME) && !id.is_empty()).then_some(id)
+}
+
+/// Storage of embedded assets by id
+///
+/// Assets are addressed by their content, so adding bytes that are already
+/// stored keeps a single copy.
+pub trait AssetStore {
+    /// Stores an asset, returning its id
+    fn insert(&mut self, asset: Asset) -> String;
+
+    /// The asset with the given id, if stored
+    fn get(&self, id: &str) -> Option<Asset>;
+
+    /// Removes an asset, returning it if it was stored
+    fn remove(&mut self, id: &str) -> Option<Asset>;
+
+    /// Ids of the stored assets, sorted
+    fn ids(&self) -> Vec<String>;
+
+    /// Returns true if an asset with the given id is stored
+    fn contains(&self, id: &str) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Removes the assets none of `documents` refer to, returning their ids
+    fn remove_unused(&mut self, documents: &[&Document]) -> Vec<String> {
+        let used: BTreeSet<String> = documents
+            .iter()
+            .flat_map(|document| document.asset_ids())
+            .collect();
+        let unused: Vec<String> = self
+            .ids()
+            .into_iter()
+            .filter(|id| !used.contains(id))
+            .collect();
+        for id in &unused {
+            self.remove(id);
+        }
+        unused
+    }
+}
+
+/// Assets kept in memory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryAssetStore {
+    assets: BTreeMap<String, Asset>,
+}
+
+impl MemoryAssetStore {
+    /// An empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored assets
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Returns true if no asset is stored
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+impl AssetStore for MemoryAssetStore {
+    fn insert(&mut self, asset: Asset) -> String {
+        let id = asset.id();
+        self.assets.entry(id.clone()).or_insert(asset);
+        id
+    }
+
+    fn get(&self, id: &str) -> Option<Asset> {
+        self.assets.get(id).cloned()
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Asset> {
+        self.assets.remove(id)
+    }
+
+    fn ids(&self) -> Vec<String> {
+        self.assets.keys().cloned().collect()
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.assets.contains_key(id)
+    }
+}
+
+impl Document {
+    /// Calls `f` with the URL of every image and figure, in reading order
+    fn image_urls_mut(&mut self, f: &mut dyn FnMut(&mut String)) {
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| {
+                if let Node::Figure { url, .. } = block {
+                    f(url);
+                }
+                for inlines in block.inline_runs_mut() {
+                    for inline in inlines {
+                        inline.walk_mut(&mut |inline| {
+                            if let InlineNode::Image { url, .. } = inline {
+                                f(url);
+                            }
+                        });
+                    }
+                }
+            });
+        }
+    }
+
+    /// Ids of the assets the document's images refer to, without
+    /// duplicates, in order of first use
+    pub fn asset_ids(&self) -> Vec<String> {
+        let mut ids = Vec::new();
+        for image in self.images() {
+            if let Some(id) = asset_id(&image.url)
+                && !ids.iter().any(|known| known == id)
+            {
+                ids.push(id.to_string());
+            }
+        }
+        ids
+    }
+
+    /// Ids of the assets the document refers to that `store` doesn't hold
+    pub fn missing_assets(&self, store: &dyn AssetStore) -> Vec<String> {
+        self.asset_ids()
+            .into_iter()
+            .filter(|id| !store.contains(id))
+            .collect()
+    }
+
+    /// Moves images with `data:` URLs into `store`, referring to them by
+    /// `asset:` URL instead, and returns how many images were changed
+    ///
+    /// Identical images share one stored asset. Malformed `data:` URLs are
+    /// left alone.
+    pub fn extract_assets(&mut self, store: &mut dyn AssetStore) -> usize {
+        let mut count = 0;
+        self.image_urls_mut(&mut |url| {
+            if let Some(asset) = Asset::from_data_url(url) {
+                *url = asset_url(&store.insert(asset));
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Replaces the `asset:` URLs for which `f` returns a new URL, given
+    /// the asset id, and returns how many were replaced
+    ///
+    /// Used on export to point images at the files their assets were
+    /// written to, e.g. `images/<id>.png`.
+    pub fn rewrite_asset_urls(&mut self, mut f: impl FnMut(&str) -> Option<String>) -> usize {
+        let mut count = 0;
+        self.image_urls_mut(&mut |url| {
+            if let Some(new_url) = asset_id(url).and_then(&mut f) {
+                *url = new_url;
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Replaces `asset:` URLs with `data:` URLs holding the assets from
+    /// `store`, for exports that must stand alone, and returns how many
+    /// were replaced
+    ///
+    /// URLs of assets missing from the store are kept.
+    pub fn embed_assets(&mut self, store: &dyn AssetStore) -> usize {
+        self.rewrite_asset_urls(|id| store.get(id).map(|asset| asset.to_data_url()))
+    }
+}
+
+impl Editor {
+    /// Store a pasted image in `store` and insert it at the end of the
+    /// selection, returning the asset id
+    ///
+    /// The image is pasted like an inline
+    /// [`DocumentFragment`](crate::DocumentFragment) and undone in one step;
+    /// the asset stays in the store.
+    pub fn paste_image(
+        &mut self,
+        store: &mut dyn AssetStore,
+        asset: Asset,
+        alt: &str,
+    ) -> Result<String, EditError> {
+        let id = store.insert(asset);
+        let image = InlineNode::image(asset_url(&id).as_str(), alt);
+        self.paste_fragment(vec![image].into())?;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, Markdown, Text};
+
+    const PNG: &str = "data:image/png;base64,iVBORw0KGgo=";
+
+    #[test]
+    fn test_data_urls() {
+        let asset = Asset::from_data_url(PNG).unwrap();
+        assert_eq!(asset.mime_type, "image/png");
+        assert_eq!(asset.bytes, b"\x89PNG\r\n\x1a\n");
+        assert_eq!(asset.to_data_url(), PNG);
+        assert_eq!(asset.id().len(), 64);
+        assert_eq!(asset_id(&asset.url()), Some(asset.id().as_str()));
+
+        let plain = Asset::from_data_url("DATA:,a%20b").unwrap();
+        assert_eq!(plain, Asset::new("text/plain", "a%20b"));
+        assert_eq!(Asset::from_data_url("data:image/png;base64,***"), None);
+        assert_eq!(Asset::from_data_url("https://example.com/a.png"), None);
+        assert_eq!(asset_id("https://example.com"), None);
+        assert_eq!(asset_id("asset:"), None);
+    }
+
+    #[test]
+    fn test_extract_and_embed_assets() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(format!(
+            "![a]({PNG}) and ![b]({PNG})\n\n![remote](https://example.com/c.png)\n"
+        )))
+        .unwrap();
+        doc.nodes.push(Node::figure(PNG, "fig", ""));
+        let original = doc.clone();
+
+        let mut store = MemoryAssetStore::new();
+        assert_eq!(doc.extract_assets(&mut store), 3);
+        assert_eq!(store.len(), 1, "identical images share an asset");
+        let id = store.ids().remove(0);
+        assert_eq!(doc.asset_ids(), vec![id.clone()]);
+        assert!(doc.missing_assets(&store).is_empty());
+        let urls: Vec<String> = doc.images().into_iter().map(|image| image.url).collect();
+        let url = asset_url(&id);
+        assert_eq!(urls, [&url, &url, "https://example.com/c.png", &url]);
+
+        let mut exported = doc.clone();
+        assert_eq!(
+            exported.rewrite_asset_urls(|id| Some(format!("images/{id}.png"))),
+            3
+        );
+        let html = Text::<Html>::try_from(&exported).unwrap();
+        assert!(html.as_str().contains(&format!("src=\"images/{id}.png\"")));
+
+        assert_eq!(doc.embed_assets(&store), 3);
+        assert_eq!(doc, original);
+
+        doc.extract_assets(&mut store);
+        assert_eq!(store.remove_unused(&[&doc]), Vec::<String>::new());
+        assert_eq!(store.remove_unused(&[]), vec![id.clone()]);
+        assert_eq!(doc.missing_assets(&store), vec![id]);
+        assert_eq!(doc.embed_assets(&store), 0);
+    }
+}
@@ -0,0 +1,126 @@
+//! Per-node attributes
+//!
+//! Applications attach arbitrary string data to top-level nodes, such as
+//! source line numbers, tracking IDs or custom classes. Attributes are kept
+//! beside the nodes in [`Document::node_attributes`], keyed by node index,
+//! so they survive JSON round trips without touching the node types, and
+//! the [`Editor`](crate::Editor) moves them along with their nodes through
+//! edits. HTML export writes them as `data-*` attributes on the node's
+//! first element.
+
+use crate::{Document, EditError};
+use std::collections::BTreeMap;
+
+/// The attributes of one node, by name
+pub type NodeAttributes = BTreeMap<String, String>;
+
+/// The `data-*` attribute name for an attribute, or `None` if its name has
+/// no letters or digits
+///
+/// Names are lowercased and characters other than letters, digits, `-` and
+/// `_` become `-`.
+pub(crate) fn data_attribute_name(name: &str) -> Option<String> {
+    if !name.chars().any(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '-' | '_' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect();
+    Some(format!("data-{name}"))
+}
+
+impl Document {
+    /// Sets an attribute of the top-level node at `node_index`, returning
+    /// its previous value
+    pub fn set_node_attribute(
+        &mut self,
+        node_index: usize,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Option<String>, EditError> {
+        if node_index >= self.nodes.len() {
+            return Err(EditError::index_out_of_bounds().at_node(node_index));
+        }
+        Ok(self
+            .node_attributes
+            .entry(node_index)
+            .or_default()
+            .insert(name.into(), value.into()))
+    }
+
+    /// Returns an attribute of the top-level node at `node_index`
+    pub fn node_attribute(&self, node_index: usize, name: &str) -> Option<&str> {
+        self.node_attributes
+            .get(&node_index)?
+            .get(name)
+            .map(String::as_str)
+    }
+
+    /// Returns all attributes of the top-level node at `node_index`
+    pub fn attributes_of(&self, node_index: usize) -> Option<&NodeAttributes> {
+        self.node_attributes.get(&node_index)
+    }
+
+    /// Removes an attribute of the top-level node at `node_index`,
+    /// returning its value
+    pub fn remove_node_attribute(&mut self, node_index: usize, name: &str) -> Option<String> {
+        let attributes = self.node_attributes.get_mut(&node_index)?;
+        let value = attributes.remove(name);
+        if attributes.is_empty() {
+            self.node_attributes.remove(&node_index);
+        }
+        value
+    }
+
+    /// Removes all attributes of the top-level node at `node_index`
+    pub fn clear_node_attributes(&mut self, node_index: usize) -> Option<NodeAttributes> {
+        self.node_attributes.remove(&node_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EditErrorKind, Html, Json, Markdown, Text};
+
+    #[test]
+    fn test_node_attributes() {
+        let mut doc = Document::try_from(Text::<Markdown>::new("# Title\n\nText\n")).unwrap();
+        assert_eq!(doc.set_node_attribute(1, "line", "3").unwrap(), None);
+        doc.set_node_attribute(1, "Tracking ID", "a\"b").unwrap();
+        doc.set_node_attribute(0, "class", "hero").unwrap();
+        assert_eq!(
+            doc.set_node_attribute(0, "class", "intro").unwrap(),
+            Some("hero".to_string())
+        );
+        assert_eq!(doc.node_attribute(1, "line"), Some("3"));
+        assert_eq!(doc.attributes_of(1).unwrap().len(), 2);
+        let err = doc.set_node_attribute(2, "line", "5").unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::IndexOutOfBounds);
+
+        let json = Text::<Json>::try_from(&doc).unwrap();
+        let restored = Document::try_from(json).unwrap();
+        assert_eq!(restored, doc);
+
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert_eq!(
+            html.as_str(),
+            "<h1 id=\"title\" data-class=\"intro\">Title</h1>\
+             <p data-tracking-id=\"a&quot;b\" data-line=\"3\">Text</p>"
+        );
+
+        assert_eq!(
+            doc.remove_node_attribute(0, "class"),
+            Some("intro".to_string())
+        );
+        assert_eq!(doc.attributes_of(0), None);
+        assert_eq!(doc.clear_node_attributes(1).unwrap().len(), 2);
+        assert!(doc.node_attributes.is_empty());
+        assert_eq!(data_attribute_name("--"), None);
+    }
+}
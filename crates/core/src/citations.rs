@@ -0,0 +1,449 @@
+//! Citations and the bibliography
+//!
+//! Pandoc-style `[@key]` and `[@key, p. 12]` citations are parsed from
+//! Markdown into [`InlineNode::Citation`], and the entries they cite are
+//! kept in [`Document::bibliography`] as CSL-JSON items, as reference
+//! managers such as Zotero export them. Exports label citations
+//! author-date from their entries and append a references section listing
+//! the cited entries in the order they were first cited.
+//!
+//! Markdown writes the references section between `<!-- references -->`
+//! and `<!-- /references -->` comment markers and drops it when read back,
+//! since it is generated. The bibliography itself is only kept in JSON.
+
+use crate::{Document, InlineNode, Node, ParseError, TextNode};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+static CITATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[@([\p{L}\p{N}_][\p{L}\p{N}_:.#$%&+?<>~/-]*)(?:,\s*([^\[\]]*?))?\s*\]").unwrap()
+});
+
+/// The comment opening the references section in Markdown
+pub(crate) const REFERENCES_START: &str = "<!-- references -->";
+/// The comment closing the references section in Markdown
+pub(crate) const REFERENCES_END: &str = "<!-- /references -->";
+
+/// A personal or institutional name in a CSL-JSON item
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CslName {
+    /// Family name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    /// Given names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given: Option<String>,
+    /// A name that isn't split, e.g. of an organization
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub literal: Option<String>,
+}
+
+impl CslName {
+    /// The family name, or the whole name if it isn't split
+    fn short(&self) -> &str {
+        self.family
+            .as_deref()
+            .or(self.literal.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// The name as `Family, Given`
+    fn sorted(&self) -> String {
+        match (&self.family, &self.given) {
+            (Some(family), Some(given)) => format!("{family}, {given}"),
+            _ => self.short().to_string(),
+        }
+    }
+}
+
+/// A bibliography entry, as a CSL-JSON item
+///
+/// The common fields are typed; all others are kept as they were read, so
+/// entries round-trip through JSON unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BibliographyEntry {
+    /// Citation key, as cited with `[@id]`
+    pub id: String,
+    /// Item type, e.g. `book` or `article-journal`
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// Title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Authors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub author: Vec<CslName>,
+    /// Publication date, as CSL `date-parts`, `literal` or `raw`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued: Option<serde_json::Value>,
+    /// Journal or book the item appeared in
+    #[serde(
+        rename = "container-title",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub container_title: Option<String>,
+    /// Publisher
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    /// Link to the item
+    #[serde(rename = "URL", default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Other CSL-JSON fields
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+impl BibliographyEntry {
+    /// Creates an entry with a key and title
+    pub fn new(id: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            title: Some(title.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the items of a CSL-JSON array, or a single item
+    pub fn from_csl_json(json: &str) -> Result<Vec<Self>, ParseError> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).map_err(|err| ParseError::Json(err.to_string()))?;
+        let entries = match value {
+            serde_json::Value::Array(_) => serde_json::from_value(value),
+            _ => serde_json::from_value(value).map(|entry| vec![entry]),
+        };
+        entries.map_err(|err| ParseError::Json(err.to_string()))
+    }
+
+    /// The year of publication
+    pub fn year(&self) -> Option<String> {
+        let issued = self.issued.as_ref()?;
+        match issued.pointer("/date-parts/0/0") {
+            Some(serde_json::Value::Number(year)) => Some(year.to_string()),
+            Some(serde_json::Value::String(year)) => Some(year.clone()),
+            _ => issued
+                .get("literal")
+                .or_else(|| issued.get("raw"))
+                .and_then(|date| date.as_str())
+                .map(str::to_string),
+        }
+    }
+
+    /// The author-date label citations of the entry show, e.g. `Doe 2020`
+    /// or `Doe et al. 2020`
+    pub fn label(&self) -> String {
+        let authors = match self.author.as_slice() {
+            [] => self.title.clone().unwrap_or_else(|| self.id.clone()),
+            [one] => one.short().to_string(),
+            [one, two] => format!("{} and {}", one.short(), two.short()),
+            [one, ..] => format!("{} et al.", one.short()),
+        };
+        match self.year() {
+            Some(year) => format!("{authors} {year}"),
+            None => authors,
+        }
+    }
+
+    /// The entry as a line of the references section, e.g.
+    /// `Doe, Jane. 2020. Title. Journal. Publisher.`
+    pub fn reference(&self) -> String {
+        let authors: Vec<String> = self.author.iter().map(CslName::sorted).collect();
+        let parts = [
+            (!authors.is_empty()).then(|| authors.join(", ")),
+            self.year(),
+            self.title.clone(),
+            self.container_title.clone(),
+            self.publisher.clone(),
+        ];
+        parts
+            .into_iter()
+            .flatten()
+            .map(|part| {
+                let part = part.trim_end_matches('.');
+                format!("{part}.")
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The text a citation is written as in Markdown
+pub(crate) fn citation_markdown(key: &str, locator: Option<&str>) -> String {
+    match locator {
+        Some(locator) => format!("[@{key}, {locator}]"),
+        None => format!("[@{key}]"),
+    }
+}
+
+/// Finds `[@key]` and `[@key, locator]` citations, as `(start, end,
+/// citation)` with byte offsets covering the brackets
+fn find_citations(text: &str) -> Vec<(usize, usize, InlineNode)> {
+    CITATION
+        .captures_iter(text)
+        .map(|captures| {
+            let whole = captures.get(0).expect("whole match");
+            let locator = captures
+                .get(2)
+                .map(|locator| locator.as_str().trim())
+                .filter(|locator| !locator.is_empty());
+            let citation = InlineNode::Citation {
+                key: captures[1].to_string(),
+                locator: locator.map(str::to_string),
+            };
+            (whole.start(), whole.end(), citation)
+        })
+        .collect()
+}
+
+/// Replaces a run of text nodes with the same formatting by its text with
+/// the citations in it split out, returning how many were found
+///
+/// The run is kept as it was if there are none.
+fn flush_run(run: &mut Vec<InlineNode>, result: &mut Vec<InlineNode>) -> usize {
+    let Some(InlineNode::Text(TextNode { formatting, .. })) = run.first() else {
+        return 0;
+    };
+    let formatting = formatting.clone();
+    let text = InlineNode::plain_text_of(run);
+    let citations = find_citations(&text);
+    if citations.is_empty() {
+        result.append(run);
+        return 0;
+    }
+    run.clear();
+    let found = citations.len();
+    let mut last = 0;
+    for (start, end, citation) in citations {
+        if start > last {
+            result.push(InlineNode::Text(TextNode::with_formatting(
+                &text[last..start],
+                formatting.clone(),
+            )));
+        }
+        result.push(citation);
+        last = end;
+    }
+    if last < text.len() {
+        result.push(InlineNode::Text(TextNode::with_formatting(
+            &text[last..],
+            formatting,
+        )));
+    }
+    found
+}
+
+/// Splits citations out of the text runs of `inlines`, returning how many
+/// were found
+///
+/// The parser may split text at brackets, so neighbouring text nodes with
+/// the same formatting are joined first, but only where a citation was
+/// found. Code and link text are left alone.
+fn split_citations(inlines: &mut Vec<InlineNode>) -> usize {
+    let mut found = 0;
+    let mut result = Vec::with_capacity(inlines.len());
+    let mut run = Vec::new();
+    for mut inline in std::mem::take(inlines) {
+        if let InlineNode::Text(TextNode { formatting, .. }) = &inline
+            && !formatting.code
+        {
+            let joins = matches!(
+                run.first(),
+                Some(InlineNode::Text(first)) if first.formatting == *formatting
+            );
+            if !joins {
+                found += flush_run(&mut run, &mut result);
+            }
+            run.push(inline);
+            continue;
+        }
+        found += flush_run(&mut run, &mut result);
+        if let InlineNode::InlineFootnote { children } = &mut inline {
+            found += split_citations(children);
+        }
+        result.push(inline);
+    }
+    found += flush_run(&mut run, &mut result);
+    *inlines = result;
+    found
+}
+
+/// The top-level blocks of the first generated references section,
+/// between comment markers and including them
+pub(crate) fn references_section(nodes: &[Node]) -> Option<Range<usize>> {
+    let is_marker =
+        |node: &Node, name: &str| matches!(node, Node::HtmlComment { text } if text.trim() == name);
+    let start = nodes
+        .iter()
+        .position(|node| is_marker(node, "references"))?;
+    let length = nodes[start..]
+        .iter()
+        .position(|node| is_marker(node, "/references"))?;
+    Some(start..start + length + 1)
+}
+
+impl Document {
+    /// Turns `[@key]` and `[@key, locator]` patterns in text into citations
+    ///
+    /// Markdown import does this already. Returns the number of citations
+    /// created.
+    pub fn parse_citations(&mut self) -> usize {
+        let mut found = 0;
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| {
+                for inlines in block.inline_runs_mut() {
+                    found += split_citations(inlines);
+                }
+            });
+        }
+        found
+    }
+
+    /// Adds the items of a CSL-JSON array to the bibliography, replacing
+    /// entries with the same key, and returns how many were read
+    pub fn load_csl_json(&mut self, json: &str) -> Result<usize, ParseError> {
+        let entries = BibliographyEntry::from_csl_json(json)?;
+        let count = entries.len();
+        for entry in entries {
+            self.add_bibliography_entry(entry);
+        }
+        Ok(count)
+    }
+
+    /// Adds an entry to the bibliography, replacing the entry with the
+    /// same key
+    pub fn add_bibliography_entry(&mut self, entry: BibliographyEntry) {
+        match self.bibliography.iter_mut().find(|e| e.id == entry.id) {
+            Some(existing) => *existing = entry,
+            None => self.bibliography.push(entry),
+        }
+    }
+
+    /// Returns the bibliography entry with the given key
+    pub fn bibliography_entry(&self, key: &str) -> Option<&BibliographyEntry> {
+        self.bibliography.iter().find(|entry| entry.id == key)
+    }
+
+    /// Keys of the cited entries, in the order they are first cited
+    pub fn cited_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        let mut visit = |inline: &InlineNode| {
+            if let InlineNode::Citation { key, .. } = inline
+                && !keys.contains(key)
+            {
+                keys.push(key.clone());
+            }
+        };
+        for node in &self.nodes {
+            node.walk(&mut |block| {
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut visit);
+                }
+            });
+        }
+        keys
+    }
+
+    /// The entries of the references section: those cited, in the order
+    /// they are first cited
+    ///
+    /// Citations of keys the bibliography lacks are left out; see
+    /// [`missing_citations`](Self::missing_citations).
+    pub fn references(&self) -> Vec<&BibliographyEntry> {
+        self.cited_keys()
+            .iter()
+            .filter_map(|key| self.bibliography_entry(key))
+            .collect()
+    }
+
+    /// Cited keys without a bibliography entry
+    pub fn missing_citations(&self) -> Vec<String> {
+        self.cited_keys()
+            .into_iter()
+            .filter(|key| self.bibliography_entry(key).is_none())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, Json, Markdown, Text};
+
+    const CSL: &str = r#"[
+        {"id": "doe2020", "type": "book", "title": "Writing Things",
+         "author": [{"family": "Doe", "given": "Jane"}],
+         "issued": {"date-parts": [[2020, 5]]}, "publisher": "Acme Press"},
+        {"id": "roe", "type": "article-journal", "title": "On Citing",
+         "author": [{"family": "Roe", "given": "R."}, {"family": "Poe"}, {"literal": "ACME"}],
+         "container-title": "Journal of Notes", "volume": "7"}
+    ]"#;
+
+    #[test]
+    fn test_citations() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "As shown [@doe2020, p. 12] and [@roe].\n\nAgain [@doe2020], not [@] or `[@code]`.\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            doc.nodes[0],
+            Node::Paragraph {
+                children: vec![
+                    InlineNode::text("As shown "),
+                    InlineNode::citation_at("doe2020", "p. 12"),
+                    InlineNode::text(" and "),
+                    InlineNode::citation("roe"),
+                    InlineNode::text("."),
+                ]
+            }
+        );
+        assert_eq!(doc.cited_keys(), vec!["doe2020", "roe"]);
+
+        assert_eq!(doc.load_csl_json(CSL).unwrap(), 2);
+        let entry = doc.bibliography_entry("roe").unwrap();
+        assert_eq!(entry.label(), "Roe et al.");
+        assert_eq!(entry.other["volume"], "7");
+        assert_eq!(
+            doc.bibliography_entry("doe2020").unwrap().reference(),
+            "Doe, Jane. 2020. Writing Things. Acme Press."
+        );
+        assert!(doc.missing_citations().is_empty());
+
+        let json = Text::<Json>::try_from(&doc).unwrap();
+        assert_eq!(Document::try_from(json).unwrap(), doc);
+
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(html.as_str().starts_with(
+            "<p>As shown <cite class=\"citation\"><a href=\"#ref-doe2020\">(Doe 2020, p. 12)</a></cite>"
+        ));
+        assert!(html.as_str().ends_with(
+            "<section class=\"references\"><h2>References</h2><ol>\
+             <li id=\"ref-doe2020\">Doe, Jane. 2020. Writing Things. Acme Press.</li>\
+             <li id=\"ref-roe\">Roe, R., Poe, ACME. On Citing. Journal of Notes.</li></ol></section>"
+        ));
+
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert!(
+            markdown
+                .as_str()
+                .starts_with("As shown [@doe2020, p. 12] and [@roe].")
+        );
+        assert!(markdown.as_str().ends_with(
+            "<!-- references -->\n\n1. Doe, Jane. 2020. Writing Things. Acme Press.\n\
+             2. Roe, R., Poe, ACME. On Citing. Journal of Notes.\n\n<!-- /references -->"
+        ));
+        let reparsed = Document::try_from(markdown).unwrap();
+        assert_eq!(reparsed.nodes, doc.nodes);
+
+        doc.bibliography.clear();
+        assert_eq!(doc.missing_citations(), vec!["doe2020", "roe"]);
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(
+            html.as_str()
+                .contains("<cite class=\"citation citation-missing\">[@roe]</cite>")
+        );
+        assert!(!html.as_str().contains("references"));
+    }
+}
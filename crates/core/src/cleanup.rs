@@ -0,0 +1,179 @@
+//! Detection of orphaned and empty content
+//!
+//! Long-lived documents accumulate blocks that no reader will ever reach:
+//! footnote definitions whose references were deleted, groups that lost
+//! their name, tables and lists emptied by earlier edits. Only top-level
+//! nodes are inspected, so each finding can be removed as a whole.
+
+use crate::{Document, InlineNode, Node};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Why a node is considered orphaned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanKind {
+    /// A footnote definition that nothing refers to
+    UnreferencedFootnote,
+    /// A group without a name
+    UnnamedGroup,
+    /// A table without any text in its header or rows
+    EmptyTable,
+    /// A list without any text in its items
+    EmptyList,
+}
+
+/// A top-level node found by [`Document::find_orphans`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Orphan {
+    /// Index of the node in the document
+    pub node_index: usize,
+    /// Why the node was reported
+    pub kind: OrphanKind,
+}
+
+/// Which orphans to look for and whether to remove them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupOptions {
+    /// Kinds of orphans to report
+    pub kinds: HashSet<OrphanKind>,
+    /// Remove the reported nodes instead of only reporting them
+    pub remove: bool,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            kinds: [
+                OrphanKind::UnreferencedFootnote,
+                OrphanKind::UnnamedGroup,
+                OrphanKind::EmptyTable,
+                OrphanKind::EmptyList,
+            ]
+            .into_iter()
+            .collect(),
+            remove: true,
+        }
+    }
+}
+
+impl CleanupOptions {
+    /// Removes every kind of orphan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only reports orphans, leaving the document unchanged
+    pub fn report_only(mut self) -> Self {
+        self.remove = false;
+        self
+    }
+
+    /// Restricts the cleanup to the given kinds
+    pub fn only(mut self, kinds: impl IntoIterator<Item = OrphanKind>) -> Self {
+        self.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    /// Leaves orphans of the given kind alone
+    pub fn without(mut self, kind: OrphanKind) -> Self {
+        self.kinds.remove(&kind);
+        self
+    }
+}
+
+impl Document {
+    /// Lists top-level nodes that are unreachable or empty
+    pub fn find_orphans(&self) -> Vec<Orphan> {
+        let references = footnote_references(&self.nodes);
+
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(node_index, node)| {
+                let kind = match node {
+                    Node::FootnoteDefinition(definition)
+                        if !references.contains(&definition.label) =>
+                    {
+                        OrphanKind::UnreferencedFootnote
+                    }
+                    Node::Group { name, .. } if name.trim().is_empty() => OrphanKind::UnnamedGroup,
+                    Node::Table { .. } if node.plain_text().trim().is_empty() => {
+                        OrphanKind::EmptyTable
+                    }
+                    Node::List { .. } if node.plain_text().trim().is_empty() => {
+                        OrphanKind::EmptyList
+                    }
+                    _ => return None,
+                };
+                Some(Orphan { node_index, kind })
+            })
+            .collect()
+    }
+}
+
+/// Labels of every footnote referenced anywhere in `nodes`
+pub(crate) fn footnote_references(nodes: &[Node]) -> HashSet<String> {
+    let mut labels = HashSet::new();
+    for node in nodes {
+        node.walk(&mut |block| {
+            if let Node::FootnoteReference(reference) = block {
+                labels.insert(reference.label.clone());
+            }
+            for inline in block.inline_runs().into_iter().flatten() {
+                inline.walk(&mut |inline| {
+                    if let InlineNode::FootnoteRef { label } = inline {
+                        labels.insert(label.clone());
+                    }
+                });
+            }
+        });
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ListItem, ListType};
+
+    #[test]
+    fn test_find_orphans() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::text("Text with a note"),
+            InlineNode::FootnoteRef {
+                label: "used".to_string(),
+            },
+        ]);
+        doc.add_footnote_definition("used", "Kept.");
+        doc.add_footnote_definition("stale", "Nobody points here.");
+        doc.nodes.push(Node::Group {
+            name: " ".to_string(),
+            children: vec![Node::paragraph("inside")],
+        });
+        doc.nodes.push(Node::List {
+            list_type: ListType::Unordered,
+            items: vec![ListItem::new(vec![Node::paragraph("")])],
+            start: None,
+            loose: false,
+        });
+        doc.nodes
+            .push(Node::simple_table(vec!["", ""], vec![vec!["", ""]]));
+        doc.nodes
+            .push(Node::simple_table(vec!["a"], Vec::<Vec<&str>>::new()));
+
+        let orphans = doc.find_orphans();
+        let kinds: Vec<OrphanKind> = orphans.iter().map(|o| o.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                OrphanKind::UnreferencedFootnote,
+                OrphanKind::UnnamedGroup,
+                OrphanKind::EmptyList,
+                OrphanKind::EmptyTable,
+            ]
+        );
+        assert_eq!(orphans[0].node_index, 2);
+    }
+}
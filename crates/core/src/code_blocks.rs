@@ -0,0 +1,408 @@
+//! Structured export of code blocks
+//!
+//! Documentation tooling, such as test runners that compile the examples in a
+//! guide, needs every code block together with where it lives. The report
+//! produced here lists each block with its info string split into language
+//! and attributes, its rendering properties and the headings it sits under.
+//! Identifiers can also be renamed across blocks, leaving strings and
+//! comments alone in languages whose syntax is known.
+
+use crate::{CodeBlockProperties, Document, Node};
+use serde::Serialize;
+use std::ops::Range;
+
+/// A code block found in the document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeBlockInfo {
+    /// Path to the block: the top-level node index followed by the child
+    /// indices through any containers (block quotes, list items, groups,
+    /// footnotes and definition list descriptions)
+    pub path: Vec<usize>,
+    /// First token of the info string, e.g. `rust`
+    pub language: String,
+    /// Remaining info string tokens, e.g. `ignore` or `should_panic`
+    pub attributes: Vec<String>,
+    /// The code itself
+    pub code: String,
+    /// Rendering properties such as highlighted lines and the start line
+    pub properties: CodeBlockProperties,
+    /// Titles of the enclosing headings, outermost first
+    pub headings: Vec<String>,
+}
+
+impl CodeBlockInfo {
+    /// Index of the top-level node containing the block
+    pub fn node_index(&self) -> usize {
+        self.path[0]
+    }
+
+    /// Title of the nearest enclosing heading
+    pub fn heading(&self) -> Option<&str> {
+        self.headings.last().map(String::as_str)
+    }
+
+    /// Returns true if the info string carries the given attribute
+    pub fn has_attribute(&self, attribute: &str) -> bool {
+        self.attributes.iter().any(|a| a == attribute)
+    }
+}
+
+/// All code blocks of a document in reading order
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CodeBlockReport {
+    /// The blocks, in document order
+    pub blocks: Vec<CodeBlockInfo>,
+}
+
+impl CodeBlockReport {
+    /// Builds the report for a document
+    pub fn new(document: &Document) -> Self {
+        let mut report = Self::default();
+        let mut headings: Vec<(u8, String)> = Vec::new();
+
+        for (index, node) in document.nodes.iter().enumerate() {
+            if let Node::Heading { level, .. } = node {
+                headings.retain(|(outer, _)| outer < level);
+                headings.push((*level, node.plain_text()));
+                continue;
+            }
+            let titles: Vec<String> = headings.iter().map(|(_, title)| title.clone()).collect();
+            report.collect(node, &mut vec![index], &titles);
+        }
+
+        report
+    }
+
+    fn collect(&mut self, node: &Node, path: &mut Vec<usize>, headings: &[String]) {
+        let visit = |report: &mut Self, children: &[Node], path: &mut Vec<usize>| {
+            for (i, child) in children.iter().enumerate() {
+                path.push(i);
+                report.collect(child, path, headings);
+                path.pop();
+            }
+        };
+
+        match node {
+            Node::CodeBlock {
+                language,
+                code,
+                properties,
+            } => {
+                let mut tokens = info_tokens(language).map(str::to_string);
+                self.blocks.push(CodeBlockInfo {
+                    path: path.clone(),
+                    language: tokens.next().unwrap_or_default(),
+                    attributes: tokens.collect(),
+                    code: code.clone(),
+                    properties: properties.clone(),
+                    headings: headings.to_vec(),
+                });
+            }
+            Node::BlockQuote { children }
+            | Node::Group { children, .. }
+            | Node::Callout { children, .. }
+            | Node::VisibilityBlock { children, .. } => visit(self, children, path),
+            Node::FootnoteDefinition(definition) => visit(self, &definition.content, path),
+            Node::List { items, .. } => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(i);
+                    visit(self, &item.children, path);
+                    path.pop();
+                }
+            }
+            Node::DefinitionList { items } => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(i);
+                    for (j, description) in item.descriptions.iter().enumerate() {
+                        path.push(j);
+                        visit(self, description, path);
+                        path.pop();
+                    }
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Number of code blocks
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns true if the document has no code blocks
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Blocks written in the given language
+    pub fn with_language<'a>(
+        &'a self,
+        language: &'a str,
+    ) -> impl Iterator<Item = &'a CodeBlockInfo> + 'a {
+        self.blocks
+            .iter()
+            .filter(move |block| block.language == language)
+    }
+
+    /// Distinct languages used, sorted
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages: Vec<&str> = self
+            .blocks
+            .iter()
+            .map(|block| block.language.as_str())
+            .collect();
+        languages.sort_unstable();
+        languages.dedup();
+        languages
+    }
+}
+
+/// Splits a code block info string into its language and attributes
+fn info_tokens(info: &str) -> impl Iterator<Item = &str> {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+}
+
+/// Language of a code block info string, e.g. `rust` for `rust,ignore`
+pub(crate) fn info_language(info: &str) -> &str {
+    info_tokens(info).next().unwrap_or_default()
+}
+
+/// Replacements made in one code block by a rename
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeBlockRename {
+    /// Index of the top-level node containing the block
+    pub node_index: usize,
+    /// Language of the block
+    pub language: String,
+    /// Number of occurrences replaced
+    pub replacements: usize,
+}
+
+/// Comment and string syntax of a language
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+};
+
+impl Syntax {
+    fn for_language(language: &str) -> Option<Self> {
+        let syntax = match language.to_ascii_lowercase().as_str() {
+            // Single quotes also start lifetimes in Rust, so only double
+            // quoted strings are skipped
+            "rust" | "rs" => Syntax {
+                quotes: &['"'],
+                ..C_LIKE
+            },
+            "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" => Syntax {
+                quotes: &['"', '\'', '`'],
+                ..C_LIKE
+            },
+            "go" => Syntax {
+                quotes: &['"', '`'],
+                ..C_LIKE
+            },
+            "c" | "h" | "cpp" | "c++" | "java" | "kotlin" | "swift" | "csharp" | "cs" => C_LIKE,
+            "python" | "py" | "ruby" | "rb" | "sh" | "bash" | "shell" | "zsh" | "toml" | "yaml"
+            | "yml" => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: &['"', '\''],
+            },
+            "sql" => Syntax {
+                line_comments: &["--"],
+                block_comment: Some(("/*", "*/")),
+                quotes: &['"', '\''],
+            },
+            _ => return None,
+        };
+        Some(syntax)
+    }
+
+    /// Byte ranges of the comments and string literals in `code`
+    fn skipped_ranges(&self, code: &str) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut i = 0;
+        while i < code.len() {
+            let rest = &code[i..];
+            let end = if self.line_comments.iter().any(|p| rest.starts_with(p)) {
+                rest.find('\n').map_or(code.len(), |n| i + n)
+            } else if let Some((open, close)) = self.block_comment
+                && rest.starts_with(open)
+            {
+                rest[open.len()..]
+                    .find(close)
+                    .map_or(code.len(), |n| i + open.len() + n + close.len())
+            } else if let Some(quote) = rest.chars().next().filter(|c| self.quotes.contains(c)) {
+                let mut escaped = false;
+                rest.char_indices()
+                    .skip(1)
+                    .find(|&(_, c)| {
+                        let closes = c == quote && !escaped;
+                        escaped = c == '\\' && !escaped;
+                        closes || c == '\n'
+                    })
+                    .map_or(code.len(), |(n, c)| i + n + c.len_utf8())
+            } else {
+                i += rest.chars().next().map_or(1, char::len_utf8);
+                continue;
+            };
+            ranges.push(i..end);
+            i = end;
+        }
+        ranges
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Replaces `old` with `new` in a code block's code
+///
+/// With `word_boundary`, only occurrences that are not part of a longer
+/// identifier are replaced. Comments and strings are skipped for languages
+/// known to [`Syntax`]. Returns the new code and the number of replacements.
+pub(crate) fn rename_in_code(
+    code: &str,
+    language: &str,
+    old: &str,
+    new: &str,
+    word_boundary: bool,
+) -> (String, usize) {
+    let skipped = Syntax::for_language(language)
+        .map(|syntax| syntax.skipped_ranges(code))
+        .unwrap_or_default();
+
+    let mut result = String::with_capacity(code.len());
+    let mut count = 0;
+    let mut position = 0;
+    for (start, _) in code.match_indices(old) {
+        let end = start + old.len();
+        if start < position || skipped.iter().any(|r| r.start < end && start < r.end) {
+            continue;
+        }
+        if word_boundary
+            && (code[..start]
+                .chars()
+                .next_back()
+                .is_some_and(is_identifier_char)
+                || code[end..].chars().next().is_some_and(is_identifier_char))
+        {
+            continue;
+        }
+        result.push_str(&code[position..start]);
+        result.push_str(new);
+        position = end;
+        count += 1;
+    }
+    result.push_str(&code[position..]);
+    (result, count)
+}
+
+impl Document {
+    /// Lists every code block with its language, properties and location
+    pub fn code_blocks(&self) -> CodeBlockReport {
+        CodeBlockReport::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    const GUIDE: &str = r#"# Guide
+
+```rust
+fn main() {}
+```
+
+## Errors
+
+> ```rust,should_panic
+> panic!("boom");
+> ```
+
+- Item
+
+  ```json
+  {"ok": true}
+  ```
+
+# Appendix
+
+```
+plain
+```
+"#;
+
+    #[test]
+    fn test_code_block_report() {
+        let doc = Document::try_from(Text::<Markdown>::new(GUIDE)).unwrap();
+        let report = doc.code_blocks();
+
+        assert_eq!(report.len(), 4);
+        assert_eq!(report.languages(), ["", "json", "rust"]);
+
+        let first = &report.blocks[0];
+        assert_eq!(first.path, vec![1]);
+        assert_eq!(first.headings, ["Guide"]);
+        assert_eq!(first.code.trim(), "fn main() {}");
+
+        let quoted = &report.blocks[1];
+        assert_eq!(quoted.language, "rust");
+        assert!(quoted.has_attribute("should_panic"));
+        assert_eq!(quoted.path.len(), 2);
+        assert_eq!(quoted.heading(), Some("Errors"));
+        assert_eq!(quoted.headings, ["Guide", "Errors"]);
+
+        let listed = &report.blocks[2];
+        assert_eq!(listed.path, vec![listed.node_index(), 0, 1]);
+
+        // A new top-level heading closes the earlier sections
+        assert_eq!(report.blocks[3].headings, ["Appendix"]);
+        assert_eq!(report.with_language("rust").count(), 2);
+    }
+
+    #[test]
+    fn test_rename_in_code() {
+        let code = "let count = 1; // count things\nprintln!(\"{count}\", count + counter);\n";
+        let (renamed, n) = rename_in_code(code, "rust", "count", "total", true);
+        assert_eq!(n, 2);
+        assert_eq!(
+            renamed,
+            "let total = 1; // count things\nprintln!(\"{count}\", total + counter);\n"
+        );
+
+        // Without word boundaries, identifiers containing the name change too
+        let (_, n) = rename_in_code(code, "rust", "count", "total", false);
+        assert_eq!(n, 3);
+
+        // Unknown languages are not tokenized
+        let (renamed, n) = rename_in_code("count # count", "text", "count", "n", true);
+        assert_eq!((renamed.as_str(), n), ("n # n", 2));
+        let (renamed, _) = rename_in_code("count # count", "python", "count", "n", true);
+        assert_eq!(renamed, "n # count");
+
+        let (renamed, _) = rename_in_code("a = 'a' /* a */ `a` a", "js", "a", "b", true);
+        assert_eq!(renamed, "b = 'a' /* a */ `a` b");
+    }
+
+    #[test]
+    fn test_code_block_report_serializes() {
+        let mut doc = Document::new();
+        doc.add_code_block("let x = 1;", "rust");
+        let json = serde_json::to_value(doc.code_blocks()).unwrap();
+        assert_eq!(json["blocks"][0]["language"], "rust");
+        assert_eq!(json["blocks"][0]["properties"]["start_line"], 1);
+    }
+}
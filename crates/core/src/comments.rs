@@ -0,0 +1,138 @@
+//! Review comments anchored to text ranges
+//!
+//! A [`Comment`] points at a byte range in a paragraph's or heading's text
+//! and is saved with the document. Through an [`Editor`](crate::Editor),
+//! edits before a comment's range shift it and edits inside grow or shrink
+//! it; a comment on a removed node is removed with it.
+
+use crate::mapping::range_fits;
+use crate::{Document, EditError, Position};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A comment on a range of text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    /// Identifier, unique within the document
+    pub id: u64,
+    /// Who wrote the comment
+    pub author: String,
+    /// Path to the paragraph or heading: the top-level node index followed
+    /// by the child indices through any containers
+    pub path: Vec<usize>,
+    /// Byte range in the node's text
+    pub range: Range<usize>,
+    /// The comment's text
+    pub body: String,
+    /// Whether the discussion is settled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resolved: bool,
+}
+
+impl Document {
+    /// Adds an unresolved comment, returning its id
+    ///
+    /// Fails if the path doesn't lead to a paragraph or heading or the
+    /// range doesn't fit its text.
+    pub fn add_comment(
+        &mut self,
+        author: impl Into<String>,
+        path: Vec<usize>,
+        range: Range<usize>,
+        body: impl Into<String>,
+    ) -> Result<u64, EditError> {
+        if !range_fits(self, &path, &range) {
+            return Err(EditError::invalid_range()
+                .at_path(path)
+                .with_message("Comment doesn't fit a paragraph or heading"));
+        }
+        let id = self
+            .comments
+            .iter()
+            .map(|c| c.id)
+            .max()
+            .map_or(1, |id| id + 1);
+        self.comments.push(Comment {
+            id,
+            author: author.into(),
+            path,
+            range,
+            body: body.into(),
+            resolved: false,
+        });
+        Ok(id)
+    }
+
+    /// Returns the comment with an id
+    pub fn comment(&self, id: u64) -> Option<&Comment> {
+        self.comments.iter().find(|c| c.id == id)
+    }
+
+    /// Marks a comment resolved or reopens it
+    pub fn resolve_comment(&mut self, id: u64, resolved: bool) -> Result<(), EditError> {
+        self.comment_index(id)
+            .map(|index| self.comments[index].resolved = resolved)
+    }
+
+    /// Removes a comment, returning it
+    pub fn delete_comment(&mut self, id: u64) -> Result<Comment, EditError> {
+        self.comment_index(id)
+            .map(|index| self.comments.remove(index))
+    }
+
+    /// Comments whose range covers a position, e.g. to show the discussion
+    /// under the cursor
+    pub fn comments_at(&self, position: &Position) -> Vec<&Comment> {
+        self.comments
+            .iter()
+            .filter(|comment| {
+                comment.path == position.path
+                    && comment.range.start <= position.offset
+                    && position.offset <= comment.range.end
+            })
+            .collect()
+    }
+
+    fn comment_index(&self, id: u64) -> Result<usize, EditError> {
+        self.comments
+            .iter()
+            .position(|c| c.id == id)
+            .ok_or_else(|| EditError::other(format!("No comment with id {id}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Json, Text};
+
+    #[test]
+    fn test_comments_round_trip_json() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Title");
+        doc.add_paragraph_with_text("Some text to discuss");
+
+        let first = doc
+            .add_comment("ana", vec![1], 5..9, "Which text?")
+            .unwrap();
+        let second = doc.add_comment("bo", vec![0], 0..5, "Too short").unwrap();
+        assert_eq!((first, second), (1, 2));
+        assert!(
+            doc.add_comment("bo", vec![1], 5..40, "Past the end")
+                .is_err()
+        );
+        assert!(doc.add_comment("bo", vec![2], 0..0, "No node").is_err());
+
+        doc.resolve_comment(second, true).unwrap();
+        assert!(doc.resolve_comment(7, true).is_err());
+        assert_eq!(doc.comments_at(&Position::new(vec![1], 7)).len(), 1);
+
+        let json: Text<Json> = doc.as_ref().try_into().unwrap();
+        let parsed = Document::try_from(json).unwrap();
+        assert_eq!(parsed.comments, doc.comments);
+        assert!(parsed.comment(second).unwrap().resolved);
+
+        assert_eq!(doc.delete_comment(first).unwrap().body, "Which text?");
+        assert_eq!(doc.add_comment("ana", vec![1], 0..4, "Again").unwrap(), 3);
+    }
+}
@@ -0,0 +1,563 @@
+//! ANSI-styled text for terminal previews
+//!
+//! Only compiled with the `ansi` feature. Headings, emphasis, links and code
+//! are styled with escape sequences, tables are drawn with box-drawing
+//! characters and tasks get checkbox glyphs. With the `syntax-highlighting`
+//! feature as well, code blocks in a known language are highlighted in 24-bit
+//! color; otherwise they are shown in a single color.
+
+use crate::merge::conflict_markers;
+use crate::visibility::is_published;
+use crate::{CalloutKind, Document, InlineNode, ListItem, Node, TableAlignment, TableCell};
+use unicode_segmentation::UnicodeSegmentation;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const STRIKETHROUGH: &str = "\x1b[9m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+
+/// Options for rendering a document for the terminal
+#[derive(Debug, Clone)]
+pub struct AnsiOptions {
+    /// Width of thematic breaks, in columns
+    pub width: usize,
+    /// Whether draft blocks are shown; hidden blocks never are
+    pub include_drafts: bool,
+    /// Highlighting theme for code blocks with the `syntax-highlighting`
+    /// feature, `base16-ocean.dark` when not set
+    pub theme: Option<String>,
+}
+
+impl Default for AnsiOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            include_drafts: false,
+            theme: None,
+        }
+    }
+}
+
+impl AnsiOptions {
+    /// Creates the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the width of thematic breaks
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Shows draft blocks
+    pub fn include_drafts(mut self) -> Self {
+        self.include_drafts = true;
+        self
+    }
+
+    /// Sets the highlighting theme for code blocks
+    pub fn with_theme(mut self, theme: impl Into<String>) -> Self {
+        self.theme = Some(theme.into());
+        self
+    }
+}
+
+/// Wraps `text` in a style, resetting it afterwards
+fn styled(style: &str, text: &str) -> String {
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("{style}{text}{RESET}")
+    }
+}
+
+/// Removes the escape sequences from styled text
+fn strip_escapes(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Number of columns `text` takes, not counting escape sequences
+fn visible_width(text: &str) -> usize {
+    strip_escapes(text).graphemes(true).count()
+}
+
+/// Prefixes the first line with `first` and the others with `rest`
+fn prefixed(lines: Vec<String>, first: &str, rest: &str) -> Vec<String> {
+    if lines.is_empty() {
+        return vec![first.trim_end().to_string()];
+    }
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let prefix = if index == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect()
+}
+
+fn callout_color(kind: CalloutKind) -> &'static str {
+    match kind {
+        CalloutKind::Note => BLUE,
+        CalloutKind::Tip => GREEN,
+        CalloutKind::Important => MAGENTA,
+        CalloutKind::Warning => YELLOW,
+        CalloutKind::Caution => RED,
+    }
+}
+
+/// Renders inline content, with hard breaks as newlines
+fn inlines(children: &[InlineNode]) -> String {
+    children.iter().map(inline).collect()
+}
+
+fn inline(node: &InlineNode) -> String {
+    match node {
+        InlineNode::Text(text) => {
+            let formatting = &text.formatting;
+            let mut style = String::new();
+            for (on, code) in [
+                (formatting.bold, BOLD),
+                (formatting.italic, ITALIC),
+                (formatting.strikethrough, STRIKETHROUGH),
+                (formatting.code, YELLOW),
+            ] {
+                if on {
+                    style.push_str(code);
+                }
+            }
+            if style.is_empty() {
+                text.text.clone()
+            } else {
+                styled(&style, &text.text)
+            }
+        }
+        InlineNode::Link { url, children, .. } => {
+            let text = InlineNode::plain_text_of(children);
+            let mut link = styled(&format!("{BLUE}{UNDERLINE}"), &text);
+            if text != *url {
+                link.push_str(&styled(DIM, &format!(" ({url})")));
+            }
+            link
+        }
+        InlineNode::Image { url, alt, .. } => {
+            let name = if alt.is_empty() { url } else { alt };
+            styled(DIM, &format!("[image: {name}]"))
+        }
+        InlineNode::CodeSpan { code } => styled(YELLOW, code),
+        InlineNode::AutoLink { url, .. } => styled(&format!("{BLUE}{UNDERLINE}"), url),
+        InlineNode::FootnoteRef { label } => styled(DIM, &format!("[^{label}]")),
+        InlineNode::InlineFootnote { children } => {
+            styled(DIM, &format!("^[{}]", InlineNode::plain_text_of(children)))
+        }
+        InlineNode::Mention { name, mention_type } => {
+            let mention = match mention_type.as_str() {
+                "user" => format!("@{name}"),
+                "issue" => format!("#{name}"),
+                _ => name.clone(),
+            };
+            styled(BOLD, &mention)
+        }
+        InlineNode::Tag { name } => styled(CYAN, &format!("#{name}")),
+        wiki_link @ InlineNode::WikiLink { .. } => styled(UNDERLINE, &wiki_link.plain_text()),
+        citation @ InlineNode::Citation { .. } => styled(ITALIC, &citation.plain_text()),
+        InlineNode::Math { math } => styled(ITALIC, math),
+        InlineNode::Emoji { shortcode } => format!(":{shortcode}:"),
+        InlineNode::Html { html } => styled(DIM, html),
+        InlineNode::HtmlComment { .. } => String::new(),
+        InlineNode::Custom { data, .. } => data.clone(),
+        InlineNode::HardBreak => "\n".to_string(),
+        InlineNode::SoftBreak => " ".to_string(),
+    }
+}
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_string).collect()
+}
+
+/// Renders a document as lines of ANSI-styled text
+struct AnsiRenderer<'a> {
+    options: &'a AnsiOptions,
+    /// The document rendered, for tables of contents to list its headings
+    document: &'a Document,
+}
+
+impl AnsiRenderer<'_> {
+    /// Renders blocks, separated by blank lines unless `tight`
+    fn blocks(&self, nodes: &[Node], tight: bool) -> Vec<String> {
+        let mut out = Vec::new();
+        for node in nodes {
+            let block = self.block(node);
+            if block.is_empty() {
+                continue;
+            }
+            if !out.is_empty() && !tight {
+                out.push(String::new());
+            }
+            out.extend(block);
+        }
+        out
+    }
+
+    fn block(&self, node: &Node) -> Vec<String> {
+        match node {
+            Node::Heading {
+                level, children, ..
+            } => {
+                let text = InlineNode::plain_text_of(children);
+                let rule = match level {
+                    1 => Some('═'),
+                    2 => Some('─'),
+                    _ => None,
+                };
+                let style = if *level <= 3 {
+                    format!("{BOLD}{CYAN}")
+                } else {
+                    BOLD.to_string()
+                };
+                let mut out = vec![styled(&style, &text)];
+                if let Some(rule) = rule {
+                    let width = visible_width(&text);
+                    out.push(styled(CYAN, &rule.to_string().repeat(width)));
+                }
+                out
+            }
+            Node::Paragraph { children } => lines(&inlines(children)),
+            Node::List {
+                list_type,
+                items,
+                start,
+                loose,
+            } => {
+                let mut out = Vec::new();
+                for (index, item) in items.iter().enumerate() {
+                    if *loose && index > 0 {
+                        out.push(String::new());
+                    }
+                    let number = start.unwrap_or(1) + index as u64;
+                    out.extend(self.list_item(item, list_type, number, *loose));
+                }
+                out
+            }
+            Node::CodeBlock { language, code, .. } => self.code_block(language, code),
+            Node::BlockQuote { children } => {
+                let bar = styled(DIM, "│ ");
+                prefixed(self.blocks(children, false), &bar, &bar)
+            }
+            Node::ThematicBreak => vec![styled(DIM, &"─".repeat(self.options.width))],
+            Node::Table {
+                header,
+                rows,
+                alignments,
+                properties,
+            } => {
+                let mut out = table(header, rows, alignments);
+                if let Some(caption) = &properties.caption {
+                    let caption = styled(ITALIC, caption);
+                    if properties.caption_at_bottom {
+                        out.push(caption);
+                    } else {
+                        out.insert(0, caption);
+                    }
+                }
+                out
+            }
+            Node::Group { children, .. } => self.blocks(children, false),
+            Node::FootnoteReference(reference) => {
+                vec![styled(DIM, &format!("[^{}]", reference.label))]
+            }
+            Node::FootnoteDefinition(definition) => {
+                let label = styled(DIM, &format!("[^{}]: ", definition.label));
+                prefixed(self.blocks(&definition.content, false), &label, "    ")
+            }
+            Node::DefinitionList { items } => {
+                let mut out = Vec::new();
+                for item in items {
+                    out.push(styled(BOLD, &InlineNode::plain_text_of(&item.term)));
+                    for description in &item.descriptions {
+                        out.extend(prefixed(self.blocks(description, false), "  ", "  "));
+                    }
+                }
+                out
+            }
+            Node::MathBlock { math } => math
+                .lines()
+                .map(|line| format!("  {}", styled(ITALIC, line)))
+                .collect(),
+            Node::Embed { url, provider, .. } => vec![format!(
+                "{}{}",
+                styled(DIM, &format!("▶ {provider}: ")),
+                styled(&format!("{BLUE}{UNDERLINE}"), url)
+            )],
+            Node::Figure {
+                url, alt, caption, ..
+            } => {
+                let name = if alt.is_empty() { url } else { alt };
+                let mut out = vec![styled(DIM, &format!("[image: {name}]"))];
+                if !caption.is_empty() {
+                    out.push(styled(ITALIC, &InlineNode::plain_text_of(caption)));
+                }
+                out
+            }
+            Node::HtmlBlock { html } => html.lines().map(|line| styled(DIM, line)).collect(),
+            Node::HtmlComment { .. } => Vec::new(),
+            Node::Callout {
+                kind,
+                title,
+                children,
+            } => {
+                let color = callout_color(*kind);
+                let bar = styled(color, "▌ ");
+                let title = title.as_deref().unwrap_or(kind.default_title());
+                let mut out = vec![format!("{bar}{}", styled(&format!("{BOLD}{color}"), title))];
+                out.extend(prefixed(self.blocks(children, false), &bar, &bar));
+                out
+            }
+            Node::VisibilityBlock {
+                visibility,
+                children,
+            } => {
+                if is_published(*visibility, self.options.include_drafts) {
+                    self.blocks(children, false)
+                } else {
+                    Vec::new()
+                }
+            }
+            Node::Custom { data, .. } => lines(data),
+            Node::Conflict { ours, theirs } => {
+                let ours = self.blocks(ours, false).join("\n");
+                let theirs = self.blocks(theirs, false).join("\n");
+                lines(&conflict_markers(&ours, &theirs))
+            }
+            Node::TableOfContents { max_level } => self
+                .document
+                .table_of_contents(*max_level)
+                .map_or_else(Vec::new, |list| self.block(&list)),
+            Node::TempListItem(_) | Node::TempTableCell(_) => Vec::new(),
+        }
+    }
+
+    fn list_item(
+        &self,
+        item: &ListItem,
+        list_type: &crate::ListType,
+        number: u64,
+        loose: bool,
+    ) -> Vec<String> {
+        let marker = match (item.checked, list_type) {
+            (Some(true), _) => styled(GREEN, "☑ "),
+            (Some(false), _) => "☐ ".to_string(),
+            (None, crate::ListType::Ordered) => format!("{number}. "),
+            (None, _) => "• ".to_string(),
+        };
+        let indent = " ".repeat(visible_width(&marker));
+        prefixed(self.blocks(&item.children, !loose), &marker, &indent)
+    }
+
+    fn code_block(&self, language: &str, code: &str) -> Vec<String> {
+        let code = code.strip_suffix('\n').unwrap_or(code);
+        let mut out = Vec::new();
+        if !language.is_empty() {
+            out.push(styled(DIM, language));
+        }
+        let body = self
+            .highlight(code, language)
+            .map(|highlighted| lines(&highlighted))
+            .unwrap_or_else(|| code.lines().map(|line| styled(YELLOW, line)).collect());
+        let bar = styled(DIM, "│ ");
+        out.extend(body.into_iter().map(|line| format!("{bar}{line}")));
+        out
+    }
+
+    #[cfg(feature = "syntax-highlighting")]
+    fn highlight(&self, code: &str, language: &str) -> Option<String> {
+        let theme = self.options.theme.as_deref().unwrap_or("base16-ocean.dark");
+        let highlighted = super::highlight::highlight_code_ansi(code, language, Some(theme))?;
+        Some(
+            highlighted
+                .lines()
+                .map(|line| format!("{line}{RESET}\n"))
+                .collect(),
+        )
+    }
+
+    #[cfg(not(feature = "syntax-highlighting"))]
+    fn highlight(&self, _code: &str, _language: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Pads `text` to `width` columns according to the column alignment
+fn pad(text: &str, width: usize, alignment: TableAlignment) -> String {
+    let space = width.saturating_sub(visible_width(text));
+    let (left, right) = match alignment {
+        TableAlignment::Right => (space, 0),
+        TableAlignment::Center => (space / 2, space - space / 2),
+        _ => (0, space),
+    };
+    format!("{}{text}{}", " ".repeat(left), " ".repeat(right))
+}
+
+/// Draws a table with box-drawing characters
+fn table(
+    header: &[TableCell],
+    rows: &[Vec<TableCell>],
+    alignments: &[TableAlignment],
+) -> Vec<String> {
+    let render_row = |cells: &[TableCell]| -> Vec<String> {
+        cells
+            .iter()
+            .map(|cell| inlines(&cell.content).replace('\n', " "))
+            .collect()
+    };
+    let header = render_row(header);
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| render_row(row)).collect();
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .chain([header.len()])
+        .max()
+        .unwrap_or(0);
+    if columns == 0 {
+        return Vec::new();
+    }
+    let mut widths = vec![1; columns];
+    for row in rows.iter().chain([&header]) {
+        for (column, cell) in row.iter().enumerate() {
+            widths[column] = widths[column].max(visible_width(cell));
+        }
+    }
+
+    let border = |left: &str, middle: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        styled(DIM, &format!("{left}{}{right}", segments.join(middle)))
+    };
+    let bar = styled(DIM, "│");
+    let line = |cells: &[String], style: Option<&str>| {
+        let padded: Vec<String> = (0..columns)
+            .map(|column| {
+                let text = cells.get(column).map_or("", String::as_str);
+                let alignment = alignments.get(column).cloned().unwrap_or_default();
+                let text = match style {
+                    Some(style) => styled(style, text),
+                    None => text.to_string(),
+                };
+                pad(&text, widths[column], alignment)
+            })
+            .collect();
+        format!("{bar} {} {bar}", padded.join(&format!(" {bar} ")))
+    };
+
+    let mut out = vec![border("┌", "┬", "┐")];
+    if !header.is_empty() {
+        out.push(line(&header, Some(BOLD)));
+        out.push(border("├", "┼", "┤"));
+    }
+    out.extend(rows.iter().map(|row| line(row, None)));
+    out.push(border("└", "┴", "┘"));
+    out
+}
+
+impl Document {
+    /// Renders the document as ANSI-styled text for terminals, with the
+    /// default options
+    pub fn to_ansi(&self) -> String {
+        self.to_ansi_with(&AnsiOptions::default())
+    }
+
+    /// Renders the document as ANSI-styled text for terminals
+    pub fn to_ansi_with(&self, options: &AnsiOptions) -> String {
+        let renderer = AnsiRenderer {
+            options,
+            document: self,
+        };
+        let mut text = renderer.blocks(&self.nodes, false).join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn render(markdown: &str) -> String {
+        Document::try_from(Text::<Markdown>::new(markdown))
+            .unwrap()
+            .to_ansi()
+    }
+
+    #[test]
+    fn test_ansi_inline_styles() {
+        let text = render(
+            "# Title\n\nSome **bold**, *italic* and `code` with [a link](https://example.com).\n",
+        );
+        assert!(text.starts_with("\x1b[1m\x1b[36mTitle\x1b[0m\n\x1b[36m═════\x1b[0m\n\n"));
+        assert!(text.contains("\x1b[1mbold\x1b[0m"));
+        assert!(text.contains("\x1b[3mitalic\x1b[0m"));
+        assert!(text.contains("\x1b[33mcode\x1b[0m"));
+        assert!(text.contains("\x1b[34m\x1b[4ma link\x1b[0m\x1b[2m (https://example.com)\x1b[0m"));
+    }
+
+    #[test]
+    fn test_ansi_lists_and_tasks() {
+        let text = render("3. one\n4. two\n   - nested\n\n- [x] done\n- [ ] open\n");
+        assert_eq!(
+            strip_escapes(&text),
+            "3. one\n4. two\n   • nested\n\n☑ done\n☐ open\n"
+        );
+    }
+
+    #[test]
+    fn test_ansi_table() {
+        let text = render("| Name | Qty |\n|:-----|----:|\n| Apple | 3 |\n| Fig | 12 |\n");
+        assert_eq!(
+            strip_escapes(&text),
+            "┌───────┬─────┐\n\
+             │ Name  │ Qty │\n\
+             ├───────┼─────┤\n\
+             │ Apple │   3 │\n\
+             │ Fig   │  12 │\n\
+             └───────┴─────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_ansi_blocks() {
+        let text =
+            render("> Quoted\n\n```rust\nfn main() {}\n```\n\n---\n\n> [!WARNING]\n> Careful\n");
+        let plain = strip_escapes(&text);
+        let expected = format!(
+            "│ Quoted\n\nrust\n│ fn main() {{}}\n\n{}\n\n▌ Warning\n▌ Careful\n",
+            "─".repeat(80)
+        );
+        assert_eq!(plain, expected);
+        let narrow = Document::try_from(Text::<Markdown>::new("---\n"))
+            .unwrap()
+            .to_ansi_with(&AnsiOptions::new().with_width(3));
+        assert_eq!(strip_escapes(&narrow), "───\n");
+    }
+}
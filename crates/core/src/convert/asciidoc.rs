@@ -0,0 +1,1863 @@
+//! AsciiDoc import and export
+//!
+//! Only compiled with the `asciidoc` feature. Rendering covers every block:
+//! headings become `=` section titles, callouts become admonitions, tables
+//! keep their column and row spans and alignments, and a code block with
+//! `<1>` markers followed by an ordered list of as many items is written as
+//! a callout list. Footnotes are written inline with `footnote:` where they
+//! are first referenced. Table styling, typed cell values, code block themes
+//! and classes, and embeds are lost.
+//!
+//! The parser reads the common subset Asciidoctor documents are written in:
+//! section titles, paragraphs, admonitions, lists (including checklists,
+//! callout lists and description lists with `+` continuations), delimited
+//! listing, literal, quote, example, sidebar, passthrough and comment
+//! blocks, tables with spans, `image::` figures, attribute entries and the
+//! usual inline formatting, macros and cross references. Includes,
+//! conditionals and custom substitutions are not processed.
+
+use super::AsciiDoc;
+use super::format::{Capabilities, FormatConverter};
+use super::importer::{find, is_blank, merge_text};
+use crate::links::has_scheme;
+use crate::merge::conflict_markers;
+use crate::visibility::is_published;
+use crate::{
+    CalloutKind, CodeBlockProperties, DefinitionItem, Document, Feature, FootnoteDefinition,
+    InlineNode, ListItem, ListType, Node, ParseError, TableAlignment, TableCell, TableProperties,
+    TextFormatting, TextNode,
+};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+static CALLOUT_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<(\d+)>\s*$").unwrap());
+static SECTION_TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(={1,6}) +(\S.*?)(?: +=+)?$").unwrap());
+static ATTRIBUTE_ENTRY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^:(!?)([\w][\w-]*)(!?):(?: +(.*))?$").unwrap());
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^ *(\*{1,5}|-|\.{1,5}|\d+\.|[a-zA-Z]\.|<\d+>|<\.>) +(.*)$").unwrap()
+});
+static DEFINITION_TERM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\S.*?)(:{2,4}|;;)(?: +(.*))?$").unwrap());
+static ADMONITION_PARAGRAPH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(NOTE|TIP|IMPORTANT|WARNING|CAUTION): +(.*)$").unwrap());
+static BLOCK_MACRO: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([a-z][\w-]*)::(\S*)\[(.*)\]$").unwrap());
+static INLINE_MACRO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(link|mailto|image|footnote|stem|latexmath|asciimath|kbd|pass|xref):([^\s\[]*)\[((?:\\\]|[^\]])*)\]",
+    )
+    .unwrap()
+});
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:https?|ftp|irc)://[^\s\[\]<>]+").unwrap());
+static CELL_SPEC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:(\d+)\*)?(?:(\d+)?(?:\.(\d+))?\+)?([<^>])?(?:\.[<^>])?[adehlmsv]?$").unwrap()
+});
+
+impl FormatConverter for AsciiDoc {
+    fn name(&self) -> &str {
+        "asciidoc"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["adoc", "asciidoc", "asc"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            lost_features: vec![
+                Feature::TableStyling,
+                Feature::CellValues,
+                Feature::CodeBlockOptions,
+                Feature::Embeds,
+            ],
+            ..Capabilities::lossless()
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .collect();
+        let mut parser = AsciiDocParser::default();
+        let mut document = Document::new();
+        document.nodes = parser.blocks(&lines);
+        document
+            .nodes
+            .extend(parser.footnotes.into_iter().map(Node::FootnoteDefinition));
+        Ok(document)
+    }
+
+    fn render(&self, document: &Document) -> Result<String, ParseError> {
+        let mut writer = AsciiDocWriter::default();
+        for node in &document.nodes {
+            node.walk(&mut |node| {
+                if let Node::FootnoteDefinition(definition) = node {
+                    writer
+                        .footnotes
+                        .insert(definition.label.clone(), definition.content.clone());
+                }
+            });
+        }
+        let mut text = writer.blocks(&document.nodes).join("\n\n");
+        text.push('\n');
+        Ok(text)
+    }
+}
+
+/// A delimiter line of a block of `c`, long enough not to be closed by a
+/// line of `content`
+fn delimiter(c: char, content: &str) -> String {
+    let longest = content
+        .lines()
+        .filter(|line| line.len() >= 4 && line.chars().all(|d| d == c))
+        .map(str::len)
+        .max()
+        .unwrap_or(3);
+    c.to_string().repeat(longest + 1)
+}
+
+/// Wraps `content` in a delimited block of `c`
+fn delimited(c: char, content: &str) -> String {
+    let delimiter = delimiter(c, content);
+    format!("{delimiter}\n{content}\n{delimiter}")
+}
+
+/// Escapes the text of a macro, which ends at the first `]`
+fn escape_brackets(text: &str) -> String {
+    text.replace(']', "\\]")
+}
+
+/// Whether a block is a delimiter line that opens or closes a block
+fn is_delimiter(line: &str) -> bool {
+    line == "--"
+        || line.starts_with("|===")
+        || (line.len() >= 4
+            && line
+                .chars()
+                .next()
+                .is_some_and(|c| "-._=*+/".contains(c) && line.chars().all(|d| d == c)))
+}
+
+/// Compact highlighted line ranges, e.g. `1;3..5`
+fn line_ranges(lines: &[u32]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let first = lines[index];
+        let mut last = first;
+        while index + 1 < lines.len() && lines[index + 1] == last + 1 {
+            index += 1;
+            last = lines[index];
+        }
+        ranges.push(if first == last {
+            first.to_string()
+        } else {
+            format!("{first}..{last}")
+        });
+        index += 1;
+    }
+    ranges.join(";")
+}
+
+/// Expands `1;3..5` or `1,3-5` into line numbers
+fn parse_line_ranges(spec: &str) -> Vec<u32> {
+    let mut lines = Vec::new();
+    for part in spec.split([';', ',']).map(str::trim) {
+        let range = part.split_once("..").or_else(|| part.split_once('-'));
+        match range {
+            Some((first, last)) => {
+                if let (Ok(first), Ok(last)) = (first.parse::<u32>(), last.parse()) {
+                    lines.extend(first..=last);
+                }
+            }
+            None => lines.extend(part.parse::<u32>().ok()),
+        }
+    }
+    lines
+}
+
+/// The number of `<n>` markers a code block has, if they run from 1
+fn callout_markers(code: &str) -> Option<usize> {
+    let mut numbers: Vec<usize> = code
+        .lines()
+        .filter_map(|line| CALLOUT_MARKER.captures(line)?[1].parse().ok())
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    let count = numbers.len();
+    (count > 0 && numbers.last() == Some(&count)).then_some(count)
+}
+
+/// Renders documents as AsciiDoc
+#[derive(Default)]
+struct AsciiDocWriter {
+    /// Footnote definitions by label, written where first referenced
+    footnotes: HashMap<String, Vec<Node>>,
+    /// Labels of the footnotes written so far
+    written: HashSet<String>,
+}
+
+impl AsciiDocWriter {
+    fn blocks(&mut self, nodes: &[Node]) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut index = 0;
+        while index < nodes.len() {
+            let node = &nodes[index];
+            index += 1;
+            if let (
+                Node::CodeBlock { code, .. },
+                Some(Node::List {
+                    list_type: ListType::Ordered,
+                    items,
+                    ..
+                }),
+            ) = (node, nodes.get(index))
+                && callout_markers(code) == Some(items.len())
+            {
+                out.extend(self.block(node));
+                out.push(self.callout_list(items));
+                index += 1;
+                continue;
+            }
+            out.extend(self.block(node));
+        }
+        out
+    }
+
+    fn block(&mut self, node: &Node) -> Option<String> {
+        let block = match node {
+            Node::Heading {
+                level, children, ..
+            } => format!(
+                "{} {}",
+                "=".repeat((*level).clamp(1, 6) as usize),
+                self.inlines(children)
+            ),
+            Node::Paragraph { children } => self.inlines(children),
+            Node::List {
+                list_type,
+                items,
+                start,
+                ..
+            } => self.list(list_type, items, *start, 1),
+            Node::CodeBlock {
+                language,
+                code,
+                properties,
+            } => code_block(language, code, properties),
+            Node::BlockQuote { children } => {
+                let content = self.blocks(children).join("\n\n");
+                delimited('_', &content)
+            }
+            Node::ThematicBreak => "'''".to_string(),
+            Node::Table {
+                header,
+                rows,
+                alignments,
+                properties,
+            } => self.table(header, rows, alignments, properties),
+            Node::Group { name, children } => {
+                let content = self.blocks(children).join("\n\n");
+                match name.as_str() {
+                    "example" => delimited('=', &content),
+                    "sidebar" => delimited('*', &content),
+                    _ => content,
+                }
+            }
+            Node::FootnoteReference(reference) => {
+                self.inline(&InlineNode::footnote_ref(&reference.label), None, None)
+            }
+            Node::FootnoteDefinition(_) => return None,
+            Node::DefinitionList { items } => {
+                let mut out = Vec::new();
+                for item in items {
+                    let mut entry = format!("{}::", self.inlines(&item.term));
+                    for (index, description) in item.descriptions.iter().enumerate() {
+                        if index > 0 {
+                            entry.push_str("\n+");
+                        }
+                        entry.push_str(&self.attached(description));
+                    }
+                    out.push(entry);
+                }
+                out.join("\n")
+            }
+            Node::MathBlock { math } => format!("[stem]\n{}", delimited('+', math)),
+            Node::Embed { url, provider, .. } => format!("{url}[{}]", escape_brackets(provider)),
+            Node::Figure {
+                url,
+                alt,
+                caption,
+                properties,
+                ..
+            } => {
+                let mut attributes = vec![macro_attribute(alt)];
+                if let Some(width) = properties.width {
+                    attributes.push(width.to_string());
+                }
+                if let Some(height) = properties.height {
+                    if properties.width.is_none() {
+                        attributes.push(String::new());
+                    }
+                    attributes.push(height.to_string());
+                }
+                let image = format!("image::{url}[{}]", attributes.join(","));
+                if caption.is_empty() {
+                    image
+                } else {
+                    format!(".{}\n{image}", self.inlines(caption))
+                }
+            }
+            Node::HtmlBlock { html } => delimited('+', html),
+            Node::HtmlComment { text } => delimited('/', text.trim()),
+            Node::Callout {
+                kind,
+                title,
+                children,
+            } => {
+                let name = kind.as_str().to_ascii_uppercase();
+                match (title, children.as_slice()) {
+                    (None, [Node::Paragraph { children }]) => {
+                        format!("{name}: {}", self.inlines(children))
+                    }
+                    _ => {
+                        let title = title.as_ref().map(|title| format!(".{title}\n"));
+                        let content = self.blocks(children).join("\n\n");
+                        format!(
+                            "{}[{name}]\n{}",
+                            title.unwrap_or_default(),
+                            delimited('=', &content)
+                        )
+                    }
+                }
+            }
+            Node::VisibilityBlock {
+                visibility,
+                children,
+            } => {
+                if !is_published(*visibility, false) {
+                    return None;
+                }
+                self.blocks(children).join("\n\n")
+            }
+            Node::Custom { kind, data } => format!("[{kind}]\n{}", delimited('.', data)),
+            Node::Conflict { ours, theirs } => {
+                let ours = self.blocks(ours).join("\n\n");
+                let theirs = self.blocks(theirs).join("\n\n");
+                conflict_markers(&ours, &theirs)
+            }
+            Node::TableOfContents { .. } => "toc::[]".to_string(),
+            Node::TempListItem(_) | Node::TempTableCell(_) => return None,
+        };
+        (!block.is_empty()).then_some(block)
+    }
+
+    /// Blocks attached to a list item or description after its first line
+    fn attached(&mut self, children: &[Node]) -> String {
+        let mut out = String::new();
+        for (index, child) in children.iter().enumerate() {
+            match child {
+                Node::Paragraph { children } if index == 0 => {
+                    out.push('\n');
+                    out.push_str(&self.inlines(children));
+                }
+                _ => {
+                    if let Some(block) = self.block(child) {
+                        out.push_str(if index == 0 { "\n" } else { "\n+\n" });
+                        out.push_str(&block);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn list(
+        &mut self,
+        list_type: &ListType,
+        items: &[ListItem],
+        start: Option<u64>,
+        depth: usize,
+    ) -> String {
+        let marker = match list_type {
+            ListType::Ordered => ".".repeat(depth.min(5)),
+            _ => "*".repeat(depth.min(5)),
+        };
+        let mut out = Vec::new();
+        if let Some(start) = start.filter(|&start| start != 1) {
+            out.push(format!("[start={start}]"));
+        }
+        for item in items {
+            let mut entry = marker.clone();
+            match item.checked {
+                Some(true) => entry.push_str(" [x]"),
+                Some(false) => entry.push_str(" [ ]"),
+                None => {}
+            }
+            let mut children = item.children.as_slice();
+            match children.first() {
+                Some(Node::Paragraph { children: text }) => {
+                    entry.push(' ');
+                    entry.push_str(&self.inlines(text));
+                    children = &children[1..];
+                }
+                _ => entry.push_str(" {empty}"),
+            }
+            for child in children {
+                match child {
+                    Node::List {
+                        list_type,
+                        items,
+                        start,
+                        ..
+                    } => {
+                        entry.push('\n');
+                        entry.push_str(&self.list(list_type, items, *start, depth + 1));
+                    }
+                    _ => {
+                        if let Some(block) = self.block(child) {
+                            entry.push_str("\n+\n");
+                            entry.push_str(&block);
+                        }
+                    }
+                }
+            }
+            out.push(entry);
+        }
+        out.join("\n")
+    }
+
+    fn callout_list(&mut self, items: &[ListItem]) -> String {
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let text: Vec<String> = item
+                    .children
+                    .iter()
+                    .filter_map(|child| match child {
+                        Node::Paragraph { children } => Some(self.inlines(children)),
+                        _ => None,
+                    })
+                    .collect();
+                format!("<{}> {}", index + 1, text.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn table(
+        &mut self,
+        header: &[TableCell],
+        rows: &[Vec<TableCell>],
+        alignments: &[TableAlignment],
+        properties: &TableProperties,
+    ) -> String {
+        let width = |row: &[TableCell]| row.iter().map(|cell| cell.colspan as usize).sum();
+        let columns = rows
+            .iter()
+            .map(|row| width(row))
+            .chain([width(header), alignments.len()])
+            .max()
+            .unwrap_or(0);
+        let cols: Vec<&str> = (0..columns)
+            .map(|column| match alignments.get(column) {
+                Some(TableAlignment::Left) => "<",
+                Some(TableAlignment::Center) => "^",
+                Some(TableAlignment::Right) => ">",
+                _ => "1",
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        if let Some(caption) = &properties.caption {
+            out.push(format!(".{caption}"));
+        }
+        let options = if header.is_empty() {
+            ""
+        } else {
+            ",options=\"header\""
+        };
+        out.push(format!("[cols=\"{}\"{options}]", cols.join(",")));
+        out.push("|===".to_string());
+        if !header.is_empty() {
+            out.push(self.table_row(header));
+            out.push(String::new());
+        }
+        for row in rows {
+            out.push(self.table_row(row));
+        }
+        out.push("|===".to_string());
+        out.join("\n")
+    }
+
+    fn table_row(&mut self, cells: &[TableCell]) -> String {
+        cells
+            .iter()
+            .map(|cell| {
+                let spec = match (cell.colspan, cell.rowspan) {
+                    (1, 1) => String::new(),
+                    (colspan, 1) => format!("{colspan}+"),
+                    (1, rowspan) => format!(".{rowspan}+"),
+                    (colspan, rowspan) => format!("{colspan}.{rowspan}+"),
+                };
+                let content = self
+                    .inlines(&cell.content)
+                    .replace(" +\n", " ")
+                    .replace('\n', " ")
+                    .replace('|', "\\|");
+                if content.is_empty() {
+                    format!("{spec}|")
+                } else {
+                    format!("{spec}|{content}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn inlines(&mut self, children: &[InlineNode]) -> String {
+        let mut out = String::new();
+        for (index, child) in children.iter().enumerate() {
+            let next = children
+                .get(index + 1)
+                .and_then(|next| next.plain_text().chars().next());
+            let previous = out.chars().last();
+            let text = self.inline(child, previous, next);
+            out.push_str(&text);
+        }
+        out
+    }
+
+    /// Renders an inline node between the characters around it, which
+    /// decide whether formatting marks must be doubled
+    fn inline(&mut self, node: &InlineNode, previous: Option<char>, next: Option<char>) -> String {
+        match node {
+            InlineNode::Text(text) => formatted(text, previous, next),
+            InlineNode::Link { url, children, .. } => {
+                let text = escape_brackets(&self.inlines(children));
+                match url.strip_prefix('#') {
+                    Some(id) if text.is_empty() => format!("<<{id}>>"),
+                    Some(id) => format!("<<{id},{text}>>"),
+                    None if has_scheme(url) => format!("{url}[{text}]"),
+                    None => format!("link:{url}[{text}]"),
+                }
+            }
+            InlineNode::Image { url, alt, title } => {
+                let mut attributes = macro_attribute(alt);
+                if let Some(title) = title {
+                    attributes.push_str(&format!(",title=\"{}\"", title.replace('"', "\\\"")));
+                }
+                format!("image:{url}[{attributes}]")
+            }
+            InlineNode::CodeSpan { code } => {
+                let tick = if adjacent(previous, next) { "``" } else { "`" };
+                let plain = code
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || " .-()/".contains(c));
+                if plain && !code.starts_with(' ') && !code.ends_with(' ') {
+                    format!("{tick}{code}{tick}")
+                } else {
+                    format!("{tick}+{code}+{tick}")
+                }
+            }
+            InlineNode::AutoLink { url, .. } => url.clone(),
+            InlineNode::FootnoteRef { label } => {
+                let id = footnote_id(label);
+                if !self.written.insert(label.clone()) {
+                    return format!("footnote:{id}[]");
+                }
+                let content = self.footnotes.get(label).cloned().unwrap_or_default();
+                let text: Vec<String> = content
+                    .iter()
+                    .map(|block| match block {
+                        Node::Paragraph { children } => self.inlines(children),
+                        block => block.plain_text(),
+                    })
+                    .collect();
+                let text = escape_brackets(&text.join(" ").replace('\n', " "));
+                format!("footnote:{id}[{text}]")
+            }
+            InlineNode::InlineFootnote { children } => {
+                format!("footnote:[{}]", escape_brackets(&self.inlines(children)))
+            }
+            InlineNode::Mention { name, mention_type } => match mention_type.as_str() {
+                "user" => format!("@{name}"),
+                "issue" => format!("#{name}"),
+                _ => name.clone(),
+            },
+            InlineNode::Tag { name } => format!("#{name}"),
+            InlineNode::WikiLink { target, alias } => {
+                let text = alias.as_deref().unwrap_or(target);
+                format!("<<{target},{text}>>")
+            }
+            InlineNode::Citation { key, locator } => match locator {
+                Some(locator) => format!("cite:[{key}({})]", escape_brackets(locator)),
+                None => format!("cite:[{key}]"),
+            },
+            InlineNode::Math { math } => format!("stem:[{}]", escape_brackets(math)),
+            InlineNode::Emoji { shortcode } => format!(":{shortcode}:"),
+            InlineNode::Html { html } => format!("+++{html}+++"),
+            InlineNode::HtmlComment { .. } => String::new(),
+            InlineNode::Custom { data, .. } => data.clone(),
+            InlineNode::HardBreak => " +\n".to_string(),
+            InlineNode::SoftBreak => "\n".to_string(),
+        }
+    }
+}
+
+/// Whether a character touching a formatting mark makes it unconstrained
+fn adjacent(previous: Option<char>, next: Option<char>) -> bool {
+    previous.is_some_and(char::is_alphanumeric) || next.is_some_and(char::is_alphanumeric)
+}
+
+/// The first positional attribute of a macro, quoted when it has a comma
+fn macro_attribute(text: &str) -> String {
+    if text.contains(',') || text.contains('"') {
+        format!("\"{}\"", text.replace('"', "\\\""))
+    } else {
+        escape_brackets(text)
+    }
+}
+
+/// A footnote label usable as an AsciiDoc footnote ID
+fn footnote_id(label: &str) -> String {
+    if label
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        label.to_string()
+    } else {
+        crate::outline::slugify(label)
+    }
+}
+
+/// Formatted text with its marks outside of the surrounding whitespace
+fn formatted(text: &TextNode, previous: Option<char>, next: Option<char>) -> String {
+    let formatting = &text.formatting;
+    let core = text.text.trim();
+    if core.is_empty()
+        || !(formatting.bold || formatting.italic || formatting.code || formatting.strikethrough)
+    {
+        return text.text.clone();
+    }
+    let leading = &text.text[..text.text.len() - text.text.trim_start().len()];
+    let trailing = &text.text[text.text.trim_end().len()..];
+    let unconstrained = adjacent(
+        previous.filter(|_| leading.is_empty()),
+        next.filter(|_| trailing.is_empty()),
+    );
+    let mark = |mark: &str| {
+        if unconstrained {
+            mark.repeat(2)
+        } else {
+            mark.to_string()
+        }
+    };
+
+    let mut out = core.to_string();
+    if formatting.code {
+        out = format!("{0}+{out}+{0}", mark("`"));
+    }
+    if formatting.strikethrough {
+        out = format!("[.line-through]{0}{out}{0}", mark("#"));
+    }
+    if formatting.italic {
+        out = format!("{0}{out}{0}", mark("_"));
+    }
+    if formatting.bold {
+        out = format!("{0}{out}{0}", mark("*"));
+    }
+    format!("{leading}{out}{trailing}")
+}
+
+fn code_block(language: &str, code: &str, properties: &CodeBlockProperties) -> String {
+    let code = code.strip_suffix('\n').unwrap_or(code);
+    let mut attributes = Vec::new();
+    if !language.is_empty() {
+        attributes.push("source".to_string());
+        attributes.push(language.to_string());
+    }
+    if properties.show_line_numbers {
+        attributes.push("linenums".to_string());
+    }
+    if properties.start_line != 1 {
+        attributes.push(format!("start={}", properties.start_line));
+    }
+    if let Some(lines) = properties
+        .highlight_lines
+        .as_deref()
+        .filter(|l| !l.is_empty())
+    {
+        attributes.push(format!("highlight={}", line_ranges(lines)));
+    }
+    if !attributes.is_empty() && language.is_empty() {
+        attributes.insert(0, "source".to_string());
+    }
+    let block = delimited('-', code);
+    if attributes.is_empty() {
+        block
+    } else {
+        format!("[{}]\n{block}", attributes.join(","))
+    }
+}
+
+/// The attribute list and title of the next block
+#[derive(Debug, Default)]
+struct BlockAttributes {
+    /// First positional attribute, such as `source` or `NOTE`
+    style: Option<String>,
+    /// The other positional attributes
+    positional: Vec<String>,
+    named: HashMap<String, String>,
+    options: Vec<String>,
+    title: Option<String>,
+}
+
+impl BlockAttributes {
+    /// Adds the attributes of an attribute list line such as
+    /// `[source,rust,linenums]` or `[%header,cols="1,2"]`
+    fn parse(&mut self, list: &str) {
+        let mut values = Vec::new();
+        let mut current = String::new();
+        let mut quoted = false;
+        for c in list.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ',' if !quoted => values.push(std::mem::take(&mut current)),
+                c => current.push(c),
+            }
+        }
+        values.push(current);
+
+        for (index, value) in values.into_iter().enumerate() {
+            let value = value.trim();
+            if let Some((name, value)) = value.split_once('=') {
+                let name = name.trim().to_string();
+                let value = value.trim().to_string();
+                if name == "options" || name == "opts" {
+                    self.options
+                        .extend(value.split(',').map(|o| o.trim().to_string()));
+                }
+                self.named.insert(name, value);
+                continue;
+            }
+            let mut parts = value.split('%');
+            let style = parts.next().unwrap_or_default();
+            self.options.extend(parts.map(str::to_string));
+            // Drop the `#id` and `.role` shorthands
+            let style = style.split(['#', '.']).next().unwrap_or_default();
+            if index == 0 {
+                if !style.is_empty() {
+                    self.style = Some(style.to_string());
+                }
+            } else {
+                self.positional.push(value.to_string());
+            }
+        }
+    }
+
+    fn admonition(&self) -> Option<CalloutKind> {
+        let style = self.style.as_deref()?;
+        matches!(style, "NOTE" | "TIP" | "IMPORTANT" | "WARNING" | "CAUTION")
+            .then(|| CalloutKind::from_name(style))
+            .flatten()
+    }
+}
+
+/// A list item: its marker, content lines and checkbox
+struct Entry {
+    marker: String,
+    lines: Vec<String>,
+    checked: Option<bool>,
+    number: Option<u64>,
+}
+
+/// The marker of a list item line, normalized so that items of one list
+/// share it, with the item's text
+fn list_item(line: &str) -> Option<(String, Option<u64>, &str)> {
+    let captures = LIST_ITEM.captures(line)?;
+    let marker = captures.get(1)?.as_str();
+    let text = captures.get(2)?.as_str();
+    let (key, number) = match marker.chars().next()? {
+        '*' | '.' | '-' => (marker.to_string(), None),
+        '<' => ("<>".to_string(), None),
+        c if c.is_ascii_digit() => ("1.".to_string(), marker.trim_end_matches('.').parse().ok()),
+        c if c.is_ascii_lowercase() => ("a.".to_string(), None),
+        _ => ("A.".to_string(), None),
+    };
+    Some((key, number, text))
+}
+
+#[derive(Default)]
+struct AsciiDocParser {
+    /// Document attributes set by attribute entries, for `{name}` references
+    attributes: HashMap<String, String>,
+    /// Definitions of the named footnotes, appended to the document
+    footnotes: Vec<FootnoteDefinition>,
+}
+
+impl AsciiDocParser {
+    fn blocks(&mut self, lines: &[String]) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut attributes = BlockAttributes::default();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i].as_str();
+            if is_blank(line) {
+                i += 1;
+                continue;
+            }
+            if line.starts_with("//") && !is_delimiter(line) {
+                i += 1;
+                continue;
+            }
+            if line.starts_with("[[") && line.ends_with("]]") {
+                i += 1;
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                attributes.parse(&line[1..line.len() - 1]);
+                i += 1;
+                continue;
+            }
+            if let Some(title) = line.strip_prefix('.')
+                && !title.starts_with(['.', ' '])
+                && !title.is_empty()
+            {
+                attributes.title = Some(title.to_string());
+                i += 1;
+                continue;
+            }
+            if let Some(captures) = ATTRIBUTE_ENTRY.captures(line) {
+                let name = captures[2].to_string();
+                if captures[1].is_empty() && captures[3].is_empty() {
+                    let value = captures.get(4).map_or("", |value| value.as_str());
+                    self.attributes.insert(name, value.to_string());
+                } else {
+                    self.attributes.remove(&name);
+                }
+                i += 1;
+                continue;
+            }
+            let block = std::mem::take(&mut attributes);
+            if let Some(captures) = SECTION_TITLE.captures(line) {
+                nodes.push(Node::Heading {
+                    level: captures[1].len() as u8,
+                    children: self.inlines(&captures[2], TextFormatting::default()),
+                    properties: Default::default(),
+                });
+                i += 1;
+                continue;
+            }
+            if line == "'''" || line == "---" || line == "***" {
+                nodes.push(Node::ThematicBreak);
+                i += 1;
+                continue;
+            }
+            if line == "<<<" {
+                i += 1;
+                continue;
+            }
+            if is_delimiter(line) {
+                let end = (i + 1..lines.len())
+                    .find(|&j| lines[j] == line)
+                    .unwrap_or(lines.len());
+                let inner = &lines[i + 1..end];
+                nodes.extend(self.delimited_block(line, inner, block));
+                i = (end + 1).min(lines.len());
+                continue;
+            }
+            if let Some(captures) = BLOCK_MACRO.captures(line) {
+                nodes.extend(self.block_macro(&captures[1], &captures[2], &captures[3], block));
+                i += 1;
+                continue;
+            }
+            if list_item(line).is_some() {
+                let (list, next) = self.list(lines, i, &block);
+                nodes.push(list);
+                i = next;
+                continue;
+            }
+            if DEFINITION_TERM.is_match(line) {
+                let (list, next) = self.definition_list(lines, i);
+                nodes.push(list);
+                i = next;
+                continue;
+            }
+            if line.starts_with(' ') {
+                let end = (i..lines.len())
+                    .find(|&j| is_blank(&lines[j]))
+                    .unwrap_or(lines.len());
+                let width = lines[i..end]
+                    .iter()
+                    .map(|line| line.len() - line.trim_start().len())
+                    .min()
+                    .unwrap_or(0);
+                let code: Vec<&str> = lines[i..end].iter().map(|line| &line[width..]).collect();
+                nodes.push(Node::code_block(code.join("\n"), ""));
+                i = end;
+                continue;
+            }
+
+            let end = (i..lines.len())
+                .find(|&j| {
+                    j > i
+                        && (is_blank(&lines[j])
+                            || is_delimiter(&lines[j])
+                            || (lines[j].starts_with('[') && lines[j].ends_with(']')))
+                })
+                .unwrap_or(lines.len());
+            let paragraph = &lines[i..end];
+            i = end;
+            nodes.push(self.paragraph(paragraph, block));
+        }
+        nodes
+    }
+
+    fn paragraph(&mut self, lines: &[String], block: BlockAttributes) -> Node {
+        match block.style.as_deref() {
+            Some("source") => {
+                let language = block.positional.first().cloned().unwrap_or_default();
+                return Node::code_block(lines.join("\n"), language);
+            }
+            Some("literal") => return Node::code_block(lines.join("\n"), ""),
+            _ => {}
+        }
+        let (kind, lines) = match ADMONITION_PARAGRAPH.captures(&lines[0]) {
+            Some(captures) => {
+                let mut rest = vec![captures[2].to_string()];
+                rest.extend(lines[1..].iter().cloned());
+                (CalloutKind::from_name(&captures[1]), rest)
+            }
+            None => (block.admonition(), lines.to_vec()),
+        };
+
+        let mut children = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let (text, hard_break) = match line.strip_suffix(" +") {
+                Some(text) => (text, true),
+                None => (line.as_str(), false),
+            };
+            children.extend(self.inlines(text.trim(), TextFormatting::default()));
+            if index + 1 < lines.len() {
+                children.push(if hard_break {
+                    InlineNode::HardBreak
+                } else {
+                    InlineNode::text(" ")
+                });
+            }
+        }
+        let paragraph = Node::Paragraph {
+            children: merge_text(children),
+        };
+        match (kind, block.style.as_deref()) {
+            (Some(kind), _) => Node::Callout {
+                kind,
+                title: block.title,
+                children: vec![paragraph],
+            },
+            (None, Some("quote")) => Node::BlockQuote {
+                children: vec![paragraph],
+            },
+            _ => paragraph,
+        }
+    }
+
+    fn delimited_block(
+        &mut self,
+        delimiter: &str,
+        inner: &[String],
+        block: BlockAttributes,
+    ) -> Vec<Node> {
+        let content = inner.join("\n");
+        let node = match delimiter.chars().next() {
+            _ if delimiter.starts_with("|===") => self.table(inner, block),
+            Some('/') => Node::HtmlComment {
+                text: format!(" {} ", content.trim()),
+            },
+            Some('-') if delimiter == "--" => match block.admonition() {
+                Some(kind) => Node::Callout {
+                    kind,
+                    title: block.title,
+                    children: self.blocks(inner),
+                },
+                None => return self.blocks(inner),
+            },
+            Some('-') => self.code_block(content, &block),
+            Some('.') => match block.style.as_deref() {
+                Some("source") => self.code_block(content, &block),
+                None | Some("literal") => Node::code_block(content, ""),
+                Some(kind) => Node::Custom {
+                    kind: kind.to_string(),
+                    data: content,
+                },
+            },
+            Some('_') => Node::BlockQuote {
+                children: self.blocks(inner),
+            },
+            Some('=') => match block.admonition() {
+                Some(kind) => Node::Callout {
+                    kind,
+                    title: block.title,
+                    children: self.blocks(inner),
+                },
+                None => Node::Group {
+                    name: "example".to_string(),
+                    children: self.blocks(inner),
+                },
+            },
+            Some('*') => Node::Group {
+                name: "sidebar".to_string(),
+                children: self.blocks(inner),
+            },
+            Some('+') => match block.style.as_deref() {
+                Some("stem" | "latexmath" | "asciimath") => Node::math_block(content),
+                _ => Node::HtmlBlock { html: content },
+            },
+            _ => return Vec::new(),
+        };
+        vec![node]
+    }
+
+    fn code_block(&self, code: String, block: &BlockAttributes) -> Node {
+        let language = match block.style.as_deref() {
+            Some("source") | None => block
+                .positional
+                .first()
+                .or_else(|| self.attributes.get("source-language"))
+                .cloned()
+                .unwrap_or_default(),
+            Some(language) => language.to_string(),
+        };
+        let mut properties = CodeBlockProperties::new();
+        let has = |name: &str| {
+            block.positional.iter().any(|value| value == name)
+                || block.options.iter().any(|value| value == name)
+        };
+        properties.show_line_numbers = has("linenums") || has("numbered");
+        if let Some(start) = block.named.get("start").and_then(|s| s.parse().ok()) {
+            properties.start_line = start;
+        }
+        if let Some(lines) = block.named.get("highlight") {
+            properties.highlight_lines = Some(parse_line_ranges(lines));
+        }
+        Node::code_block_with_properties(code, language, properties)
+    }
+
+    fn block_macro(
+        &mut self,
+        name: &str,
+        target: &str,
+        attributes: &str,
+        block: BlockAttributes,
+    ) -> Vec<Node> {
+        let mut list = BlockAttributes::default();
+        list.parse(attributes);
+        let node = match name {
+            "image" => {
+                let alt = list
+                    .named
+                    .get("alt")
+                    .or(list.style.as_ref())
+                    .cloned()
+                    .unwrap_or_default();
+                let size = |value: Option<&String>| value.and_then(|v| v.parse().ok());
+                let width = size(list.named.get("width").or(list.positional.first()));
+                let height = size(list.named.get("height").or(list.positional.get(1)));
+                match block.title {
+                    None if width.is_none() && height.is_none() => {
+                        Node::paragraph_with_inlines(vec![InlineNode::image(target, alt)])
+                    }
+                    title => {
+                        let caption = title
+                            .map(|title| self.inlines(&title, TextFormatting::default()))
+                            .unwrap_or_default();
+                        let mut figure = Node::Figure {
+                            url: target.to_string(),
+                            alt,
+                            title: None,
+                            caption,
+                            properties: Default::default(),
+                        };
+                        if let Node::Figure { properties, .. } = &mut figure {
+                            properties.width = width;
+                            properties.height = height;
+                        }
+                        figure
+                    }
+                }
+            }
+            "toc" => return Vec::new(),
+            _ => Node::Custom {
+                kind: name.to_string(),
+                data: target.to_string(),
+            },
+        };
+        vec![node]
+    }
+
+    fn list(&mut self, lines: &[String], start: usize, block: &BlockAttributes) -> (Node, usize) {
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut i = start;
+        let mut after_blank = false;
+        while i < lines.len() {
+            let line = lines[i].as_str();
+            if let Some((marker, number, text)) = list_item(line) {
+                let (checked, text) = match text.get(..4) {
+                    Some("[x] " | "[X] " | "[*] ") => (Some(true), &text[4..]),
+                    Some("[ ] ") => (Some(false), &text[4..]),
+                    _ => (None, text),
+                };
+                entries.push(Entry {
+                    marker,
+                    lines: vec![text.to_string()],
+                    checked,
+                    number,
+                });
+                after_blank = false;
+                i += 1;
+                continue;
+            }
+            if is_blank(line) {
+                let next = (i..lines.len())
+                    .find(|&j| !is_blank(&lines[j]))
+                    .unwrap_or(lines.len());
+                // Only items with a marker seen before continue the list
+                // after a blank line; the end of the input ends the list
+                let continues = lines
+                    .get(next)
+                    .and_then(|line| list_item(line))
+                    .is_some_and(|(marker, ..)| entries.iter().any(|entry| entry.marker == marker));
+                if !continues {
+                    break;
+                }
+                after_blank = true;
+                i = next;
+                continue;
+            }
+            let entry = entries.last_mut().expect("lists start with an item");
+            if line == "+" {
+                // A list continuation attaches the next block to the item
+                let first = i + 1;
+                let end = match lines.get(first) {
+                    Some(open) if is_delimiter(open) => (first + 1..lines.len())
+                        .find(|&j| lines[j] == *open)
+                        .map_or(lines.len(), |close| close + 1),
+                    _ => (first..lines.len())
+                        .find(|&j| is_blank(&lines[j]) || lines[j] == "+")
+                        .unwrap_or(lines.len()),
+                };
+                entry.lines.push(String::new());
+                entry.lines.extend(lines[first..end].iter().cloned());
+                after_blank = false;
+                i = end;
+                continue;
+            }
+            if after_blank || is_delimiter(line) {
+                break;
+            }
+            entry.lines.push(line.trim_start().to_string());
+            i += 1;
+        }
+
+        let (mut list, _) = self.build_list(&entries, 0, &[]);
+        if let Node::List { start, .. } = &mut list
+            && let Some(first) = block.named.get("start").and_then(|s| s.parse().ok())
+        {
+            *start = Some(first);
+        }
+        (list, i)
+    }
+
+    /// Builds the list of the items from `from` sharing its marker, nesting
+    /// the items with other markers that follow them
+    fn build_list(&mut self, entries: &[Entry], from: usize, outer: &[&str]) -> (Node, usize) {
+        let marker = entries[from].marker.as_str();
+        let mut nested_outer = outer.to_vec();
+        nested_outer.push(marker);
+        let mut items = Vec::new();
+        let mut i = from;
+        while i < entries.len() && entries[i].marker == marker {
+            let entry = &entries[i];
+            let mut item = ListItem::new(self.blocks(&entry.lines));
+            item.checked = entry.checked;
+            i += 1;
+            while i < entries.len()
+                && entries[i].marker != marker
+                && !outer.contains(&entries[i].marker.as_str())
+            {
+                let (nested, next) = self.build_list(entries, i, &nested_outer);
+                item.children.push(nested);
+                i = next;
+            }
+            items.push(item);
+        }
+
+        let list_type = if marker.starts_with(['*', '-']) {
+            if items.iter().any(|item| item.checked.is_some()) {
+                ListType::Task
+            } else {
+                ListType::Unordered
+            }
+        } else {
+            ListType::Ordered
+        };
+        let start = entries[from].number.filter(|&number| number != 1);
+        let list = Node::List {
+            list_type,
+            items,
+            start,
+            loose: false,
+        };
+        (list, i)
+    }
+
+    fn definition_list(&mut self, lines: &[String], start: usize) -> (Node, usize) {
+        let mut items = Vec::new();
+        let mut i = start;
+        while let Some(captures) = lines.get(i).and_then(|line| DEFINITION_TERM.captures(line)) {
+            let term = self.inlines(&captures[1], TextFormatting::default());
+            let mut description: Vec<String> = captures
+                .get(3)
+                .map(|text| text.as_str().to_string())
+                .into_iter()
+                .collect();
+            i += 1;
+            if description.is_empty() {
+                while i < lines.len() && is_blank(&lines[i]) {
+                    i += 1;
+                }
+            }
+            while i < lines.len() && !is_blank(&lines[i]) && !DEFINITION_TERM.is_match(&lines[i]) {
+                if lines[i] == "+" {
+                    description.push(String::new());
+                } else {
+                    description.push(lines[i].trim_start().to_string());
+                }
+                i += 1;
+            }
+            let descriptions = if description.is_empty() {
+                Vec::new()
+            } else {
+                vec![self.blocks(&description)]
+            };
+            items.push(DefinitionItem::new(term, descriptions));
+
+            let next = (i..lines.len())
+                .find(|&j| !is_blank(&lines[j]))
+                .unwrap_or(lines.len());
+            if next == lines.len() || !DEFINITION_TERM.is_match(&lines[next]) {
+                break;
+            }
+            i = next;
+        }
+        (Node::DefinitionList { items }, i)
+    }
+
+    fn table(&mut self, inner: &[String], block: BlockAttributes) -> Node {
+        let mut cols: Vec<TableAlignment> = Vec::new();
+        if let Some(spec) = block.named.get("cols") {
+            for column in spec.split([',', ';']).map(str::trim) {
+                let (count, column) = match column.split_once('*') {
+                    Some((count, column)) => (count.trim().parse().unwrap_or(1), column),
+                    None => (1, column),
+                };
+                let alignment = if column.contains('^') {
+                    TableAlignment::Center
+                } else if column.contains('>') {
+                    TableAlignment::Right
+                } else if column.contains('<') {
+                    TableAlignment::Left
+                } else {
+                    TableAlignment::None
+                };
+                cols.extend(std::iter::repeat_n(alignment, count));
+            }
+        }
+
+        let first = inner.iter().position(|line| !is_blank(line)).unwrap_or(0);
+        let cells = table_cells(&inner[first..].join("\n"));
+        let columns = if cols.is_empty() {
+            let first_line = inner.get(first).map_or(0, String::len);
+            cells
+                .iter()
+                .filter(|cell| cell.offset < first_line)
+                .map(|cell| cell.colspan * cell.repeat)
+                .sum::<usize>()
+                .max(1)
+        } else {
+            cols.len()
+        };
+        let implicit_header = inner.get(first + 1).is_some_and(|line| is_blank(line))
+            && inner.get(first).is_some_and(|line| line.starts_with('|'));
+        let header_row = block.options.iter().any(|option| option == "header")
+            || (implicit_header && !block.options.iter().any(|option| option == "noheader"));
+
+        let mut rows: Vec<Vec<TableCell>> = Vec::new();
+        let mut row: Vec<TableCell> = Vec::new();
+        let mut occupied = vec![0usize; columns];
+        let mut column = 0;
+        for cell in &cells {
+            for _ in 0..cell.repeat {
+                while column < columns && occupied[column] > 0 {
+                    column += 1;
+                }
+                let content = self.inlines(&cell.text, TextFormatting::default());
+                let mut table_cell = TableCell::new(content);
+                table_cell.colspan = cell.colspan as u32;
+                table_cell.rowspan = cell.rowspan as u32;
+                row.push(table_cell);
+                for covered in occupied.iter_mut().skip(column).take(cell.colspan) {
+                    *covered = cell.rowspan;
+                }
+                column += cell.colspan;
+                while column < columns && occupied[column] > 0 {
+                    column += 1;
+                }
+                if column >= columns {
+                    rows.push(std::mem::take(&mut row));
+                    for covered in &mut occupied {
+                        *covered = covered.saturating_sub(1);
+                    }
+                    column = 0;
+                }
+            }
+        }
+        if !row.is_empty() {
+            rows.push(row);
+        }
+
+        let header = if header_row && !rows.is_empty() {
+            rows.remove(0)
+        } else {
+            Vec::new()
+        };
+        let mut alignments = cols;
+        alignments.resize(columns, TableAlignment::None);
+        Node::Table {
+            header,
+            rows,
+            alignments,
+            properties: TableProperties {
+                caption: block.title,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Parses inline markup, with `formatting` applied to the text
+    fn inlines(&mut self, text: &str, formatting: TextFormatting) -> Vec<InlineNode> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        let collect = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+        let flush = |text: &mut String, nodes: &mut Vec<InlineNode>| {
+            if !text.is_empty() {
+                let text = std::mem::take(text);
+                nodes.push(InlineNode::Text(TextNode::with_formatting(
+                    text,
+                    formatting.clone(),
+                )));
+            }
+        };
+
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let previous = i.checked_sub(1).map(|p| chars[p]);
+            if c == '\\' && chars.get(i + 1).is_some_and(char::is_ascii_punctuation) {
+                text.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            if c == '{'
+                && let Some(end) = (i + 1..chars.len()).find(|&k| chars[k] == '}')
+                && let Some(value) = self.attributes.get(&collect(i + 1, end))
+            {
+                text.push_str(value);
+                i = end + 1;
+                continue;
+            }
+            let starts = previous.is_none_or(|p| !p.is_alphanumeric());
+            if chars[i..].starts_with(&['+', '+', '+'])
+                && let Some(end) = find(&chars, i + 3, "+++")
+            {
+                flush(&mut text, &mut nodes);
+                nodes.push(InlineNode::Html {
+                    html: collect(i + 3, end),
+                });
+                i = end + 3;
+                continue;
+            }
+            if c == '+' && starts {
+                let end = find(&chars, i + 1, "+").filter(|&end| {
+                    end > i + 1 && chars.get(end + 1).is_none_or(|n| !n.is_alphanumeric())
+                });
+                if let Some(end) = end {
+                    text.push_str(&collect(i + 1, end));
+                    i = end + 1;
+                    continue;
+                }
+            }
+            if chars[i..].starts_with(&['[', '.']) {
+                let close = (i + 2..chars.len()).find(|&k| chars[k] == ']');
+                if let Some(close) = close.filter(|&close| chars.get(close + 1) == Some(&'#')) {
+                    let role = collect(i + 2, close);
+                    if let Some((content, end)) = self.marked(&chars, close + 1, '#') {
+                        flush(&mut text, &mut nodes);
+                        let mut inner = formatting.clone();
+                        inner.strikethrough |= role == "line-through";
+                        nodes.extend(self.inlines(&content, inner));
+                        i = end;
+                        continue;
+                    }
+                }
+            }
+            if "*_`#".contains(c)
+                && let Some((content, end)) = self.marked(&chars, i, c)
+            {
+                flush(&mut text, &mut nodes);
+                let mut inner = formatting.clone();
+                match c {
+                    '*' => inner.bold = true,
+                    '_' => inner.italic = true,
+                    '`' => {
+                        let code = content
+                            .strip_prefix('+')
+                            .and_then(|code| code.strip_suffix('+'))
+                            .unwrap_or(&content);
+                        nodes.push(InlineNode::code_span(code));
+                        i = end;
+                        continue;
+                    }
+                    _ => {}
+                }
+                nodes.extend(self.inlines(&content, inner));
+                i = end;
+                continue;
+            }
+            if chars[i..].starts_with(&['<', '<'])
+                && let Some(end) = find(&chars, i + 2, ">>")
+            {
+                let reference = collect(i + 2, end);
+                let (id, label) = match reference.split_once(',') {
+                    Some((id, label)) => (id.trim().to_string(), label.trim().to_string()),
+                    None => (reference.trim().to_string(), String::new()),
+                };
+                if !id.is_empty() && !id.contains(char::is_whitespace) {
+                    flush(&mut text, &mut nodes);
+                    let label = if label.is_empty() { id.clone() } else { label };
+                    nodes.push(InlineNode::link(format!("#{id}"), label));
+                    i = end + 2;
+                    continue;
+                }
+            }
+            if c.is_ascii_alphabetic() {
+                let rest = collect(i, chars.len());
+                // Footnotes may follow a word directly
+                let captures = INLINE_MACRO
+                    .captures(&rest)
+                    .filter(|_| starts || rest.starts_with("footnote:"));
+                if let Some(captures) = captures {
+                    flush(&mut text, &mut nodes);
+                    let content = captures[3].replace("\\]", "]");
+                    nodes.extend(self.inline_macro(&captures[1], &captures[2], &content));
+                    i += captures[0].chars().count();
+                    continue;
+                }
+                if let Some(url) = URL.find(&rest).filter(|_| starts) {
+                    let mut url = url.as_str();
+                    let after = &rest[url.len()..];
+                    flush(&mut text, &mut nodes);
+                    let label = after
+                        .strip_prefix('[')
+                        .and_then(|after| after.find(']').map(|end| &after[..end]));
+                    match label {
+                        Some(label) => {
+                            i += url.chars().count() + label.chars().count() + 2;
+                            let label = label.trim_end_matches('^');
+                            nodes.push(if label.is_empty() {
+                                InlineNode::autolink_url(url)
+                            } else {
+                                InlineNode::link(url, label)
+                            });
+                        }
+                        None => {
+                            url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+                            i += url.chars().count();
+                            nodes.push(InlineNode::autolink_url(url));
+                        }
+                    }
+                    continue;
+                }
+            }
+            text.push(c);
+            i += 1;
+        }
+        flush(&mut text, &mut nodes);
+        nodes
+    }
+
+    /// Content of the text marked with `mark` at `i`, constrained
+    /// (`*bold*`) or unconstrained (`**bold**`), and the index after it
+    fn marked(&self, chars: &[char], i: usize, mark: char) -> Option<(String, usize)> {
+        let previous = i.checked_sub(1).map(|p| chars[p]);
+        if chars.get(i + 1) == Some(&mark) {
+            let close = [mark, mark];
+            let end = (i + 3..chars.len()).find(|&k| chars[k..].starts_with(&close))?;
+            return Some((chars[i + 2..end].iter().collect(), end + 2));
+        }
+        let opens = previous.is_none_or(|p| !p.is_alphanumeric() && p != mark)
+            && chars.get(i + 1).is_some_and(|next| !next.is_whitespace());
+        if !opens {
+            return None;
+        }
+        let end = (i + 2..chars.len()).find(|&k| {
+            chars[k] == mark
+                && !chars[k - 1].is_whitespace()
+                && chars.get(k + 1).is_none_or(|next| !next.is_alphanumeric())
+        })?;
+        Some((chars[i + 1..end].iter().collect(), end + 1))
+    }
+
+    fn inline_macro(&mut self, name: &str, target: &str, content: &str) -> Vec<InlineNode> {
+        let node = match name {
+            "link" => {
+                let label = content.split(",window=").next().unwrap_or_default();
+                let label = label.trim_end_matches('^').trim_matches('"');
+                if label.is_empty() {
+                    InlineNode::link(target, target)
+                } else {
+                    InlineNode::Link {
+                        url: target.to_string(),
+                        title: None,
+                        children: self.inlines(label, TextFormatting::default()),
+                        reference: None,
+                    }
+                }
+            }
+            "mailto" if content.is_empty() => InlineNode::autolink_email(target),
+            "mailto" => InlineNode::link(format!("mailto:{target}"), content),
+            "image" => {
+                let mut attributes = BlockAttributes::default();
+                attributes.parse(content);
+                InlineNode::Image {
+                    url: target.to_string(),
+                    alt: attributes
+                        .named
+                        .get("alt")
+                        .or(attributes.style.as_ref())
+                        .cloned()
+                        .unwrap_or_default(),
+                    title: attributes.named.get("title").cloned(),
+                }
+            }
+            "footnote" if target.is_empty() => InlineNode::InlineFootnote {
+                children: self.inlines(content, TextFormatting::default()),
+            },
+            "footnote" => {
+                let defined = self.footnotes.iter().any(|note| note.label == target);
+                if !defined && !content.is_empty() {
+                    let children = self.inlines(content, TextFormatting::default());
+                    self.footnotes.push(FootnoteDefinition::new(
+                        target,
+                        vec![Node::Paragraph { children }],
+                    ));
+                }
+                InlineNode::footnote_ref(target)
+            }
+            "stem" | "latexmath" | "asciimath" => InlineNode::math(content),
+            "kbd" => InlineNode::code_span(content),
+            "pass" => InlineNode::text(content),
+            _ => {
+                let url = if target.contains(['.', '#']) {
+                    target.to_string()
+                } else {
+                    format!("#{target}")
+                };
+                let label = if content.is_empty() { target } else { content };
+                InlineNode::link(url, label)
+            }
+        };
+        vec![node]
+    }
+}
+
+/// A table cell in the source: its text, spans, how many times it
+/// repeats and where it starts
+struct SourceCell {
+    text: String,
+    colspan: usize,
+    rowspan: usize,
+    repeat: usize,
+    offset: usize,
+}
+
+/// Splits the content of a table into its cells
+fn table_cells(content: &str) -> Vec<SourceCell> {
+    let mut cells: Vec<SourceCell> = Vec::new();
+    let mut current: Option<SourceCell> = None;
+    let mut text = String::new();
+    let mut escaped = false;
+    for (offset, c) in content.char_indices() {
+        if escaped {
+            text.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => {
+                escaped = true;
+                text.push(c);
+            }
+            '|' => {
+                // A cell spec like `2+` or `.3+^` right before the separator
+                // belongs to the next cell
+                let spec_start = text.rfind(char::is_whitespace).map_or(0, |space| space + 1);
+                let spec = &text[spec_start..];
+                // Specs follow whitespace, or open the table
+                let separate = spec_start > 0 || current.is_none();
+                let captures = CELL_SPEC
+                    .captures(spec)
+                    .filter(|_| separate && !spec.is_empty());
+                let (repeat, colspan, rowspan) = match &captures {
+                    Some(captures) => {
+                        let number = |index: usize| {
+                            captures.get(index).and_then(|m| m.as_str().parse().ok())
+                        };
+                        (
+                            number(1).unwrap_or(1),
+                            number(2).unwrap_or(1),
+                            number(3).unwrap_or(1),
+                        )
+                    }
+                    None => (1, 1, 1),
+                };
+                if captures.is_some() {
+                    text.truncate(spec_start);
+                }
+                if let Some(mut cell) = current.take() {
+                    cell.text = unescape_cell(&text);
+                    cells.push(cell);
+                }
+                text.clear();
+                current = Some(SourceCell {
+                    text: String::new(),
+                    colspan,
+                    rowspan,
+                    repeat,
+                    offset,
+                });
+            }
+            c => text.push(c),
+        }
+    }
+    if let Some(mut cell) = current {
+        cell.text = unescape_cell(&text);
+        cells.push(cell);
+    }
+    cells
+}
+
+fn unescape_cell(text: &str) -> String {
+    text.replace("\\|", "|")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn markdown(text: &str) -> Document {
+        Document::try_from(Text::<Markdown>::new(text)).unwrap()
+    }
+
+    fn render(document: &Document) -> String {
+        Text::<AsciiDoc>::try_from(document).unwrap().into_inner()
+    }
+
+    fn parse(text: &str) -> Document {
+        Document::try_from(Text::<AsciiDoc>::new(text)).unwrap()
+    }
+
+    #[test]
+    fn test_render_asciidoc() {
+        let mut doc = markdown(
+            "# Guide\n\nSome **bold**, *italic* and `code` with [a link](https://example.com) \
+             and a note[^n].\n\n> [!WARNING]\n> Careful\n\n- One\n  1. First\n- [x] Done\n\n\
+             ```rust\nlet x = 1; // <1>\nlet y = 2; // <2>\n```\n\n1. The first\n2. The second\n\n\
+             [^n]: The footnote.\n",
+        );
+        // The Markdown parser keeps footnote definitions out of the nodes
+        doc.nodes
+            .push(Node::FootnoteDefinition(FootnoteDefinition::new(
+                "n",
+                vec![Node::paragraph("The footnote.")],
+            )));
+        doc.nodes.push(Node::Table {
+            header: vec![
+                TableCell::text("A"),
+                TableCell::text("B"),
+                TableCell::text("C"),
+            ],
+            rows: vec![
+                vec![
+                    TableCell::with_spans(vec![InlineNode::text("wide")], 2, 1),
+                    TableCell::with_rowspan(vec![InlineNode::text("tall")], 2),
+                ],
+                vec![TableCell::text("1"), TableCell::text("a|b")],
+            ],
+            alignments: vec![
+                TableAlignment::Left,
+                TableAlignment::Center,
+                TableAlignment::None,
+            ],
+            properties: TableProperties::default(),
+        });
+
+        assert_eq!(
+            render(&doc),
+            "= Guide\n\n\
+             Some *bold*, _italic_ and `code` with https://example.com[a link] and a \
+             note{footnote}.\n\n\
+             WARNING: Careful\n\n\
+             * One\n.. First\n* [x] Done\n\n\
+             [source,rust]\n----\nlet x = 1; // <1>\nlet y = 2; // <2>\n----\n\n\
+             <1> The first\n<2> The second\n\n\
+             [cols=\"<,^,1\",options=\"header\"]\n|===\n|A |B |C\n\n\
+             2+|wide .2+|tall\n|1 |a\\|b\n|===\n"
+                .replace("{footnote}", "footnote:n[The footnote.]")
+        );
+    }
+
+    #[test]
+    fn test_parse_asciidoc() {
+        let doc = parse(
+            "= Guide\n:product: Editor\n\nThe *{product}* has _docs_ and ``code`` at \
+             https://example.com[the site].\nSee <<setup,Setup>> or footnote:[Later.] +\nnow.\n\n\
+             NOTE: Read this.\n\n[TIP]\n.Hint\n====\nInside.\n====\n\n\
+             [source,python,linenums,highlight=2]\n----\nprint(1)  # <1>\n----\n<1> Prints.\n\n\
+             * Top\n** Nested\n+\n----\ncode\n----\n* [ ] Open\n\n\
+             CPU:: The brain.\nRAM::\n  Memory.\n\n\
+             .Sizes\n[cols=\"1,>1\",options=\"header\"]\n|===\n|Name |Size\n\n\
+             .2+|a |1\n|2\n|===\n",
+        );
+        assert_eq!(doc.nodes[0].as_heading().unwrap().0, 1);
+        assert_eq!(
+            doc.nodes[1].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("The "),
+                InlineNode::bold_text("Editor"),
+                InlineNode::text(" has "),
+                InlineNode::italic_text("docs"),
+                InlineNode::text(" and "),
+                InlineNode::code_span("code"),
+                InlineNode::text(" at "),
+                InlineNode::link("https://example.com", "the site"),
+                InlineNode::text(". See "),
+                InlineNode::link("#setup", "Setup"),
+                InlineNode::text(" or "),
+                InlineNode::inline_footnote("Later."),
+                InlineNode::HardBreak,
+                InlineNode::text("now."),
+            ]
+        );
+        assert_eq!(
+            doc.nodes[2],
+            Node::Callout {
+                kind: CalloutKind::Note,
+                title: None,
+                children: vec![Node::paragraph("Read this.")],
+            }
+        );
+        assert_eq!(
+            doc.nodes[3],
+            Node::Callout {
+                kind: CalloutKind::Tip,
+                title: Some("Hint".to_string()),
+                children: vec![Node::paragraph("Inside.")],
+            }
+        );
+        assert_eq!(
+            doc.nodes[4],
+            Node::code_block_with_properties(
+                "print(1)  # <1>",
+                "python",
+                CodeBlockProperties::new()
+                    .with_line_numbers(true)
+                    .with_highlight_lines(vec![2])
+            )
+        );
+        assert!(matches!(
+            &doc.nodes[5],
+            Node::List { list_type: ListType::Ordered, items, .. } if items.len() == 1
+        ));
+
+        let Node::List {
+            list_type, items, ..
+        } = &doc.nodes[6]
+        else {
+            panic!("expected a list");
+        };
+        assert_eq!(*list_type, ListType::Task);
+        assert_eq!(items[1].checked, Some(false));
+        let Node::List { items: nested, .. } = &items[0].children[1] else {
+            panic!("expected a nested list");
+        };
+        assert_eq!(nested[0].children[1], Node::code_block("code", ""));
+
+        let Node::DefinitionList { items } = &doc.nodes[7] else {
+            panic!("expected a description list");
+        };
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].descriptions, [vec![Node::paragraph("Memory.")]]);
+
+        let Node::Table {
+            header,
+            rows,
+            alignments,
+            properties,
+        } = &doc.nodes[8]
+        else {
+            panic!("expected a table");
+        };
+        assert_eq!(properties.caption.as_deref(), Some("Sizes"));
+        assert_eq!(alignments, &[TableAlignment::None, TableAlignment::Right]);
+        assert_eq!(header.len(), 2);
+        assert_eq!(rows[0][0].rowspan, 2);
+        assert_eq!(rows[1].len(), 1);
+        assert_eq!(InlineNode::plain_text_of(&rows[1][0].content), "2");
+        assert_eq!(doc.nodes.len(), 9);
+    }
+
+    #[test]
+    fn test_asciidoc_round_trip() {
+        let doc = markdown(
+            "# Title\n\n## Section\n\nText with **bold**, ~~gone~~ and a [link](/docs).\n\n\
+             > Quoted\n>\n> > Nested\n\n3. Three\n4. Four\n\n---\n\n\
+             ::: note Heads up\nFirst.\n\nSecond.\n:::\n\n\
+             | A | B |\n|---|--:|\n| 1 | 2 |\n\n$$\nx^2\n$$\n\n<!-- hidden -->\n",
+        );
+        let asciidoc = render(&doc);
+        assert_eq!(render(&parse(&asciidoc)), asciidoc);
+        assert!(asciidoc.contains("[start=3]\n. Three\n. Four"));
+        assert!(asciidoc.contains(".Heads up\n[NOTE]\n====\nFirst.\n\nSecond.\n===="));
+    }
+
+    #[test]
+    fn test_list_before_trailing_blank_lines() {
+        let doc = parse("* a\n* b\n\n");
+        assert_eq!(doc.nodes.len(), 1);
+        assert!(matches!(&doc.nodes[0], Node::List { items, .. } if items.len() == 2));
+        assert_eq!(parse(". one\n\n\n").nodes.len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+//! Compact binary format for autosave and IPC
+//!
+//! JSON is readable but large and slow to parse for documents saved after
+//! every few keystrokes or sent between processes. The binary format is the
+//! same data as MessagePack: an 8-byte header, the magic `MDOC` followed by
+//! the little-endian [`SCHEMA_VERSION`] the document was written with, then
+//! the document as a map with field names. Like JSON payloads, documents
+//! written with an older schema version are upgraded by a
+//! [`JsonMigrations`] registry when they are read, so stored bytes keep
+//! loading across releases.
+//!
+//! The format holds bytes rather than text, so it has no [`Text`](super::Text)
+//! wrapper or [`FormatConverter`](super::format::FormatConverter).
+
+use super::json::{JsonMigrations, SCHEMA_VERSION, VERSION_FIELD, migrated_document};
+use crate::{Document, ParseError};
+use serde_json::Value;
+
+/// Bytes every binary document starts with
+const MAGIC: &[u8; 4] = b"MDOC";
+
+/// Length of the magic and schema version
+const HEADER_LEN: usize = 8;
+
+fn binary_error(message: impl std::fmt::Display) -> ParseError {
+    ParseError::Generic(format!("binary document: {message}"))
+}
+
+impl Document {
+    /// Encodes the document in the compact binary format
+    pub fn to_binary(&self) -> Result<Vec<u8>, ParseError> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 256);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+        rmp_serde::encode::write_named(&mut bytes, self).map_err(binary_error)?;
+        Ok(bytes)
+    }
+
+    /// Decodes a document from the binary format, upgrading older schema
+    /// versions with the built-in migrations
+    pub fn from_binary(bytes: &[u8]) -> Result<Document, ParseError> {
+        Self::from_binary_with(bytes, &JsonMigrations::default())
+    }
+
+    /// Decodes a document from the binary format, upgrading older schema
+    /// versions with `migrations`
+    ///
+    /// Documents of the current version are decoded directly; older ones
+    /// go through the same migrations as JSON payloads.
+    pub fn from_binary_with(
+        bytes: &[u8],
+        migrations: &JsonMigrations,
+    ) -> Result<Document, ParseError> {
+        let (header, body) = bytes
+            .split_at_checked(HEADER_LEN)
+            .ok_or_else(|| binary_error("too short"))?;
+        if &header[..4] != MAGIC {
+            return Err(binary_error("missing the MDOC header"));
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version == SCHEMA_VERSION {
+            return rmp_serde::from_slice(body).map_err(binary_error);
+        }
+
+        let mut payload: Value = rmp_serde::from_slice(body).map_err(binary_error)?;
+        if let Some(object) = payload.as_object_mut() {
+            object.insert(VERSION_FIELD.to_string(), Value::from(version));
+        }
+        migrated_document(payload, migrations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CodeBlockProperties, Json, Markdown, Node, Text};
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "# Title\n\nSome *emphasis* and [a link](https://example.com).\n\n\
+             - [x] done\n- [ ] todo\n\n| a | b |\n|---|--:|\n| 1 | 2 |\n\n> quote\n",
+        ))
+        .unwrap();
+        doc.nodes.push(Node::CodeBlock {
+            language: "rust".to_string(),
+            code: "fn main() {}".to_string(),
+            properties: CodeBlockProperties::new().with_highlight_lines(vec![1]),
+        });
+        doc.add_paragraph_with_text("Last");
+
+        let bytes = doc.to_binary().unwrap();
+        assert_eq!(&bytes[..8], b"MDOC\x01\0\0\0");
+        assert_eq!(Document::from_binary(&bytes).unwrap(), doc);
+        let json = Text::<Json>::try_from(&doc).unwrap();
+        assert!(bytes.len() * 2 < json.as_str().len());
+
+        assert!(Document::from_binary(b"MDOC").is_err());
+        assert!(Document::from_binary(b"PK\x03\x04\x01\0\0\0").is_err());
+        let mut newer = bytes.clone();
+        newer[4] = 99;
+        assert!(Document::from_binary(&newer).is_err());
+    }
+
+    #[test]
+    fn test_binary_migrations() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Old");
+        let mut bytes = doc.to_binary().unwrap();
+        bytes[4..8].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(Document::from_binary(&bytes).unwrap(), doc);
+
+        let migrations = JsonMigrations::new().with_migration(0, |payload| {
+            payload["nodes"][0]["children"][0]["text"] = Value::from("Migrated");
+            Ok(())
+        });
+        let migrated = Document::from_binary_with(&bytes, &migrations).unwrap();
+        assert_eq!(migrated.nodes[0].plain_text(), "Migrated");
+    }
+}
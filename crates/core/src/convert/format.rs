@@ -0,0 +1,273 @@
+//! Pluggable document formats
+//!
+//! Every format the crate reads or writes implements [`FormatConverter`],
+//! and so can third-party crates: a `Textile` marker type implementing the
+//! trait gets the same `Text<Textile>` conversions as [`Markdown`](super::Markdown),
+//! [`Html`](super::Html) and [`Json`](super::Json), without changes here.
+//! A [`ConverterRegistry`] looks converters up by name or file extension
+//! for apps that pick the format at runtime.
+
+use super::Text;
+use crate::{Document, ExportTarget, Feature, ParseError};
+use std::rc::Rc;
+
+/// What a converter can do
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether [`FormatConverter::parse`] is implemented
+    pub parse: bool,
+    /// Whether [`FormatConverter::render`] is implemented
+    pub render: bool,
+    /// Features rendering loses, as in a
+    /// [`DegradationReport`](crate::DegradationReport)
+    pub lost_features: Vec<Feature>,
+}
+
+impl Capabilities {
+    /// A converter that parses and renders without losing anything
+    pub fn lossless() -> Self {
+        Self {
+            parse: true,
+            render: true,
+            lost_features: Vec::new(),
+        }
+    }
+
+    /// Capabilities of a parsing and rendering converter for a built-in
+    /// export target
+    pub(crate) fn of_target(target: ExportTarget) -> Self {
+        Self {
+            lost_features: Feature::ALL
+                .into_iter()
+                .filter(|feature| !target.supports(*feature))
+                .collect(),
+            ..Self::lossless()
+        }
+    }
+
+    /// Returns true if rendering keeps the feature
+    pub fn supports(&self, feature: Feature) -> bool {
+        !self.lost_features.contains(&feature)
+    }
+}
+
+/// Parses documents from and renders them to a text format
+///
+/// Implementors that are also `Default` get `TryFrom` conversions between
+/// [`Document`] and [`Text`] of themselves.
+pub trait FormatConverter {
+    /// Short lowercase name of the format, e.g. `"markdown"`
+    fn name(&self) -> &str;
+
+    /// File extensions of the format without the dot, e.g. `["md"]`
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    /// What the converter can do
+    fn capabilities(&self) -> Capabilities;
+
+    /// Parses a document
+    ///
+    /// The default fails, for converters that only render.
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        let _ = text;
+        Err(ParseError::Generic(format!(
+            "{} can't be parsed",
+            self.name()
+        )))
+    }
+
+    /// Renders a document
+    ///
+    /// The default fails, for converters that only parse.
+    fn render(&self, document: &Document) -> Result<String, ParseError> {
+        let _ = document;
+        Err(ParseError::Generic(format!(
+            "{} can't be rendered",
+            self.name()
+        )))
+    }
+}
+
+impl<T: FormatConverter + Default> TryFrom<Text<T>> for Document {
+    type Error = ParseError;
+
+    fn try_from(text: Text<T>) -> Result<Self, Self::Error> {
+        T::default().parse(text.as_str())
+    }
+}
+
+impl<T: FormatConverter + Default> TryFrom<&Document> for Text<T> {
+    type Error = ParseError;
+
+    fn try_from(document: &Document) -> Result<Self, Self::Error> {
+        T::default().render(document).map(Text::new)
+    }
+}
+
+/// Converters looked up by name or file extension
+///
+/// The default registry holds the built-in Markdown, HTML and JSON
+/// converters. A converter registered under a name already taken replaces
+/// the earlier one.
+#[derive(Clone)]
+pub struct ConverterRegistry {
+    converters: Vec<Rc<dyn FormatConverter>>,
+}
+
+impl Default for ConverterRegistry {
+    fn default() -> Self {
+        Self::empty()
+            .with_converter(super::Markdown)
+            .with_converter(super::Html)
+            .with_converter(super::Json)
+    }
+}
+
+impl std::fmt::Debug for ConverterRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConverterRegistry")
+            .field("converters", &self.names())
+            .finish()
+    }
+}
+
+impl ConverterRegistry {
+    /// A registry with the built-in converters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry without any converters
+    pub fn empty() -> Self {
+        Self {
+            converters: Vec::new(),
+        }
+    }
+
+    /// Adds a converter
+    pub fn register(&mut self, converter: impl FormatConverter + 'static) {
+        let converter: Rc<dyn FormatConverter> = Rc::new(converter);
+        match self
+            .converters
+            .iter()
+            .position(|c| c.name() == converter.name())
+        {
+            Some(index) => self.converters[index] = converter,
+            None => self.converters.push(converter),
+        }
+    }
+
+    /// Adds a converter, builder style
+    pub fn with_converter(mut self, converter: impl FormatConverter + 'static) -> Self {
+        self.register(converter);
+        self
+    }
+
+    /// Names of the registered formats, in registration order
+    pub fn names(&self) -> Vec<&str> {
+        self.converters.iter().map(|c| c.name()).collect()
+    }
+
+    /// Returns the converter for a format name
+    pub fn get(&self, name: &str) -> Option<&dyn FormatConverter> {
+        self.converters
+            .iter()
+            .find(|c| c.name() == name)
+            .map(|c| c.as_ref())
+    }
+
+    /// Returns the converter for a file extension, ignoring case and a
+    /// leading dot
+    pub fn for_extension(&self, extension: &str) -> Option<&dyn FormatConverter> {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.converters
+            .iter()
+            .find(|c| c.extensions().contains(&extension.as_str()))
+            .map(|c| c.as_ref())
+    }
+
+    /// Parses `text` in the named format
+    pub fn parse(&self, name: &str, text: &str) -> Result<Document, ParseError> {
+        self.converter(name)?.parse(text)
+    }
+
+    /// Renders a document in the named format
+    pub fn render(&self, name: &str, document: &Document) -> Result<String, ParseError> {
+        self.converter(name)?.render(document)
+    }
+
+    fn converter(&self, name: &str) -> Result<&dyn FormatConverter, ParseError> {
+        self.get(name)
+            .ok_or_else(|| ParseError::Generic(format!("Unknown format: {name}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, Markdown};
+
+    /// Renders headings as `= Title =` lines and everything else as text
+    #[derive(Default)]
+    struct Wiki;
+
+    impl FormatConverter for Wiki {
+        fn name(&self) -> &str {
+            "wiki"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["wiki"]
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                parse: false,
+                render: true,
+                lost_features: vec![Feature::Formatting, Feature::Links],
+            }
+        }
+
+        fn render(&self, document: &Document) -> Result<String, ParseError> {
+            let lines: Vec<String> = document
+                .nodes
+                .iter()
+                .map(|node| match node {
+                    crate::Node::Heading { .. } => format!("= {} =", node.plain_text()),
+                    _ => node.plain_text(),
+                })
+                .collect();
+            Ok(lines.join("\n"))
+        }
+    }
+
+    #[test]
+    fn test_third_party_converter() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Title");
+        doc.add_paragraph_with_text("Body");
+
+        let wiki: Text<Wiki> = doc.as_ref().try_into().unwrap();
+        assert_eq!(wiki.as_str(), "= Title =\nBody");
+        let error = Document::try_from(wiki).unwrap_err();
+        assert_eq!(error.to_string(), "Parse error: wiki can't be parsed");
+
+        let registry = ConverterRegistry::new().with_converter(Wiki);
+        assert_eq!(registry.names(), ["markdown", "html", "json", "wiki"]);
+        assert_eq!(registry.for_extension(".MD").unwrap().name(), "markdown");
+        assert!(!registry.get("wiki").unwrap().capabilities().parse);
+        assert_eq!(registry.render("wiki", &doc).unwrap(), "= Title =\nBody");
+        assert!(registry.parse("textile", "h1. Title").is_err());
+
+        let parsed = registry.parse("markdown", "# Title\n\nBody\n").unwrap();
+        assert_eq!(parsed.nodes, doc.nodes);
+    }
+
+    #[test]
+    fn test_builtin_capabilities() {
+        assert!(!Markdown.capabilities().supports(Feature::CellSpans));
+        assert_eq!(Html.capabilities(), Capabilities::lossless());
+    }
+}
@@ -0,0 +1,121 @@
+//! Server-side syntax highlighting for code blocks in HTML export
+//!
+//! Only compiled with the `syntax-highlighting` feature. The syntax and theme
+//! sets bundled with syntect are loaded once on first use.
+
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{IncludeBackground, styled_line_to_highlighted_html};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Theme used when a code block does not name one, or names an unknown theme
+pub const DEFAULT_HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+fn theme(name: Option<&str>) -> &'static Theme {
+    let themes = &theme_set().themes;
+    name.and_then(|name| themes.get(name))
+        .unwrap_or_else(|| &themes[DEFAULT_HIGHLIGHT_THEME])
+}
+
+/// Highlight code into inline-styled `<span>`s for the given language and theme
+///
+/// Returns `None` when the language is empty or unknown, so the caller can
+/// fall back to plain escaped code.
+pub(crate) fn highlight_code(
+    code: &str,
+    language: &str,
+    theme_name: Option<&str>,
+) -> Option<String> {
+    if language.is_empty() {
+        return None;
+    }
+
+    let syntaxes = syntax_set();
+    let syntax = syntaxes.find_syntax_by_token(language)?;
+    let mut highlighter = HighlightLines::new(syntax, theme(theme_name));
+
+    let mut html = String::with_capacity(code.len() * 2);
+    for line in LinesWithEndings::from(code) {
+        let regions = highlighter.highlight_line(line, syntaxes).ok()?;
+        html.push_str(&styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()?);
+    }
+
+    Some(html)
+}
+
+/// Highlight code with 24-bit ANSI colors for terminals
+///
+/// Returns `None` when the language is empty or unknown, like
+/// [`highlight_code`].
+#[cfg(feature = "ansi")]
+pub(crate) fn highlight_code_ansi(
+    code: &str,
+    language: &str,
+    theme_name: Option<&str>,
+) -> Option<String> {
+    if language.is_empty() {
+        return None;
+    }
+
+    let syntaxes = syntax_set();
+    let syntax = syntaxes.find_syntax_by_token(language)?;
+    let mut highlighter = HighlightLines::new(syntax, theme(theme_name));
+
+    let mut ansi = String::with_capacity(code.len() * 2);
+    for line in LinesWithEndings::from(code) {
+        let regions = highlighter.highlight_line(line, syntaxes).ok()?;
+        ansi.push_str(&syntect::util::as_24_bit_terminal_escaped(&regions, false));
+    }
+
+    Some(ansi)
+}
+
+/// Names of the themes available for highlighting
+pub fn highlight_themes() -> Vec<&'static str> {
+    theme_set().themes.keys().map(String::as_str).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_known_language() {
+        let html = highlight_code("fn main() {}\n", "rust", None).unwrap();
+        assert!(html.contains("<span style=\""));
+        assert!(html.contains("main"));
+        assert!(!html.contains("background-color"));
+    }
+
+    #[test]
+    fn test_highlight_unknown_language_or_theme() {
+        assert!(highlight_code("x", "", None).is_none());
+        assert!(highlight_code("x", "no-such-language", None).is_none());
+
+        // Unknown themes fall back to the default one
+        assert_eq!(
+            highlight_code("let x = 1;", "rs", Some("missing")),
+            highlight_code("let x = 1;", "rs", Some(DEFAULT_HIGHLIGHT_THEME))
+        );
+        assert!(highlight_themes().contains(&DEFAULT_HIGHLIGHT_THEME));
+    }
+
+    #[test]
+    fn test_highlight_escapes_html() {
+        let html = highlight_code("a < b && c > d", "js", None).unwrap();
+        assert!(html.contains("&lt;"));
+        assert!(!html.contains("< b"));
+    }
+}
@@ -1,333 +1,1115 @@
 use crate::convert::html_escape;
-use crate::{Document, InlineNode, ListType, Node, ParseError, TableAlignment};
+use crate::convert::locale::LocaleProvider;
+#[cfg(feature = "katex")]
+use crate::convert::math::KatexRenderer;
+use crate::convert::math::{HtmlMathMode, MathMlRenderer, MathRenderer};
+use crate::convert::render::RendererRegistry;
+use crate::convert::sanitize::HtmlSanitizer;
+use crate::custom::CustomRenderer;
+use crate::links::{LinkPipeline, LinkRewriter};
+use crate::mentions::MentionResolver;
+use crate::models::embed::aspect_ratio_padding;
+use crate::models::{fnv1a, table_cell_columns};
+use crate::safe_mode::{SafeMode, SafetyReport};
+use crate::toc;
+use crate::track_changes::split_inlines_at;
+use crate::urls::UrlPolicy;
+use crate::visibility::is_published;
+use crate::wiki_links::WikiLinkResolver;
+use crate::{BibliographyEntry, NodeAttributes, attributes};
+use crate::{
+    CodeBlockProperties, CodeOutput, CodeOutputKind, Document, ExportTarget, FigureAlignment,
+    HeadingProperties, InlineNode, ListType, Node, Outline, ParseError, TableAlignment, TableCell,
+    TrackedChange, Visibility,
+};
 use regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::rc::Rc;
 
 use super::Html;
-use super::Text;
+use super::format::{Capabilities, FormatConverter};
 
-impl TryFrom<Text<Html>> for Document {
-    type Error = ParseError;
+impl FormatConverter for Html {
+    fn name(&self) -> &str {
+        "html"
+    }
 
-    fn try_from(html: Text<Html>) -> Result<Self, Self::Error> {
-        from_html(html.as_str())
+    fn extensions(&self) -> &[&str] {
+        &["html", "htm"]
     }
-}
 
-impl TryFrom<&Document> for Text<Html> {
-    type Error = ParseError;
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::of_target(ExportTarget::Html)
+    }
+
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        from_html(text)
+    }
 
-    fn try_from(document: &Document) -> Result<Self, Self::Error> {
-        Ok(Text::new(to_html(document)))
+    fn render(&self, document: &Document) -> Result<String, ParseError> {
+        Ok(to_html(document))
     }
 }
-/// Convert a document to HTML
+
+/// Convert a document to HTML with the default options
 fn to_html(document: &Document) -> String {
-    let mut html = String::new();
+    document.to_html_with(&HtmlOptions::default()).html
+}
 
-    for node in &document.nodes {
-        match node_to_html(node, 0) {
-            Ok(node_html) => html.push_str(&node_html),
-            Err(err) => eprintln!("Error converting node to HTML: {}", err),
-        }
+/// How HTML export marks up heading sections
+///
+/// A section runs from a heading up to the next heading of the same or a
+/// higher level, as in [`Document::section_range`], so sections of deeper
+/// headings nest inside it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HtmlSectionMode {
+    /// Headings and content one after another, without wrappers
+    #[default]
+    Flat,
+    /// Each section in a `<details>` element with the heading in its
+    /// `<summary>`, collapsible without scripts
+    Details,
+    /// Each section in a `<section>` element with `data-level` and
+    /// `data-collapsed` attributes for a script to toggle
+    Attributes,
+}
+
+/// Options for HTML export
+///
+/// The defaults match the plain [`Text<Html>`] conversion.
+#[derive(Clone, Default)]
+pub struct HtmlOptions {
+    math_renderer: Option<Rc<dyn MathRenderer>>,
+    math_mode: HtmlMathMode,
+    links: LinkPipeline,
+    escape_raw_html: bool,
+    sanitizer: Option<HtmlSanitizer>,
+    mentions: Option<Rc<dyn MentionResolver>>,
+    wiki_links: Option<Rc<dyn WikiLinkResolver>>,
+    tag_url: Option<String>,
+    locale: Option<Rc<dyn LocaleProvider>>,
+    url_policy: Option<UrlPolicy>,
+    custom_renderers: BTreeMap<String, Rc<dyn CustomRenderer>>,
+    sections: HtmlSectionMode,
+    collapsed: BTreeSet<usize>,
+    renderers: RendererRegistry,
+    drafts: bool,
+    languages: BTreeMap<usize, String>,
+    safe_mode: Option<SafeMode>,
+}
+
+impl fmt::Debug for HtmlOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HtmlOptions")
+            .field("math_renderer", &self.math_renderer.is_some())
+            .field("math_mode", &self.math_mode)
+            .field("links", &self.links)
+            .field("escape_raw_html", &self.escape_raw_html)
+            .field("sanitizer", &self.sanitizer)
+            .field("mentions", &self.mentions.is_some())
+            .field("wiki_links", &self.wiki_links.is_some())
+            .field("tag_url", &self.tag_url)
+            .field("locale", &self.locale.is_some())
+            .field("url_policy", &self.url_policy)
+            .field(
+                "custom_renderers",
+                &self.custom_renderers.keys().collect::<Vec<_>>(),
+            )
+            .field("sections", &self.sections)
+            .field("collapsed", &self.collapsed)
+            .field("renderers", &self.renderers)
+            .field("drafts", &self.drafts)
+            .field("languages", &self.languages)
+            .field("safe_mode", &self.safe_mode)
+            .finish()
+    }
+}
+
+impl HtmlOptions {
+    /// Options matching the plain conversion
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders math with the given renderer instead of emitting `$...$`
+    ///
+    /// The renderer replaces any [`HtmlMathMode`] set before; math it fails
+    /// to render is still written with the mode's delimiters.
+    pub fn with_math_renderer(mut self, renderer: impl MathRenderer + 'static) -> Self {
+        self.math_renderer = Some(Rc::new(renderer));
+        self
+    }
+
+    /// Writes math as `mode` says, replacing any renderer set before
+    pub fn with_math_mode(mut self, mode: HtmlMathMode) -> Self {
+        self.math_mode = mode;
+        self.math_renderer = None;
+        self
+    }
+
+    /// Renders math to MathML with the built-in [`MathMlRenderer`]
+    pub fn with_mathml(self) -> Self {
+        self.with_math_mode(HtmlMathMode::MathMl)
+    }
+
+    /// Escapes raw HTML from the document so it shows as text
+    ///
+    /// By default raw HTML blocks and tags pass through untouched, which is
+    /// only safe for trusted documents.
+    pub fn escape_raw_html(mut self) -> Self {
+        self.escape_raw_html = true;
+        self
+    }
+
+    /// Sanitizes raw HTML, embeds and URLs with `sanitizer`
+    ///
+    /// Raw HTML and pre-rendered embeds keep only allowed tags and
+    /// attributes, and link, image and figure URLs with a disallowed scheme
+    /// become `#`. Also applies to
+    /// [`Document::from_html_with`], which sanitizes the HTML before
+    /// parsing it.
+    pub fn with_sanitizer(mut self, sanitizer: HtmlSanitizer) -> Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Sanitizes with the default [`HtmlSanitizer`] allowlist
+    pub fn sanitized(self) -> Self {
+        self.with_sanitizer(HtmlSanitizer::default())
+    }
+
+    /// Rewrites link, image and figure URLs with `rewriter`
+    ///
+    /// Rewriters run in the order they were added, each on the previous
+    /// one's output. The document itself is left unchanged.
+    pub fn with_link_rewriter(mut self, rewriter: impl LinkRewriter + 'static) -> Self {
+        self.links = self.links.then(rewriter);
+        self
+    }
+
+    /// Links mentions to the URLs `resolver` returns
+    ///
+    /// Mentions the resolver returns `None` for are rendered as a plain
+    /// `<span>`, as without a resolver. Resolved URLs go through the link
+    /// rewriters and the sanitizer like any other link.
+    pub fn with_mention_resolver(mut self, resolver: impl MentionResolver + 'static) -> Self {
+        self.mentions = Some(Rc::new(resolver));
+        self
+    }
+
+    /// Links wiki-links to the URLs `resolver` returns for their targets
+    ///
+    /// Wiki-links the resolver returns `None` for are rendered as a plain
+    /// `<span>`, as without a resolver. Resolved URLs go through the link
+    /// rewriters and the sanitizer like any other link.
+    pub fn with_wiki_link_resolver(mut self, resolver: impl WikiLinkResolver + 'static) -> Self {
+        self.wiki_links = Some(Rc::new(resolver));
+        self
+    }
+
+    /// Links tags to `template` with `{tag}` replaced by the tag name, e.g.
+    /// `/tags/{tag}`
+    ///
+    /// Without a template, tags are rendered as a `<span>` carrying the
+    /// name in `data-tag`.
+    pub fn with_tag_url(mut self, template: impl Into<String>) -> Self {
+        self.tag_url = Some(template.into());
+        self
     }
 
+    /// Renders table cells holding a typed value with `locale`
+    ///
+    /// The formatted value replaces the cell's display text; cells without
+    /// a value render their content as usual.
+    pub fn with_locale(mut self, locale: impl LocaleProvider + 'static) -> Self {
+        self.locale = Some(Rc::new(locale));
+        self
+    }
+
+    /// Removes links and images whose URL `policy` doesn't allow when
+    /// importing with [`Document::from_html_with`]
+    ///
+    /// Import uses the default [`UrlPolicy`] unless one is set.
+    pub fn with_url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.url_policy = Some(policy);
+        self
+    }
+
+    /// Renders and imports documents as untrusted, removing what `mode`
+    /// doesn't allow
+    ///
+    /// Export renders a safe copy of the document and reports what was
+    /// removed in [`HtmlOutput::safety`]; [`Document::from_html_with`]
+    /// makes the parsed document safe. Use
+    /// [`Document::from_untrusted_html`] to get the import report.
+    pub fn with_safe_mode(mut self, mode: SafeMode) -> Self {
+        self.safe_mode = Some(mode);
+        self
+    }
+
+    /// Renders custom blocks and inline directives of `kind` with `renderer`
+    ///
+    /// Custom nodes of kinds without a renderer are written as their escaped
+    /// data, in a `<pre>` for blocks and a `<span>` inline.
+    pub fn with_custom_renderer(
+        mut self,
+        kind: impl Into<String>,
+        renderer: impl CustomRenderer + 'static,
+    ) -> Self {
+        self.custom_renderers.insert(kind.into(), Rc::new(renderer));
+        self
+    }
+
+    /// Renders draft blocks in a `<div data-visibility="draft">`, e.g. for
+    /// previews
+    ///
+    /// Without this, draft blocks are left out like hidden ones, which are
+    /// never rendered.
+    pub fn with_drafts(mut self) -> Self {
+        self.drafts = true;
+        self
+    }
+
+    /// Overrides the HTML of block nodes with the renderers in `registry`
+    pub fn with_renderers(mut self, registry: RendererRegistry) -> Self {
+        self.renderers = registry;
+        self
+    }
+
+    /// Wraps each heading's section as `mode` says
+    ///
+    /// Only applies to whole documents; [`HtmlRenderCache`] renders nodes
+    /// without section wrappers.
+    pub fn with_sections(mut self, mode: HtmlSectionMode) -> Self {
+        self.sections = mode;
+        self
+    }
+
+    /// Renders the sections of the headings at these top-level indices
+    /// collapsed, such as the editor's [`folded`](crate::Editor::folded)
+    /// nodes
+    ///
+    /// Headings folded in the document itself, see
+    /// [`Document::fold_section`], render collapsed too; other sections
+    /// render expanded. Indices that aren't headings are ignored.
+    pub fn with_collapsed(mut self, node_indices: impl IntoIterator<Item = usize>) -> Self {
+        self.collapsed = node_indices.into_iter().collect();
+        self
+    }
+
+    /// Marks the top-level nodes at these indices as written in another
+    /// language, with a `lang` attribute on their element
+    ///
+    /// The languages are BCP 47 tags, such as those of
+    /// `LanguageReport::node_languages` with the `language-detection`
+    /// feature.
+    pub fn with_node_languages(
+        mut self,
+        languages: impl IntoIterator<Item = (usize, String)>,
+    ) -> Self {
+        self.languages = languages.into_iter().collect();
+        self
+    }
+}
+
+/// Adds an attribute to the first element of `html`
+fn with_attribute(html: String, name: &str, value: &str) -> String {
+    if !html.starts_with('<') || html.starts_with("<!") {
+        return html;
+    }
+    let Some(end) = html.find('>') else {
+        return html;
+    };
+    let at = if html[..end].ends_with('/') {
+        end - 1
+    } else {
+        end
+    };
+    let mut tagged = html;
+    tagged.insert_str(at, &format!(" {}=\"{}\"", name, html_escape(value)));
+    tagged
+}
+
+/// The list of the cited bibliography entries appended to a document, or
+/// nothing if there are none
+fn references_section(document: &Document) -> String {
+    let references = document.references();
+    if references.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from("<section class=\"references\"><h2>References</h2><ol>");
+    for entry in references {
+        html.push_str(&format!(
+            "<li id=\"ref-{}\">{}</li>",
+            html_escape(&entry.id),
+            html_escape(&entry.reference())
+        ));
+    }
+    html.push_str("</ol></section>");
     html
 }
 
-/// Convert a node to HTML
-fn node_to_html(node: &Node, _indent: usize) -> Result<String, ParseError> {
-    match node {
-        Node::Heading { level, children } => {
-            let tag = format!("h{}", level);
-            Ok(format!("<{}>{}</{}>", tag, inlines_to_html(children), tag))
-        }
+/// Math that could not be rendered during HTML export
+///
+/// The expression is emitted as `$...$` TeX instead, so a client-side
+/// script can still pick it up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MathDiagnostic {
+    /// Index of the top-level node containing the math
+    pub node_index: usize,
+    /// The TeX source
+    pub tex: String,
+    /// Whether the math is a block rather than inline
+    pub display: bool,
+    /// Why rendering failed
+    pub message: String,
+}
 
-        Node::Paragraph { children } => Ok(format!("<p>{}</p>", inlines_to_html(children))),
+/// HTML produced by [`Document::to_html_with`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HtmlOutput {
+    /// The HTML
+    pub html: String,
+    /// Problems found while rendering, in document order
+    pub diagnostics: Vec<MathDiagnostic>,
+    /// Content removed by [`HtmlOptions::with_safe_mode`]
+    pub safety: SafetyReport,
+}
 
-        Node::List { list_type, items } => {
-            let tag = match list_type {
-                ListType::Ordered => "ol",
-                ListType::Unordered => "ul",
-                ListType::Task => "ul class=\"task-list\"",
-            };
+impl Document {
+    /// Parses HTML with the given options
+    ///
+    /// Only the sanitizer and the URL policy apply to import; the other
+    /// options are ignored.
+    pub fn from_html_with(html: &str, options: &HtmlOptions) -> Result<Document, ParseError> {
+        let default_policy = UrlPolicy::default();
+        let policy = options.url_policy.as_ref().unwrap_or(&default_policy);
+        let mut document = match &options.sanitizer {
+            Some(sanitizer) => parse_html(&sanitizer.sanitize(html), policy)?,
+            None => parse_html(html, policy)?,
+        };
+        if let Some(mode) = &options.safe_mode {
+            document.make_safe(mode);
+        }
+        Ok(document)
+    }
+
+    /// Converts the document to HTML with the given options
+    pub fn to_html_with(&self, options: &HtmlOptions) -> HtmlOutput {
+        let mut writer = HtmlWriter::new(options);
+        let (html, safety) = match &options.safe_mode {
+            Some(mode) => {
+                let mut document = self.clone();
+                let safety = document.make_safe(mode);
+                (writer.document(&document), safety)
+            }
+            None => (writer.document(self), SafetyReport::default()),
+        };
+        HtmlOutput {
+            html,
+            diagnostics: writer.diagnostics,
+            safety,
+        }
+    }
+}
 
-            let mut html = format!("<{}>", tag);
+/// HTML of one top-level node, as returned by [`HtmlRenderCache::render`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderedNode {
+    /// Identifier derived from the node's content, unique within a render
+    ///
+    /// Unchanged nodes keep their identifier across renders, so a preview
+    /// can patch only the elements whose identifier changed.
+    pub id: String,
+    /// The node's HTML
+    pub html: String,
+}
 
-            for item in items {
-                let checked_attr = if let Some(checked) = item.checked {
-                    if checked { " checked" } else { "" }
-                } else {
-                    ""
-                };
+/// Per-node HTML cache for live previews
+///
+/// Rendering a large document after every keystroke repeats work for all
+/// the nodes that didn't change. The cache keys each top-level node's HTML
+/// by a hash of its content, so [`render`](Self::render) only renders nodes
+/// it hasn't seen. Entries not used by the latest render are dropped.
+///
+/// Math diagnostics are not collected; use [`Document::to_html_with`] for
+/// those.
+#[derive(Debug, Clone)]
+pub struct HtmlRenderCache {
+    options: HtmlOptions,
+    entries: HashMap<u64, String>,
+    last_rendered: usize,
+}
 
-                let checkbox = if item.checked.is_some() {
-                    format!("<input type=\"checkbox\" {}> ", checked_attr)
-                } else {
-                    String::new()
-                };
+impl HtmlRenderCache {
+    /// Creates an empty cache rendering with the given options
+    pub fn new(options: HtmlOptions) -> Self {
+        Self {
+            options,
+            entries: HashMap::new(),
+            last_rendered: 0,
+        }
+    }
 
-                let mut item_html = String::new();
-                if !item.children.is_empty() {
-                    if let Node::Paragraph { children } = &item.children[0] {
-                        // If the first child is a paragraph, integrate the checkbox
-                        let para_content = inlines_to_html(children);
-                        item_html.push_str(&format!("<p>{}{}</p>", checkbox, para_content));
+    /// Renders the document's top-level nodes in order, reusing cached HTML
+    pub fn render(&mut self, document: &Document) -> Vec<RenderedNode> {
+        let mut writer = HtmlWriter::new(&self.options);
+        writer.set_document(document);
+        let mut anchors = document.heading_anchors().into_iter().peekable();
+        let mut entries = HashMap::with_capacity(document.nodes.len());
+        let mut seen: HashMap<u64, usize> = HashMap::new();
+        let bibliography = (!document.bibliography.is_empty())
+            .then(|| fnv1a(format!("{:?}", document.bibliography).as_bytes()));
+        self.last_rendered = 0;
+
+        let mut rendered = Vec::with_capacity(document.nodes.len());
+        for (index, node) in document.nodes.iter().enumerate() {
+            let anchor = anchors.next_if(|(i, _)| *i == index).map(|(_, a)| a);
+            // A heading's id depends on the headings before it, so it is
+            // part of the key
+            let key = match (&anchor, node) {
+                (Some(anchor), _) => {
+                    fnv1a(format!("{:x}#{}", node.content_hash(), anchor).as_bytes())
+                }
+                // So is a table of contents' list
+                (None, Node::TableOfContents { max_level }) => {
+                    let list = writer.toc_list(*max_level);
+                    let list_hash = list.map_or(0, |list| list.content_hash());
+                    fnv1a(format!("{:x}#{:x}", node.content_hash(), list_hash).as_bytes())
+                }
+                (None, _) => node.content_hash(),
+            };
+            // And a node's attributes, and the bibliography citations are
+            // labelled from
+            let key = match &bibliography {
+                Some(bibliography) => fnv1a(format!("{:x}#{:x}", key, bibliography).as_bytes()),
+                None => key,
+            };
+            let key = match document.attributes_of(index) {
+                Some(attributes) => fnv1a(format!("{:x}{:?}", key, attributes).as_bytes()),
+                None => key,
+            };
 
-                        // Add the rest of the children normally
-                        for child in &item.children[1..] {
-                            item_html.push_str(&node_to_html(child, 0)?);
-                        }
-                    } else {
-                        // If the first child is not a paragraph, add checkbox first (if task list) then content
-                        item_html.push_str(&checkbox);
-                        for child in &item.children {
-                            item_html.push_str(&node_to_html(child, 0)?);
-                        }
+            let html = match self
+                .entries
+                .remove(&key)
+                .or_else(|| entries.get(&key).cloned())
+            {
+                Some(html) => html,
+                None => {
+                    self.last_rendered += 1;
+                    match &self.options.safe_mode {
+                        Some(mode) => mode
+                            .node(node.clone(), index, &mut SafetyReport::default())
+                            .map_or_else(String::new, |node| {
+                                writer.top_level(index, &node, anchor.as_deref())
+                            }),
+                        None => writer.top_level(index, node, anchor.as_deref()),
                     }
-                } else {
-                    // Handle empty list items, potentially with just a checkbox
-                    item_html.push_str(&checkbox);
                 }
+            };
+            entries.insert(key, html.clone());
 
-                html.push_str(&format!("<li>{}</li>", item_html));
-            }
-
-            html.push_str(&format!("</{}>", tag.split(' ').next().unwrap_or(tag))); // Close using base tag (e.g., ul, ol)
-            Ok(html)
+            let occurrence = seen.entry(key).or_default();
+            let id = match *occurrence {
+                0 => format!("{:016x}", key),
+                n => format!("{:016x}-{}", key, n),
+            };
+            *occurrence += 1;
+            rendered.push(RenderedNode { id, html });
         }
 
-        Node::CodeBlock {
-            language,
-            code,
-            properties,
-        } => {
-            let mut classes = Vec::new();
+        self.entries = entries;
+        rendered
+    }
 
-            // Add language class
-            if !language.is_empty() {
-                classes.push(format!("language-{}", language));
-            }
+    /// Number of nodes the last [`render`](Self::render) had to render
+    pub fn last_rendered(&self) -> usize {
+        self.last_rendered
+    }
 
-            // Add custom class if specified
-            if let Some(css_class) = &properties.css_class {
-                classes.push(css_class.clone());
-            }
+    /// Drops all cached HTML
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
 
-            // Add line-numbers class if enabled
-            if properties.show_line_numbers {
-                classes.push("line-numbers".to_string());
-            }
+/// Renders nodes under a set of options, collecting diagnostics
+struct HtmlWriter<'a> {
+    options: &'a HtmlOptions,
+    /// Index of the top-level node being rendered
+    node_index: usize,
+    diagnostics: Vec<MathDiagnostic>,
+    /// Tracked changes in the inline content about to be rendered
+    changes: Vec<TrackedChange>,
+    /// Outline of the document being rendered, if it has a table of
+    /// contents to expand
+    outline: Option<Outline>,
+    /// Attributes of the top-level nodes of the document being rendered
+    attributes: BTreeMap<usize, NodeAttributes>,
+    /// Bibliography of the document being rendered, to label citations
+    bibliography: Vec<BibliographyEntry>,
+}
 
-            // Create the class attribute if we have classes
-            let class_attr = if !classes.is_empty() {
-                format!(" class=\"{}\"", classes.join(" "))
-            } else {
-                String::new()
-            };
+impl<'a> HtmlWriter<'a> {
+    fn new(options: &'a HtmlOptions) -> Self {
+        Self {
+            options,
+            node_index: 0,
+            diagnostics: Vec::new(),
+            changes: Vec::new(),
+            outline: None,
+            attributes: BTreeMap::new(),
+            bibliography: Vec::new(),
+        }
+    }
 
-            // Build additional data attributes
-            let mut data_attrs = Vec::new();
+    /// Keep the node attributes and bibliography of `document`, and its
+    /// outline if it has a top-level table of contents
+    fn set_document(&mut self, document: &Document) {
+        self.attributes = document.node_attributes.clone();
+        self.bibliography = document.bibliography.clone();
+        self.outline = document
+            .nodes
+            .iter()
+            .any(|node| matches!(node, Node::TableOfContents { .. }))
+            .then(|| document.outline());
+    }
 
-            // Add line numbering start attribute if showing line numbers
-            if properties.show_line_numbers && properties.start_line > 1 {
-                data_attrs.push(format!("data-start=\"{}\"", properties.start_line));
+    /// The list a table of contents expands to
+    fn toc_list(&self, max_level: u8) -> Option<Node> {
+        toc::entries_list(&self.outline.as_ref()?.entries, max_level)
+    }
+
+    /// Convert a document to HTML
+    fn document(&mut self, document: &Document) -> String {
+        self.set_document(document);
+        let mut html = String::new();
+        let mut anchors = document.heading_anchors().into_iter().peekable();
+        let sections = self.options.sections;
+        // Levels of the headings whose sections are open
+        let mut open: Vec<u8> = Vec::new();
+
+        for (index, node) in document.nodes.iter().enumerate() {
+            let anchor = anchors.next_if(|(i, _)| *i == index).map(|(_, a)| a);
+            let marked = self.mark_changes(document, index, node);
+            let rendered =
+                self.top_level(index, marked.as_ref().unwrap_or(node), anchor.as_deref());
+            self.changes.clear();
+            let Node::Heading { level, .. } = node else {
+                html.push_str(&rendered);
+                continue;
+            };
+            if sections == HtmlSectionMode::Flat {
+                html.push_str(&rendered);
+                continue;
             }
 
-            // Add theme if specified
-            if let Some(theme) = &properties.theme {
-                data_attrs.push(format!("data-theme=\"{}\"", theme));
+            while open.last().is_some_and(|open_level| open_level >= level) {
+                open.pop();
+                html.push_str(section_end(sections));
             }
+            open.push(*level);
+            let collapsed =
+                self.options.collapsed.contains(&index) || document.is_section_folded(index);
+            html.push_str(&match sections {
+                HtmlSectionMode::Details => format!(
+                    "<details{}><summary>{}</summary>",
+                    if collapsed { "" } else { " open" },
+                    rendered
+                ),
+                _ => format!(
+                    "<section data-level=\"{}\" data-collapsed=\"{}\">{}",
+                    level, collapsed, rendered
+                ),
+            });
+        }
+        for _ in open {
+            html.push_str(section_end(sections));
+        }
+        html.push_str(&references_section(document));
 
-            // Add line highlighting if specified
-            if let Some(highlight_lines) = &properties.highlight_lines {
-                let line_numbers = highlight_lines
-                    .iter()
-                    .map(|line| line.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",");
+        html
+    }
 
-                data_attrs.push(format!("data-line=\"{}\"", line_numbers));
+    /// Prepare the tracked changes of a top-level paragraph or heading to be
+    /// rendered, returning a copy of the node with its text split where
+    /// changes start and end
+    fn mark_changes(&mut self, document: &Document, index: usize, node: &Node) -> Option<Node> {
+        let changes = document.tracked_changes_in(index);
+        if changes.is_empty() {
+            return None;
+        }
+        let mut node = node.clone();
+        let (Node::Paragraph { children } | Node::Heading { children, .. }) = &mut node else {
+            return None;
+        };
+        for change in &changes {
+            split_inlines_at(children, change.range.start);
+            split_inlines_at(children, change.range.end);
+        }
+        self.changes = changes.into_iter().cloned().collect();
+        Some(node)
+    }
+
+    /// Convert a top-level node to HTML, giving a heading its anchor as `id`
+    fn top_level(&mut self, index: usize, node: &Node, anchor: Option<&str>) -> String {
+        self.node_index = index;
+        let result = match node {
+            Node::Heading {
+                level,
+                children,
+                properties,
+            } => {
+                let html = self.heading(*level, children, properties, anchor);
+                Ok(self.options.renderers.render(node, html))
             }
+            _ => self.node(node),
+        };
+        let html = result.unwrap_or_else(|err| {
+            eprintln!("Error converting node to HTML: {}", err);
+            String::new()
+        });
+        let html = match self.options.languages.get(&index) {
+            Some(language) => with_attribute(html, "lang", language),
+            None => html,
+        };
+        let attributes = self.attributes.get(&index).into_iter().flatten();
+        attributes.fold(
+            html,
+            |html, (name, value)| match attributes::data_attribute_name(name) {
+                Some(name) => with_attribute(html, &name, value),
+                None => html,
+            },
+        )
+    }
 
-            // Add copy button attribute
-            data_attrs.push(format!(
-                "data-copy-button=\"{}\"",
-                properties.show_copy_button
-            ));
+    /// Apply the configured link rewriters to a URL, then the sanitizer
+    fn url(&self, url: &str) -> String {
+        let url = self
+            .options
+            .links
+            .rewrite(url)
+            .unwrap_or_else(|| url.to_string());
+        match &self.options.sanitizer {
+            Some(sanitizer) if !sanitizer.allows_url(&url) => "#".to_string(),
+            _ => url,
+        }
+    }
 
-            // Create the data attributes string
-            let data_attrs_str = if !data_attrs.is_empty() {
-                format!(" {}", data_attrs.join(" "))
-            } else {
-                String::new()
-            };
+    /// Pass raw HTML through, or escape or sanitize it if configured to
+    fn raw_html(&self, html: &str) -> String {
+        if self.options.escape_raw_html {
+            return html_escape(html);
+        }
+        match &self.options.sanitizer {
+            Some(sanitizer) => sanitizer.sanitize(html),
+            None => html.to_string(),
+        }
+    }
 
-            // Add style attribute if specified
-            let style_attr = if let Some(style) = &properties.style {
-                format!(" style=\"{}\"", style)
-            } else {
-                String::new()
-            };
+    /// Render a notebook cell output: text in a `<pre>`, images inline and
+    /// HTML as raw HTML
+    fn code_output(&self, output: &CodeOutput) -> String {
+        let kind = match output.kind {
+            CodeOutputKind::Stdout => "stdout",
+            CodeOutputKind::Stderr => "stderr",
+            CodeOutputKind::Result => "result",
+            CodeOutputKind::Display => "display",
+            CodeOutputKind::Error => "error",
+        };
+        let body = match output.mime_type.as_str() {
+            _ if output.is_binary() => format!(
+                "<img src=\"data:{};base64,{}\" alt=\"\">",
+                html_escape(&output.mime_type),
+                html_escape(&output.data)
+            ),
+            "text/html" | "image/svg+xml" => self.raw_html(&output.data),
+            _ => format!("<pre>{}</pre>", html_escape(&output.data)),
+        };
+        format!("<div class=\"code-output code-output-{kind}\">{body}</div>")
+    }
 
-            // Add max-height style if specified
-            let container_style = if let Some(max_height) = &properties.max_height {
-                format!(" style=\"max-height:{}; overflow:auto;\"", max_height)
-            } else {
-                String::new()
-            };
+    /// Render an HTML comment, left out where raw HTML is escaped or
+    /// sanitized
+    fn html_comment(&self, text: &str) -> String {
+        if self.options.escape_raw_html || self.options.sanitizer.is_some() {
+            return String::new();
+        }
+        format!("<!--{}-->", text.replace("-->", "--&gt;"))
+    }
 
-            // Generate pre and code tags with attributes
-            let html = if properties.max_height.is_some() {
-                format!(
-                    "<div class=\"code-container\"{container_style}><pre{style_attr}><code{class_attr}{data_attrs_str}>{}</code></pre></div>",
-                    html_escape(code)
-                )
-            } else {
-                format!(
-                    "<pre{style_attr}><code{class_attr}{data_attrs_str}>{}</code></pre>",
-                    html_escape(code)
-                )
-            };
+    /// Render math with the configured renderer or math mode, falling back
+    /// to delimited TeX
+    fn math(&mut self, tex: &str, display: bool) -> String {
+        let rendered = match (&self.options.math_renderer, self.options.math_mode) {
+            (Some(renderer), _) => Some(renderer.render(tex, display)),
+            (None, HtmlMathMode::MathMl) => Some(MathMlRenderer.render(tex, display)),
+            #[cfg(feature = "katex")]
+            (None, HtmlMathMode::Katex) => Some(KatexRenderer.render(tex, display)),
+            _ => None,
+        };
+        match rendered {
+            Some(Ok(html)) => return html,
+            Some(Err(message)) => self.diagnostics.push(MathDiagnostic {
+                node_index: self.node_index,
+                tex: tex.to_string(),
+                display,
+                message,
+            }),
+            None => {}
+        }
 
-            Ok(html)
+        let tex = html_escape(tex);
+        match (self.options.math_mode, display) {
+            (HtmlMathMode::MathJax, false) => format!("\\({tex}\\)"),
+            (HtmlMathMode::MathJax, true) => format!("\\[{tex}\\]"),
+            _ => format!("${tex}$"),
         }
+    }
 
-        Node::BlockQuote { children } => {
-            let mut html = String::from("<blockquote>");
-            for child in children {
-                html.push_str(&node_to_html(child, 0)?);
-            }
-            html.push_str("</blockquote>");
-            Ok(html)
+    /// Render a custom node with the renderer registered for its kind, or
+    /// as its escaped data
+    fn custom(&self, kind: &str, data: &str, inline: bool) -> String {
+        if let Some(renderer) = self.options.custom_renderers.get(kind) {
+            return renderer.render(data, inline);
         }
+        let (tag, body) = match inline {
+            true => ("span", html_escape(data)),
+            false => ("pre", format!("<code>{}</code>", html_escape(data))),
+        };
+        format!(
+            "<{tag} class=\"custom\" data-kind=\"{}\">{body}</{tag}>",
+            html_escape(kind)
+        )
+    }
+}
 
-        Node::ThematicBreak => Ok(String::from("<hr>")),
+/// Closing tag of a section wrapper
+fn section_end(mode: HtmlSectionMode) -> &'static str {
+    match mode {
+        HtmlSectionMode::Details => "</details>",
+        _ => "</section>",
+    }
+}
 
-        Node::Group { name, children } => {
-            let mut html = format!("<div class=\"group\" data-name=\"{}\">", html_escape(name));
-            for child in children {
-                html.push_str(&node_to_html(child, 0)?);
-            }
-            html.push_str("</div>");
-            Ok(html)
+/// Highlighted HTML for a code block, if the language is known to the highlighter
+#[cfg(feature = "syntax-highlighting")]
+fn code_block_body(code: &str, language: &str, properties: &CodeBlockProperties) -> Option<String> {
+    super::highlight::highlight_code(code, language, properties.theme.as_deref())
+}
+
+#[cfg(not(feature = "syntax-highlighting"))]
+fn code_block_body(
+    _code: &str,
+    _language: &str,
+    _properties: &CodeBlockProperties,
+) -> Option<String> {
+    None
+}
+
+/// Convert a node to HTML with the default options
+pub(crate) fn node_to_html(node: &Node, _indent: usize) -> Result<String, ParseError> {
+    HtmlWriter::new(&HtmlOptions::default()).node(node)
+}
+
+impl HtmlWriter<'_> {
+    /// Convert a heading to HTML, with an `id` for top-level headings
+    fn heading(
+        &mut self,
+        level: u8,
+        children: &[InlineNode],
+        properties: &HeadingProperties,
+        id: Option<&str>,
+    ) -> String {
+        let tag = format!("h{}", level);
+        let mut attrs = String::new();
+        if let Some(id) = id {
+            attrs.push_str(&format!(" id=\"{}\"", html_escape(id)));
+        }
+        if let Some(target) = properties.word_target {
+            attrs.push_str(&format!(" data-word-target=\"{}\"", target));
         }
+        format!("<{}{}>{}</{}>", tag, attrs, self.inlines(children), tag)
+    }
 
-        Node::Table {
-            header,
-            rows,
-            alignments,
-            properties,
-        } => {
-            let mut html = String::new();
-
-            // Apply table classes based on properties
-            let mut table_classes = Vec::new();
-            if properties.has_borders {
-                table_classes.push("bordered");
-            }
-            if properties.striped_rows {
-                table_classes.push("striped");
-            }
-            if properties.hoverable {
-                table_classes.push("hoverable");
-            }
-            if let Some(css_class) = &properties.css_class {
-                table_classes.push(css_class);
-            }
+    /// Convert a node to HTML, applying the registered node renderers
+    fn node(&mut self, node: &Node) -> Result<String, ParseError> {
+        let html = self.builtin_node(node)?;
+        Ok(self.options.renderers.render(node, html))
+    }
 
-            // Start table tag with classes and style
-            html.push_str("<table");
-            if !table_classes.is_empty() {
-                html.push_str(" class=\"");
-                html.push_str(&table_classes.join(" "));
-                html.push('"');
-            }
-            if let Some(style) = &properties.style {
-                html.push_str(" style=\"");
-                html.push_str(style);
-                html.push('"');
-            }
-            html.push_str(">\n");
+    /// Convert a node to HTML with the built-in markup
+    fn builtin_node(&mut self, node: &Node) -> Result<String, ParseError> {
+        match node {
+            Node::Heading {
+                level,
+                children,
+                properties,
+            } => Ok(self.heading(*level, children, properties, None)),
 
-            // Add caption if present
-            if let Some(caption) = &properties.caption {
-                if !properties.caption_at_bottom {
-                    html.push_str(&format!("<caption>{}</caption>\n", html_escape(caption)));
-                }
-            }
+            Node::Paragraph { children } => Ok(format!("<p>{}</p>", self.inlines(children))),
 
-            // Table header
-            if !header.is_empty() && properties.has_header {
-                html.push_str("<thead>\n<tr>");
-
-                for (i, cell) in header.iter().enumerate() {
-                    let alignment = if i < alignments.len() {
-                        match &alignments[i] {
-                            TableAlignment::Left => " class=\"align-left\"",
-                            TableAlignment::Center => " class=\"align-center\"",
-                            TableAlignment::Right => " class=\"align-right\"",
-                            TableAlignment::Justify => " class=\"align-justify\"",
-                            TableAlignment::None => "",
-                            _ => "", // Vertical alignments don't apply to horizontal text alignment
-                        }
+            Node::List {
+                list_type,
+                items,
+                start,
+                loose,
+            } => {
+                // Paragraphs of tight lists go without `<p>`, as in CommonMark
+                let paragraph = |content: String| match loose {
+                    true => format!("<p>{}</p>", content),
+                    false => content,
+                };
+                let tag = match list_type {
+                    ListType::Ordered => "ol",
+                    ListType::Unordered => "ul",
+                    ListType::Task => "ul class=\"task-list\"",
+                };
+
+                let mut html = match (list_type, start) {
+                    (ListType::Ordered, Some(start)) => format!("<{} start=\"{}\">", tag, start),
+                    _ => format!("<{}>", tag),
+                };
+
+                for item in items {
+                    let checked_attr = if let Some(checked) = item.checked {
+                        if checked { " checked" } else { "" }
                     } else {
                         ""
                     };
 
-                    // Cell opening tag with attributes
-                    html.push_str("<th");
-                    html.push_str(alignment);
+                    let checkbox = if item.checked.is_some() {
+                        format!("<input type=\"checkbox\" {}> ", checked_attr)
+                    } else {
+                        String::new()
+                    };
 
-                    // Add colspan if greater than 1
-                    if cell.colspan > 1 {
-                        html.push_str(&format!(" colspan=\"{}\"", cell.colspan));
+                    let mut item_html = String::new();
+                    if !item.children.is_empty() {
+                        if let Node::Paragraph { children } = &item.children[0] {
+                            // If the first child is a paragraph, integrate the checkbox
+                            let para_content = self.inlines(children);
+                            item_html.push_str(&paragraph(format!("{}{}", checkbox, para_content)));
+
+                            // Add the rest of the children normally
+                            for child in &item.children[1..] {
+                                match child {
+                                    Node::Paragraph { children } => {
+                                        item_html.push_str(&paragraph(self.inlines(children)))
+                                    }
+                                    _ => item_html.push_str(&self.node(child)?),
+                                }
+                            }
+                        } else {
+                            // If the first child is not a paragraph, add checkbox first (if task list) then content
+                            item_html.push_str(&checkbox);
+                            for child in &item.children {
+                                item_html.push_str(&self.node(child)?);
+                            }
+                        }
+                    } else {
+                        // Handle empty list items, potentially with just a checkbox
+                        item_html.push_str(&checkbox);
                     }
 
-                    // Add rowspan if greater than 1
-                    if cell.rowspan > 1 {
-                        html.push_str(&format!(" rowspan=\"{}\"", cell.rowspan));
-                    }
+                    html.push_str(&format!("<li>{}</li>", item_html));
+                }
 
-                    // Add background color if present
-                    if let Some(bg_color) = &cell.background_color {
-                        html.push_str(&format!(" style=\"background-color: {}\"", bg_color));
-                    }
+                html.push_str(&format!("</{}>", tag.split(' ').next().unwrap_or(tag))); // Close using base tag (e.g., ul, ol)
+                Ok(html)
+            }
 
-                    // Add CSS class if present
-                    if let Some(css_class) = &cell.css_class {
-                        html.push_str(&format!(" class=\"{}\"", css_class));
-                    }
+            Node::CodeBlock {
+                language,
+                code,
+                properties,
+            } => {
+                let mut classes = Vec::new();
 
-                    // Add custom style if present
-                    if let Some(style) = &cell.style {
-                        if cell.background_color.is_some() {
-                            html.push_str(&format!("; {}", style));
-                        } else {
-                            html.push_str(&format!(" style=\"{}\"", style));
-                        }
-                    }
+                // Add language class
+                if !language.is_empty() {
+                    classes.push(format!("language-{}", language));
+                }
+
+                // Add custom class if specified
+                if let Some(css_class) = &properties.css_class {
+                    classes.push(css_class.clone());
+                }
+
+                // Add line-numbers class if enabled
+                if properties.show_line_numbers {
+                    classes.push("line-numbers".to_string());
+                }
+
+                // Create the class attribute if we have classes
+                let class_attr = if !classes.is_empty() {
+                    format!(" class=\"{}\"", classes.join(" "))
+                } else {
+                    String::new()
+                };
+
+                // Build additional data attributes
+                let mut data_attrs = Vec::new();
+
+                // Add line numbering start attribute if showing line numbers
+                if properties.show_line_numbers && properties.start_line > 1 {
+                    data_attrs.push(format!("data-start=\"{}\"", properties.start_line));
+                }
+
+                // Add theme if specified
+                if let Some(theme) = &properties.theme {
+                    data_attrs.push(format!("data-theme=\"{}\"", theme));
+                }
+
+                // Add line highlighting if specified
+                if let Some(highlight_lines) = &properties.highlight_lines {
+                    let line_numbers = highlight_lines
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",");
+
+                    data_attrs.push(format!("data-line=\"{}\"", line_numbers));
+                }
+
+                // Add the notebook execution count if set
+                if let Some(count) = properties.execution_count {
+                    data_attrs.push(format!("data-execution-count=\"{}\"", count));
+                }
+
+                // Add copy button attribute
+                data_attrs.push(format!(
+                    "data-copy-button=\"{}\"",
+                    properties.show_copy_button
+                ));
+
+                // Create the data attributes string
+                let data_attrs_str = if !data_attrs.is_empty() {
+                    format!(" {}", data_attrs.join(" "))
+                } else {
+                    String::new()
+                };
+
+                // Add style attribute if specified
+                let style_attr = if let Some(style) = &properties.style {
+                    format!(" style=\"{}\"", style)
+                } else {
+                    String::new()
+                };
 
-                    html.push('>');
+                // Add max-height style if specified
+                let container_style = if let Some(max_height) = &properties.max_height {
+                    format!(" style=\"max-height:{}; overflow:auto;\"", max_height)
+                } else {
+                    String::new()
+                };
 
-                    // Cell content
-                    for inline in &cell.content {
-                        html.push_str(&inline_node_to_html(inline)?);
+                // Pre-highlight the code when built with server-side highlighting
+                let (body, highlighted_attr) = match code_block_body(code, language, properties) {
+                    Some(body) => (body, " data-highlighted=\"true\""),
+                    None => (html_escape(code), ""),
+                };
+
+                // Generate pre and code tags with attributes
+                let mut html = if properties.max_height.is_some() {
+                    format!(
+                        "<div class=\"code-container\"{container_style}><pre{style_attr}><code{class_attr}{data_attrs_str}{highlighted_attr}>{body}</code></pre></div>",
+                    )
+                } else {
+                    format!(
+                        "<pre{style_attr}><code{class_attr}{data_attrs_str}{highlighted_attr}>{body}</code></pre>",
+                    )
+                };
+                if !properties.outputs.is_empty() {
+                    html.push_str("<div class=\"code-outputs\">");
+                    for output in &properties.outputs {
+                        html.push_str(&self.code_output(output));
                     }
+                    html.push_str("</div>");
+                }
+
+                Ok(html)
+            }
 
-                    html.push_str("</th>");
+            Node::BlockQuote { children } => {
+                let mut html = String::from("<blockquote>");
+                for child in children {
+                    html.push_str(&self.node(child)?);
                 }
+                html.push_str("</blockquote>");
+                Ok(html)
+            }
+
+            Node::ThematicBreak => Ok(String::from("<hr>")),
 
-                html.push_str("</tr>\n</thead>\n");
+            Node::Group { name, children } => {
+                let mut html = format!("<div class=\"group\" data-name=\"{}\">", html_escape(name));
+                for child in children {
+                    html.push_str(&self.node(child)?);
+                }
+                html.push_str("</div>");
+                Ok(html)
             }
 
-            // Table rows
-            if !rows.is_empty() {
-                html.push_str("<tbody>\n");
+            Node::Table {
+                header,
+                rows,
+                alignments,
+                properties,
+            } => {
+                let mut html = String::new();
 
-                for row in rows {
-                    html.push_str("<tr>");
+                // Apply table classes based on properties
+                let mut table_classes = Vec::new();
+                if properties.has_borders {
+                    table_classes.push("bordered");
+                }
+                if properties.striped_rows {
+                    table_classes.push("striped");
+                }
+                if properties.hoverable {
+                    table_classes.push("hoverable");
+                }
+                if let Some(css_class) = &properties.css_class {
+                    table_classes.push(css_class);
+                }
+
+                // Start table tag with classes and style
+                html.push_str("<table");
+                if !table_classes.is_empty() {
+                    html.push_str(" class=\"");
+                    html.push_str(&table_classes.join(" "));
+                    html.push('"');
+                }
+                if let Some(style) = &properties.style {
+                    html.push_str(" style=\"");
+                    html.push_str(style);
+                    html.push('"');
+                }
+                if let Some(aria_label) = &properties.aria_label {
+                    html.push_str(&format!(" aria-label=\"{}\"", html_escape(aria_label)));
+                }
+                if let Some(summary) = &properties.summary {
+                    html.push_str(&format!(" aria-description=\"{}\"", html_escape(summary)));
+                }
+                html.push_str(">\n");
+
+                // Add caption if present
+                if let Some(caption) = &properties.caption
+                    && !properties.caption_at_bottom
+                {
+                    html.push_str(&format!("<caption>{}</caption>\n", html_escape(caption)));
+                }
+
+                // Table header
+                if !header.is_empty() && properties.has_header {
+                    html.push_str("<thead>\n<tr>");
 
-                    for (i, cell) in row.iter().enumerate() {
+                    let columns = table_cell_columns(std::slice::from_ref(header)).remove(0);
+                    for (cell, i) in header.iter().zip(columns) {
                         let alignment = if i < alignments.len() {
                             match &alignments[i] {
                                 TableAlignment::Left => " class=\"align-left\"",
@@ -341,11 +1123,8 @@ fn node_to_html(node: &Node, _indent: usize) -> Result<String, ParseError> {
                             ""
                         };
 
-                        // Determine if this is a header or data cell
-                        let tag = if cell.is_header { "th" } else { "td" };
-
                         // Cell opening tag with attributes
-                        html.push_str(&format!("<{}", tag));
+                        html.push_str("<th");
                         html.push_str(alignment);
 
                         // Add colspan if greater than 1
@@ -380,256 +1159,600 @@ fn node_to_html(node: &Node, _indent: usize) -> Result<String, ParseError> {
                         html.push('>');
 
                         // Cell content
-                        for inline in &cell.content {
-                            html.push_str(&inline_node_to_html(inline)?);
+                        html.push_str(&self.cell_content(cell)?);
+
+                        html.push_str("</th>");
+                    }
+
+                    html.push_str("</tr>\n</thead>\n");
+                }
+
+                // Table rows
+                if !rows.is_empty() {
+                    html.push_str("<tbody>\n");
+
+                    // Align merged cells by the grid column they start in
+                    let columns = table_cell_columns(rows);
+
+                    for (row, row_columns) in rows.iter().zip(&columns) {
+                        html.push_str("<tr>");
+
+                        for (cell, &i) in row.iter().zip(row_columns) {
+                            let alignment = if i < alignments.len() {
+                                match &alignments[i] {
+                                    TableAlignment::Left => " class=\"align-left\"",
+                                    TableAlignment::Center => " class=\"align-center\"",
+                                    TableAlignment::Right => " class=\"align-right\"",
+                                    TableAlignment::Justify => " class=\"align-justify\"",
+                                    TableAlignment::None => "",
+                                    _ => "", // Vertical alignments don't apply to horizontal text alignment
+                                }
+                            } else {
+                                ""
+                            };
+
+                            // Determine if this is a header or data cell
+                            let tag = if cell.is_header { "th" } else { "td" };
+
+                            // Cell opening tag with attributes
+                            html.push_str(&format!("<{}", tag));
+                            html.push_str(alignment);
+
+                            // Add colspan if greater than 1
+                            if cell.colspan > 1 {
+                                html.push_str(&format!(" colspan=\"{}\"", cell.colspan));
+                            }
+
+                            // Add rowspan if greater than 1
+                            if cell.rowspan > 1 {
+                                html.push_str(&format!(" rowspan=\"{}\"", cell.rowspan));
+                            }
+
+                            // Add background color if present
+                            if let Some(bg_color) = &cell.background_color {
+                                html.push_str(&format!(
+                                    " style=\"background-color: {}\"",
+                                    bg_color
+                                ));
+                            }
+
+                            // Add CSS class if present
+                            if let Some(css_class) = &cell.css_class {
+                                html.push_str(&format!(" class=\"{}\"", css_class));
+                            }
+
+                            // Add custom style if present
+                            if let Some(style) = &cell.style {
+                                if cell.background_color.is_some() {
+                                    html.push_str(&format!("; {}", style));
+                                } else {
+                                    html.push_str(&format!(" style=\"{}\"", style));
+                                }
+                            }
+
+                            html.push('>');
+
+                            // Cell content
+                            html.push_str(&self.cell_content(cell)?);
+
+                            html.push_str(&format!("</{}>", tag));
                         }
 
-                        html.push_str(&format!("</{}>", tag));
+                        html.push_str("</tr>\n");
                     }
 
-                    html.push_str("</tr>\n");
+                    html.push_str("</tbody>\n");
+                }
+
+                // Add caption at bottom if specified
+                if let Some(caption) = &properties.caption
+                    && properties.caption_at_bottom
+                {
+                    html.push_str(&format!("<caption>{}</caption>\n", html_escape(caption)));
                 }
 
-                html.push_str("</tbody>\n");
+                html.push_str("</table>");
+                Ok(html)
             }
 
-            // Add caption at bottom if specified
-            if let Some(caption) = &properties.caption {
-                if properties.caption_at_bottom {
-                    html.push_str(&format!("<caption>{}</caption>\n", html_escape(caption)));
+            Node::FootnoteReference(footnote_ref) => {
+                let id = footnote_ref
+                    .identifier
+                    .as_ref()
+                    .unwrap_or(&footnote_ref.label);
+                Ok(format!(
+                    "<sup class=\"footnote-ref\"><a href=\"#fn-{}\" id=\"fnref-{}\">{}</a></sup>",
+                    html_escape(id),
+                    html_escape(id),
+                    html_escape(&footnote_ref.label)
+                ))
+            }
+
+            Node::FootnoteDefinition(footnote_def) => {
+                let mut html = format!(
+                    "<div class=\"footnote\" id=\"fn-{}\">\n<p>{}: ",
+                    html_escape(&footnote_def.label),
+                    html_escape(&footnote_def.label)
+                );
+
+                for child in &footnote_def.content {
+                    html.push_str(&self.node(child)?);
                 }
+
+                // Add backlink if needed, depends on specific requirements
+                // html.push_str(&format!(" <a href="#fnref-{}" class="footnote-backref">↩</a>", html_escape(&footnote_def.label)));
+
+                html.push_str("</p>\n</div>");
+                Ok(html)
             }
 
-            html.push_str("</table>");
-            Ok(html)
-        }
+            Node::DefinitionList { items } => {
+                let mut html = String::from("<dl>");
 
-        Node::FootnoteReference(footnote_ref) => {
-            let id = footnote_ref
-                .identifier
-                .as_ref()
-                .unwrap_or(&footnote_ref.label);
-            Ok(format!(
-                "<sup class=\"footnote-ref\"><a href=\"#fn-{}\" id=\"fnref-{}\">{}</a></sup>",
-                html_escape(id),
-                html_escape(id),
-                html_escape(&footnote_ref.label)
-            ))
-        }
+                for item in items {
+                    html.push_str(&format!("<dt>{}</dt>", self.inlines(&item.term)));
 
-        Node::FootnoteDefinition(footnote_def) => {
-            let mut html = format!(
-                "<div class=\"footnote\" id=\"fn-{}\">\n<p>{}: ",
-                html_escape(&footnote_def.label),
-                html_escape(&footnote_def.label)
-            );
+                    for desc in &item.descriptions {
+                        html.push_str("<dd>");
+                        for node in desc {
+                            html.push_str(&self.node(node)?);
+                        }
+                        html.push_str("</dd>");
+                    }
+                }
 
-            for child in &footnote_def.content {
-                html.push_str(&node_to_html(child, 0)?);
+                html.push_str("</dl>");
+                Ok(html)
             }
 
-            // Add backlink if needed, depends on specific requirements
-            // html.push_str(&format!(" <a href="#fnref-{}" class="footnote-backref">↩</a>", html_escape(&footnote_def.label)));
+            Node::MathBlock { math } => Ok(format!(
+                "<div class=\"math-block\">{}</div>",
+                self.math(math, true)
+            )),
 
-            html.push_str("</p>\n</div>");
-            Ok(html)
-        }
+            Node::Embed {
+                url,
+                provider,
+                html,
+                aspect_ratio,
+            } => {
+                // Responsive container: padding-bottom keeps the aspect ratio while the
+                // iframe is stretched over it
+                let style_attr = aspect_ratio
+                    .as_deref()
+                    .and_then(aspect_ratio_padding)
+                    .map(|padding| {
+                        format!(
+                            " style=\"position:relative;padding-bottom:{}%;height:0;overflow:hidden;\"",
+                            padding
+                        )
+                    })
+                    .unwrap_or_default();
+
+                let content = match (html, &self.options.sanitizer) {
+                    (Some(embed_html), Some(sanitizer)) => sanitizer.sanitize(embed_html),
+                    (Some(embed_html), None) => embed_html.clone(),
+                    (None, _) => format!(
+                        "<a href=\"{}\">{}</a>",
+                        html_escape(&self.url(url)),
+                        html_escape(url)
+                    ),
+                };
 
-        Node::DefinitionList { items } => {
-            let mut html = String::from("<dl>");
+                Ok(format!(
+                    "<div class=\"embed embed-{}\"{}>{}</div>",
+                    html_escape(provider),
+                    style_attr,
+                    content
+                ))
+            }
+            Node::Figure {
+                url,
+                alt,
+                title,
+                caption,
+                properties,
+            } => {
+                let mut img_attrs = format!(
+                    "src=\"{}\" alt=\"{}\"",
+                    html_escape(&self.url(url)),
+                    html_escape(alt)
+                );
+                if let Some(t) = title {
+                    img_attrs.push_str(&format!(" title=\"{}\"", html_escape(t)));
+                }
+                if let Some(width) = properties.width {
+                    img_attrs.push_str(&format!(" width=\"{}\"", width));
+                }
+                if let Some(height) = properties.height {
+                    img_attrs.push_str(&format!(" height=\"{}\"", height));
+                }
+                if properties.lazy_loading {
+                    img_attrs.push_str(" loading=\"lazy\"");
+                }
 
-            for item in items {
-                html.push_str(&format!("<dt>{}</dt>", inlines_to_html(&item.term)));
+                let class = match properties.alignment {
+                    FigureAlignment::None => "figure".to_string(),
+                    alignment => format!("figure figure-{}", alignment.as_str()),
+                };
+                let figcaption = if caption.is_empty() {
+                    String::new()
+                } else {
+                    format!("<figcaption>{}</figcaption>", self.inlines(caption))
+                };
 
-                for desc in &item.descriptions {
-                    html.push_str("<dd>");
-                    for node in desc {
-                        html.push_str(&node_to_html(node, 0)?);
+                Ok(format!(
+                    "<figure class=\"{}\"><img {}>{}</figure>",
+                    class, img_attrs, figcaption
+                ))
+            }
+            Node::HtmlBlock { html } => Ok(self.raw_html(html)),
+            Node::HtmlComment { text } => Ok(self.html_comment(text)),
+            Node::Callout {
+                kind,
+                title,
+                children,
+            } => {
+                let title = title.as_deref().unwrap_or(kind.default_title());
+                let mut html = format!(
+                    "<div class=\"admonition admonition-{}\"><p class=\"admonition-title\">{}</p>",
+                    kind.as_str(),
+                    html_escape(title)
+                );
+                for child in children {
+                    html.push_str(&self.node(child)?);
+                }
+                html.push_str("</div>");
+                Ok(html)
+            }
+            Node::VisibilityBlock {
+                visibility,
+                children,
+            } => {
+                if !is_published(*visibility, self.options.drafts) {
+                    return Ok(String::new());
+                }
+                let mut html = String::new();
+                for child in children {
+                    html.push_str(&self.node(child)?);
+                }
+                Ok(match visibility {
+                    Visibility::Visible => html,
+                    _ => format!(
+                        "<div data-visibility=\"{}\">{}</div>",
+                        visibility.as_str(),
+                        html
+                    ),
+                })
+            }
+            Node::Custom { kind, data } => Ok(self.custom(kind, data, false)),
+            Node::TableOfContents { max_level } => {
+                let list = match self.toc_list(*max_level) {
+                    Some(list) => self.node(&list)?,
+                    None => String::new(),
+                };
+                Ok(format!("<nav class=\"table-of-contents\">{list}</nav>"))
+            }
+            Node::Conflict { ours, theirs } => {
+                let mut html = String::from("<div class=\"merge-conflict\">");
+                for (class, blocks) in [("conflict-ours", ours), ("conflict-theirs", theirs)] {
+                    html.push_str(&format!("<div class=\"{class}\">"));
+                    for child in blocks {
+                        html.push_str(&self.node(child)?);
                     }
-                    html.push_str("</dd>");
+                    html.push_str("</div>");
                 }
+                html.push_str("</div>");
+                Ok(html)
+            }
+            // Handle temporary nodes (should ideally not be serialized)
+            Node::TempListItem(_) => {
+                eprintln!("Warning: Attempting to serialize TempListItem");
+                Ok(String::new())
+            }
+            Node::TempTableCell(_) => {
+                eprintln!("Warning: Attempting to serialize TempTableCell");
+                Ok(String::new())
             }
+        }
+    }
 
-            html.push_str("</dl>");
-            Ok(html)
+    /// Convert inline nodes to HTML
+    fn inlines(&mut self, inlines: &[InlineNode]) -> String {
+        let changes = std::mem::take(&mut self.changes);
+        let mut html = String::new();
+        // Inlines sharing the tracked changes covering them are wrapped together
+        let mut group = String::new();
+        let mut covering: Vec<&TrackedChange> = Vec::new();
+        let mut offset = 0;
+
+        for inline in inlines {
+            let width = match inline {
+                InlineNode::Text(text) => text.text.len(),
+                _ => 1,
+            };
+            let current: Vec<&TrackedChange> = changes
+                .iter()
+                .filter(|change| change.range.start <= offset && offset + width <= change.range.end)
+                .collect();
+            offset += width;
+            if current != covering {
+                html.push_str(&wrap_changes(&covering, &group));
+                group.clear();
+                covering = current;
+            }
+            match self.inline(inline) {
+                Ok(inline_html) => group.push_str(&inline_html),
+                Err(err) => eprintln!("Error converting inline node to HTML: {}", err),
+            }
         }
+        html.push_str(&wrap_changes(&covering, &group));
+
+        html
+    }
 
-        Node::MathBlock { math } => Ok(format!(
-            "<div class=\"math-block\">${}$</div>",
-            html_escape(math)
-        )),
-        // Handle temporary nodes (should ideally not be serialized)
-        Node::TempListItem(_) => {
-            eprintln!("Warning: Attempting to serialize TempListItem");
-            Ok(String::new())
+    /// Content of a table cell, formatted with the locale if it holds a value
+    fn cell_content(&mut self, cell: &TableCell) -> Result<String, ParseError> {
+        if let (Some(locale), Some(value)) = (&self.options.locale, &cell.value) {
+            return Ok(html_escape(&locale.format(value)));
         }
-        Node::TempTableCell(_) => {
-            eprintln!("Warning: Attempting to serialize TempTableCell");
-            Ok(String::new())
+        let mut html = String::new();
+        for inline in &cell.content {
+            html.push_str(&self.inline(inline)?);
         }
+        Ok(html)
     }
-}
 
-/// Convert inline nodes to HTML
-fn inlines_to_html(inlines: &[InlineNode]) -> String {
-    let mut html = String::new();
+    /// Convert an inline node to HTML
+    fn inline(&mut self, inline: &InlineNode) -> Result<String, ParseError> {
+        match inline {
+            InlineNode::Text(text_node) => {
+                let mut result = html_escape(&text_node.text);
 
-    for inline in inlines {
-        match inline_node_to_html(inline) {
-            Ok(inline_html) => html.push_str(&inline_html),
-            Err(err) => eprintln!("Error converting inline node to HTML: {}", err),
-        }
-    }
+                if text_node.formatting.bold {
+                    result = format!("<strong>{}</strong>", result);
+                }
 
-    html
-}
+                if text_node.formatting.italic {
+                    result = format!("<em>{}</em>", result);
+                }
 
-/// Convert an inline node to HTML
-fn inline_node_to_html(inline: &InlineNode) -> Result<String, ParseError> {
-    match inline {
-        InlineNode::Text(text_node) => {
-            let mut result = html_escape(&text_node.text);
+                if text_node.formatting.strikethrough {
+                    result = format!("<del>{}</del>", result);
+                }
 
-            if text_node.formatting.bold {
-                result = format!("<strong>{}</strong>", result);
-            }
+                if text_node.formatting.code {
+                    result = format!("<code>{}</code>", result);
+                }
 
-            if text_node.formatting.italic {
-                result = format!("<em>{}</em>", result);
+                Ok(result)
             }
 
-            if text_node.formatting.strikethrough {
-                result = format!("<del>{}</del>", result);
+            InlineNode::Link {
+                url,
+                title,
+                children,
+                ..
+            } => {
+                let title_attr = if let Some(t) = title {
+                    format!(" title=\"{}\"", html_escape(t))
+                } else {
+                    String::new()
+                };
+
+                Ok(format!(
+                    "<a href=\"{}\"{}>{}</a>",
+                    html_escape(&self.url(url)),
+                    title_attr,
+                    self.inlines(children)
+                ))
             }
 
-            if text_node.formatting.code {
-                result = format!("<code>{}</code>", result);
+            InlineNode::Image { url, alt, title } => {
+                let title_attr = if let Some(t) = title {
+                    format!(" title=\"{}\"", html_escape(t))
+                } else {
+                    String::new()
+                };
+
+                Ok(format!(
+                    "<img src=\"{}\" alt=\"{}\"{}>",
+                    html_escape(&self.url(url)),
+                    html_escape(alt),
+                    title_attr
+                ))
             }
 
-            Ok(result)
-        }
+            InlineNode::CodeSpan { code } => Ok(format!("<code>{}</code>", html_escape(code))),
 
-        InlineNode::Link {
-            url,
-            title,
-            children,
-        } => {
-            let title_attr = if let Some(t) = title {
-                format!(" title=\"{}\"", html_escape(t))
-            } else {
-                String::new()
-            };
+            InlineNode::AutoLink { url, is_email } => {
+                let display = url.clone(); // Display the URL as is
 
-            Ok(format!(
-                "<a href=\"{}\"{}>{}",
-                html_escape(url),
-                title_attr,
-                inlines_to_html(children)
-            ))
-        }
+                let href = if *is_email && !url.starts_with("mailto:") {
+                    format!("mailto:{}", url)
+                } else {
+                    self.url(url)
+                };
 
-        InlineNode::Image { url, alt, title } => {
-            let title_attr = if let Some(t) = title {
-                format!(" title=\"{}\"", html_escape(t))
-            } else {
-                String::new()
-            };
+                Ok(format!(
+                    "<a href=\"{}\">{}</a>",
+                    html_escape(&href),
+                    html_escape(&display)
+                ))
+            }
 
-            Ok(format!(
-                "<img src=\"{}\" alt=\"{}\"{}>",
-                html_escape(url),
-                html_escape(alt),
-                title_attr
-            ))
-        }
+            InlineNode::FootnoteRef { label } => Ok(format!(
+                "<sup class=\"footnote-ref\"><a href=\"#fn-{}\" id=\"fnref-{}\">{}</a></sup>",
+                html_escape(label),
+                html_escape(label),
+                html_escape(label)
+            )),
 
-        InlineNode::CodeSpan { code } => Ok(format!("<code>{}</code>", html_escape(code))),
+            InlineNode::InlineFootnote { children } => Ok(format!(
+                "<sup class=\"footnote-inline\">{}</sup>",
+                self.inlines(children)
+            )),
 
-        InlineNode::AutoLink { url, is_email } => {
-            let display = url.clone(); // Display the URL as is
+            InlineNode::Mention { name, mention_type } => {
+                let label = match mention_type.as_str() {
+                    "user" => format!("@{}", name),
+                    "issue" => format!("#{}", name),
+                    _ => name.clone(),
+                };
+                let url = self
+                    .options
+                    .mentions
+                    .as_ref()
+                    .and_then(|resolver| resolver.resolve(name, mention_type));
+                Ok(match url {
+                    Some(url) => format!(
+                        "<a class=\"mention mention-{}\" href=\"{}\">{}</a>",
+                        html_escape(mention_type),
+                        html_escape(&self.url(&url)),
+                        html_escape(&label)
+                    ),
+                    None => format!(
+                        "<span class=\"mention mention-{}\">{}</span>",
+                        html_escape(mention_type),
+                        html_escape(&label)
+                    ),
+                })
+            }
 
-            let href = if *is_email && !url.starts_with("mailto:") {
-                format!("mailto:{}", url)
-            } else {
-                url.clone()
-            };
+            InlineNode::Tag { name } => Ok(match &self.options.tag_url {
+                Some(template) => format!(
+                    "<a class=\"tag\" href=\"{}\">#{}</a>",
+                    html_escape(&self.url(&template.replace("{tag}", name))),
+                    html_escape(name)
+                ),
+                None => format!(
+                    "<span class=\"tag\" data-tag=\"{}\">#{}</span>",
+                    html_escape(name),
+                    html_escape(name)
+                ),
+            }),
 
-            Ok(format!(
-                "<a href=\"{}\">{}</a>",
-                html_escape(&href),
-                html_escape(&display)
-            ))
-        }
+            InlineNode::WikiLink { target, .. } => {
+                let text = inline.plain_text();
+                let url = self
+                    .options
+                    .wiki_links
+                    .as_ref()
+                    .and_then(|resolver| resolver.resolve(target));
+                Ok(match url {
+                    Some(url) => format!(
+                        "<a class=\"wiki-link\" href=\"{}\">{}</a>",
+                        html_escape(&self.url(&url)),
+                        html_escape(&text)
+                    ),
+                    None => format!("<span class=\"wiki-link\">{}</span>", html_escape(&text)),
+                })
+            }
 
-        InlineNode::FootnoteRef { label } => Ok(format!(
-            "<sup class=\"footnote-ref\"><a href=\"#fn-{}\" id=\"fnref-{}\">{}</a></sup>",
-            html_escape(label),
-            html_escape(label),
-            html_escape(label)
-        )),
-
-        InlineNode::InlineFootnote { children } => Ok(format!(
-            "<sup class=\"footnote-inline\">{}</sup>",
-            inlines_to_html(children)
-        )),
-
-        InlineNode::Mention { name, mention_type } => match mention_type.as_str() {
-            "user" => Ok(format!(
-                "<span class=\"mention mention-user\">@{}</span>",
-                html_escape(name)
-            )),
-            "issue" => Ok(format!(
-                "<span class=\"mention mention-issue\">#{}</span>",
-                html_escape(name)
-            )),
-            _ => Ok(format!(
-                "<span class=\"mention mention-{}\">{}</span>",
-                html_escape(mention_type),
-                html_escape(name)
+            InlineNode::Citation { key, locator } => {
+                let entry = self.bibliography.iter().find(|entry| entry.id == *key);
+                Ok(match entry {
+                    Some(entry) => {
+                        let mut label = entry.label();
+                        if let Some(locator) = locator {
+                            label = format!("{label}, {locator}");
+                        }
+                        format!(
+                            "<cite class=\"citation\"><a href=\"#ref-{}\">({})</a></cite>",
+                            html_escape(key),
+                            html_escape(&label)
+                        )
+                    }
+                    None => format!(
+                        "<cite class=\"citation citation-missing\">{}</cite>",
+                        html_escape(&inline.plain_text())
+                    ),
+                })
+            }
+
+            InlineNode::Math { math } => Ok(format!(
+                "<span class=\"math-inline\">{}</span>",
+                self.math(math, false)
             )),
-        },
-
-        InlineNode::Math { math } => Ok(format!(
-            "<span class=\"math-inline\">${}$</span>",
-            html_escape(math)
-        )),
-
-        InlineNode::Emoji { shortcode } => {
-            // Basic emoji rendering, replace with actual emoji character if possible
-            // using a library like `emojis` crate in the future.
-            Ok(format!(
-                "<span class=\"emoji emoji-{}\">{}</span>",
-                html_escape(shortcode),
-                html_escape(shortcode) // Display shortcode for now
-            ))
-        }
 
-        InlineNode::HardBreak => Ok("<br/>\n".to_string()),
-        InlineNode::SoftBreak => Ok("<br/>\n".to_string()),
+            InlineNode::Html { html } => Ok(self.raw_html(html)),
+            InlineNode::HtmlComment { text } => Ok(self.html_comment(text)),
+            InlineNode::Custom { kind, data } => Ok(self.custom(kind, data, true)),
+
+            InlineNode::Emoji { shortcode } => {
+                // Basic emoji rendering, replace with actual emoji character if possible
+                // using a library like `emojis` crate in the future.
+                Ok(format!(
+                    "<span class=\"emoji emoji-{}\">{}</span>",
+                    html_escape(shortcode),
+                    html_escape(shortcode) // Display shortcode for now
+                ))
+            }
+
+            InlineNode::HardBreak => Ok("<br/>\n".to_string()),
+            InlineNode::SoftBreak => Ok("<br/>\n".to_string()),
+        }
     }
 }
 
+/// Convert inline nodes to HTML with the given options, without a
+/// surrounding block element
+/// Wrap rendered inlines in `<ins>` and `<del>` for the tracked changes
+/// covering them
+fn wrap_changes(changes: &[&TrackedChange], html: &str) -> String {
+    changes.iter().rev().fold(html.to_string(), |html, change| {
+        let tag = change.kind.tag();
+        format!(
+            "<{tag} data-change-id=\"{}\" data-author=\"{}\">{html}</{tag}>",
+            change.id,
+            html_escape(&change.author)
+        )
+    })
+}
+
+pub(crate) fn inlines_to_html(inlines: &[InlineNode], options: &HtmlOptions) -> String {
+    HtmlWriter::new(options).inlines(inlines)
+}
+
 /// Creates a document from HTML
+///
+/// Links and images the default [`UrlPolicy`] doesn't allow are removed.
 fn from_html(html: &str) -> Result<Document, ParseError> {
-    let md = mdka::from_html(html);
+    parse_html(html, &UrlPolicy::default())
+}
+
+/// Parse HTML, removing links and images `policy` doesn't allow
+fn parse_html(html: &str, policy: &UrlPolicy) -> Result<Document, ParseError> {
+    // Heading anchors are derived from the heading text, so exported ids can
+    // be dropped; mdka would otherwise turn them into separate elements
+    let html = regex::Regex::new(r#"(<h[1-6]\b[^>]*?)\s+id="[^"]*""#)
+        .unwrap()
+        .replace_all(html, "$1");
+    let md = mdka::from_html(&html);
     // Use regex to remove excessive newlines potentially introduced by mdka
     let md = regex::Regex::new(r"\n{2,}")
         .unwrap()
         .replace_all(&md, "\n\n");
 
     // Now parse the cleaned markdown using our parser
-
-    crate::convert::markdown::parse_markdown(&md)
+    let mut document = crate::convert::markdown::parse_markdown(&md)?;
+    document.sanitize_urls(policy);
+    Ok(document)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        CodeBlockProperties, Document, InlineNode, ListType, Node, TableAlignment, TableCell,
-        TableProperties, TextFormatting, TextNode,
+        CalloutKind, CellDate, CellValue, CodeBlockProperties, Document, InlineNode, ListType,
+        Locale, MentionUrls, Node, TableAlignment, TableCell, TableProperties, Text,
+        TextFormatting, TextNode,
     };
 
+    fn inlines_to_html(inlines: &[InlineNode]) -> String {
+        HtmlWriter::new(&HtmlOptions::default()).inlines(inlines)
+    }
+
     // Helper function to create a test document (can be adapted from serialization.rs)
     fn create_test_document() -> Document {
         let mut doc = Document::new();
@@ -671,20 +1794,27 @@ mod tests {
         // Print the HTML for inspection
         println!("Generated HTML: {}", html);
 
-        assert!(html.contains("<h1>Test Document</h1>"));
+        assert!(html.contains("<h1 id=\"test-document\">Test Document</h1>"));
         assert!(html.contains("<p>Simple paragraph.</p>"));
-        assert!(html.contains("<pre><code class=\"language-rust\" data-copy-button=\"true\">"));
-        assert!(html.contains("println!(&quot;Hello&quot;);"));
+        // Server-side highlighting marks the code and splits it into spans
+        if cfg!(feature = "syntax-highlighting") {
+            assert!(html.contains(
+                "<pre><code class=\"language-rust\" data-copy-button=\"true\" data-highlighted=\"true\">"
+            ));
+        } else {
+            assert!(html.contains("<pre><code class=\"language-rust\" data-copy-button=\"true\">"));
+            assert!(html.contains("println!(&quot;Hello&quot;);"));
+        }
 
-        // Fix: Check for list items with paragraphs, which seems to be the actual format
+        // Items of tight lists are rendered without paragraphs
         assert!(html.contains("<ul>"));
-        assert!(html.contains("<li><p>Item 1</p></li>"));
-        assert!(html.contains("<li><p>Item 2</p></li>"));
+        assert!(html.contains("<li>Item 1</li>"));
+        assert!(html.contains("<li>Item 2</li>"));
         assert!(html.contains("</ul>"));
 
         assert!(html.contains("<ul class=\"task-list\">"));
-        assert!(html.contains("<li><p><input type=\"checkbox\"  checked> Task A</p></li>"));
-        assert!(html.contains("<li><p><input type=\"checkbox\" > Task B</p></li>"));
+        assert!(html.contains("<li><input type=\"checkbox\"  checked> Task A</li>"));
+        assert!(html.contains("<li><input type=\"checkbox\" > Task B</li>"));
     }
 
     #[test]
@@ -696,6 +1826,238 @@ mod tests {
         assert!(html.contains("<div class=\"math-block\">$E = mc^2$</div>"));
     }
 
+    #[test]
+    fn test_html_link_rewriting() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::Link {
+                url: "guide.md#Setup".to_string(),
+                title: None,
+                children: vec![InlineNode::text("Guide")],
+                reference: None,
+            },
+            InlineNode::Image {
+                url: "img/logo.png".to_string(),
+                alt: "Logo".to_string(),
+                title: None,
+            },
+            InlineNode::AutoLink {
+                url: "https://example.com".to_string(),
+                is_email: false,
+            },
+        ]);
+        let options = HtmlOptions::new()
+            .with_link_rewriter(crate::ExtensionMap::markdown_to_html())
+            .with_link_rewriter(crate::NormalizeAnchors)
+            .with_link_rewriter(crate::BaseUrl::new("https://example.com/docs/"));
+
+        let html = doc.to_html_with(&options).html;
+        assert!(html.contains("href=\"https://example.com/docs/guide.html#setup\""));
+        assert!(html.contains("src=\"https://example.com/docs/img/logo.png\""));
+        assert!(html.contains("href=\"https://example.com\""));
+        // Export leaves the document alone
+        assert_eq!(doc.links().links[0].url, "guide.md#Setup");
+    }
+
+    #[test]
+    fn test_html_raw_html() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::Html {
+                html: "<kbd>".to_string(),
+            },
+            InlineNode::text("Ctrl"),
+            InlineNode::Html {
+                html: "</kbd>".to_string(),
+            },
+        ]);
+        doc.nodes.push(Node::HtmlBlock {
+            html: "<div class=\"note\">Hi</div>".to_string(),
+        });
+
+        assert_eq!(
+            to_html(&doc),
+            "<p><kbd>Ctrl</kbd></p><div class=\"note\">Hi</div>"
+        );
+        let escaped = doc.to_html_with(&HtmlOptions::new().escape_raw_html()).html;
+        assert_eq!(
+            escaped,
+            "<p>&lt;kbd&gt;Ctrl&lt;/kbd&gt;</p>&lt;div class=&quot;note&quot;&gt;Hi&lt;/div&gt;"
+        );
+    }
+
+    #[test]
+    fn test_html_sanitized() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::link("javascript:alert(1)", "click"),
+            InlineNode::Html {
+                html: "<kbd onclick=\"x()\">".to_string(),
+            },
+            InlineNode::text("K"),
+            InlineNode::Html {
+                html: "</kbd>".to_string(),
+            },
+        ]);
+        doc.nodes.push(Node::HtmlBlock {
+            html: "<script>alert(1)</script><b>bold</b>".to_string(),
+        });
+
+        let html = doc.to_html_with(&HtmlOptions::new().sanitized()).html;
+        assert_eq!(
+            html,
+            "<p><a href=\"#\">click</a><kbd>K</kbd></p><b>bold</b>"
+        );
+
+        let imported = Document::from_html_with(
+            "<p>Safe <a href=\"javascript:alert(1)\">link</a></p><script>alert(1)</script>",
+            &HtmlOptions::new().sanitized(),
+        )
+        .unwrap();
+        assert_eq!(imported.nodes.len(), 1);
+        assert!(!imported.links().urls().contains(&"javascript:alert(1)"));
+    }
+
+    #[test]
+    fn test_html_locale() {
+        let mut doc = Document::new();
+        let date = CellDate::new(2024, 3, 1).unwrap();
+        doc.nodes.push(Node::Table {
+            header: vec![TableCell::header("Due"), TableCell::header("Amount")],
+            rows: vec![vec![
+                TableCell::typed(CellValue::date(date)),
+                TableCell::text("1234.5 EUR")
+                    .with_value(CellValue::currency(1234.5, "EUR").unwrap()),
+            ]],
+            alignments: vec![TableAlignment::None; 2],
+            properties: TableProperties::default(),
+        });
+
+        let plain = doc.to_html_with(&HtmlOptions::new()).html;
+        assert!(plain.contains("<td>2024-03-01</td><td>1234.5 EUR</td>"));
+
+        let german = doc
+            .to_html_with(&HtmlOptions::new().with_locale(Locale::de_de()))
+            .html;
+        assert!(german.contains("<td>01.03.2024</td><td>1.234,50\u{a0}€</td>"));
+    }
+
+    #[test]
+    fn test_html_mention_resolver() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::user_mention("octocat"),
+            InlineNode::text(" closed "),
+            InlineNode::issue_mention("42"),
+        ]);
+
+        assert_eq!(
+            doc.to_html_with(&HtmlOptions::new()).html,
+            "<p><span class=\"mention mention-user\">@octocat</span> closed \
+             <span class=\"mention mention-issue\">#42</span></p>"
+        );
+
+        let options = HtmlOptions::new()
+            .with_mention_resolver(MentionUrls::new().users("https://github.com/{name}"));
+        assert_eq!(
+            doc.to_html_with(&options).html,
+            "<p><a class=\"mention mention-user\" href=\"https://github.com/octocat\">@octocat</a> \
+             closed <span class=\"mention mention-issue\">#42</span></p>"
+        );
+
+        let options = HtmlOptions::new()
+            .with_mention_resolver(|name: &str, kind: &str| {
+                (kind == "issue").then(|| format!("javascript:open({name})"))
+            })
+            .sanitized();
+        assert!(
+            doc.to_html_with(&options)
+                .html
+                .contains("<a class=\"mention mention-issue\" href=\"#\">#42</a>")
+        );
+    }
+
+    #[test]
+    fn test_html_render_cache() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Intro");
+        doc.add_paragraph_with_text("Same");
+        doc.add_paragraph_with_text("Same");
+        doc.add_code_block("let x = 1;", "rust");
+
+        let mut cache = HtmlRenderCache::new(HtmlOptions::new());
+        let first = cache.render(&doc);
+        assert_eq!(cache.last_rendered(), 3);
+        let html: String = first.iter().map(|node| node.html.as_str()).collect();
+        assert_eq!(html, to_html(&doc));
+        // Equal nodes share their HTML but not their id
+        assert_ne!(first[1].id, first[2].id);
+        assert!(first[2].id.starts_with(&first[1].id));
+
+        doc.nodes[2] = Node::paragraph("Changed");
+        let second = cache.render(&doc);
+        assert_eq!(cache.last_rendered(), 1);
+        assert_eq!(second[2].html, "<p>Changed</p>");
+        assert_eq!(second[0], first[0]);
+        assert_eq!(second[3], first[3]);
+
+        // A new heading with the same text changes the anchor of the old one
+        doc.nodes.insert(0, Node::heading(1, "Intro"));
+        let third = cache.render(&doc);
+        assert_eq!(cache.last_rendered(), 1);
+        assert!(third[1].html.contains("id=\"intro-1\""));
+        assert_eq!(third[0].html, first[0].html);
+    }
+
+    #[test]
+    fn test_html_math_modes() {
+        let doc = create_math_test_document();
+        let html = doc
+            .to_html_with(&HtmlOptions::new().with_math_mode(HtmlMathMode::MathJax))
+            .html;
+        assert!(html.contains("<span class=\"math-inline\">\\(a^2 + b^2 = c^2\\)</span>"));
+        assert!(html.contains("<div class=\"math-block\">\\[E = mc^2\\]</div>"));
+
+        // A mode set later replaces the renderer
+        let options = HtmlOptions::new()
+            .with_mathml()
+            .with_math_mode(HtmlMathMode::Raw);
+        let html = doc.to_html_with(&options).html;
+        assert!(html.contains("<div class=\"math-block\">$E = mc^2$</div>"));
+    }
+
+    #[test]
+    fn test_html_math_rendering() {
+        let mut doc = create_math_test_document();
+        doc.nodes.push(Node::MathBlock {
+            math: "\\frac{1}{".to_string(),
+        });
+
+        let output = doc.to_html_with(&HtmlOptions::new().with_mathml());
+        assert!(output.html.contains(
+            "<span class=\"math-inline\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow><msup>"
+        ));
+        assert!(output.html.contains(
+            "<div class=\"math-block\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">"
+        ));
+
+        // Invalid TeX falls back to the source and is reported
+        assert!(
+            output
+                .html
+                .contains("<div class=\"math-block\">$\\frac{1}{$</div>")
+        );
+        assert_eq!(
+            output.diagnostics,
+            vec![MathDiagnostic {
+                node_index: doc.nodes.len() - 1,
+                tex: "\\frac{1}{".to_string(),
+                display: true,
+                message: "missing closing '}'".to_string(),
+            }]
+        );
+    }
+
     #[test]
     fn test_html_escape() {
         assert_eq!(html_escape("<script>"), "&lt;script&gt;");
@@ -711,7 +2073,9 @@ mod tests {
 
         assert_eq!(doc.nodes.len(), 2);
         match &doc.nodes[0] {
-            Node::Heading { level, children } => {
+            Node::Heading {
+                level, children, ..
+            } => {
                 assert_eq!(*level, 1);
                 assert!(inlines_to_html(children).contains("Title"));
             }
@@ -733,7 +2097,9 @@ mod tests {
         assert_eq!(doc.nodes.len(), 2); // Expecting two list nodes
 
         match &doc.nodes[0] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 assert_eq!(*list_type, ListType::Unordered);
                 assert_eq!(items.len(), 2);
                 // Further checks on item content if needed
@@ -741,7 +2107,9 @@ mod tests {
             _ => panic!("Expected unordered list"),
         }
         match &doc.nodes[1] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 assert_eq!(*list_type, ListType::Ordered);
                 assert_eq!(items.len(), 1);
             }
@@ -784,12 +2152,14 @@ mod tests {
                 url: "https://example.com".to_string(),
                 title: None,
                 children: vec![InlineNode::text("link")],
+                reference: None,
             },
             InlineNode::text(" and a "),
             InlineNode::Link {
                 url: "https://example.org".to_string(),
                 title: Some("Example title".to_string()),
                 children: vec![InlineNode::text("link with title")],
+                reference: None,
             },
         ]);
 
@@ -939,6 +2309,137 @@ mod tests {
         assert!(html.contains("</blockquote>"));
     }
 
+    #[test]
+    fn test_html_serialization_callout() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::Callout {
+            kind: CalloutKind::Warning,
+            title: None,
+            children: vec![Node::paragraph("Back up first.")],
+        });
+        doc.nodes.push(Node::Callout {
+            kind: CalloutKind::Tip,
+            title: Some("A <faster> way".to_string()),
+            children: vec![Node::paragraph("Use the cache.")],
+        });
+
+        let html = to_html(&doc);
+        assert!(html.contains(
+            "<div class=\"admonition admonition-warning\"><p class=\"admonition-title\">Warning</p><p>Back up first.</p></div>"
+        ));
+        assert!(html.contains("<p class=\"admonition-title\">A &lt;faster&gt; way</p>"));
+    }
+
+    #[test]
+    fn test_html_serialization_custom() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::Custom {
+            kind: "mermaid".to_string(),
+            data: "A --> B".to_string(),
+        });
+        doc.nodes.push(Node::paragraph_with_inlines(vec![
+            InlineNode::text("Press "),
+            InlineNode::Custom {
+                kind: "kbd".to_string(),
+                data: "<Ctrl>".to_string(),
+            },
+        ]));
+
+        let html = to_html(&doc);
+        assert!(
+            html.contains(
+                "<pre class=\"custom\" data-kind=\"mermaid\"><code>A --&gt; B</code></pre>"
+            )
+        );
+        assert!(
+            html.contains(
+                "<p>Press <span class=\"custom\" data-kind=\"kbd\">&lt;Ctrl&gt;</span></p>"
+            )
+        );
+
+        let options = HtmlOptions::new().with_custom_renderer("kbd", |data: &str, inline: bool| {
+            assert!(inline);
+            format!("<kbd>{}</kbd>", html_escape(data))
+        });
+        let html = doc.to_html_with(&options).html;
+        assert!(html.contains("<p>Press <kbd>&lt;Ctrl&gt;</kbd></p>"));
+        assert!(html.contains("data-kind=\"mermaid\""));
+    }
+
+    #[test]
+    fn test_html_sections() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Preface");
+        doc.add_heading(1, "Book");
+        doc.add_heading(2, "Intro");
+        doc.add_paragraph_with_text("Hello");
+        doc.add_heading(2, "Usage");
+        doc.add_heading(1, "Appendix");
+
+        let options = HtmlOptions::new()
+            .with_sections(HtmlSectionMode::Details)
+            .with_collapsed([2]);
+        assert_eq!(
+            doc.to_html_with(&options).html,
+            "<p>Preface</p>\
+             <details open><summary><h1 id=\"book\">Book</h1></summary>\
+             <details><summary><h2 id=\"intro\">Intro</h2></summary><p>Hello</p></details>\
+             <details open><summary><h2 id=\"usage\">Usage</h2></summary></details>\
+             </details>\
+             <details open><summary><h1 id=\"appendix\">Appendix</h1></summary></details>"
+        );
+
+        let options = options.with_sections(HtmlSectionMode::Attributes);
+        let html = doc.to_html_with(&options).html;
+        assert!(html.starts_with(
+            "<p>Preface</p><section data-level=\"1\" data-collapsed=\"false\"><h1 id=\"book\">Book</h1>\
+             <section data-level=\"2\" data-collapsed=\"true\"><h2 id=\"intro\">Intro</h2><p>Hello</p></section>"
+        ));
+        assert_eq!(
+            html.matches("<section").count(),
+            html.matches("</section>").count()
+        );
+        assert_eq!(to_html(&doc).matches("<details").count(), 0);
+    }
+
+    #[test]
+    fn test_html_node_languages() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Greetings");
+        doc.add_paragraph_with_text("Bonjour à tous");
+        doc.nodes.push(Node::ThematicBreak);
+
+        let options =
+            HtmlOptions::new().with_node_languages([(1, "fr".to_string()), (2, "de".to_string())]);
+        assert_eq!(
+            doc.to_html_with(&options).html,
+            "<h1 id=\"greetings\">Greetings</h1><p lang=\"fr\">Bonjour à tous</p><hr lang=\"de\">"
+        );
+    }
+
+    #[test]
+    fn test_from_html_url_policy() {
+        let html = r#"<p><a href="javascript:alert(1)">click</a> <a href="https://example.com">safe</a> <img src="data:text/html,x" alt="logo"></p>"#;
+        let doc = Document::try_from(Text::<Html>::new(html)).unwrap();
+        let inlines = doc.nodes[0].as_paragraph().unwrap();
+        assert!(inlines.contains(&InlineNode::link("https://example.com", "safe")));
+        assert!(!inlines.iter().any(|inline| matches!(
+            inline,
+            InlineNode::Link { url, .. } | InlineNode::Image { url, .. }
+                if !url.starts_with("https:")
+        )));
+
+        let options = HtmlOptions::new().with_url_policy(UrlPolicy::new().deny_scheme("https"));
+        let doc = Document::from_html_with(html, &options).unwrap();
+        assert!(
+            !doc.nodes[0]
+                .as_paragraph()
+                .unwrap()
+                .iter()
+                .any(|inline| matches!(inline, InlineNode::Link { .. }))
+        );
+    }
+
     #[test]
     fn test_from_html_table() {
         let html = r#"<table>
@@ -981,6 +2482,20 @@ mod tests {
         assert!(found_table, "Should have found a table node");
     }
 
+    #[test]
+    fn test_heading_ids() {
+        let mut doc = Document::new();
+        doc.add_heading(2, "Setup & Usage");
+        doc.add_heading(2, "Setup & Usage");
+        let html = to_html(&doc);
+        assert!(html.contains("<h2 id=\"setup--usage\">Setup &amp; Usage</h2>"));
+        assert!(html.contains("<h2 id=\"setup--usage-1\">"));
+
+        // Ids are regenerated rather than imported as extra nodes
+        let parsed = from_html(&html).unwrap();
+        assert_eq!(parsed.nodes.len(), 2);
+    }
+
     #[test]
     fn test_html_roundtrip() {
         // Create a document with varied content
@@ -1074,9 +2589,14 @@ mod tests {
         let html = to_html(&doc);
 
         // Test for basic code block
-        assert!(
-            html.contains("<pre><code class=\"language-javascript\" data-copy-button=\"true\">")
-        );
+        let highlighted = if cfg!(feature = "syntax-highlighting") {
+            " data-highlighted=\"true\""
+        } else {
+            ""
+        };
+        assert!(html.contains(&format!(
+            "<pre><code class=\"language-javascript\" data-copy-button=\"true\"{highlighted}>"
+        )));
 
         // Test for line numbers
         assert!(html.contains("class=\"language-python line-numbers\""));
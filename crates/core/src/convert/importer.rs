@@ -0,0 +1,56 @@
+//! Helpers shared by the line-based importers
+//!
+//! The Org, AsciiDoc and reStructuredText parsers split their input into
+//! lines, measure and strip indentation, and scan inline markup as `char`
+//! slices; these are the pieces they have in common.
+
+use crate::InlineNode;
+
+pub(crate) fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// The width of a line's leading whitespace, in bytes
+pub(crate) fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Removes up to `width` bytes of leading whitespace from every line
+pub(crate) fn strip_indent(lines: &[String], width: usize) -> Vec<String> {
+    lines
+        .iter()
+        .map(|line| line[indent(line).min(width)..].to_string())
+        .collect()
+}
+
+/// Removes the indentation the non-blank lines share
+pub(crate) fn dedent(lines: &[String]) -> Vec<String> {
+    let width = lines
+        .iter()
+        .filter(|line| !is_blank(line))
+        .map(|line| indent(line))
+        .min()
+        .unwrap_or(0);
+    strip_indent(lines, width)
+}
+
+/// The index of `pattern` in `chars` from `from` on
+pub(crate) fn find(chars: &[char], from: usize, pattern: &str) -> Option<usize> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    (from..chars.len()).find(|&k| chars[k..].starts_with(&pattern))
+}
+
+/// Joins adjacent text nodes of the same formatting
+pub(crate) fn merge_text(children: Vec<InlineNode>) -> Vec<InlineNode> {
+    let mut merged: Vec<InlineNode> = Vec::with_capacity(children.len());
+    for child in children {
+        if let (Some(InlineNode::Text(last)), InlineNode::Text(text)) = (merged.last_mut(), &child)
+            && last.formatting == text.formatting
+        {
+            last.text.push_str(&text.text);
+            continue;
+        }
+        merged.push(child);
+    }
+    merged
+}
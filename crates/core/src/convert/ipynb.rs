@@ -0,0 +1,388 @@
+//! Jupyter notebook import
+//!
+//! Reads nbformat 4 notebooks (`.ipynb`) so they can be published as
+//! documents:
+//!
+//! - Markdown cells are parsed as Markdown, with images pointing at cell
+//!   attachments (`attachment:plot.png`) turned into `data:` URLs
+//! - Code cells become code blocks in the notebook's language, carrying
+//!   their execution count and outputs in [`CodeBlockProperties`]
+//! - Raw cells become HTML blocks when their MIME type is `text/html`,
+//!   Markdown when it is `text/markdown`, and plain code blocks otherwise
+//! - The notebook's `title` and `authors` become the document metadata,
+//!   and its kernel name a custom `kernel` entry
+//!
+//! Each output keeps one representation, the richest of PNG, JPEG, GIF,
+//! SVG, HTML and plain text it has. Error tracebacks lose their terminal
+//! colors.
+
+use super::Ipynb;
+use super::format::{Capabilities, FormatConverter};
+use crate::{
+    CodeBlockProperties, CodeOutput, CodeOutputKind, Document, DocumentMetadata, InlineNode,
+    Markdown, Node, ParseError, Text,
+};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::sync::LazyLock;
+
+static ANSI_ESCAPE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap());
+
+/// Output MIME types kept, richest first
+const PREFERRED_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/svg+xml",
+    "text/html",
+    "text/plain",
+];
+
+impl FormatConverter for Ipynb {
+    fn name(&self) -> &str {
+        "ipynb"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ipynb"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            parse: true,
+            render: false,
+            lost_features: Vec::new(),
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        parse_notebook(text)
+    }
+}
+
+/// Parses a notebook's JSON
+fn parse_notebook(text: &str) -> Result<Document, ParseError> {
+    let notebook: Value =
+        serde_json::from_str(text).map_err(|e| ParseError::Json(e.to_string()))?;
+    let major = notebook["nbformat"].as_u64().unwrap_or(0);
+    if major < 4 {
+        return Err(ParseError::Json(format!(
+            "unsupported notebook format {major}, expected 4"
+        )));
+    }
+    let cells = notebook["cells"]
+        .as_array()
+        .ok_or_else(|| ParseError::Json("notebook has no cells".to_string()))?;
+
+    let metadata = &notebook["metadata"];
+    let language = metadata["language_info"]["name"]
+        .as_str()
+        .or_else(|| metadata["kernelspec"]["language"].as_str())
+        .unwrap_or_default();
+
+    let mut document = Document::new();
+    for cell in cells {
+        let source = joined(&cell["source"]);
+        match cell["cell_type"].as_str() {
+            Some("markdown") => {
+                let mut nodes = markdown(&source)?;
+                if let Some(attachments) = cell["attachments"].as_object() {
+                    resolve_attachments(&mut nodes, attachments);
+                }
+                document.nodes.extend(nodes);
+            }
+            Some("code") => {
+                let mut properties = CodeBlockProperties::new().with_outputs(
+                    cell["outputs"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(output)
+                        .collect(),
+                );
+                properties.execution_count = cell["execution_count"]
+                    .as_u64()
+                    .and_then(|count| u32::try_from(count).ok());
+                document.nodes.push(Node::CodeBlock {
+                    language: language.to_string(),
+                    code: source.trim_end_matches('\n').to_string(),
+                    properties,
+                });
+            }
+            Some("raw") => {
+                let format = cell["metadata"]["format"]
+                    .as_str()
+                    .or_else(|| cell["metadata"]["raw_mimetype"].as_str())
+                    .unwrap_or_default();
+                match format {
+                    "text/html" => document.nodes.push(Node::HtmlBlock { html: source }),
+                    "text/markdown" => document.nodes.extend(markdown(&source)?),
+                    _ => document
+                        .nodes
+                        .push(Node::code_block(source.trim_end_matches('\n'), "")),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut info = DocumentMetadata {
+        title: metadata["title"].as_str().map(str::to_string),
+        ..Default::default()
+    };
+    let authors: Vec<&str> = metadata["authors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|author| author["name"].as_str().or_else(|| author.as_str()))
+        .collect();
+    if !authors.is_empty() {
+        info.author = Some(authors.join(", "));
+    }
+    info.set_custom_value(
+        "kernel",
+        metadata["kernelspec"]["name"].as_str().map(str::to_string),
+    );
+    if info != DocumentMetadata::default() {
+        document.metadata = Some(info);
+    }
+    Ok(document)
+}
+
+/// Text of a notebook string, which may be split into a list of lines
+fn joined(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn markdown(source: &str) -> Result<Vec<Node>, ParseError> {
+    Document::try_from(Text::<Markdown>::new(source)).map(|document| document.nodes)
+}
+
+/// Points images at `attachment:` URLs to `data:` URLs of the attachments
+fn resolve_attachments(nodes: &mut [Node], attachments: &Map<String, Value>) {
+    let resolve = |url: &mut String| {
+        let Some(name) = url.strip_prefix("attachment:") else {
+            return;
+        };
+        let Some(bundle) = attachments.get(name).and_then(Value::as_object) else {
+            return;
+        };
+        if let Some((mime_type, data)) = bundle.iter().next() {
+            let data: String = joined(data).split_whitespace().collect();
+            *url = format!("data:{mime_type};base64,{data}");
+        }
+    };
+    for node in nodes {
+        node.walk_mut(&mut |block| {
+            if let Node::Figure { url, .. } = block {
+                resolve(url);
+            }
+            for inlines in block.inline_runs_mut() {
+                for inline in inlines {
+                    inline.walk_mut(&mut |inline| {
+                        if let InlineNode::Image { url, .. } = inline {
+                            resolve(url);
+                        }
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Reads one cell output, or `None` for outputs without a kept type
+fn output(output: &Value) -> Option<CodeOutput> {
+    let kind = match output["output_type"].as_str()? {
+        "stream" => match output["name"].as_str() {
+            Some("stderr") => CodeOutputKind::Stderr,
+            _ => CodeOutputKind::Stdout,
+        },
+        "execute_result" => CodeOutputKind::Result,
+        "display_data" | "update_display_data" => CodeOutputKind::Display,
+        "error" => {
+            let traceback = joined_lines(&output["traceback"]);
+            let text = match traceback.is_empty() {
+                true => format!(
+                    "{}: {}",
+                    output["ename"].as_str().unwrap_or("Error"),
+                    output["evalue"].as_str().unwrap_or_default()
+                ),
+                false => traceback,
+            };
+            return Some(CodeOutput::text(
+                CodeOutputKind::Error,
+                ANSI_ESCAPE.replace_all(&text, ""),
+            ));
+        }
+        _ => return None,
+    };
+    if output["output_type"] == "stream" {
+        return Some(CodeOutput::text(kind, joined(&output["text"])));
+    }
+
+    let data = output["data"].as_object()?;
+    let mime_type = PREFERRED_TYPES
+        .iter()
+        .find(|mime_type| data.contains_key(**mime_type))?;
+    let mut output = CodeOutput {
+        kind,
+        mime_type: mime_type.to_string(),
+        data: joined(&data[*mime_type]),
+    };
+    if output.is_binary() {
+        output.data.retain(|c| !c.is_whitespace());
+    }
+    Some(output)
+}
+
+/// Traceback lines, joined with newlines
+fn joined_lines(value: &Value) -> String {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, ListType};
+
+    const NOTEBOOK: &str = r##"{
+ "nbformat": 4,
+ "nbformat_minor": 5,
+ "metadata": {
+  "title": "Growth",
+  "authors": [{"name": "Ada"}, {"name": "Grace"}],
+  "kernelspec": {"name": "python3", "language": "python", "display_name": "Python 3"},
+  "language_info": {"name": "python"}
+ },
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": ["# Growth\n", "\n", "- one\n", "- two\n", "\n", "![plot](attachment:plot.png)"],
+   "attachments": {"plot.png": {"image/png": "iVBOR\nw0KGgo="}}
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 3,
+   "metadata": {},
+   "source": ["import math\n", "print(math.pi)\n", "math.e"],
+   "outputs": [
+    {"output_type": "stream", "name": "stdout", "text": ["3.14159\n"]},
+    {"output_type": "execute_result", "execution_count": 3, "metadata": {},
+     "data": {"text/plain": ["2.718"], "text/html": ["<b>2.718</b>"]}},
+    {"output_type": "display_data", "metadata": {},
+     "data": {"image/png": "AAAA\n", "text/plain": ["<Figure>"]}}
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "metadata": {},
+   "source": "1 / 0",
+   "outputs": [
+    {"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero",
+     "traceback": ["\u001b[0;31mZeroDivisionError\u001b[0m", "division by zero"]}
+   ]
+  },
+  {"cell_type": "raw", "metadata": {"format": "text/html"}, "source": "<hr class=\"x\">"},
+  {"cell_type": "raw", "metadata": {}, "source": "%% raw text\n"}
+ ]
+}"##;
+
+    #[test]
+    fn test_parse_ipynb() {
+        let doc = Document::try_from(Text::<Ipynb>::new(NOTEBOOK)).unwrap();
+        let metadata = doc.metadata.as_ref().unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Growth"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada, Grace"));
+        assert_eq!(metadata.custom_value("kernel"), Some("python3"));
+
+        assert_eq!(doc.nodes.len(), 7);
+        assert!(
+            matches!(&doc.nodes[1], Node::List { list_type: ListType::Unordered, items, .. } if items.len() == 2)
+        );
+        assert_eq!(doc.images()[0].url, "data:image/png;base64,iVBORw0KGgo=");
+
+        let Node::CodeBlock {
+            language,
+            code,
+            properties,
+        } = &doc.nodes[3]
+        else {
+            panic!("expected a code block");
+        };
+        assert_eq!(language, "python");
+        assert_eq!(code, "import math\nprint(math.pi)\nmath.e");
+        assert_eq!(properties.execution_count, Some(3));
+        assert_eq!(
+            properties.outputs,
+            [
+                CodeOutput::text(CodeOutputKind::Stdout, "3.14159\n"),
+                CodeOutput {
+                    kind: CodeOutputKind::Result,
+                    mime_type: "text/html".to_string(),
+                    data: "<b>2.718</b>".to_string(),
+                },
+                CodeOutput {
+                    kind: CodeOutputKind::Display,
+                    mime_type: "image/png".to_string(),
+                    data: "AAAA".to_string(),
+                },
+            ]
+        );
+
+        let Node::CodeBlock { properties, .. } = &doc.nodes[4] else {
+            panic!("expected a code block");
+        };
+        assert_eq!(properties.execution_count, None);
+        assert_eq!(
+            properties.outputs,
+            [CodeOutput::text(
+                CodeOutputKind::Error,
+                "ZeroDivisionError\ndivision by zero"
+            )]
+        );
+        assert_eq!(
+            doc.nodes[5],
+            Node::HtmlBlock {
+                html: "<hr class=\"x\">".to_string()
+            }
+        );
+        assert_eq!(doc.nodes[6], Node::code_block("%% raw text", ""));
+
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(
+            html.as_str()
+                .contains("data-execution-count=\"3\" data-copy-button=\"true\"")
+        );
+        // Server-side highlighting splits the code into spans
+        if !cfg!(feature = "syntax-highlighting") {
+            assert!(
+                html.as_str()
+                    .contains("data-copy-button=\"true\">import math")
+            );
+        }
+        assert!(html.as_str().contains(
+            "<div class=\"code-outputs\"><div class=\"code-output code-output-stdout\"><pre>3.14159\n</pre></div>\
+             <div class=\"code-output code-output-result\"><b>2.718</b></div>\
+             <div class=\"code-output code-output-display\"><img src=\"data:image/png;base64,AAAA\" alt=\"\"></div></div>"
+        ));
+
+        assert!(
+            Document::try_from(Text::<Ipynb>::new("{\"nbformat\": 3, \"worksheets\": []}"))
+                .is_err()
+        );
+        assert!(Document::try_from(Text::<Ipynb>::new("not json")).is_err());
+    }
+}
@@ -1,32 +1,202 @@
-use crate::{Document, ParseError};
-use serde_json;
+//! JSON import and export, with schema versioning
+//!
+//! Exported documents carry a `schema_version` next to their fields. On
+//! import, payloads written with an older version are upgraded one version
+//! at a time by the migrations of a [`JsonMigrations`] registry before they
+//! are read, so documents stored by earlier releases keep loading after the
+//! model changes. Payloads without a version predate versioning and count
+//! as version 0.
+
+use crate::{Document, ExportTarget, ParseError};
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
 
 use super::Json;
-use super::Text;
+use super::format::{Capabilities, FormatConverter};
+
+/// Version of the JSON schema documents are exported with
+///
+/// Bumped whenever a model change needs stored documents to be migrated,
+/// together with a built-in migration from the previous version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Name of the field holding the schema version
+pub(crate) const VERSION_FIELD: &str = "schema_version";
+
+/// Upgrades a JSON payload from one schema version to the next
+type Migration = Rc<dyn Fn(&mut Value) -> Result<(), ParseError>>;
+
+/// Migrations upgrading older JSON payloads to [`SCHEMA_VERSION`]
+///
+/// Each migration takes a payload from one version to the next. The
+/// built-in ones cover every earlier version of this crate; apps that store
+/// extra data in documents, such as custom metadata, can register their
+/// own to rewrite it, or replace a built-in one.
+#[derive(Clone)]
+pub struct JsonMigrations {
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl fmt::Debug for JsonMigrations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonMigrations")
+            .field("from_versions", &self.migrations.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for JsonMigrations {
+    fn default() -> Self {
+        // Version 0 payloads, written before versioning, have the same shape
+        // as version 1
+        Self::empty().with_migration(0, |_| Ok(()))
+    }
+}
+
+impl JsonMigrations {
+    /// The built-in migrations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry without any migrations, which only reads current payloads
+    pub fn empty() -> Self {
+        Self {
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Upgrades payloads at version `from` to `from + 1` with `migrate`,
+    /// replacing any migration registered for `from`
+    ///
+    /// The migration sees the whole payload, `schema_version` included,
+    /// and doesn't need to update the version itself.
+    pub fn with_migration(
+        mut self,
+        from: u32,
+        migrate: impl Fn(&mut Value) -> Result<(), ParseError> + 'static,
+    ) -> Self {
+        self.migrations.insert(from, Rc::new(migrate));
+        self
+    }
+
+    /// Upgrades a payload to [`SCHEMA_VERSION`], returning the version it
+    /// was written with
+    ///
+    /// Fails for payloads newer than this crate, and for versions without
+    /// a migration to the next one.
+    pub fn migrate(&self, payload: &mut Value) -> Result<u32, ParseError> {
+        let object = payload
+            .as_object()
+            .ok_or_else(|| ParseError::Json("expected a JSON object".to_string()))?;
+        let written = match object.get(VERSION_FIELD) {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| ParseError::Json(format!("invalid {VERSION_FIELD}: {version}")))?,
+        };
+        if written > SCHEMA_VERSION {
+            return Err(ParseError::Json(format!(
+                "schema version {written} is newer than the supported version {SCHEMA_VERSION}"
+            )));
+        }
+        for version in written..SCHEMA_VERSION {
+            let migrate = self.migrations.get(&version).ok_or_else(|| {
+                ParseError::Json(format!("no migration from schema version {version}"))
+            })?;
+            migrate(payload)?;
+            if let Some(object) = payload.as_object_mut() {
+                object.insert(VERSION_FIELD.to_string(), Value::from(version + 1));
+            }
+        }
+        Ok(written)
+    }
+
+    /// Loads a stored payload and checks it survives a round trip at the
+    /// current version, returning the document
+    ///
+    /// Meant for compatibility tests over payloads an app has stored: run
+    /// it on a sample of each version in use whenever the crate or the
+    /// app's migrations change.
+    pub fn check_compatibility(&self, json: &str) -> Result<Document, ParseError> {
+        let document = Document::from_json_with(json, self)?;
+        let reloaded = Document::from_json_with(&to_json(&document)?, self)?;
+        if reloaded != document {
+            return Err(ParseError::Json(
+                "the document changed when written back at the current version".to_string(),
+            ));
+        }
+        Ok(document)
+    }
+}
 
-impl TryFrom<Text<Json>> for Document {
-    type Error = ParseError;
+impl Document {
+    /// Parses JSON, upgrading payloads of older schema versions with
+    /// `migrations`
+    pub fn from_json_with(json: &str, migrations: &JsonMigrations) -> Result<Document, ParseError> {
+        let payload: Value =
+            serde_json::from_str(json).map_err(|e| ParseError::Json(e.to_string()))?;
+        migrated_document(payload, migrations)
+    }
+}
 
-    fn try_from(json: Text<Json>) -> Result<Self, Self::Error> {
-        from_json(json.as_str())
+/// Reads a document from a payload of any supported schema version
+pub(crate) fn migrated_document(
+    mut payload: Value,
+    migrations: &JsonMigrations,
+) -> Result<Document, ParseError> {
+    migrations.migrate(&mut payload)?;
+    if let Some(object) = payload.as_object_mut() {
+        object.remove(VERSION_FIELD);
     }
+    serde_json::from_value(payload).map_err(|e| ParseError::Json(e.to_string()))
+}
+
+/// A document with the schema version it is written with
+#[derive(Serialize)]
+struct Versioned<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    document: &'a Document,
 }
 
-impl TryFrom<&Document> for Text<Json> {
-    type Error = ParseError;
+impl FormatConverter for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::of_target(ExportTarget::Json)
+    }
 
-    fn try_from(document: &Document) -> Result<Self, Self::Error> {
-        to_json(document).map(Text::new)
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        from_json(text)
+    }
+
+    fn render(&self, document: &Document) -> Result<String, ParseError> {
+        to_json(document)
     }
 }
 
 fn from_json(json: &str) -> Result<Document, ParseError> {
-    serde_json::from_str(json).map_err(|e| ParseError::Json(e.to_string()))
+    Document::from_json_with(json, &JsonMigrations::default())
 }
 
 fn to_json(document: &Document) -> Result<String, ParseError> {
+    let versioned = Versioned {
+        schema_version: SCHEMA_VERSION,
+        document,
+    };
     // Use pretty printing for better readability
-    serde_json::to_string_pretty(document).map_err(|e| ParseError::Json(e.to_string()))
+    serde_json::to_string_pretty(&versioned).map_err(|e| ParseError::Json(e.to_string()))
 }
 
 #[cfg(test)]
@@ -142,6 +312,8 @@ mod tests {
                 ListItem::paragraph("Nested Item 1"),
                 ListItem::paragraph("Nested Item 2"),
             ],
+            start: None,
+            loose: false,
         };
 
         let mut parent_item = ListItem::paragraph("Parent Item");
@@ -150,6 +322,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Ordered,
             items: vec![ListItem::paragraph("List Item 1"), parent_item],
+            start: None,
+            loose: false,
         });
 
         // Footnote
@@ -263,6 +437,7 @@ mod tests {
                 url: "https://example.com".to_string(),
                 title: None,
                 children: vec![InlineNode::text("link")],
+                reference: None,
             },
             InlineNode::text(" and an "),
             InlineNode::Image {
@@ -351,4 +526,80 @@ mod tests {
             from_json(&json).expect("Deserialization of large document should succeed");
         assert_eq!(doc.nodes.len(), deserialized.nodes.len());
     }
+
+    /// Payloads as stored by each schema version, which must keep loading
+    const FIXTURES: &[(u32, &str)] = &[
+        (
+            0,
+            r#"{"nodes": [
+                {"type": "heading", "level": 1, "children": [{"type": "text", "text": "Title", "formatting": {"bold": false, "italic": false, "strikethrough": false, "code": false}}]},
+                {"type": "code_block", "language": "rust", "code": "fn main() {}", "properties": {"show_line_numbers": true, "start_line": 1, "show_copy_button": true}}
+            ], "metadata": {"title": "Stored"}}"#,
+        ),
+        (
+            1,
+            r#"{"schema_version": 1, "nodes": [
+                {"type": "heading", "level": 1, "children": [{"type": "text", "text": "Title", "formatting": {"bold": false, "italic": false, "strikethrough": false, "code": false}}]},
+                {"type": "code_block", "language": "rust", "code": "fn main() {}", "properties": {"show_line_numbers": true, "start_line": 1, "show_copy_button": true}}
+            ], "metadata": {"title": "Stored"}}"#,
+        ),
+    ];
+
+    #[test]
+    fn test_schema_versions_stay_compatible() {
+        let migrations = JsonMigrations::default();
+        assert_eq!(
+            FIXTURES.last().map(|(version, _)| *version),
+            Some(SCHEMA_VERSION),
+            "add a fixture for the current schema version"
+        );
+        let documents: Vec<Document> = FIXTURES
+            .iter()
+            .map(|(version, json)| {
+                migrations
+                    .check_compatibility(json)
+                    .unwrap_or_else(|err| panic!("version {version}: {err}"))
+            })
+            .collect();
+        assert!(documents.windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(documents[0].nodes.len(), 2);
+
+        let json = to_json(&documents[0]).unwrap();
+        assert!(json.starts_with("{\n  \"schema_version\": 1,\n  \"nodes\": ["));
+    }
+
+    #[test]
+    fn test_json_migrations() {
+        let newer = r#"{"schema_version": 99, "nodes": []}"#;
+        assert!(from_json(newer).unwrap_err().to_string().contains("newer"));
+        assert!(
+            Document::from_json_with(FIXTURES[0].1, &JsonMigrations::empty())
+                .unwrap_err()
+                .to_string()
+                .contains("no migration from schema version 0")
+        );
+
+        // An app renaming one of its custom metadata keys in old payloads
+        let migrations = JsonMigrations::new().with_migration(0, |payload| {
+            if let Some(custom) = payload["metadata"]["custom"].as_array_mut() {
+                for entry in custom {
+                    if entry[0] == "owner" {
+                        entry[0] = Value::from("author_id");
+                    }
+                }
+            }
+            Ok(())
+        });
+        let doc = Document::from_json_with(
+            r#"{"nodes": [], "metadata": {"custom": [["owner", "42"]]}}"#,
+            &migrations,
+        )
+        .unwrap();
+        let metadata = doc.metadata.unwrap();
+        assert_eq!(metadata.custom_value("author_id"), Some("42"));
+
+        let mut payload = serde_json::json!({"nodes": []});
+        assert_eq!(migrations.migrate(&mut payload).unwrap(), 0);
+        assert_eq!(payload["schema_version"], SCHEMA_VERSION);
+    }
 }
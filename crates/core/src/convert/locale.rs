@@ -0,0 +1,217 @@
+//! Locale-aware formatting of typed table values
+//!
+//! Table cells may carry a [`CellValue`] next to their display text. HTML
+//! export configured with
+//! [`HtmlOptions::with_locale`](crate::HtmlOptions::with_locale) renders
+//! those values through a [`LocaleProvider`], so one document can show
+//! `1,234.50` to one reader and `1.234,50` to another. [`Locale`] covers
+//! the common conventions; apps with full locale data implement the trait
+//! themselves.
+
+use crate::{CellDate, CellValue};
+
+/// Formats typed values for display
+pub trait LocaleProvider {
+    /// Formats a plain number
+    fn format_number(&self, value: f64) -> String;
+
+    /// Formats an amount of money in the given ISO 4217 currency
+    fn format_currency(&self, amount: f64, currency: &str) -> String;
+
+    /// Formats a calendar date
+    fn format_date(&self, date: CellDate) -> String;
+
+    /// Formats any typed value
+    fn format(&self, value: &CellValue) -> String {
+        match value {
+            CellValue::Number { value } => self.format_number(*value),
+            CellValue::Currency { amount, currency } => self.format_currency(*amount, currency),
+            CellValue::Date { date } => self.format_date(*date),
+        }
+    }
+}
+
+/// Separator- and pattern-based number, currency and date formatting
+///
+/// The default formats numbers without grouping, amounts as `12.50 USD`
+/// and dates as ISO `2024-03-01`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    decimal_separator: char,
+    group_separator: Option<char>,
+    date_pattern: String,
+    symbols: bool,
+    symbol_after: bool,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            group_separator: None,
+            date_pattern: "%Y-%m-%d".to_string(),
+            symbols: false,
+            symbol_after: true,
+        }
+    }
+}
+
+impl Locale {
+    /// A locale with the given separators and the default date pattern and
+    /// currency style
+    pub fn new(decimal_separator: char, group_separator: Option<char>) -> Self {
+        Self {
+            decimal_separator,
+            group_separator,
+            ..Self::default()
+        }
+    }
+
+    /// US English: `1,234.5`, `$12.50`, `03/01/2024`
+    pub fn en_us() -> Self {
+        Self::new('.', Some(','))
+            .with_date_pattern("%m/%d/%Y")
+            .with_symbol_before()
+    }
+
+    /// British English: `1,234.5`, `£12.50`, `01/03/2024`
+    pub fn en_gb() -> Self {
+        Self::new('.', Some(','))
+            .with_date_pattern("%d/%m/%Y")
+            .with_symbol_before()
+    }
+
+    /// German: `1.234,5`, `12,50 €`, `01.03.2024`
+    pub fn de_de() -> Self {
+        Self::new(',', Some('.'))
+            .with_date_pattern("%d.%m.%Y")
+            .with_symbol_after()
+    }
+
+    /// French: `1 234,5` with a narrow no-break space, `12,50 €`, `01/03/2024`
+    pub fn fr_fr() -> Self {
+        Self::new(',', Some('\u{202f}'))
+            .with_date_pattern("%d/%m/%Y")
+            .with_symbol_after()
+    }
+
+    /// Formats dates with `pattern`, where `%Y`, `%m` and `%d` stand for the
+    /// four-digit year and the two-digit month and day
+    pub fn with_date_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.date_pattern = pattern.into();
+        self
+    }
+
+    /// Writes currency symbols such as `$` before the amount instead of
+    /// codes behind it
+    pub fn with_symbol_before(mut self) -> Self {
+        self.symbols = true;
+        self.symbol_after = false;
+        self
+    }
+
+    /// Writes currency symbols such as `€` behind the amount instead of codes
+    pub fn with_symbol_after(mut self) -> Self {
+        self.symbols = true;
+        self.symbol_after = true;
+        self
+    }
+
+    /// Formats the digits of a non-negative number with `decimals` places,
+    /// or as few as needed without
+    fn digits(&self, value: f64, decimals: Option<usize>) -> String {
+        let plain = match decimals {
+            Some(decimals) => format!("{:.*}", decimals, value),
+            None => value.to_string(),
+        };
+        let (integer, fraction) = plain.split_once('.').unwrap_or((&plain, ""));
+
+        let mut out = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                out.extend(self.group_separator);
+            }
+            out.push(digit);
+        }
+        if !fraction.is_empty() {
+            out.push(self.decimal_separator);
+            out.push_str(fraction);
+        }
+        out
+    }
+}
+
+/// Digits after the decimal point for amounts in a currency
+fn minor_units(currency: &str) -> usize {
+    match currency {
+        "JPY" | "KRW" | "VND" | "CLP" | "ISK" => 0,
+        "BHD" | "KWD" | "OMR" | "JOD" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// Symbol of common currencies
+fn currency_symbol(currency: &str) -> Option<&'static str> {
+    Some(match currency {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        _ => return None,
+    })
+}
+
+impl LocaleProvider for Locale {
+    fn format_number(&self, value: f64) -> String {
+        let sign = if value < 0.0 { "-" } else { "" };
+        format!("{}{}", sign, self.digits(value.abs(), None))
+    }
+
+    fn format_currency(&self, amount: f64, currency: &str) -> String {
+        let sign = if amount < 0.0 { "-" } else { "" };
+        let digits = self.digits(amount.abs(), Some(minor_units(currency)));
+        match currency_symbol(currency).filter(|_| self.symbols) {
+            Some(symbol) if self.symbol_after => format!("{sign}{digits}\u{a0}{symbol}"),
+            Some(symbol) => format!("{sign}{symbol}{digits}"),
+            None => format!("{sign}{digits}\u{a0}{currency}"),
+        }
+    }
+
+    fn format_date(&self, date: CellDate) -> String {
+        self.date_pattern
+            .replace("%Y", &format!("{:04}", date.year))
+            .replace("%m", &format!("{:02}", date.month))
+            .replace("%d", &format!("{:02}", date.day))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_formatting() {
+        let date = CellDate::new(2024, 3, 1).unwrap();
+
+        let iso = Locale::default();
+        assert_eq!(iso.format_number(1234.5), "1234.5");
+        assert_eq!(iso.format_currency(12.5, "USD"), "12.50\u{a0}USD");
+        assert_eq!(iso.format_date(date), "2024-03-01");
+
+        let us = Locale::en_us();
+        assert_eq!(us.format_number(-1234567.25), "-1,234,567.25");
+        assert_eq!(us.format_currency(-12.5, "USD"), "-$12.50");
+        assert_eq!(us.format_currency(1500.0, "JPY"), "¥1,500");
+        assert_eq!(us.format_currency(3.0, "CHF"), "3.00\u{a0}CHF");
+        assert_eq!(us.format_date(date), "03/01/2024");
+
+        let de = Locale::de_de();
+        assert_eq!(de.format_number(1234.5), "1.234,5");
+        assert_eq!(de.format_currency(1234.5, "EUR"), "1.234,50\u{a0}€");
+        assert_eq!(de.format(&CellValue::date(date)), "01.03.2024");
+
+        assert_eq!(Locale::fr_fr().format_number(1234.0), "1\u{202f}234");
+    }
+}
@@ -2,60 +2,421 @@ mod parser;
 
 // Make parse_markdown fully public so it can be re-exported
 use super::Markdown;
-use super::Text;
-use crate::ParseError;
+use super::format::{Capabilities, FormatConverter};
+use crate::citations::{REFERENCES_END, REFERENCES_START, citation_markdown};
 use crate::convert::html_escape;
-use crate::{Document, InlineNode, ListType, Node, TableAlignment};
+use crate::custom::Directives;
+use crate::links::{LinkPipeline, LinkRewriter};
+use crate::merge::conflict_markers;
+use crate::models::table_cell_columns;
+use crate::toc;
+use crate::visibility::marker;
+use crate::wiki_links::wiki_link_markdown;
+use crate::{
+    Document, FigureAlignment, FigureProperties, InlineNode, ListType, Node, TableAlignment,
+    Visibility,
+};
+use crate::{ExportTarget, ParseError, TagOptions};
+use std::collections::HashMap;
 
 pub(crate) use parser::parse_markdown;
+use parser::parse_markdown_with_spans;
 
-impl TryFrom<Text<Markdown>> for Document {
-    type Error = ParseError;
+impl FormatConverter for Markdown {
+    fn name(&self) -> &str {
+        "markdown"
+    }
 
-    fn try_from(markdown: Text<Markdown>) -> Result<Self, Self::Error> {
-        parse_markdown(markdown.as_str())
+    fn extensions(&self) -> &[&str] {
+        &["md", "markdown"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::of_target(ExportTarget::Markdown)
     }
-}
 
-impl TryFrom<&Document> for Text<Markdown> {
-    type Error = ParseError;
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        parse_markdown(text)
+    }
 
-    fn try_from(document: &Document) -> Result<Self, Self::Error> {
-        Ok(Text::new(to_markdown(document)))
+    fn render(&self, document: &Document) -> Result<String, ParseError> {
+        Ok(to_markdown(document))
     }
 }
-/// Convert a document to Markdown
+/// Convert a document to Markdown with the default options
 fn to_markdown(document: &Document) -> String {
-    let mut markdown = String::new();
+    document.to_markdown_with(&MarkdownOptions::default())
+}
+
+/// How Markdown export writes links
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// Writes every link inline as `[text](url)`
+    Inline,
+    /// Writes links that were reference-style as `[text][label]` and the
+    /// rest inline
+    #[default]
+    Preserve,
+    /// Writes every link as `[text][label]`, numbering links without a label
+    Reference,
+}
+
+/// How Markdown export numbers the items of ordered lists
+///
+/// Markers are always written from the items' positions, so lists stay
+/// correctly numbered however their items were moved or removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListNumbering {
+    /// Numbers items `1.`, `2.`, `3.` in order
+    #[default]
+    Sequential,
+    /// Writes every item as `1.`, which renders numbered in order and keeps
+    /// diffs small when items are added or moved
+    Repeated,
+}
+
+/// Options for Markdown import and export
+///
+/// The defaults match the plain [`Text<Markdown>`] conversions.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownOptions {
+    link_style: LinkStyle,
+    numbering: ListNumbering,
+    links: LinkPipeline,
+    mentions: bool,
+    tags: Option<TagOptions>,
+    directives: Directives,
+    source_positions: bool,
+    /// Whether to include drafts, when exporting for publishing
+    publish: Option<bool>,
+}
+
+impl MarkdownOptions {
+    /// Options matching the plain conversion
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes links in the given style
+    pub fn with_link_style(mut self, style: LinkStyle) -> Self {
+        self.link_style = style;
+        self
+    }
+
+    /// Numbers ordered list items as `numbering` says
+    pub fn with_list_numbering(mut self, numbering: ListNumbering) -> Self {
+        self.numbering = numbering;
+        self
+    }
+
+    /// Rewrites link, image and figure URLs with `rewriter`
+    ///
+    /// Rewriters run in the order they were added, each on the previous
+    /// one's output. The document itself is left unchanged.
+    pub fn with_link_rewriter(mut self, rewriter: impl LinkRewriter + 'static) -> Self {
+        self.links = self.links.then(rewriter);
+        self
+    }
+
+    /// Parses `@user` and `#123` patterns as mentions on import
+    ///
+    /// See [`Document::parse_mentions`] for what counts as a mention.
+    /// Export is unaffected, as mentions are always written back the same
+    /// way.
+    pub fn with_mentions(mut self) -> Self {
+        self.mentions = true;
+        self
+    }
+
+    /// Parses `#tag` patterns as tags on import
+    ///
+    /// See [`Document::parse_tags`]; `options` sets what counts as a tag.
+    /// Tags are always exported back as `#tag`.
+    pub fn with_tags(mut self, options: TagOptions) -> Self {
+        self.tags = Some(options);
+        self
+    }
+
+    /// Parses the registered fenced blocks and inline directives as custom
+    /// nodes on import
+    ///
+    /// See [`Document::parse_directives`]. Custom nodes are always exported
+    /// back to the syntax they came from.
+    pub fn with_directives(mut self, directives: Directives) -> Self {
+        self.directives = directives;
+        self
+    }
+
+    /// Records where each top-level node came from in the source on import
+    ///
+    /// See [`Document::source_span`]. The positions are kept as node
+    /// attributes, so they are saved with the document and exported to
+    /// HTML as `data-source-*` attributes.
+    pub fn with_source_positions(mut self) -> Self {
+        self.source_positions = true;
+        self
+    }
+
+    /// Leaves hidden blocks out of the export, and draft blocks unless
+    /// `include_drafts` is set
+    ///
+    /// By default every block is written, with draft and hidden blocks
+    /// between comment markers.
+    pub fn for_publishing(mut self, include_drafts: bool) -> Self {
+        self.publish = Some(include_drafts);
+        self
+    }
+}
+
+/// Reference labels match case-insensitively with whitespace collapsed
+pub(crate) fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// A link reference definition, written as `[label]: url "title"`
+struct LinkDefinition {
+    label: String,
+    url: String,
+    title: Option<String>,
+}
+
+/// Settles the label of every link for the given style
+///
+/// A label already defined with a different URL or title can't be shared,
+/// so later links using it are written inline. Returns the definitions to
+/// append, in order of first use.
+fn assign_references(document: &mut Document, style: LinkStyle) -> Vec<LinkDefinition> {
+    let mut definitions: Vec<LinkDefinition> = Vec::new();
+    let mut by_label: HashMap<String, usize> = HashMap::new();
+    let mut next_number = 1;
+
+    // Existing labels are claimed first so numbered ones don't collide
+    let mut passes = vec![false];
+    if style == LinkStyle::Reference {
+        passes.push(true);
+    }
+    for numbering in passes {
+        for node in &mut document.nodes {
+            node.walk_mut(&mut |block| {
+                for inline in block.inline_runs_mut().into_iter().flatten() {
+                    inline.walk_mut(&mut |inline| {
+                        let InlineNode::Link {
+                            url,
+                            title,
+                            reference,
+                            ..
+                        } = inline
+                        else {
+                            return;
+                        };
+                        if style == LinkStyle::Inline {
+                            *reference = None;
+                            return;
+                        }
+                        // Email addresses are written as autolinks
+                        if url.contains('@') && !url.contains("://") {
+                            return;
+                        }
+                        if numbering {
+                            if reference.is_some() {
+                                return;
+                            }
+                            let existing = definitions
+                                .iter()
+                                .find(|d| d.url == *url && d.title == *title)
+                                .map(|d| d.label.clone());
+                            *reference = Some(existing.unwrap_or_else(|| {
+                                loop {
+                                    let label = next_number.to_string();
+                                    next_number += 1;
+                                    if !by_label.contains_key(&label) {
+                                        break label;
+                                    }
+                                }
+                            }));
+                        }
+                        let Some(label) = reference else {
+                            return;
+                        };
+                        match by_label.get(&normalize_label(label)) {
+                            Some(&i)
+                                if definitions[i].url == *url && definitions[i].title == *title => {
+                            }
+                            Some(_) => *reference = None,
+                            None => {
+                                by_label.insert(normalize_label(label), definitions.len());
+                                definitions.push(LinkDefinition {
+                                    label: label.clone(),
+                                    url: url.clone(),
+                                    title: title.clone(),
+                                });
+                            }
+                        }
+                    });
+                }
+            });
+        }
+    }
+    definitions
+}
+
+impl Document {
+    /// Parses Markdown with the given options
+    ///
+    /// Only mention, tag and directive parsing and source positions apply
+    /// to import; the other options are ignored.
+    pub fn from_markdown_with(
+        markdown: &str,
+        options: &MarkdownOptions,
+    ) -> Result<Document, ParseError> {
+        let mut document = if options.source_positions {
+            let (mut document, spans) = parse_markdown_with_spans(markdown)?;
+            document.record_source_spans(markdown, spans);
+            document
+        } else {
+            parse_markdown(markdown)?
+        };
+        if options.mentions {
+            document.parse_mentions();
+        }
+        if let Some(tags) = &options.tags {
+            document.parse_tags(tags);
+        }
+        if !options.directives.is_empty() {
+            document.parse_directives(&options.directives);
+        }
+        Ok(document)
+    }
 
-    for node in &document.nodes {
-        markdown.push_str(&node_to_markdown(node));
-        markdown.push_str("\n\n");
+    /// Converts the document to Markdown with the given options
+    pub fn to_markdown_with(&self, options: &MarkdownOptions) -> String {
+        let mut document = match options.publish {
+            Some(include_drafts) => self.published(include_drafts),
+            None => self.clone(),
+        };
+        if !options.links.is_empty() {
+            document.rewrite_links_with(&options.links);
+        }
+        let definitions = assign_references(&mut document, options.link_style);
+
+        let mut markdown = String::new();
+        for node in &document.nodes {
+            match node {
+                Node::TableOfContents { max_level } => {
+                    let list = document.table_of_contents(*max_level);
+                    markdown.push_str(&toc::marker(Some(*max_level)));
+                    markdown.push_str("\n\n");
+                    if let Some(list) = list {
+                        markdown.push_str(node_markdown(&list, options.numbering).trim_end());
+                        markdown.push_str("\n\n");
+                    }
+                    markdown.push_str(&toc::marker(None));
+                }
+                _ => markdown.push_str(&node_markdown(node, options.numbering)),
+            }
+            markdown.push_str("\n\n");
+        }
+
+        let references = document.references();
+        if !references.is_empty() {
+            markdown.push_str(REFERENCES_START);
+            markdown.push_str("\n\n");
+            for (number, entry) in references.iter().enumerate() {
+                markdown.push_str(&format!("{}. {}\n", number + 1, entry.reference()));
+            }
+            markdown.push('\n');
+            markdown.push_str(REFERENCES_END);
+            markdown.push_str("\n\n");
+        }
+
+        for definition in &definitions {
+            let url = if definition.url.is_empty() || definition.url.contains(' ') {
+                format!("<{}>", definition.url)
+            } else {
+                definition.url.clone()
+            };
+            markdown.push_str(&format!("[{}]: {}", definition.label, url));
+            if let Some(title) = &definition.title {
+                markdown.push_str(&format!(" \"{}\"", title));
+            }
+            markdown.push('\n');
+        }
+
+        // Trim trailing newlines
+        markdown.trim_end().to_string()
     }
+}
 
-    // Trim trailing newlines
-    markdown.trim_end().to_string()
+/// Quotes the Markdown of each child with `> `, separated by empty quote lines
+fn blockquote_to_markdown(children: &[Node], numbering: ListNumbering) -> String {
+    let blocks: Vec<String> = children
+        .iter()
+        .map(|child| {
+            let lines: Vec<String> = node_markdown(child, numbering)
+                .lines()
+                .map(|line| match line {
+                    "" => ">".to_string(),
+                    line => format!("> {}", line),
+                })
+                .collect();
+            lines.join("\n")
+        })
+        .collect();
+
+    // An empty blockquote line between children, not just a newline
+    blocks.join("\n>\n")
 }
 
 /// Convert a node to Markdown
-fn node_to_markdown(node: &Node) -> String {
+pub(crate) fn node_to_markdown(node: &Node) -> String {
+    node_markdown(node, ListNumbering::default())
+}
+
+/// Convert a node to Markdown, numbering ordered lists as `numbering` says
+fn node_markdown(node: &Node, numbering: ListNumbering) -> String {
     match node {
-        Node::Heading { level, children } => {
-            format!(
+        Node::Heading {
+            level,
+            children,
+            properties,
+        } => {
+            let mut markdown = format!(
                 "{} {}",
                 "#".repeat(*level as usize),
                 inlines_to_markdown(children)
-            )
+            );
+            if let Some(target) = properties.word_target {
+                markdown.push_str(&format!(" {{target={}}}", target));
+            }
+            markdown
         }
 
         Node::Paragraph { children } => inlines_to_markdown(children),
 
-        Node::List { list_type, items } => {
+        Node::List {
+            list_type,
+            items,
+            start,
+            loose,
+        } => {
             let mut markdown = String::new();
+            let start = start.unwrap_or(1);
+            // Loose lists separate items and the blocks within them by a blank line
+            let separator = if *loose { "\n\n" } else { "\n" };
 
             for (i, item) in items.iter().enumerate() {
+                if i > 0 && *loose {
+                    markdown.push('\n');
+                }
                 let prefix = match list_type {
-                    ListType::Ordered => format!("{}. ", i + 1),
+                    ListType::Ordered => match numbering {
+                        ListNumbering::Sequential => format!("{}. ", start + i as u64),
+                        ListNumbering::Repeated => format!("{}. ", start),
+                    },
                     ListType::Unordered => "* ".to_string(),
                     ListType::Task => {
                         if let Some(checked) = item.checked {
@@ -72,27 +433,37 @@ fn node_to_markdown(node: &Node) -> String {
 
                 let mut item_md = String::new();
                 let mut first = true;
+                // Continuation lines are indented to the item's content
+                let indent = " ".repeat(prefix.len());
+                let indented = |line: &str| match line.trim().is_empty() {
+                    true => String::new(),
+                    false => format!("{}{}", indent, line),
+                };
 
                 for child in &item.children {
-                    let child_md = node_to_markdown(child);
+                    let child_md = node_markdown(child, numbering);
 
                     if first {
                         // For the first child, prefix with the list marker
+                        let mut lines = child_md.lines();
                         item_md.push_str(&prefix);
-                        item_md.push_str(&child_md);
+                        item_md.push_str(lines.next().unwrap_or_default());
+                        for line in lines {
+                            item_md.push('\n');
+                            item_md.push_str(&indented(line));
+                        }
                         first = false;
                     } else {
                         // For subsequent children, indent appropriately
-                        let indent = " ".repeat(prefix.len());
-                        item_md.push('\n');
-
-                        for line in child_md.lines() {
-                            item_md.push_str(&indent);
-                            item_md.push_str(line);
-                            item_md.push('\n');
-                        }
+                        item_md.push_str(separator);
+                        let lines: Vec<String> = child_md.lines().map(indented).collect();
+                        item_md.push_str(lines.join("\n").trim_end());
                     }
                 }
+                if first {
+                    // An empty item still needs its marker
+                    item_md.push_str(prefix.trim_end());
+                }
 
                 markdown.push_str(&item_md);
                 markdown.push('\n');
@@ -132,26 +503,7 @@ fn node_to_markdown(node: &Node) -> String {
             markdown
         }
 
-        Node::BlockQuote { children } => {
-            let mut markdown = String::new();
-
-            for child in children {
-                let child_md = node_to_markdown(child);
-
-                for line in child_md.lines() {
-                    markdown.push_str("> ");
-                    markdown.push_str(line);
-                    markdown.push('\n');
-                }
-
-                // Add an empty blockquote line between children, not just a newline
-                if !markdown.is_empty() {
-                    markdown.push_str("> \n");
-                }
-            }
-
-            markdown.trim_end().to_string()
-        }
+        Node::BlockQuote { children } => blockquote_to_markdown(children, numbering),
 
         Node::ThematicBreak => "---".to_string(),
 
@@ -161,7 +513,7 @@ fn node_to_markdown(node: &Node) -> String {
             let mut markdown = format!("<!-- group: {} -->\n\n", name);
 
             for child in children {
-                markdown.push_str(&node_to_markdown(child));
+                markdown.push_str(&node_markdown(child, numbering));
                 markdown.push_str("\n\n");
             }
 
@@ -230,11 +582,20 @@ fn node_to_markdown(node: &Node) -> String {
             }
             markdown.push('\n');
 
-            // Format data rows
-            for row in rows {
+            // Format data rows, leaving slots covered by merged cells empty
+            let columns = table_cell_columns(rows);
+            for (row, row_columns) in rows.iter().zip(&columns) {
+                let width = row_columns
+                    .last()
+                    .zip(row.last())
+                    .map_or(0, |(start, cell)| start + cell.colspan.max(1) as usize);
+                let mut slots = vec![String::new(); width];
+                for (cell, &column) in row.iter().zip(row_columns) {
+                    slots[column] = inlines_to_markdown(&cell.content);
+                }
+
                 markdown.push('|');
-                for cell in row {
-                    let content = inlines_to_markdown(&cell.content);
+                for content in slots {
                     markdown.push(' ');
                     markdown.push_str(&content);
                     markdown.push_str(" |");
@@ -253,7 +614,7 @@ fn node_to_markdown(node: &Node) -> String {
             let mut markdown = format!("[^{}]:", footnote_def.label);
 
             for (i, child) in footnote_def.content.iter().enumerate() {
-                let child_md = node_to_markdown(child);
+                let child_md = node_markdown(child, numbering);
 
                 if i == 0 {
                     markdown.push(' ');
@@ -284,7 +645,7 @@ fn node_to_markdown(node: &Node) -> String {
                     markdown.push_str(":   ");
 
                     for (i, node) in desc.iter().enumerate() {
-                        let node_md = node_to_markdown(node);
+                        let node_md = node_markdown(node, numbering);
 
                         if i == 0 {
                             markdown.push_str(&node_md);
@@ -307,11 +668,102 @@ fn node_to_markdown(node: &Node) -> String {
         Node::MathBlock { math } => {
             format!("$$\n{}\n$$", math)
         }
+
+        // Markdown has no embed syntax, so fall back to a plain autolink on its own line
+        Node::Embed { url, .. } => format!("<{}>", url),
         // Handle temporary nodes (should ideally not be serialized)
         Node::TempListItem(_) => {
             eprintln!("Warning: Attempting to serialize TempListItem to Markdown");
             String::new()
         }
+        Node::Figure {
+            url,
+            alt,
+            title,
+            caption,
+            properties,
+        } => {
+            // The image on its own line, followed by an emphasized caption line
+            let mut markdown = inline_to_markdown(&InlineNode::Image {
+                url: url.clone(),
+                alt: alt.clone(),
+                title: title.clone(),
+            });
+            markdown.push_str(&figure_attributes(properties));
+            if !caption.is_empty() {
+                markdown.push_str(&format!("\n*{}*", inlines_to_markdown(caption)));
+            }
+            markdown
+        }
+        Node::HtmlBlock { html } => html.clone(),
+        Node::HtmlComment { text } => format!("<!--{}-->", text),
+        // Titled callouts need the fenced container syntax, as GitHub
+        // alerts have no title
+        Node::Callout {
+            kind,
+            title: None,
+            children,
+        } => {
+            let body = blockquote_to_markdown(children, numbering);
+            let marker = format!("> [!{}]", kind.as_str().to_uppercase());
+            if body.is_empty() {
+                marker
+            } else {
+                format!("{}\n{}", marker, body)
+            }
+        }
+        Node::Callout {
+            kind,
+            title: Some(title),
+            children,
+        } => {
+            let mut markdown = format!("::: {} {}\n", kind.as_str(), title);
+            let blocks: Vec<String> = children
+                .iter()
+                .map(|child| node_markdown(child, numbering))
+                .collect();
+            if !blocks.is_empty() {
+                markdown.push_str(&blocks.join("\n\n"));
+                markdown.push('\n');
+            }
+            markdown.push_str(":::");
+            markdown
+        }
+        Node::VisibilityBlock {
+            visibility,
+            children,
+        } => {
+            let mut blocks: Vec<String> = children
+                .iter()
+                .map(|child| node_markdown(child, numbering))
+                .collect();
+            if *visibility != Visibility::Visible {
+                blocks.insert(0, marker(*visibility, false));
+                blocks.push(marker(*visibility, true));
+            }
+            blocks.join("\n\n")
+        }
+        Node::Custom { kind, data } => {
+            // The fence has to be longer than any backtick run in the data
+            let longest = data.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+            let fence = "`".repeat(longest.max(2) + 1);
+            let newline = if data.ends_with('\n') { "" } else { "\n" };
+            format!("{fence}{kind}\n{data}{newline}{fence}")
+        }
+        Node::Conflict { ours, theirs } => {
+            let side = |blocks: &[Node]| {
+                blocks
+                    .iter()
+                    .map(|child| node_markdown(child, numbering))
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            };
+            conflict_markers(&side(ours), &side(theirs))
+        }
+        // Only top-level tables of contents are expanded, by the document
+        Node::TableOfContents { max_level } => {
+            format!("{}\n\n{}", toc::marker(Some(*max_level)), toc::marker(None))
+        }
         Node::TempTableCell(_) => {
             eprintln!("Warning: Attempting to serialize TempTableCell to Markdown");
             String::new()
@@ -319,6 +771,29 @@ fn node_to_markdown(node: &Node) -> String {
     }
 }
 
+/// Formats figure properties as a `{key=value ...}` attribute block
+fn figure_attributes(properties: &FigureProperties) -> String {
+    let mut attrs = Vec::new();
+    if let Some(width) = properties.width {
+        attrs.push(format!("width={}", width));
+    }
+    if let Some(height) = properties.height {
+        attrs.push(format!("height={}", height));
+    }
+    if properties.alignment != FigureAlignment::None {
+        attrs.push(format!("align={}", properties.alignment.as_str()));
+    }
+    if properties.lazy_loading {
+        attrs.push("loading=lazy".to_string());
+    }
+
+    if attrs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", attrs.join(" "))
+    }
+}
+
 /// Convert inline nodes to Markdown
 fn inlines_to_markdown(inlines: &[InlineNode]) -> String {
     let mut markdown = String::new();
@@ -359,10 +834,18 @@ fn inline_to_markdown(inline: &InlineNode) -> String {
             url,
             title,
             children,
+            reference,
         } => {
             if url.contains('@') && !url.contains("://") {
                 // If the URL looks like an email address, format it as an autolink
                 format!("<{}>", url)
+            } else if let Some(label) = reference {
+                let text = inlines_to_markdown(children);
+                if normalize_label(label) == normalize_label(&text) {
+                    format!("[{}][]", text)
+                } else {
+                    format!("[{}][{}]", text, label)
+                }
             } else if let Some(t) = title {
                 format!("[{}]({} \"{}\")", inlines_to_markdown(children), url, t)
             } else {
@@ -395,6 +878,12 @@ fn inline_to_markdown(inline: &InlineNode) -> String {
             format!("[^{}]", inlines_to_markdown(children))
         }
 
+        InlineNode::Tag { name } => format!("#{name}"),
+
+        InlineNode::WikiLink { target, alias } => wiki_link_markdown(target, alias.as_deref()),
+
+        InlineNode::Citation { key, locator } => citation_markdown(key, locator.as_deref()),
+
         InlineNode::Mention { name, mention_type } => match mention_type.as_str() {
             "user" => format!("@{}", name),
             "issue" => format!("#{}", name),
@@ -409,6 +898,10 @@ fn inline_to_markdown(inline: &InlineNode) -> String {
             format!(":{shortcode}:")
         }
 
+        InlineNode::Html { html } => html.clone(),
+        InlineNode::HtmlComment { text } => format!("<!--{}-->", text),
+        InlineNode::Custom { kind, data } => format!(":{}[{}]", kind, data),
+
         InlineNode::HardBreak => "  \n".to_string(), // Standard Markdown for hard break
         InlineNode::SoftBreak => " ".to_string(),    // Standard Markdown for soft break
     }
@@ -418,8 +911,8 @@ fn inline_to_markdown(inline: &InlineNode) -> String {
 mod tests {
     use super::*;
     use crate::{
-        Document, FootnoteDefinition, InlineNode, ListType, Node, TableCell, TableProperties,
-        TextFormatting, TextNode,
+        CalloutKind, Document, FootnoteDefinition, InlineNode, ListItem, ListType, Node, TableCell,
+        TableProperties, Text, TextFormatting, TextNode,
     };
 
     fn get_node_type_short(node: &Node) -> &'static str {
@@ -436,6 +929,15 @@ mod tests {
             Node::FootnoteDefinition(_) => "footnote_definition",
             Node::DefinitionList { .. } => "definition_list",
             Node::MathBlock { .. } => "math_block",
+            Node::Embed { .. } => "embed",
+            Node::Figure { .. } => "figure",
+            Node::HtmlBlock { .. } => "html_block",
+            Node::HtmlComment { .. } => "html_comment",
+            Node::Callout { .. } => "callout",
+            Node::VisibilityBlock { .. } => "visibility_block",
+            Node::Custom { .. } => "custom",
+            Node::Conflict { .. } => "conflict",
+            Node::TableOfContents { .. } => "table_of_contents",
             Node::TempListItem(_) | Node::TempTableCell(_) => "temp",
         }
     }
@@ -556,6 +1058,20 @@ Header 1 | Header 2
         );
     }
 
+    #[test]
+    fn test_markdown_table_header_parsed() {
+        let doc = parse_markdown("| Name | Qty |\n|:-----|----:|\n| Apple | 3 |\n").unwrap();
+        let Node::Table { header, rows, .. } = &doc.nodes[0] else {
+            panic!("Expected table");
+        };
+        let texts: Vec<String> = header
+            .iter()
+            .map(|cell| InlineNode::plain_text_of(&cell.content))
+            .collect();
+        assert_eq!(texts, ["Name", "Qty"]);
+        assert_eq!(rows.len(), 1);
+    }
+
     #[test]
     fn test_markdown_parsing_basic() {
         let markdown = "# Title\n\nThis is a paragraph with **bold** text.\n\n* Item 1\n* Item 2";
@@ -643,6 +1159,137 @@ Header 1 | Header 2
         }
     }
 
+    #[test]
+    fn test_callout_parsing_roundtrip() {
+        let markdown = "> [!WARNING]\n> Back up first.\n\n\
+                        ::: tip Pro tip\n\nUse `--dry-run`.\n\n\
+                        ::: note\nNested\n:::\n\n:::\n\n\
+                        ```\n::: not a container\n```\n";
+        let doc = parse_markdown(markdown).unwrap();
+        assert_eq!(doc.nodes.len(), 3);
+        assert_eq!(
+            doc.nodes[0],
+            Node::Callout {
+                kind: CalloutKind::Warning,
+                title: None,
+                children: vec![Node::paragraph("Back up first.")],
+            }
+        );
+        let Node::Callout {
+            kind,
+            title,
+            children,
+        } = &doc.nodes[1]
+        else {
+            panic!("Expected callout");
+        };
+        assert_eq!(*kind, CalloutKind::Tip);
+        assert_eq!(title.as_deref(), Some("Pro tip"));
+        assert!(matches!(
+            children[1],
+            Node::Callout {
+                kind: CalloutKind::Note,
+                ..
+            }
+        ));
+        assert!(matches!(doc.nodes[2], Node::CodeBlock { .. }));
+
+        // An unknown container name stays text
+        let doc = parse_markdown("::: spoiler\nHidden\n:::\n").unwrap();
+        assert!(matches!(doc.nodes[0], Node::Paragraph { .. }));
+
+        let md = to_markdown(&parse_markdown(markdown).unwrap());
+        assert_eq!(
+            md,
+            "> [!WARNING]\n> Back up first.\n\n\
+             ::: tip Pro tip\nUse `--dry-run`.\n\n> [!NOTE]\n> Nested\n:::\n\n\
+             ```\n::: not a container\n```"
+        );
+        assert_eq!(
+            parse_markdown(&md).unwrap().nodes,
+            parse_markdown(markdown).unwrap().nodes
+        );
+
+        // Canonical callouts come back exactly as written
+        for markdown in [
+            "> [!NOTE]\n> Be careful",
+            "> [!TIP]\n> One\n>\n> Two",
+            "::: warning Heads up\nBack up first.\n:::",
+            "::: note Outer\nText\n\n::: tip Inner\nNested\n:::\n:::",
+        ] {
+            assert_eq!(to_markdown(&parse_markdown(markdown).unwrap()), markdown);
+        }
+    }
+
+    #[test]
+    fn test_list_start_and_looseness_round_trip() {
+        let source = "3. three\n4. four\n5. five\n\n\
+                      * loose\n\n* items\n\n  with two paragraphs\n";
+        let doc = parse_markdown(source).unwrap();
+        assert!(matches!(
+            &doc.nodes[0],
+            Node::List {
+                start: Some(3),
+                loose: false,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &doc.nodes[1],
+            Node::List {
+                start: None,
+                loose: true,
+                ..
+            }
+        ));
+
+        let md = to_markdown(&doc);
+        assert!(md.starts_with("3. three\n4. four\n5. five\n"));
+        assert!(md.contains("* loose\n\n* items\n\n  with two paragraphs"));
+        assert_eq!(parse_markdown(&md).unwrap().nodes, doc.nodes);
+
+        let html = doc.to_html_with(&crate::HtmlOptions::new()).html;
+        assert!(html.starts_with("<ol start=\"3\"><li>three</li>"));
+        assert!(html.contains("<ul><li><p>loose</p></li>"));
+    }
+
+    #[test]
+    fn test_ordered_list_numbering_after_edits() {
+        let markdown = "1. a\n2. b\n   1. x\n   2. y\n3. c\n4. d\n5. e\n6. f\n7. g\n8. h\n\
+                        9. i\n10. j\n    1. deep\n    2. deeper\n11. k\n";
+        let mut doc = parse_markdown(markdown).unwrap();
+        let Node::List { items, .. } = &mut doc.nodes[0] else {
+            panic!("Expected list");
+        };
+        // Remove and reorder items at both levels, leave one empty and start
+        // another with a code block
+        items.remove(0);
+        items.swap(0, 8);
+        let Node::List { items: nested, .. } = &mut items[0].children[1] else {
+            panic!("Expected nested list");
+        };
+        nested.insert(
+            0,
+            ListItem::new(vec![Node::code_block("let x = 1;", "rust")]),
+        );
+        nested.push(ListItem::new(Vec::new()));
+        items[3].children.clear();
+
+        let md = to_markdown(&doc);
+        assert!(md.starts_with(
+            "1. j\n   1. ```rust\n      let x = 1;\n      ```\n   2. deep\n   3. deeper\n   4.\n"
+        ));
+        assert!(md.contains("\n4.\n5. f\n"));
+        assert!(md.ends_with("9. b\n   1. x\n   2. y\n10. k"));
+        assert_eq!(parse_markdown(&md).unwrap().nodes, doc.nodes);
+
+        let options = MarkdownOptions::new().with_list_numbering(ListNumbering::Repeated);
+        let md = doc.to_markdown_with(&options);
+        assert!(md.starts_with("1. j\n   1. ```rust"));
+        assert!(!md.contains("2."));
+        assert_eq!(parse_markdown(&md).unwrap().nodes, doc.nodes);
+    }
+
     #[test]
     fn test_list_serialization_parsing() {
         // Create a document with nested lists
@@ -666,7 +1313,10 @@ Header 1 | Header 2
         assert!(doc.nodes.len() >= 2, "Should have at least two lists");
 
         // First list should be unordered with 3 items
-        if let Node::List { list_type, items } = &doc.nodes[0] {
+        if let Node::List {
+            list_type, items, ..
+        } = &doc.nodes[0]
+        {
             assert_eq!(*list_type, ListType::Unordered);
             assert_eq!(items.len(), 3);
 
@@ -681,7 +1331,10 @@ Header 1 | Header 2
         }
 
         // Second list should be ordered with 3 items
-        if let Node::List { list_type, items } = &doc.nodes[1] {
+        if let Node::List {
+            list_type, items, ..
+        } = &doc.nodes[1]
+        {
             assert_eq!(*list_type, ListType::Ordered);
             assert_eq!(items.len(), 3);
 
@@ -774,22 +1427,19 @@ And an image: ![alt text](https://example.com/image.jpg "Image title")
             if let Node::Paragraph { children } = node {
                 for inline in children {
                     match inline {
-                        InlineNode::Link { url, title, .. } => {
-                            if url == "https://example.com" {
-                                if title.is_none() {
-                                    found_link = true;
-                                } else if title.as_ref().is_some_and(|t| t == "Example") {
-                                    found_link_with_title = true;
-                                }
+                        InlineNode::Link { url, title, .. } if url == "https://example.com" => {
+                            if title.is_none() {
+                                found_link = true;
+                            } else if title.as_ref().is_some_and(|t| t == "Example") {
+                                found_link_with_title = true;
                             }
                         }
-                        InlineNode::Image { url, alt, title } => {
+                        InlineNode::Image { url, alt, title }
                             if url == "https://example.com/image.jpg"
                                 && alt == "alt text"
-                                && title.as_ref().is_some_and(|t| t == "Image title")
-                            {
-                                found_image = true;
-                            }
+                                && title.as_ref().is_some_and(|t| t == "Image title") =>
+                        {
+                            found_image = true;
                         }
                         _ => {}
                     }
@@ -809,6 +1459,147 @@ And an image: ![alt text](https://example.com/image.jpg "Image title")
         assert!(md.contains("![alt text](https://example.com/image.jpg \"Image title\")"));
     }
 
+    #[test]
+    fn test_reference_links() {
+        let source = "See the [guide][docs], the [FAQ][] and [home](https://example.com).\n\n\
+                      [docs]: https://example.com/guide \"Guide\"\n\
+                      [faq]: https://example.com/faq";
+        let doc = parse_markdown(source).unwrap();
+        let Node::Paragraph { children } = &doc.nodes[0] else {
+            panic!("Expected paragraph");
+        };
+        assert!(matches!(
+            &children[1],
+            InlineNode::Link { reference: Some(label), .. } if label == "docs"
+        ));
+
+        let md = to_markdown(&doc);
+        assert_eq!(
+            md,
+            "See the [guide][docs], the [FAQ][] and [home](https://example.com).\n\n\
+             [docs]: https://example.com/guide \"Guide\"\n\
+             [FAQ]: https://example.com/faq"
+        );
+        assert_eq!(parse_markdown(&md).unwrap(), doc);
+
+        let inline =
+            doc.to_markdown_with(&MarkdownOptions::new().with_link_style(LinkStyle::Inline));
+        assert_eq!(
+            inline,
+            "See the [guide](https://example.com/guide \"Guide\"), the [FAQ](https://example.com/faq) and [home](https://example.com)."
+        );
+
+        let mut doc = doc;
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::link("https://example.com", "again"),
+            InlineNode::reference_link("https://example.org", "docs", "clash"),
+        ]);
+        let numbered =
+            doc.to_markdown_with(&MarkdownOptions::new().with_link_style(LinkStyle::Reference));
+        assert!(numbered.contains("and [home][1]."));
+        // The clashing label can't be reused, so that link is numbered too
+        assert!(numbered.contains("[again][1][clash][2]"));
+        assert!(numbered.ends_with("[1]: https://example.com\n[2]: https://example.org"));
+
+        let inline_clash = doc.to_markdown_with(&MarkdownOptions::new());
+        assert!(inline_clash.contains("[clash](https://example.org)"));
+    }
+
+    #[test]
+    fn test_directives_roundtrip() {
+        let markdown = "Press :kbd[Ctrl]+:kbd[C] to copy.\n\n```mermaid\ngraph TD; A-->B\n```\n\n````chart\n```\n{}\n````\n";
+        let options = MarkdownOptions::new().with_directives(
+            Directives::new()
+                .with_block("mermaid")
+                .with_block("chart")
+                .with_inline("kbd"),
+        );
+        let doc = Document::from_markdown_with(markdown, &options).unwrap();
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap()[1..4],
+            [
+                InlineNode::Custom {
+                    kind: "kbd".to_string(),
+                    data: "Ctrl".to_string(),
+                },
+                InlineNode::text("+"),
+                InlineNode::Custom {
+                    kind: "kbd".to_string(),
+                    data: "C".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            doc.nodes[2],
+            Node::Custom {
+                kind: "chart".to_string(),
+                data: "```\n{}".to_string(),
+            }
+        );
+
+        let exported = doc.to_markdown_with(&options);
+        assert_eq!(exported.trim_end(), markdown.trim_end());
+        assert_eq!(
+            Document::from_markdown_with(&exported, &options)
+                .unwrap()
+                .nodes,
+            doc.nodes
+        );
+
+        let json = serde_json::to_string(&doc).unwrap();
+        assert!(json.contains(r#"{"type":"custom","kind":"mermaid","data":"graph TD; A-->B"}"#));
+        assert_eq!(
+            serde_json::from_str::<Document>(&json).unwrap().nodes,
+            doc.nodes
+        );
+    }
+
+    #[test]
+    fn test_parse_mentions_option() {
+        let markdown = "Ping @octocat about #42.";
+        let plain = Document::from_markdown_with(markdown, &MarkdownOptions::new()).unwrap();
+        assert_eq!(plain.nodes[0].as_paragraph().unwrap().len(), 1);
+
+        let doc = Document::from_markdown_with(markdown, &MarkdownOptions::new().with_mentions())
+            .unwrap();
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("Ping "),
+                InlineNode::user_mention("octocat"),
+                InlineNode::text(" about "),
+                InlineNode::issue_mention("42"),
+                InlineNode::text("."),
+            ]
+        );
+        assert_eq!(Text::<Markdown>::try_from(&doc).unwrap().as_str(), markdown);
+    }
+
+    #[test]
+    fn test_raw_html() {
+        let source = "Press <kbd>Ctrl</kbd>+<kbd>C</kbd> to copy.\n\n\
+                      <details>\n<summary>More</summary>\n</details>\n\n\
+                      After";
+        let doc = parse_markdown(source).unwrap();
+        assert_eq!(doc.nodes.len(), 3);
+        let Node::Paragraph { children } = &doc.nodes[0] else {
+            panic!("Expected paragraph");
+        };
+        assert_eq!(
+            children[1],
+            InlineNode::Html {
+                html: "<kbd>".to_string()
+            }
+        );
+        assert_eq!(doc.nodes[0].plain_text(), "Press Ctrl+C to copy.");
+        assert!(matches!(
+            &doc.nodes[1],
+            Node::HtmlBlock { html } if html == "<details>\n<summary>More</summary>\n</details>"
+        ));
+
+        assert_eq!(to_markdown(&doc), source);
+    }
+
     #[test]
     fn test_horizontal_rule() {
         let markdown = "Before rule\n\n---\n\nAfter rule";
@@ -904,7 +1695,10 @@ No language specified
         let doc = parse_markdown(markdown).expect("Should parse task list");
         assert_eq!(doc.nodes.len(), 1, "Should have one list");
 
-        if let Node::List { list_type, items } = &doc.nodes[0] {
+        if let Node::List {
+            list_type, items, ..
+        } = &doc.nodes[0]
+        {
             assert_eq!(*list_type, ListType::Task);
             assert_eq!(items.len(), 3, "Should have three tasks");
 
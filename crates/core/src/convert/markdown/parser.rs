@@ -1,12 +1,19 @@
+use crate::citations;
+use crate::html_comments::comment_text;
+use crate::models::embed::paragraph_to_embed;
+use crate::toc;
+use crate::visibility::wrap_markers;
 use crate::{
-    CodeBlockProperties, Document, FootnoteDefinition, InlineNode, ListItem, ListType, Node,
+    CalloutKind, CodeBlockProperties, DefaultEmbedResolver, Document, FigureAlignment,
+    FigureProperties, FootnoteDefinition, HeadingProperties, InlineNode, ListItem, ListType, Node,
     ParseError, TableAlignment, TableCell, TableProperties, TextFormatting, TextNode,
 };
 use pulldown_cmark::{
-    Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, /* LinkType, */ Options, Parser,
-    Tag, TagEnd,
+    Alignment, BlockQuoteKind, CodeBlockKind, CowStr, Event, HeadingLevel, LinkType,
+    /* LinkType, */ Options, Parser, Tag, TagEnd,
 };
 use std::collections::HashMap;
+use std::ops::Range;
 
 /// Converts a pulldown-cmark Alignment to our TableAlignment
 fn convert_alignment(alignment: Alignment) -> TableAlignment {
@@ -18,6 +25,17 @@ fn convert_alignment(alignment: Alignment) -> TableAlignment {
     }
 }
 
+/// Converts a GitHub alert type to our CalloutKind
+fn callout_kind(kind: BlockQuoteKind) -> CalloutKind {
+    match kind {
+        BlockQuoteKind::Note => CalloutKind::Note,
+        BlockQuoteKind::Tip => CalloutKind::Tip,
+        BlockQuoteKind::Important => CalloutKind::Important,
+        BlockQuoteKind::Warning => CalloutKind::Warning,
+        BlockQuoteKind::Caution => CalloutKind::Caution,
+    }
+}
+
 /// Convert a heading level to u8
 fn level_to_u8(level: HeadingLevel) -> u8 {
     match level {
@@ -30,15 +48,108 @@ fn level_to_u8(level: HeadingLevel) -> u8 {
     }
 }
 
+/// Reads heading properties from a `{key=value}` attribute block
+fn heading_properties(attrs: &[(CowStr, Option<CowStr>)]) -> HeadingProperties {
+    let mut properties = HeadingProperties::default();
+    for (key, value) in attrs {
+        if key.as_ref() == "target" {
+            properties.word_target = value.as_ref().and_then(|v| v.parse().ok());
+        }
+    }
+    properties
+}
+
+/// Parses a figure attribute block such as `{width=640 align=center loading=lazy}`
+fn parse_figure_attributes(text: &str) -> Option<FigureProperties> {
+    let inner = text.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut properties = FigureProperties::default();
+
+    for attr in inner.split_whitespace() {
+        let (key, value) = attr.split_once('=')?;
+        match key {
+            "width" => properties.width = Some(value.parse().ok()?),
+            "height" => properties.height = Some(value.parse().ok()?),
+            "align" => {
+                properties.alignment = match value {
+                    "left" => FigureAlignment::Left,
+                    "center" => FigureAlignment::Center,
+                    "right" => FigureAlignment::Right,
+                    _ => FigureAlignment::None,
+                }
+            }
+            "loading" => properties.lazy_loading = value == "lazy",
+            _ => return None,
+        }
+    }
+
+    Some(properties)
+}
+
+/// Converts a paragraph holding an image followed by an attribute block and/or
+/// an emphasized caption line into a figure
+fn paragraph_to_figure(node: &Node) -> Option<Node> {
+    let (image, rest) = node.as_paragraph()?.split_first()?;
+    let InlineNode::Image { url, alt, title } = image else {
+        return None;
+    };
+
+    let mut rest = rest;
+    let mut properties = None;
+    if let [InlineNode::Text(text), tail @ ..] = rest
+        && let Some(parsed) = parse_figure_attributes(&text.text)
+    {
+        properties = Some(parsed);
+        rest = tail;
+    }
+
+    // Skip the line break between the image and the caption
+    let caption_start = rest
+        .iter()
+        .position(|inline| !matches!(inline, InlineNode::Text(t) if t.text.trim().is_empty()))
+        .unwrap_or(rest.len());
+    let caption = &rest[caption_start..];
+
+    let is_caption = caption
+        .iter()
+        .any(|inline| matches!(inline, InlineNode::Text(t) if t.formatting.italic))
+        && caption
+            .iter()
+            .all(|inline| !matches!(inline, InlineNode::Text(t) if !t.formatting.italic));
+    let attributes_only = caption.is_empty() && properties.is_some();
+    if !is_caption && !attributes_only {
+        return None;
+    }
+
+    let caption = caption
+        .iter()
+        .cloned()
+        .map(|inline| match inline {
+            InlineNode::Text(mut text) => {
+                text.formatting.italic = false;
+                InlineNode::Text(text)
+            }
+            other => other,
+        })
+        .collect();
+
+    Some(Node::Figure {
+        url: url.clone(),
+        alt: alt.clone(),
+        title: title.clone(),
+        caption,
+        properties: properties.unwrap_or_default(),
+    })
+}
+
 /// Represents the current parsing context (e.g., inside a list, blockquote).
 #[allow(dead_code)]
 #[derive(Debug)]
 enum Context {
     Document,
     Paragraph,
-    Heading(u8),
-    BlockQuote,
-    List(ListType, Option<u64>), // Type and start number
+    Heading(u8, HeadingProperties),
+    BlockQuote(Option<CalloutKind>),
+    List(ListType, Option<u64>, bool), // Type, start number and looseness
     ListItem,
     Table(Vec<TableAlignment>),
     TableHead,
@@ -59,14 +170,16 @@ struct ParserStack {
     footnotes: HashMap<String, FootnoteDefinition>,
     // Temporary storage for current code block language
     current_code_language: Option<String>,
-    // Temporary storage for TaskListMarker status
-    pending_task_status: Option<bool>,
+    // TaskListMarker status of each open list item, innermost last
+    pending_task_status: Vec<Option<bool>>,
     // Temporary storage for last link index
     last_link_index: Option<usize>,
     // Temporary storage for code block handling
     in_code_block: bool,
     // Temporary storage for code block text
     code_block_text: String,
+    // Source of the raw HTML block being read, if any
+    html_block: Option<String>,
 }
 
 impl ParserStack {
@@ -77,10 +190,11 @@ impl ParserStack {
             formatting: TextFormatting::default(),
             footnotes: HashMap::new(),
             current_code_language: None,
-            pending_task_status: None, // Initialize
+            pending_task_status: Vec::new(),
             last_link_index: None,
             in_code_block: false,
             code_block_text: String::new(),
+            html_block: None,
         }
     }
 
@@ -109,7 +223,7 @@ impl ParserStack {
         // Paragraphs and Headings need to consume the inline accumulator.
         // Other contexts might flush first if they contained loose inlines.
         let inlines_for_context = match self.current_context() {
-            Context::Paragraph | Context::Heading(_) | Context::TableCell => {
+            Context::Paragraph | Context::Heading(..) | Context::TableCell => {
                 // Take the inlines directly, don't flush.
                 Some(std::mem::take(&mut self.inline_accumulator))
             }
@@ -136,15 +250,23 @@ impl ParserStack {
                 children: inlines_for_context
                     .expect("Inlines should have been taken for Paragraph"),
             }),
-            Context::Heading(level) => Some(Node::Heading {
+            Context::Heading(level, properties) => Some(Node::Heading {
                 level,
                 children: inlines_for_context.expect("Inlines should have been taken for Heading"),
+                properties,
             }),
-            Context::BlockQuote => {
+            Context::BlockQuote(kind) => {
                 // BlockQuote children are added via flush_inline_accumulator or popping child contexts.
-                Some(Node::BlockQuote { children })
+                Some(match kind {
+                    Some(kind) => Node::Callout {
+                        kind,
+                        title: None,
+                        children,
+                    },
+                    None => Node::BlockQuote { children },
+                })
             }
-            Context::List(list_type, _) => {
+            Context::List(list_type, start, loose) => {
                 // Extract ListItem structs from TempListItem nodes
                 let items = children
                     .into_iter()
@@ -160,12 +282,17 @@ impl ParserStack {
                         }
                     })
                     .collect();
-                Some(Node::List { list_type, items })
+                Some(Node::List {
+                    list_type,
+                    items,
+                    start: start.filter(|start| *start != 1),
+                    loose,
+                })
             }
             Context::ListItem => {
                 // ListItem children are added via flush_inline_accumulator or popping child contexts.
                 let mut list_item = ListItem::new(children);
-                if let Some(checked) = self.pending_task_status.take() {
+                if let Some(checked) = self.pending_task_status.pop().flatten() {
                     list_item.checked = Some(checked);
                 }
                 Some(Node::TempListItem(list_item))
@@ -201,13 +328,13 @@ impl ParserStack {
         match self.current_context() {
             // If current context expects block nodes, wrap inline in a paragraph
             Context::Document
-            | Context::BlockQuote
+            | Context::BlockQuote(_)
             | Context::ListItem
             | Context::FootnoteDefinition(_) => {
                 self.inline_accumulator.push(inline);
             }
             // If context expects inlines, add directly
-            Context::Paragraph | Context::Heading(_) | Context::TableCell => {
+            Context::Paragraph | Context::Heading(..) | Context::TableCell => {
                 self.inline_accumulator.push(inline);
             }
             // Other contexts might not directly accept inlines
@@ -226,16 +353,17 @@ impl ParserStack {
     /// Flush accumulated inline nodes into a Paragraph node if applicable.
     fn flush_inline_accumulator(&mut self) {
         if !self.inline_accumulator.is_empty() {
-            let inlines = std::mem::take(&mut self.inline_accumulator);
             let node = match self.current_context() {
                 // Only create Paragraphs if the context expects block nodes.
                 Context::Document
-                | Context::BlockQuote
+                | Context::BlockQuote(_)
                 | Context::ListItem
-                | Context::FootnoteDefinition(_) => Some(Node::paragraph_with_inlines(inlines)),
+                | Context::FootnoteDefinition(_) => Some(Node::paragraph_with_inlines(
+                    std::mem::take(&mut self.inline_accumulator),
+                )),
                 // Do nothing if context is already Paragraph/Heading/TableCell
                 // as pop_context will handle the inlines.
-                Context::Paragraph | Context::Heading(_) | Context::TableCell => None,
+                Context::Paragraph | Context::Heading(..) | Context::TableCell => None,
                 _ => {
                     eprintln!(
                         "Warning: Flushing inlines in unexpected context {:?}",
@@ -294,34 +422,258 @@ fn _convert_nodes_to_inlines(nodes: Vec<Node>) -> Option<Vec<InlineNode>> {
     }
 }
 
-/// Parse Markdown text into a Document using a stack-based approach.
+/// A stretch of the source: plain Markdown, or the body of a top-level
+/// `::: kind title` container
+enum Segment<'a> {
+    /// Markdown text and where it starts in the source
+    Markdown(&'a str, usize),
+    Callout {
+        kind: CalloutKind,
+        title: Option<String>,
+        body: &'a str,
+        /// Source range of the whole container, fences included
+        source: Range<usize>,
+    },
+}
+
+/// Reads a `::: kind title` container opening line
+fn container_opening(line: &str) -> Option<(CalloutKind, Option<String>)> {
+    let rest = line.strip_prefix(":::")?.trim_start_matches(':').trim();
+    let (name, title) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let kind = CalloutKind::from_name(name)?;
+    let title = title.trim();
+    Some((kind, (!title.is_empty()).then(|| title.to_string())))
+}
+
+/// Returns true for a line of three or more colons, which closes a container
+fn is_container_closing(line: &str) -> bool {
+    let line = line.trim_end();
+    line.len() >= 3 && line.chars().all(|c| c == ':')
+}
+
+/// Splits the source at top-level `:::` containers
+///
+/// Only containers starting in the first column and outside code fences
+/// are recognized; nested ones are found when the body is parsed in turn.
+/// An unclosed container runs to the end of the source.
+fn split_containers(markdown: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut in_fence = false;
+    // Kind, title, opening line start, body start and nesting depth of the
+    // open container
+    let mut open: Option<(CalloutKind, Option<String>, usize, usize, usize)> = None;
+
+    for line in markdown.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+        let trimmed = text.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        match &mut open {
+            None => {
+                if let Some((kind, title)) = container_opening(text) {
+                    if start < line_start {
+                        segments.push(Segment::Markdown(&markdown[start..line_start], start));
+                    }
+                    open = Some((kind, title, line_start, offset, 0));
+                }
+            }
+            Some((kind, title, open_start, body_start, depth)) => {
+                if is_container_closing(text) {
+                    if *depth == 0 {
+                        segments.push(Segment::Callout {
+                            kind: *kind,
+                            title: title.take(),
+                            body: &markdown[*body_start..line_start],
+                            source: *open_start..offset,
+                        });
+                        open = None;
+                        start = offset;
+                    } else {
+                        *depth -= 1;
+                    }
+                } else if text.starts_with(":::") {
+                    *depth += 1;
+                }
+            }
+        }
+    }
+
+    if let Some((kind, title, open_start, body_start, _)) = open {
+        segments.push(Segment::Callout {
+            kind,
+            title,
+            body: &markdown[body_start..],
+            source: open_start..markdown.len(),
+        });
+    } else if start < markdown.len() {
+        segments.push(Segment::Markdown(&markdown[start..], start));
+    }
+    segments
+}
+
+/// Inline raw HTML, or a comment if it is exactly one
+fn inline_html(html: String) -> InlineNode {
+    match comment_text(&html) {
+        Some(text) => InlineNode::html_comment(text),
+        None => InlineNode::Html { html },
+    }
+}
+
+/// Parse Markdown text into a Document
+///
+/// Top-level `::: kind title` containers become callouts, parsed
+/// separately from the Markdown around them; GitHub `> [!NOTE]` alerts
+/// are recognized by the block parser. Blocks between `<!-- draft -->`
+/// or `<!-- hidden -->` comment markers become visibility blocks, and
+/// blocks between `<!-- toc:N -->` and `<!-- /toc -->` a managed table of
+/// contents. `[@key]` citations are parsed, and a generated references
+/// section between `<!-- references -->` markers is dropped.
 pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
+    parse_markdown_with_spans(markdown).map(|(document, _)| document)
+}
+
+/// Parse Markdown text into a Document, along with the source byte range
+/// of each top-level node
+pub(crate) fn parse_markdown_with_spans(
+    markdown: &str,
+) -> Result<(Document, Vec<Range<usize>>), ParseError> {
+    let segments = split_containers(markdown);
+    let (mut document, mut spans) = if !segments
+        .iter()
+        .any(|segment| matches!(segment, Segment::Callout { .. }))
+    {
+        parse_blocks(markdown)?
+    } else {
+        let mut document = Document::new();
+        let mut spans = Vec::new();
+        for segment in segments {
+            match segment {
+                Segment::Markdown(text, start) => {
+                    let (parsed, parsed_spans) = parse_blocks(text)?;
+                    document.nodes.extend(parsed.nodes);
+                    spans.extend(
+                        parsed_spans
+                            .into_iter()
+                            .map(|span| span.start + start..span.end + start),
+                    );
+                }
+                Segment::Callout {
+                    kind,
+                    title,
+                    body,
+                    source,
+                } => {
+                    document.nodes.push(Node::Callout {
+                        kind,
+                        title,
+                        children: parse_markdown(body)?.nodes,
+                    });
+                    spans.push(source);
+                }
+            }
+        }
+        (document, spans)
+    };
+
+    while let Some(section) = citations::references_section(&document.nodes) {
+        spans.drain(section.start.min(spans.len())..section.end.min(spans.len()));
+        document.nodes.drain(section);
+    }
+    let toc_ends: Vec<usize> = (0..document.nodes.len())
+        .filter(|&index| toc::is_closing_marker(&document.nodes[index]))
+        .collect();
+    wrap_markers(&mut document.nodes);
+    toc::wrap_markers(&mut document.nodes);
+    document.parse_citations();
+    let mut wrapped_spans = Vec::with_capacity(document.nodes.len());
+    let mut next = 0;
+    for node in &document.nodes {
+        let end = consumed_nodes(node, next, &toc_ends);
+        if let (Some(first), Some(last)) = (spans.get(next), spans.get(end - 1)) {
+            wrapped_spans.push(first.start..last.end);
+        }
+        next = end;
+    }
+    Ok((document, wrapped_spans))
+}
+
+/// The index past the nodes from before marker wrapping that a top-level
+/// node wrapped, given the index of the first of them and the indices of
+/// table of contents closing markers
+///
+/// Visibility blocks wrap their markers and the nodes their children
+/// wrapped; a table of contents runs up to the next closing marker.
+fn consumed_nodes(node: &Node, start: usize, toc_ends: &[usize]) -> usize {
+    match node {
+        Node::VisibilityBlock { children, .. } => {
+            let end = children.iter().fold(start + 1, |next, child| {
+                consumed_nodes(child, next, toc_ends)
+            });
+            end + 1
+        }
+        Node::TableOfContents { .. } => toc_ends
+            .iter()
+            .find(|&&end| end > start)
+            .map_or(start + 1, |end| end + 1),
+        _ => start + 1,
+    }
+}
+
+/// Parse Markdown text into a Document using a stack-based approach,
+/// along with the source byte range of each top-level node
+fn parse_blocks(markdown: &str) -> Result<(Document, Vec<Range<usize>>), ParseError> {
     let options = Options::all();
     let parser = Parser::new_ext(markdown, options);
     let mut stack = ParserStack::new();
     let mut current_table_state: Option<TableState> = None;
+    // Source range of the top-level block being read
+    let mut block = 0..0;
+    let mut spans: Vec<Range<usize>> = Vec::new();
 
-    let mut events = parser.peekable();
+    let mut events = parser.into_offset_iter().peekable();
 
-    while let Some(event) = events.next() {
+    while let Some((event, range)) = events.next() {
+        if stack.stack.len() == 1 && !stack.in_code_block && stack.html_block.is_none() {
+            block = range;
+        }
         match event {
             Event::Start(tag) => match tag {
                 Tag::Paragraph => {
                     // Flush before starting a new paragraph context *if needed* by the parent context.
                     match stack.current_context() {
                         Context::ListItem
-                        | Context::BlockQuote
+                        | Context::BlockQuote(_)
                         | Context::FootnoteDefinition(_) => stack.flush_inline_accumulator(),
                         _ => {}
                     }
+                    // Items of tight lists hold bare text, not paragraphs
+                    if let Context::ListItem = stack.current_context() {
+                        let list = stack.stack.len().checked_sub(2);
+                        if let Some((Context::List(_, _, loose), _)) =
+                            list.and_then(|index| stack.stack.get_mut(index))
+                        {
+                            *loose = true;
+                        }
+                    }
                     stack.push_context(Context::Paragraph)
                 }
-                Tag::Heading { level, .. } => {
-                    stack.push_context(Context::Heading(level_to_u8(level)))
-                }
-                Tag::BlockQuote(_) => {
+                Tag::Heading { level, attrs, .. } => stack.push_context(Context::Heading(
+                    level_to_u8(level),
+                    heading_properties(&attrs),
+                )),
+                Tag::BlockQuote(kind) => {
                     stack.flush_inline_accumulator(); // Flush before block node
-                    stack.push_context(Context::BlockQuote);
+                    stack.push_context(Context::BlockQuote(kind.map(callout_kind)));
                 }
                 Tag::CodeBlock(kind) => {
                     stack.flush_inline_accumulator(); // Ensure pending text becomes a node
@@ -340,9 +692,12 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                         Some(_) => ListType::Ordered,
                         None => ListType::Unordered, // Initial assumption, may change to Task
                     };
-                    stack.push_context(Context::List(list_type, start));
+                    stack.push_context(Context::List(list_type, start, false));
+                }
+                Tag::Item => {
+                    stack.pending_task_status.push(None);
+                    stack.push_context(Context::ListItem)
                 }
-                Tag::Item => stack.push_context(Context::ListItem),
                 Tag::FootnoteDefinition(label) => {
                     stack.push_context(Context::FootnoteDefinition(label.into_string()))
                 }
@@ -389,12 +744,12 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                     stack.flush_inline_accumulator();
                     let mut alt_text = String::new();
                     // Peek ahead for the Text event containing alt text
-                    if let Some(Event::Text(alt)) = events.peek() {
+                    if let Some((Event::Text(alt), _)) = events.peek() {
                         alt_text = alt.to_string();
                         events.next(); // Consume the peeked Text event
                     }
                     // Expect End(Image) next, consume it if present
-                    if let Some(Event::End(TagEnd::Image)) = events.peek() {
+                    if let Some((Event::End(TagEnd::Image), _)) = events.peek() {
                         events.next();
                     } else {
                         eprintln!("Warning: Expected End(Image) after Image start/alt text");
@@ -413,18 +768,36 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
 
                     stack.push_inline(image_node);
                 }
+                Tag::HtmlBlock => {
+                    stack.flush_inline_accumulator();
+                    stack.html_block = Some(String::new());
+                }
                 // Add autolink handler as a separate case in the Event::Start(tag) match
                 Tag::Link {
                     link_type,
                     dest_url,
                     title,
-                    ..
+                    id,
                 } => {
                     // Check if it's an autolink or if it looks like an email address
                     let url_str = dest_url.into_string();
                     let is_email_format = url_str.contains('@') && !url_str.contains("://");
 
-                    if link_type == LinkType::Autolink || is_email_format {
+                    if let LinkType::WikiLink { has_pothole } = link_type {
+                        // The text of a piped wiki-link is its alias
+                        let mut alias = String::new();
+                        for (event, _) in events.by_ref() {
+                            match event {
+                                Event::End(TagEnd::Link) => break,
+                                Event::Text(text) | Event::Code(text) => alias.push_str(&text),
+                                _ => continue,
+                            }
+                        }
+                        stack.push_inline(InlineNode::WikiLink {
+                            target: url_str,
+                            alias: has_pothole.then_some(alias),
+                        });
+                    } else if link_type == LinkType::Autolink || is_email_format {
                         // Convert to AutoLink node for both URL and email autolinks
                         stack.push_inline(InlineNode::AutoLink {
                             url: url_str,
@@ -432,7 +805,7 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                         });
 
                         // Skip the URL text content and end tag, as they're redundant for autolinks
-                        for event in events.by_ref() {
+                        for (event, _) in events.by_ref() {
                             match event {
                                 Event::End(TagEnd::Link) => break,
                                 _ => continue,
@@ -448,6 +821,13 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                                 Some(title.into_string())
                             },
                             children: vec![], // Will be populated later
+                            // Keep the label of reference-style links for export
+                            reference: match link_type {
+                                LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut => {
+                                    Some(id.into_string())
+                                }
+                                _ => None,
+                            },
                         };
 
                         // Store the link and its index in the accumulator for later
@@ -465,6 +845,11 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                     TagEnd::Paragraph => {
                         // Paragraph end always pops the Paragraph context.
                         if let Some(node) = stack.pop_context() {
+                            // A bare provider URL on its own line becomes an embed, and
+                            // a captioned image becomes a figure
+                            let node = paragraph_to_embed(&node, &DefaultEmbedResolver)
+                                .or_else(|| paragraph_to_figure(&node))
+                                .unwrap_or(node);
                             stack.current_nodes().push(node);
                         }
                     }
@@ -525,8 +910,21 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                     }
                     TagEnd::TableHead => {
                         if let Context::TableHead = *stack.current_context() {
-                            // Pop TableHead context - pop_context returns None
-                            let _ = stack.pop_context();
+                            // Header cells come straight inside the head, without a row
+                            let (_, children) =
+                                stack.stack.pop().expect("TableHead should be on stack");
+                            let header_cells: Vec<TableCell> = children
+                                .into_iter()
+                                .filter_map(|node| match node {
+                                    Node::TempTableCell(cell) => Some(cell),
+                                    _ => None,
+                                })
+                                .collect();
+                            if let Some(ref mut table) = current_table_state
+                                && !header_cells.is_empty()
+                            {
+                                table.header = header_cells;
+                            }
                         } else {
                             eprintln!("Warning: TableHeadEnd without TableHead context");
                         }
@@ -571,22 +969,34 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                     TagEnd::Strikethrough => stack.formatting.strikethrough = false,
                     TagEnd::Link => {
                         // If we have a last link index, we need to update the link's children
-                        if let Some(link_index) = stack.last_link_index.take() {
-                            if link_index < stack.inline_accumulator.len() {
-                                // Get the accumulated text nodes that should become the link's children
-                                let accumulated_since_link: Vec<InlineNode> =
-                                    stack.inline_accumulator.drain(link_index + 1..).collect();
-
-                                // Update the link's children
-                                if let InlineNode::Link { children, .. } =
-                                    &mut stack.inline_accumulator[link_index]
-                                {
-                                    *children = accumulated_since_link;
-                                }
+                        if let Some(link_index) = stack.last_link_index.take()
+                            && link_index < stack.inline_accumulator.len()
+                        {
+                            // Get the accumulated text nodes that should become the link's children
+                            let accumulated_since_link: Vec<InlineNode> =
+                                stack.inline_accumulator.drain(link_index + 1..).collect();
+
+                            // Update the link's children
+                            if let InlineNode::Link { children, .. } =
+                                &mut stack.inline_accumulator[link_index]
+                            {
+                                *children = accumulated_since_link;
                             }
                         }
                     }
                     TagEnd::Image => { /* Handled at Start? */ }
+                    TagEnd::HtmlBlock => {
+                        if let Some(html) = stack.html_block.take() {
+                            let html = html.trim_end_matches('\n');
+                            let node = match comment_text(html) {
+                                Some(text) => Node::html_comment(text),
+                                None => Node::HtmlBlock {
+                                    html: html.to_string(),
+                                },
+                            };
+                            stack.current_nodes().push(node);
+                        }
+                    }
                     TagEnd::CodeBlock => {
                         if stack.in_code_block {
                             let language = stack.current_code_language.take().unwrap_or_default();
@@ -622,10 +1032,11 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
                 }
             }
             Event::Code(text) => stack.push_inline(InlineNode::code_span(text.into_string())),
-            Event::Html(html) => {
-                // Decide how to handle raw HTML. Convert to text? Special node?
-                stack.handle_text(html.into_string()); // Treat as text for now
-            }
+            Event::Html(html) => match &mut stack.html_block {
+                Some(block) => block.push_str(&html),
+                None => stack.push_inline(inline_html(html.into_string())),
+            },
+            Event::InlineHtml(html) => stack.push_inline(inline_html(html.into_string())),
             Event::FootnoteReference(label) => {
                 stack.push_inline(InlineNode::footnote_ref(label.into_string()));
             }
@@ -637,19 +1048,21 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
             }
             Event::TaskListMarker(checked) => {
                 // Store the status temporarily. It will be applied when the ListItem context is popped.
-                stack.pending_task_status = Some(checked);
+                if let Some(status) = stack.pending_task_status.last_mut() {
+                    *status = Some(checked);
+                }
 
                 // The old logic tried to modify the stack directly, which was complex.
                 // We still need to update the parent List type.
                 let mut list_context_index = None;
                 for (index, (context, _)) in stack.stack.iter().enumerate().rev() {
-                    if let Context::List(_, _) = context {
+                    if let Context::List(..) = context {
                         list_context_index = Some(index);
                         break;
                     }
                 }
                 if let Some(idx) = list_context_index {
-                    if let Some((Context::List(list_type, _), _)) = stack.stack.get_mut(idx) {
+                    if let Some((Context::List(list_type, ..), _)) = stack.stack.get_mut(idx) {
                         *list_type = ListType::Task;
                     }
                 } else {
@@ -661,6 +1074,7 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
             // Add catch-all for other Event types
             _ => { /* Optional: Log unhandled Events */ }
         }
+        spans.resize(stack.stack[0].1.len(), block.clone());
     }
 
     // Finalize the document
@@ -680,10 +1094,11 @@ pub(crate) fn parse_markdown(markdown: &str) -> Result<Document, ParseError> {
 
     let (_doc_context, nodes) = stack.stack.pop().expect("Stack should have Document root");
     let mut document = Document::new();
+    spans.resize(nodes.len(), block);
     document.nodes = nodes;
     // Add footnotes? The original code didn't add them to the Document struct.
     // document.footnotes = stack.footnotes;
-    Ok(document)
+    Ok((document, spans))
 }
 
 // Temporary struct for new table state mgmt (integrate with ParserStack)
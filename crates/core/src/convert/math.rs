@@ -0,0 +1,446 @@
+//! TeX rendering for HTML export
+//!
+//! By default math is exported as its TeX source between `$` signs and left
+//! for a client-side script to typeset. An [`HtmlMathMode`] set on
+//! [`HtmlOptions`](super::html::HtmlOptions::with_math_mode) picks other
+//! delimiters or renders it during export instead: [`MathMlRenderer`] covers
+//! the TeX most documents use and produces MathML, which browsers display
+//! natively, and with the `katex` feature `KatexRenderer` renders all of
+//! KaTeX's TeX to HTML. Any other [`MathRenderer`] can be plugged in too.
+
+use super::html_escape;
+
+/// Renders TeX to HTML
+pub trait MathRenderer {
+    /// Renders `tex` inline or, with `display`, as a block
+    ///
+    /// Returns a human-readable message if the TeX is invalid or uses
+    /// something the renderer does not support.
+    fn render(&self, tex: &str, display: bool) -> Result<String, String>;
+}
+
+/// How HTML export writes math
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HtmlMathMode {
+    /// TeX source between `$` signs
+    #[default]
+    Raw,
+    /// TeX source between `\(...\)` inline and `\[...\]` for blocks, the
+    /// delimiters MathJax and KaTeX's auto-render extension look for by
+    /// default
+    MathJax,
+    /// MathML from the built-in [`MathMlRenderer`]
+    MathMl,
+    /// HTML from [`KatexRenderer`], which only needs the KaTeX stylesheet
+    #[cfg(feature = "katex")]
+    Katex,
+}
+
+/// Renders TeX to HTML with KaTeX during export
+///
+/// The output uses KaTeX's HTML and MathML markup, so pages need the KaTeX
+/// stylesheet but no scripts.
+#[cfg(feature = "katex")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KatexRenderer;
+
+#[cfg(feature = "katex")]
+impl MathRenderer for KatexRenderer {
+    fn render(&self, tex: &str, display: bool) -> Result<String, String> {
+        let opts = katex::Opts::builder()
+            .display_mode(display)
+            .build()
+            .map_err(|err| err.to_string())?;
+        katex::render_with_opts(tex, &opts).map_err(|err| err.to_string())
+    }
+}
+
+/// Converts common TeX to MathML
+///
+/// Supports numbers, identifiers, operators, sub- and superscripts, groups,
+/// `\frac`, `\sqrt`, `\text`, `\left`/`\right`, spacing commands, Greek
+/// letters, common symbols and function names. Environments and alignment
+/// are not supported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MathMlRenderer;
+
+impl MathRenderer for MathMlRenderer {
+    fn render(&self, tex: &str, display: bool) -> Result<String, String> {
+        let mut parser = Parser {
+            chars: tex.chars().collect(),
+            pos: 0,
+        };
+        let body = parser.row(None)?;
+        let display = if display { " display=\"block\"" } else { "" };
+        Ok(format!(
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"{display}>{body}</math>"
+        ))
+    }
+}
+
+const IDENTIFIERS: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("ell", "ℓ"),
+];
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("times", "×"),
+    ("cdot", "⋅"),
+    ("div", "÷"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("le", "≤"),
+    ("leq", "≤"),
+    ("ge", "≥"),
+    ("geq", "≥"),
+    ("ne", "≠"),
+    ("neq", "≠"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("propto", "∝"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftrightarrow", "⇔"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("land", "∧"),
+    ("lor", "∨"),
+    ("circ", "∘"),
+    ("mid", "∣"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("int", "∫"),
+    ("oint", "∮"),
+    ("ldots", "…"),
+    ("cdots", "⋯"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "log", "ln", "exp", "lim", "max", "min", "sup",
+    "inf", "det", "gcd", "arg",
+];
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, symbol)| *symbol)
+}
+
+/// Wraps several elements in an `mrow`, leaving a single one alone
+fn mrow(mut items: Vec<String>) -> String {
+    if items.len() == 1 {
+        items.remove(0)
+    } else {
+        format!("<mrow>{}</mrow>", items.concat())
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+        self.pos += ch.is_some() as usize;
+        ch
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses elements up to `closing`, or the end of the input without one
+    fn row(&mut self, closing: Option<char>) -> Result<String, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            match (self.peek(), closing) {
+                (None, None) => break,
+                (None, Some(closing)) => return Err(format!("missing closing '{closing}'")),
+                (Some(ch), Some(closing)) if ch == closing => {
+                    self.pos += 1;
+                    break;
+                }
+                (Some('}'), _) => return Err("unexpected '}'".to_string()),
+                (Some(ch @ ('^' | '_')), _) => return Err(format!("'{ch}' without a base")),
+                _ => {
+                    let base = self.atom()?;
+                    items.push(self.scripts(base)?);
+                }
+            }
+        }
+        Ok(mrow(items))
+    }
+
+    /// Attaches any subscript and superscript following `base`
+    fn scripts(&mut self, base: String) -> Result<String, String> {
+        let (mut sub, mut sup) = (None, None);
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some('_') if sub.is_none() => {
+                    self.pos += 1;
+                    sub = Some(self.argument("_")?);
+                }
+                Some('^') if sup.is_none() => {
+                    self.pos += 1;
+                    sup = Some(self.argument("^")?);
+                }
+                _ => break,
+            }
+        }
+        Ok(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<msub>{base}{sub}</msub>"),
+            (None, Some(sup)) => format!("<msup>{base}{sup}</msup>"),
+            (Some(sub), Some(sup)) => format!("<msubsup>{base}{sub}{sup}</msubsup>"),
+        })
+    }
+
+    /// Parses the single element or group a command or script applies to
+    fn argument(&mut self, of: &str) -> Result<String, String> {
+        self.skip_space();
+        match self.peek() {
+            None | Some('}' | '^' | '_') => Err(format!("missing argument for {of}")),
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<String, String> {
+        let Some(ch) = self.next() else {
+            return Err("unexpected end of input".to_string());
+        };
+        match ch {
+            '{' => self.row(Some('}')),
+            '\\' => self.command(),
+            '&' => Err("alignment is not supported".to_string()),
+            ch if ch.is_ascii_digit() || ch == '.' => {
+                let start = self.pos - 1;
+                while self
+                    .peek()
+                    .is_some_and(|ch| ch.is_ascii_digit() || ch == '.')
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                Ok(format!("<mn>{number}</mn>"))
+            }
+            ch if ch.is_alphabetic() => Ok(format!("<mi>{ch}</mi>")),
+            ch => Ok(format!("<mo>{}</mo>", html_escape(&ch.to_string()))),
+        }
+    }
+
+    /// Reads the text of a `{...}` group without interpreting it
+    fn raw_group(&mut self, of: &str) -> Result<String, String> {
+        self.skip_space();
+        if self.next() != Some('{') {
+            return Err(format!("missing argument for {of}"));
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(ch) = self.next() {
+            match ch {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    return Ok(self.chars[start..self.pos - 1].iter().collect());
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        Err("missing closing '}'".to_string())
+    }
+
+    /// Reads the delimiter after `\left` or `\right`
+    fn delimiter(&mut self, of: &str) -> Result<String, String> {
+        self.skip_space();
+        match self.next() {
+            Some('.') => Ok(String::new()),
+            Some('\\') => self.command(),
+            Some(ch) if !ch.is_alphanumeric() && ch != '{' && ch != '}' => {
+                Ok(format!("<mo>{}</mo>", html_escape(&ch.to_string())))
+            }
+            _ => Err(format!("missing delimiter for {of}")),
+        }
+    }
+
+    fn command(&mut self) -> Result<String, String> {
+        let name: String = if self.peek().is_some_and(|ch| ch.is_ascii_alphabetic()) {
+            let start = self.pos;
+            while self.peek().is_some_and(|ch| ch.is_ascii_alphabetic()) {
+                self.pos += 1;
+            }
+            self.chars[start..self.pos].iter().collect()
+        } else {
+            match self.next() {
+                Some(ch) => ch.to_string(),
+                None => return Err("'\\' at end of input".to_string()),
+            }
+        };
+
+        match name.as_str() {
+            "frac" => {
+                let numerator = self.argument("\\frac")?;
+                let denominator = self.argument("\\frac")?;
+                Ok(format!("<mfrac>{numerator}{denominator}</mfrac>"))
+            }
+            "sqrt" => {
+                self.skip_space();
+                if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let index = self.row(Some(']'))?;
+                    let radicand = self.argument("\\sqrt")?;
+                    Ok(format!("<mroot>{radicand}{index}</mroot>"))
+                } else {
+                    Ok(format!("<msqrt>{}</msqrt>", self.argument("\\sqrt")?))
+                }
+            }
+            "text" => Ok(format!(
+                "<mtext>{}</mtext>",
+                html_escape(&self.raw_group("\\text")?)
+            )),
+            "mathrm" | "operatorname" => Ok(format!(
+                "<mi mathvariant=\"normal\">{}</mi>",
+                html_escape(&self.raw_group(&format!("\\{name}"))?)
+            )),
+            "left" | "right" => self.delimiter(&format!("\\{name}")),
+            "," | ":" | ";" | " " => Ok("<mspace width=\"0.2em\"/>".to_string()),
+            "!" => Ok(String::new()),
+            "quad" => Ok("<mspace width=\"1em\"/>".to_string()),
+            "qquad" => Ok("<mspace width=\"2em\"/>".to_string()),
+            "{" | "}" | "%" | "$" | "#" | "&" | "_" | "|" => {
+                Ok(format!("<mo>{}</mo>", html_escape(&name)))
+            }
+            "\\" => Err("line breaks are not supported".to_string()),
+            "begin" | "end" => Err("environments are not supported".to_string()),
+            name if FUNCTIONS.contains(&name) => Ok(format!("<mi>{name}</mi>")),
+            name => {
+                if let Some(symbol) = lookup(IDENTIFIERS, name) {
+                    Ok(format!("<mi>{symbol}</mi>"))
+                } else if let Some(symbol) = lookup(OPERATORS, name) {
+                    Ok(format!("<mo>{symbol}</mo>"))
+                } else {
+                    Err(format!("unknown command \\{name}"))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mathml(tex: &str) -> Result<String, String> {
+        MathMlRenderer.render(tex, false).map(|math| {
+            math.trim_start_matches("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">")
+                .trim_end_matches("</math>")
+                .to_string()
+        })
+    }
+
+    #[test]
+    fn test_mathml_rendering() {
+        assert_eq!(
+            mathml("a^2 + b^2").unwrap(),
+            "<mrow><msup><mi>a</mi><mn>2</mn></msup><mo>+</mo><msup><mi>b</mi><mn>2</mn></msup></mrow>"
+        );
+        assert_eq!(
+            mathml("\\frac{1}{\\sqrt[3]{x}}").unwrap(),
+            "<mfrac><mn>1</mn><mroot><mi>x</mi><mn>3</mn></mroot></mfrac>"
+        );
+        assert_eq!(
+            mathml("\\sum_{i=0}^n \\alpha_i").unwrap(),
+            "<mrow><msubsup><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>0</mn></mrow><mi>n</mi></msubsup><msub><mi>α</mi><mi>i</mi></msub></mrow>"
+        );
+        assert_eq!(
+            mathml("\\left( x < 1 \\right)").unwrap(),
+            "<mrow><mo>(</mo><mi>x</mi><mo>&lt;</mo><mn>1</mn><mo>)</mo></mrow>"
+        );
+        assert!(
+            MathMlRenderer
+                .render("x", true)
+                .unwrap()
+                .contains("display=\"block\"")
+        );
+    }
+
+    #[cfg(feature = "katex")]
+    #[test]
+    fn test_katex_rendering() {
+        let html = KatexRenderer.render("a^2", true).unwrap();
+        assert!(html.contains("katex-display"));
+        assert!(KatexRenderer.render("\\frac{1}", false).is_err());
+    }
+
+    #[test]
+    fn test_mathml_errors() {
+        assert_eq!(mathml("\\foo").unwrap_err(), "unknown command \\foo");
+        assert_eq!(mathml("{x").unwrap_err(), "missing closing '}'");
+        assert_eq!(mathml("x}").unwrap_err(), "unexpected '}'");
+        assert_eq!(mathml("x^").unwrap_err(), "missing argument for ^");
+        assert_eq!(
+            mathml("\\frac{1}").unwrap_err(),
+            "missing argument for \\frac"
+        );
+        assert!(mathml("\\begin{matrix}").is_err());
+    }
+}
@@ -1,12 +1,49 @@
 use std::{convert::Infallible, fmt, ops::Deref, str::FromStr};
 
+#[cfg(feature = "ansi")]
+pub mod ansi;
+#[cfg(feature = "asciidoc")]
+pub mod asciidoc;
+#[cfg(feature = "binary")]
+pub mod binary;
+pub mod format;
+#[cfg(feature = "syntax-highlighting")]
+pub mod highlight;
 pub mod html;
+mod importer;
+pub mod ipynb;
 pub mod json;
+pub mod locale;
 pub mod markdown;
-
+pub mod math;
+pub mod org;
+pub mod render;
+pub mod rst;
+pub mod sanitize;
+pub mod template;
+
+/// The AsciiDoc format
+#[cfg(feature = "asciidoc")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsciiDoc;
+/// The HTML format
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Html;
+/// The Jupyter notebook format, which is only parsed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ipynb;
+/// The JSON format, which keeps the whole document
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Json;
+/// The Markdown format
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Markdown;
+/// The Emacs Org-mode format
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Org;
+/// The reStructuredText format, which is only parsed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rst;
 
 pub struct Text<T> {
     text: String,
@@ -88,7 +125,10 @@ mod tests {
         let html: Result<Text<Html>, _> = doc.as_ref().try_into();
         assert!(html.is_ok());
         let html = html.unwrap();
-        assert!(html.as_str().contains("<h1>Test Document</h1>"));
+        assert!(
+            html.as_str()
+                .contains("<h1 id=\"test-document\">Test Document</h1>")
+        );
 
         // HTML to Document
         let doc2: Result<Document, _> = html.try_into();
@@ -0,0 +1,1733 @@
+//! Org-mode import and export
+//!
+//! Headlines with a TODO state are tasks: consecutive TODO headlines of one
+//! level become a task list whose items hold the headline text, the section
+//! under it and the TODO headlines below it, checked when the state is a
+//! done state. The states come from `#+TODO:` lines, defaulting to `TODO`
+//! and `DONE`. A task list ending a section is written back as TODO
+//! headlines one level below the section's heading; other task lists are
+//! written as checkbox items, which Org also treats as tasks.
+//!
+//! Besides headlines, the parser reads paragraphs, plain, checkbox and
+//! description lists, tables with alignment cookies and captions,
+//! `#+BEGIN_SRC` and `#+BEGIN_EXAMPLE` blocks with their `-n` numbering,
+//! fixed-width lines, quote, verse, comment and HTML export blocks, special
+//! blocks (admonitions become callouts, others groups), `\[...\]` math,
+//! footnotes, image links as figures, `#+TITLE:`, `#+AUTHOR:` and
+//! `#+DATE:` metadata, and the inline markup, links and footnote
+//! references. Drawers, planning lines, tags and priorities are dropped.
+//! Rendering loses cell spans, table styling, typed cell values, code block
+//! themes and highlighted lines, and embed details.
+
+use super::Org;
+use super::format::{Capabilities, FormatConverter};
+use super::importer::{dedent, find, indent, is_blank, merge_text};
+use crate::links::has_scheme;
+use crate::merge::conflict_markers;
+use crate::outline::slugify;
+use crate::visibility::is_published;
+use crate::{
+    CalloutKind, CodeBlockProperties, DefinitionItem, Document, DocumentMetadata, Feature,
+    FigureProperties, FootnoteDefinition, InlineNode, ListItem, ListType, Node, ParseError,
+    TableAlignment, TableCell, TableProperties, TextFormatting, TextNode,
+};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static HEADLINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\*+) +(.*)$").unwrap());
+static TAGS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+:[\w@#%:]+:$").unwrap());
+static PRIORITY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[#[A-Za-z0-9]\] *").unwrap());
+static KEYWORD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^#\+([A-Za-z_]+):(?: +(.*))?$").unwrap());
+static BLOCK_BEGIN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^#\+begin_(\S+)(?: +(.*))?$").unwrap());
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^( *)([-+*]|(\d+)[.)])(?: +(.*))?$").unwrap());
+static COUNTER_COOKIE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[@(\d+)\] *").unwrap());
+static CHECKBOX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[([ xX-])\](?: +|$)").unwrap());
+static FOOTNOTE_DEFINITION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[fn:([^\]:\s]+)\](?: +(.*))?$").unwrap());
+static ALIGNMENT_COOKIE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<([lcr])?\d*>$").unwrap());
+static PLANNING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:(?:SCHEDULED|DEADLINE|CLOSED): *\S.*)+$").unwrap());
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:https?|ftp)://[^\s\[\]<>]+").unwrap());
+
+impl FormatConverter for Org {
+    fn name(&self) -> &str {
+        "org"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["org"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            lost_features: vec![
+                Feature::CellSpans,
+                Feature::TableStyling,
+                Feature::CellValues,
+                Feature::CodeBlockOptions,
+                Feature::Embeds,
+            ],
+            ..Capabilities::lossless()
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| line.replace('\t', "        ").trim_end().to_string())
+            .collect();
+        let mut parser = OrgParser::new(&lines);
+        let mut document = Document::new();
+        document.nodes = parser.blocks(&lines);
+        document
+            .nodes
+            .extend(parser.footnotes.into_iter().map(Node::FootnoteDefinition));
+        if parser.metadata != DocumentMetadata::default() {
+            document.metadata = Some(parser.metadata);
+        }
+        Ok(document)
+    }
+
+    fn render(&self, document: &Document) -> Result<String, ParseError> {
+        let mut out = Vec::new();
+        if let Some(metadata) = &document.metadata {
+            let keywords: Vec<String> = [
+                ("TITLE", &metadata.title),
+                ("AUTHOR", &metadata.author),
+                ("DATE", &metadata.date),
+            ]
+            .into_iter()
+            .filter_map(|(name, value)| Some(format!("#+{name}: {}", value.as_ref()?)))
+            .collect();
+            if !keywords.is_empty() {
+                out.push(keywords.join("\n"));
+            }
+        }
+        out.extend(sections(&document.nodes));
+        let mut text = out.join("\n\n");
+        text.push('\n');
+        Ok(text)
+    }
+}
+
+/// Indents the non-blank lines of `text` by `width` spaces
+fn indented(text: &str, width: usize) -> String {
+    let prefix = " ".repeat(width);
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn is_rule(line: &str) -> bool {
+    line.len() >= 5 && line.chars().all(|c| c == '-')
+}
+
+fn is_fixed_width(line: &str) -> bool {
+    line == ":" || line.starts_with(": ")
+}
+
+fn is_image(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit_once('.').is_some_and(|(_, extension)| {
+        matches!(
+            extension.to_ascii_lowercase().as_str(),
+            "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "bmp" | "avif"
+        )
+    })
+}
+
+/// The URL an Org link target points to
+///
+/// `file:` links become paths, `*Heading` and named targets become
+/// anchors, and URLs with a scheme are kept.
+fn link_url(target: &str) -> String {
+    if let Some(path) = target.strip_prefix("file:") {
+        return path.split("::").next().unwrap_or_default().to_string();
+    }
+    if let Some(heading) = target.strip_prefix('*') {
+        return format!("#{}", slugify(heading));
+    }
+    if target.starts_with('#') || has_scheme(target) || target.contains(['/', '.']) {
+        return target.to_string();
+    }
+    format!("#{}", slugify(target))
+}
+
+/// The Org link target for a URL
+fn link_target(url: &str) -> String {
+    if url.starts_with('#') || has_scheme(url) || url.starts_with(['/', '.', '~']) {
+        url.to_string()
+    } else {
+        format!("file:{url}")
+    }
+}
+
+/// Escapes the lines of a source block Org would read as headlines or
+/// keywords
+fn escape_code(code: &str) -> String {
+    code.lines()
+        .map(|line| {
+            let content = line.trim_start();
+            let content = content.strip_prefix(',').unwrap_or(content);
+            if content.starts_with('*') || content.starts_with("#+") {
+                let width = indent(line);
+                format!("{},{}", &line[..width], &line[width..])
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn unescape_code(line: &str) -> String {
+    let width = indent(line);
+    match line[width..].strip_prefix(',') {
+        Some(rest) if rest.starts_with('*') || rest.starts_with("#+") => {
+            format!("{}{rest}", &line[..width])
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Renders top-level nodes, writing a task list that ends a section as
+/// TODO headlines below the section's heading
+fn sections(nodes: &[Node]) -> Vec<String> {
+    let mut out = Vec::new();
+    for (index, node) in nodes.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|previous| &nodes[previous]);
+        if let (
+            Some(Node::Heading { level, .. }),
+            Node::List {
+                list_type: ListType::Task,
+                items,
+                ..
+            },
+        ) = (previous, node)
+            && matches!(nodes.get(index + 1), None | Some(Node::Heading { .. }))
+            && headline_tasks(items, level + 1)
+        {
+            out.push(task_headlines(items, level + 1));
+            continue;
+        }
+        out.extend(block(node));
+    }
+    out
+}
+
+fn blocks(nodes: &[Node]) -> Vec<String> {
+    nodes.iter().filter_map(block).collect()
+}
+
+fn block(node: &Node) -> Option<String> {
+    let block = match node {
+        Node::Heading {
+            level, children, ..
+        } => format!(
+            "{} {}",
+            "*".repeat((*level).max(1) as usize),
+            inlines(children).replace('\n', " ")
+        ),
+        Node::Paragraph { children } => inlines(children),
+        Node::List {
+            list_type,
+            items,
+            start,
+            loose,
+        } => list(list_type, items, *start, *loose),
+        Node::CodeBlock {
+            language,
+            code,
+            properties,
+        } => code_block(language, code, properties),
+        Node::BlockQuote { children } => special_block("QUOTE", "", children),
+        Node::ThematicBreak => "-----".to_string(),
+        Node::Table {
+            header,
+            rows,
+            alignments,
+            properties,
+        } => table(header, rows, alignments, properties),
+        Node::Group { name, children } => {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                blocks(children).join("\n\n")
+            } else {
+                special_block(name, "", children)
+            }
+        }
+        Node::FootnoteReference(reference) => format!("[fn:{}]", reference.label),
+        Node::FootnoteDefinition(definition) => {
+            let label = format!("[fn:{}]", definition.label);
+            let content = blocks(&definition.content).join("\n\n");
+            if content.is_empty() {
+                label
+            } else {
+                format!("{label} {content}")
+            }
+        }
+        Node::DefinitionList { items } => items
+            .iter()
+            .map(|item| {
+                let mut entry = format!("- {} ::", inlines(&item.term).replace('\n', " "));
+                let descriptions: Vec<Node> = item.descriptions.concat();
+                entry.push_str(&item_body(&descriptions, 2));
+                entry
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Node::MathBlock { math } => format!("\\[\n{}\n\\]", math.trim_end()),
+        Node::Embed { url, provider, .. } => format!("[[{url}][{provider}]]"),
+        Node::Figure {
+            url,
+            alt,
+            title,
+            caption,
+            properties,
+        } => {
+            let mut out = Vec::new();
+            if !caption.is_empty() {
+                out.push(format!(
+                    "#+CAPTION: {}",
+                    inlines(caption).replace('\n', " ")
+                ));
+            }
+            let mut attributes = Vec::new();
+            if !alt.is_empty() {
+                attributes.push(format!(":alt {alt}"));
+            }
+            if let Some(title) = title {
+                attributes.push(format!(":title {title}"));
+            }
+            if let Some(width) = properties.width {
+                attributes.push(format!(":width {width}"));
+            }
+            if let Some(height) = properties.height {
+                attributes.push(format!(":height {height}"));
+            }
+            if !attributes.is_empty() {
+                out.push(format!("#+ATTR_HTML: {}", attributes.join(" ")));
+            }
+            out.push(format!("[[{}]]", link_target(url)));
+            out.join("\n")
+        }
+        Node::HtmlBlock { html } => {
+            format!("#+BEGIN_EXPORT html\n{}\n#+END_EXPORT", html.trim_end())
+        }
+        Node::HtmlComment { text } => {
+            format!("#+BEGIN_COMMENT\n{}\n#+END_COMMENT", text.trim())
+        }
+        Node::Callout {
+            kind,
+            title,
+            children,
+        } => special_block(
+            &kind.as_str().to_ascii_uppercase(),
+            title.as_deref().unwrap_or_default(),
+            children,
+        ),
+        Node::VisibilityBlock {
+            visibility,
+            children,
+        } => {
+            if !is_published(*visibility, false) {
+                return None;
+            }
+            blocks(children).join("\n\n")
+        }
+        Node::Custom { kind, data } => {
+            format!("#+BEGIN_{kind}\n{}\n#+END_{kind}", data.trim_end())
+        }
+        Node::Conflict { ours, theirs } => {
+            conflict_markers(&blocks(ours).join("\n\n"), &blocks(theirs).join("\n\n"))
+        }
+        Node::TableOfContents { max_level } => format!("#+TOC: headlines {max_level}"),
+        Node::TempListItem(_) | Node::TempTableCell(_) => return None,
+    };
+    (!block.is_empty()).then_some(block)
+}
+
+fn special_block(name: &str, parameters: &str, children: &[Node]) -> String {
+    let begin = if parameters.is_empty() {
+        format!("#+BEGIN_{name}")
+    } else {
+        format!("#+BEGIN_{name} {parameters}")
+    };
+    let content = blocks(children).join("\n\n");
+    if content.is_empty() {
+        format!("{begin}\n#+END_{name}")
+    } else {
+        format!("{begin}\n{content}\n#+END_{name}")
+    }
+}
+
+fn code_block(language: &str, code: &str, properties: &CodeBlockProperties) -> String {
+    let code = escape_code(code.strip_suffix('\n').unwrap_or(code));
+    let (name, mut begin) = if language.is_empty() {
+        ("EXAMPLE", "#+BEGIN_EXAMPLE".to_string())
+    } else {
+        ("SRC", format!("#+BEGIN_SRC {language}"))
+    };
+    if properties.show_line_numbers {
+        begin.push_str(" -n");
+        if properties.start_line != 1 {
+            begin.push_str(&format!(" {}", properties.start_line));
+        }
+    }
+    format!("{begin}\n{code}\n#+END_{name}")
+}
+
+/// Whether a task list can be written as TODO headlines of `level`: every
+/// item is a task starting with a one-line paragraph
+fn headline_tasks(items: &[ListItem], level: u8) -> bool {
+    level <= 6
+        && items.iter().all(|item| {
+            item.checked.is_some()
+                && matches!(
+                    item.children.first(),
+                    Some(Node::Paragraph { children }) if !children.iter().any(|child| {
+                        matches!(child, InlineNode::HardBreak | InlineNode::SoftBreak)
+                    })
+                )
+        })
+}
+
+fn task_headlines(items: &[ListItem], level: u8) -> String {
+    let mut out = Vec::new();
+    for item in items {
+        let state = if item.checked == Some(true) {
+            "DONE"
+        } else {
+            "TODO"
+        };
+        let (title, mut body) = match item.children.split_first() {
+            Some((Node::Paragraph { children }, body)) => (inlines(children), body),
+            _ => (String::new(), item.children.as_slice()),
+        };
+        let mut entry = vec![format!("{} {state} {title}", "*".repeat(level as usize))];
+        let mut subtasks = None;
+        if let Some((
+            Node::List {
+                list_type: ListType::Task,
+                items,
+                ..
+            },
+            rest,
+        )) = body.split_last()
+            && headline_tasks(items, level + 1)
+        {
+            subtasks = Some(task_headlines(items, level + 1));
+            body = rest;
+        }
+        entry.extend(blocks(body));
+        entry.extend(subtasks);
+        out.push(entry.join("\n\n"));
+    }
+    out.join("\n\n")
+}
+
+fn list(list_type: &ListType, items: &[ListItem], start: Option<u64>, loose: bool) -> String {
+    let first = start.unwrap_or(1);
+    let mut out = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let marker = match list_type {
+            ListType::Ordered => format!("{}.", first + index as u64),
+            _ => "-".to_string(),
+        };
+        let mut entry = marker.clone();
+        if index == 0 && first != 1 && *list_type == ListType::Ordered {
+            entry.push_str(&format!(" [@{first}]"));
+        }
+        match item.checked {
+            Some(true) => entry.push_str(" [X]"),
+            Some(false) => entry.push_str(" [ ]"),
+            None => {}
+        }
+        entry.push_str(&item_body(&item.children, marker.len() + 1));
+        out.push(entry);
+    }
+    out.join(if loose { "\n\n" } else { "\n" })
+}
+
+/// The content of a list item after its marker, with a first paragraph on
+/// the marker's line and the other blocks indented by `width`
+fn item_body(children: &[Node], width: usize) -> String {
+    let mut out = String::new();
+    for (index, child) in children.iter().enumerate() {
+        let Some(block) = block(child) else {
+            continue;
+        };
+        match child {
+            Node::Paragraph { .. } if index == 0 => {
+                out.push(' ');
+                out.push_str(indented(&block, width).trim_start());
+                continue;
+            }
+            Node::List { .. } | Node::DefinitionList { .. } => out.push('\n'),
+            _ => out.push_str(if index == 0 { "\n" } else { "\n\n" }),
+        }
+        out.push_str(&indented(&block, width));
+    }
+    out
+}
+
+fn table(
+    header: &[TableCell],
+    rows: &[Vec<TableCell>],
+    alignments: &[TableAlignment],
+    properties: &TableProperties,
+) -> String {
+    let row_cells = |row: &[TableCell]| -> Vec<String> {
+        let mut cells = Vec::new();
+        for cell in row {
+            let content = inlines(&cell.content)
+                .replace('\n', " ")
+                .replace('|', "\\vert{}");
+            cells.push(content);
+            cells.extend((1..cell.colspan).map(|_| String::new()));
+        }
+        cells
+    };
+    let mut grid: Vec<Vec<String>> = Vec::new();
+    let cookie = |alignment: &TableAlignment| match alignment {
+        TableAlignment::Left => "<l>",
+        TableAlignment::Center => "<c>",
+        TableAlignment::Right => "<r>",
+        _ => "",
+    };
+    if alignments
+        .iter()
+        .any(|alignment| !cookie(alignment).is_empty())
+    {
+        grid.push(
+            alignments
+                .iter()
+                .map(|alignment| cookie(alignment).to_string())
+                .collect(),
+        );
+    }
+    let header_row = (!header.is_empty()).then_some(grid.len());
+    if !header.is_empty() {
+        grid.push(row_cells(header));
+    }
+    grid.extend(rows.iter().map(|row| row_cells(row)));
+
+    let columns = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            grid.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(1)
+        })
+        .collect();
+    let mut out = Vec::new();
+    if let Some(caption) = &properties.caption {
+        out.push(format!("#+CAPTION: {caption}"));
+    }
+    for (index, row) in grid.iter().enumerate() {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = row.get(column).map_or("", String::as_str);
+                format!("{cell}{}", " ".repeat(width - cell.chars().count()))
+            })
+            .collect();
+        out.push(format!("| {} |", cells.join(" | ")));
+        if header_row == Some(index) {
+            let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+            out.push(format!("|{}|", rule.join("+")));
+        }
+    }
+    out.join("\n")
+}
+
+fn inlines(children: &[InlineNode]) -> String {
+    children.iter().map(inline).collect()
+}
+
+fn inline(node: &InlineNode) -> String {
+    match node {
+        InlineNode::Text(text) => formatted(text),
+        InlineNode::Link { url, children, .. } => {
+            let text = inlines(children);
+            let target = link_target(url);
+            if text.is_empty() || text == *url {
+                format!("[[{target}]]")
+            } else {
+                format!("[[{target}][{}]]", text.replace('\n', " "))
+            }
+        }
+        InlineNode::Image { url, .. } => format!("[[{}]]", link_target(url)),
+        InlineNode::CodeSpan { code } => {
+            if code.contains('~') && !code.contains('=') {
+                format!("={code}=")
+            } else {
+                format!("~{code}~")
+            }
+        }
+        InlineNode::AutoLink { url, is_email } => {
+            if *is_email {
+                format!("[[mailto:{url}][{url}]]")
+            } else {
+                url.clone()
+            }
+        }
+        InlineNode::FootnoteRef { label } => format!("[fn:{label}]"),
+        InlineNode::InlineFootnote { children } => format!("[fn::{}]", inlines(children)),
+        InlineNode::Mention { name, mention_type } => match mention_type.as_str() {
+            "user" => format!("@{name}"),
+            "issue" => format!("#{name}"),
+            _ => name.clone(),
+        },
+        InlineNode::Tag { name } => format!("#{name}"),
+        InlineNode::WikiLink { target, alias } => match alias {
+            Some(alias) => format!("[[{target}][{alias}]]"),
+            None => format!("[[{target}]]"),
+        },
+        InlineNode::Citation { key, locator } => match locator {
+            Some(locator) => format!("[cite:@{key} {locator}]"),
+            None => format!("[cite:@{key}]"),
+        },
+        InlineNode::Math { math } => format!("\\({math}\\)"),
+        InlineNode::Emoji { shortcode } => format!(":{shortcode}:"),
+        InlineNode::Html { html } => format!("@@html:{html}@@"),
+        InlineNode::HtmlComment { .. } => String::new(),
+        InlineNode::Custom { data, .. } => data.clone(),
+        InlineNode::HardBreak => "\\\\\n".to_string(),
+        InlineNode::SoftBreak => "\n".to_string(),
+    }
+}
+
+/// Formatted text with its marks outside of the surrounding whitespace
+fn formatted(text: &TextNode) -> String {
+    let formatting = &text.formatting;
+    let core = text.text.trim();
+    if core.is_empty()
+        || !(formatting.bold || formatting.italic || formatting.code || formatting.strikethrough)
+    {
+        return text.text.clone();
+    }
+    let leading = &text.text[..text.text.len() - text.text.trim_start().len()];
+    let trailing = &text.text[text.text.trim_end().len()..];
+    let mut out = core.to_string();
+    if formatting.code {
+        out = format!("~{out}~");
+    }
+    if formatting.strikethrough {
+        out = format!("+{out}+");
+    }
+    if formatting.italic {
+        out = format!("/{out}/");
+    }
+    if formatting.bold {
+        out = format!("*{out}*");
+    }
+    format!("{leading}{out}{trailing}")
+}
+
+/// A headline's level, TODO state and title
+struct Headline {
+    level: usize,
+    /// Whether the TODO state is a done state, or `None` without a state
+    done: Option<bool>,
+    title: String,
+}
+
+/// Keywords captured before the element they describe
+#[derive(Default)]
+struct Affiliated {
+    caption: Option<String>,
+    /// `#+ATTR_HTML:` attributes such as `:alt` and `:width`
+    attributes: Vec<(String, String)>,
+}
+
+impl Affiliated {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parses `:name value` attribute pairs
+fn attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    for word in text.split_whitespace() {
+        match word.strip_prefix(':') {
+            Some(name) if !name.is_empty() => attributes.push((name.to_string(), String::new())),
+            _ => {
+                if let Some((_, value)) = attributes.last_mut() {
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(word);
+                }
+            }
+        }
+    }
+    attributes
+}
+
+/// A list item's indentation, whether it is numbered, its number and the
+/// text after its marker
+fn list_item(line: &str, nested: bool) -> Option<(usize, bool, Option<u64>, &str)> {
+    let captures = LIST_ITEM.captures(line)?;
+    let indent = captures[1].len();
+    // A star in the first column starts a headline
+    if &captures[2] == "*" && indent == 0 && !nested {
+        return None;
+    }
+    let number = captures
+        .get(3)
+        .and_then(|number| number.as_str().parse().ok());
+    let text = captures.get(4).map_or("", |text| text.as_str());
+    Some((indent, captures.get(3).is_some(), number, text))
+}
+
+struct OrgParser {
+    /// Open TODO states
+    todo: Vec<String>,
+    /// Done TODO states
+    done: Vec<String>,
+    metadata: DocumentMetadata,
+    /// Footnotes defined inline with `[fn:name:text]`, appended to the
+    /// document
+    footnotes: Vec<FootnoteDefinition>,
+    /// Depth of list items being parsed, where no headline can start
+    nested: usize,
+}
+
+impl OrgParser {
+    /// Creates a parser for the TODO states the `#+TODO:` lines of the
+    /// document declare
+    fn new(lines: &[String]) -> Self {
+        let mut todo = Vec::new();
+        let mut done = Vec::new();
+        let name = |word: &str| word.split('(').next().unwrap_or_default().to_string();
+        for line in lines {
+            let Some(captures) = KEYWORD.captures(line) else {
+                continue;
+            };
+            if !matches!(
+                captures[1].to_ascii_uppercase().as_str(),
+                "TODO" | "SEQ_TODO" | "TYP_TODO"
+            ) {
+                continue;
+            }
+            let value = captures.get(2).map_or("", |value| value.as_str());
+            match value.split_once('|') {
+                Some((open, closed)) => {
+                    todo.extend(open.split_whitespace().map(name));
+                    done.extend(closed.split_whitespace().map(name));
+                }
+                // Without a bar, the last state is the done one
+                None => {
+                    let mut states: Vec<String> = value.split_whitespace().map(name).collect();
+                    done.extend(states.pop());
+                    todo.extend(states);
+                }
+            }
+        }
+        if todo.is_empty() && done.is_empty() {
+            todo.push("TODO".to_string());
+            done.push("DONE".to_string());
+        }
+        Self {
+            todo,
+            done,
+            metadata: DocumentMetadata::default(),
+            footnotes: Vec::new(),
+            nested: 0,
+        }
+    }
+
+    fn headline(&self, line: &str) -> Option<Headline> {
+        if self.nested > 0 {
+            return None;
+        }
+        let captures = HEADLINE.captures(line)?;
+        let mut title = captures[2].trim().to_string();
+        if let Some(tags) = TAGS.find(&title) {
+            title.truncate(tags.start());
+        }
+        let (first, rest) = title.split_once(' ').unwrap_or((&title, ""));
+        let done = if self.todo.iter().any(|state| state == first) {
+            Some(false)
+        } else if self.done.iter().any(|state| state == first) {
+            Some(true)
+        } else {
+            None
+        };
+        if done.is_some() {
+            title = rest.trim_start().to_string();
+        }
+        if let Some(priority) = PRIORITY.find(&title) {
+            title = title[priority.end()..].to_string();
+        }
+        Some(Headline {
+            level: captures[1].len(),
+            done,
+            title,
+        })
+    }
+
+    /// Whether a line ends the paragraph before it
+    fn interrupts(&self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        is_blank(line)
+            || self.headline(line).is_some()
+            || trimmed.starts_with("#+")
+            || trimmed.starts_with('|')
+            || trimmed.starts_with("\\[")
+            || is_rule(trimmed)
+            || is_fixed_width(trimmed)
+            || FOOTNOTE_DEFINITION.is_match(line)
+            || list_item(line, self.nested > 0).is_some()
+    }
+
+    fn blocks(&mut self, lines: &[String]) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut affiliated = Affiliated::default();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i].as_str();
+            let trimmed = line.trim_start();
+            if is_blank(line) {
+                i += 1;
+                continue;
+            }
+            if let Some(headline) = self.headline(line) {
+                if headline.done.is_some() {
+                    let (list, next) = self.task_list(lines, i);
+                    nodes.push(list);
+                    i = next;
+                    continue;
+                }
+                nodes.push(Node::Heading {
+                    level: headline.level.min(6) as u8,
+                    children: self.inlines(&headline.title),
+                    properties: Default::default(),
+                });
+                i += 1;
+                continue;
+            }
+            // Drawers such as `:PROPERTIES:` and planning lines
+            if trimmed.len() > 2
+                && trimmed.starts_with(':')
+                && trimmed.ends_with(':')
+                && !trimmed.contains(' ')
+                && let Some(end) =
+                    (i + 1..lines.len()).find(|&j| lines[j].trim().eq_ignore_ascii_case(":END:"))
+            {
+                i = end + 1;
+                continue;
+            }
+            if PLANNING.is_match(trimmed) {
+                i += 1;
+                continue;
+            }
+            if let Some(captures) = BLOCK_BEGIN.captures(trimmed) {
+                let name = captures[1].to_string();
+                let parameters = captures.get(2).map_or("", |p| p.as_str()).trim();
+                let end = block_end(lines, i, &name);
+                affiliated = Affiliated::default();
+                nodes.extend(self.block(&name, parameters, &lines[i + 1..end]));
+                i = (end + 1).min(lines.len());
+                continue;
+            }
+            if let Some(captures) = KEYWORD.captures(trimmed) {
+                let value = captures.get(2).map_or("", |value| value.as_str()).trim();
+                let value = (!value.is_empty()).then(|| value.to_string());
+                match captures[1].to_ascii_uppercase().as_str() {
+                    "TITLE" => self.metadata.title = value,
+                    "AUTHOR" => self.metadata.author = value,
+                    "DATE" => self.metadata.date = value,
+                    "CAPTION" => affiliated.caption = value,
+                    "ATTR_HTML" => affiliated
+                        .attributes
+                        .extend(attributes(value.as_deref().unwrap_or_default())),
+                    _ => {}
+                }
+                i += 1;
+                continue;
+            }
+            if trimmed == "#" || trimmed.starts_with("# ") || trimmed.starts_with("#+") {
+                i += 1;
+                continue;
+            }
+            if let Some(captures) = FOOTNOTE_DEFINITION.captures(line) {
+                // A definition ends at the next one, a headline or two
+                // blank lines
+                let end = (i + 1..lines.len())
+                    .find(|&j| {
+                        FOOTNOTE_DEFINITION.is_match(&lines[j])
+                            || self.headline(&lines[j]).is_some()
+                            || (is_blank(&lines[j]) && lines.get(j + 1).is_none_or(|l| is_blank(l)))
+                    })
+                    .unwrap_or(lines.len());
+                let mut body = vec![captures.get(2).map_or("", |text| text.as_str()).to_string()];
+                body.extend(lines[i + 1..end].iter().cloned());
+                let content = self.blocks(&body);
+                nodes.push(Node::FootnoteDefinition(FootnoteDefinition::new(
+                    &captures[1],
+                    content,
+                )));
+                i = end;
+                continue;
+            }
+            if is_rule(trimmed) {
+                nodes.push(Node::ThematicBreak);
+                i += 1;
+                continue;
+            }
+            if trimmed.starts_with('|') {
+                let end = (i..lines.len())
+                    .find(|&j| !lines[j].trim_start().starts_with('|'))
+                    .unwrap_or(lines.len());
+                let block = std::mem::take(&mut affiliated);
+                nodes.push(self.table(&lines[i..end], block));
+                i = end;
+                continue;
+            }
+            if is_fixed_width(trimmed) {
+                let end = (i..lines.len())
+                    .find(|&j| !is_fixed_width(lines[j].trim_start()))
+                    .unwrap_or(lines.len());
+                let code: Vec<&str> = lines[i..end]
+                    .iter()
+                    .map(|line| {
+                        let line = line.trim_start();
+                        line.strip_prefix(": ").unwrap_or(&line[1..])
+                    })
+                    .collect();
+                nodes.push(Node::code_block(code.join("\n"), ""));
+                i = end;
+                continue;
+            }
+            if let Some(first) = trimmed.strip_prefix("\\[") {
+                let mut math = vec![first.to_string()];
+                let mut end = i;
+                while !math
+                    .last()
+                    .is_some_and(|last| last.trim_end().ends_with("\\]"))
+                {
+                    end += 1;
+                    let Some(line) = lines.get(end) else {
+                        break;
+                    };
+                    math.push(line.trim().to_string());
+                }
+                let math = math.join("\n");
+                let math = math.trim_end().strip_suffix("\\]").unwrap_or(&math);
+                nodes.push(Node::MathBlock {
+                    math: math.trim().to_string(),
+                });
+                i = end + 1;
+                continue;
+            }
+            if list_item(line, self.nested > 0).is_some() {
+                let (list, next) = self.list(lines, i);
+                nodes.push(list);
+                i = next;
+                continue;
+            }
+
+            let end = (i + 1..lines.len())
+                .find(|&j| self.interrupts(&lines[j]))
+                .unwrap_or(lines.len());
+            let block = std::mem::take(&mut affiliated);
+            nodes.push(self.paragraph(&lines[i..end], block));
+            i = end;
+        }
+        nodes
+    }
+
+    /// Consecutive TODO headlines of one level as a task list, each item
+    /// holding the headline's section and the TODO headlines below it
+    fn task_list(&mut self, lines: &[String], start: usize) -> (Node, usize) {
+        let level = self.headline(&lines[start]).map_or(1, |h| h.level);
+        let mut items = Vec::new();
+        let mut i = start;
+        while let Some(headline) = lines
+            .get(i)
+            .and_then(|line| self.headline(line))
+            .filter(|h| h.level == level && h.done.is_some())
+        {
+            let end = (i + 1..lines.len())
+                .find(|&j| self.headline(&lines[j]).is_some())
+                .unwrap_or(lines.len());
+            let mut children = vec![Node::Paragraph {
+                children: self.inlines(&headline.title),
+            }];
+            children.extend(self.blocks(&lines[i + 1..end]));
+            i = end;
+            while lines
+                .get(i)
+                .and_then(|line| self.headline(line))
+                .is_some_and(|h| h.level > level && h.done.is_some())
+            {
+                let (subtasks, next) = self.task_list(lines, i);
+                children.push(subtasks);
+                i = next;
+            }
+            items.push(ListItem {
+                children,
+                checked: headline.done,
+            });
+        }
+        let list = Node::List {
+            list_type: ListType::Task,
+            items,
+            start: None,
+            loose: false,
+        };
+        (list, i)
+    }
+
+    fn block(&mut self, name: &str, parameters: &str, inner: &[String]) -> Vec<Node> {
+        let node = match name.to_ascii_lowercase().as_str() {
+            "src" | "example" => {
+                let mut words = parameters.split_whitespace().peekable();
+                let language = match words.peek() {
+                    Some(word)
+                        if name.eq_ignore_ascii_case("src")
+                            && !word.starts_with(['-', '+', ':']) =>
+                    {
+                        words.next().unwrap_or_default()
+                    }
+                    _ => "",
+                };
+                let mut properties = CodeBlockProperties::new();
+                while let Some(word) = words.next() {
+                    if word == "-n" || word == "+n" {
+                        properties.show_line_numbers = true;
+                        if let Some(start) = words.peek().and_then(|start| start.parse().ok()) {
+                            properties.start_line = start;
+                            words.next();
+                        }
+                    }
+                }
+                let code: Vec<String> = dedent(inner).iter().map(|l| unescape_code(l)).collect();
+                Node::code_block_with_properties(code.join("\n"), language, properties)
+            }
+            "quote" => Node::BlockQuote {
+                children: self.blocks(inner),
+            },
+            "verse" => {
+                let lines = dedent(inner);
+                let mut children = Vec::new();
+                for (index, line) in lines.iter().enumerate() {
+                    if index > 0 {
+                        children.push(InlineNode::HardBreak);
+                    }
+                    children.extend(self.inlines(line));
+                }
+                Node::Paragraph { children }
+            }
+            "export" => {
+                if !parameters.eq_ignore_ascii_case("html") {
+                    return Vec::new();
+                }
+                Node::HtmlBlock {
+                    html: inner.join("\n"),
+                }
+            }
+            "comment" => Node::HtmlComment {
+                text: inner.join("\n"),
+            },
+            "center" => return self.blocks(inner),
+            lowercase => {
+                let children = self.blocks(inner);
+                match CalloutKind::from_name(lowercase) {
+                    Some(kind) => Node::Callout {
+                        kind,
+                        title: (!parameters.is_empty()).then(|| parameters.to_string()),
+                        children,
+                    },
+                    None => Node::Group {
+                        name: lowercase.to_string(),
+                        children,
+                    },
+                }
+            }
+        };
+        vec![node]
+    }
+
+    fn list(&mut self, lines: &[String], start: usize) -> (Node, usize) {
+        let nested = self.nested > 0;
+        let (base, ordered, _, _) = list_item(&lines[start], nested).unwrap_or_default();
+        let mut items = Vec::new();
+        let mut definitions = Vec::new();
+        let mut first_number = None;
+        let mut description = false;
+        let mut tasks = false;
+        let mut loose = false;
+        let mut i = start;
+        while let Some((_, _, number, text)) = lines
+            .get(i)
+            .and_then(|line| list_item(line, nested))
+            .filter(|&(indent, numbered, _, _)| indent == base && numbered == ordered)
+        {
+            // The item runs over the lines indented below its marker,
+            // across single blank lines
+            let mut end = i + 1;
+            while end < lines.len() {
+                if is_blank(&lines[end]) {
+                    if lines.get(end + 1).is_none_or(|line| is_blank(line)) {
+                        break;
+                    }
+                    end += 1;
+                } else if indent(&lines[end]) > base {
+                    end += 1;
+                } else {
+                    break;
+                }
+            }
+            let mut body_end = end;
+            while body_end > i + 1 && is_blank(&lines[body_end - 1]) {
+                body_end -= 1;
+            }
+
+            let mut text = text;
+            let counter = COUNTER_COOKIE
+                .captures(text)
+                .and_then(|c| c[1].parse().ok());
+            if let Some(cookie) = COUNTER_COOKIE.find(text) {
+                text = &text[cookie.end()..];
+            }
+            if items.is_empty() && definitions.is_empty() && ordered {
+                first_number = counter.or(number);
+            }
+            let mut checked = None;
+            if let Some(captures) = CHECKBOX.captures(text) {
+                checked = Some(&captures[1] != " " && &captures[1] != "-");
+                text = &text[captures[0].len()..];
+                tasks = true;
+            }
+            if items.is_empty() && definitions.is_empty() && !ordered {
+                description = text.contains(" :: ") || text.ends_with(" ::");
+            }
+            let (term, text) = if description {
+                match text.split_once(" :: ") {
+                    Some((term, text)) => (term, text),
+                    None => (text.strip_suffix(" ::").unwrap_or(text), ""),
+                }
+            } else {
+                ("", text)
+            };
+
+            let mut body = Vec::new();
+            if !text.is_empty() {
+                body.push(text.to_string());
+            }
+            body.extend(dedent(&lines[i + 1..body_end]));
+            self.nested += 1;
+            let children = self.blocks(&body);
+            self.nested -= 1;
+            if description {
+                let descriptions = if children.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![children]
+                };
+                definitions.push(DefinitionItem::new(self.inlines(term), descriptions));
+            } else {
+                items.push(ListItem { children, checked });
+            }
+
+            let next_is_item = lines
+                .get(end)
+                .and_then(|line| list_item(line, nested))
+                .is_some_and(|(indent, numbered, _, _)| indent == base && numbered == ordered);
+            if next_is_item && body_end < end {
+                loose = true;
+            }
+            i = end;
+        }
+
+        if description {
+            return (Node::DefinitionList { items: definitions }, i);
+        }
+        let list_type = if tasks {
+            ListType::Task
+        } else if ordered {
+            ListType::Ordered
+        } else {
+            ListType::Unordered
+        };
+        let list = Node::List {
+            list_type,
+            items,
+            start: first_number.filter(|&number| number != 1),
+            loose,
+        };
+        (list, i)
+    }
+
+    fn table(&mut self, lines: &[String], affiliated: Affiliated) -> Node {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut header_rows = None;
+        let mut alignments = Vec::new();
+        for line in lines {
+            let line = line.trim();
+            if line.starts_with("|-") {
+                if header_rows.is_none() && !rows.is_empty() {
+                    header_rows = Some(rows.len());
+                }
+                continue;
+            }
+            let cells = table_cells(line);
+            let cookies = cells.iter().any(|cell| !cell.is_empty())
+                && cells
+                    .iter()
+                    .all(|cell| cell.is_empty() || ALIGNMENT_COOKIE.is_match(cell));
+            if cookies {
+                alignments = cells
+                    .iter()
+                    .map(|cell| match ALIGNMENT_COOKIE.captures(cell) {
+                        Some(captures) => match captures.get(1).map(|a| a.as_str()) {
+                            Some("l") => TableAlignment::Left,
+                            Some("c") => TableAlignment::Center,
+                            Some("r") => TableAlignment::Right,
+                            _ => TableAlignment::None,
+                        },
+                        None => TableAlignment::None,
+                    })
+                    .collect();
+                continue;
+            }
+            rows.push(cells);
+        }
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        alignments.resize(columns.max(alignments.len()), TableAlignment::None);
+        let mut rows: Vec<Vec<TableCell>> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| TableCell::new(self.inlines(cell)))
+                    .collect()
+            })
+            .collect();
+        // Rows above the first rule are the header when rows follow it
+        let header = if header_rows.is_some_and(|count| count < rows.len()) {
+            rows.remove(0)
+        } else {
+            Vec::new()
+        };
+        Node::Table {
+            header,
+            rows,
+            alignments,
+            properties: TableProperties {
+                caption: affiliated.caption,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn paragraph(&mut self, lines: &[String], affiliated: Affiliated) -> Node {
+        if let [line] = lines
+            && let Some(target) = line
+                .trim()
+                .strip_prefix("[[")
+                .and_then(|link| link.strip_suffix("]]"))
+                .filter(|target| !target.contains(']'))
+            && is_image(&link_url(target))
+        {
+            let number = |name: &str| affiliated.attribute(name).and_then(|n| n.parse().ok());
+            let properties = FigureProperties {
+                width: number("width"),
+                height: number("height"),
+                ..Default::default()
+            };
+            let caption = affiliated.caption.as_deref().unwrap_or_default();
+            return Node::Figure {
+                url: link_url(target),
+                alt: affiliated.attribute("alt").unwrap_or_default().to_string(),
+                title: affiliated.attribute("title").map(str::to_string),
+                caption: self.inlines(caption),
+                properties,
+            };
+        }
+
+        let mut children = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let (text, hard_break) = match line.trim_end().strip_suffix("\\\\") {
+                Some(text) => (text, true),
+                None => (line.as_str(), false),
+            };
+            children.extend(self.inlines(text.trim()));
+            if index + 1 < lines.len() {
+                children.push(if hard_break {
+                    InlineNode::HardBreak
+                } else {
+                    InlineNode::SoftBreak
+                });
+            }
+        }
+        Node::Paragraph {
+            children: merge_text(children),
+        }
+    }
+
+    fn inlines(&mut self, text: &str) -> Vec<InlineNode> {
+        merge_text(self.formatted(text, TextFormatting::default()))
+    }
+
+    fn formatted(&mut self, text: &str, formatting: TextFormatting) -> Vec<InlineNode> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        let collect = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+        let flush = |text: &mut String, nodes: &mut Vec<InlineNode>| {
+            if !text.is_empty() {
+                let text = std::mem::take(text);
+                nodes.push(InlineNode::Text(TextNode::with_formatting(
+                    text,
+                    formatting.clone(),
+                )));
+            }
+        };
+
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let previous = i.checked_sub(1).map(|p| chars[p]);
+            if chars[i..].starts_with(&['[', '['])
+                && let Some(end) = find(&chars, i + 2, "]]")
+            {
+                let link = collect(i + 2, end);
+                flush(&mut text, &mut nodes);
+                let node = match link.split_once("][") {
+                    Some((target, description)) => InlineNode::Link {
+                        url: link_url(target),
+                        title: None,
+                        children: self.formatted(description, TextFormatting::default()),
+                        reference: None,
+                    },
+                    None if is_image(&link_url(&link)) => InlineNode::Image {
+                        url: link_url(&link),
+                        alt: String::new(),
+                        title: None,
+                    },
+                    None => {
+                        let label = link
+                            .strip_prefix("file:")
+                            .or_else(|| link.strip_prefix('*'))
+                            .unwrap_or(&link);
+                        InlineNode::link(link_url(&link), label)
+                    }
+                };
+                nodes.push(node);
+                i = end + 2;
+                continue;
+            }
+            if chars[i..].starts_with(&['[', 'f', 'n', ':'])
+                && let Some(end) = closing_bracket(&chars, i)
+            {
+                let footnote = collect(i + 4, end);
+                flush(&mut text, &mut nodes);
+                match footnote.split_once(':') {
+                    Some(("", definition)) => nodes.push(InlineNode::InlineFootnote {
+                        children: self.inlines(definition),
+                    }),
+                    Some((label, definition)) => {
+                        let children = self.inlines(definition);
+                        self.footnotes.push(FootnoteDefinition::new(
+                            label,
+                            vec![Node::Paragraph { children }],
+                        ));
+                        nodes.push(InlineNode::footnote_ref(label));
+                    }
+                    None => nodes.push(InlineNode::footnote_ref(footnote)),
+                }
+                i = end + 1;
+                continue;
+            }
+            if chars[i..].starts_with(&['\\', '('])
+                && let Some(end) = find(&chars, i + 2, "\\)")
+            {
+                flush(&mut text, &mut nodes);
+                nodes.push(InlineNode::math(collect(i + 2, end)));
+                i = end + 2;
+                continue;
+            }
+            if c == '$'
+                && previous.is_none_or(|p| !p.is_alphanumeric() && p != '$')
+                && chars.get(i + 1).is_some_and(|next| !next.is_whitespace())
+                && let Some(end) = (i + 2..chars.len()).find(|&k| {
+                    chars[k] == '$'
+                        && !chars[k - 1].is_whitespace()
+                        && chars.get(k + 1).is_none_or(|next| !next.is_alphanumeric())
+                })
+            {
+                flush(&mut text, &mut nodes);
+                nodes.push(InlineNode::math(collect(i + 1, end)));
+                i = end + 1;
+                continue;
+            }
+            if chars[i..].starts_with(&['@', '@'])
+                && let Some(end) = find(&chars, i + 2, "@@")
+                && let Some((backend, content)) = collect(i + 2, end).split_once(':')
+            {
+                flush(&mut text, &mut nodes);
+                if backend.eq_ignore_ascii_case("html") {
+                    nodes.push(InlineNode::Html {
+                        html: content.to_string(),
+                    });
+                }
+                i = end + 2;
+                continue;
+            }
+            if "*/_+=~".contains(c)
+                && let Some(end) = emphasis_end(&chars, i)
+            {
+                flush(&mut text, &mut nodes);
+                let content = collect(i + 1, end);
+                let mut inner = formatting.clone();
+                match c {
+                    '=' | '~' => {
+                        nodes.push(InlineNode::code_span(content));
+                        i = end + 1;
+                        continue;
+                    }
+                    '*' => inner.bold = true,
+                    '/' => inner.italic = true,
+                    '+' => inner.strikethrough = true,
+                    _ => {}
+                }
+                nodes.extend(self.formatted(&content, inner));
+                i = end + 1;
+                continue;
+            }
+            if c.is_ascii_alphabetic() && previous.is_none_or(|p| !p.is_alphanumeric()) {
+                let rest = collect(i, chars.len());
+                if let Some(url) = URL.find(&rest) {
+                    let url = url
+                        .as_str()
+                        .trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+                    flush(&mut text, &mut nodes);
+                    nodes.push(InlineNode::autolink_url(url));
+                    i += url.chars().count();
+                    continue;
+                }
+            }
+            text.push(c);
+            i += 1;
+        }
+        flush(&mut text, &mut nodes);
+        nodes
+    }
+}
+
+/// The index of the line ending the block of `name` that begins at
+/// `start`, skipping blocks of the same name nested in it
+fn block_end(lines: &[String], start: usize, name: &str) -> usize {
+    let begin = format!("#+begin_{name}").to_ascii_lowercase();
+    let end = format!("#+end_{name}").to_ascii_lowercase();
+    let mut depth = 0;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        let line = line.trim().to_ascii_lowercase();
+        if line == end {
+            if depth == 0 {
+                return index;
+            }
+            depth -= 1;
+        } else if line == begin || line.starts_with(&format!("{begin} ")) {
+            depth += 1;
+        }
+    }
+    lines.len()
+}
+
+/// The index of the bracket closing the one at `open`
+fn closing_bracket(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (k, c) in chars.iter().enumerate().skip(open) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(k);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The index of the mark closing the emphasis opened at `i`, following
+/// Org's rules for the characters around the marks
+fn emphasis_end(chars: &[char], i: usize) -> Option<usize> {
+    let mark = chars[i];
+    let opens = i
+        .checked_sub(1)
+        .is_none_or(|p| chars[p].is_whitespace() || "-('\"{".contains(chars[p]));
+    if !opens || chars.get(i + 1).is_none_or(|next| next.is_whitespace()) {
+        return None;
+    }
+    (i + 2..chars.len()).find(|&k| {
+        chars[k] == mark
+            && !chars[k - 1].is_whitespace()
+            && chars
+                .get(k + 1)
+                .is_none_or(|next| next.is_whitespace() || "-.,;:!?')}\"\\[".contains(*next))
+    })
+}
+
+/// The cells of a table row, with `\vert` entities as bars
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|')
+        .map(|cell| cell.trim().replace("\\vert{}", "|").replace("\\vert", "|"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn markdown(text: &str) -> Document {
+        Document::try_from(Text::<Markdown>::new(text)).unwrap()
+    }
+
+    fn render(document: &Document) -> String {
+        Text::<Org>::try_from(document).unwrap().into_inner()
+    }
+
+    fn parse(text: &str) -> Document {
+        Document::try_from(Text::<Org>::new(text)).unwrap()
+    }
+
+    #[test]
+    fn test_render_org() {
+        let doc = markdown(
+            "# Guide\n\nSome **bold**, *italic*, ~~old~~ and `code` with \
+             [a link](https://example.com) and [setup](#setup).\n\n\
+             1. First\n2. Second\n   - Nested\n\n\
+             ```rust\nfn main() {}\n*ptr = 1;\n```\n\n\
+             | Name | Size |\n|:-----|-----:|\n| a | 1 |\n\n\
+             > [!TIP]\n> Try it\n",
+        );
+        assert_eq!(
+            render(&doc),
+            "* Guide\n\n\
+             Some *bold*, /italic/, +old+ and ~code~ with [[https://example.com][a link]] and \
+             [[#setup][setup]].\n\n\
+             1. First\n2. Second\n   - Nested\n\n\
+             #+BEGIN_SRC rust\nfn main() {}\n,*ptr = 1;\n#+END_SRC\n\n\
+             | <l>  | <r>  |\n| Name | Size |\n|------+------|\n| a    | 1    |\n\n\
+             #+BEGIN_TIP\nTry it\n#+END_TIP\n"
+        );
+    }
+
+    #[test]
+    fn test_todo_headlines_are_tasks() {
+        let doc = parse(
+            "#+TODO: TODO NEXT | DONE CANCELLED\n#+TITLE: Plan\n\n\
+             * Release\n** TODO [#A] Write notes :docs:\nSCHEDULED: <2024-05-01>\n\
+             :PROPERTIES:\n:ID: 1\n:END:\nDraft them.\n\
+             *** DONE Outline\n*** NEXT Review\n** CANCELLED Blog post\n* Later\n",
+        );
+        assert_eq!(
+            doc.metadata.as_ref().unwrap().title.as_deref(),
+            Some("Plan")
+        );
+        assert_eq!(doc.nodes.len(), 3);
+        let Node::List {
+            list_type, items, ..
+        } = &doc.nodes[1]
+        else {
+            panic!("expected a task list");
+        };
+        assert_eq!(*list_type, ListType::Task);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].checked, Some(false));
+        assert_eq!(items[0].children[0], Node::paragraph("Write notes"));
+        assert_eq!(items[0].children[1], Node::paragraph("Draft them."));
+        let Node::List { items: nested, .. } = &items[0].children[2] else {
+            panic!("expected nested tasks");
+        };
+        assert_eq!(
+            nested.iter().map(|item| item.checked).collect::<Vec<_>>(),
+            [Some(true), Some(false)]
+        );
+        assert_eq!(items[1].checked, Some(true), "CANCELLED is a done state");
+        assert_eq!(doc.task_summary().total, 4);
+
+        assert_eq!(
+            render(&doc),
+            "#+TITLE: Plan\n\n* Release\n\n** TODO Write notes\n\nDraft them.\n\n\
+             *** DONE Outline\n\n*** TODO Review\n\n** DONE Blog post\n\n* Later\n"
+        );
+
+        // Checklists not ending a section are written as checkbox items
+        let doc = markdown("## Steps\n\n- [ ] One\n- [x] Two\n\nDone soon.\n");
+        assert!(render(&doc).contains("- [ ] One\n- [X] Two\n\nDone soon."));
+    }
+
+    #[test]
+    fn test_parse_org() {
+        let doc = parse(
+            "Text with *bold*, /italic/, =verbatim=, ~code~ and +gone+ at \
+             [[https://example.com][the site]].\nSee [[*Getting Started]] or \
+             [[file:notes.org][notes]][fn:1], \\(x^2\\) and https://rust-lang.org.\n\n\
+             - [X] Done\n- [ ] Open\n  #+begin_src python -n 5\n    print(1)\n    ,* not a headline\n  #+end_src\n\n\
+             3. [@3] Three\n4. Four\n\n\
+             - Term :: Meaning\n\n\
+             #+CAPTION: Sizes\n| Name | Size |\n|------+------|\n|   | <r> |\n| a \\vert b | 1 |\n\n\
+             #+BEGIN_WARNING Careful\nHot.\n#+END_WARNING\n\n\
+             #+CAPTION: A cat\n#+ATTR_HTML: :alt The cat :width 300\n[[file:cat.png]]\n\n\
+             : fixed\n: width\n\n\\[\nE = mc^2\n\\]\n\n\
+             [fn:1] The notes.\n",
+        );
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("Text with "),
+                InlineNode::bold_text("bold"),
+                InlineNode::text(", "),
+                InlineNode::italic_text("italic"),
+                InlineNode::text(", "),
+                InlineNode::code_span("verbatim"),
+                InlineNode::text(", "),
+                InlineNode::code_span("code"),
+                InlineNode::text(" and "),
+                InlineNode::Text(TextNode::with_formatting(
+                    "gone",
+                    TextFormatting {
+                        strikethrough: true,
+                        ..Default::default()
+                    }
+                )),
+                InlineNode::text(" at "),
+                InlineNode::link("https://example.com", "the site"),
+                InlineNode::text("."),
+                InlineNode::SoftBreak,
+                InlineNode::text("See "),
+                InlineNode::link("#getting-started", "Getting Started"),
+                InlineNode::text(" or "),
+                InlineNode::link("notes.org", "notes"),
+                InlineNode::footnote_ref("1"),
+                InlineNode::text(", "),
+                InlineNode::math("x^2"),
+                InlineNode::text(" and "),
+                InlineNode::autolink_url("https://rust-lang.org"),
+                InlineNode::text("."),
+            ]
+        );
+
+        let Node::List {
+            list_type, items, ..
+        } = &doc.nodes[1]
+        else {
+            panic!("expected a task list");
+        };
+        assert_eq!(*list_type, ListType::Task);
+        assert_eq!(items[0].checked, Some(true));
+        assert_eq!(
+            items[1].children[1],
+            Node::code_block_with_properties(
+                "print(1)\n* not a headline",
+                "python",
+                CodeBlockProperties::new()
+                    .with_line_numbers(true)
+                    .with_start_line(5)
+            )
+        );
+        assert!(matches!(
+            &doc.nodes[2],
+            Node::List { list_type: ListType::Ordered, start: Some(3), items, .. } if items.len() == 2
+        ));
+        assert_eq!(
+            doc.nodes[3],
+            Node::DefinitionList {
+                items: vec![DefinitionItem::single("Term", "Meaning")]
+            }
+        );
+
+        let Node::Table {
+            header,
+            rows,
+            alignments,
+            properties,
+        } = &doc.nodes[4]
+        else {
+            panic!("expected a table");
+        };
+        assert_eq!(properties.caption.as_deref(), Some("Sizes"));
+        assert_eq!(header.len(), 2);
+        assert_eq!(alignments, &[TableAlignment::None, TableAlignment::Right]);
+        assert_eq!(rows.len(), 1, "the cookie row is not content");
+        assert_eq!(InlineNode::plain_text_of(&rows[0][0].content), "a | b");
+
+        assert_eq!(
+            doc.nodes[5],
+            Node::Callout {
+                kind: CalloutKind::Warning,
+                title: Some("Careful".to_string()),
+                children: vec![Node::paragraph("Hot.")],
+            }
+        );
+        let Node::Figure {
+            url,
+            alt,
+            caption,
+            properties,
+            ..
+        } = &doc.nodes[6]
+        else {
+            panic!("expected a figure");
+        };
+        assert_eq!((url.as_str(), alt.as_str()), ("cat.png", "The cat"));
+        assert_eq!(caption, &[InlineNode::text("A cat")]);
+        assert_eq!(properties.width, Some(300));
+        assert_eq!(doc.nodes[7], Node::code_block("fixed\nwidth", ""));
+        assert_eq!(
+            doc.nodes[8],
+            Node::MathBlock {
+                math: "E = mc^2".to_string()
+            }
+        );
+        assert!(matches!(&doc.nodes[9], Node::FootnoteDefinition(note) if note.label == "1"));
+    }
+
+    #[test]
+    fn test_org_round_trip() {
+        let doc = markdown(
+            "# Title\n\n## Section\n\nText with **bold**, a [link](/docs) and a\nsoft break.\n\n\
+             > Quoted\n>\n> > Nested\n\n3. Three\n4. Four\n\n- Loose\n\n- Items\n\n---\n\n\
+             ::: note Heads up\nFirst.\n\nSecond.\n:::\n\n\
+             | A | B |\n|---|:-:|\n| 1 | 2 |\n\n$$\nx^2\n$$\n\n<!-- hidden -->\n\n\
+             ### Tasks\n\n- [ ] Open\n- [x] Done\n",
+        );
+        let org = render(&doc);
+        assert_eq!(render(&parse(&org)), org);
+        assert!(org.contains("3. [@3] Three\n4. Four"));
+        assert!(org.contains("#+BEGIN_NOTE Heads up\nFirst.\n\nSecond.\n#+END_NOTE"));
+        assert!(org.contains("*** Tasks\n\n**** TODO Open\n\n**** DONE Done"));
+    }
+}
@@ -0,0 +1,139 @@
+//! Per-node-type overrides of HTML rendering
+//!
+//! Applications often need a node type rendered their own way: code blocks
+//! as a component with a copy button, or `data-` attributes on every block
+//! for a preview to hook into. A [`RendererRegistry`] passed to
+//! [`HtmlOptions::with_renderers`](crate::HtmlOptions::with_renderers) maps
+//! [`Node::type_name`] values to [`NodeRenderer`]s, which receive the node
+//! and the HTML the built-in conversion produced for it and return the HTML
+//! to use instead. Nested blocks are rendered, and overridden, before the
+//! block containing them.
+
+use crate::Node;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// Renders a block node to HTML
+///
+/// Closures taking the node and its default HTML are renderers too.
+pub trait NodeRenderer {
+    /// Returns the HTML for `node`
+    ///
+    /// `html` is what the built-in conversion, or the renderer registered
+    /// before this one, produced for the node. The default keeps it.
+    fn render(&self, node: &Node, html: String) -> String {
+        let _ = node;
+        html
+    }
+}
+
+impl<F> NodeRenderer for F
+where
+    F: Fn(&Node, String) -> String,
+{
+    fn render(&self, node: &Node, html: String) -> String {
+        self(node, html)
+    }
+}
+
+/// Node renderers keyed by node type
+///
+/// Renderers for a node's type run first, in the order they were added,
+/// then those registered for all nodes.
+#[derive(Clone, Default)]
+pub struct RendererRegistry {
+    by_type: BTreeMap<String, Vec<Rc<dyn NodeRenderer>>>,
+    all: Vec<Rc<dyn NodeRenderer>>,
+}
+
+impl fmt::Debug for RendererRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RendererRegistry")
+            .field("by_type", &self.by_type.keys().collect::<Vec<_>>())
+            .field("all", &self.all.len())
+            .finish()
+    }
+}
+
+impl RendererRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders nodes whose [`Node::type_name`] is `type_name`, such as
+    /// `"code_block"`, with `renderer`
+    pub fn with_renderer(
+        mut self,
+        type_name: impl Into<String>,
+        renderer: impl NodeRenderer + 'static,
+    ) -> Self {
+        self.by_type
+            .entry(type_name.into())
+            .or_default()
+            .push(Rc::new(renderer));
+        self
+    }
+
+    /// Renders every block node with `renderer`
+    pub fn with_renderer_for_all(mut self, renderer: impl NodeRenderer + 'static) -> Self {
+        self.all.push(Rc::new(renderer));
+        self
+    }
+
+    /// Returns true if no renderers are registered
+    pub fn is_empty(&self) -> bool {
+        self.by_type.is_empty() && self.all.is_empty()
+    }
+
+    /// Runs the renderers registered for the node on its default HTML
+    pub(crate) fn render(&self, node: &Node, html: String) -> String {
+        let by_type = self.by_type.get(node.type_name()).into_iter().flatten();
+        by_type
+            .chain(&self.all)
+            .fold(html, |html, renderer| renderer.render(node, html))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::html_escape;
+    use crate::{Document, HtmlOptions};
+
+    #[test]
+    fn test_renderer_registry() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Intro");
+        doc.nodes.push(Node::BlockQuote {
+            children: vec![Node::code_block("a < b", "rust")],
+        });
+
+        let registry = RendererRegistry::new()
+            .with_renderer("code_block", |node: &Node, _html: String| {
+                let Node::CodeBlock { code, language, .. } = node else {
+                    unreachable!()
+                };
+                format!(
+                    "<code-view lang=\"{}\">{}</code-view>",
+                    html_escape(language),
+                    html_escape(code)
+                )
+            })
+            .with_renderer_for_all(|node: &Node, html: String| {
+                format!("<div data-node=\"{}\">{}</div>", node.type_name(), html)
+            });
+        let html = doc
+            .to_html_with(&HtmlOptions::new().with_renderers(registry))
+            .html;
+
+        assert_eq!(
+            html,
+            "<div data-node=\"heading\"><h1 id=\"intro\">Intro</h1></div>\
+             <div data-node=\"blockquote\"><blockquote>\
+             <div data-node=\"code_block\"><code-view lang=\"rust\">a &lt; b</code-view></div>\
+             </blockquote></div>"
+        );
+    }
+}
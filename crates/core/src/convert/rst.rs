@@ -0,0 +1,1271 @@
+//! reStructuredText import
+//!
+//! Parses the parts of reStructuredText that Sphinx projects are mostly
+//! written in, so their pages can move into Markdown-based pipelines:
+//!
+//! - Sections, with levels given by the order their underline (and
+//!   overline) styles first appear, and transitions
+//! - Paragraphs, block quotes, line blocks, literal blocks after `::` and
+//!   doctest blocks
+//! - Bullet, enumerated, definition and field lists
+//! - Grid tables, including cells spanning columns, and simple tables
+//! - Footnotes, hyperlink targets and comments
+//! - Directives: admonitions become callouts; `code-block`, `code`,
+//!   `sourcecode` and `highlight` give code blocks; `math`, `image`,
+//!   `figure`, `list-table` and `raw:: html` map to their nodes, while
+//!   `contents`, `index` and `meta` are dropped and any other directive is
+//!   kept as a [`Node::Custom`] block named after it
+//! - Inline emphasis, strong emphasis, literals, roles, references and
+//!   standalone URLs
+//!
+//! Substitutions, citations and cells spanning rows are not interpreted;
+//! their text is kept.
+
+use super::Rst;
+use super::format::{Capabilities, FormatConverter};
+use super::importer::{dedent, indent, is_blank, strip_indent};
+use crate::outline::slugify;
+use crate::{
+    CalloutKind, CodeBlockProperties, Document, FootnoteDefinition, InlineNode, ListItem, ListType,
+    Node, ParseError, TableAlignment, TableCell, TableProperties,
+};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+static ENUMERATOR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:\((\d+|#|[A-Za-z])\)|(\d+|#|[A-Za-z])([.)]))(?: +|$)").unwrap()
+});
+static FIELD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^:([^:\s][^:]*):(?: +(.*))?$").unwrap());
+static TARGET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\.\. _(`[^`]+`|[^:]+):(?:\s+(.*))?$").unwrap());
+static SIMPLE_TABLE_BORDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^=+( +=+)+ *$").unwrap());
+
+impl FormatConverter for Rst {
+    fn name(&self) -> &str {
+        "rst"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rst", "rest"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            parse: true,
+            render: false,
+            lost_features: Vec::new(),
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        Ok(parse_rst(text))
+    }
+}
+
+/// Parses a reStructuredText document
+fn parse_rst(text: &str) -> Document {
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| expand_tabs(line).trim_end().to_string())
+        .collect();
+    let mut parser = RstParser::new(&lines);
+    let mut document = Document::new();
+    document.nodes = parser.blocks(&lines);
+    document
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        if c == '\t' {
+            let width = 8 - expanded.chars().count() % 8;
+            expanded.extend(std::iter::repeat_n(' ', width));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+/// The end of the lines from `from` on that are blank or indented by at
+/// least `min_indent`, without trailing blank lines
+fn indented_end(lines: &[String], from: usize, min_indent: usize) -> usize {
+    let mut end = from;
+    while end < lines.len() && (is_blank(&lines[end]) || indent(&lines[end]) >= min_indent) {
+        end += 1;
+    }
+    while end > from && is_blank(&lines[end - 1]) {
+        end -= 1;
+    }
+    end
+}
+
+/// The character of a section adornment or transition line
+fn adornment(line: &str) -> Option<char> {
+    let mut chars = line.chars();
+    let first = chars.next()?;
+    (first.is_ascii_punctuation() && line.len() >= 2 && chars.all(|c| c == first)).then_some(first)
+}
+
+/// Marker of a list item: whether the list is ordered, the number of its
+/// first item, the width of the marker and its spacing, and its style
+struct ListMarker {
+    ordered: bool,
+    number: Option<u64>,
+    width: usize,
+    style: String,
+}
+
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let first = line.chars().next()?;
+    if matches!(first, '*' | '-' | '+' | '•') {
+        let rest = &line[first.len_utf8()..];
+        if rest.is_empty() || rest.starts_with(' ') {
+            let width = first.len_utf8() + rest.len() - rest.trim_start().len();
+            return Some(ListMarker {
+                ordered: false,
+                number: None,
+                width: width.max(2),
+                style: first.to_string(),
+            });
+        }
+        return None;
+    }
+    let captures = ENUMERATOR.captures(line)?;
+    let (value, style) = match (captures.get(1), captures.get(2)) {
+        (Some(value), _) => (value.as_str(), "()".to_string()),
+        (None, Some(value)) => (value.as_str(), captures[3].to_string()),
+        _ => return None,
+    };
+    let number = match value.parse::<u64>() {
+        Ok(number) => Some(number),
+        Err(_) => value
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .map(|letter| u64::from(letter.to_ascii_lowercase() as u8 - b'a' + 1)),
+    };
+    let kind = match value.parse::<u64>() {
+        Ok(_) => "1",
+        Err(_) if value == "#" => "1",
+        Err(_) if value.chars().all(|c| c.is_ascii_lowercase()) => "a",
+        Err(_) => "A",
+    };
+    let matched = captures.get(0)?.as_str();
+    Some(ListMarker {
+        ordered: true,
+        number,
+        width: matched.len().max(value.len() + 2),
+        style: format!("{kind}{style}"),
+    })
+}
+
+/// Normalizes a reference name: without backquotes, lowercase, with runs
+/// of whitespace collapsed
+fn reference_name(name: &str) -> String {
+    name.trim_matches('`')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Splits `title <target>` into its title and target
+fn explicit_target(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim();
+    let open = text.strip_suffix('>')?.rfind('<')?;
+    if open > 0 && !text[..open].ends_with(char::is_whitespace) {
+        return None;
+    }
+    Some((text[..open].trim(), &text[open + 1..text.len() - 1]))
+}
+
+/// Whether inline markup may start after `previous`
+fn starts_markup(previous: Option<char>) -> bool {
+    previous.is_none_or(|c| c.is_whitespace() || "'\"([{<-/:".contains(c))
+}
+
+/// Whether inline markup may end before `next`
+fn ends_markup(next: Option<char>) -> bool {
+    next.is_none_or(|c| c.is_whitespace() || "'\")]}>-/:.,;!?\\".contains(c))
+}
+
+/// Expands `1,3-5` into line numbers
+fn line_numbers(spec: &str) -> Vec<u32> {
+    let mut lines = Vec::new();
+    for part in spec.split(',').map(str::trim) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                if let (Ok(first), Ok(last)) = (first.trim().parse::<u32>(), last.trim().parse()) {
+                    lines.extend(first..=last);
+                }
+            }
+            None => lines.extend(part.parse::<u32>().ok()),
+        }
+    }
+    lines
+}
+
+struct RstParser {
+    /// URLs of the hyperlink targets, by reference name
+    targets: HashMap<String, String>,
+    /// Underline character and whether there is an overline, per level
+    section_styles: Vec<(char, bool)>,
+    /// Language of literal blocks, set with the `highlight` directive
+    literal_language: String,
+    /// Auto-numbered footnote references and definitions seen so far
+    auto_references: usize,
+    auto_definitions: usize,
+}
+
+impl RstParser {
+    fn new(lines: &[String]) -> Self {
+        let mut targets = HashMap::new();
+        let mut aliases = Vec::new();
+        for line in lines {
+            let Some(captures) = TARGET.captures(line) else {
+                continue;
+            };
+            let name = reference_name(&captures[1]);
+            let url: String = captures
+                .get(2)
+                .map_or("", |url| url.as_str())
+                .split_whitespace()
+                .collect();
+            match url.strip_suffix('_') {
+                Some(alias) => aliases.push((name, reference_name(alias))),
+                None if url.is_empty() => {
+                    targets.insert(name.clone(), format!("#{}", slugify(&name)));
+                }
+                None => {
+                    targets.insert(name, url);
+                }
+            }
+        }
+        // Indirect targets point at other targets, possibly indirect ones
+        for _ in 0..aliases.len() {
+            for (name, alias) in &aliases {
+                if let Some(url) = targets.get(alias).cloned() {
+                    targets.insert(name.clone(), url);
+                }
+            }
+        }
+        Self {
+            targets,
+            section_styles: Vec::new(),
+            literal_language: String::new(),
+            auto_references: 0,
+            auto_definitions: 0,
+        }
+    }
+
+    fn resolve(&self, name: &str) -> String {
+        let name = reference_name(name);
+        self.targets
+            .get(&name)
+            .cloned()
+            .unwrap_or_else(|| format!("#{}", slugify(&name)))
+    }
+
+    fn section_level(&mut self, style: (char, bool)) -> u8 {
+        let index = match self.section_styles.iter().position(|known| *known == style) {
+            Some(index) => index,
+            None => {
+                self.section_styles.push(style);
+                self.section_styles.len() - 1
+            }
+        };
+        (index + 1).min(6) as u8
+    }
+
+    fn heading(&mut self, title: &str, style: (char, bool)) -> Node {
+        Node::Heading {
+            level: self.section_level(style),
+            children: self.inlines(title.trim()),
+            properties: Default::default(),
+        }
+    }
+
+    /// Parses a sequence of body elements
+    fn blocks(&mut self, lines: &[String]) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i].as_str();
+            if is_blank(line) {
+                i += 1;
+                continue;
+            }
+            if indent(line) > 0 {
+                let end = indented_end(lines, i, 1);
+                let children = self.blocks(&dedent(&lines[i..end]));
+                if !children.is_empty() {
+                    nodes.push(Node::BlockQuote { children });
+                }
+                i = end;
+                continue;
+            }
+            if let Some((node, next)) = self.section(lines, i) {
+                nodes.push(node);
+                i = next;
+                continue;
+            }
+            if adornment(line).is_some_and(|_| line.len() >= 4)
+                && lines.get(i + 1).is_none_or(|next| is_blank(next))
+            {
+                nodes.push(Node::ThematicBreak);
+                i += 1;
+                continue;
+            }
+            if line == ".." || line.starts_with(".. ") {
+                let end = indented_end(lines, i + 1, 1);
+                let body = dedent(&lines[i + 1..end]);
+                nodes.extend(self.explicit_markup(line[2..].trim(), body));
+                i = end;
+                continue;
+            }
+            if line.starts_with("+-") || line.starts_with("+=") {
+                let end = (i..lines.len())
+                    .find(|&j| !(lines[j].starts_with('+') || lines[j].starts_with('|')))
+                    .unwrap_or(lines.len());
+                if let Some(table) = self.grid_table(&lines[i..end]) {
+                    nodes.push(table);
+                    i = end;
+                    continue;
+                }
+            }
+            if SIMPLE_TABLE_BORDER.is_match(line)
+                && let Some((table, next)) = self.simple_table(lines, i)
+            {
+                nodes.push(table);
+                i = next;
+                continue;
+            }
+            if let Some(marker) = list_marker(line) {
+                let (list, next) = self.list(lines, i, marker);
+                nodes.push(list);
+                i = next;
+                continue;
+            }
+            if FIELD.is_match(line) {
+                let (list, next) = self.field_list(lines, i);
+                nodes.push(list);
+                i = next;
+                continue;
+            }
+            if line == "|" || line.starts_with("| ") {
+                let end = (i..lines.len())
+                    .find(|&j| !(lines[j] == "|" || lines[j].starts_with("| ")))
+                    .unwrap_or(lines.len());
+                let mut children = Vec::new();
+                for (k, line) in lines[i..end].iter().enumerate() {
+                    if k > 0 {
+                        children.push(InlineNode::HardBreak);
+                    }
+                    children.extend(self.inlines(line[1..].trim()));
+                }
+                nodes.push(Node::Paragraph { children });
+                i = end;
+                continue;
+            }
+            if line.starts_with(">>>") {
+                let end = (i..lines.len())
+                    .find(|&j| is_blank(&lines[j]))
+                    .unwrap_or(lines.len());
+                nodes.push(Node::code_block(lines[i..end].join("\n"), "pycon"));
+                i = end;
+                continue;
+            }
+            if lines
+                .get(i + 1)
+                .is_some_and(|next| !is_blank(next) && indent(next) > 0)
+            {
+                let (list, next) = self.definition_list(lines, i);
+                nodes.push(list);
+                i = next;
+                continue;
+            }
+
+            let end = (i..lines.len())
+                .find(|&j| is_blank(&lines[j]) || indent(&lines[j]) > 0)
+                .unwrap_or(lines.len());
+            let text = lines[i..end]
+                .iter()
+                .map(|line| line.trim())
+                .collect::<Vec<_>>()
+                .join(" ");
+            i = end;
+            let (text, literal) = match text.strip_suffix("::") {
+                Some(text) => (
+                    text.strip_suffix(' ')
+                        .unwrap_or(&format!("{text}:"))
+                        .to_string(),
+                    true,
+                ),
+                None => (text, false),
+            };
+            let text = if text == ":" { String::new() } else { text };
+            if !text.trim().is_empty() {
+                nodes.push(Node::Paragraph {
+                    children: self.inlines(text.trim()),
+                });
+            }
+            if literal {
+                let start = (i..lines.len())
+                    .find(|&j| !is_blank(&lines[j]))
+                    .unwrap_or(lines.len());
+                if start < lines.len() && indent(&lines[start]) > 0 {
+                    let end = indented_end(lines, start, 1);
+                    let code = dedent(&lines[start..end]).join("\n");
+                    nodes.push(Node::code_block(code, self.literal_language.clone()));
+                    i = end;
+                }
+            }
+        }
+        nodes
+    }
+
+    /// A section title at line `i`, with the line after it
+    fn section(&mut self, lines: &[String], i: usize) -> Option<(Node, usize)> {
+        let line = &lines[i];
+        if let Some(over) = adornment(line) {
+            let title = lines.get(i + 1)?;
+            let under = lines.get(i + 2)?;
+            let fits = under.chars().count() >= title.trim().chars().count();
+            if adornment(under) == Some(over) && !is_blank(title) && fits {
+                return Some((self.heading(title, (over, true)), i + 3));
+            }
+            return None;
+        }
+        let under = lines.get(i + 1)?;
+        let style = adornment(under)?;
+        (under.chars().count() >= line.chars().count())
+            .then(|| (self.heading(line, (style, false)), i + 2))
+    }
+
+    /// Directives, footnotes, targets and comments, which start with `..`
+    fn explicit_markup(&mut self, first: &str, body: Vec<String>) -> Vec<Node> {
+        if first.starts_with('_') || first.starts_with('|') {
+            return Vec::new();
+        }
+        if let Some(rest) = first.strip_prefix('[')
+            && let Some((label, text)) = rest.split_once(']')
+        {
+            let label = match label {
+                "#" | "*" => {
+                    self.auto_definitions += 1;
+                    self.auto_definitions.to_string()
+                }
+                label => label.trim_start_matches('#').to_string(),
+            };
+            let mut lines = vec![text.trim().to_string()];
+            lines.extend(body);
+            let content = self.blocks(&lines);
+            return vec![Node::FootnoteDefinition(FootnoteDefinition::new(
+                label, content,
+            ))];
+        }
+        if let Some((name, argument)) = first.split_once("::") {
+            let name = name.trim();
+            if !name.is_empty() && !name.contains(char::is_whitespace) {
+                return self.directive(name, argument.trim(), body);
+            }
+        }
+
+        let mut text = first.to_string();
+        for line in &body {
+            text.push('\n');
+            text.push_str(line);
+        }
+        match text.trim() {
+            "" => Vec::new(),
+            text => vec![Node::HtmlComment {
+                text: format!(" {text} "),
+            }],
+        }
+    }
+
+    fn directive(&mut self, name: &str, argument: &str, body: Vec<String>) -> Vec<Node> {
+        let mut options = HashMap::new();
+        let mut content_start = 0;
+        for line in &body {
+            let Some(captures) = FIELD.captures(line) else {
+                break;
+            };
+            let value = captures.get(2).map_or("", |value| value.as_str());
+            options.insert(captures[1].to_lowercase(), value.trim().to_string());
+            content_start += 1;
+        }
+        let content: Vec<String> = body[content_start..]
+            .iter()
+            .skip_while(|line| is_blank(line))
+            .cloned()
+            .collect();
+        let with_argument = || {
+            let mut lines = Vec::new();
+            if !argument.is_empty() {
+                lines.push(argument.to_string());
+                lines.push(String::new());
+            }
+            lines.extend(content.iter().cloned());
+            lines
+        };
+
+        let name = name.to_ascii_lowercase();
+        let node = match name.as_str() {
+            "code" | "code-block" | "sourcecode" => {
+                let mut properties = CodeBlockProperties::new();
+                if options.contains_key("linenos") {
+                    properties.show_line_numbers = true;
+                }
+                if let Some(start) = options.get("lineno-start").and_then(|s| s.parse().ok()) {
+                    properties.show_line_numbers = true;
+                    properties.start_line = start;
+                }
+                if let Some(lines) = options.get("emphasize-lines") {
+                    properties.highlight_lines = Some(line_numbers(lines));
+                }
+                let language = match argument {
+                    "" => self.literal_language.clone(),
+                    language => language.to_string(),
+                };
+                Node::code_block_with_properties(content.join("\n"), language, properties)
+            }
+            "highlight" => {
+                self.literal_language = argument.to_string();
+                return Vec::new();
+            }
+            "contents" | "index" | "meta" => return Vec::new(),
+            "math" => Node::math_block(with_argument().join("\n").trim()),
+            "image" => Node::paragraph_with_inlines(vec![InlineNode::Image {
+                url: argument.to_string(),
+                alt: options.remove("alt").unwrap_or_default(),
+                title: None,
+            }]),
+            "figure" => {
+                let caption = self
+                    .blocks(&content)
+                    .into_iter()
+                    .find_map(|node| match node {
+                        Node::Paragraph { children } => Some(children),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                Node::Figure {
+                    url: argument.to_string(),
+                    alt: options.remove("alt").unwrap_or_default(),
+                    title: None,
+                    caption,
+                    properties: Default::default(),
+                }
+            }
+            "list-table" => {
+                let header_rows = options
+                    .get("header-rows")
+                    .and_then(|rows| rows.parse().ok())
+                    .unwrap_or(0);
+                match self.list_table(&content, header_rows) {
+                    Some(table) => table,
+                    None => return Vec::new(),
+                }
+            }
+            "raw" if argument.eq_ignore_ascii_case("html") => Node::HtmlBlock {
+                html: content.join("\n"),
+            },
+            "admonition" => Node::Callout {
+                kind: CalloutKind::Note,
+                title: Some(argument.to_string()),
+                children: self.blocks(&content),
+            },
+            "seealso" => Node::Callout {
+                kind: CalloutKind::Note,
+                title: Some("See also".to_string()),
+                children: self.blocks(&with_argument()),
+            },
+            _ => match CalloutKind::from_name(&name) {
+                Some(kind) => Node::Callout {
+                    kind,
+                    title: None,
+                    children: self.blocks(&with_argument()),
+                },
+                None => Node::Custom {
+                    kind: name,
+                    data: with_argument().join("\n"),
+                },
+            },
+        };
+        vec![node]
+    }
+
+    fn list(&mut self, lines: &[String], start: usize, first: ListMarker) -> (Node, usize) {
+        let mut items = Vec::new();
+        let mut loose = false;
+        let mut i = start;
+        while i < lines.len() {
+            let Some(marker) = list_marker(&lines[i]).filter(|m| m.style == first.style) else {
+                break;
+            };
+            let end = indented_end(lines, i + 1, 1);
+            let mut item_lines = vec![lines[i][marker.width.min(lines[i].len())..].to_string()];
+            item_lines.extend(strip_indent(&lines[i + 1..end], marker.width));
+            items.push(ListItem::new(self.blocks(&item_lines)));
+
+            let next = (end..lines.len())
+                .find(|&j| !is_blank(&lines[j]))
+                .unwrap_or(lines.len());
+            let continues = next < lines.len()
+                && list_marker(&lines[next]).is_some_and(|m| m.style == first.style);
+            if !continues {
+                i = end;
+                break;
+            }
+            loose |= next > end;
+            i = next;
+        }
+        let list = Node::List {
+            list_type: if first.ordered {
+                ListType::Ordered
+            } else {
+                ListType::Unordered
+            },
+            items,
+            start: first.number.filter(|&number| number != 1),
+            loose,
+        };
+        (list, i)
+    }
+
+    fn field_list(&mut self, lines: &[String], start: usize) -> (Node, usize) {
+        let mut items = Vec::new();
+        let mut i = start;
+        while let Some(captures) = lines.get(i).and_then(|line| FIELD.captures(line)) {
+            let end = indented_end(lines, i + 1, 1);
+            let mut body = vec![
+                captures
+                    .get(2)
+                    .map_or("", |value| value.as_str())
+                    .to_string(),
+            ];
+            body.extend(dedent(&lines[i + 1..end]));
+            items.push(crate::DefinitionItem::new(
+                self.inlines(captures[1].trim()),
+                vec![self.blocks(&body)],
+            ));
+            i = (end..lines.len())
+                .find(|&j| !is_blank(&lines[j]))
+                .unwrap_or(lines.len());
+        }
+        (Node::DefinitionList { items }, i)
+    }
+
+    fn definition_list(&mut self, lines: &[String], start: usize) -> (Node, usize) {
+        let mut items = Vec::new();
+        let mut i = start;
+        loop {
+            let end = indented_end(lines, i + 1, 1);
+            let term = lines[i].split(" : ").next().unwrap_or_default().trim();
+            items.push(crate::DefinitionItem::new(
+                self.inlines(term),
+                vec![self.blocks(&dedent(&lines[i + 1..end]))],
+            ));
+            let next = (end..lines.len())
+                .find(|&j| !is_blank(&lines[j]))
+                .unwrap_or(lines.len());
+            let is_term = next < lines.len()
+                && indent(&lines[next]) == 0
+                && list_marker(&lines[next]).is_none()
+                && !lines[next].starts_with("..")
+                && lines
+                    .get(next + 1)
+                    .is_some_and(|line| !is_blank(line) && indent(line) > 0);
+            if !is_term {
+                return (Node::DefinitionList { items }, end);
+            }
+            i = next;
+        }
+    }
+
+    fn table(&self, header: Option<Vec<TableCell>>, rows: Vec<Vec<TableCell>>) -> Node {
+        let columns = header
+            .iter()
+            .chain(&rows)
+            .map(|row| row.iter().map(|cell| cell.colspan as usize).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        Node::Table {
+            header: header.unwrap_or_default(),
+            rows,
+            alignments: vec![TableAlignment::None; columns],
+            properties: TableProperties::default(),
+        }
+    }
+
+    fn cell(&mut self, text: &str, colspan: u32) -> TableCell {
+        TableCell::with_colspan(self.inlines(text), colspan)
+    }
+
+    fn grid_table(&mut self, lines: &[String]) -> Option<Node> {
+        let bounds: Vec<usize> = lines[0]
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c == '+')
+            .map(|(position, _)| position)
+            .collect();
+        if bounds.len() < 2 {
+            return None;
+        }
+        let mut rows = Vec::new();
+        let mut header_rows = 0;
+        let mut segment: Vec<Vec<char>> = Vec::new();
+        for line in &lines[1..] {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.first() != Some(&'+') {
+                segment.push(chars);
+                continue;
+            }
+            if !segment.is_empty() {
+                rows.push(self.grid_row(&segment, &bounds));
+                segment.clear();
+            }
+            if chars.contains(&'=') {
+                header_rows = rows.len();
+            }
+        }
+        let header = (header_rows > 0).then(|| rows.remove(0));
+        Some(self.table(header, rows))
+    }
+
+    fn grid_row(&mut self, segment: &[Vec<char>], bounds: &[usize]) -> Vec<TableCell> {
+        let first = &segment[0];
+        let mut cells = Vec::new();
+        let mut start = 0;
+        while start + 1 < bounds.len() {
+            let mut end = start + 1;
+            while end + 1 < bounds.len() && first.get(bounds[end]) != Some(&'|') {
+                end += 1;
+            }
+            let text: Vec<String> = segment
+                .iter()
+                .map(|line| {
+                    let from = (bounds[start] + 1).min(line.len());
+                    let to = bounds[end].min(line.len());
+                    line[from..to].iter().collect::<String>().trim().to_string()
+                })
+                .filter(|text| !text.is_empty())
+                .collect();
+            cells.push(self.cell(&text.join(" "), (end - start) as u32));
+            start = end;
+        }
+        cells
+    }
+
+    /// A simple table starting at the border at line `start`, with the line
+    /// after it
+    fn simple_table(&mut self, lines: &[String], start: usize) -> Option<(Node, usize)> {
+        let end = (start + 2..lines.len()).find(|&j| {
+            SIMPLE_TABLE_BORDER.is_match(&lines[j])
+                && lines.get(j + 1).is_none_or(|next| is_blank(next))
+        })?;
+        let border: Vec<char> = lines[start].chars().collect();
+        let mut columns = Vec::new();
+        for (position, c) in border.iter().enumerate() {
+            if *c == '=' && (position == 0 || border[position - 1] == ' ') {
+                columns.push(position);
+            }
+        }
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut header_rows = 0;
+        for line in &lines[start + 1..end] {
+            if SIMPLE_TABLE_BORDER.is_match(line) {
+                header_rows = rows.len();
+                continue;
+            }
+            if is_blank(line) || line.chars().all(|c| c == '-' || c == ' ') {
+                continue;
+            }
+            let chars: Vec<char> = line.chars().collect();
+            let cells: Vec<String> = columns
+                .iter()
+                .enumerate()
+                .map(|(k, &from)| {
+                    let to = columns.get(k + 1).copied().unwrap_or(chars.len());
+                    let from = from.min(chars.len());
+                    chars[from..to.min(chars.len()).max(from)]
+                        .iter()
+                        .collect::<String>()
+                        .trim()
+                        .to_string()
+                })
+                .collect();
+            match rows.last_mut() {
+                // A blank first column continues the row above
+                Some(row) if cells[0].is_empty() => {
+                    for (cell, text) in row.iter_mut().zip(cells) {
+                        if !text.is_empty() {
+                            cell.push(' ');
+                            cell.push_str(&text);
+                        }
+                    }
+                }
+                _ => rows.push(cells),
+            }
+        }
+
+        let mut rows: Vec<Vec<TableCell>> = rows
+            .into_iter()
+            .map(|row| row.iter().map(|text| self.cell(text.trim(), 1)).collect())
+            .collect();
+        let header = (header_rows > 0 && !rows.is_empty()).then(|| rows.remove(0));
+        Some((self.table(header, rows), end + 1))
+    }
+
+    /// A table from a `list-table` directive: a bullet list of rows, each a
+    /// bullet list of cells
+    fn list_table(&mut self, content: &[String], header_rows: usize) -> Option<Node> {
+        let Some(Node::List { items, .. }) = self.blocks(content).into_iter().next() else {
+            return None;
+        };
+        let mut rows: Vec<Vec<TableCell>> = items
+            .into_iter()
+            .map(|row| {
+                let cells = row.children.into_iter().find_map(|child| match child {
+                    Node::List { items, .. } => Some(items),
+                    _ => None,
+                });
+                cells
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|cell| {
+                        let content = match cell.children.as_slice() {
+                            [Node::Paragraph { children }] => children.clone(),
+                            children => {
+                                let text: Vec<String> =
+                                    children.iter().map(Node::plain_text).collect();
+                                vec![InlineNode::text(text.join(" "))]
+                            }
+                        };
+                        TableCell::new(content)
+                    })
+                    .collect()
+            })
+            .collect();
+        let header = (header_rows > 0 && !rows.is_empty()).then(|| rows.remove(0));
+        Some(self.table(header, rows))
+    }
+
+    /// Parses inline markup
+    fn inlines(&mut self, text: &str) -> Vec<InlineNode> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        let mut i = 0;
+        let flush = |text: &mut String, nodes: &mut Vec<InlineNode>| {
+            if !text.is_empty() {
+                nodes.push(InlineNode::text(std::mem::take(text)));
+            }
+        };
+        let collect = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+
+        while i < chars.len() {
+            let c = chars[i];
+            let previous = i.checked_sub(1).map(|p| chars[p]);
+            if c == '\\' {
+                match chars.get(i + 1) {
+                    Some(next) if next.is_whitespace() => {}
+                    Some(next) => text.push(*next),
+                    None => text.push(c),
+                }
+                i += 2;
+                continue;
+            }
+            let starts = starts_markup(previous);
+            let opens = |width: usize| {
+                starts
+                    && chars
+                        .get(i + width)
+                        .is_some_and(|next| !next.is_whitespace())
+            };
+            // The end of markup closed by `close` whose content starts at `from`
+            let closing = |from: usize, close: &str| {
+                let close: Vec<char> = close.chars().collect();
+                (from + 1..chars.len()).find(|&k| {
+                    chars[k..].starts_with(&close)
+                        && !chars[k - 1].is_whitespace()
+                        && chars[k - 1] != '\\'
+                        && ends_markup(chars.get(k + close.len()).copied())
+                })
+            };
+
+            if c == '`'
+                && chars.get(i + 1) == Some(&'`')
+                && opens(2)
+                && let Some(end) = closing(i + 2, "``")
+            {
+                flush(&mut text, &mut nodes);
+                nodes.push(InlineNode::code_span(collect(i + 2, end)));
+                i = end + 2;
+                continue;
+            }
+            if c == '*'
+                && chars.get(i + 1) == Some(&'*')
+                && opens(2)
+                && let Some(end) = closing(i + 2, "**")
+            {
+                flush(&mut text, &mut nodes);
+                nodes.push(InlineNode::bold_text(collect(i + 2, end)));
+                i = end + 2;
+                continue;
+            }
+            if c == '*'
+                && chars.get(i + 1) != Some(&'*')
+                && opens(1)
+                && let Some(end) = closing(i + 1, "*")
+            {
+                flush(&mut text, &mut nodes);
+                nodes.push(InlineNode::italic_text(collect(i + 1, end)));
+                i = end + 1;
+                continue;
+            }
+            if c == ':' && starts {
+                let mut j = i + 1;
+                while j < chars.len() && (chars[j].is_alphanumeric() || "-_+.:".contains(chars[j]))
+                {
+                    j += 1;
+                }
+                if j > i + 2
+                    && chars.get(j) == Some(&'`')
+                    && chars[j - 1] == ':'
+                    && let Some(end) = closing(j + 1, "`")
+                {
+                    flush(&mut text, &mut nodes);
+                    let role = collect(i + 1, j - 1);
+                    nodes.push(self.role(&role, &collect(j + 1, end)));
+                    i = end + 1;
+                    continue;
+                }
+            }
+            if c == '`' && opens(1) {
+                let end = (i + 2..chars.len())
+                    .find(|&k| chars[k] == '`' && !chars[k - 1].is_whitespace());
+                if let Some(end) = end {
+                    let underscores = chars[end + 1..].iter().take_while(|&&c| c == '_').count();
+                    if ends_markup(chars.get(end + 1 + underscores).copied()) {
+                        flush(&mut text, &mut nodes);
+                        let content = collect(i + 1, end);
+                        nodes.push(match underscores {
+                            0 => InlineNode::italic_text(content),
+                            _ => self.reference(&content),
+                        });
+                        i = end + 1 + underscores;
+                        continue;
+                    }
+                }
+            }
+            if c == '[' && starts {
+                let end = (i + 1..chars.len()).find(|&k| chars[k] == ']');
+                if let Some(end) = end {
+                    let label = collect(i + 1, end);
+                    let valid = !label.is_empty()
+                        && label
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || "#*-_".contains(c));
+                    if valid
+                        && chars.get(end + 1) == Some(&'_')
+                        && ends_markup(chars.get(end + 2).copied())
+                    {
+                        flush(&mut text, &mut nodes);
+                        let label = match label.as_str() {
+                            "#" | "*" => {
+                                self.auto_references += 1;
+                                self.auto_references.to_string()
+                            }
+                            label => label.trim_start_matches('#').to_string(),
+                        };
+                        nodes.push(InlineNode::FootnoteRef { label });
+                        i = end + 2;
+                        continue;
+                    }
+                }
+            }
+            if starts
+                && ["http://", "https://", "mailto:"]
+                    .iter()
+                    .any(|scheme| chars[i..].starts_with(&scheme.chars().collect::<Vec<_>>()))
+            {
+                let mut end = (i..chars.len())
+                    .find(|&k| chars[k].is_whitespace() || chars[k] == '<' || chars[k] == '>')
+                    .unwrap_or(chars.len());
+                while end > i && ".,;:!?)'\"".contains(chars[end - 1]) {
+                    end -= 1;
+                }
+                flush(&mut text, &mut nodes);
+                let url = collect(i, end);
+                nodes.push(match url.strip_prefix("mailto:") {
+                    Some(email) => InlineNode::autolink_email(email),
+                    None => InlineNode::autolink_url(url),
+                });
+                i = end;
+                continue;
+            }
+            if c == '_' && ends_markup(chars.get(i + 1).copied()) {
+                let word_start = text
+                    .char_indices()
+                    .rev()
+                    .take_while(|(_, c)| c.is_alphanumeric() || "-.".contains(*c))
+                    .last()
+                    .map(|(position, _)| position);
+                if let Some(word_start) = word_start {
+                    let word = text[word_start..].to_string();
+                    if self.targets.contains_key(&reference_name(&word)) {
+                        text.truncate(word_start);
+                        flush(&mut text, &mut nodes);
+                        nodes.push(InlineNode::link(self.resolve(&word), word));
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+            text.push(c);
+            i += 1;
+        }
+        flush(&mut text, &mut nodes);
+        nodes
+    }
+
+    /// A hyperlink reference, `` `text <url>`_ `` or `` `name`_ ``
+    fn reference(&self, content: &str) -> InlineNode {
+        match explicit_target(content) {
+            Some((title, target)) => {
+                let target: String = target.split_whitespace().collect();
+                let url = match target.strip_suffix('_') {
+                    Some(name) => self.resolve(name),
+                    None => target.clone(),
+                };
+                let title = if title.is_empty() { &target } else { title };
+                InlineNode::link(url, title)
+            }
+            None => InlineNode::link(self.resolve(content), content),
+        }
+    }
+
+    /// Interpreted text with a role, such as `` :code:`x` `` or a Sphinx
+    /// cross-reference
+    fn role(&self, role: &str, content: &str) -> InlineNode {
+        let (title, target) = match explicit_target(content) {
+            Some((title, target)) => (Some(title), target),
+            None => (None, content),
+        };
+        let shown = || {
+            let target = match target.strip_prefix('~') {
+                Some(target) => target.rsplit('.').next().unwrap_or(target),
+                None => target,
+            };
+            title.unwrap_or(target).to_string()
+        };
+        match role.rsplit(':').next().unwrap_or(role) {
+            "code" | "literal" | "samp" | "file" | "command" | "kbd" | "func" | "class"
+            | "meth" | "mod" | "attr" | "data" | "obj" | "exc" | "const" | "envvar" | "option"
+            | "program" | "program-option" => InlineNode::code_span(shown()),
+            "math" => InlineNode::Math {
+                math: content.to_string(),
+            },
+            "emphasis" | "title-reference" | "title" | "t" | "dfn" => {
+                InlineNode::italic_text(shown())
+            }
+            "strong" => InlineNode::bold_text(shown()),
+            "ref" => InlineNode::link(format!("#{}", slugify(target)), shown()),
+            "doc" => InlineNode::link(target, shown()),
+            _ => InlineNode::text(shown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Text;
+
+    fn parse(rst: &str) -> Document {
+        Document::try_from(Text::<Rst>::new(rst)).unwrap()
+    }
+
+    #[test]
+    fn test_sections_and_inline_markup() {
+        let doc = parse(
+            "=====\nGuide\n=====\n\nIntro with *emphasis*, **strong**, ``code`` and \
+             `Docs <https://docs.rs>`_.\n\nSetup\n-----\n\nSee install_ and [1]_, or \
+             :func:`~pkg.run` at https://example.com.\n\nDetails\n~~~~~~~\n\nOther\n-----\n\n\
+             ----\n\n.. _install: https://example.com/install\n\n.. [1] A note.\n",
+        );
+        let headings: Vec<(u8, String)> = doc
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                node.as_heading()
+                    .map(|(level, _)| (level, node.plain_text()))
+            })
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                (1, "Guide".to_string()),
+                (2, "Setup".to_string()),
+                (3, "Details".to_string()),
+                (2, "Other".to_string()),
+            ]
+        );
+        assert_eq!(
+            doc.nodes[1].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("Intro with "),
+                InlineNode::italic_text("emphasis"),
+                InlineNode::text(", "),
+                InlineNode::bold_text("strong"),
+                InlineNode::text(", "),
+                InlineNode::code_span("code"),
+                InlineNode::text(" and "),
+                InlineNode::link("https://docs.rs", "Docs"),
+                InlineNode::text("."),
+            ]
+        );
+        assert_eq!(
+            doc.nodes[3].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("See "),
+                InlineNode::link("https://example.com/install", "install"),
+                InlineNode::text(" and "),
+                InlineNode::footnote_ref("1"),
+                InlineNode::text(", or "),
+                InlineNode::code_span("run"),
+                InlineNode::text(" at "),
+                InlineNode::autolink_url("https://example.com"),
+                InlineNode::text("."),
+            ]
+        );
+        assert!(matches!(doc.nodes[6], Node::ThematicBreak));
+        assert!(matches!(&doc.nodes[7], Node::FootnoteDefinition(def) if def.label == "1"));
+        assert_eq!(doc.nodes.len(), 8);
+    }
+
+    #[test]
+    fn test_directives() {
+        let doc = parse(
+            ".. note:: Keep this in mind.\n\n   Second paragraph.\n\n\
+             .. admonition:: Custom title\n\n   Body.\n\n\
+             .. code-block:: python\n   :linenos:\n   :emphasize-lines: 1,3-4\n\n   \
+             def f():\n       return 1\n\n\
+             .. highlight:: rust\n\nExample::\n\n    fn main() {}\n\n\
+             .. toctree::\n   :maxdepth: 2\n\n   intro\n\n\
+             .. This is a comment.\n",
+        );
+        assert_eq!(
+            doc.nodes[0],
+            Node::Callout {
+                kind: CalloutKind::Note,
+                title: None,
+                children: vec![
+                    Node::paragraph("Keep this in mind."),
+                    Node::paragraph("Second paragraph."),
+                ],
+            }
+        );
+        assert!(matches!(
+            &doc.nodes[1],
+            Node::Callout { title: Some(title), .. } if title == "Custom title"
+        ));
+        let properties = CodeBlockProperties::new()
+            .with_line_numbers(true)
+            .with_highlight_lines(vec![1, 3, 4]);
+        assert_eq!(
+            doc.nodes[2],
+            Node::code_block_with_properties("def f():\n    return 1", "python", properties)
+        );
+        assert_eq!(doc.nodes[3], Node::paragraph("Example:"));
+        assert_eq!(doc.nodes[4], Node::code_block("fn main() {}", "rust"));
+        assert_eq!(
+            doc.nodes[5],
+            Node::Custom {
+                kind: "toctree".to_string(),
+                data: "intro".to_string(),
+            }
+        );
+        assert_eq!(
+            doc.nodes[6],
+            Node::HtmlComment {
+                text: " This is a comment. ".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        let doc = parse(
+            "- One\n- Two\n\n  * Nested\n\n3. Third\n#. Fourth\n\n\
+             Term\n   Definition.\n\n:Author: Ada\n",
+        );
+        assert_eq!(doc.nodes.len(), 4);
+        let Node::List {
+            list_type,
+            items,
+            loose,
+            ..
+        } = &doc.nodes[0]
+        else {
+            panic!("expected a bullet list");
+        };
+        assert_eq!(*list_type, ListType::Unordered);
+        assert!(!loose);
+        assert_eq!(items.len(), 2);
+        assert!(matches!(&items[1].children[1], Node::List { items, .. } if items.len() == 1));
+        assert!(matches!(
+            &doc.nodes[1],
+            Node::List { list_type: ListType::Ordered, start: Some(3), items, .. } if items.len() == 2
+        ));
+
+        let Node::DefinitionList { items } = &doc.nodes[2] else {
+            panic!("expected a definition list");
+        };
+        assert_eq!(InlineNode::plain_text_of(&items[0].term), "Term");
+        assert_eq!(
+            items[0].descriptions,
+            [vec![Node::paragraph("Definition.")]]
+        );
+        assert!(matches!(&doc.nodes[3], Node::DefinitionList { items } if items.len() == 1));
+    }
+
+    #[test]
+    fn test_tables() {
+        let grid = "+-----+-----+-----+\n| A   | B   | C   |\n+=====+=====+=====+\n\
+                    | wide      | x   |\n+-----+-----+-----+\n| 1   | 2   | 3   |\n\
+                    | one | two |     |\n+-----+-----+-----+\n";
+        let simple = "=====  =====\nName   Value\n=====  =====\na      1\n\
+                      b      2\n       more\n=====  =====\n";
+        let list = ".. list-table::\n   :header-rows: 1\n\n   * - Name\n     - Value\n   \
+                    * - a\n     - 1\n";
+        let doc = parse(&format!("{grid}\n{simple}\n{list}"));
+        assert_eq!(doc.nodes.len(), 3);
+
+        let Node::Table { header, rows, .. } = &doc.nodes[0] else {
+            panic!("expected a grid table");
+        };
+        assert_eq!(header.len(), 3);
+        assert_eq!(rows[0][0].colspan, 2);
+        assert_eq!(InlineNode::plain_text_of(&rows[1][0].content), "1 one");
+
+        let Node::Table { header, rows, .. } = &doc.nodes[1] else {
+            panic!("expected a simple table");
+        };
+        assert_eq!(InlineNode::plain_text_of(&header[1].content), "Value");
+        assert_eq!(InlineNode::plain_text_of(&rows[1][1].content), "2 more");
+
+        let Node::Table { header, rows, .. } = &doc.nodes[2] else {
+            panic!("expected a list table");
+        };
+        assert_eq!(InlineNode::plain_text_of(&header[0].content), "Name");
+        assert_eq!(InlineNode::plain_text_of(&rows[0][1].content), "1");
+    }
+}
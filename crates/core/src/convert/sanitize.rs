@@ -0,0 +1,471 @@
+//! Allowlist sanitization of untrusted HTML
+//!
+//! Raw HTML in a document, and links written by its author, end up verbatim
+//! in the HTML export. When documents come from untrusted sources an
+//! [`HtmlSanitizer`] set on
+//! [`HtmlOptions`](super::html::HtmlOptions::with_sanitizer) keeps only
+//! allowed tags, attributes and URL schemes, both on export and on import
+//! with [`Document::from_html_with`](crate::Document::from_html_with).
+
+use std::collections::BTreeSet;
+
+/// Tags whose content is dropped along with them when they are not allowed
+const DROP_CONTENT: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "template", "noscript", "textarea", "title",
+];
+
+/// Attributes holding a URL, whose scheme is checked
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "cite", "action", "poster", "srcset"];
+
+/// Keeps allowed tags, attributes and URL schemes of HTML and drops the rest
+///
+/// Disallowed tags are removed but their text is kept, except for tags like
+/// `<script>` and `<style>` whose content is removed too. Comments and event
+/// handler attributes (`onclick` and the like) are always removed. URLs
+/// without a scheme are relative and always allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlSanitizer {
+    tags: BTreeSet<String>,
+    attributes: BTreeSet<String>,
+    url_schemes: BTreeSet<String>,
+}
+
+impl Default for HtmlSanitizer {
+    fn default() -> Self {
+        let set = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        Self {
+            tags: set(&[
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "br",
+                "caption",
+                "code",
+                "dd",
+                "del",
+                "details",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "ins",
+                "kbd",
+                "li",
+                "mark",
+                "ol",
+                "p",
+                "pre",
+                "s",
+                "samp",
+                "small",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "tr",
+                "u",
+                "ul",
+            ]),
+            attributes: set(&[
+                "align", "alt", "class", "colspan", "height", "href", "id", "open", "rowspan",
+                "src", "title", "width",
+            ]),
+            url_schemes: set(&["http", "https", "mailto"]),
+        }
+    }
+}
+
+impl HtmlSanitizer {
+    /// A sanitizer allowing common formatting tags and `http`, `https` and
+    /// `mailto` URLs
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows a tag, given in lowercase
+    pub fn allow_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    /// Removes a tag from the allowlist
+    pub fn deny_tag(mut self, tag: &str) -> Self {
+        self.tags.remove(tag);
+        self
+    }
+
+    /// Allows an attribute on every allowed tag, given in lowercase
+    ///
+    /// Event handler attributes are removed even if allowed.
+    pub fn allow_attribute(mut self, attribute: impl Into<String>) -> Self {
+        self.attributes.insert(attribute.into());
+        self
+    }
+
+    /// Allows URLs with the given scheme, e.g. `"tel"`
+    pub fn allow_url_scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.url_schemes.insert(scheme.into());
+        self
+    }
+
+    /// Returns true if the tag is allowed
+    pub fn allows_tag(&self, tag: &str) -> bool {
+        self.tags.contains(&tag.to_ascii_lowercase())
+    }
+
+    /// Returns true if the URL is relative or has an allowed scheme
+    ///
+    /// Character references and whitespace that browsers ignore are taken
+    /// into account, so `jav&#x61;script:` is recognized as `javascript:`.
+    pub fn allows_url(&self, url: &str) -> bool {
+        match url_scheme(url) {
+            Some((scheme, _)) => self.url_schemes.contains(&scheme),
+            None => true,
+        }
+    }
+
+    /// Sanitizes an HTML fragment
+    pub fn sanitize(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        // Name of a disallowed tag whose content is being dropped
+        let mut dropping: Option<String> = None;
+
+        while let Some(start) = rest.find('<') {
+            if dropping.is_none() {
+                output.push_str(&rest[..start]);
+            }
+            rest = &rest[start..];
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let Some(tag) = Tag::parse(rest) else {
+                // A `<` that doesn't open a tag is text
+                if dropping.is_none() {
+                    output.push_str("&lt;");
+                }
+                rest = &rest[1..];
+                continue;
+            };
+            rest = &rest[tag.length..];
+
+            if let Some(name) = &dropping {
+                if tag.closing && tag.name == *name {
+                    dropping = None;
+                }
+                continue;
+            }
+            if tag.name.starts_with('!') || tag.name.starts_with('?') {
+                continue;
+            }
+            if !self.tags.contains(&tag.name) {
+                if !tag.closing && !tag.self_closing && DROP_CONTENT.contains(&tag.name.as_str()) {
+                    dropping = Some(tag.name);
+                }
+                continue;
+            }
+            output.push_str(&self.write_tag(&tag));
+        }
+        if dropping.is_none() {
+            output.push_str(rest);
+        }
+        output
+    }
+
+    fn write_tag(&self, tag: &Tag) -> String {
+        if tag.closing {
+            return format!("</{}>", tag.name);
+        }
+        let mut html = format!("<{}", tag.name);
+        for (name, value) in &tag.attributes {
+            if name.starts_with("on") || !self.attributes.contains(name) {
+                continue;
+            }
+            match value {
+                Some(value) => {
+                    if URL_ATTRIBUTES.contains(&name.as_str()) && !self.allows_url(value) {
+                        continue;
+                    }
+                    html.push_str(&format!(" {}=\"{}\"", name, escape_attribute(value)));
+                }
+                None => html.push_str(&format!(" {}", name)),
+            }
+        }
+        html.push_str(if tag.self_closing { " />" } else { ">" });
+        html
+    }
+}
+
+/// Lowercase attribute names with their raw values
+pub(crate) type Attributes = Vec<(String, Option<String>)>;
+
+/// The start tags of `html` in order, with their lowercase names and
+/// attributes, leaving out comments and declarations
+pub(crate) fn start_tags(html: &str) -> Vec<(String, Attributes)> {
+    let mut tags = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(tag) = Tag::parse(rest) else {
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[tag.length..];
+        if !tag.closing && !tag.name.starts_with(['!', '?']) {
+            tags.push((tag.name, tag.attributes));
+        }
+    }
+    tags
+}
+
+/// A start or end tag read from HTML source
+struct Tag {
+    /// Lowercase tag name, starting with `!` or `?` for declarations
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attributes: Attributes,
+    /// Length of the tag in the source, including the angle brackets
+    length: usize,
+}
+
+impl Tag {
+    /// Parses the tag at the start of `source`, which begins with `<`
+    fn parse(source: &str) -> Option<Self> {
+        let mut pos = 1;
+        // Advances past the characters matching `pred`, returning them
+        let read_while = |pos: &mut usize, pred: &dyn Fn(char) -> bool| {
+            let start = *pos;
+            *pos += source[start..]
+                .find(|c: char| !pred(c))
+                .unwrap_or(source.len() - start);
+            &source[start..*pos]
+        };
+        let is_name = |c: char| !c.is_whitespace() && !matches!(c, '/' | '>' | '=');
+
+        let closing = source[pos..].starts_with('/');
+        if closing {
+            pos += 1;
+        }
+        if !source[pos..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '!' || c == '?') {
+            return None;
+        }
+        let name = read_while(&mut pos, &is_name).to_ascii_lowercase();
+
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+        loop {
+            read_while(&mut pos, &|c| c.is_whitespace());
+            let c = source[pos..].chars().next()?;
+            match c {
+                '>' => {
+                    return Some(Self {
+                        name,
+                        closing,
+                        self_closing,
+                        attributes,
+                        length: pos + 1,
+                    });
+                }
+                '/' => {
+                    self_closing = true;
+                    pos += 1;
+                }
+                _ => {
+                    self_closing = false;
+                    // A stray `=` still makes progress as part of the name
+                    pos += c.len_utf8();
+                    let attribute = format!("{}{}", c, read_while(&mut pos, &is_name));
+                    read_while(&mut pos, &|c| c.is_whitespace());
+                    let value = if source[pos..].starts_with('=') {
+                        pos += 1;
+                        read_while(&mut pos, &|c| c.is_whitespace());
+                        match source[pos..].chars().next()? {
+                            quote @ ('"' | '\'') => {
+                                pos += 1;
+                                let value = read_while(&mut pos, &|c| c != quote);
+                                source[pos..].strip_prefix(quote)?;
+                                pos += 1;
+                                Some(value.to_string())
+                            }
+                            _ => Some(
+                                read_while(&mut pos, &|c| !c.is_whitespace() && c != '>')
+                                    .to_string(),
+                            ),
+                        }
+                    } else {
+                        None
+                    };
+                    attributes.push((attribute.to_ascii_lowercase(), value));
+                }
+            }
+        }
+    }
+}
+
+/// Splits a URL into its lowercase scheme and the rest, or returns `None`
+/// for a relative URL
+///
+/// Character references and whitespace that browsers ignore are decoded
+/// and removed first, so `jav&#x61;script:` has the scheme `javascript`.
+/// An `&` left before the first `:`, `/`, `?` or `#` may be a reference
+/// browsers read differently, so that part is returned as the scheme,
+/// which no allowlist contains.
+pub(crate) fn url_scheme(url: &str) -> Option<(String, String)> {
+    let decoded: String = decode_references(url)
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let i = decoded.find([':', '/', '?', '#']).unwrap_or(decoded.len());
+    let scheme = decoded[..i].to_ascii_lowercase();
+    let rest = decoded.get(i + 1..).unwrap_or_default().to_string();
+    (scheme.contains('&') || decoded[i..].starts_with(':')).then_some((scheme, rest))
+}
+
+/// Escapes an attribute value for a double-quoted attribute, keeping any
+/// character references it already has
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Decodes numeric character references and the named ones used to hide
+/// URL schemes
+fn decode_references(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+            .map_or(rest.len(), |i| i + 1);
+        let reference = &rest[1..end];
+        let (c, end) = match reference.strip_prefix('#') {
+            // Browsers read the digits of a numeric reference up to the
+            // first other character, with or without a semicolon
+            Some(number) => {
+                let (digits, radix, skip) = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => (hex, 16, 3),
+                    None => (number, 10, 2),
+                };
+                let length = digits
+                    .find(|c: char| !c.is_digit(radix))
+                    .unwrap_or(digits.len());
+                let c = u32::from_str_radix(&digits[..length], radix)
+                    .ok()
+                    .and_then(char::from_u32);
+                (c, skip + length)
+            }
+            None => (
+                match reference.to_ascii_lowercase().as_str() {
+                    "colon" => Some(':'),
+                    "tab" => Some('\t'),
+                    "newline" => Some('\n'),
+                    "amp" => Some('&'),
+                    _ => None,
+                },
+                end,
+            ),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = rest[end..].strip_prefix(';').unwrap_or(&rest[end..]);
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_html() {
+        let sanitizer = HtmlSanitizer::new();
+        assert_eq!(
+            sanitizer.sanitize("Press <kbd class=\"key\" onclick=\"steal()\">Ctrl</kbd>"),
+            "Press <kbd class=\"key\">Ctrl</kbd>"
+        );
+        assert_eq!(
+            sanitizer.sanitize("<p>Hi<script>alert('x')</script> there<!-- note --></p>"),
+            "<p>Hi there</p>"
+        );
+        assert_eq!(
+            sanitizer.sanitize("<blink>Old</blink> <img src=x onerror=alert(1)> 1 < 2"),
+            "Old <img src=\"x\"> 1 &lt; 2"
+        );
+        assert_eq!(
+            sanitizer.sanitize("<a href=\"jav&#x61;script:alert(1)\" title='t'>x</a>"),
+            "<a title=\"t\">x</a>"
+        );
+        assert_eq!(
+            sanitizer.sanitize("<a href=\"/docs?a=1&amp;b=2\">docs</a><br/>"),
+            "<a href=\"/docs?a=1&amp;b=2\">docs</a><br />"
+        );
+
+        assert!(sanitizer.allows_url("guide.html#intro"));
+        assert!(sanitizer.allows_url("HTTPS://example.com"));
+        assert!(!sanitizer.allows_url(" java\tscript:alert(1)"));
+
+        // Numeric references need no semicolon, and an `&` that can't be
+        // decoded before the scheme's colon is not trusted
+        assert_eq!(
+            sanitizer.sanitize("<a href=\"javascript&#58alert(1)\">x</a>"),
+            "<a>x</a>"
+        );
+        assert!(!sanitizer.allows_url("javascript&#58alert(1)"));
+        assert!(!sanitizer.allows_url("javascript&#x3a alert(1)"));
+        assert!(!sanitizer.allows_url("javascript&#X3A;alert(1)"));
+        assert!(!sanitizer.allows_url("javascript&colon;alert(1)"));
+        assert!(!sanitizer.allows_url("javascript&colonalert(1)"));
+        assert!(!sanitizer.allows_url("javascript&#0058;alert(1)"));
+        assert!(!sanitizer.allows_url("java&unknown;script:alert(1)"));
+        assert!(sanitizer.allows_url("/search?q=a&b=c"));
+        assert!(!sanitizer.allows_url("data:text/html,<b>"));
+
+        let custom = HtmlSanitizer::new()
+            .allow_tag("iframe")
+            .allow_attribute("style")
+            .allow_url_scheme("data")
+            .deny_tag("img");
+        assert_eq!(
+            custom.sanitize("<iframe src=\"data:x\" style=\"border:0\"></iframe><img src=y>"),
+            "<iframe src=\"data:x\" style=\"border:0\"></iframe>"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Standalone HTML pages from templates
+//!
+//! [`Document::to_html_with`] produces an HTML fragment. For a complete page
+//! [`Document::to_html_document_with`] collects the title, metadata, a table
+//! of contents and the body into an [`HtmlPage`] and renders it with an
+//! [`HtmlTemplate`]. [`PageTemplate`] substitutes `{{title}}`-style
+//! placeholders in a user-provided file without extra dependencies; the
+//! `minijinja` feature adds [`JinjaTemplate`] for loops and conditionals.
+
+use crate::convert::html_escape;
+use crate::{Document, HtmlOptions, HtmlOutput, Outline, OutlineEntry, ParseError};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The parts of a document a page template can place
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HtmlPage {
+    /// The metadata title, or the text of the first heading
+    pub title: String,
+    /// The metadata author
+    pub author: Option<String>,
+    /// The metadata date
+    pub date: Option<String>,
+    /// Custom metadata entries
+    pub metadata: BTreeMap<String, String>,
+    /// Table of contents as nested lists of links to the heading anchors,
+    /// empty without headings
+    pub toc: String,
+    /// The document body
+    pub body: String,
+}
+
+/// Renders an [`HtmlPage`] into a complete HTML document
+pub trait HtmlTemplate {
+    /// Renders `page`, or returns a human-readable message if the template
+    /// can't be rendered
+    fn render(&self, page: &HtmlPage) -> Result<String, String>;
+}
+
+/// A template with `{{name}}` placeholders
+///
+/// The placeholders are `{{title}}`, `{{author}}`, `{{date}}`,
+/// `{{meta.KEY}}` for custom metadata, `{{toc}}` and `{{body}}`, with
+/// optional spaces inside the braces. The table of contents and the body are
+/// inserted as HTML and everything else escaped; missing metadata is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageTemplate {
+    source: String,
+}
+
+impl Default for PageTemplate {
+    fn default() -> Self {
+        Self::new(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{{title}}</title>\n</head>\n<body>\n{{body}}\n</body>\n</html>\n",
+        )
+    }
+}
+
+impl PageTemplate {
+    /// Creates a template from its source
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+        }
+    }
+
+    /// Reads a template file
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::read_to_string(path).map(Self::new)
+    }
+}
+
+impl HtmlTemplate for PageTemplate {
+    fn render(&self, page: &HtmlPage) -> Result<String, String> {
+        let mut html = String::with_capacity(self.source.len() + page.body.len());
+        let mut rest = self.source.as_str();
+        while let Some(start) = rest.find("{{") {
+            html.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| "Unclosed placeholder in page template".to_string())?;
+            let name = rest[start + 2..start + end].trim();
+            let text = |value: Option<&String>| value.map(|v| html_escape(v)).unwrap_or_default();
+            html.push_str(&match name {
+                "title" => html_escape(&page.title),
+                "author" => text(page.author.as_ref()),
+                "date" => text(page.date.as_ref()),
+                "toc" => page.toc.clone(),
+                "body" => page.body.clone(),
+                _ => match name.strip_prefix("meta.") {
+                    Some(key) => text(page.metadata.get(key)),
+                    None => return Err(format!("Unknown placeholder: {{{{{name}}}}}")),
+                },
+            });
+            rest = &rest[start + end + 2..];
+        }
+        html.push_str(rest);
+        Ok(html)
+    }
+}
+
+/// A MiniJinja template, rendered with the [`HtmlPage`] fields as variables
+///
+/// Values are escaped except `toc` and `body`, so templates can write
+/// `{{ body }}` without `|safe`. Custom metadata is available as `metadata`.
+#[cfg(feature = "minijinja")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JinjaTemplate {
+    source: String,
+}
+
+#[cfg(feature = "minijinja")]
+impl JinjaTemplate {
+    /// Creates a template, or returns the syntax error in `source`
+    pub fn new(source: impl Into<String>) -> Result<Self, String> {
+        let source = source.into();
+        minijinja::Environment::new()
+            .template_from_str(&source)
+            .map_err(|err| err.to_string())?;
+        Ok(Self { source })
+    }
+
+    /// Reads a template file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        Self::new(std::fs::read_to_string(path).map_err(|err| err.to_string())?)
+    }
+}
+
+#[cfg(feature = "minijinja")]
+impl HtmlTemplate for JinjaTemplate {
+    fn render(&self, page: &HtmlPage) -> Result<String, String> {
+        use minijinja::{AutoEscape, Environment, Value, context};
+
+        let mut env = Environment::new();
+        env.set_auto_escape_callback(|_| AutoEscape::Html);
+        let context = context! {
+            title => &page.title,
+            author => &page.author,
+            date => &page.date,
+            metadata => &page.metadata,
+            toc => Value::from_safe_string(page.toc.clone()),
+            body => Value::from_safe_string(page.body.clone()),
+        };
+        env.render_str(&self.source, context)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Nested lists of links to the outline's headings
+fn toc_html(entries: &[OutlineEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from("<ul>");
+    for entry in entries {
+        html.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>{}</li>",
+            html_escape(&entry.anchor),
+            html_escape(&entry.text),
+            toc_html(&entry.children)
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+impl Document {
+    /// Collects the parts of a page, rendering the body with `options`
+    pub fn html_page(&self, options: &HtmlOptions) -> (HtmlPage, HtmlOutput) {
+        let output = self.to_html_with(options);
+        let metadata = self.metadata.clone().unwrap_or_default();
+        let outline = Outline::new(self);
+        let title = metadata
+            .title
+            .or_else(|| outline.iter().next().map(|entry| entry.text.clone()))
+            .unwrap_or_default();
+        let toc = match toc_html(&outline.entries) {
+            toc if toc.is_empty() => toc,
+            toc => format!("<nav class=\"toc\">{toc}</nav>"),
+        };
+        let page = HtmlPage {
+            title,
+            author: metadata.author,
+            date: metadata.date,
+            metadata: metadata.custom.into_iter().collect(),
+            toc,
+            body: output.html.clone(),
+        };
+        (page, output)
+    }
+
+    /// Converts the document to a complete HTML page with the default
+    /// template
+    pub fn to_html_document(&self) -> String {
+        let (page, _) = self.html_page(&HtmlOptions::default());
+        PageTemplate::default()
+            .render(&page)
+            .expect("the default template renders")
+    }
+
+    /// Converts the document to a complete HTML page with `template`
+    ///
+    /// The returned output's `html` is the whole page, with the body's math
+    /// diagnostics.
+    pub fn to_html_document_with(
+        &self,
+        options: &HtmlOptions,
+        template: &dyn HtmlTemplate,
+    ) -> Result<HtmlOutput, ParseError> {
+        let (page, output) = self.html_page(options);
+        let html = template.render(&page).map_err(ParseError::Generic)?;
+        Ok(HtmlOutput { html, ..output })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DocumentMetadata;
+
+    fn article() -> Document {
+        let mut doc = Document::new();
+        doc.metadata = Some(DocumentMetadata {
+            title: Some("Notes & Ideas".to_string()),
+            author: Some("Ada".to_string()),
+            date: None,
+            custom: vec![("lang".to_string(), "en".to_string())],
+        });
+        doc.add_heading(1, "Intro");
+        doc.add_paragraph_with_text("Hello");
+        doc.add_heading(2, "Details");
+        doc
+    }
+
+    #[test]
+    fn test_page_template() {
+        let template = PageTemplate::new(
+            "<html lang=\"{{ meta.lang }}\"><title>{{title}}</title>\
+             <p>{{author}}{{date}}{{meta.missing}}</p>{{toc}}<main>{{ body }}</main></html>",
+        );
+        let output = article()
+            .to_html_document_with(&HtmlOptions::default(), &template)
+            .unwrap();
+        assert_eq!(
+            output.html,
+            "<html lang=\"en\"><title>Notes &amp; Ideas</title><p>Ada</p>\
+             <nav class=\"toc\"><ul><li><a href=\"#intro\">Intro</a>\
+             <ul><li><a href=\"#details\">Details</a></li></ul></li></ul></nav>\
+             <main><h1 id=\"intro\">Intro</h1><p>Hello</p><h2 id=\"details\">Details</h2></main></html>"
+        );
+
+        let error = article()
+            .to_html_document_with(&HtmlOptions::default(), &PageTemplate::new("{{ nope }}"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Parse error: Unknown placeholder: {{nope}}"
+        );
+    }
+
+    #[test]
+    fn test_default_page_template() {
+        let mut doc = article();
+        doc.metadata = None;
+        let html = doc.to_html_document();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Intro</title>"));
+        assert!(html.contains("<body>\n<h1 id=\"intro\">Intro</h1>"));
+    }
+
+    #[cfg(feature = "minijinja")]
+    #[test]
+    fn test_jinja_template() {
+        let template = JinjaTemplate::new(
+            "<title>{{ title }}</title>{% if author %}<p>{{ author }}</p>{% endif %}\
+             {{ metadata.lang }}{{ body }}",
+        )
+        .unwrap();
+        let output = article()
+            .to_html_document_with(&HtmlOptions::default(), &template)
+            .unwrap();
+        assert!(
+            output
+                .html
+                .starts_with("<title>Notes &amp; Ideas</title><p>Ada</p>en<h1")
+        );
+
+        assert!(JinjaTemplate::new("{% if %}").is_err());
+    }
+}
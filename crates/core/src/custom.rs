@@ -0,0 +1,237 @@
+//! Extension blocks and inline directives
+//!
+//! Apps often add syntax of their own: ```` ```mermaid ```` diagrams,
+//! ```` ```chart ```` specs, or `:kbd[Ctrl]` inline directives. Registered
+//! in a [`Directives`] set, such syntax parses into [`Node::Custom`] and
+//! [`InlineNode::Custom`] nodes that keep their source, so they round-trip
+//! through Markdown and JSON unchanged. A [`CustomRenderer`] passed to
+//! [`HtmlOptions::with_custom_renderer`](crate::HtmlOptions::with_custom_renderer)
+//! turns them into HTML; without one they render as their escaped source.
+
+use crate::{Document, InlineNode, Node, TextNode};
+use std::collections::BTreeSet;
+
+/// Renders the data of custom nodes of one kind to HTML
+///
+/// Closures taking the data and whether the node is inline are renderers
+/// too. The output is inserted as is, so a renderer must escape anything
+/// it copies from the data.
+pub trait CustomRenderer {
+    /// Returns the HTML for a custom block, or with `inline` an inline
+    /// directive
+    fn render(&self, data: &str, inline: bool) -> String;
+}
+
+impl<F> CustomRenderer for F
+where
+    F: Fn(&str, bool) -> String,
+{
+    fn render(&self, data: &str, inline: bool) -> String {
+        self(data, inline)
+    }
+}
+
+/// Fenced block languages and inline directive names parsed as custom nodes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Directives {
+    blocks: BTreeSet<String>,
+    inlines: BTreeSet<String>,
+}
+
+impl Directives {
+    /// Creates an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses fenced code blocks with the language `kind` as custom blocks
+    pub fn with_block(mut self, kind: impl Into<String>) -> Self {
+        self.blocks.insert(kind.into());
+        self
+    }
+
+    /// Parses `:kind[data]` in text as inline directives
+    pub fn with_inline(mut self, kind: impl Into<String>) -> Self {
+        self.inlines.insert(kind.into());
+        self
+    }
+
+    /// Returns true if nothing is registered
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.inlines.is_empty()
+    }
+
+    /// Finds registered `:kind[data]` directives, as `(start, end, node)`
+    /// with byte offsets
+    ///
+    /// A directive starts at the start of the text or after a character
+    /// that can't be part of a word, and its data can't contain `]`.
+    fn find_inline(&self, text: &str) -> Vec<(usize, usize, InlineNode)> {
+        let mut found = Vec::new();
+        let mut search = 0;
+        while let Some(i) = text[search..].find(':') {
+            let start = search + i;
+            search = start + 1;
+            if text[..start]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            {
+                continue;
+            }
+            let rest = &text[start + 1..];
+            let Some(open) = rest.find('[') else {
+                break;
+            };
+            let kind = &rest[..open];
+            if !self.inlines.contains(kind) {
+                continue;
+            }
+            let Some(close) = rest[open + 1..].find(']') else {
+                continue;
+            };
+            let data = &rest[open + 1..open + 1 + close];
+            let end = start + 1 + open + 1 + close + 1;
+            found.push((
+                start,
+                end,
+                InlineNode::Custom {
+                    kind: kind.to_string(),
+                    data: data.to_string(),
+                },
+            ));
+            search = end;
+        }
+        found
+    }
+
+    /// Splits directives out of the text runs of `inlines`, returning how
+    /// many were found
+    ///
+    /// The parser may split text at brackets, so adjacent runs with the same
+    /// formatting are joined first. `inlines` is left as it was if there are
+    /// no directives.
+    fn split_inlines(&self, inlines: &mut Vec<InlineNode>) -> usize {
+        let mut joined: Vec<InlineNode> = Vec::with_capacity(inlines.len());
+        for inline in inlines.iter() {
+            match (joined.last_mut(), inline) {
+                (Some(InlineNode::Text(last)), InlineNode::Text(next))
+                    if last.formatting == next.formatting =>
+                {
+                    last.text.push_str(&next.text);
+                }
+                _ => joined.push(inline.clone()),
+            }
+        }
+
+        let mut found = 0;
+        let mut result = Vec::with_capacity(joined.len());
+        for mut inline in joined {
+            match &mut inline {
+                InlineNode::Text(TextNode { text, formatting }) if !formatting.code => {
+                    let mut last = 0;
+                    for (start, end, directive) in self.find_inline(text) {
+                        if start > last {
+                            result.push(InlineNode::Text(TextNode::with_formatting(
+                                &text[last..start],
+                                formatting.clone(),
+                            )));
+                        }
+                        result.push(directive);
+                        last = end;
+                        found += 1;
+                    }
+                    if last == 0 {
+                        result.push(inline);
+                    } else if last < text.len() {
+                        result.push(InlineNode::Text(TextNode::with_formatting(
+                            &text[last..],
+                            formatting.clone(),
+                        )));
+                    }
+                }
+                InlineNode::Link { children, .. } | InlineNode::InlineFootnote { children } => {
+                    found += self.split_inlines(children);
+                    result.push(inline);
+                }
+                _ => result.push(inline),
+            }
+        }
+        if found > 0 {
+            *inlines = result;
+        }
+        found
+    }
+}
+
+impl Document {
+    /// Turns registered fenced blocks and inline directives into custom
+    /// nodes, returning how many were created
+    ///
+    /// Code blocks are converted when their language is a registered block
+    /// kind, wherever they are nested. Inline directives are found in text
+    /// outside code.
+    pub fn parse_directives(&mut self, directives: &Directives) -> usize {
+        let mut found = 0;
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| {
+                if let Node::CodeBlock { language, code, .. } = block
+                    && directives.blocks.contains(language.as_str())
+                {
+                    *block = Node::Custom {
+                        kind: std::mem::take(language),
+                        data: std::mem::take(code),
+                    };
+                    found += 1;
+                }
+                if !directives.inlines.is_empty() {
+                    for inlines in block.inline_runs_mut() {
+                        found += directives.split_inlines(inlines);
+                    }
+                }
+            });
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    #[test]
+    fn test_parse_directives() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "Press :kbd[Ctrl] then:kbd[C], not `:kbd[X]` or :abbr[Y].\n\n\
+             ```mermaid\ngraph TD; A-->B\n```\n\n\
+             > ```rust\n> fn main() {}\n> ```\n",
+        ))
+        .unwrap();
+        let directives = Directives::new().with_block("mermaid").with_inline("kbd");
+        assert_eq!(doc.parse_directives(&directives), 2);
+
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap()[..3],
+            [
+                InlineNode::text("Press "),
+                InlineNode::Custom {
+                    kind: "kbd".to_string(),
+                    data: "Ctrl".to_string(),
+                },
+                InlineNode::text(" then:kbd[C], not "),
+            ]
+        );
+        assert_eq!(
+            doc.nodes[1],
+            Node::Custom {
+                kind: "mermaid".to_string(),
+                data: "graph TD; A-->B".to_string(),
+            }
+        );
+        assert!(matches!(
+            &doc.nodes[2],
+            Node::BlockQuote { children } if matches!(children[0], Node::CodeBlock { .. })
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+//! Lossy export detection
+//!
+//! Not every export format can express everything a document holds: merged
+//! table cells have no Markdown syntax, and plain text keeps neither
+//! formatting nor link targets. [`ExportTarget::supports`] answers which
+//! [`Feature`]s survive an export, and [`Document::degradation_report`]
+//! lists the nodes that use the ones that don't, so an app can warn before
+//! a lossy export.
+
+use crate::{CodeBlockProperties, Document, InlineNode, Node, TableProperties};
+use serde::Serialize;
+
+/// A format a document can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTarget {
+    /// Markdown, as written by the [`Text<Markdown>`](crate::Text) conversion
+    Markdown,
+    /// HTML, as written by [`Document::to_html_with`]
+    Html,
+    /// Plain text, as returned by [`Node::plain_text`]
+    PlainText,
+    /// JSON, which keeps the whole document
+    Json,
+}
+
+/// Something a document can contain that some export targets can't express
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Table cells spanning several columns or rows
+    CellSpans,
+    /// Table styling and caption properties
+    TableStyling,
+    /// Typed values stored alongside table cell text
+    CellValues,
+    /// Code block line numbers, themes, highlighted lines and notebook
+    /// outputs
+    CodeBlockOptions,
+    /// Embedded media with a provider, preview HTML or aspect ratio
+    Embeds,
+    /// Footnotes written inline rather than as a labelled definition
+    InlineFootnotes,
+    /// Bold, italic, strikethrough and code formatting
+    Formatting,
+    /// Link targets
+    Links,
+    /// Images and figures
+    Images,
+    /// Table layout
+    Tables,
+    /// Heading levels
+    Headings,
+    /// Checked and unchecked task items
+    TaskStates,
+    /// Rendered math
+    Math,
+    /// Raw HTML blocks and tags
+    RawHtml,
+    /// Note, tip and warning callouts
+    Callouts,
+}
+
+impl Feature {
+    /// Every feature, in declaration order
+    pub const ALL: [Feature; 15] = [
+        Self::CellSpans,
+        Self::TableStyling,
+        Self::CellValues,
+        Self::CodeBlockOptions,
+        Self::Embeds,
+        Self::InlineFootnotes,
+        Self::Formatting,
+        Self::Links,
+        Self::Images,
+        Self::Tables,
+        Self::Headings,
+        Self::TaskStates,
+        Self::Math,
+        Self::RawHtml,
+        Self::Callouts,
+    ];
+
+    /// What an export to a target lacking the feature loses
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::CellSpans => "merged cells are split into single cells",
+            Self::TableStyling => "table styling and caption are dropped",
+            Self::CellValues => "typed cell values are dropped, keeping the text",
+            Self::CodeBlockOptions => "code block display options are dropped",
+            Self::Embeds => "the embed is reduced to its URL",
+            Self::InlineFootnotes => "the inline footnote's content is dropped",
+            Self::Formatting => "text formatting is dropped",
+            Self::Links => "link targets are dropped",
+            Self::Images => "images are reduced to their alt text",
+            Self::Tables => "the table is flattened to tab-separated lines",
+            Self::Headings => "headings become plain lines",
+            Self::TaskStates => "task check marks are dropped",
+            Self::Math => "math is left as TeX source",
+            Self::RawHtml => "raw HTML tags are dropped",
+            Self::Callouts => "the callout's kind and title are dropped",
+        }
+    }
+}
+
+impl ExportTarget {
+    /// Returns true if exporting to this target keeps the feature
+    pub fn supports(self, feature: Feature) -> bool {
+        match self {
+            Self::Html | Self::Json => true,
+            Self::Markdown => !matches!(
+                feature,
+                Feature::CellSpans
+                    | Feature::TableStyling
+                    | Feature::CellValues
+                    | Feature::CodeBlockOptions
+                    | Feature::Embeds
+                    | Feature::InlineFootnotes
+            ),
+            Self::PlainText => false,
+        }
+    }
+}
+
+/// A node whose export to the target loses a feature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DegradedNode {
+    /// Path to the node: the top-level node index followed by the child
+    /// indices through any containers
+    pub path: Vec<usize>,
+    /// Serialized type of the node, e.g. `"table"`
+    pub node_type: &'static str,
+    /// The feature the target lacks
+    pub feature: Feature,
+}
+
+impl DegradedNode {
+    /// Index of the top-level node containing the degraded node
+    pub fn node_index(&self) -> usize {
+        self.path[0]
+    }
+
+    /// What the export loses
+    pub fn description(&self) -> &'static str {
+        self.feature.description()
+    }
+}
+
+/// Every loss an export to a target would cause, in document order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DegradationReport {
+    /// The export target checked
+    pub target: ExportTarget,
+    /// Nodes using features the target lacks, one entry per feature
+    pub nodes: Vec<DegradedNode>,
+}
+
+impl DegradationReport {
+    /// Returns true if the export keeps everything
+    pub fn is_lossless(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Number of degraded node and feature pairs
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if no node degrades
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Distinct features lost, in order of first occurrence
+    pub fn features(&self) -> Vec<Feature> {
+        let mut features = Vec::new();
+        for node in &self.nodes {
+            if !features.contains(&node.feature) {
+                features.push(node.feature);
+            }
+        }
+        features
+    }
+}
+
+/// Features a block uses itself, not counting nested blocks
+fn block_features(node: &Node) -> Vec<Feature> {
+    let mut features = Vec::new();
+    match node {
+        Node::Heading { .. } => features.push(Feature::Headings),
+        Node::Table {
+            header,
+            rows,
+            properties,
+            ..
+        } => {
+            features.push(Feature::Tables);
+            if header
+                .iter()
+                .chain(rows.iter().flatten())
+                .any(|cell| cell.colspan > 1 || cell.rowspan > 1)
+            {
+                features.push(Feature::CellSpans);
+            }
+            if *properties != TableProperties::default() {
+                features.push(Feature::TableStyling);
+            }
+            if header
+                .iter()
+                .chain(rows.iter().flatten())
+                .any(|cell| cell.value.is_some())
+            {
+                features.push(Feature::CellValues);
+            }
+        }
+        Node::CodeBlock { properties, .. } if *properties != CodeBlockProperties::default() => {
+            features.push(Feature::CodeBlockOptions)
+        }
+        Node::List { items, .. } if items.iter().any(|item| item.checked.is_some()) => {
+            features.push(Feature::TaskStates)
+        }
+        Node::Embed { .. } => features.push(Feature::Embeds),
+        Node::Figure { .. } => features.push(Feature::Images),
+        Node::MathBlock { .. } => features.push(Feature::Math),
+        Node::HtmlBlock { .. } => features.push(Feature::RawHtml),
+        Node::Callout { .. } => features.push(Feature::Callouts),
+        _ => {}
+    }
+
+    for inline in node.inline_runs().into_iter().flatten() {
+        inline.walk(&mut |inline| {
+            let feature = match inline {
+                InlineNode::Text(text) if text.formatting != Default::default() => {
+                    Feature::Formatting
+                }
+                InlineNode::CodeSpan { .. } => Feature::Formatting,
+                InlineNode::Link { .. } => Feature::Links,
+                InlineNode::Image { .. } => Feature::Images,
+                InlineNode::InlineFootnote { .. } => Feature::InlineFootnotes,
+                InlineNode::Math { .. } => Feature::Math,
+                InlineNode::Html { .. } => Feature::RawHtml,
+                _ => return,
+            };
+            if !features.contains(&feature) {
+                features.push(feature);
+            }
+        });
+    }
+    features
+}
+
+impl Document {
+    /// Lists the nodes an export to `target` would degrade
+    pub fn degradation_report(&self, target: ExportTarget) -> DegradationReport {
+        let mut nodes = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            node.walk_with_path(&mut vec![index], &mut |block, path| {
+                for feature in block_features(block) {
+                    if !target.supports(feature) {
+                        nodes.push(DegradedNode {
+                            path: path.to_vec(),
+                            node_type: block.type_name(),
+                            feature,
+                        });
+                    }
+                }
+            });
+        }
+        DegradationReport { target, nodes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, TableCell, Text};
+
+    #[test]
+    fn test_degradation_report() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "# Title\n\nSome **bold** text with a [link](https://example.com).\n\n\
+             > - [x] Done\n\n| A | B |\n|---|---|\n| 1 | 2 |\n",
+        ))
+        .unwrap();
+        if let Node::Table { header, .. } = &mut doc.nodes[3] {
+            *header = vec![TableCell::with_colspan(vec![InlineNode::text("Merged")], 2)];
+        }
+
+        assert!(doc.degradation_report(ExportTarget::Json).is_lossless());
+        assert!(doc.degradation_report(ExportTarget::Html).is_lossless());
+
+        let markdown = doc.degradation_report(ExportTarget::Markdown);
+        assert_eq!(markdown.len(), 1);
+        assert_eq!(markdown.nodes[0].path, vec![3]);
+        assert_eq!(markdown.nodes[0].node_type, "table");
+        assert_eq!(markdown.nodes[0].feature, Feature::CellSpans);
+
+        let plain = doc.degradation_report(ExportTarget::PlainText);
+        assert_eq!(
+            plain.features(),
+            [
+                Feature::Headings,
+                Feature::Formatting,
+                Feature::Links,
+                Feature::TaskStates,
+                Feature::Tables,
+                Feature::CellSpans
+            ]
+        );
+        // The task list is found inside the blockquote
+        assert_eq!(plain.nodes[3].path, vec![2, 0]);
+        assert_eq!(plain.nodes[3].node_index(), 2);
+        assert_eq!(plain.nodes[3].description(), "task check marks are dropped");
+    }
+}
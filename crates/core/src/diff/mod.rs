@@ -0,0 +1,598 @@
+//! Structural diffing between documents
+//!
+//! A diff aligns the block nodes of two documents and reports which nodes were
+//! inserted, deleted, or modified. Modified text-bearing nodes also carry an
+//! inline, word-level text diff, which can be rendered as HTML or reduced to
+//! compact [`TextEdit`] patches.
+
+mod text;
+mod unified;
+
+pub use text::{
+    DiffGranularity, TextChange, TextEdit, apply_text_edits, diff_text, diff_text_with, text_edits,
+};
+pub use unified::{to_text_diff, to_text_diff_with};
+
+use crate::convert::html::node_to_html;
+use crate::convert::html_escape;
+use crate::convert::markdown::node_to_markdown;
+use crate::{Document, EditError, InlineNode, Node};
+use serde::{Deserialize, Serialize};
+use std::mem::discriminant;
+
+/// A single node-level change between two documents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NodeChange {
+    /// A node that only exists in the new document
+    #[serde(rename = "insert")]
+    Insert {
+        /// Index of the node in the new document
+        index: usize,
+        /// The inserted node
+        node: Node,
+    },
+    /// A node that only exists in the old document
+    #[serde(rename = "delete")]
+    Delete {
+        /// Index of the node in the old document
+        index: usize,
+        /// The deleted node
+        node: Node,
+    },
+    /// A node of the same kind whose content changed
+    #[serde(rename = "modify")]
+    Modify {
+        /// Index of the node in the old document
+        old_index: usize,
+        /// Index of the node in the new document
+        new_index: usize,
+        /// The node before the change
+        old: Box<Node>,
+        /// The node after the change
+        new: Box<Node>,
+        /// Word-level diff of the node's plain text
+        text_changes: Vec<TextChange>,
+    },
+}
+
+impl NodeChange {
+    /// Returns the byte-offset edits that turn the old plain text of a
+    /// modified node into the new one
+    ///
+    /// Only [`NodeChange::Modify`] carries a text diff; other changes return
+    /// `None`.
+    pub fn text_edits(&self) -> Option<Vec<TextEdit>> {
+        match self {
+            Self::Modify { text_changes, .. } => Some(text_edits(text_changes)),
+            _ => None,
+        }
+    }
+}
+
+/// An ordered set of changes that transforms one document into another
+///
+/// Changes are stored in document order, so walking them alongside the base
+/// document reproduces the target document. Change sets serialize with serde,
+/// so a diff computed on one copy of a document can be replayed on another.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// The node-level changes, in document order
+    pub changes: Vec<NodeChange>,
+}
+
+/// One step of a walk over the base document guided by a change set
+enum Step<'a> {
+    /// A base node carried over unchanged
+    Keep(&'a Node),
+    /// A change from the change set
+    Change(&'a NodeChange),
+}
+
+impl ChangeSet {
+    /// Returns true if the documents were identical
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the number of node-level changes
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns the change set that undoes this one
+    ///
+    /// Applying the inverse to the target document reproduces the base.
+    pub fn invert(&self) -> ChangeSet {
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| match change {
+                NodeChange::Insert { index, node } => NodeChange::Delete {
+                    index: *index,
+                    node: node.clone(),
+                },
+                NodeChange::Delete { index, node } => NodeChange::Insert {
+                    index: *index,
+                    node: node.clone(),
+                },
+                NodeChange::Modify {
+                    old_index,
+                    new_index,
+                    old,
+                    new,
+                    text_changes,
+                } => NodeChange::Modify {
+                    old_index: *new_index,
+                    new_index: *old_index,
+                    old: new.clone(),
+                    new: old.clone(),
+                    text_changes: text_changes.iter().map(TextChange::invert).collect(),
+                },
+            })
+            .collect();
+
+        ChangeSet { changes }
+    }
+
+    /// Walks the base document, interleaving unchanged nodes with the changes
+    ///
+    /// Fails with [`EditErrorKind::Conflict`](crate::EditErrorKind::Conflict) if a deleted or modified base node
+    /// differs from the node recorded in the change.
+    fn walk<'a>(&'a self, base: &'a [Node]) -> Result<Vec<Step<'a>>, EditError> {
+        /// Keeps base nodes up to `target`, returning how many were kept
+        fn keep_until<'a>(
+            steps: &mut Vec<Step<'a>>,
+            base: &'a [Node],
+            old_pos: &mut usize,
+            target: usize,
+        ) -> Result<usize, EditError> {
+            if target < *old_pos || target > base.len() {
+                return Err(EditError::invalid_range());
+            }
+            steps.extend(base[*old_pos..target].iter().map(Step::Keep));
+            let kept = target - *old_pos;
+            *old_pos = target;
+            Ok(kept)
+        }
+
+        let mut steps = Vec::new();
+        let (mut old_pos, mut new_pos) = (0usize, 0usize);
+
+        for change in &self.changes {
+            match change {
+                NodeChange::Insert { index, .. } => {
+                    let gap = index
+                        .checked_sub(new_pos)
+                        .ok_or(EditError::invalid_range())?;
+                    let target = old_pos + gap;
+                    new_pos += keep_until(&mut steps, base, &mut old_pos, target)? + 1;
+                }
+                NodeChange::Delete { index, node } => {
+                    new_pos += keep_until(&mut steps, base, &mut old_pos, *index)?;
+                    match base.get(old_pos) {
+                        Some(current) if current == node => old_pos += 1,
+                        Some(_) => return Err(EditError::conflict(old_pos)),
+                        None => return Err(EditError::index_out_of_bounds()),
+                    }
+                }
+                NodeChange::Modify { old_index, old, .. } => {
+                    new_pos += keep_until(&mut steps, base, &mut old_pos, *old_index)? + 1;
+                    match base.get(old_pos) {
+                        Some(current) if current == old.as_ref() => old_pos += 1,
+                        Some(_) => return Err(EditError::conflict(old_pos)),
+                        None => return Err(EditError::index_out_of_bounds()),
+                    }
+                }
+            }
+            steps.push(Step::Change(change));
+        }
+
+        keep_until(&mut steps, base, &mut old_pos, base.len())?;
+        Ok(steps)
+    }
+
+    /// Applies the change set to a document, transforming it into the target
+    ///
+    /// The document is left untouched if the change set does not fit it.
+    pub fn apply(&self, document: &mut Document) -> Result<(), EditError> {
+        let nodes = self
+            .walk(&document.nodes)?
+            .into_iter()
+            .filter_map(|step| match step {
+                Step::Keep(node) => Some(node.clone()),
+                Step::Change(NodeChange::Insert { node, .. }) => Some(node.clone()),
+                Step::Change(NodeChange::Modify { new, .. }) => Some(new.as_ref().clone()),
+                Step::Change(NodeChange::Delete { .. }) => None,
+            })
+            .collect();
+
+        document.nodes = nodes;
+        Ok(())
+    }
+
+    /// Renders the change set against its base document as markdown with
+    /// `<ins>` and `<del>` marks around inserted and deleted content
+    pub fn to_markdown(&self, base: &Document) -> Result<String, EditError> {
+        let blocks: Vec<String> = self
+            .walk(&base.nodes)?
+            .into_iter()
+            .map(|step| match step {
+                Step::Keep(node) => node_to_markdown(node),
+                Step::Change(NodeChange::Insert { node, .. }) => mark_node(node, "ins"),
+                Step::Change(NodeChange::Delete { node, .. }) => mark_node(node, "del"),
+                Step::Change(NodeChange::Modify {
+                    old,
+                    new,
+                    text_changes,
+                    ..
+                }) => match new.as_ref() {
+                    Node::Heading { level, .. } => {
+                        format!(
+                            "{} {}",
+                            "#".repeat(*level as usize),
+                            mark_text(text_changes)
+                        )
+                    }
+                    Node::Paragraph { .. } => mark_text(text_changes),
+                    _ => format!("{}\n\n{}", mark_node(old, "del"), mark_node(new, "ins")),
+                },
+            })
+            .collect();
+
+        Ok(blocks.join("\n\n"))
+    }
+
+    /// Renders the change set against its base document as HTML
+    ///
+    /// Modified paragraphs and headings show their word diff inline with
+    /// `<ins>` and `<del>`; other changed blocks are wrapped whole.
+    pub fn to_html(&self, base: &Document) -> Result<String, EditError> {
+        let render =
+            |node: &Node| node_to_html(node, 0).map_err(|err| EditError::other(err.to_string()));
+        let wrap =
+            |node: &Node, tag: &str| render(node).map(|html| format!("<{tag}>{html}</{tag}>"));
+
+        let mut html = String::new();
+        for step in self.walk(&base.nodes)? {
+            let block = match step {
+                Step::Keep(node) => render(node)?,
+                Step::Change(NodeChange::Insert { node, .. }) => wrap(node, "ins")?,
+                Step::Change(NodeChange::Delete { node, .. }) => wrap(node, "del")?,
+                Step::Change(NodeChange::Modify {
+                    old,
+                    new,
+                    text_changes,
+                    ..
+                }) => match new.as_ref() {
+                    Node::Heading { level, .. } => {
+                        format!("<h{level}>{}</h{level}>", mark_html(text_changes))
+                    }
+                    Node::Paragraph { .. } => format!("<p>{}</p>", mark_html(text_changes)),
+                    _ => format!("{}{}", wrap(old, "del")?, wrap(new, "ins")?),
+                },
+            };
+            html.push_str(&block);
+            html.push('\n');
+        }
+        Ok(html)
+    }
+}
+
+/// Wraps a node's markdown in the given mark tag
+fn mark_node(node: &Node, tag: &str) -> String {
+    match node {
+        Node::Heading {
+            level, children, ..
+        } => format!(
+            "{} <{tag}>{}</{tag}>",
+            "#".repeat(*level as usize),
+            InlineNode::plain_text_of(children)
+        ),
+        Node::Paragraph { children } => {
+            format!("<{tag}>{}</{tag}>", InlineNode::plain_text_of(children))
+        }
+        _ => format!("<{tag}>\n\n{}\n\n</{tag}>", node_to_markdown(node)),
+    }
+}
+
+/// Renders an inline text diff with mark tags
+fn mark_text(changes: &[TextChange]) -> String {
+    changes
+        .iter()
+        .map(|change| match change {
+            TextChange::Equal(text) => text.clone(),
+            TextChange::Insert(text) => format!("<ins>{}</ins>", text),
+            TextChange::Delete(text) => format!("<del>{}</del>", text),
+        })
+        .collect()
+}
+
+/// Renders an inline text diff as escaped HTML with mark tags
+fn mark_html(changes: &[TextChange]) -> String {
+    changes
+        .iter()
+        .map(|change| match change {
+            TextChange::Equal(text) => html_escape(text),
+            TextChange::Insert(text) => format!("<ins>{}</ins>", html_escape(text)),
+            TextChange::Delete(text) => format!("<del>{}</del>", html_escape(text)),
+        })
+        .collect()
+}
+
+/// Computes the structural diff that transforms `old` into `new`
+pub fn diff_documents(old: &Document, new: &Document) -> ChangeSet {
+    let (a, b) = (&old.nodes, &new.nodes);
+    let (n, m) = (a.len(), b.len());
+
+    // lcs[i][j] holds the LCS length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut gap_i, mut gap_j) = (0, 0);
+
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            push_gap(&mut changes, a, b, gap_i..i, gap_j..j);
+            i += 1;
+            j += 1;
+            gap_i = i;
+            gap_j = j;
+        } else if j >= m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    push_gap(&mut changes, a, b, gap_i..n, gap_j..m);
+
+    ChangeSet { changes }
+}
+
+/// Emits changes for an unmatched region, pairing nodes of the same kind as modifications
+fn push_gap(
+    changes: &mut Vec<NodeChange>,
+    old: &[Node],
+    new: &[Node],
+    old_range: std::ops::Range<usize>,
+    new_range: std::ops::Range<usize>,
+) {
+    let mut old_iter = old_range.peekable();
+    let mut new_iter = new_range.peekable();
+
+    loop {
+        match (old_iter.peek().copied(), new_iter.peek().copied()) {
+            (Some(oi), Some(ni)) if discriminant(&old[oi]) == discriminant(&new[ni]) => {
+                changes.push(NodeChange::Modify {
+                    old_index: oi,
+                    new_index: ni,
+                    old: Box::new(old[oi].clone()),
+                    new: Box::new(new[ni].clone()),
+                    text_changes: diff_text(&old[oi].plain_text(), &new[ni].plain_text()),
+                });
+                old_iter.next();
+                new_iter.next();
+            }
+            (Some(oi), _) => {
+                changes.push(NodeChange::Delete {
+                    index: oi,
+                    node: old[oi].clone(),
+                });
+                old_iter.next();
+            }
+            (None, Some(ni)) => {
+                changes.push(NodeChange::Insert {
+                    index: ni,
+                    node: new[ni].clone(),
+                });
+                new_iter.next();
+            }
+            (None, None) => break,
+        }
+    }
+}
+
+impl Document {
+    /// Computes the structural diff that transforms this document into `other`
+    pub fn diff(&self, other: &Document) -> ChangeSet {
+        diff_documents(self, other)
+    }
+
+    /// Replays a change set computed against another copy of this document
+    ///
+    /// Returns [`EditErrorKind::Conflict`](crate::EditErrorKind::Conflict) with the offending node index when this
+    /// document has diverged from the change set's base, in which case the
+    /// document is left unchanged.
+    pub fn apply_changeset(&mut self, changes: &ChangeSet) -> Result<(), EditError> {
+        changes.apply(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_document() -> Document {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Release Notes");
+        doc.add_paragraph_with_text("The quick brown fox");
+        doc.add_code_block("let x = 1;", "rust");
+        doc.add_paragraph_with_text("Goodbye");
+        doc
+    }
+
+    #[test]
+    fn test_identical_documents() {
+        let doc = base_document();
+        assert!(doc.diff(&doc.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_node_level_changes() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes.remove(2);
+        new.nodes[1] = Node::paragraph("The slow brown fox");
+        new.add_paragraph_with_text("Appendix");
+
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), 3);
+
+        match &changes.changes[0] {
+            NodeChange::Modify {
+                old_index,
+                new_index,
+                text_changes,
+                ..
+            } => {
+                assert_eq!((*old_index, *new_index), (1, 1));
+                assert!(text_changes.contains(&TextChange::Delete("quick".to_string())));
+                assert!(text_changes.contains(&TextChange::Insert("slow".to_string())));
+            }
+            other => panic!("Expected modify, got {:?}", other),
+        }
+        assert!(matches!(
+            changes.changes[1],
+            NodeChange::Delete { index: 2, .. }
+        ));
+        assert!(matches!(
+            changes.changes[2],
+            NodeChange::Insert { index: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn test_apply_reproduces_target() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes.insert(0, Node::paragraph("Preface"));
+        new.nodes[2] = Node::paragraph("A quick brown fox");
+        new.nodes.pop();
+
+        let changes = old.diff(&new);
+        let mut patched = old.clone();
+        changes.apply(&mut patched).unwrap();
+        assert_eq!(patched.nodes, new.nodes);
+    }
+
+    #[test]
+    fn test_apply_rejects_mismatched_base() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes.truncate(1);
+
+        let changes = old.diff(&new);
+        let mut short = Document::new();
+        short.add_heading(1, "Release Notes");
+        assert!(changes.apply(&mut short).is_err());
+    }
+
+    #[test]
+    fn test_replay_serialized_changeset() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes[1] = Node::paragraph("The quick red fox");
+        new.nodes.remove(2);
+
+        let json = serde_json::to_string(&old.diff(&new)).unwrap();
+        assert!(json.contains("\"type\":\"modify\""));
+        let changes: ChangeSet = serde_json::from_str(&json).unwrap();
+
+        let mut replica = base_document();
+        replica.apply_changeset(&changes).unwrap();
+        assert_eq!(replica.nodes, new.nodes);
+    }
+
+    #[test]
+    fn test_replay_detects_conflicts() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes[1] = Node::paragraph("The quick red fox");
+
+        // Someone else edited the same paragraph on the replica
+        let mut replica = base_document();
+        replica.nodes[1] = Node::paragraph("A lazy dog");
+        let before = replica.clone();
+
+        let result = replica.apply_changeset(&old.diff(&new));
+        assert_eq!(result.unwrap_err(), EditError::conflict(1));
+        assert_eq!(replica, before);
+
+        // A deleted node that changed remotely is a conflict too
+        let mut new = base_document();
+        new.nodes.remove(2);
+        let mut replica = base_document();
+        replica.nodes[2] = Node::code_block("let x = 2;", "rust");
+        assert_eq!(
+            replica.apply_changeset(&old.diff(&new)).unwrap_err(),
+            EditError::conflict(2)
+        );
+    }
+
+    #[test]
+    fn test_invert_restores_base() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes.insert(1, Node::paragraph("Summary"));
+        new.nodes[2] = Node::paragraph("The quick brown cat");
+        new.nodes.remove(3);
+
+        let mut restored = new.clone();
+        old.diff(&new).invert().apply(&mut restored).unwrap();
+        assert_eq!(restored.nodes, old.nodes);
+    }
+
+    #[test]
+    fn test_render_markdown_with_marks() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes[1] = Node::paragraph("The slow brown fox");
+        new.nodes.remove(3);
+        new.add_heading(2, "Credits");
+
+        let markdown = old.diff(&new).to_markdown(&old).unwrap();
+
+        assert!(markdown.starts_with("# Release Notes"));
+        assert!(markdown.contains("The <del>quick</del><ins>slow</ins> brown fox"));
+        assert!(markdown.contains("```rust"));
+        assert!(markdown.contains("<del>Goodbye</del>"));
+        assert!(markdown.contains("## <ins>Credits</ins>"));
+    }
+
+    #[test]
+    fn test_render_html_and_text_edits() {
+        let old = base_document();
+        let mut new = base_document();
+        new.nodes[1] = Node::paragraph("The slow brown fox & friends");
+        new.nodes.remove(3);
+
+        let changes = old.diff(&new);
+        let html = changes.to_html(&old).unwrap();
+        assert!(html.contains(
+            "<p>The <del>quick</del><ins>slow</ins> brown fox<ins> &amp; friends</ins></p>"
+        ));
+        assert!(html.contains("<del><p>Goodbye</p></del>"));
+
+        let modify = changes
+            .changes
+            .iter()
+            .find(|change| matches!(change, NodeChange::Modify { .. }))
+            .unwrap();
+        let edits = modify.text_edits().unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            apply_text_edits("The quick brown fox", &edits).unwrap(),
+            "The slow brown fox & friends"
+        );
+    }
+}
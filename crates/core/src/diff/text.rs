@@ -0,0 +1,330 @@
+use crate::EditError;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A single run in an inline text diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "text")]
+pub enum TextChange {
+    /// Text present in both versions
+    #[serde(rename = "equal")]
+    Equal(String),
+    /// Text present only in the new version
+    #[serde(rename = "insert")]
+    Insert(String),
+    /// Text present only in the old version
+    #[serde(rename = "delete")]
+    Delete(String),
+}
+
+impl TextChange {
+    /// Returns the text carried by this change
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Equal(text) | Self::Insert(text) | Self::Delete(text) => text,
+        }
+    }
+
+    /// Returns the change as seen from the new text towards the old one
+    pub fn invert(&self) -> TextChange {
+        match self {
+            Self::Equal(text) => Self::Equal(text.clone()),
+            Self::Insert(text) => Self::Delete(text.clone()),
+            Self::Delete(text) => Self::Insert(text.clone()),
+        }
+    }
+}
+
+/// Splits text into word and whitespace tokens, preserving every character
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut last_is_space: Option<bool> = None;
+
+    for (idx, ch) in text.char_indices() {
+        let is_space = ch.is_whitespace();
+        if let Some(prev) = last_is_space
+            && prev != is_space
+        {
+            tokens.push(&text[start..idx]);
+            start = idx;
+        }
+        last_is_space = Some(is_space);
+    }
+
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+
+    tokens
+}
+
+/// Appends a change, merging it into the previous run when the kinds match
+fn push_change(changes: &mut Vec<TextChange>, change: TextChange) {
+    match (changes.last_mut(), change) {
+        (Some(TextChange::Equal(prev)), TextChange::Equal(text))
+        | (Some(TextChange::Insert(prev)), TextChange::Insert(text))
+        | (Some(TextChange::Delete(prev)), TextChange::Delete(text)) => prev.push_str(&text),
+        (_, change) => changes.push(change),
+    }
+}
+
+/// The unit a text diff works in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffGranularity {
+    /// Words and the whitespace between them
+    #[default]
+    Word,
+    /// Grapheme clusters, i.e. user-perceived characters
+    Grapheme,
+}
+
+/// Computes a word-level diff between two strings
+///
+/// Deletions are emitted before insertions within each changed region, so
+/// replaying the `Equal` and `Delete` runs yields the old text and replaying the
+/// `Equal` and `Insert` runs yields the new text.
+pub fn diff_text(old: &str, new: &str) -> Vec<TextChange> {
+    diff_text_with(old, new, DiffGranularity::Word)
+}
+
+/// Computes a diff between two strings at the given granularity
+///
+/// Grapheme diffs suit short edits such as typo fixes, where a word diff
+/// would replace the whole word.
+pub fn diff_text_with(old: &str, new: &str, granularity: DiffGranularity) -> Vec<TextChange> {
+    let (old_tokens, new_tokens) = match granularity {
+        DiffGranularity::Word => (tokenize(old), tokenize(new)),
+        DiffGranularity::Grapheme => (old.graphemes(true).collect(), new.graphemes(true).collect()),
+    };
+    diff_tokens(&old_tokens, &new_tokens)
+}
+
+pub(super) fn diff_tokens(old_tokens: &[&str], new_tokens: &[&str]) -> Vec<TextChange> {
+    // The common prefix and suffix need no LCS table
+    let prefix = old_tokens
+        .iter()
+        .zip(new_tokens)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_tokens[prefix..]
+        .iter()
+        .rev()
+        .zip(new_tokens[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old_tokens[prefix..old_tokens.len() - suffix];
+    let new_middle = &new_tokens[prefix..new_tokens.len() - suffix];
+    let (n, m) = (old_middle.len(), new_middle.len());
+
+    // lcs[i][j] holds the LCS length of old_middle[i..] and new_middle[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_middle[i] == new_middle[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    if prefix > 0 {
+        push_change(
+            &mut changes,
+            TextChange::Equal(old_tokens[..prefix].concat()),
+        );
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_middle[i] == new_middle[j] {
+            push_change(&mut changes, TextChange::Equal(old_middle[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j >= m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push_change(&mut changes, TextChange::Delete(old_middle[i].to_string()));
+            i += 1;
+        } else {
+            push_change(&mut changes, TextChange::Insert(new_middle[j].to_string()));
+            j += 1;
+        }
+    }
+    if suffix > 0 {
+        let start = old_tokens.len() - suffix;
+        push_change(
+            &mut changes,
+            TextChange::Equal(old_tokens[start..].concat()),
+        );
+    }
+
+    changes
+}
+
+/// A compact replacement in a string
+///
+/// Offsets are byte offsets into the original text, so a list of edits can
+/// be stored or sent without the unchanged text around them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    /// Byte offset in the original text where the edit starts
+    pub offset: usize,
+    /// Number of bytes removed at the offset
+    pub delete: usize,
+    /// Text inserted at the offset
+    pub insert: String,
+}
+
+/// Converts a text diff into the edits that turn the old text into the new one
+///
+/// Adjacent deletions and insertions become a single replacement.
+pub fn text_edits(changes: &[TextChange]) -> Vec<TextEdit> {
+    let mut edits: Vec<TextEdit> = Vec::new();
+    let mut offset = 0;
+
+    for change in changes {
+        let last = edits
+            .last_mut()
+            .filter(|edit| edit.offset + edit.delete == offset);
+        match change {
+            TextChange::Equal(text) => offset += text.len(),
+            TextChange::Delete(text) => {
+                match last {
+                    Some(edit) => edit.delete += text.len(),
+                    None => edits.push(TextEdit {
+                        offset,
+                        delete: text.len(),
+                        insert: String::new(),
+                    }),
+                }
+                offset += text.len();
+            }
+            TextChange::Insert(text) => match last {
+                Some(edit) => edit.insert.push_str(text),
+                None => edits.push(TextEdit {
+                    offset,
+                    delete: 0,
+                    insert: text.clone(),
+                }),
+            },
+        }
+    }
+
+    edits
+}
+
+/// Applies edits produced by [`text_edits`] to the original text
+///
+/// Fails with [`EditErrorKind::InvalidRange`](crate::EditErrorKind::InvalidRange) if the edits overlap, are out of
+/// order, or do not fall on character boundaries of `text`.
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> Result<String, EditError> {
+    let mut result = String::with_capacity(text.len());
+    let mut position = 0;
+
+    for edit in edits {
+        let end = edit.offset + edit.delete;
+        if edit.offset < position
+            || end > text.len()
+            || !text.is_char_boundary(edit.offset)
+            || !text.is_char_boundary(end)
+        {
+            return Err(EditError::invalid_range());
+        }
+        result.push_str(&text[position..edit.offset]);
+        result.push_str(&edit.insert);
+        position = end;
+    }
+
+    result.push_str(&text[position..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_preserves_text() {
+        let text = "Hello,  brave new\tworld";
+        assert_eq!(tokenize(text).concat(), text);
+        assert_eq!(tokenize(text).len(), 7);
+    }
+
+    #[test]
+    fn test_diff_text_words() {
+        let changes = diff_text("The quick brown fox", "The slow brown fox jumps");
+
+        assert_eq!(
+            changes,
+            vec![
+                TextChange::Equal("The ".to_string()),
+                TextChange::Delete("quick".to_string()),
+                TextChange::Insert("slow".to_string()),
+                TextChange::Equal(" brown fox".to_string()),
+                TextChange::Insert(" jumps".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_text_graphemes() {
+        let changes = diff_text_with("colour 👍🏽", "color 👍🏿", DiffGranularity::Grapheme);
+        assert_eq!(
+            changes,
+            vec![
+                TextChange::Equal("colo".to_string()),
+                TextChange::Delete("u".to_string()),
+                TextChange::Equal("r ".to_string()),
+                TextChange::Delete("👍🏽".to_string()),
+                TextChange::Insert("👍🏿".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_edits_round_trip() {
+        let old = "The quick brown fox";
+        let new = "The slow brown fox jumps";
+        let edits = text_edits(&diff_text(old, new));
+        assert_eq!(
+            edits,
+            vec![
+                TextEdit {
+                    offset: 4,
+                    delete: 5,
+                    insert: "slow".to_string(),
+                },
+                TextEdit {
+                    offset: 19,
+                    delete: 0,
+                    insert: " jumps".to_string(),
+                },
+            ]
+        );
+        assert_eq!(apply_text_edits(old, &edits).unwrap(), new);
+
+        let edits = text_edits(&diff_text_with("naïve", "naive", DiffGranularity::Grapheme));
+        assert_eq!(apply_text_edits("naïve", &edits).unwrap(), "naive");
+        // Edits that split a character are rejected
+        let bad = TextEdit {
+            offset: 3,
+            delete: 1,
+            insert: String::new(),
+        };
+        assert!(apply_text_edits("naïve", &[bad]).is_err());
+    }
+
+    #[test]
+    fn test_diff_text_identical_and_empty() {
+        assert_eq!(
+            diff_text("same", "same"),
+            vec![TextChange::Equal("same".to_string())]
+        );
+        assert_eq!(
+            diff_text("", "added"),
+            vec![TextChange::Insert("added".to_string())]
+        );
+        assert!(diff_text("", "").is_empty());
+    }
+}
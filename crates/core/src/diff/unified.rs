@@ -0,0 +1,145 @@
+use super::TextChange;
+use super::text::diff_tokens;
+use crate::{Document, MarkdownOptions};
+
+/// Lines of unchanged context [`to_text_diff`] keeps around each change
+const DEFAULT_CONTEXT: usize = 3;
+
+/// Renders a unified diff between the Markdown of two documents
+///
+/// Both documents are written with the default [`MarkdownOptions`], so the
+/// output only reflects content changes and not differences in how the
+/// source Markdown was formatted. The result follows the `diff -u` layout,
+/// with `--- old` and `+++ new` headers and three lines of context, and is
+/// empty when the documents render identically.
+pub fn to_text_diff(old: &Document, new: &Document) -> String {
+    to_text_diff_with(old, new, DEFAULT_CONTEXT)
+}
+
+/// Renders a unified diff with the given number of context lines
+pub fn to_text_diff_with(old: &Document, new: &Document, context: usize) -> String {
+    let old_text = canonical_markdown(old);
+    let new_text = canonical_markdown(new);
+    let old_lines: Vec<&str> = old_text.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_text.split_inclusive('\n').collect();
+
+    let mut lines: Vec<(char, &str)> = Vec::new();
+    let changes = diff_tokens(&old_lines, &new_lines);
+    // Runs are merged, so walk both sides to borrow the lines back
+    let (mut i, mut j) = (0, 0);
+    for change in &changes {
+        let (tag, count) = match change {
+            TextChange::Equal(text) => (' ', text.split_inclusive('\n').count()),
+            TextChange::Delete(text) => ('-', text.split_inclusive('\n').count()),
+            TextChange::Insert(text) => ('+', text.split_inclusive('\n').count()),
+        };
+        for _ in 0..count {
+            if tag == '+' {
+                lines.push((tag, new_lines[j]));
+                j += 1;
+            } else {
+                lines.push((tag, old_lines[i]));
+                i += 1;
+                if tag == ' ' {
+                    j += 1;
+                }
+            }
+        }
+    }
+
+    // Ranges of lines to print, merged when their context overlaps
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, (tag, _)) in lines.iter().enumerate() {
+        if *tag == ' ' {
+            continue;
+        }
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("--- old\n+++ new\n");
+    for (start, end) in hunks {
+        let count = |lines: &[(char, &str)], skip: char| {
+            lines.iter().filter(|(tag, _)| *tag != skip).count()
+        };
+        let old_before = count(&lines[..start], '+');
+        let new_before = count(&lines[..start], '-');
+        let old_len = count(&lines[start..end], '+');
+        let new_len = count(&lines[start..end], '-');
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_before, old_len),
+            hunk_range(new_before, new_len)
+        ));
+        for (tag, line) in &lines[start..end] {
+            output.push(*tag);
+            output.push_str(line);
+        }
+    }
+    output
+}
+
+/// The Markdown of a document, ending in a newline unless empty
+fn canonical_markdown(document: &Document) -> String {
+    let mut markdown = document.to_markdown_with(&MarkdownOptions::default());
+    if !markdown.is_empty() && !markdown.ends_with('\n') {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Formats a hunk range as `diff -u` does: 1-based, with an empty range
+/// pointing at the line before it
+fn hunk_range(before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn parse(markdown: &str) -> Document {
+        Document::try_from(Text::<Markdown>::new(markdown)).unwrap()
+    }
+
+    #[test]
+    fn test_to_text_diff() {
+        let old = parse("# Title\n\nOne\n\nTwo\n\nThree\n\nFour\n\nFive\n\nSix\n");
+        // Formatting differences in the source don't show up
+        assert_eq!(
+            to_text_diff(
+                &old,
+                &parse("Title\n=====\n\nOne\n\nTwo\n\nThree\n\nFour\n\nFive\n\nSix")
+            ),
+            ""
+        );
+
+        let new = parse("# Title\n\nOne\n\nTwo\n\n3\n\nFour\n\nFive\n\nSix\n\nSeven\n");
+        assert_eq!(
+            to_text_diff(&old, &new),
+            "--- old\n+++ new\n@@ -4,10 +4,12 @@\n \n Two\n \n-Three\n+3\n \n Four\n \n Five\n \n Six\n+\n+Seven\n"
+        );
+
+        assert_eq!(
+            to_text_diff_with(&old, &new, 1),
+            "--- old\n+++ new\n@@ -6,3 +6,3 @@\n \n-Three\n+3\n \n@@ -13 +13,3 @@\n Six\n+\n+Seven\n"
+        );
+
+        assert_eq!(
+            to_text_diff(&Document::new(), &parse("Hello\n")),
+            "--- old\n+++ new\n@@ -0,0 +1 @@\n+Hello\n"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use regex::Regex;
+use std::ops::Range;
+
+/// A single autocorrect rule
+#[derive(Debug, Clone)]
+pub enum AutocorrectRule {
+    /// Replace an exact string
+    ///
+    /// Word-like strings (starting and ending with an alphanumeric character)
+    /// are only replaced as whole words, once a non-word character is typed
+    /// after them. Other strings, such as `(c)`, are replaced as soon as they
+    /// are complete.
+    Literal { from: String, to: String },
+    /// Replace a regex match ending at the cursor
+    ///
+    /// The replacement may refer to capture groups (`$1`, `${name}`).
+    Regex { pattern: Regex, replacement: String },
+}
+
+impl AutocorrectRule {
+    /// Creates a literal rule
+    pub fn literal(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self::Literal {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Creates a regex rule, anchoring the pattern to the cursor
+    pub fn regex(pattern: &str, replacement: impl Into<String>) -> Result<Self, regex::Error> {
+        Ok(Self::Regex {
+            pattern: Regex::new(&format!("(?:{})$", pattern))?,
+            replacement: replacement.into(),
+        })
+    }
+
+    /// Finds the byte range to replace at the end of `text` and its replacement
+    fn apply(&self, text: &str) -> Option<(Range<usize>, String)> {
+        match self {
+            Self::Literal { from, to } if is_word(from) => {
+                // The last character must be the boundary that completed the word
+                let boundary = text.chars().next_back()?;
+                if is_word_char(boundary) {
+                    return None;
+                }
+                let end = text.len() - boundary.len_utf8();
+                let start = end.checked_sub(from.len())?;
+                let preceded_by_word = text[..start].chars().next_back().is_some_and(is_word_char);
+                (text.get(start..end)? == from && !preceded_by_word)
+                    .then(|| (start..end, to.clone()))
+            }
+            Self::Literal { from, to } => (!from.is_empty() && text.ends_with(from.as_str()))
+                .then(|| (text.len() - from.len()..text.len(), to.clone())),
+            Self::Regex {
+                pattern,
+                replacement,
+            } => {
+                let captures = pattern.captures(text)?;
+                let matched = captures.get(0)?;
+                if matched.is_empty() {
+                    return None;
+                }
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                Some((matched.range(), expanded))
+            }
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_word(s: &str) -> bool {
+    s.chars().next().is_some_and(is_word_char) && s.chars().next_back().is_some_and(is_word_char)
+}
+
+/// A correction applied to the document after a text insertion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correction {
+    /// The node the correction was made in
+    pub node_index: usize,
+    /// Offset in the node where the replaced text started
+    pub start: usize,
+    /// The text that was replaced
+    pub original: String,
+    /// The text it was replaced with
+    pub replacement: String,
+}
+
+/// An ordered set of autocorrect rules
+///
+/// Rules are tried in the order they were added and the first one that
+/// matches the text before the cursor wins.
+#[derive(Debug, Clone, Default)]
+pub struct Autocorrect {
+    rules: Vec<AutocorrectRule>,
+}
+
+impl Autocorrect {
+    /// Creates an engine without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an engine with common typographic replacements
+    pub fn with_defaults() -> Self {
+        Self::new()
+            .with_rule(AutocorrectRule::literal("(c)", "©"))
+            .with_rule(AutocorrectRule::literal("(r)", "®"))
+            .with_rule(AutocorrectRule::literal("(tm)", "™"))
+            .with_rule(AutocorrectRule::literal("...", "…"))
+            .with_rule(AutocorrectRule::literal("->", "→"))
+            .with_rule(AutocorrectRule::literal("<-", "←"))
+    }
+
+    /// Creates an engine with the common replacements and the typographic
+    /// conventions of `language`, a BCP 47 tag
+    ///
+    /// French gets guillemets for `<<` and `>>` and a narrow no-break space
+    /// before `;`, `:`, `!` and `?` typed after a space; German gets a low
+    /// opening quote for `,,`. Other languages get the common replacements
+    /// only.
+    pub fn for_language(language: &str) -> Self {
+        let engine = Self::with_defaults();
+        match crate::language::primary_subtag(language).as_str() {
+            "fr" => [";", ":", "!", "?"]
+                .into_iter()
+                .fold(engine, |engine, mark| {
+                    engine.with_rule(AutocorrectRule::literal(
+                        format!(" {mark}"),
+                        format!("\u{202f}{mark}"),
+                    ))
+                })
+                .with_rule(AutocorrectRule::literal("<<", "«\u{202f}"))
+                .with_rule(AutocorrectRule::literal(">>", "\u{202f}»")),
+            "de" => engine.with_rule(AutocorrectRule::literal(",,", "„")),
+            _ => engine,
+        }
+    }
+
+    /// Adds a rule, returning the engine
+    pub fn with_rule(mut self, rule: AutocorrectRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds a rule
+    pub fn add_rule(&mut self, rule: AutocorrectRule) {
+        self.rules.push(rule);
+    }
+
+    /// Removes all rules
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Returns the configured rules
+    pub fn rules(&self) -> &[AutocorrectRule] {
+        &self.rules
+    }
+
+    /// Finds the correction to make for the text before the cursor
+    ///
+    /// Returns the byte range within `text` to replace and the replacement.
+    pub fn correct(&self, text: &str) -> Option<(Range<usize>, String)> {
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.apply(text))
+            .find(|(range, replacement)| text[range.clone()] != *replacement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_word_rules() {
+        let engine = Autocorrect::new().with_rule(AutocorrectRule::literal("teh", "the"));
+
+        assert_eq!(engine.correct("teh"), None);
+        assert_eq!(
+            engine.correct("I saw teh "),
+            Some((6..9, "the".to_string()))
+        );
+        assert_eq!(engine.correct("teh,"), Some((0..3, "the".to_string())));
+        // Only whole words are corrected
+        assert_eq!(engine.correct("Tehran"), None);
+        assert_eq!(engine.correct("pteh "), None);
+    }
+
+    #[test]
+    fn test_literal_symbol_rules() {
+        let engine = Autocorrect::with_defaults();
+
+        assert_eq!(engine.correct("Acme (c)"), Some((5..8, "©".to_string())));
+        assert_eq!(engine.correct("wait..."), Some((4..7, "…".to_string())));
+        assert_eq!(engine.correct("(c"), None);
+    }
+
+    #[test]
+    fn test_language_rules() {
+        let french = Autocorrect::for_language("fr-CA");
+        assert_eq!(
+            french.correct("Vraiment ?"),
+            Some((8..10, "\u{202f}?".to_string()))
+        );
+        assert_eq!(french.correct("<<"), Some((0..2, "«\u{202f}".to_string())));
+        assert_eq!(french.correct("(c)"), Some((0..3, "©".to_string())));
+
+        let german = Autocorrect::for_language("de");
+        assert_eq!(german.correct("Er sagt ,,"), Some((8..10, "„".to_string())));
+        assert_eq!(
+            Autocorrect::for_language("en").rules().len(),
+            Autocorrect::with_defaults().rules().len()
+        );
+    }
+
+    #[test]
+    fn test_regex_rules() {
+        let engine = Autocorrect::new()
+            .with_rule(AutocorrectRule::regex(r"(\d+)x(\d+)", "$1×$2").unwrap())
+            .with_rule(AutocorrectRule::regex(r"\s{2,}", " ").unwrap());
+
+        assert_eq!(engine.correct("size 3x4"), Some((5..8, "3×4".to_string())));
+        assert_eq!(engine.correct("a  "), Some((1..3, " ".to_string())));
+        assert_eq!(engine.correct("a "), None);
+        assert!(AutocorrectRule::regex("(", "").is_err());
+    }
+}
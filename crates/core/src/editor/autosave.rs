@@ -0,0 +1,123 @@
+use crate::Document;
+use crate::store::{DocumentStore, StoreError};
+use std::time::{Duration, Instant};
+
+/// When an editor saves its document on its own
+///
+/// A save happens once `after_changes` changes (commands, undos and redos)
+/// piled up since the last one, or once the document has been left alone
+/// for `debounce`. The editor has no timer, so the debounce is checked when
+/// the app calls [`Editor::poll_autosave`](crate::Editor::poll_autosave),
+/// e.g. from its idle loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutosavePolicy {
+    /// Changes after which to save, if any
+    pub after_changes: Option<usize>,
+    /// Pause after the last change after which to save, if any
+    pub debounce: Option<Duration>,
+}
+
+impl Default for AutosavePolicy {
+    fn default() -> Self {
+        Self {
+            after_changes: Some(50),
+            debounce: Some(Duration::from_secs(2)),
+        }
+    }
+}
+
+impl AutosavePolicy {
+    /// Creates a policy saving after the given number of changes and pause
+    pub fn new(after_changes: Option<usize>, debounce: Option<Duration>) -> Self {
+        Self {
+            after_changes,
+            debounce,
+        }
+    }
+}
+
+/// An editor's autosave target and the changes not saved yet
+pub(crate) struct Autosave {
+    store: Box<dyn DocumentStore>,
+    id: String,
+    policy: AutosavePolicy,
+    /// Changes since the last save
+    pending: usize,
+    last_change: Option<Instant>,
+    last_revision: Option<u64>,
+    /// Failure of a save made while recording a change, kept for
+    /// [`poll`](Self::poll) to report
+    error: Option<StoreError>,
+}
+
+impl Autosave {
+    pub(crate) fn new(
+        store: Box<dyn DocumentStore>,
+        id: String,
+        policy: AutosavePolicy,
+        last_revision: Option<u64>,
+    ) -> Self {
+        Self {
+            store,
+            id,
+            policy,
+            pending: 0,
+            last_change: None,
+            last_revision,
+            error: None,
+        }
+    }
+
+    /// Records a change, saving if enough of them piled up
+    pub(crate) fn changed(&mut self, document: &Document) {
+        self.pending += 1;
+        self.last_change = Some(Instant::now());
+        if self
+            .policy
+            .after_changes
+            .is_some_and(|after| self.pending >= after)
+            && let Err(err) = self.save(document)
+        {
+            self.error = Some(err);
+        }
+    }
+
+    /// Saves if the document was left alone for the debounce, reporting
+    /// the failure of an earlier automatic save first
+    pub(crate) fn poll(
+        &mut self,
+        document: &Document,
+        now: Instant,
+    ) -> Result<Option<u64>, StoreError> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let settled = match (self.policy.debounce, self.last_change) {
+            (Some(debounce), Some(last_change)) => now.duration_since(last_change) >= debounce,
+            _ => false,
+        };
+        match self.pending > 0 && settled {
+            true => self.save(document).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    pub(crate) fn save(&mut self, document: &Document) -> Result<u64, StoreError> {
+        let revision = self.store.save(&self.id, document)?;
+        self.pending = 0;
+        self.last_revision = Some(revision);
+        Ok(revision)
+    }
+
+    pub(crate) fn has_unsaved_changes(&self) -> bool {
+        self.pending > 0
+    }
+
+    pub(crate) fn last_revision(&self) -> Option<u64> {
+        self.last_revision
+    }
+
+    pub(crate) fn into_store(self) -> Box<dyn DocumentStore> {
+        self.store
+    }
+}
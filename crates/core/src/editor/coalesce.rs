@@ -0,0 +1,194 @@
+use super::command::{Command as EditorCommand, DeleteTextCommand};
+use super::commands::InsertTextCommand;
+use crate::EditError;
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// When consecutive text edits share one undo step
+///
+/// Insertions that continue where the previous one ended, and deletions that
+/// continue backwards or forwards from the previous one, are merged while
+/// they stay in the same node, follow each other within `window` and add up
+/// to at most `max_chars`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoalescePolicy {
+    /// Longest pause between two edits of the same undo step
+    pub window: Duration,
+    /// Most characters inserted or bytes deleted in one undo step
+    pub max_chars: usize,
+}
+
+impl Default for CoalescePolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(1),
+            max_chars: 64,
+        }
+    }
+}
+
+impl CoalescePolicy {
+    /// Creates a policy with the given pause window and size limit
+    pub fn new(window: Duration, max_chars: usize) -> Self {
+        Self { window, max_chars }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    Insert,
+    Delete,
+}
+
+/// A single text insertion or deletion
+struct Keystroke {
+    kind: EditKind,
+    node_index: usize,
+    start: usize,
+    end: usize,
+    size: usize,
+}
+
+impl Keystroke {
+    fn of(command: &dyn EditorCommand) -> Option<Self> {
+        let any = command.as_any();
+        if let Some(insert) = any.downcast_ref::<InsertTextCommand>() {
+            let text = insert.text();
+            return Some(Self {
+                kind: EditKind::Insert,
+                node_index: insert.node_index(),
+                start: insert.position(),
+                end: insert.position() + text.len(),
+                size: text.chars().count(),
+            });
+        }
+        any.downcast_ref::<DeleteTextCommand>().map(|delete| {
+            let (start, end) = delete.range();
+            Self {
+                kind: EditKind::Delete,
+                node_index: delete.node_index(),
+                start,
+                end,
+                size: end - start,
+            }
+        })
+    }
+}
+
+/// Text edits merged into one undo step
+///
+/// The commands are shared with the [`OpenGroup`] so that later keystrokes
+/// can join the step while it sits on the undo stack.
+pub(crate) struct CoalescedCommand {
+    commands: Rc<RefCell<Vec<Box<dyn EditorCommand>>>>,
+}
+
+impl EditorCommand for CoalescedCommand {
+    fn execute(&mut self) -> Result<(), EditError> {
+        for command in self.commands.borrow_mut().iter_mut() {
+            command.execute()?;
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        for command in self.commands.borrow_mut().iter_mut().rev() {
+            command.undo()?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + self
+                .commands
+                .borrow()
+                .iter()
+                .map(|command| command.retained_bytes())
+                .sum::<usize>()
+    }
+}
+
+/// The undo step that text edits are currently merged into
+pub(crate) struct OpenGroup {
+    kind: EditKind,
+    node_index: usize,
+    /// Where the next edit must start (insertions) or touch (deletions)
+    caret: usize,
+    size: usize,
+    last_edit: Instant,
+    commands: Rc<RefCell<Vec<Box<dyn EditorCommand>>>>,
+}
+
+impl OpenGroup {
+    /// Wraps a text edit into a new undo step, or returns it unchanged if it
+    /// is not a text edit
+    pub(crate) fn start(
+        command: Box<dyn EditorCommand>,
+    ) -> Result<(Self, Box<dyn EditorCommand>), Box<dyn EditorCommand>> {
+        let Some(keystroke) = Keystroke::of(command.as_ref()) else {
+            return Err(command);
+        };
+        let commands = Rc::new(RefCell::new(vec![command]));
+        let group = Self {
+            kind: keystroke.kind,
+            node_index: keystroke.node_index,
+            caret: match keystroke.kind {
+                EditKind::Insert => keystroke.end,
+                EditKind::Delete => keystroke.start,
+            },
+            size: keystroke.size,
+            last_edit: Instant::now(),
+            commands: commands.clone(),
+        };
+        Ok((group, Box::new(CoalescedCommand { commands })))
+    }
+
+    /// Returns true if `command` is the undo step of this group
+    pub(crate) fn is_step(&self, command: &dyn EditorCommand) -> bool {
+        command
+            .as_any()
+            .downcast_ref::<CoalescedCommand>()
+            .is_some_and(|step| Rc::ptr_eq(&step.commands, &self.commands))
+    }
+
+    /// Merges a text edit into the group if the policy allows it
+    ///
+    /// Returns the command back if it starts a new undo step.
+    pub(crate) fn join(
+        &mut self,
+        command: Box<dyn EditorCommand>,
+        policy: &CoalescePolicy,
+    ) -> Result<(), Box<dyn EditorCommand>> {
+        let Some(keystroke) = Keystroke::of(command.as_ref()) else {
+            return Err(command);
+        };
+        let adjacent = match keystroke.kind {
+            EditKind::Insert => keystroke.start == self.caret,
+            EditKind::Delete => keystroke.end == self.caret || keystroke.start == self.caret,
+        };
+        if keystroke.kind != self.kind
+            || keystroke.node_index != self.node_index
+            || !adjacent
+            || self.last_edit.elapsed() >= policy.window
+            || self.size + keystroke.size > policy.max_chars
+        {
+            return Err(command);
+        }
+
+        self.caret = match keystroke.kind {
+            EditKind::Insert => keystroke.end,
+            EditKind::Delete => keystroke.start,
+        };
+        self.size += keystroke.size;
+        self.last_edit = Instant::now();
+        self.commands.borrow_mut().push(command);
+        Ok(())
+    }
+}
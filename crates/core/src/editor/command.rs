@@ -1,6 +1,9 @@
+use crate::offsets::{check_offset, offset_text};
 use crate::{Document, EditError, InlineNode, Node};
+use serde::{Serialize, ser};
 use std::any::Any;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 
 /// Trait representing a document editing command that can be executed and undone
@@ -12,8 +15,276 @@ pub trait Command {
     /// Get this command as Any to allow downcasting to specific types
     #[allow(dead_code)]
     fn as_any(&self) -> &dyn Any;
+    /// Estimate the bytes of memory this command keeps alive for undo and redo
+    ///
+    /// Commands that store node snapshots should add them on top of their own size.
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+    }
+    /// Top-level node indices the command changed, as they are after it
+    /// last ran or was undone
+    ///
+    /// An empty range marks where nodes were removed. Commands that return
+    /// `None` leave it to the editor to find the nodes that changed.
+    fn affected_range(&self) -> Option<Range<usize>> {
+        None
+    }
+    /// For commands that reorder top-level nodes, the index each node in
+    /// [`affected_range`](Self::affected_range) had before the command last
+    /// ran or was undone
+    ///
+    /// Positions in moved nodes then move with them, which comparing the
+    /// nodes before and after can't tell from an edit.
+    fn node_order(&self) -> Option<Vec<usize>> {
+        None
+    }
+    /// Name of the command, reported in errors it causes
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+}
+
+/// The order undoing a reordering restores, for an `order` of the nodes
+/// from `start` as [`Command::node_order`] reports it
+pub(crate) fn inverse_order(start: usize, order: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![0; order.len()];
+    for (offset, &old) in order.iter().enumerate() {
+        inverse[old - start] = start + offset;
+    }
+    inverse
+}
+
+/// Estimates the heap memory held by a value
+///
+/// Walks the value as serde sees it and adds up the bytes of its strings
+/// and primitives, without serializing it.
+pub(crate) fn estimate_bytes<T: Serialize + ?Sized>(value: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    value.serialize(&mut counter).map_or(0, |()| counter.0)
+}
+
+/// Serializer that only counts bytes, for [`estimate_bytes`]
+struct ByteCounter(usize);
+
+#[derive(Debug)]
+struct CountError;
+
+impl std::fmt::Display for CountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("value can't be measured")
+    }
+}
+
+impl std::error::Error for CountError {}
+
+impl ser::Error for CountError {
+    fn custom<T: std::fmt::Display>(_message: T) -> Self {
+        CountError
+    }
+}
+
+impl ByteCounter {
+    fn add<T>(&mut self, _value: T) -> Result<(), CountError> {
+        self.0 += size_of::<T>();
+        Ok(())
+    }
+}
+
+impl ser::Serializer for &mut ByteCounter {
+    type Ok = ();
+    type Error = CountError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, value: bool) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_i8(self, value: i8) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_i16(self, value: i16) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_i32(self, value: i32) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_i64(self, value: i64) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_u8(self, value: u8) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_u16(self, value: u16) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_u32(self, value: u32) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_u64(self, value: u64) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_f32(self, value: f32) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_f64(self, value: f64) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_char(self, value: char) -> Result<(), CountError> {
+        self.add(value)
+    }
+    fn serialize_str(self, value: &str) -> Result<(), CountError> {
+        self.0 += value.len();
+        Ok(())
+    }
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), CountError> {
+        self.0 += value.len();
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<(), CountError> {
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), CountError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), CountError> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CountError> {
+        Ok(())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), CountError> {
+        Ok(())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), CountError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), CountError> {
+        value.serialize(self)
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, CountError> {
+        Ok(self)
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self, CountError> {
+        Ok(self)
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, CountError> {
+        Ok(self)
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, CountError> {
+        Ok(self)
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, CountError> {
+        Ok(self)
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, CountError> {
+        Ok(self)
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, CountError> {
+        Ok(self)
+    }
+}
+
+/// Implements the compound serializers of [`ByteCounter`] by counting each
+/// element
+macro_rules! count_elements {
+    ($($trait:ident :: $method:ident),*) => {$(
+        impl ser::$trait for &mut ByteCounter {
+            type Ok = ();
+            type Error = CountError;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CountError> {
+                value.serialize(&mut **self)
+            }
+
+            fn end(self) -> Result<(), CountError> {
+                Ok(())
+            }
+        }
+    )*};
 }
 
+count_elements!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl ser::SerializeMap for &mut ByteCounter {
+    type Ok = ();
+    type Error = CountError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), CountError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CountError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CountError> {
+        Ok(())
+    }
+}
+
+/// Implements the struct serializers of [`ByteCounter`]; field names are
+/// static and not counted
+macro_rules! count_fields {
+    ($($trait:ident),*) => {$(
+        impl ser::$trait for &mut ByteCounter {
+            type Ok = ();
+            type Error = CountError;
+
+            fn serialize_field<T: Serialize + ?Sized>(
+                &mut self,
+                _key: &'static str,
+                value: &T,
+            ) -> Result<(), CountError> {
+                value.serialize(&mut **self)
+            }
+
+            fn end(self) -> Result<(), CountError> {
+                Ok(())
+            }
+        }
+    )*};
+}
+
+count_fields!(SerializeStruct, SerializeStructVariant);
+
 /// Command to delete text from a node
 pub struct DeleteTextCommand {
     document: Rc<RefCell<Document>>,
@@ -38,19 +309,43 @@ impl DeleteTextCommand {
             deleted_text: None,
         }
     }
+
+    pub(crate) fn node_index(&self) -> usize {
+        self.node_index
+    }
+
+    /// Byte range the command deletes
+    pub(crate) fn range(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
 }
 
 impl Command for DeleteTextCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.deleted_text)
+    }
+
+    fn affected_range(&self) -> Option<Range<usize>> {
+        Some(self.node_index..self.node_index + 1)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
+        let length = offset_text(&document.nodes[self.node_index]).len();
         if self.start >= self.end {
-            return Err(EditError::InvalidRange);
+            return Err(EditError::invalid_range()
+                .at_node(self.node_index)
+                .with_ranges(0..length, self.start..self.end));
         }
+        check_offset(&document.nodes[self.node_index], self.start)
+            .map_err(|err| err.at_node(self.node_index))?;
+        check_offset(&document.nodes[self.node_index], self.end)
+            .map_err(|err| err.at_node(self.node_index))?;
 
         match &mut document.nodes[self.node_index] {
             Node::Paragraph { children } => {
@@ -113,11 +408,13 @@ impl Command for DeleteTextCommand {
                     code.replace_range(self.start..self.end, "");
                     Ok(())
                 } else {
-                    Err(EditError::InvalidRange)
+                    Err(EditError::invalid_range()
+                        .at_node(self.node_index)
+                        .with_ranges(0..code.len(), self.start..self.end))
                 }
             }
             // Add more node types as needed
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -126,7 +423,7 @@ impl Command for DeleteTextCommand {
             let mut document = self.document.borrow_mut();
 
             if self.node_index >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.node_index));
             }
 
             match &mut document.nodes[self.node_index] {
@@ -184,14 +481,14 @@ impl Command for DeleteTextCommand {
                         code.insert_str(self.start, deleted_text);
                         Ok(())
                     } else {
-                        Err(EditError::InvalidRange)
+                        Err(EditError::invalid_range().at_node(self.node_index))
                     }
                 }
                 // Add more node types as needed
-                _ => Err(EditError::UnsupportedOperation),
+                _ => Err(EditError::unsupported().at_node(self.node_index)),
             }
         } else {
-            Err(EditError::OperationFailed)
+            Err(EditError::operation_failed().at_node(self.node_index))
         }
     }
 
@@ -220,15 +517,19 @@ impl MergeNodesCommand {
 }
 
 impl Command for MergeNodesCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_second_node)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.first_index >= document.nodes.len() || self.second_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.first_index));
         }
 
         if self.second_index != self.first_index + 1 {
-            return Err(EditError::InvalidRange);
+            return Err(EditError::invalid_range().at_node(self.first_index));
         }
 
         // Store original second node for undo
@@ -269,7 +570,7 @@ impl Command for MergeNodesCommand {
             ) => {
                 // Only merge if languages match
                 if first_lang != second_lang {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.first_index));
                 }
 
                 // Merge code
@@ -282,7 +583,7 @@ impl Command for MergeNodesCommand {
                 Ok(())
             }
             // Add more mergeable node type pairs as needed
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.first_index)),
         }
     }
 
@@ -291,7 +592,7 @@ impl Command for MergeNodesCommand {
             let mut document = self.document.borrow_mut();
 
             if self.first_index >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.first_index));
             }
 
             document.nodes.insert(self.second_index, node.clone());
@@ -319,12 +620,12 @@ impl Command for MergeNodesCommand {
                     let first_len = code.len() - second_code.len() - 1;
                     code.truncate(first_len);
                 }
-                _ => return Err(EditError::UnsupportedOperation),
+                _ => return Err(EditError::unsupported().at_node(self.first_index)),
             }
 
             Ok(())
         } else {
-            Err(EditError::OperationFailed)
+            Err(EditError::operation_failed().at_node(self.first_index))
         }
     }
 
@@ -35,25 +35,31 @@ impl AddTaskItemCommand {
 }
 
 impl Command for AddTaskItemCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + self.text.len()
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Get the node
         match &mut document.nodes[self.node_index] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Check if it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Ensure position is valid (allowing appending at the end)
                 if self.position > items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                 }
 
                 // Create a new list item with the text and checked state
@@ -67,7 +73,7 @@ impl Command for AddTaskItemCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -76,15 +82,17 @@ impl Command for AddTaskItemCommand {
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Get the node
         match &mut document.nodes[self.node_index] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Check if it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Remove the item if we know its position
@@ -93,13 +101,13 @@ impl Command for AddTaskItemCommand {
                         items.remove(position);
                         Ok(())
                     } else {
-                        Err(EditError::IndexOutOfBounds)
+                        Err(EditError::index_out_of_bounds().at_node(self.node_index))
                     }
                 } else {
-                    Err(EditError::OperationFailed)
+                    Err(EditError::operation_failed().at_node(self.node_index))
                 }
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -111,6 +119,7 @@ impl Command for AddTaskItemCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::EditErrorKind;
 
     #[test]
     fn test_add_task_item() {
@@ -131,7 +140,9 @@ mod tests {
         {
             let doc = doc_rc.borrow();
             match &doc.nodes[0] {
-                Node::List { list_type, items } => {
+                Node::List {
+                    list_type, items, ..
+                } => {
                     assert_eq!(*list_type, ListType::Task);
                     assert_eq!(items.len(), 2);
 
@@ -185,10 +196,10 @@ mod tests {
 
         // Try to add a task item to a paragraph
         let mut cmd = AddTaskItemCommand::new(doc_rc.clone(), 0, 0, "New task", false);
-        assert!(matches!(
-            cmd.execute(),
-            Err(EditError::UnsupportedOperation)
-        ));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::UnsupportedOperation
+        );
     }
 
     #[test]
@@ -204,6 +215,9 @@ mod tests {
 
         // Try to add at an invalid position
         let mut cmd = AddTaskItemCommand::new(doc_rc.clone(), 0, 2, "New task", false);
-        assert!(matches!(cmd.execute(), Err(EditError::IndexOutOfBounds)));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
     }
 }
@@ -0,0 +1,106 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::offsets::check_offset;
+use crate::{Document, EditError, InlineNode, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to replace a range of text inside a single text run
+///
+/// Used by autocorrect so that a correction is recorded separately from the
+/// insertion that triggered it and can be undone on its own.
+pub struct ApplyCorrectionCommand {
+    document: Rc<RefCell<Document>>,
+    node_index: usize,
+    start: usize,
+    end: usize,
+    replacement: String,
+    original_node: Option<Node>,
+}
+
+impl ApplyCorrectionCommand {
+    /// Create a command replacing `start..end` of the node's text with `replacement`
+    pub fn new(
+        document: Rc<RefCell<Document>>,
+        node_index: usize,
+        start: usize,
+        end: usize,
+        replacement: String,
+    ) -> Self {
+        Self {
+            document,
+            node_index,
+            start,
+            end,
+            replacement,
+            original_node: None,
+        }
+    }
+}
+
+impl Command for ApplyCorrectionCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + self.replacement.len() + estimate_bytes(&self.original_node)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        if self.start > self.end {
+            return Err(EditError::invalid_range().at_node(self.node_index));
+        }
+
+        let mut document = self.document.borrow_mut();
+        let node = document
+            .nodes
+            .get_mut(self.node_index)
+            .ok_or(EditError::index_out_of_bounds().at_node(self.node_index))?;
+        check_offset(node, self.start).map_err(|err| err.at_node(self.node_index))?;
+        check_offset(node, self.end).map_err(|err| err.at_node(self.node_index))?;
+        let original = node.clone();
+
+        let children = match node {
+            Node::Paragraph { children } | Node::Heading { children, .. } => children,
+            _ => return Err(EditError::unsupported().at_node(self.node_index)),
+        };
+
+        // Find the text run that holds the whole range
+        let mut offset = 0;
+        for child in children.iter_mut() {
+            let InlineNode::Text(text_node) = child else {
+                offset += 1;
+                continue;
+            };
+            let next_offset = offset + text_node.text.len();
+            if self.start >= offset && self.end <= next_offset {
+                let range = self.start - offset..self.end - offset;
+                if !text_node.text.is_char_boundary(range.start)
+                    || !text_node.text.is_char_boundary(range.end)
+                {
+                    return Err(EditError::invalid_range().at_node(self.node_index));
+                }
+                text_node.text.replace_range(range, &self.replacement);
+                self.original_node = Some(original);
+                return Ok(());
+            }
+            offset = next_offset;
+        }
+
+        Err(EditError::invalid_range().at_node(self.node_index))
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let original = self.original_node.take().ok_or_else(|| {
+            EditError::other("No original state to restore").at_node(self.node_index)
+        })?;
+        let mut document = self.document.borrow_mut();
+        let node = document
+            .nodes
+            .get_mut(self.node_index)
+            .ok_or(EditError::index_out_of_bounds().at_node(self.node_index))?;
+        *node = original;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,71 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::tasks::{TaskAction, apply_task_action};
+use crate::{Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// What a [`BulkTaskCommand`] does to the tasks of the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkTaskAction {
+    /// Check every task
+    CheckAll,
+    /// Uncheck every task
+    UncheckAll,
+    /// Remove checked tasks with everything nested in them
+    RemoveCompleted,
+}
+
+/// Command to check, uncheck or remove the tasks of the whole document
+pub struct BulkTaskCommand {
+    document: Rc<RefCell<Document>>,
+    action: BulkTaskAction,
+    /// Original nodes for undo
+    original_nodes: Option<Vec<Node>>,
+}
+
+impl BulkTaskCommand {
+    /// Create a command applying `action` to every task
+    pub fn new(document: Rc<RefCell<Document>>, action: BulkTaskAction) -> Self {
+        Self {
+            document,
+            action,
+            original_nodes: None,
+        }
+    }
+}
+
+impl Command for BulkTaskCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let action = match self.action {
+            BulkTaskAction::CheckAll => TaskAction::Check,
+            BulkTaskAction::UncheckAll => TaskAction::Uncheck,
+            BulkTaskAction::RemoveCompleted => TaskAction::RemoveCompleted,
+        };
+        let original_nodes = document.nodes.clone();
+        if apply_task_action(&mut document.nodes, action) == 0 {
+            return Err(EditError::operation_failed().with_message("No task to change"));
+        }
+
+        self.original_nodes = Some(original_nodes);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        if let Some(original_nodes) = self.original_nodes.take() {
+            self.document.borrow_mut().nodes = original_nodes;
+            Ok(())
+        } else {
+            Err(EditError::other("No original state to restore"))
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,86 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Comment, Document, EditError};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// What a [`CommentCommand`] does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentAction {
+    /// Add a comment by an author on a range of the node at a path
+    Add {
+        author: String,
+        path: Vec<usize>,
+        range: Range<usize>,
+        body: String,
+    },
+    /// Mark a comment resolved or reopen it
+    Resolve { id: u64, resolved: bool },
+    /// Remove a comment
+    Delete { id: u64 },
+}
+
+/// Command to add, resolve or delete a comment
+pub struct CommentCommand {
+    document: Rc<RefCell<Document>>,
+    action: CommentAction,
+    previous_comments: Option<Vec<Comment>>,
+}
+
+impl CommentCommand {
+    pub fn new(document: Rc<RefCell<Document>>, action: CommentAction) -> Self {
+        Self {
+            document,
+            action,
+            previous_comments: None,
+        }
+    }
+}
+
+impl Command for CommentCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.previous_comments)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let previous = document.comments.clone();
+
+        match &self.action {
+            CommentAction::Add {
+                author,
+                path,
+                range,
+                body,
+            } => {
+                document.add_comment(
+                    author.as_str(),
+                    path.clone(),
+                    range.clone(),
+                    body.as_str(),
+                )?;
+            }
+            CommentAction::Resolve { id, resolved } => {
+                document.resolve_comment(*id, *resolved)?;
+            }
+            CommentAction::Delete { id } => {
+                document.delete_comment(*id)?;
+            }
+        }
+        self.previous_comments = Some(previous);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let Some(previous) = self.previous_comments.take() else {
+            return Err(EditError::operation_failed().with_message("Nothing to undo"));
+        };
+        self.document.borrow_mut().comments = previous;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -1,5 +1,5 @@
-use crate::editor::command::Command;
-use crate::{Document, EditError, Node};
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, HeadingProperties, Node};
 use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -26,6 +26,10 @@ impl CopySelectionCommand {
 }
 
 impl Command for CopySelectionCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.copied_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let document = self.document.borrow();
 
@@ -76,7 +80,9 @@ impl Command for CopySelectionCommand {
                         self.copied_nodes.push(node.clone());
                     }
                 }
-                Node::Heading { level, children } => {
+                Node::Heading {
+                    level, children, ..
+                } => {
                     // Extract the selected portion of text
                     if selection.start.path.len() > 1 && selection.end.path.len() > 1 {
                         let start_pos = selection.start.path[1];
@@ -88,6 +94,7 @@ impl Command for CopySelectionCommand {
                         self.copied_nodes.push(Node::Heading {
                             level: *level,
                             children: selected_children,
+                            properties: HeadingProperties::default(),
                         });
                     } else {
                         // Copy the entire heading
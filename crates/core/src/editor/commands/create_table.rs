@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, EditError, Node, TableAlignment, TableCell, TableProperties};
 use std::any::Any;
 use std::cell::RefCell;
@@ -126,11 +126,18 @@ impl CreateTableCommand {
 }
 
 impl Command for CreateTableCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + estimate_bytes(&self.header_data)
+            + estimate_bytes(&self.row_data)
+            + estimate_bytes(&self.old_node)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.position > document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.position));
         }
 
         // Create a new table node
@@ -1,5 +1,5 @@
-use crate::editor::command::Command;
-use crate::{Document, EditError, InlineNode, ListItem, Node, TextNode};
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, HeadingProperties, InlineNode, ListItem, Node, TextNode};
 use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -28,6 +28,10 @@ impl CreateTOCCommand {
 }
 
 impl Command for CreateTOCCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
@@ -40,7 +44,10 @@ impl Command for CreateTOCCommand {
 
         // First pass: collect all headings
         for (index, node) in document.nodes.iter().enumerate() {
-            if let Node::Heading { level, children } = node {
+            if let Node::Heading {
+                level, children, ..
+            } = node
+            {
                 // Only include headings up to the specified level
                 if *level <= self.max_level {
                     // Extract heading text
@@ -82,6 +89,7 @@ impl Command for CreateTOCCommand {
                     text: "Table of Contents".to_string(),
                     formatting: Default::default(),
                 })],
+                properties: HeadingProperties::default(),
             });
         }
 
@@ -114,6 +122,8 @@ impl Command for CreateTOCCommand {
             toc_nodes.push(Node::List {
                 list_type: crate::ListType::Unordered,
                 items: list_items,
+                start: None,
+                loose: false,
             });
         }
 
@@ -138,7 +148,7 @@ impl Command for CreateTOCCommand {
             document.nodes = original_nodes;
             Ok(())
         } else {
-            Err(EditError::Other("No original state to restore".to_string()))
+            Err(EditError::other("No original state to restore"))
         }
     }
 
@@ -183,7 +193,9 @@ mod tests {
 
         // First node should be a heading "Table of Contents"
         match &doc.nodes[0] {
-            Node::Heading { level, children } => {
+            Node::Heading {
+                level, children, ..
+            } => {
                 assert_eq!(*level, 2);
                 if let InlineNode::Text(text_node) = &children[0] {
                     assert_eq!(text_node.text, "Table of Contents");
@@ -196,7 +208,9 @@ mod tests {
 
         // Second node should be a list with TOC entries
         match &doc.nodes[1] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 assert_eq!(*list_type, ListType::Unordered);
                 assert_eq!(items.len(), 4); // Should have 4 entries
 
@@ -239,7 +253,9 @@ mod tests {
 
         // First node should be first heading again
         match &doc.nodes[0] {
-            Node::Heading { level, children } => {
+            Node::Heading {
+                level, children, ..
+            } => {
                 assert_eq!(*level, 1);
                 if let InlineNode::Text(text_node) = &children[0] {
                     assert_eq!(text_node.text, "First Section");
@@ -1,5 +1,5 @@
-use crate::editor::command::Command;
-use crate::{Document, EditError, InlineNode, Node, Selection};
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, HeadingProperties, InlineNode, Node, Selection};
 use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -32,6 +32,10 @@ impl CutSelectionCommand {
 }
 
 impl Command for CutSelectionCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes) + estimate_bytes(&self.cut_content)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
@@ -93,7 +97,7 @@ impl Command for CutSelectionCommand {
         // Single node selection - text cut operation
         let node_idx = selection.start.path[0];
         if node_idx >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(node_idx));
         }
 
         // Clone the node for inspection before modification
@@ -221,10 +225,12 @@ impl Command for CutSelectionCommand {
                 Node::Heading {
                     children,
                     level: _level,
+                    ..
                 },
                 Node::Heading {
                     children: orig_children,
                     level: orig_level,
+                    ..
                 },
             ) => {
                 // Get start and end offsets within the node
@@ -235,6 +241,7 @@ impl Command for CutSelectionCommand {
                 let mut cut_heading = Node::Heading {
                     level: *orig_level,
                     children: Vec::new(),
+                    properties: HeadingProperties::default(),
                 };
 
                 // Extract selected text and add to cut_heading
@@ -356,7 +363,7 @@ impl Command for CutSelectionCommand {
                     || start_offset > orig_code.len()
                     || start_offset >= end_offset
                 {
-                    return Err(EditError::InvalidRange);
+                    return Err(EditError::invalid_range());
                 }
 
                 // Store the cut content
@@ -377,7 +384,7 @@ impl Command for CutSelectionCommand {
             _ => {
                 // For unsupported node types, just restore the selection
                 document.selection = Some(self.original_selection.take().unwrap());
-                return Err(EditError::UnsupportedOperation);
+                return Err(EditError::unsupported());
             }
         }
 
@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, EditError, Node};
 use std::any::Any;
 use std::cell::RefCell;
@@ -22,11 +22,15 @@ impl DeleteNodeCommand {
 }
 
 impl Command for DeleteNodeCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.deleted_node)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Store the node for undo
@@ -52,7 +56,7 @@ impl Command for DeleteNodeCommand {
                 }
                 Ok(())
             }
-            None => Err(EditError::OperationFailed),
+            None => Err(EditError::operation_failed().at_node(self.node_index)),
         }
     }
 
@@ -39,6 +39,17 @@ impl DuplicateNodeCommand {
             Node::FootnoteDefinition(_) => "FootnoteDefinition".to_string(),
             Node::DefinitionList { .. } => "DefinitionList".to_string(),
             Node::MathBlock { .. } => "MathBlock".to_string(),
+            Node::Embed { provider, .. } => format!("Embed ({})", provider),
+            Node::Figure { .. } => "Figure".to_string(),
+            Node::HtmlBlock { .. } => "HtmlBlock".to_string(),
+            Node::HtmlComment { .. } => "HtmlComment".to_string(),
+            Node::Callout { kind, .. } => format!("Callout ({})", kind.as_str()),
+            Node::VisibilityBlock { visibility, .. } => {
+                format!("VisibilityBlock ({})", visibility.as_str())
+            }
+            Node::Custom { kind, .. } => format!("Custom ({})", kind),
+            Node::Conflict { .. } => "Conflict".to_string(),
+            Node::TableOfContents { .. } => "TableOfContents".to_string(),
             Node::TempListItem(_) => "TemporaryListItem".to_string(),
             Node::TempTableCell(_) => "TemporaryTableCell".to_string(),
         }
@@ -51,11 +62,11 @@ impl Command for DuplicateNodeCommand {
 
         // Empty document check
         if document.nodes.is_empty() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Store the node type for debugging/tracing
@@ -64,7 +75,7 @@ impl Command for DuplicateNodeCommand {
         // Check if trying to duplicate a temporary node
         match &document.nodes[self.node_index] {
             Node::TempListItem(_) | Node::TempTableCell(_) => {
-                return Err(EditError::UnsupportedOperation);
+                return Err(EditError::unsupported().at_node(self.node_index));
             }
             _ => {}
         }
@@ -87,7 +98,7 @@ impl Command for DuplicateNodeCommand {
             let mut document = self.document.borrow_mut();
 
             if index >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.node_index));
             }
 
             // Remove the duplicated node
@@ -98,7 +109,7 @@ impl Command for DuplicateNodeCommand {
 
             Ok(())
         } else {
-            Err(EditError::OperationFailed)
+            Err(EditError::operation_failed().at_node(self.node_index))
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::models::{Document, ListType, Node};
 use crate::{EditError, InlineNode};
 use std::any::Any;
@@ -58,23 +58,33 @@ impl EditTaskItemCommand {
 }
 
 impl Command for EditTaskItemCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + self.text.len() + estimate_bytes(&self.previous_text)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_idx >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(
+                EditError::index_out_of_bounds().at_path(vec![self.node_idx, self.item_idx])
+            );
         }
 
         match &mut document.nodes[self.node_idx] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Verify that it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::Other("Node is not a task list".into()));
+                    return Err(EditError::other("Node is not a task list")
+                        .at_path(vec![self.node_idx, self.item_idx]));
                 }
 
                 // Get the task item
                 if self.item_idx >= items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds()
+                        .at_path(vec![self.node_idx, self.item_idx]));
                 }
 
                 let item = &mut items[self.item_idx];
@@ -123,7 +133,10 @@ impl Command for EditTaskItemCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::Other("Node is not a list".into())),
+            _ => {
+                Err(EditError::other("Node is not a list")
+                    .at_path(vec![self.node_idx, self.item_idx]))
+            }
         }
     }
 
@@ -132,19 +145,25 @@ impl Command for EditTaskItemCommand {
             let mut document = self.document.borrow_mut();
 
             if self.node_idx >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(
+                    EditError::index_out_of_bounds().at_path(vec![self.node_idx, self.item_idx])
+                );
             }
 
             match &mut document.nodes[self.node_idx] {
-                Node::List { list_type, items } => {
+                Node::List {
+                    list_type, items, ..
+                } => {
                     // Verify that it's a task list
                     if *list_type != ListType::Task {
-                        return Err(EditError::Other("Node is not a task list".into()));
+                        return Err(EditError::other("Node is not a task list")
+                            .at_path(vec![self.node_idx, self.item_idx]));
                     }
 
                     // Get the task item
                     if self.item_idx >= items.len() {
-                        return Err(EditError::IndexOutOfBounds);
+                        return Err(EditError::index_out_of_bounds()
+                            .at_path(vec![self.node_idx, self.item_idx]));
                     }
 
                     let item = &mut items[self.item_idx];
@@ -155,25 +174,27 @@ impl Command for EditTaskItemCommand {
                         .iter()
                         .position(|child| matches!(child, Node::Paragraph { .. }));
 
-                    if let Some(idx) = para_idx {
-                        if let Node::Paragraph { children } = &mut item.children[idx] {
-                            // Restore previous text
-                            children.clear();
-                            children.push(InlineNode::text(previous_text.clone()));
-                            return Ok(());
-                        }
+                    if let Some(idx) = para_idx
+                        && let Node::Paragraph { children } = &mut item.children[idx]
+                    {
+                        // Restore previous text
+                        children.clear();
+                        children.push(InlineNode::text(previous_text.clone()));
+                        return Ok(());
                     }
 
-                    return Err(EditError::Other(
-                        "Paragraph node not found in task item".into(),
-                    ));
+                    return Err(EditError::other("Paragraph node not found in task item")
+                        .at_path(vec![self.node_idx, self.item_idx]));
+                }
+                _ => {
+                    return Err(EditError::other("Node is not a list")
+                        .at_path(vec![self.node_idx, self.item_idx]));
                 }
-                _ => return Err(EditError::Other("Node is not a list".into())),
             }
         }
 
-        Err(EditError::Other(
-            "Previous text state not available for undo".into(),
+        Err(EditError::other(
+            "Previous text state not available for undo",
         ))
     }
 
@@ -185,6 +206,7 @@ impl Command for EditTaskItemCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::EditErrorKind;
 
     #[test]
     fn test_edit_task_item() {
@@ -196,6 +218,8 @@ mod tests {
         let task_list = Node::List {
             list_type: ListType::Task,
             items,
+            start: None,
+            loose: false,
         };
 
         doc.nodes.push(task_list);
@@ -263,8 +287,8 @@ mod tests {
         // Try to edit a task in a paragraph
         let result = cmd.execute();
         assert!(result.is_err());
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Node is not a list");
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Node is not a list"));
         } else {
             panic!("Expected EditError::Other");
         }
@@ -280,6 +304,8 @@ mod tests {
         let task_list = Node::List {
             list_type: ListType::Task,
             items,
+            start: None,
+            loose: false,
         };
 
         doc.nodes.push(task_list);
@@ -290,12 +316,12 @@ mod tests {
         let mut cmd = EditTaskItemCommand::new(doc_rc.clone(), 0, 1, "Updated text");
         let result = cmd.execute();
         assert!(result.is_err());
-        assert!(matches!(result, Err(EditError::IndexOutOfBounds)));
+        assert_eq!(result.unwrap_err().kind(), EditErrorKind::IndexOutOfBounds);
 
         // Try to edit with an invalid node index
         let mut cmd = EditTaskItemCommand::new(doc_rc, 1, 0, "Updated text");
         let result = cmd.execute();
         assert!(result.is_err());
-        assert!(matches!(result, Err(EditError::IndexOutOfBounds)));
+        assert_eq!(result.unwrap_err().kind(), EditErrorKind::IndexOutOfBounds);
     }
 }
@@ -3,7 +3,7 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::editor::EditError;
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, InlineNode, Node};
 
 /// Command for finding and replacing text throughout a document
@@ -47,6 +47,13 @@ impl FindReplaceCommand {
 }
 
 impl Command for FindReplaceCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + self.find.len()
+            + self.replace.len()
+            + estimate_bytes(&self.original_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         if self.find.is_empty() {
             return Ok(());
@@ -211,7 +218,7 @@ impl Command for FindReplaceCommand {
             if node_idx < document.nodes.len() {
                 document.nodes[node_idx] = original_node;
             } else {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds());
             }
         }
         self.replacements = 0;
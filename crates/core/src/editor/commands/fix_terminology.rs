@@ -0,0 +1,75 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::terminology::apply_terminology;
+use crate::{Document, EditError, Node, Terminology};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Command to replace every variant of the preferred terms of a
+/// [`Terminology`] throughout the document
+pub struct FixTerminologyCommand {
+    document: Rc<RefCell<Document>>,
+    terminology: Terminology,
+    /// Original top-level nodes that changed, by index, for undo
+    original_nodes: Vec<(usize, Node)>,
+}
+
+impl FixTerminologyCommand {
+    /// Create a command enforcing `terminology`
+    pub fn new(document: Rc<RefCell<Document>>, terminology: Terminology) -> Self {
+        Self {
+            document,
+            terminology,
+            original_nodes: Vec::new(),
+        }
+    }
+}
+
+impl Command for FixTerminologyCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn affected_range(&self) -> Option<Range<usize>> {
+        let first = self.original_nodes.first()?.0;
+        let last = self.original_nodes.last()?.0;
+        Some(first..last + 1)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let mut original_nodes = Vec::new();
+        for (index, node) in document.nodes.iter_mut().enumerate() {
+            let original = node.clone();
+            if apply_terminology(node, &self.terminology) > 0 {
+                original_nodes.push((index, original));
+            }
+        }
+        if original_nodes.is_empty() {
+            return Err(EditError::operation_failed().with_message("No term to replace"));
+        }
+
+        self.original_nodes = original_nodes;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        if self.original_nodes.is_empty() {
+            return Err(EditError::other("No original state to restore"));
+        }
+        let mut document = self.document.borrow_mut();
+        for (index, original) in self.original_nodes.drain(..) {
+            let node = document
+                .nodes
+                .get_mut(index)
+                .ok_or(EditError::index_out_of_bounds().at_node(index))?;
+            *node = original;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
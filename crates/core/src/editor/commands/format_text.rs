@@ -1,4 +1,5 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
+use crate::offsets::{check_offset, offset_text};
 use crate::{Document, EditError, InlineNode, Node, TextFormatting, TextNode};
 use std::any::Any;
 use std::cell::RefCell;
@@ -34,16 +35,25 @@ impl FormatTextCommand {
 }
 
 impl Command for FormatTextCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
+        let node = &document.nodes[self.node_index];
         if self.start >= self.end {
-            return Err(EditError::InvalidRange);
+            return Err(EditError::invalid_range()
+                .at_node(self.node_index)
+                .with_ranges(0..offset_text(node).len(), self.start..self.end));
         }
+        check_offset(node, self.start).map_err(|err| err.at_node(self.node_index))?;
+        check_offset(node, self.end).map_err(|err| err.at_node(self.node_index))?;
 
         match &mut document.nodes[self.node_index] {
             Node::Paragraph { children } | Node::Heading { children, .. } => {
@@ -126,7 +136,7 @@ impl Command for FormatTextCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -134,7 +144,7 @@ impl Command for FormatTextCommand {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         match &mut document.nodes[self.node_index] {
@@ -144,10 +154,10 @@ impl Command for FormatTextCommand {
                     *children = original.clone();
                     Ok(())
                 } else {
-                    Err(EditError::OperationFailed)
+                    Err(EditError::operation_failed().at_node(self.node_index))
                 }
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
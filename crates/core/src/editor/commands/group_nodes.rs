@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, EditError, Node};
 use std::any::Any;
 use std::cell::RefCell;
@@ -32,6 +32,10 @@ impl GroupNodesCommand {
 }
 
 impl Command for GroupNodesCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
@@ -41,7 +45,7 @@ impl Command for GroupNodesCommand {
         // Validate node indices
         for &idx in &self.node_indices {
             if idx >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(idx));
             }
         }
 
@@ -79,7 +83,7 @@ impl Command for GroupNodesCommand {
             document.nodes = original_nodes;
             Ok(())
         } else {
-            Err(EditError::Other("No original state to restore".to_string()))
+            Err(EditError::other("No original state to restore"))
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::error::EditError;
 use crate::models::{Document, ListItem, ListType, Node};
 use std::any::Any;
@@ -66,11 +66,17 @@ impl IndentTaskItemCommand {
 }
 
 impl Command for IndentTaskItemCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_items)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_idx >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(
+                EditError::index_out_of_bounds().at_path(vec![self.node_idx, self.item_idx])
+            );
         }
 
         // Verify that we have a task list
@@ -80,16 +86,22 @@ impl Command for IndentTaskItemCommand {
                 list_type, items, ..
             } => {
                 if *list_type != ListType::Task {
-                    return Err(EditError::Other("Node is not a task list".into()));
+                    return Err(EditError::other("Node is not a task list")
+                        .at_path(vec![self.node_idx, self.item_idx]));
                 }
                 items
             }
-            _ => return Err(EditError::Other("Node is not a list".into())),
+            _ => {
+                return Err(EditError::other("Node is not a list")
+                    .at_path(vec![self.node_idx, self.item_idx]));
+            }
         };
 
         // Check if item index is valid
         if self.item_idx >= items.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(
+                EditError::index_out_of_bounds().at_path(vec![self.node_idx, self.item_idx])
+            );
         }
 
         // Store original items for undo
@@ -108,7 +120,8 @@ impl Command for IndentTaskItemCommand {
         match self.direction {
             IndentDirection::Increase => {
                 if self.item_idx == 0 {
-                    return Err(EditError::Other("Cannot indent the first item".into()));
+                    return Err(EditError::other("Cannot indent the first item")
+                        .at_path(vec![self.node_idx, self.item_idx]));
                 }
 
                 // First, we need to clone the item we want to move
@@ -130,6 +143,8 @@ impl Command for IndentTaskItemCommand {
                     let nested_list = Node::List {
                         list_type: ListType::Task,
                         items: vec![current_item],
+                        start: None,
+                        loose: false,
                     };
                     previous_item.children.push(nested_list);
                 } else {
@@ -140,11 +155,10 @@ impl Command for IndentTaskItemCommand {
                             items: nested_items,
                             ..
                         } = child
+                            && *list_type == ListType::Task
                         {
-                            if *list_type == ListType::Task {
-                                nested_items.push(current_item);
-                                break;
-                            }
+                            nested_items.push(current_item);
+                            break;
                         }
                     }
                 }
@@ -170,43 +184,46 @@ impl Command for IndentTaskItemCommand {
                         if let Node::List {
                             list_type,
                             items: nested_items,
+                            ..
                         } = child
+                            && *list_type == ListType::Task
+                            && self.item_idx < nested_items.len()
                         {
-                            if *list_type == ListType::Task && self.item_idx < nested_items.len() {
-                                // Found the nested item to dedent
-                                found = true;
-
-                                // Store the item to dedent
-                                item_to_dedent = Some(nested_items[self.item_idx].clone());
-
-                                // Update the parent item's children by rebuilding without the dedented item
-                                let mut updated_parent = new_items.pop().unwrap();
-                                let mut updated_children = Vec::new();
-
-                                for (i, child_node) in item.children.iter().enumerate() {
-                                    if i == child_idx {
-                                        // Create an updated version of the nested list
-                                        let mut remaining_items = nested_items.clone();
-                                        remaining_items.remove(self.item_idx);
-
-                                        // Only keep the list if it's not empty
-                                        if !remaining_items.is_empty() {
-                                            let updated_list = Node::List {
-                                                list_type: ListType::Task,
-                                                items: remaining_items,
-                                            };
-                                            updated_children.push(updated_list);
-                                        }
-                                    } else {
-                                        // Keep any other children
-                                        updated_children.push(child_node.clone());
+                            // Found the nested item to dedent
+                            found = true;
+
+                            // Store the item to dedent
+                            item_to_dedent = Some(nested_items[self.item_idx].clone());
+
+                            // Update the parent item's children by rebuilding without the dedented item
+                            let mut updated_parent = new_items.pop().unwrap();
+                            let mut updated_children = Vec::new();
+
+                            for (i, child_node) in item.children.iter().enumerate() {
+                                if i == child_idx {
+                                    // Create an updated version of the nested list
+                                    let mut remaining_items = nested_items.clone();
+                                    remaining_items.remove(self.item_idx);
+
+                                    // Only keep the list if it's not empty
+                                    if !remaining_items.is_empty() {
+                                        let updated_list = Node::List {
+                                            list_type: ListType::Task,
+                                            items: remaining_items,
+                                            start: None,
+                                            loose: false,
+                                        };
+                                        updated_children.push(updated_list);
                                     }
+                                } else {
+                                    // Keep any other children
+                                    updated_children.push(child_node.clone());
                                 }
-
-                                updated_parent.children = updated_children;
-                                new_items.push(updated_parent);
-                                break;
                             }
+
+                            updated_parent.children = updated_children;
+                            new_items.push(updated_parent);
+                            break;
                         }
                     }
 
@@ -216,7 +233,8 @@ impl Command for IndentTaskItemCommand {
                 }
 
                 if !found {
-                    return Err(EditError::Other("Item not found in any nested list".into()));
+                    return Err(EditError::other("Item not found in any nested list")
+                        .at_path(vec![self.node_idx, self.item_idx]));
                 }
 
                 // Insert the dedented item at the insertion point
@@ -243,7 +261,9 @@ impl Command for IndentTaskItemCommand {
             let mut document = self.document.borrow_mut();
 
             if self.node_idx >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(
+                    EditError::index_out_of_bounds().at_path(vec![self.node_idx, self.item_idx])
+                );
             }
 
             match &mut document.nodes[self.node_idx] {
@@ -252,10 +272,12 @@ impl Command for IndentTaskItemCommand {
                     *items = original_items.clone();
                     Ok(())
                 }
-                _ => Err(EditError::Other("Node is not a list".into())),
+                _ => Err(EditError::other("Node is not a list")
+                    .at_path(vec![self.node_idx, self.item_idx])),
             }
         } else {
-            Err(EditError::Other("No original items to restore".into()))
+            Err(EditError::other("No original items to restore")
+                .at_path(vec![self.node_idx, self.item_idx]))
         }
     }
 
@@ -282,6 +304,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -362,6 +386,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -371,8 +397,8 @@ mod tests {
         let result = cmd.execute();
         assert!(result.is_err());
 
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Cannot indent the first item");
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Cannot indent the first item"));
         } else {
             panic!("Expected EditError::Other");
         }
@@ -393,12 +419,16 @@ mod tests {
         let nested_task = Node::List {
             list_type: ListType::Task,
             items: vec![ListItem::task("Nested Task", false)],
+            start: None,
+            loose: false,
         };
         items[1].children.push(nested_task);
 
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items,
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -489,6 +519,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -498,8 +530,8 @@ mod tests {
         let result = cmd.execute();
         assert!(result.is_err());
 
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Item not found in any nested list");
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Item not found in any nested list"));
         } else {
             panic!("Expected EditError::Other");
         }
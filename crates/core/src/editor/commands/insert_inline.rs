@@ -0,0 +1,200 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::offsets::check_offset;
+use crate::urls::UrlPolicy;
+use crate::{Document, EditError, InlineNode, Node, TextNode};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to insert an inline node, such as a mention, or a run of inline
+/// nodes into a paragraph or heading
+///
+/// A text run the position falls inside is split around the new node, with
+/// both halves keeping their formatting.
+pub struct InsertInlineCommand {
+    document: Rc<RefCell<Document>>,
+    node_index: usize,
+    position: usize,
+    inlines: Vec<InlineNode>,
+    /// Allowlist the inlines' link and image URLs are checked against
+    url_policy: Option<UrlPolicy>,
+    /// Original children for undo
+    original_children: Option<Vec<InlineNode>>,
+}
+
+impl InsertInlineCommand {
+    /// Create a new command inserting `inline` at byte offset `position`
+    pub fn new(
+        document: Rc<RefCell<Document>>,
+        node_index: usize,
+        position: usize,
+        inline: InlineNode,
+    ) -> Self {
+        Self::many(document, node_index, position, vec![inline])
+    }
+
+    /// Create a new command inserting `inlines` in order at byte offset
+    /// `position`
+    pub fn many(
+        document: Rc<RefCell<Document>>,
+        node_index: usize,
+        position: usize,
+        inlines: Vec<InlineNode>,
+    ) -> Self {
+        Self {
+            document,
+            node_index,
+            position,
+            inlines,
+            url_policy: None,
+            original_children: None,
+        }
+    }
+
+    /// Rejects the inlines with [`EditError::invalid_url`] if one holds a link
+    /// or image URL `policy` doesn't allow
+    pub fn with_url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.url_policy = Some(policy);
+        self
+    }
+}
+
+impl Command for InsertInlineCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.inlines) + estimate_bytes(&self.original_children)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        if let Some(policy) = &self.url_policy {
+            for inline in &self.inlines {
+                policy
+                    .check_inline(inline)
+                    .map_err(|err| err.at_node(self.node_index))?;
+            }
+        }
+        let mut document = self.document.borrow_mut();
+        let node = document
+            .nodes
+            .get_mut(self.node_index)
+            .ok_or_else(|| EditError::index_out_of_bounds().at_node(self.node_index))?;
+        check_offset(node, self.position).map_err(|err| err.at_node(self.node_index))?;
+        let (Node::Paragraph { children } | Node::Heading { children, .. }) = node else {
+            return Err(EditError::unsupported().at_node(self.node_index));
+        };
+
+        // Find the child to insert before, and how far into it a text run
+        // has to be split
+        let mut offset = 0;
+        let mut target = None;
+        for (index, child) in children.iter().enumerate() {
+            let len = match child {
+                InlineNode::Text(text) => text.text.len(),
+                _ => 1,
+            };
+            if self.position < offset + len || (self.position == offset && len == 0) {
+                target = Some((index, self.position - offset));
+                break;
+            }
+            offset += len;
+        }
+        if target.is_none() && self.position > offset {
+            return Err(EditError::invalid_range()
+                .at_node(self.node_index)
+                .with_ranges(0..offset, self.position..self.position));
+        }
+
+        self.original_children = Some(children.clone());
+        let index = match target {
+            Some((index, 0)) => index,
+            Some((index, split)) => {
+                let InlineNode::Text(text) = &mut children[index] else {
+                    unreachable!("only text runs are longer than one offset");
+                };
+                let tail =
+                    TextNode::with_formatting(text.text.split_off(split), text.formatting.clone());
+                children.insert(index + 1, InlineNode::Text(tail));
+                index + 1
+            }
+            None => children.len(),
+        };
+        children.splice(index..index, self.inlines.iter().cloned());
+
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let Some(original_children) = self.original_children.take() else {
+            return Err(EditError::other("No original state to restore"));
+        };
+        let mut document = self.document.borrow_mut();
+        match document.nodes.get_mut(self.node_index) {
+            Some(Node::Paragraph { children } | Node::Heading { children, .. }) => {
+                *children = original_children;
+                Ok(())
+            }
+            _ => Err(EditError::operation_failed().at_node(self.node_index)),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_inline() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::Paragraph {
+            children: vec![
+                InlineNode::text("Thanks "),
+                InlineNode::bold_text("everyone"),
+            ],
+        });
+        let doc_rc = Rc::new(RefCell::new(doc));
+
+        let mut cmd =
+            InsertInlineCommand::new(doc_rc.clone(), 0, 9, InlineNode::user_mention("octocat"));
+        cmd.execute().unwrap();
+        assert_eq!(
+            doc_rc.borrow().nodes[0].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("Thanks "),
+                InlineNode::bold_text("ev"),
+                InlineNode::user_mention("octocat"),
+                InlineNode::bold_text("eryone"),
+            ]
+        );
+
+        cmd.undo().unwrap();
+        assert_eq!(doc_rc.borrow().nodes[0].as_paragraph().unwrap().len(), 2);
+
+        // At the end of the node
+        let mut cmd =
+            InsertInlineCommand::new(doc_rc.clone(), 0, 15, InlineNode::issue_mention("42"));
+        cmd.execute().unwrap();
+        assert_eq!(
+            doc_rc.borrow().nodes[0].as_paragraph().unwrap()[2],
+            InlineNode::issue_mention("42")
+        );
+
+        let mut cmd =
+            InsertInlineCommand::new(doc_rc.clone(), 0, 99, InlineNode::issue_mention("1"));
+        assert!(cmd.execute().is_err());
+
+        let mut cmd = InsertInlineCommand::new(
+            doc_rc.clone(),
+            0,
+            0,
+            InlineNode::link("javascript:alert(1)", "x"),
+        )
+        .with_url_policy(UrlPolicy::default());
+        let err = cmd.execute().unwrap_err();
+        assert_eq!(err.kind(), crate::EditErrorKind::InvalidUrl);
+        assert_eq!(err.node_index(), Some(0));
+        assert_eq!(doc_rc.borrow().nodes[0].as_paragraph().unwrap().len(), 3);
+    }
+}
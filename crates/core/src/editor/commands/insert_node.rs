@@ -1,8 +1,10 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::error::EditError;
-use crate::models::{CodeBlockProperties, Document, InlineNode, Node};
+use crate::models::{CodeBlockProperties, Document, HeadingProperties, InlineNode, Node};
+use crate::urls::UrlPolicy;
 use std::any::Any;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 
 /// Command to insert a new node at a specific position in the document
@@ -10,6 +12,8 @@ pub struct InsertNodeCommand {
     document: Rc<RefCell<Document>>,
     position: usize,
     node: Node,
+    /// Allowlist the node's link and image URLs are checked against
+    url_policy: Option<UrlPolicy>,
     // Store the inserted index for undo
     inserted_index: Option<usize>,
 }
@@ -21,10 +25,18 @@ impl InsertNodeCommand {
             document,
             position,
             node,
+            url_policy: None,
             inserted_index: None,
         }
     }
 
+    /// Rejects the node with [`EditError::invalid_url`] if it holds a link
+    /// or image URL `policy` doesn't allow
+    pub fn with_url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.url_policy = Some(policy);
+        self
+    }
+
     /// Helper method to create a new paragraph node with text
     pub fn new_paragraph(document: Rc<RefCell<Document>>, position: usize, text: &str) -> Self {
         Self::new(
@@ -52,6 +64,7 @@ impl InsertNodeCommand {
             Node::Heading {
                 level: safe_level,
                 children: vec![InlineNode::text(text)],
+                properties: HeadingProperties::default(),
             },
         )
     }
@@ -89,26 +102,42 @@ impl InsertNodeCommand {
 }
 
 impl Command for InsertNodeCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.node)
+    }
+
+    fn affected_range(&self) -> Option<Range<usize>> {
+        Some(match self.inserted_index {
+            Some(index) => index..index + 1,
+            None => self.position..self.position,
+        })
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         // Validate the document
         if self.document.borrow().nodes.is_empty() && self.position > 0 {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.position));
         }
 
         let mut document = self.document.borrow_mut();
 
         // Check if position is valid (can be equal to length to append at the end)
         if self.position > document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.position));
         }
 
         // Check for temporary node variants that shouldn't be inserted directly
         match &self.node {
             Node::TempListItem(_) | Node::TempTableCell(_) => {
-                return Err(EditError::UnsupportedOperation);
+                return Err(EditError::unsupported().at_node(self.position));
             }
             _ => {}
         }
+        if let Some(policy) = &self.url_policy {
+            policy
+                .check_node(&self.node)
+                .map_err(|err| err.at_node(self.position))?;
+        }
 
         // Insert the node at the specified position
         document.nodes.insert(self.position, self.node.clone());
@@ -124,7 +153,7 @@ impl Command for InsertNodeCommand {
             let mut document = self.document.borrow_mut();
 
             if index >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.position));
             }
 
             // Remove the node we inserted
@@ -135,7 +164,7 @@ impl Command for InsertNodeCommand {
 
             Ok(())
         } else {
-            Err(EditError::OperationFailed)
+            Err(EditError::operation_failed().at_node(self.position))
         }
     }
 
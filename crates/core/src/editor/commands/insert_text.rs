@@ -1,7 +1,9 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
+use crate::offsets::check_offset;
 use crate::{Document, EditError, InlineNode, Node};
 use std::any::Any;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 
 /// Command to insert text at a specific position in a node
@@ -29,9 +31,29 @@ impl InsertTextCommand {
             affected_nodes: None,
         }
     }
+
+    pub(crate) fn node_index(&self) -> usize {
+        self.node_index
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
 }
 
 impl Command for InsertTextCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + self.text.len() + estimate_bytes(&self.affected_nodes)
+    }
+
+    fn affected_range(&self) -> Option<Range<usize>> {
+        Some(self.node_index..self.node_index + 1)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         // Early validation
         if self.text.is_empty() {
@@ -42,8 +64,10 @@ impl Command for InsertTextCommand {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
+        check_offset(&document.nodes[self.node_index], self.position)
+            .map_err(|err| err.at_node(self.node_index))?;
 
         match &mut document.nodes[self.node_index] {
             Node::Paragraph { children } | Node::Heading { children, .. } => {
@@ -95,7 +119,9 @@ impl Command for InsertTextCommand {
 
                 // If the position is beyond the end of all nodes
                 if node_idx_to_modify.is_none() && self.position > current_offset {
-                    return Err(EditError::InvalidRange);
+                    return Err(EditError::invalid_range()
+                        .at_node(self.node_index)
+                        .with_ranges(0..current_offset, self.position..self.position));
                 }
 
                 // If we found a valid position within a node
@@ -104,7 +130,7 @@ impl Command for InsertTextCommand {
                         InlineNode::Text(text_node) => {
                             // Sanity check for position bounds
                             if position_in_node > text_node.text.len() {
-                                return Err(EditError::InvalidRange);
+                                return Err(EditError::invalid_range().at_node(self.node_index));
                             }
 
                             // Insert the text at the specified position
@@ -118,7 +144,7 @@ impl Command for InsertTextCommand {
                     Ok(())
                 } else {
                     // If no valid position found, the position is out of bounds
-                    Err(EditError::InvalidRange)
+                    Err(EditError::invalid_range().at_node(self.node_index))
                 }
             }
             Node::CodeBlock { code, .. } => {
@@ -132,22 +158,33 @@ impl Command for InsertTextCommand {
                     code.insert_str(self.position, &self.text);
                     Ok(())
                 } else {
-                    Err(EditError::InvalidRange)
+                    Err(EditError::invalid_range()
+                        .at_node(self.node_index)
+                        .with_ranges(0..code.len(), self.position..self.position))
                 }
             }
             // Be more specific about which node types are not supported
-            Node::List { .. } => Err(EditError::UnsupportedOperation),
-            Node::BlockQuote { .. } => Err(EditError::UnsupportedOperation),
-            Node::ThematicBreak => Err(EditError::UnsupportedOperation),
-            Node::Table { .. } => Err(EditError::UnsupportedOperation),
-            Node::Group { .. } => Err(EditError::UnsupportedOperation),
-            Node::FootnoteReference(_) => Err(EditError::UnsupportedOperation),
-            Node::FootnoteDefinition(_) => Err(EditError::UnsupportedOperation),
-            Node::DefinitionList { .. } => Err(EditError::UnsupportedOperation),
-            Node::MathBlock { .. } => Err(EditError::UnsupportedOperation),
+            Node::List { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::BlockQuote { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::ThematicBreak => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Table { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Group { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::FootnoteReference(_) => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::FootnoteDefinition(_) => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::DefinitionList { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::MathBlock { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Embed { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Figure { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::HtmlBlock { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::HtmlComment { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Callout { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::VisibilityBlock { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Custom { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::Conflict { .. } => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::TableOfContents { .. } => Err(EditError::unsupported().at_node(self.node_index)),
             // Handle temporary variants
-            Node::TempListItem(_) => Err(EditError::UnsupportedOperation),
-            Node::TempTableCell(_) => Err(EditError::UnsupportedOperation),
+            Node::TempListItem(_) => Err(EditError::unsupported().at_node(self.node_index)),
+            Node::TempTableCell(_) => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -156,7 +193,7 @@ impl Command for InsertTextCommand {
             let mut document = self.document.borrow_mut();
 
             if self.node_index >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.node_index));
             }
 
             match &mut document.nodes[self.node_index] {
@@ -171,7 +208,7 @@ impl Command for InsertTextCommand {
                         *code = text_node.text.clone();
                         Ok(())
                     } else {
-                        Err(EditError::OperationFailed)
+                        Err(EditError::operation_failed().at_node(self.node_index))
                     }
                 }
                 // These node types should never have been modified
@@ -184,11 +221,20 @@ impl Command for InsertTextCommand {
                 | Node::FootnoteDefinition(_)
                 | Node::DefinitionList { .. }
                 | Node::MathBlock { .. }
+                | Node::Embed { .. }
+                | Node::Figure { .. }
+                | Node::HtmlBlock { .. }
+                | Node::HtmlComment { .. }
+                | Node::Callout { .. }
+                | Node::VisibilityBlock { .. }
+                | Node::Custom { .. }
+                | Node::Conflict { .. }
+                | Node::TableOfContents { .. }
                 | Node::TempListItem(_)
-                | Node::TempTableCell(_) => Err(EditError::UnsupportedOperation),
+                | Node::TempTableCell(_) => Err(EditError::unsupported().at_node(self.node_index)),
             }
         } else {
-            Err(EditError::OperationFailed)
+            Err(EditError::operation_failed().at_node(self.node_index))
         }
     }
 
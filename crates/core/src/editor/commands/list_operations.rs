@@ -0,0 +1,323 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, InlineNode, ListItem, ListType, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Operations on the items of a list of any type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListOperation {
+    /// Insert an item with a paragraph of text; items of task lists start
+    /// unchecked
+    AddItem { position: usize, text: String },
+    /// Remove an item; the last remaining item cannot be removed
+    RemoveItem(usize),
+    /// Replace the text of an item's first paragraph, keeping nested lists
+    EditItem { item: usize, text: String },
+    /// Move an item to another position
+    MoveItem { from: usize, to: usize },
+    /// Nest an item at the end of the previous item, in a list of the same
+    /// type
+    IndentItem(usize),
+    /// Move a nested item out of its parent item, to right after it
+    ///
+    /// The items following it in the nested list become its own nested
+    /// items, so the reading order does not change.
+    UnindentItem { parent: usize, item: usize },
+    /// Change the list type, adding or dropping checkboxes as needed
+    ConvertType(ListType),
+}
+
+/// Command to perform an operation on an existing list
+pub struct ListOperationsCommand {
+    document: Rc<RefCell<Document>>,
+    /// The index of the list node in the document
+    node_index: usize,
+    /// The operation to perform
+    operation: ListOperation,
+    /// Original node for undo
+    original_node: Option<Node>,
+}
+
+impl ListOperationsCommand {
+    /// Create a new list operations command
+    pub fn new(
+        document: Rc<RefCell<Document>>,
+        node_index: usize,
+        operation: ListOperation,
+    ) -> Self {
+        Self {
+            document,
+            node_index,
+            operation,
+            original_node: None,
+        }
+    }
+
+    fn item_error(&self, item: usize) -> EditError {
+        EditError::index_out_of_bounds().at_path(vec![self.node_index, item])
+    }
+}
+
+/// Returns the list nested at the end of an item, if any, creating one of
+/// `list_type` otherwise
+fn nested_list(item: &mut ListItem, list_type: ListType) -> &mut Vec<ListItem> {
+    if !matches!(item.children.last(), Some(Node::List { .. })) {
+        item.children.push(Node::List {
+            list_type,
+            items: Vec::new(),
+            start: None,
+            loose: false,
+        });
+    }
+    match item.children.last_mut() {
+        Some(Node::List { items, .. }) => items,
+        _ => unreachable!("a nested list was just ensured"),
+    }
+}
+
+impl Command for ListOperationsCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_node)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let Some(node) = document.nodes.get_mut(self.node_index) else {
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
+        };
+        let original = node.clone();
+        let Node::List {
+            list_type, items, ..
+        } = node
+        else {
+            return Err(EditError::invalid_node()
+                .at_node(self.node_index)
+                .with_message("Node is not a list"));
+        };
+
+        match &self.operation {
+            ListOperation::AddItem { position, text } => {
+                if *position > items.len() {
+                    return Err(self.item_error(*position));
+                }
+                let item = match list_type {
+                    ListType::Task => ListItem::task(text.clone(), false),
+                    _ => ListItem::paragraph(text.clone()),
+                };
+                items.insert(*position, item);
+            }
+            ListOperation::RemoveItem(item) => {
+                if *item >= items.len() {
+                    return Err(self.item_error(*item));
+                }
+                if items.len() == 1 {
+                    return Err(EditError::unsupported()
+                        .at_path(vec![self.node_index, *item])
+                        .with_message("Cannot remove the only item of a list"));
+                }
+                items.remove(*item);
+            }
+            ListOperation::EditItem { item, text } => {
+                let Some(list_item) = items.get_mut(*item) else {
+                    return Err(self.item_error(*item));
+                };
+                let children = vec![InlineNode::text(text.clone())];
+                match list_item.children.first_mut() {
+                    Some(Node::Paragraph { children: existing }) => *existing = children,
+                    _ => list_item.children.insert(0, Node::Paragraph { children }),
+                }
+            }
+            ListOperation::MoveItem { from, to } => {
+                if *from >= items.len() {
+                    return Err(self.item_error(*from));
+                }
+                if *to >= items.len() {
+                    return Err(self.item_error(*to));
+                }
+                let moved = items.remove(*from);
+                items.insert(*to, moved);
+            }
+            ListOperation::IndentItem(item) => {
+                if *item >= items.len() {
+                    return Err(self.item_error(*item));
+                }
+                if *item == 0 {
+                    return Err(EditError::unsupported()
+                        .at_path(vec![self.node_index, 0])
+                        .with_message("Cannot indent the first item"));
+                }
+                let moved = items.remove(*item);
+                let list_type = list_type.clone();
+                nested_list(&mut items[*item - 1], list_type).push(moved);
+            }
+            ListOperation::UnindentItem { parent, item } => {
+                let Some(parent_item) = items.get_mut(*parent) else {
+                    return Err(self.item_error(*parent));
+                };
+                let nested = match parent_item.children.last_mut() {
+                    Some(Node::List {
+                        list_type, items, ..
+                    }) if *item < items.len() => (list_type.clone(), items),
+                    _ => {
+                        return Err(EditError::index_out_of_bounds().at_path(vec![
+                            self.node_index,
+                            *parent,
+                            *item,
+                        ]));
+                    }
+                };
+                let (nested_type, nested_items) = nested;
+                let following = nested_items.split_off(*item + 1);
+                let mut moved = nested_items.pop().expect("item is within the nested list");
+                if nested_items.is_empty() {
+                    parent_item.children.pop();
+                }
+                if !following.is_empty() {
+                    nested_list(&mut moved, nested_type).extend(following);
+                }
+                match list_type {
+                    ListType::Task => moved.checked = moved.checked.or(Some(false)),
+                    _ => moved.checked = None,
+                }
+                items.insert(*parent + 1, moved);
+            }
+            ListOperation::ConvertType(new_type) => {
+                for item in items.iter_mut() {
+                    item.checked = match new_type {
+                        ListType::Task => item.checked.or(Some(false)),
+                        _ => None,
+                    };
+                }
+                *list_type = new_type.clone();
+            }
+        }
+
+        self.original_node = Some(original);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let Some(original_node) = self.original_node.take() else {
+            return Err(EditError::other("No original state to restore").at_node(self.node_index));
+        };
+        let mut document = self.document.borrow_mut();
+        match document.nodes.get_mut(self.node_index) {
+            Some(node) => {
+                *node = original_node;
+                Ok(())
+            }
+            None => Err(EditError::index_out_of_bounds().at_node(self.node_index)),
+        }
+    }
+
+    fn affected_range(&self) -> Option<std::ops::Range<usize>> {
+        Some(self.node_index..self.node_index + 1)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(document: &Rc<RefCell<Document>>, operation: ListOperation) -> Result<(), EditError> {
+        ListOperationsCommand::new(document.clone(), 0, operation).execute()
+    }
+
+    fn texts(document: &Rc<RefCell<Document>>) -> Vec<String> {
+        match &document.borrow().nodes[0] {
+            Node::List { items, .. } => items
+                .iter()
+                .map(|item| item.as_text().unwrap_or_default().to_string())
+                .collect(),
+            _ => panic!("Expected list"),
+        }
+    }
+
+    #[test]
+    fn test_list_item_operations() {
+        let mut doc = Document::new();
+        doc.add_ordered_list(vec!["One", "Two", "Three"]);
+        let document = Rc::new(RefCell::new(doc));
+
+        let add = ListOperation::AddItem {
+            position: 3,
+            text: "Four".to_string(),
+        };
+        run(&document, add).unwrap();
+        run(&document, ListOperation::MoveItem { from: 3, to: 0 }).unwrap();
+        let edit = ListOperation::EditItem {
+            item: 1,
+            text: "First".to_string(),
+        };
+        run(&document, edit).unwrap();
+        run(&document, ListOperation::RemoveItem(2)).unwrap();
+        assert_eq!(texts(&document), ["Four", "First", "Three"]);
+        assert!(run(&document, ListOperation::RemoveItem(3)).is_err());
+        assert!(run(&document, ListOperation::MoveItem { from: 0, to: 3 }).is_err());
+
+        let mut cmd = ListOperationsCommand::new(
+            document.clone(),
+            0,
+            ListOperation::ConvertType(ListType::Task),
+        );
+        cmd.execute().unwrap();
+        assert!(matches!(
+            &document.borrow().nodes[0],
+            Node::List { list_type: ListType::Task, items, .. } if items.iter().all(|item| item.checked == Some(false))
+        ));
+        cmd.undo().unwrap();
+        assert!(matches!(
+            &document.borrow().nodes[0],
+            Node::List { list_type: ListType::Ordered, items, .. } if items[0].checked.is_none()
+        ));
+    }
+
+    #[test]
+    fn test_indent_and_unindent() {
+        let mut doc = Document::new();
+        doc.add_unordered_list(vec!["a", "b", "c", "d"]);
+        let document = Rc::new(RefCell::new(doc));
+
+        assert!(run(&document, ListOperation::IndentItem(0)).is_err());
+        run(&document, ListOperation::IndentItem(1)).unwrap();
+        run(&document, ListOperation::IndentItem(1)).unwrap();
+        run(&document, ListOperation::IndentItem(1)).unwrap();
+        assert_eq!(texts(&document), ["a"]);
+        assert_eq!(
+            document.borrow().nodes[0].plain_text(),
+            "a\nb\nc\nd",
+            "nesting keeps the reading order"
+        );
+
+        // "b" leaves the nested list and takes "c" and "d" along
+        run(
+            &document,
+            ListOperation::UnindentItem { parent: 0, item: 0 },
+        )
+        .unwrap();
+        assert_eq!(texts(&document), ["a", "b"]);
+        {
+            let doc = document.borrow();
+            let Node::List { items, .. } = &doc.nodes[0] else {
+                panic!("Expected list");
+            };
+            assert_eq!(items[0].children.len(), 1);
+            assert!(matches!(
+                &items[1].children[1],
+                Node::List { list_type: ListType::Unordered, items, .. } if items.len() == 2
+            ));
+        }
+        assert!(
+            run(
+                &document,
+                ListOperation::UnindentItem { parent: 0, item: 0 }
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Which kinds of adjacent top-level nodes to merge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// Merge consecutive lists of the same type
+    pub lists: bool,
+    /// Merge consecutive blockquotes
+    pub blockquotes: bool,
+    /// Merge consecutive code blocks
+    pub code_blocks: bool,
+    /// Only merge code blocks whose languages match
+    pub same_language: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            lists: true,
+            blockquotes: true,
+            code_blocks: true,
+            same_language: true,
+        }
+    }
+}
+
+impl MergeOptions {
+    /// Merges lists, blockquotes and code blocks of the same language
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merges code blocks regardless of their language, keeping the first one's
+    pub fn any_language(mut self) -> Self {
+        self.same_language = false;
+        self
+    }
+
+    /// Leaves code blocks alone
+    pub fn without_code_blocks(mut self) -> Self {
+        self.code_blocks = false;
+        self
+    }
+}
+
+/// Folds `next` into `previous` if the options allow, handing it back otherwise
+fn merge(previous: &mut Node, next: Node, options: &MergeOptions) -> Option<Node> {
+    match (previous, next) {
+        (
+            Node::List {
+                list_type, items, ..
+            },
+            Node::List {
+                list_type: next_type,
+                items: next_items,
+                ..
+            },
+        ) if options.lists && *list_type == next_type => {
+            items.extend(next_items);
+            None
+        }
+        (
+            Node::BlockQuote { children },
+            Node::BlockQuote {
+                children: next_children,
+            },
+        ) if options.blockquotes => {
+            children.extend(next_children);
+            None
+        }
+        (
+            Node::CodeBlock { language, code, .. },
+            Node::CodeBlock {
+                language: next_language,
+                code: next_code,
+                ..
+            },
+        ) if options.code_blocks && (!options.same_language || *language == next_language) => {
+            if !code.is_empty() && !code.ends_with('\n') {
+                code.push('\n');
+            }
+            code.push_str(&next_code);
+            None
+        }
+        (_, next) => Some(next),
+    }
+}
+
+/// Command to merge consecutive compatible top-level nodes
+pub struct MergeAdjacentCommand {
+    document: Rc<RefCell<Document>>,
+    options: MergeOptions,
+    /// Number of nodes folded into their predecessor
+    merged: usize,
+    /// Original nodes for undo
+    original_nodes: Option<Vec<Node>>,
+}
+
+impl MergeAdjacentCommand {
+    /// Create a new merge command
+    pub fn new(document: Rc<RefCell<Document>>, options: MergeOptions) -> Self {
+        Self {
+            document,
+            options,
+            merged: 0,
+            original_nodes: None,
+        }
+    }
+
+    /// Number of nodes the last execution folded into their predecessor
+    pub fn merged(&self) -> usize {
+        self.merged
+    }
+}
+
+impl Command for MergeAdjacentCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        self.original_nodes = Some(document.nodes.clone());
+
+        let mut nodes: Vec<Node> = Vec::with_capacity(document.nodes.len());
+        self.merged = 0;
+        for node in std::mem::take(&mut document.nodes) {
+            let unmerged = match nodes.last_mut() {
+                Some(previous) => merge(previous, node, &self.options),
+                None => Some(node),
+            };
+            match unmerged {
+                Some(node) => nodes.push(node),
+                None => self.merged += 1,
+            }
+        }
+        document.nodes = nodes;
+
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        if let Some(original_nodes) = self.original_nodes.take() {
+            let mut document = self.document.borrow_mut();
+            document.nodes = original_nodes;
+            Ok(())
+        } else {
+            Err(EditError::other("No original state to restore"))
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_adjacent() {
+        let mut doc = Document::new();
+        doc.add_unordered_list(vec!["a"]);
+        doc.add_unordered_list(vec!["b", "c"]);
+        doc.add_ordered_list(vec!["1"]);
+        doc.add_code_block("let a = 1;", "rust");
+        doc.add_code_block("let b = 2;", "rust");
+        doc.add_code_block("print(3)", "python");
+        doc.nodes.push(Node::blockquote("One"));
+        doc.nodes.push(Node::blockquote("Two"));
+        let doc_rc = Rc::new(RefCell::new(doc));
+
+        let mut cmd = MergeAdjacentCommand::new(doc_rc.clone(), MergeOptions::new());
+        cmd.execute().unwrap();
+        assert_eq!(cmd.merged(), 3);
+        {
+            let doc = doc_rc.borrow();
+            assert_eq!(doc.nodes.len(), 5);
+            assert!(matches!(&doc.nodes[0], Node::List { items, .. } if items.len() == 3));
+            assert!(
+                matches!(&doc.nodes[2], Node::CodeBlock { code, .. } if code == "let a = 1;\nlet b = 2;")
+            );
+            assert!(matches!(&doc.nodes[4], Node::BlockQuote { children } if children.len() == 2));
+        }
+
+        cmd.undo().unwrap();
+        assert_eq!(doc_rc.borrow().nodes.len(), 8);
+
+        let options = MergeOptions::new().any_language();
+        let mut cmd = MergeAdjacentCommand::new(doc_rc.clone(), options);
+        cmd.execute().unwrap();
+        assert_eq!(cmd.merged(), 4);
+        assert!(
+            matches!(&doc_rc.borrow().nodes[2], Node::CodeBlock { language, code, .. } if language == "rust" && code.ends_with("print(3)"))
+        );
+    }
+}
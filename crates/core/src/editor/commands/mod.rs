@@ -1,4 +1,7 @@
 pub mod add_task_item;
+pub mod apply_correction;
+pub mod bulk_tasks;
+pub mod comments;
 pub mod copy_selection;
 pub mod create_table;
 pub mod create_toc;
@@ -7,22 +10,39 @@ pub mod delete_node;
 pub mod duplicate_node;
 pub mod edit_task_item;
 pub mod find_replace;
+pub mod fix_terminology;
 pub mod format_text;
 pub mod group_nodes;
 pub mod indent_task_item;
+pub mod insert_inline;
 pub mod insert_node;
 pub mod insert_text;
+pub mod list_operations;
+pub mod merge_adjacent;
 pub mod move_node;
+pub mod move_section;
 pub mod move_task_item;
+pub mod multi_selection;
 pub mod node_conversion;
+pub mod remove_html_comments;
 pub mod remove_task_item;
+pub mod rename_symbol;
 pub mod selection_format;
 pub mod selection_indent;
+pub mod set_code_block_properties;
+pub mod set_link_titles;
+pub mod set_visibility;
+pub mod set_word_target;
 pub mod sort_task_list;
 pub mod table_operations;
 pub mod toggle_task;
+pub mod track_changes;
+pub mod update_images;
 
 pub use add_task_item::AddTaskItemCommand;
+pub use apply_correction::ApplyCorrectionCommand;
+pub use bulk_tasks::{BulkTaskAction, BulkTaskCommand};
+pub use comments::{CommentAction, CommentCommand};
 pub use copy_selection::CopySelectionCommand;
 pub use create_table::CreateTableCommand;
 pub use create_toc::CreateTOCCommand;
@@ -31,17 +51,31 @@ pub use delete_node::DeleteNodeCommand;
 pub use duplicate_node::DuplicateNodeCommand;
 pub use edit_task_item::EditTaskItemCommand;
 pub use find_replace::FindReplaceCommand;
+pub use fix_terminology::FixTerminologyCommand;
 pub use format_text::FormatTextCommand;
 pub use group_nodes::GroupNodesCommand;
 pub use indent_task_item::IndentTaskItemCommand;
+pub use insert_inline::InsertInlineCommand;
 pub use insert_node::InsertNodeCommand;
 pub use insert_text::InsertTextCommand;
+pub use list_operations::{ListOperation, ListOperationsCommand};
+pub use merge_adjacent::{MergeAdjacentCommand, MergeOptions};
 pub use move_node::MoveNodeCommand;
+pub use move_section::MoveSectionCommand;
 pub use move_task_item::{MoveTaskItemCommand, MoveTaskPositionCommand};
+pub use multi_selection::{MultiSelectionCommand, SelectionEdit};
 pub use node_conversion::ConvertNodeTypeCommand;
+pub use remove_html_comments::RemoveHtmlCommentsCommand;
 pub use remove_task_item::RemoveTaskItemCommand;
+pub use rename_symbol::RenameSymbolCommand;
 pub use selection_format::SelectionFormatCommand;
 pub use selection_indent::{IndentDirection, SelectionIndentCommand};
+pub use set_code_block_properties::SetCodeBlockPropertiesCommand;
+pub use set_link_titles::SetLinkTitlesCommand;
+pub use set_visibility::SetVisibilityCommand;
+pub use set_word_target::SetWordTargetCommand;
 pub use sort_task_list::SortCriteria;
 pub use table_operations::{TableOperation, TableOperationsCommand};
 pub use toggle_task::ToggleTaskCommand;
+pub use track_changes::{TrackChangesCommand, TrackedEdit};
+pub use update_images::UpdateImagesCommand;
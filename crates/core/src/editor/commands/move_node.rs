@@ -1,7 +1,8 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, inverse_order};
 use crate::{Document, EditError};
 use std::any::Any;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 
 /// Command to move a node from one position to another in a document
@@ -9,6 +10,8 @@ pub struct MoveNodeCommand {
     document: Rc<RefCell<Document>>,
     from_index: usize,
     to_index: usize,
+    /// Whether the command was last undone rather than run
+    undone: bool,
 }
 
 impl MoveNodeCommand {
@@ -17,24 +20,45 @@ impl MoveNodeCommand {
             document,
             from_index,
             to_index,
+            undone: false,
         }
     }
 }
 
 impl Command for MoveNodeCommand {
+    fn affected_range(&self) -> Option<Range<usize>> {
+        let to_index = match self.to_index > self.from_index {
+            true => self.to_index - 1,
+            false => self.to_index,
+        };
+        Some(self.from_index.min(to_index)..self.from_index.max(to_index) + 1)
+    }
+
+    fn node_order(&self) -> Option<Vec<usize>> {
+        let range = self.affected_range()?;
+        let to_index = range.start + range.end - 1 - self.from_index;
+        let mut order: Vec<usize> = range.clone().filter(|&i| i != self.from_index).collect();
+        order.insert(to_index - range.start, self.from_index);
+        Some(match self.undone {
+            true => inverse_order(range.start, &order),
+            false => order,
+        })
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.from_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.from_index));
         }
 
         if self.to_index > document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.from_index));
         }
 
         // If source and destination are the same, no need to move
         if self.from_index == self.to_index {
+            self.undone = false;
             return Ok(());
         }
 
@@ -51,6 +75,7 @@ impl Command for MoveNodeCommand {
         // Insert the node at the new position
         document.nodes.insert(adjusted_to_index, node);
 
+        self.undone = false;
         Ok(())
     }
 
@@ -66,7 +91,7 @@ impl Command for MoveNodeCommand {
         };
 
         if current_pos >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.from_index));
         }
 
         // Remove the node from its current position
@@ -75,6 +100,7 @@ impl Command for MoveNodeCommand {
         // Insert it back at the original position
         document.nodes.insert(self.from_index, node);
 
+        self.undone = true;
         Ok(())
     }
 
@@ -0,0 +1,169 @@
+use crate::editor::command::{Command, inverse_order};
+use crate::{Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Command to move a heading together with its section content
+pub struct MoveSectionCommand {
+    document: Rc<RefCell<Document>>,
+    heading_index: usize,
+    to_position: usize,
+    /// Change of level of every heading in the section
+    level_shift: i8,
+    // Where the section was taken from and where it ended up, for undo
+    moved: Option<(Range<usize>, usize)>,
+    /// Whether the command was last undone rather than run
+    undone: bool,
+}
+
+/// Changes the level of every heading in `nodes` by `shift`
+fn shift_levels(nodes: &mut [Node], shift: i8) {
+    for node in nodes {
+        if let Node::Heading { level, .. } = node {
+            *level = level.saturating_add_signed(shift);
+        }
+    }
+}
+
+impl MoveSectionCommand {
+    /// Create a command moving the section of the heading at `heading_index`
+    ///
+    /// `to_position` is a node index in the document before the move and may
+    /// not fall inside the section itself.
+    pub fn new(document: Rc<RefCell<Document>>, heading_index: usize, to_position: usize) -> Self {
+        Self {
+            document,
+            heading_index,
+            to_position,
+            level_shift: 0,
+            moved: None,
+            undone: false,
+        }
+    }
+
+    /// Also change the level of every heading in the section by `shift`
+    pub fn with_level_shift(mut self, shift: i8) -> Self {
+        self.level_shift = shift;
+        self
+    }
+}
+
+impl Command for MoveSectionCommand {
+    fn affected_range(&self) -> Option<Range<usize>> {
+        let (range, insert_at) = self.moved.as_ref()?;
+        Some(range.start.min(*insert_at)..range.end.max(insert_at + range.len()))
+    }
+
+    fn node_order(&self) -> Option<Vec<usize>> {
+        let (section, insert_at) = self.moved.as_ref()?;
+        let range = self.affected_range()?;
+        let mut order: Vec<usize> = range.clone().filter(|i| !section.contains(i)).collect();
+        order.splice(
+            insert_at - range.start..insert_at - range.start,
+            section.clone(),
+        );
+        Some(match self.undone {
+            true => inverse_order(range.start, &order),
+            false => order,
+        })
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+
+        let range = document
+            .section_range(self.heading_index)
+            .ok_or(EditError::invalid_node().at_node(self.heading_index))?;
+        if self.to_position > document.nodes.len() {
+            return Err(EditError::index_out_of_bounds().at_node(self.heading_index));
+        }
+        if self.to_position > range.start && self.to_position < range.end {
+            return Err(EditError::invalid_range().at_node(self.heading_index));
+        }
+        let out_of_range = document.nodes[range.clone()].iter().any(|node| {
+            matches!(node, Node::Heading { level, .. }
+                if !(1..=6).contains(&(*level as i8 + self.level_shift)))
+        });
+        if out_of_range {
+            return Err(EditError::unsupported()
+                .at_node(self.heading_index)
+                .with_message("Heading levels must stay between 1 and 6"));
+        }
+
+        let mut section: Vec<_> = document.nodes.drain(range.clone()).collect();
+        shift_levels(&mut section, self.level_shift);
+        let insert_at = if self.to_position >= range.end {
+            self.to_position - section.len()
+        } else {
+            self.to_position
+        };
+        document.nodes.splice(insert_at..insert_at, section);
+
+        self.moved = Some((range, insert_at));
+        self.undone = false;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let (range, insert_at) = self
+            .moved
+            .clone()
+            .filter(|_| !self.undone)
+            .ok_or(EditError::operation_failed().at_node(self.heading_index))?;
+        let mut document = self.document.borrow_mut();
+
+        let end = insert_at + range.len();
+        if end > document.nodes.len() {
+            return Err(EditError::index_out_of_bounds().at_node(self.heading_index));
+        }
+        let mut section: Vec<_> = document.nodes.drain(insert_at..end).collect();
+        shift_levels(&mut section, -self.level_shift);
+        document.nodes.splice(range.start..range.start, section);
+        self.undone = true;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(document: &Rc<RefCell<Document>>) -> Vec<String> {
+        document
+            .borrow()
+            .nodes
+            .iter()
+            .map(|node| node.plain_text())
+            .collect()
+    }
+
+    #[test]
+    fn test_move_section_down_and_undo() {
+        let mut doc = Document::new();
+        doc.add_heading(2, "A");
+        doc.add_paragraph_with_text("a");
+        doc.add_heading(3, "A.1");
+        doc.add_heading(2, "B");
+        doc.add_paragraph_with_text("b");
+        let document = Rc::new(RefCell::new(doc));
+
+        let mut cmd = MoveSectionCommand::new(document.clone(), 0, 5);
+        cmd.execute().unwrap();
+        assert_eq!(titles(&document), ["B", "b", "A", "a", "A.1"]);
+
+        cmd.undo().unwrap();
+        assert_eq!(titles(&document), ["A", "a", "A.1", "B", "b"]);
+
+        // Moving into its own section is rejected
+        let mut cmd = MoveSectionCommand::new(document.clone(), 0, 2);
+        assert!(cmd.execute().is_err());
+        let mut cmd = MoveSectionCommand::new(document.clone(), 1, 0);
+        assert!(cmd.execute().is_err());
+    }
+}
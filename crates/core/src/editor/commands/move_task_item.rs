@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::error::EditError;
 use crate::models::{Document, ListItem, ListType, Node};
 use std::any::Any;
@@ -96,23 +96,29 @@ impl MoveTaskPositionCommand {
 }
 
 impl Command for MoveTaskItemCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.previous_items)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_idx >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
         }
 
         match &mut document.nodes[self.node_idx] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Verify that it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::Other("Node is not a task list".into()));
+                    return Err(EditError::other("Node is not a task list").at_node(self.node_idx));
                 }
 
                 // Check if the item index is valid
                 if self.item_idx >= items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
                 }
 
                 // Store previous items for undo
@@ -122,13 +128,15 @@ impl Command for MoveTaskItemCommand {
                 let new_idx = if self.move_down {
                     // Moving down
                     if self.item_idx + 1 >= items.len() {
-                        return Err(EditError::Other("Already at the bottom".into()));
+                        return Err(
+                            EditError::other("Already at the bottom").at_node(self.node_idx)
+                        );
                     }
                     self.item_idx + 1
                 } else {
                     // Moving up
                     if self.item_idx == 0 {
-                        return Err(EditError::Other("Already at the top".into()));
+                        return Err(EditError::other("Already at the top").at_node(self.node_idx));
                     }
                     self.item_idx - 1
                 };
@@ -138,7 +146,7 @@ impl Command for MoveTaskItemCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::Other("Node is not a list".into())),
+            _ => Err(EditError::other("Node is not a list").at_node(self.node_idx)),
         }
     }
 
@@ -147,7 +155,7 @@ impl Command for MoveTaskItemCommand {
             let mut document = self.document.borrow_mut();
 
             if self.node_idx >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
             }
 
             match &mut document.nodes[self.node_idx] {
@@ -156,12 +164,10 @@ impl Command for MoveTaskItemCommand {
                     *items = previous_items.clone();
                     Ok(())
                 }
-                _ => Err(EditError::Other("Node is not a list".into())),
+                _ => Err(EditError::other("Node is not a list").at_node(self.node_idx)),
             }
         } else {
-            Err(EditError::Other(
-                "Previous state not available for undo".into(),
-            ))
+            Err(EditError::other("Previous state not available for undo").at_node(self.node_idx))
         }
     }
 
@@ -171,23 +177,29 @@ impl Command for MoveTaskItemCommand {
 }
 
 impl Command for MoveTaskPositionCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.previous_items)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_idx >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
         }
 
         match &mut document.nodes[self.node_idx] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Verify that it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::Other("Node is not a task list".into()));
+                    return Err(EditError::other("Node is not a task list").at_node(self.node_idx));
                 }
 
                 // Check if indices are valid
                 if self.from_idx >= items.len() || self.to_idx >= items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
                 }
 
                 // No-op if from and to are the same
@@ -206,7 +218,7 @@ impl Command for MoveTaskPositionCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::Other("Node is not a list".into())),
+            _ => Err(EditError::other("Node is not a list").at_node(self.node_idx)),
         }
     }
 
@@ -215,7 +227,7 @@ impl Command for MoveTaskPositionCommand {
             let mut document = self.document.borrow_mut();
 
             if self.node_idx >= document.nodes.len() {
-                return Err(EditError::IndexOutOfBounds);
+                return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
             }
 
             match &mut document.nodes[self.node_idx] {
@@ -224,12 +236,10 @@ impl Command for MoveTaskPositionCommand {
                     *items = previous_items.clone();
                     Ok(())
                 }
-                _ => Err(EditError::Other("Node is not a list".into())),
+                _ => Err(EditError::other("Node is not a list").at_node(self.node_idx)),
             }
         } else {
-            Err(EditError::Other(
-                "Previous state not available for undo".into(),
-            ))
+            Err(EditError::other("Previous state not available for undo").at_node(self.node_idx))
         }
     }
 
@@ -256,6 +266,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -310,6 +322,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -364,6 +378,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -417,6 +433,8 @@ mod tests {
         doc.nodes.push(Node::List {
             list_type: ListType::Task,
             items: items.clone(),
+            start: None,
+            loose: false,
         });
 
         let doc_rc = Rc::new(RefCell::new(doc));
@@ -425,8 +443,8 @@ mod tests {
         let mut cmd_up = MoveTaskItemCommand::move_up(doc_rc.clone(), 0, 0);
         let result = cmd_up.execute();
         assert!(result.is_err());
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Already at the top");
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Already at the top"));
         } else {
             panic!("Expected EditError::Other");
         }
@@ -435,8 +453,8 @@ mod tests {
         let mut cmd_down = MoveTaskItemCommand::move_down(doc_rc.clone(), 0, 1);
         let result = cmd_down.execute();
         assert!(result.is_err());
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Already at the bottom");
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Already at the bottom"));
         } else {
             panic!("Expected EditError::Other");
         }
@@ -0,0 +1,346 @@
+use crate::editor::command::{Command, DeleteTextCommand, estimate_bytes};
+use crate::{Document, EditError, Node, Position, Selection, TextFormatting};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// What a [`MultiSelectionCommand`] does at every selection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionEdit {
+    /// Replace the selected text, or insert at a cursor
+    Insert(String),
+    /// Delete the selected text; cursors are left alone
+    Delete,
+    /// Format the selected text
+    Format(TextFormatting),
+}
+
+/// A selection within one node, as a byte range
+struct SelectedRange {
+    node_index: usize,
+    range: Range<usize>,
+    primary: bool,
+}
+
+/// Command to edit the text at the primary and all further selections of
+/// the document in one step
+///
+/// Every selection must lie within one paragraph, heading or code block.
+/// Overlapping selections are merged first. Edits are applied from the end
+/// of the document backwards, so each selection's offsets stay valid, and
+/// the selections are moved to where their edit left them.
+pub struct MultiSelectionCommand {
+    document: Rc<RefCell<Document>>,
+    edit: SelectionEdit,
+    /// Nodes the command changed, before it ran
+    original_nodes: Vec<(usize, Node)>,
+    /// Primary selection before the command ran
+    original_selection: Option<Selection>,
+    /// Further selections before the command ran
+    original_selections: Vec<Selection>,
+}
+
+impl MultiSelectionCommand {
+    pub fn new(document: Rc<RefCell<Document>>, edit: SelectionEdit) -> Self {
+        Self {
+            document,
+            edit,
+            original_nodes: Vec::new(),
+            original_selection: None,
+            original_selections: Vec::new(),
+        }
+    }
+
+    /// Applies the edit to one range
+    fn apply(&self, selected: &SelectedRange) -> Result<(), EditError> {
+        let SelectedRange {
+            node_index, range, ..
+        } = selected;
+        match &self.edit {
+            SelectionEdit::Insert(text) => {
+                if !range.is_empty() {
+                    DeleteTextCommand::new(
+                        self.document.clone(),
+                        *node_index,
+                        range.start,
+                        range.end,
+                    )
+                    .execute()?;
+                }
+                if !text.is_empty() {
+                    super::InsertTextCommand::new(
+                        self.document.clone(),
+                        *node_index,
+                        range.start,
+                        text.clone(),
+                    )
+                    .execute()?;
+                }
+                Ok(())
+            }
+            SelectionEdit::Delete if !range.is_empty() => {
+                DeleteTextCommand::new(self.document.clone(), *node_index, range.start, range.end)
+                    .execute()
+            }
+            SelectionEdit::Format(formatting) if !range.is_empty() => {
+                self.document.borrow_mut().selection = Some(Selection::new(
+                    Position::new(vec![*node_index], range.start),
+                    Position::new(vec![*node_index], range.end),
+                ));
+                super::SelectionFormatCommand::new(self.document.clone(), formatting.clone())
+                    .execute()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The selection a range ends up as, `shift` being the change in length
+    /// of the edits before it in the same node
+    fn moved(&self, selected: &SelectedRange, shift: isize) -> Selection {
+        let position = |offset: usize| {
+            Position::new(
+                vec![selected.node_index],
+                offset.saturating_add_signed(shift),
+            )
+        };
+        match &self.edit {
+            SelectionEdit::Insert(text) => {
+                Selection::collapsed(position(selected.range.start + text.len()))
+            }
+            SelectionEdit::Delete => Selection::collapsed(position(selected.range.start)),
+            SelectionEdit::Format(_) => {
+                Selection::new(position(selected.range.start), position(selected.range.end))
+            }
+        }
+    }
+
+    /// Change in length of the text a range's edit makes
+    fn length_change(&self, selected: &SelectedRange) -> isize {
+        let removed = selected.range.len() as isize;
+        match &self.edit {
+            SelectionEdit::Insert(text) => text.len() as isize - removed,
+            SelectionEdit::Delete => -removed,
+            SelectionEdit::Format(_) => 0,
+        }
+    }
+}
+
+/// Turns selections into sorted byte ranges, merging overlapping ones and
+/// cursors at the same place
+///
+/// The first selection is the primary one.
+fn selected_ranges(selections: &[Selection]) -> Result<Vec<SelectedRange>, EditError> {
+    let mut ranges = Vec::new();
+    for (i, selection) in selections.iter().enumerate() {
+        let (start, end) = (&selection.start, &selection.end);
+        if start.path.len() != 1 || start.path != end.path {
+            return Err(EditError::unsupported()
+                .at_path(start.path.clone())
+                .with_message("Selection must lie within one top-level node"));
+        }
+        ranges.push(SelectedRange {
+            node_index: start.path[0],
+            range: start.offset.min(end.offset)..start.offset.max(end.offset),
+            primary: i == 0,
+        });
+    }
+    ranges.sort_by_key(|selected| (selected.node_index, selected.range.start));
+
+    let mut merged: Vec<SelectedRange> = Vec::new();
+    for selected in ranges {
+        match merged.last_mut() {
+            Some(last)
+                if last.node_index == selected.node_index
+                    && (selected.range.start < last.range.end
+                        || selected.range.start == last.range.start) =>
+            {
+                last.range.end = last.range.end.max(selected.range.end);
+                last.primary |= selected.primary;
+            }
+            _ => merged.push(selected),
+        }
+    }
+    Ok(merged)
+}
+
+impl Command for MultiSelectionCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + estimate_bytes(&self.original_nodes)
+            + estimate_bytes(&self.original_selections)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let document = self.document.borrow();
+        let selection = document.selection.clone();
+        let selections = document.selections.clone();
+        let ranges = selected_ranges(&document.all_selections())?;
+        if ranges.is_empty() {
+            return Err(EditError::operation_failed().with_message("Nothing is selected"));
+        }
+        let mut indices: Vec<usize> = ranges.iter().map(|selected| selected.node_index).collect();
+        indices.dedup();
+        let nodes = indices
+            .into_iter()
+            .map(|index| {
+                let node = document.nodes.get(index).cloned();
+                node.map(|node| (index, node))
+                    .ok_or_else(|| EditError::index_out_of_bounds().at_node(index))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(document);
+
+        let result = ranges
+            .iter()
+            .rev()
+            .try_for_each(|selected| self.apply(selected));
+        let mut document = self.document.borrow_mut();
+        if let Err(err) = result {
+            for (index, node) in nodes {
+                document.nodes[index] = node;
+            }
+            document.selection = selection;
+            return Err(err);
+        }
+
+        let mut moved = Vec::new();
+        let mut shift = 0;
+        for (i, selected) in ranges.iter().enumerate() {
+            if i > 0 && ranges[i - 1].node_index != selected.node_index {
+                shift = 0;
+            }
+            moved.push((selected.primary, self.moved(selected, shift)));
+            shift += self.length_change(selected);
+        }
+        let primary = moved.iter().position(|(primary, _)| *primary).unwrap_or(0);
+        document.selection = Some(moved.remove(primary).1);
+        document.selections = moved.into_iter().map(|(_, selection)| selection).collect();
+
+        self.original_nodes = nodes;
+        self.original_selection = selection;
+        self.original_selections = selections;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        if self.original_nodes.is_empty() {
+            return Err(EditError::operation_failed().with_message("Nothing to undo"));
+        }
+        let mut document = self.document.borrow_mut();
+        for (index, node) in std::mem::take(&mut self.original_nodes) {
+            document.nodes[index] = node;
+        }
+        document.selection = self.original_selection.take();
+        document.selections = std::mem::take(&mut self.original_selections);
+        Ok(())
+    }
+
+    fn affected_range(&self) -> Option<Range<usize>> {
+        let first = self.original_nodes.first()?.0;
+        let last = self.original_nodes.last()?.0;
+        Some(first..last + 1)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(node_index: usize, offset: usize) -> Selection {
+        Selection::collapsed(Position::new(vec![node_index], offset))
+    }
+
+    #[test]
+    fn test_insert_at_every_cursor() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("let a = 1;");
+        doc.add_paragraph_with_text("let b = 2;");
+        doc.selection = Some(cursor(1, 3));
+        doc.selections = vec![cursor(0, 3), cursor(0, 10), cursor(1, 3)];
+        let document = Rc::new(RefCell::new(doc));
+
+        let edit = SelectionEdit::Insert(" mut".to_string());
+        let mut cmd = MultiSelectionCommand::new(document.clone(), edit);
+        cmd.execute().unwrap();
+        {
+            let doc = document.borrow();
+            assert_eq!(doc.nodes[0].plain_text(), "let mut a = 1; mut");
+            assert_eq!(doc.nodes[1].plain_text(), "let mut b = 2;");
+            assert_eq!(doc.selection, Some(cursor(1, 7)));
+            assert_eq!(doc.selections, [cursor(0, 7), cursor(0, 18)]);
+        }
+
+        cmd.undo().unwrap();
+        let doc = document.borrow();
+        assert_eq!(doc.nodes[0].plain_text(), "let a = 1;");
+        assert_eq!(doc.selections.len(), 3);
+    }
+
+    #[test]
+    fn test_delete_and_format_selections() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("one two three");
+        doc.selection = Some(Selection::new(
+            Position::new(vec![0], 4),
+            Position::new(vec![0], 8),
+        ));
+        doc.selections = vec![Selection::new(
+            Position::new(vec![0], 13),
+            Position::new(vec![0], 8),
+        )];
+        let document = Rc::new(RefCell::new(doc));
+
+        let bold = TextFormatting {
+            bold: true,
+            ..Default::default()
+        };
+        let mut cmd = MultiSelectionCommand::new(document.clone(), SelectionEdit::Format(bold));
+        cmd.execute().unwrap();
+        {
+            let doc = document.borrow();
+            let Node::Paragraph { children } = &doc.nodes[0] else {
+                panic!("Expected paragraph");
+            };
+            let bold: Vec<(String, bool)> = children
+                .iter()
+                .filter_map(|child| match child {
+                    crate::InlineNode::Text(text) => {
+                        Some((text.text.clone(), text.formatting.bold))
+                    }
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(bold[0], ("one ".to_string(), false));
+            assert!(bold[1..].iter().all(|(_, bold)| *bold));
+            assert_eq!(doc.nodes[0].plain_text(), "one two three");
+        }
+
+        let mut cmd = MultiSelectionCommand::new(document.clone(), SelectionEdit::Delete);
+        cmd.execute().unwrap();
+        let doc = document.borrow();
+        assert_eq!(doc.nodes[0].plain_text(), "one ");
+        assert_eq!(doc.selection, Some(cursor(0, 4)));
+        assert_eq!(doc.selections, [cursor(0, 4)]);
+    }
+
+    #[test]
+    fn test_selection_across_nodes_is_rejected() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("One");
+        doc.add_paragraph_with_text("Two");
+        doc.selection = Some(Selection::new(
+            Position::new(vec![0], 1),
+            Position::new(vec![1], 1),
+        ));
+        let document = Rc::new(RefCell::new(doc));
+
+        let mut cmd = MultiSelectionCommand::new(document.clone(), SelectionEdit::Delete);
+        assert!(cmd.execute().is_err());
+        assert_eq!(document.borrow().nodes[0].plain_text(), "One");
+    }
+}
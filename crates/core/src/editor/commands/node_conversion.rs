@@ -1,5 +1,7 @@
-use crate::editor::command::Command;
-use crate::{Document, EditError, InlineNode, ListType, Node, NodeConversionType};
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{
+    Document, EditError, HeadingProperties, InlineNode, ListType, Node, NodeConversionType,
+};
 use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -28,11 +30,15 @@ impl ConvertNodeTypeCommand {
 }
 
 impl Command for ConvertNodeTypeCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_node)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Store the original node for undo
@@ -70,7 +76,8 @@ impl Command for ConvertNodeTypeCommand {
             _ => None, // Other node types not supported for now
         };
 
-        let inline_content = inline_content.ok_or(EditError::UnsupportedOperation)?;
+        let inline_content =
+            inline_content.ok_or(EditError::unsupported().at_node(self.node_index))?;
 
         // Create the new node with the extracted content
         let new_node = match &self.target_type {
@@ -80,6 +87,7 @@ impl Command for ConvertNodeTypeCommand {
             NodeConversionType::Heading(level) => Node::Heading {
                 level: *level,
                 children: inline_content,
+                properties: HeadingProperties::default(),
             },
             NodeConversionType::List(list_type) => {
                 // For conversion to list, create a single item
@@ -115,7 +123,7 @@ impl Command for ConvertNodeTypeCommand {
         let mut document = self.document.borrow_mut();
 
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         if let Some(original) = &self.original_node {
@@ -123,7 +131,7 @@ impl Command for ConvertNodeTypeCommand {
             document.nodes[self.node_index] = original.clone();
             Ok(())
         } else {
-            Err(EditError::OperationFailed)
+            Err(EditError::operation_failed().at_node(self.node_index))
         }
     }
 
@@ -185,6 +193,26 @@ fn extract_text_from_inline_nodes(nodes: &[InlineNode]) -> String {
                 // For mentions, use the name
                 result.push_str(name);
             }
+            InlineNode::Tag { name } => {
+                // For tags, keep the hash
+                result.push('#');
+                result.push_str(name);
+            }
+            wiki_link @ InlineNode::WikiLink { .. } => {
+                // For wiki-links, use the text they show
+                result.push_str(&wiki_link.plain_text());
+            }
+            citation @ InlineNode::Citation { .. } => {
+                // For citations, keep their syntax
+                result.push_str(&citation.plain_text());
+            }
+            InlineNode::Html { .. } | InlineNode::HtmlComment { .. } => {
+                // Raw HTML tags and comments carry no text of their own
+            }
+            InlineNode::Custom { data, .. } => {
+                // For custom directives, use their content
+                result.push_str(data);
+            }
         }
     }
 
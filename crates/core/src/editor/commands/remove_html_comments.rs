@@ -0,0 +1,85 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::html_comments::remove_html_comments;
+use crate::{Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to remove HTML comments, addressed by their index from
+/// [`Document::html_comments`]
+pub struct RemoveHtmlCommentsCommand {
+    document: Rc<RefCell<Document>>,
+    indices: Vec<usize>,
+    /// Original nodes for undo
+    original_nodes: Option<Vec<Node>>,
+}
+
+impl RemoveHtmlCommentsCommand {
+    /// Create a command removing the comments at `indices`
+    pub fn new(document: Rc<RefCell<Document>>, indices: Vec<usize>) -> Self {
+        Self {
+            document,
+            indices,
+            original_nodes: None,
+        }
+    }
+}
+
+impl Command for RemoveHtmlCommentsCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.indices) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let count = document.html_comments().len();
+        if self.indices.iter().any(|index| *index >= count) {
+            return Err(EditError::index_out_of_bounds());
+        }
+
+        self.original_nodes = Some(document.nodes.clone());
+        remove_html_comments(&mut document.nodes, &mut 0, &self.indices);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        if let Some(original_nodes) = self.original_nodes.take() {
+            self.document.borrow_mut().nodes = original_nodes;
+            Ok(())
+        } else {
+            Err(EditError::other("No original state to restore"))
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InlineNode;
+
+    #[test]
+    fn test_remove_html_comments_and_undo() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::html_comment(" a "));
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::text("Text"),
+            InlineNode::html_comment("b"),
+        ]);
+        let document = Rc::new(RefCell::new(doc));
+
+        let mut cmd = RemoveHtmlCommentsCommand::new(document.clone(), vec![2]);
+        assert!(cmd.execute().is_err());
+
+        let mut cmd = RemoveHtmlCommentsCommand::new(document.clone(), vec![0, 1]);
+        cmd.execute().unwrap();
+        assert!(document.borrow().html_comments().is_empty());
+        assert_eq!(document.borrow().nodes.len(), 1);
+
+        cmd.undo().unwrap();
+        assert_eq!(document.borrow().html_comments().len(), 2);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, EditError, ListItem, ListType, Node};
 use std::any::Any;
 use std::cell::RefCell;
@@ -25,30 +25,36 @@ impl RemoveTaskItemCommand {
 }
 
 impl Command for RemoveTaskItemCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.removed_item)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Get the node
         match &mut document.nodes[self.node_index] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Check if it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Check if item_index is valid
                 if self.item_index >= items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                 }
 
                 // Prevent removing the last item (would leave an empty list)
                 if items.len() == 1 {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Remove the item and store it for undo
@@ -56,7 +62,7 @@ impl Command for RemoveTaskItemCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -65,15 +71,17 @@ impl Command for RemoveTaskItemCommand {
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Get the node
         match &mut document.nodes[self.node_index] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Check if it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Ensure we have a stored item to put back
@@ -84,13 +92,13 @@ impl Command for RemoveTaskItemCommand {
                         Ok(())
                     } else {
                         self.removed_item = Some(item); // Put it back for future undo attempts
-                        Err(EditError::IndexOutOfBounds)
+                        Err(EditError::index_out_of_bounds().at_node(self.node_index))
                     }
                 } else {
-                    Err(EditError::OperationFailed)
+                    Err(EditError::operation_failed().at_node(self.node_index))
                 }
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -102,6 +110,7 @@ impl Command for RemoveTaskItemCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::EditErrorKind;
 
     #[test]
     fn test_remove_task_item() {
@@ -162,10 +171,10 @@ mod tests {
 
         // Try to remove the only item
         let mut cmd = RemoveTaskItemCommand::new(doc_rc.clone(), 0, 0);
-        assert!(matches!(
-            cmd.execute(),
-            Err(EditError::UnsupportedOperation)
-        ));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::UnsupportedOperation
+        );
     }
 
     #[test]
@@ -181,10 +190,10 @@ mod tests {
 
         // Try to remove from a paragraph
         let mut cmd = RemoveTaskItemCommand::new(doc_rc.clone(), 0, 0);
-        assert!(matches!(
-            cmd.execute(),
-            Err(EditError::UnsupportedOperation)
-        ));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::UnsupportedOperation
+        );
     }
 
     #[test]
@@ -200,6 +209,9 @@ mod tests {
 
         // Try to remove with an invalid index
         let mut cmd = RemoveTaskItemCommand::new(doc_rc.clone(), 0, 2);
-        assert!(matches!(cmd.execute(), Err(EditError::IndexOutOfBounds)));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
     }
 }
@@ -0,0 +1,117 @@
+use crate::code_blocks::{CodeBlockRename, info_language, rename_in_code};
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to rename an identifier in the code blocks of a document
+///
+/// Prose is never touched. Blocks nested in quotes, lists and other
+/// containers are included.
+pub struct RenameSymbolCommand {
+    document: Rc<RefCell<Document>>,
+    old: String,
+    new: String,
+    language: Option<String>,
+    word_boundary: bool,
+    original_nodes: Vec<(usize, Node)>,
+    report: Vec<CodeBlockRename>,
+}
+
+impl RenameSymbolCommand {
+    /// Create a command renaming `old` to `new`
+    ///
+    /// With a language, only blocks whose info string starts with it are
+    /// changed.
+    pub fn new(
+        document: Rc<RefCell<Document>>,
+        old: &str,
+        new: &str,
+        language: Option<&str>,
+        word_boundary: bool,
+    ) -> Self {
+        Self {
+            document,
+            old: old.to_string(),
+            new: new.to_string(),
+            language: language.map(str::to_string),
+            word_boundary,
+            original_nodes: Vec::new(),
+            report: Vec::new(),
+        }
+    }
+
+    /// Replacements made by the last execution, one entry per changed block
+    pub fn report(&self) -> &[CodeBlockRename] {
+        &self.report
+    }
+}
+
+impl Command for RenameSymbolCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + self.old.len()
+            + self.new.len()
+            + estimate_bytes(&self.original_nodes)
+            + estimate_bytes(&self.report)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        if self.old.is_empty() {
+            return Err(EditError::other("Symbol to rename must not be empty"));
+        }
+
+        let mut document = self.document.borrow_mut();
+        let mut original_nodes = Vec::new();
+        let mut report = Vec::new();
+        for (node_index, node) in document.nodes.iter_mut().enumerate() {
+            let original = node.clone();
+            node.walk_mut(&mut |block| {
+                if let Node::CodeBlock { language, code, .. } = block {
+                    let language = info_language(language);
+                    if self
+                        .language
+                        .as_ref()
+                        .is_some_and(|filter| !filter.eq_ignore_ascii_case(language))
+                    {
+                        return;
+                    }
+                    let (renamed, replacements) =
+                        rename_in_code(code, language, &self.old, &self.new, self.word_boundary);
+                    if replacements > 0 {
+                        report.push(CodeBlockRename {
+                            node_index,
+                            language: language.to_string(),
+                            replacements,
+                        });
+                        *code = renamed;
+                    }
+                }
+            });
+            if original != *node {
+                original_nodes.push((node_index, original));
+            }
+        }
+
+        self.original_nodes = original_nodes;
+        self.report = report;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        for (i, node) in self.original_nodes.drain(..) {
+            let slot = document
+                .nodes
+                .get_mut(i)
+                .ok_or(EditError::index_out_of_bounds())?;
+            *slot = node;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
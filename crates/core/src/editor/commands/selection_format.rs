@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, EditError, InlineNode, Node, Selection, TextFormatting, TextNode};
 use std::any::Any;
 use std::cell::RefCell;
@@ -26,6 +26,10 @@ impl SelectionFormatCommand {
 }
 
 impl Command for SelectionFormatCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
@@ -47,14 +51,17 @@ impl Command for SelectionFormatCommand {
         // Handle multi-node selection
         if selection.start.path[0] != selection.end.path[0] {
             // Currently only support formatting within a single node
+            let node_idx = selection.start.path[0];
             document.selection = Some(selection);
-            return Err(EditError::UnsupportedOperation);
+            return Err(EditError::unsupported()
+                .at_node(node_idx)
+                .with_message("selection spans several nodes"));
         }
 
         // Get node index from selection
         let node_idx = selection.start.path[0];
         if node_idx >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(node_idx));
         }
 
         // Store the original node for undo
@@ -164,7 +171,7 @@ impl Command for SelectionFormatCommand {
             // Code blocks don't support rich text formatting
             _ => {
                 document.selection = Some(selection);
-                return Err(EditError::UnsupportedOperation);
+                return Err(EditError::unsupported());
             }
         }
 
@@ -1,4 +1,4 @@
-use crate::editor::command::Command;
+use crate::editor::command::{Command, estimate_bytes};
 use crate::{Document, EditError, ListType, Node, Selection};
 use std::any::Any;
 use std::cell::RefCell;
@@ -44,6 +44,10 @@ impl SelectionIndentCommand {
 }
 
 impl Command for SelectionIndentCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_nodes)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
@@ -130,8 +134,9 @@ fn handle_increase_indent(
         let current_is_list = matches!(&document.nodes[idx], Node::List { .. });
         let prev_is_list = matches!(&document.nodes[idx - 1], Node::List { .. });
 
-        if current_is_list && prev_is_list {
-            if let (
+        if current_is_list
+            && prev_is_list
+            && let (
                 Node::List {
                     list_type: prev_type,
                     ..
@@ -141,12 +146,10 @@ fn handle_increase_indent(
                     ..
                 },
             ) = (&document.nodes[idx - 1], &document.nodes[idx])
-            {
-                if SelectionIndentCommand::list_types_compatible(prev_type, curr_type) {
-                    // Mark this node for merging
-                    nodes_to_remove.push(idx);
-                }
-            }
+            && SelectionIndentCommand::list_types_compatible(prev_type, curr_type)
+        {
+            // Mark this node for merging
+            nodes_to_remove.push(idx);
         }
     }
 
@@ -172,6 +175,8 @@ fn handle_increase_indent(
         document.nodes[idx] = Node::List {
             list_type: ListType::Unordered,
             items: Vec::new(),
+            start: None,
+            loose: false,
         };
     }
 
@@ -229,15 +234,14 @@ fn handle_decrease_indent(
         match &mut document.nodes[idx] {
             Node::BlockQuote { children } => {
                 // If first child is a blockquote, unwrap it
-                if !children.is_empty() {
-                    if let Some(Node::BlockQuote {
+                if !children.is_empty()
+                    && let Some(Node::BlockQuote {
                         children: inner_children,
                     }) = children.first()
-                    {
-                        // Clone to avoid borrow issues
-                        let inner = inner_children.clone();
-                        *children = inner;
-                    }
+                {
+                    // Clone to avoid borrow issues
+                    let inner = inner_children.clone();
+                    *children = inner;
                 }
             }
             Node::CodeBlock { code, .. } => {
@@ -0,0 +1,186 @@
+use crate::code_blocks::info_language;
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{CodeBlockProperties, Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Which code blocks the command changes
+enum Target {
+    /// The top-level code block at the index, given new properties
+    Node(usize),
+    /// Every code block in the language, wherever it is nested
+    Language(String),
+}
+
+/// Command to change the rendering properties of code blocks
+pub struct SetCodeBlockPropertiesCommand {
+    document: Rc<RefCell<Document>>,
+    target: Target,
+    properties: CodeBlockProperties,
+    /// Original top-level nodes that changed, by index, for undo
+    original_nodes: Vec<(usize, Node)>,
+    changed: usize,
+}
+
+impl SetCodeBlockPropertiesCommand {
+    /// Create a command replacing the properties of the code block at
+    /// `node_index`
+    pub fn new(
+        document: Rc<RefCell<Document>>,
+        node_index: usize,
+        properties: CodeBlockProperties,
+    ) -> Self {
+        Self {
+            document,
+            target: Target::Node(node_index),
+            properties,
+            original_nodes: Vec::new(),
+            changed: 0,
+        }
+    }
+
+    /// Create a command applying `template` to every code block whose info
+    /// string starts with `language`, ignoring case
+    ///
+    /// The template sets the presentation shared by the blocks: line
+    /// numbers, theme, copy button, CSS class and style, and maximum
+    /// height. The start line and highlighted lines belong to each block's
+    /// code and are kept.
+    pub fn for_language(
+        document: Rc<RefCell<Document>>,
+        language: &str,
+        template: CodeBlockProperties,
+    ) -> Self {
+        Self {
+            document,
+            target: Target::Language(language.to_string()),
+            properties: template,
+            original_nodes: Vec::new(),
+            changed: 0,
+        }
+    }
+
+    /// Number of code blocks the last execution changed
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+}
+
+/// Copies the presentation fields of `template` onto `properties`
+fn apply_template(properties: &mut CodeBlockProperties, template: &CodeBlockProperties) {
+    *properties = CodeBlockProperties {
+        start_line: properties.start_line,
+        highlight_lines: properties.highlight_lines.take(),
+        execution_count: properties.execution_count,
+        outputs: std::mem::take(&mut properties.outputs),
+        ..template.clone()
+    };
+}
+
+impl Command for SetCodeBlockPropertiesCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.properties) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn affected_range(&self) -> Option<Range<usize>> {
+        let first = self.original_nodes.first()?.0;
+        let last = self.original_nodes.last()?.0;
+        Some(first..last + 1)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let mut original_nodes = Vec::new();
+        let mut changed = 0;
+        match &self.target {
+            Target::Node(node_index) => {
+                let node = document
+                    .nodes
+                    .get_mut(*node_index)
+                    .ok_or(EditError::index_out_of_bounds().at_node(*node_index))?;
+                let original = node.clone();
+                let Node::CodeBlock { properties, .. } = node else {
+                    return Err(EditError::invalid_node()
+                        .at_node(*node_index)
+                        .with_message("Node is not a code block"));
+                };
+                if *properties != self.properties {
+                    *properties = self.properties.clone();
+                    original_nodes.push((*node_index, original));
+                    changed = 1;
+                }
+            }
+            Target::Language(filter) => {
+                for (node_index, node) in document.nodes.iter_mut().enumerate() {
+                    let original = node.clone();
+                    node.walk_mut(&mut |block| {
+                        if let Node::CodeBlock {
+                            language,
+                            properties,
+                            ..
+                        } = block
+                        {
+                            if !filter.eq_ignore_ascii_case(info_language(language)) {
+                                return;
+                            }
+                            let before = properties.clone();
+                            apply_template(properties, &self.properties);
+                            changed += usize::from(*properties != before);
+                        }
+                    });
+                    if original != *node {
+                        original_nodes.push((node_index, original));
+                    }
+                }
+            }
+        }
+
+        self.original_nodes = original_nodes;
+        self.changed = changed;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        for (index, original) in self.original_nodes.drain(..) {
+            let node = document
+                .nodes
+                .get_mut(index)
+                .ok_or(EditError::index_out_of_bounds().at_node(index))?;
+            *node = original;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_template_keeps_block_lines() {
+        let mut properties = CodeBlockProperties::new()
+            .with_start_line(10)
+            .with_highlight_lines(vec![11])
+            .with_css_class("old");
+        let template = CodeBlockProperties::new()
+            .with_theme("github")
+            .with_line_numbers(true)
+            .with_start_line(1);
+        apply_template(&mut properties, &template);
+        assert_eq!(
+            properties,
+            CodeBlockProperties::new()
+                .with_theme("github")
+                .with_line_numbers(true)
+                .with_start_line(10)
+                .with_highlight_lines(vec![11])
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::link_titles::apply_link_titles;
+use crate::{Document, EditError, LinkTitle, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to give links the titles found for their pages
+///
+/// See [`Document::link_title_targets`] for the links that take a title.
+pub struct SetLinkTitlesCommand {
+    document: Rc<RefCell<Document>>,
+    titles: Vec<LinkTitle>,
+    original_nodes: Vec<(usize, Node)>,
+}
+
+impl SetLinkTitlesCommand {
+    /// Create a command applying the given titles
+    pub fn new(document: Rc<RefCell<Document>>, titles: Vec<LinkTitle>) -> Self {
+        Self {
+            document,
+            titles,
+            original_nodes: Vec::new(),
+        }
+    }
+}
+
+impl Command for SetLinkTitlesCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.titles) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let node_count = document.nodes.len();
+        if let Some(title) = self.titles.iter().find(|t| t.node_index >= node_count) {
+            return Err(EditError::index_out_of_bounds().at_node(title.node_index));
+        }
+
+        let mut indices: Vec<usize> = self.titles.iter().map(|t| t.node_index).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        let mut original_nodes = Vec::new();
+        for i in indices {
+            let original = document.nodes[i].clone();
+            if apply_link_titles(&mut document.nodes[i], i, &self.titles) > 0 {
+                original_nodes.push((i, original));
+            }
+        }
+
+        self.original_nodes = original_nodes;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        for (i, node) in self.original_nodes.drain(..) {
+            let slot = document
+                .nodes
+                .get_mut(i)
+                .ok_or(EditError::index_out_of_bounds())?;
+            *slot = node;
+        }
+        Ok(())
+    }
+
+    fn affected_range(&self) -> Option<std::ops::Range<usize>> {
+        let first = self.original_nodes.first()?.0;
+        let last = self.original_nodes.last()?.0;
+        Some(first..last + 1)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
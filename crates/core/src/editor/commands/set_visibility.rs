@@ -0,0 +1,131 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, Node, Position, Selection, Visibility};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to set the visibility of the top-level nodes a selection touches
+///
+/// The nodes are taken out of any visibility blocks and, unless they are
+/// made visible, wrapped in one new block.
+pub struct SetVisibilityCommand {
+    document: Rc<RefCell<Document>>,
+    visibility: Visibility,
+    /// Make the nodes visible instead if they all have `visibility` already
+    toggle: bool,
+    /// Index of the first node, the original nodes and how many replaced them
+    original: Option<(usize, Vec<Node>, usize)>,
+    original_selection: Option<Selection>,
+}
+
+impl SetVisibilityCommand {
+    pub fn new(document: Rc<RefCell<Document>>, visibility: Visibility, toggle: bool) -> Self {
+        Self {
+            document,
+            visibility,
+            toggle,
+            original: None,
+            original_selection: None,
+        }
+    }
+}
+
+/// Maps a position in the original nodes to the replacing ones
+///
+/// `offsets` holds, for each original node from `start`, the index its
+/// blocks start at among the unwrapped blocks.
+fn map_position(
+    position: &Position,
+    start: usize,
+    nodes: &[Node],
+    offsets: &[usize],
+    wrapped: bool,
+) -> Position {
+    let mut path = position.path.clone();
+    let index = path[0] - start;
+    let flat = match &nodes[index] {
+        Node::VisibilityBlock { .. } if path.len() > 1 => offsets[index] + path.remove(1),
+        _ => offsets[index],
+    };
+    if wrapped {
+        path[0] = start;
+        path.insert(1, flat);
+    } else {
+        path[0] = start + flat;
+    }
+    Position::new(path, position.offset)
+}
+
+impl Command for SetVisibilityCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original.as_ref().map(|(_, nodes, _)| nodes))
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let Some(selection) = document.selection.clone() else {
+            return Err(EditError::other("No selection to change the visibility of"));
+        };
+        let start = selection.start.path[0].min(selection.end.path[0]);
+        let end = selection.start.path[0].max(selection.end.path[0]);
+        if end >= document.nodes.len() {
+            return Err(EditError::index_out_of_bounds().at_node(end));
+        }
+
+        let nodes: Vec<Node> = document.nodes[start..=end].to_vec();
+        let already = nodes.iter().all(|node| {
+            matches!(node, Node::VisibilityBlock { visibility, .. } if *visibility == self.visibility)
+        });
+        let visibility = if self.toggle && already {
+            Visibility::Visible
+        } else {
+            self.visibility
+        };
+
+        let mut offsets = Vec::with_capacity(nodes.len());
+        let mut blocks = Vec::new();
+        for node in &nodes {
+            offsets.push(blocks.len());
+            match node {
+                Node::VisibilityBlock { children, .. } => blocks.extend(children.iter().cloned()),
+                _ => blocks.push(node.clone()),
+            }
+        }
+        let wrapped = visibility != Visibility::Visible;
+        let replacement = if wrapped {
+            vec![Node::VisibilityBlock {
+                visibility,
+                children: blocks,
+            }]
+        } else {
+            blocks
+        };
+
+        let map = |position: &Position| map_position(position, start, &nodes, &offsets, wrapped);
+        let mapped = Selection::new(map(&selection.start), map(&selection.end));
+        let replaced = replacement.len();
+        document.nodes.splice(start..=end, replacement);
+        document.selection = Some(mapped);
+
+        self.original = Some((start, nodes, replaced));
+        self.original_selection = Some(selection);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let Some((start, nodes, replaced)) = self.original.take() else {
+            return Err(EditError::other("No original state to restore"));
+        };
+        let mut document = self.document.borrow_mut();
+        if start + replaced > document.nodes.len() {
+            return Err(EditError::index_out_of_bounds().at_node(start + replaced));
+        }
+        document.nodes.splice(start..start + replaced, nodes);
+        document.selection = self.original_selection.take();
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
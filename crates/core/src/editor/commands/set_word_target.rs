@@ -0,0 +1,66 @@
+use crate::editor::command::Command;
+use crate::{Document, EditError, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to set or clear the word-count target on a section heading
+pub struct SetWordTargetCommand {
+    document: Rc<RefCell<Document>>,
+    node_index: usize,
+    target: Option<usize>,
+    previous_target: Option<usize>,
+}
+
+impl SetWordTargetCommand {
+    /// Create a new command to set the word target of the heading at `node_index`
+    pub fn new(document: Rc<RefCell<Document>>, node_index: usize, target: Option<usize>) -> Self {
+        Self {
+            document,
+            node_index,
+            target,
+            previous_target: None,
+        }
+    }
+}
+
+impl Command for SetWordTargetCommand {
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+
+        self.previous_target = match document.nodes.get(self.node_index) {
+            Some(Node::Heading { properties, .. }) => properties.word_target,
+            _ => None,
+        };
+        document.set_word_target(self.node_index, self.target)
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        document.set_word_target(self.node_index, self.previous_target)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_word_target_undo() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Chapter");
+        doc.set_word_target(0, Some(500)).unwrap();
+        let doc_rc = Rc::new(RefCell::new(doc));
+
+        let mut cmd = SetWordTargetCommand::new(doc_rc.clone(), 0, Some(1000));
+        cmd.execute().unwrap();
+        assert_eq!(doc_rc.borrow().stats().sections[0].word_target, Some(1000));
+
+        cmd.undo().unwrap();
+        assert_eq!(doc_rc.borrow().stats().sections[0].word_target, Some(500));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    editor::command::Command,
+    editor::command::{Command, estimate_bytes},
     error::EditError,
     models::{Document, ListItem, ListType, Node},
 };
@@ -42,10 +42,14 @@ impl SortTaskListCommand {
 }
 
 impl Command for SortTaskListCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.document) + estimate_bytes(&self.original_items)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         // Check if node_idx is valid
         if self.node_idx >= self.document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
         }
 
         // Check if the node is a task list
@@ -55,11 +59,11 @@ impl Command for SortTaskListCommand {
                 list_type, items, ..
             } => {
                 if *list_type != ListType::Task {
-                    return Err(EditError::Other("Node is not a task list".to_string()));
+                    return Err(EditError::other("Node is not a task list").at_node(self.node_idx));
                 }
                 items
             }
-            _ => return Err(EditError::Other("Node is not a list".to_string())),
+            _ => return Err(EditError::other("Node is not a list").at_node(self.node_idx)),
         };
 
         // Store the original items for undo
@@ -139,12 +143,12 @@ impl Command for SortTaskListCommand {
     fn undo(&mut self) -> Result<(), EditError> {
         // Check if node_idx is valid
         if self.node_idx >= self.document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_idx));
         }
 
         // Check if we have the original items
         if self.original_items.is_none() {
-            return Err(EditError::Other("No original items to restore".to_string()));
+            return Err(EditError::other("No original items to restore").at_node(self.node_idx));
         }
 
         // Clone the document for modification
@@ -155,7 +159,7 @@ impl Command for SortTaskListCommand {
             // Restore original items
             *items = self.original_items.clone().unwrap();
         } else {
-            return Err(EditError::Other("Node is not a list".to_string()));
+            return Err(EditError::other("Node is not a list").at_node(self.node_idx));
         }
 
         // Update the document
@@ -172,6 +176,7 @@ impl Command for SortTaskListCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::EditErrorKind;
 
     #[test]
     fn test_sort_task_list_alphabetically() {
@@ -267,7 +272,7 @@ mod tests {
 
         let result = command.execute();
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), EditError::IndexOutOfBounds));
+        assert_eq!(result.unwrap_err().kind(), EditErrorKind::IndexOutOfBounds);
     }
 
     #[test]
@@ -279,10 +284,7 @@ mod tests {
 
         let result = command.execute();
         assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            EditError::Other(msg) if msg == "Node is not a list"
-        ));
+        assert_eq!(result.unwrap_err().message(), Some("Node is not a list"));
     }
 
     #[test]
@@ -296,10 +298,10 @@ mod tests {
 
         let result = command.execute();
         assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            EditError::Other(msg) if msg == "Node is not a task list"
-        ));
+        assert_eq!(
+            result.unwrap_err().message(),
+            Some("Node is not a task list")
+        );
     }
 
     /// Helper function to create a document with a task list for testing
@@ -315,6 +317,8 @@ mod tests {
         document.nodes.push(Node::List {
             list_type: ListType::Task,
             items,
+            start: None,
+            loose: false,
         });
         document
     }
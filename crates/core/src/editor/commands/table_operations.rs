@@ -1,5 +1,8 @@
-use crate::editor::command::Command;
-use crate::{Document, EditError, Node, TableAlignment, TableCell, TableProperties};
+use crate::editor::command::{Command, estimate_bytes};
+use crate::models::{compare_cells, table_cell_columns};
+use crate::{
+    CellValue, Document, EditError, InlineNode, Node, TableAlignment, TableCell, TableProperties,
+};
 use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -8,6 +11,8 @@ use std::rc::Rc;
 pub enum TableOperation {
     /// Add a row at the specified index (0 is first row after header)
     AddRow(usize),
+    /// Add a row of empty cells at the specified index
+    AddEmptyRow(usize),
     /// Remove the row at the specified index
     RemoveRow(usize),
     /// Add a column at the specified index (0 is first column)
@@ -50,6 +55,58 @@ pub enum TableOperation {
     },
     /// Set table properties
     SetTableProperties(TableProperties),
+    /// Set the caption, shown above the table or below it, or remove it
+    /// with `None`
+    SetCaption {
+        text: Option<String>,
+        at_bottom: bool,
+    },
+    /// Set or clear the summary and accessible name of the table
+    SetAccessibility {
+        summary: Option<String>,
+        aria_label: Option<String>,
+    },
+    /// Merge a rectangular block of body cells into its top-left cell
+    ///
+    /// Rows are body row indices and columns are logical grid columns. The
+    /// covered cells are removed and their content is appended to the merged
+    /// cell. Fails if a spanned cell only partly overlaps the block.
+    MergeCells {
+        start_row: usize,
+        start_col: usize,
+        end_row: usize,
+        end_col: usize,
+    },
+    /// Split a merged body cell back into single cells
+    ///
+    /// `row` and `column` may point anywhere inside the merged cell. The
+    /// content stays in the top-left cell and the others are left empty.
+    SplitCell { row: usize, column: usize },
+    /// Swap rows and columns
+    ///
+    /// The header row becomes the first column and the first column becomes
+    /// the header row. The first column keeps its alignment; the others share
+    /// the original body columns' alignment when they all agree. Fails on
+    /// tables with merged cells.
+    Transpose,
+    /// Reflow the cells of a single-column table into a grid of `n` columns
+    ///
+    /// Body cells fill the grid row by row and the last row is padded with
+    /// empty cells. The header cell stays in the first column and every
+    /// column takes the original alignment.
+    ReshapeToColumns(usize),
+    /// Set or clear the typed value of a body cell, keeping its display text
+    SetCellValue {
+        row: usize,
+        column: usize,
+        value: Option<CellValue>,
+    },
+    /// Sort the body rows by a logical grid column
+    ///
+    /// Rows whose cell holds a typed value come first, ordered by value;
+    /// the rest follow in text order and rows lacking the column go last.
+    /// The sort is stable. Fails on tables with cells spanning rows.
+    SortByColumn { column: usize, descending: bool },
 }
 
 /// Command to perform operations on an existing table
@@ -80,17 +137,21 @@ impl TableOperationsCommand {
 }
 
 impl Command for TableOperationsCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.original_node)
+    }
+
     fn execute(&mut self) -> Result<(), EditError> {
         let mut document = self.document.borrow_mut();
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Check if the node is a table
         if !matches!(document.nodes[self.node_index], Node::Table { .. }) {
-            return Err(EditError::Other("Node is not a table".to_string()));
+            return Err(EditError::other("Node is not a table").at_node(self.node_index));
         }
 
         // Store original node for undo
@@ -106,68 +167,56 @@ impl Command for TableOperationsCommand {
             } => {
                 match &self.operation {
                     TableOperation::AddRow(index) => {
-                        let rows_len = rows.len();
-                        let row_index = index.min(&rows_len);
-                        let num_columns = alignments.len();
-                        let empty_row = (0..num_columns)
-                            .map(|i| {
-                                TableCell::text(format!("Row {}, Col {}", rows.len() + 1, i + 1))
-                            })
-                            .collect();
-                        rows.insert(*row_index, empty_row);
+                        let row_number = rows.len() + 1;
+                        insert_row(rows, *index, alignments.len(), |column| {
+                            TableCell::text(format!("Row {}, Col {}", row_number, column + 1))
+                        });
+                    }
+                    TableOperation::AddEmptyRow(index) => {
+                        insert_row(rows, *index, alignments.len(), |_| {
+                            TableCell::new(Vec::new())
+                        });
                     }
                     TableOperation::RemoveRow(index) => {
                         if *index < rows.len() {
-                            rows.remove(*index);
+                            remove_row(rows, *index);
                         } else {
-                            return Err(EditError::IndexOutOfBounds);
+                            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                         }
                     }
                     TableOperation::AddColumn(index) => {
-                        let alignments_len = alignments.len();
-                        let col_index = index.min(&alignments_len);
+                        let col_index = (*index).min(alignments.len());
+                        let column_number = alignments.len() + 1;
 
                         // Add column to header if present
                         if !header.is_empty() {
-                            header.insert(
-                                *col_index,
-                                TableCell::text(format!("Column {}", alignments.len() + 1)),
-                            );
+                            insert_column(std::slice::from_mut(header), col_index, |_| {
+                                TableCell::text(format!("Column {column_number}"))
+                            });
                         }
 
                         // Add column to each row
-                        for (i, row) in rows.iter_mut().enumerate() {
-                            row.insert(
-                                *col_index,
-                                TableCell::text(format!(
-                                    "Row {}, Col {}",
-                                    i + 1,
-                                    alignments.len() + 1
-                                )),
-                            );
-                        }
+                        insert_column(rows, col_index, |row| {
+                            TableCell::text(format!("Row {}, Col {}", row + 1, column_number))
+                        });
 
                         // Add alignment for the new column
-                        alignments.insert(*col_index, TableAlignment::default());
+                        alignments.insert(col_index, TableAlignment::default());
                     }
                     TableOperation::RemoveColumn(index) => {
                         if *index < alignments.len() {
                             // Remove from header if present
                             if !header.is_empty() {
-                                header.remove(*index);
+                                remove_column(std::slice::from_mut(header), *index);
                             }
 
                             // Remove from each row
-                            for row in rows.iter_mut() {
-                                if *index < row.len() {
-                                    row.remove(*index);
-                                }
-                            }
+                            remove_column(rows, *index);
 
                             // Remove alignment
                             alignments.remove(*index);
                         } else {
-                            return Err(EditError::IndexOutOfBounds);
+                            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                         }
                     }
                     TableOperation::SetCell {
@@ -181,14 +230,18 @@ impl Command for TableOperationsCommand {
                             if !header.is_empty() && *column < header.len() {
                                 header[*column] = TableCell::text(content);
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         } else {
                             // Modify body cell
                             if *row < rows.len() && *column < rows[*row].len() {
                                 rows[*row][*column] = TableCell::text(content);
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         }
                     }
@@ -196,7 +249,7 @@ impl Command for TableOperationsCommand {
                         if *column < alignments.len() {
                             alignments[*column] = alignment.clone();
                         } else {
-                            return Err(EditError::IndexOutOfBounds);
+                            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                         }
                     }
                     TableOperation::SetCellBackground {
@@ -210,14 +263,18 @@ impl Command for TableOperationsCommand {
                             if !header.is_empty() && *column < header.len() {
                                 header[*column].background_color = Some(color.clone());
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         } else {
                             // Modify body cell
                             if *row < rows.len() && *column < rows[*row].len() {
                                 rows[*row][*column].background_color = Some(color.clone());
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         }
                     }
@@ -232,14 +289,18 @@ impl Command for TableOperationsCommand {
                             if !header.is_empty() && *column < header.len() {
                                 header[*column].style = Some(style.clone());
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         } else {
                             // Modify body cell
                             if *row < rows.len() && *column < rows[*row].len() {
                                 rows[*row][*column].style = Some(style.clone());
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         }
                     }
@@ -256,7 +317,9 @@ impl Command for TableOperationsCommand {
                                 header[*column].colspan = *colspan;
                                 header[*column].rowspan = *rowspan;
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         } else {
                             // Modify body cell
@@ -264,13 +327,55 @@ impl Command for TableOperationsCommand {
                                 rows[*row][*column].colspan = *colspan;
                                 rows[*row][*column].rowspan = *rowspan;
                             } else {
-                                return Err(EditError::IndexOutOfBounds);
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
                             }
                         }
                     }
                     TableOperation::SetTableProperties(new_properties) => {
                         *properties = new_properties.clone();
                     }
+                    TableOperation::SetCaption { text, at_bottom } => {
+                        properties.caption = text.clone();
+                        properties.caption_at_bottom = *at_bottom;
+                    }
+                    TableOperation::SetAccessibility {
+                        summary,
+                        aria_label,
+                    } => {
+                        properties.summary = summary.clone();
+                        properties.aria_label = aria_label.clone();
+                    }
+                    TableOperation::MergeCells {
+                        start_row,
+                        start_col,
+                        end_row,
+                        end_col,
+                    } => merge_cells(rows, (*start_row, *start_col), (*end_row, *end_col))
+                        .map_err(|err| err.at_node(self.node_index))?,
+                    TableOperation::SplitCell { row, column } => split_cell(rows, *row, *column)
+                        .map_err(|err| err.at_node(self.node_index))?,
+                    TableOperation::Transpose => transpose(header, rows, alignments)
+                        .map_err(|err| err.at_node(self.node_index))?,
+                    TableOperation::ReshapeToColumns(columns) => {
+                        reshape_to_columns(header, rows, alignments, *columns)
+                            .map_err(|err| err.at_node(self.node_index))?
+                    }
+                    TableOperation::SetCellValue { row, column, value } => {
+                        match rows.get_mut(*row).and_then(|cells| cells.get_mut(*column)) {
+                            Some(cell) => cell.value = value.clone(),
+                            None => {
+                                return Err(
+                                    EditError::index_out_of_bounds().at_node(self.node_index)
+                                );
+                            }
+                        }
+                    }
+                    TableOperation::SortByColumn { column, descending } => {
+                        sort_rows(rows, *column, *descending)
+                            .map_err(|err| err.at_node(self.node_index))?
+                    }
                 }
             }
             _ => unreachable!(), // We already checked this is a table
@@ -286,10 +391,10 @@ impl Command for TableOperationsCommand {
                 document.nodes[self.node_index] = original_node;
                 Ok(())
             } else {
-                Err(EditError::IndexOutOfBounds)
+                Err(EditError::index_out_of_bounds().at_node(self.node_index))
             }
         } else {
-            Err(EditError::Other("No original state to restore".to_string()))
+            Err(EditError::other("No original state to restore").at_node(self.node_index))
         }
     }
 
@@ -298,6 +403,371 @@ impl Command for TableOperationsCommand {
     }
 }
 
+/// Grid area covered by a cell, as inclusive (row, column) corners
+fn cell_area(row: usize, column: usize, cell: &TableCell) -> ((usize, usize), (usize, usize)) {
+    let rowspan = cell.rowspan.max(1) as usize;
+    let colspan = cell.colspan.max(1) as usize;
+    ((row, column), (row + rowspan - 1, column + colspan - 1))
+}
+
+/// The cell occupying each grid slot, as its row and its index in that row
+///
+/// A spanned cell occupies every slot it covers, so rows further down can
+/// point at a cell anchored in an earlier row.
+fn cell_grid(rows: &[Vec<TableCell>]) -> Vec<Vec<Option<(usize, usize)>>> {
+    let columns = table_cell_columns(rows);
+    let mut grid: Vec<Vec<Option<(usize, usize)>>> = vec![Vec::new(); rows.len()];
+    for (r, row) in rows.iter().enumerate() {
+        for (i, cell) in row.iter().enumerate() {
+            let (top_left, bottom_right) = cell_area(r, columns[r][i], cell);
+            for slots in &mut grid[r..=bottom_right.0.min(rows.len() - 1)] {
+                if slots.len() <= bottom_right.1 {
+                    slots.resize(bottom_right.1 + 1, None);
+                }
+                slots[top_left.1..=bottom_right.1].fill(Some((r, i)));
+            }
+        }
+    }
+    grid
+}
+
+/// Inserts a grid column before logical column `column`
+///
+/// Cells spanning across the column are widened; every other row gets a
+/// cell from `new_cell`, which takes the row index.
+fn insert_column(
+    rows: &mut [Vec<TableCell>],
+    column: usize,
+    mut new_cell: impl FnMut(usize) -> TableCell,
+) {
+    let columns = table_cell_columns(rows);
+    let grid = cell_grid(rows);
+    for (r, slots) in grid.iter().enumerate() {
+        match slots.get(column).copied().flatten() {
+            Some((anchor, i)) if columns[anchor][i] < column => {
+                if anchor == r {
+                    rows[r][i].colspan = rows[r][i].colspan.max(1) + 1;
+                }
+            }
+            _ => {
+                let position = columns[r].iter().filter(|&&c| c < column).count();
+                rows[r].insert(position, new_cell(r));
+            }
+        }
+    }
+}
+
+/// Removes logical grid column `column`, narrowing the cells spanning it
+fn remove_column(rows: &mut [Vec<TableCell>], column: usize) {
+    let grid = cell_grid(rows);
+    for (r, slots) in grid.iter().enumerate() {
+        if let Some((anchor, i)) = slots.get(column).copied().flatten()
+            && anchor == r
+        {
+            if rows[r][i].colspan > 1 {
+                rows[r][i].colspan -= 1;
+            } else {
+                rows[r].remove(i);
+            }
+        }
+    }
+}
+
+/// Inserts a body row at `index`, with a cell from `new_cell` for each of
+/// the `width` grid columns
+///
+/// Cells spanning rows across the insertion point grow to span the new row
+/// too and take its place in their columns.
+fn insert_row(
+    rows: &mut Vec<Vec<TableCell>>,
+    index: usize,
+    width: usize,
+    mut new_cell: impl FnMut(usize) -> TableCell,
+) {
+    let index = index.min(rows.len());
+    let grid = cell_grid(rows);
+    let mut spanning = Vec::new();
+    let mut cells = Vec::new();
+    for column in 0..width {
+        match grid
+            .get(index)
+            .and_then(|slots| slots.get(column))
+            .copied()
+            .flatten()
+        {
+            Some((anchor, i)) if anchor < index => {
+                if !spanning.contains(&(anchor, i)) {
+                    spanning.push((anchor, i));
+                    rows[anchor][i].rowspan = rows[anchor][i].rowspan.max(1) + 1;
+                }
+            }
+            _ => cells.push(new_cell(column)),
+        }
+    }
+    rows.insert(index, cells);
+}
+
+/// Removes the body row at `index`
+///
+/// Cells from earlier rows spanning it get shorter, and cells of the row
+/// spanning further rows move down into the next row.
+fn remove_row(rows: &mut Vec<Vec<TableCell>>, index: usize) {
+    let columns = table_cell_columns(rows);
+    let grid = cell_grid(rows);
+    let mut spanning = Vec::new();
+    for &(anchor, i) in grid[index].iter().flatten() {
+        if anchor < index && !spanning.contains(&(anchor, i)) {
+            spanning.push((anchor, i));
+            rows[anchor][i].rowspan -= 1;
+        }
+    }
+
+    let removed = rows.remove(index);
+    let mut moved = 0;
+    for (mut cell, start) in removed.into_iter().zip(&columns[index]) {
+        if cell.rowspan > 1
+            && let Some(next) = rows.get_mut(index)
+        {
+            let position = columns[index + 1].iter().filter(|&&c| c < *start).count() + moved;
+            cell.rowspan -= 1;
+            next.insert(position, cell);
+            moved += 1;
+        }
+    }
+}
+
+fn merge_cells(
+    rows: &mut [Vec<TableCell>],
+    start: (usize, usize),
+    end: (usize, usize),
+) -> Result<(), EditError> {
+    if start.0 > end.0 || start.1 > end.1 || end.0 >= rows.len() {
+        return Err(EditError::invalid_range());
+    }
+
+    // Collect the cells inside the block in reading order
+    let columns = table_cell_columns(rows);
+    let mut merged = Vec::new();
+    let mut covered = 0;
+    for (r, row) in rows.iter().enumerate() {
+        for (i, cell) in row.iter().enumerate() {
+            let (top_left, bottom_right) = cell_area(r, columns[r][i], cell);
+            let intersects = top_left.0 <= end.0
+                && bottom_right.0 >= start.0
+                && top_left.1 <= end.1
+                && bottom_right.1 >= start.1;
+            if !intersects {
+                continue;
+            }
+            let inside = top_left.0 >= start.0
+                && bottom_right.0 <= end.0
+                && top_left.1 >= start.1
+                && bottom_right.1 <= end.1;
+            if !inside {
+                return Err(EditError::invalid_range());
+            }
+            covered += (bottom_right.0 - top_left.0 + 1) * (bottom_right.1 - top_left.1 + 1);
+            merged.push((r, i));
+        }
+    }
+
+    // Ragged rows can leave holes in the block
+    let area = (end.0 - start.0 + 1) * (end.1 - start.1 + 1);
+    if covered != area {
+        return Err(EditError::invalid_range());
+    }
+
+    // Remove back to front so earlier indices stay valid
+    let mut removed: Vec<TableCell> = merged[1..]
+        .iter()
+        .rev()
+        .map(|&(r, i)| rows[r].remove(i))
+        .collect();
+    removed.reverse();
+
+    let (first_row, first_index) = merged[0];
+    let target = &mut rows[first_row][first_index];
+    for cell in removed.into_iter().filter(|cell| !cell.content.is_empty()) {
+        if !target.content.is_empty() {
+            target.content.push(InlineNode::text(" "));
+        }
+        target.content.extend(cell.content);
+    }
+    target.rowspan = (end.0 - start.0 + 1) as u32;
+    target.colspan = (end.1 - start.1 + 1) as u32;
+    Ok(())
+}
+
+fn split_cell(rows: &mut [Vec<TableCell>], row: usize, column: usize) -> Result<(), EditError> {
+    let columns = table_cell_columns(rows);
+    let anchor = rows.iter().enumerate().find_map(|(r, cells)| {
+        cells.iter().enumerate().find_map(|(i, cell)| {
+            let (top_left, bottom_right) = cell_area(r, columns[r][i], cell);
+            let contains = (top_left.0..=bottom_right.0).contains(&row)
+                && (top_left.1..=bottom_right.1).contains(&column);
+            contains.then_some((r, i, top_left, bottom_right))
+        })
+    });
+    let Some((anchor_row, anchor_index, top_left, bottom_right)) = anchor else {
+        return Err(EditError::index_out_of_bounds());
+    };
+
+    let cell = &mut rows[anchor_row][anchor_index];
+    let is_header = cell.is_header;
+    cell.rowspan = 1;
+    cell.colspan = 1;
+
+    // Fill every freed grid slot with an empty cell
+    for r in top_left.0..=bottom_right.0.min(rows.len() - 1) {
+        let before = columns[r].iter().filter(|&&c| c < top_left.1).count();
+        for c in top_left.1..=bottom_right.1 {
+            if (r, c) == top_left {
+                continue;
+            }
+            let mut empty = TableCell::new(Vec::new());
+            empty.is_header = is_header;
+            rows[r].insert(before + c - top_left.1, empty);
+        }
+    }
+    Ok(())
+}
+
+/// Returns a copy of the cell marked as a header or body cell
+fn with_header_flag(mut cell: TableCell, is_header: bool) -> TableCell {
+    cell.is_header = is_header;
+    cell
+}
+
+fn transpose(
+    header: &mut Vec<TableCell>,
+    rows: &mut Vec<Vec<TableCell>>,
+    alignments: &mut Vec<TableAlignment>,
+) -> Result<(), EditError> {
+    let spanned = |cell: &TableCell| cell.colspan > 1 || cell.rowspan > 1;
+    if header.iter().chain(rows.iter().flatten()).any(spanned) {
+        return Err(EditError::unsupported());
+    }
+
+    let has_header = !header.is_empty();
+    let mut grid: Vec<Vec<TableCell>> = Vec::new();
+    if has_header {
+        grid.push(std::mem::take(header));
+    }
+    grid.append(rows);
+    let width = grid
+        .iter()
+        .map(Vec::len)
+        .chain([alignments.len()])
+        .max()
+        .unwrap_or(0);
+
+    // Column c of the grid becomes row c of the transposed grid
+    let mut transposed: Vec<Vec<TableCell>> = vec![Vec::with_capacity(grid.len()); width];
+    for row in grid.iter_mut() {
+        row.resize_with(width, || TableCell::new(Vec::new()));
+        for (c, cell) in row.drain(..).enumerate() {
+            transposed[c].push(cell);
+        }
+    }
+
+    let common = |columns: &[TableAlignment]| match columns.split_first() {
+        Some((first, rest)) if rest.iter().all(|a| a == first) => first.clone(),
+        _ => TableAlignment::default(),
+    };
+    let new_width = grid.len();
+    let mut new_alignments = vec![common(alignments.as_slice()); new_width];
+    if has_header && new_width > 0 {
+        new_alignments.fill(common(alignments.get(1..).unwrap_or_default()));
+        new_alignments[0] = alignments.first().cloned().unwrap_or_default();
+    }
+
+    let mut transposed = transposed.into_iter();
+    if has_header && let Some(first) = transposed.next() {
+        *header = first
+            .into_iter()
+            .map(|cell| with_header_flag(cell, true))
+            .collect();
+    }
+    *rows = transposed
+        .map(|row| {
+            row.into_iter()
+                .map(|cell| with_header_flag(cell, false))
+                .collect()
+        })
+        .collect();
+    *alignments = new_alignments;
+    Ok(())
+}
+
+fn sort_rows(
+    rows: &mut Vec<Vec<TableCell>>,
+    column: usize,
+    descending: bool,
+) -> Result<(), EditError> {
+    if rows.iter().flatten().any(|cell| cell.rowspan > 1) {
+        return Err(EditError::unsupported());
+    }
+
+    // Pair each row with the index of its cell in the column, if any
+    let columns = table_cell_columns(rows);
+    let mut keyed: Vec<(Option<usize>, Vec<TableCell>)> = rows
+        .drain(..)
+        .zip(&columns)
+        .map(|(row, row_columns)| (row_columns.iter().position(|&c| c == column), row))
+        .collect();
+    keyed.sort_by(|(a, row_a), (b, row_b)| match (a, b) {
+        (Some(a), Some(b)) => {
+            let ordering = compare_cells(&row_a[*a], &row_b[*b]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        }
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    *rows = keyed.into_iter().map(|(_, row)| row).collect();
+    Ok(())
+}
+
+fn reshape_to_columns(
+    header: &mut Vec<TableCell>,
+    rows: &mut Vec<Vec<TableCell>>,
+    alignments: &mut Vec<TableAlignment>,
+    columns: usize,
+) -> Result<(), EditError> {
+    if columns == 0 {
+        return Err(EditError::invalid_range());
+    }
+    if header.len() > 1 || alignments.len() > 1 || rows.iter().any(|row| row.len() > 1) {
+        return Err(EditError::unsupported());
+    }
+
+    let cells: Vec<TableCell> = rows.drain(..).flatten().collect();
+    let mut reshaped: Vec<Vec<TableCell>> = Vec::new();
+    for cell in cells {
+        match reshaped.last_mut() {
+            Some(row) if row.len() < columns => row.push(cell),
+            _ => reshaped.push(vec![cell]),
+        }
+    }
+    if let Some(last) = reshaped.last_mut() {
+        last.resize_with(columns, || TableCell::new(Vec::new()));
+    }
+
+    if !header.is_empty() {
+        header.resize_with(columns, || {
+            with_header_flag(TableCell::new(Vec::new()), true)
+        });
+    }
+    let alignment = alignments.first().cloned().unwrap_or_default();
+    *alignments = vec![alignment; columns];
+    *rows = reshaped;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -510,4 +980,274 @@ mod tests {
             _ => panic!("Expected Table node"),
         }
     }
+
+    fn grid_table(rows: Vec<Vec<&str>>) -> Rc<RefCell<Document>> {
+        let mut doc = Document::new();
+        doc.nodes
+            .push(Node::simple_table(vec!["A", "B", "C"], rows));
+        Rc::new(RefCell::new(doc))
+    }
+
+    fn body_rows(document: &Rc<RefCell<Document>>) -> Vec<Vec<TableCell>> {
+        match &document.borrow().nodes[0] {
+            Node::Table { rows, .. } => rows.clone(),
+            _ => panic!("Expected Table node"),
+        }
+    }
+
+    #[test]
+    fn test_merge_and_split_cells() {
+        let document = grid_table(vec![
+            vec!["a1", "b1", "c1"],
+            vec!["a2", "b2", "c2"],
+            vec!["a3", "b3", "c3"],
+        ]);
+
+        let mut merge = TableOperationsCommand::new(
+            document.clone(),
+            0,
+            TableOperation::MergeCells {
+                start_row: 0,
+                start_col: 1,
+                end_row: 1,
+                end_col: 2,
+            },
+        );
+        merge.execute().unwrap();
+
+        let rows = body_rows(&document);
+        assert_eq!(rows.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1, 3]);
+        assert_eq!((rows[0][1].rowspan, rows[0][1].colspan), (2, 2));
+        assert_eq!(
+            InlineNode::plain_text_of(&rows[0][1].content),
+            "b1 c1 b2 c2"
+        );
+        assert_eq!(
+            table_cell_columns(&rows),
+            [vec![0, 1], vec![0], vec![0, 1, 2]]
+        );
+
+        // Blocks that cut through the merged cell are rejected
+        let mut partial = TableOperationsCommand::new(
+            document.clone(),
+            0,
+            TableOperation::MergeCells {
+                start_row: 1,
+                start_col: 0,
+                end_row: 2,
+                end_col: 1,
+            },
+        );
+        assert!(partial.execute().is_err());
+
+        // Splitting from any covered slot restores the grid shape
+        let mut split = TableOperationsCommand::new(
+            document.clone(),
+            0,
+            TableOperation::SplitCell { row: 1, column: 2 },
+        );
+        split.execute().unwrap();
+        let rows = body_rows(&document);
+        assert_eq!(rows.iter().map(Vec::len).collect::<Vec<_>>(), [3, 3, 3]);
+        assert_eq!(rows[1][0].content, vec![InlineNode::text("a2")]);
+        assert!(rows[1][1].content.is_empty() && rows[1][2].content.is_empty());
+
+        split.undo().unwrap();
+        merge.undo().unwrap();
+        assert_eq!(
+            body_rows(&document)[0][1].content,
+            vec![InlineNode::text("b1")]
+        );
+    }
+
+    /// Applies `operation` to a 3x3 table whose b1:c2 block is merged,
+    /// returning the body rows
+    fn after_merge(operation: TableOperation) -> Vec<Vec<TableCell>> {
+        let document = grid_table(vec![
+            vec!["a1", "b1", "c1"],
+            vec!["a2", "b2", "c2"],
+            vec!["a3", "b3", "c3"],
+        ]);
+        let merge = TableOperation::MergeCells {
+            start_row: 0,
+            start_col: 1,
+            end_row: 1,
+            end_col: 2,
+        };
+        TableOperationsCommand::new(document.clone(), 0, merge)
+            .execute()
+            .unwrap();
+        let mut command = TableOperationsCommand::new(document.clone(), 0, operation);
+        command.execute().unwrap();
+        body_rows(&document)
+    }
+
+    fn spans(cell: &TableCell) -> (u32, u32) {
+        (cell.rowspan, cell.colspan)
+    }
+
+    #[test]
+    fn test_columns_across_merged_cells() {
+        // A column inserted inside the merged cell widens it
+        let rows = after_merge(TableOperation::AddColumn(2));
+        assert_eq!(spans(&rows[0][1]), (2, 3));
+        assert_eq!(
+            table_cell_columns(&rows),
+            [vec![0, 1], vec![0], vec![0, 1, 2, 3]]
+        );
+        assert_eq!(cell_texts(&rows[2])[2], "Row 3, Col 4");
+
+        // One inserted at its start goes before it in every row
+        let rows = after_merge(TableOperation::AddColumn(1));
+        assert_eq!(spans(&rows[0][2]), (2, 2));
+        assert_eq!(
+            table_cell_columns(&rows),
+            [vec![0, 1, 2], vec![0, 1], vec![0, 1, 2, 3]]
+        );
+
+        // Removing a spanned column narrows the merged cell
+        let rows = after_merge(TableOperation::RemoveColumn(2));
+        assert_eq!(spans(&rows[0][1]), (2, 1));
+        assert_eq!(cell_texts(&rows[2]), ["a3", "b3"]);
+        let rows = after_merge(TableOperation::RemoveColumn(0));
+        assert_eq!(rows.iter().map(Vec::len).collect::<Vec<_>>(), [1, 0, 2]);
+
+        let document = grid_table(vec![vec!["a1", "b1", "c1"]]);
+        let merge = TableOperation::MergeCells {
+            start_row: 0,
+            start_col: 0,
+            end_row: 0,
+            end_col: 1,
+        };
+        TableOperationsCommand::new(document.clone(), 0, merge)
+            .execute()
+            .unwrap();
+        TableOperationsCommand::new(document.clone(), 0, TableOperation::AddColumn(2))
+            .execute()
+            .unwrap();
+        assert_eq!(table_cell_columns(&body_rows(&document)), [vec![0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_rows_across_merged_cells() {
+        // A row inserted inside the merged cell lengthens it
+        let rows = after_merge(TableOperation::AddEmptyRow(1));
+        assert_eq!(spans(&rows[0][1]), (3, 2));
+        assert_eq!(rows.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1, 1, 3]);
+        let rows = after_merge(TableOperation::AddRow(2));
+        assert_eq!(spans(&rows[0][1]), (2, 2));
+        assert_eq!(rows[2].len(), 3);
+
+        // Removing a spanned row shortens it
+        let rows = after_merge(TableOperation::RemoveRow(1));
+        assert_eq!(spans(&rows[0][1]), (1, 2));
+        assert_eq!(cell_texts(&rows[1]), ["a3", "b3", "c3"]);
+
+        // Removing its first row moves it down
+        let rows = after_merge(TableOperation::RemoveRow(0));
+        assert_eq!(cell_texts(&rows[0]), ["a2", "b1 c1 b2 c2"]);
+        assert_eq!(spans(&rows[0][1]), (1, 2));
+        assert_eq!(table_cell_columns(&rows), [vec![0, 1], vec![0, 1, 2]]);
+    }
+
+    fn cell_texts(cells: &[TableCell]) -> Vec<String> {
+        cells
+            .iter()
+            .map(|cell| InlineNode::plain_text_of(&cell.content))
+            .collect()
+    }
+
+    #[test]
+    fn test_transpose() {
+        let document = grid_table(vec![vec!["a1", "b1", "c1"], vec!["a2", "b2"]]);
+        if let Node::Table { alignments, .. } = &mut document.borrow_mut().nodes[0] {
+            *alignments = vec![
+                TableAlignment::Left,
+                TableAlignment::Right,
+                TableAlignment::Right,
+            ];
+        }
+
+        let mut transpose =
+            TableOperationsCommand::new(document.clone(), 0, TableOperation::Transpose);
+        transpose.execute().unwrap();
+        match &document.borrow().nodes[0] {
+            Node::Table {
+                header,
+                rows,
+                alignments,
+                ..
+            } => {
+                assert_eq!(cell_texts(header), ["A", "a1", "a2"]);
+                assert!(header.iter().all(|cell| cell.is_header));
+                assert_eq!(cell_texts(&rows[0]), ["B", "b1", "b2"]);
+                assert_eq!(cell_texts(&rows[1]), ["C", "c1", ""]);
+                assert!(!rows[0][0].is_header);
+                assert_eq!(
+                    alignments,
+                    &[
+                        TableAlignment::Left,
+                        TableAlignment::Right,
+                        TableAlignment::Right
+                    ]
+                );
+            }
+            _ => panic!("Expected Table node"),
+        }
+
+        transpose.undo().unwrap();
+        assert_eq!(body_rows(&document)[1].len(), 2);
+
+        let mut merge = TableOperationsCommand::new(
+            document.clone(),
+            0,
+            TableOperation::MergeCells {
+                start_row: 0,
+                start_col: 0,
+                end_row: 0,
+                end_col: 1,
+            },
+        );
+        merge.execute().unwrap();
+        let mut transpose =
+            TableOperationsCommand::new(document.clone(), 0, TableOperation::Transpose);
+        assert!(transpose.execute().is_err());
+    }
+
+    #[test]
+    fn test_reshape_to_columns() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::simple_table(
+            vec!["Names"],
+            vec![vec!["a"], vec!["b"], vec!["c"], vec!["d"], vec!["e"]],
+        ));
+        let document = Rc::new(RefCell::new(doc));
+
+        let mut reshape =
+            TableOperationsCommand::new(document.clone(), 0, TableOperation::ReshapeToColumns(2));
+        reshape.execute().unwrap();
+        match &document.borrow().nodes[0] {
+            Node::Table {
+                header,
+                rows,
+                alignments,
+                ..
+            } => {
+                assert_eq!(cell_texts(header), ["Names", ""]);
+                assert_eq!(
+                    rows.iter().map(|row| cell_texts(row)).collect::<Vec<_>>(),
+                    [vec!["a", "b"], vec!["c", "d"], vec!["e", ""]]
+                );
+                assert_eq!(alignments.len(), 2);
+            }
+            _ => panic!("Expected Table node"),
+        }
+
+        // Only single-column tables can be reshaped
+        let mut again =
+            TableOperationsCommand::new(document.clone(), 0, TableOperation::ReshapeToColumns(3));
+        assert!(again.execute().is_err());
+        reshape.undo().unwrap();
+        assert_eq!(body_rows(&document).len(), 5);
+    }
 }
@@ -30,20 +30,22 @@ impl Command for ToggleTaskCommand {
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Get the node
         match &mut document.nodes[self.node_index] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Check if it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Check if item_index is valid
                 if self.item_index >= items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                 }
 
                 // Get the item and toggle its checked status
@@ -60,7 +62,7 @@ impl Command for ToggleTaskCommand {
 
                 Ok(())
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -69,20 +71,22 @@ impl Command for ToggleTaskCommand {
 
         // Check if node_index is valid
         if self.node_index >= document.nodes.len() {
-            return Err(EditError::IndexOutOfBounds);
+            return Err(EditError::index_out_of_bounds().at_node(self.node_index));
         }
 
         // Get the node
         match &mut document.nodes[self.node_index] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 // Check if it's a task list
                 if *list_type != ListType::Task {
-                    return Err(EditError::UnsupportedOperation);
+                    return Err(EditError::unsupported().at_node(self.node_index));
                 }
 
                 // Check if item_index is valid
                 if self.item_index >= items.len() {
-                    return Err(EditError::IndexOutOfBounds);
+                    return Err(EditError::index_out_of_bounds().at_node(self.node_index));
                 }
 
                 // Restore the previous state
@@ -90,10 +94,10 @@ impl Command for ToggleTaskCommand {
                     items[self.item_index].checked = Some(previous_state);
                     Ok(())
                 } else {
-                    Err(EditError::OperationFailed)
+                    Err(EditError::operation_failed().at_node(self.node_index))
                 }
             }
-            _ => Err(EditError::UnsupportedOperation),
+            _ => Err(EditError::unsupported().at_node(self.node_index)),
         }
     }
 
@@ -105,7 +109,7 @@ impl Command for ToggleTaskCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Node;
+    use crate::{EditErrorKind, Node};
 
     #[test]
     fn test_toggle_task() {
@@ -126,7 +130,9 @@ mod tests {
         {
             let doc = doc_rc.borrow();
             match &doc.nodes[0] {
-                Node::List { list_type, items } => {
+                Node::List {
+                    list_type, items, ..
+                } => {
                     assert_eq!(*list_type, ListType::Task);
                     assert_eq!(items.len(), 2);
                     assert_eq!(items[0].checked, Some(true));
@@ -144,7 +150,9 @@ mod tests {
         {
             let doc = doc_rc.borrow();
             match &doc.nodes[0] {
-                Node::List { list_type, items } => {
+                Node::List {
+                    list_type, items, ..
+                } => {
                     assert_eq!(*list_type, ListType::Task);
                     assert_eq!(items.len(), 2);
                     assert_eq!(items[0].checked, Some(true));
@@ -161,7 +169,9 @@ mod tests {
         {
             let doc = doc_rc.borrow();
             match &doc.nodes[0] {
-                Node::List { list_type, items } => {
+                Node::List {
+                    list_type, items, ..
+                } => {
                     assert_eq!(*list_type, ListType::Task);
                     assert_eq!(items.len(), 2);
                     assert_eq!(items[0].checked, Some(true));
@@ -178,7 +188,9 @@ mod tests {
         {
             let doc = doc_rc.borrow();
             match &doc.nodes[0] {
-                Node::List { list_type, items } => {
+                Node::List {
+                    list_type, items, ..
+                } => {
                     assert_eq!(*list_type, ListType::Task);
                     assert_eq!(items.len(), 2);
                     assert_eq!(items[0].checked, Some(false));
@@ -202,10 +214,10 @@ mod tests {
 
         // Try to toggle a "task" in a paragraph
         let mut cmd = ToggleTaskCommand::new(doc_rc.clone(), 0, 0);
-        assert!(matches!(
-            cmd.execute(),
-            Err(EditError::UnsupportedOperation)
-        ));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::UnsupportedOperation
+        );
     }
 
     #[test]
@@ -221,10 +233,16 @@ mod tests {
 
         // Try to toggle an out-of-bounds item
         let mut cmd = ToggleTaskCommand::new(doc_rc.clone(), 0, 1);
-        assert!(matches!(cmd.execute(), Err(EditError::IndexOutOfBounds)));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
 
         // Try to toggle an item in an out-of-bounds node
         let mut cmd = ToggleTaskCommand::new(doc_rc.clone(), 1, 0);
-        assert!(matches!(cmd.execute(), Err(EditError::IndexOutOfBounds)));
+        assert_eq!(
+            cmd.execute().unwrap_err().kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
     }
 }
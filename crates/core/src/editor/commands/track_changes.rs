@@ -0,0 +1,160 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::{Document, EditError, Node, TrackedChange};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// What a [`TrackChangesCommand`] does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackedEdit {
+    /// Insert text by an author, marked as an insertion
+    Insert {
+        author: String,
+        node_index: usize,
+        position: usize,
+        text: String,
+    },
+    /// Mark a range of text as deleted by an author
+    Delete {
+        author: String,
+        node_index: usize,
+        start: usize,
+        end: usize,
+    },
+    /// Accept or reject one tracked change, or all of them without an id
+    Resolve { id: Option<u64>, accept: bool },
+}
+
+/// Command to make or resolve a suggested edit
+pub struct TrackChangesCommand {
+    document: Rc<RefCell<Document>>,
+    edit: TrackedEdit,
+    /// Nodes the command changed, before it ran
+    previous_nodes: Vec<(usize, Node)>,
+    /// Tracked changes before the command ran
+    previous_changes: Option<Vec<TrackedChange>>,
+}
+
+impl TrackChangesCommand {
+    pub fn new(document: Rc<RefCell<Document>>, edit: TrackedEdit) -> Self {
+        Self {
+            document,
+            edit,
+            previous_nodes: Vec::new(),
+            previous_changes: None,
+        }
+    }
+}
+
+impl Command for TrackChangesCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + estimate_bytes(&self.previous_nodes)
+            + estimate_bytes(&self.previous_changes)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let document = self.document.borrow_mut();
+        let changes = document.tracked_changes.clone();
+        let mut indices: Vec<usize> = match &self.edit {
+            TrackedEdit::Insert { node_index, .. } | TrackedEdit::Delete { node_index, .. } => {
+                vec![*node_index]
+            }
+            TrackedEdit::Resolve { id: Some(id), .. } => document
+                .tracked_change(*id)
+                .map(|change| change.path[0])
+                .into_iter()
+                .collect(),
+            TrackedEdit::Resolve { id: None, .. } => {
+                changes.iter().map(|change| change.path[0]).collect()
+            }
+        };
+        indices.sort_unstable();
+        indices.dedup();
+        let nodes: Vec<(usize, Node)> = indices
+            .into_iter()
+            .filter_map(|index| Some((index, document.nodes.get(index)?.clone())))
+            .collect();
+        drop(document);
+
+        let result = match &self.edit {
+            TrackedEdit::Insert {
+                author,
+                node_index,
+                position,
+                text,
+            } => {
+                let mut insert = super::InsertTextCommand::new(
+                    self.document.clone(),
+                    *node_index,
+                    *position,
+                    text.clone(),
+                );
+                insert.execute().map(|()| {
+                    self.document.borrow_mut().track_insertion(
+                        *node_index,
+                        *position,
+                        text.len(),
+                        author,
+                    )
+                })
+            }
+            TrackedEdit::Delete {
+                author,
+                node_index,
+                start,
+                end,
+            } => self
+                .document
+                .borrow_mut()
+                .track_deletion(*node_index, *start..*end, author),
+            TrackedEdit::Resolve {
+                id: Some(id),
+                accept,
+            } => self
+                .document
+                .borrow_mut()
+                .resolve_tracked_change(*id, *accept),
+            TrackedEdit::Resolve { id: None, accept } => {
+                // Resolving a change can remove others inside its text
+                let mut document = self.document.borrow_mut();
+                let mut result = Ok(());
+                while let Some(id) = document.tracked_changes.first().map(|change| change.id) {
+                    result = document.resolve_tracked_change(id, *accept);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                result
+            }
+        };
+
+        let mut document = self.document.borrow_mut();
+        if let Err(err) = result {
+            for (index, node) in nodes {
+                document.nodes[index] = node;
+            }
+            document.tracked_changes = changes;
+            return Err(err);
+        }
+        self.previous_nodes = nodes;
+        self.previous_changes = Some(changes);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let Some(changes) = self.previous_changes.take() else {
+            return Err(EditError::operation_failed().with_message("Nothing to undo"));
+        };
+        let mut document = self.document.borrow_mut();
+        for (index, node) in std::mem::take(&mut self.previous_nodes) {
+            document.nodes[index] = node;
+        }
+        document.tracked_changes = changes;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,117 @@
+use crate::editor::command::{Command, estimate_bytes};
+use crate::images::apply_image_updates;
+use crate::{Document, EditError, ImageInfo, Node};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command to rewrite the alt text, titles and captions of several images
+///
+/// Images are addressed by their index from [`Document::images`]; the URL is
+/// never changed.
+pub struct UpdateImagesCommand {
+    document: Rc<RefCell<Document>>,
+    updates: Vec<ImageInfo>,
+    original_nodes: Vec<(usize, Node)>,
+}
+
+impl UpdateImagesCommand {
+    /// Create a command applying the given image descriptions
+    pub fn new(document: Rc<RefCell<Document>>, updates: Vec<ImageInfo>) -> Self {
+        Self {
+            document,
+            updates,
+            original_nodes: Vec::new(),
+        }
+    }
+}
+
+impl Command for UpdateImagesCommand {
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.updates) + estimate_bytes(&self.original_nodes)
+    }
+
+    fn execute(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        let image_count = document.images().len();
+        if let Some(update) = self
+            .updates
+            .iter()
+            .find(|update| update.index >= image_count)
+        {
+            return Err(EditError::index_out_of_bounds()
+                .at_node(update.node_index)
+                .with_ranges(0..image_count, update.index..update.index + 1));
+        }
+
+        let mut original_nodes = Vec::new();
+        let mut next_index = 0;
+        for (i, node) in document.nodes.iter_mut().enumerate() {
+            let original = self
+                .updates
+                .iter()
+                .any(|update| update.node_index == i)
+                .then(|| node.clone());
+            next_index = apply_image_updates(node, next_index, &self.updates);
+            if let Some(original) = original
+                && original != *node
+            {
+                original_nodes.push((i, original));
+            }
+        }
+
+        self.original_nodes = original_nodes;
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        for (i, node) in self.original_nodes.drain(..) {
+            let slot = document
+                .nodes
+                .get_mut(i)
+                .ok_or(EditError::index_out_of_bounds().at_node(i))?;
+            *slot = node;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InlineNode;
+
+    #[test]
+    fn test_update_images_and_undo() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::Paragraph {
+            children: vec![
+                InlineNode::image("a.png", ""),
+                InlineNode::image("b.png", ""),
+            ],
+        });
+        doc.nodes.push(Node::figure("c.png", "", "Chart"));
+        let document = Rc::new(RefCell::new(doc));
+
+        let mut updates = document.borrow().images();
+        updates.remove(0);
+        updates[0].alt = "Second".to_string();
+        updates[1].alt = "Third".to_string();
+        updates[1].caption = Some("Quarterly chart".to_string());
+
+        let mut cmd = UpdateImagesCommand::new(document.clone(), updates);
+        cmd.execute().unwrap();
+        let images = document.borrow().images();
+        assert_eq!(images[0].alt, "");
+        assert_eq!(images[1].alt, "Second");
+        assert_eq!(images[2].caption.as_deref(), Some("Quarterly chart"));
+
+        cmd.undo().unwrap();
+        assert!(document.borrow().images().iter().all(ImageInfo::lacks_alt));
+    }
+}
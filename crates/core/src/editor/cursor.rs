@@ -0,0 +1,153 @@
+use crate::offsets::{grapheme_boundaries, offset_text};
+use crate::{Document, Position};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A keyboard cursor movement
+///
+/// Every top-level node counts as one line. Horizontal moves step over whole
+/// grapheme clusters, so a cursor never lands inside an emoji sequence or
+/// between a letter and its combining accent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorMotion {
+    /// One grapheme back, or to the end of the previous node
+    Left,
+    /// One grapheme forward, or to the start of the next node
+    Right,
+    /// To the start of the current or previous word
+    WordLeft,
+    /// To the end of the current or next word
+    WordRight,
+    /// To the start of the node
+    LineStart,
+    /// To the end of the node
+    LineEnd,
+    /// To the same column in the previous node
+    Up,
+    /// To the same column in the next node
+    Down,
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().any(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Orders positions by node and offset
+pub(crate) fn position_key(position: &Position) -> (usize, usize) {
+    (position.path.first().copied().unwrap_or(0), position.offset)
+}
+
+impl CursorMotion {
+    /// Returns the position reached by applying the motion at `position`
+    ///
+    /// The document must not be empty. Positions outside the document are
+    /// clamped to it first.
+    pub(crate) fn apply(self, document: &Document, position: &Position) -> Position {
+        let last = document.nodes.len() - 1;
+        let node = position.path.first().copied().unwrap_or(0).min(last);
+        let text = offset_text(&document.nodes[node]);
+        let bounds = grapheme_boundaries(&text);
+        // Snap to the grapheme boundary at or before the offset
+        let column = bounds.partition_point(|&b| b <= position.offset) - 1;
+        let offset = bounds[column];
+        let at = |node: usize, offset: usize| Position::new(vec![node], offset);
+        let line_end = |node: usize| offset_text(&document.nodes[node]).len();
+
+        match self {
+            Self::Left if column > 0 => at(node, bounds[column - 1]),
+            Self::Left | Self::WordLeft if column == 0 && node > 0 => {
+                at(node - 1, line_end(node - 1))
+            }
+            Self::Left => at(node, offset),
+            Self::Right if column + 1 < bounds.len() => at(node, bounds[column + 1]),
+            Self::Right | Self::WordRight if column + 1 == bounds.len() && node < last => {
+                at(node + 1, 0)
+            }
+            Self::Right => at(node, offset),
+            Self::WordLeft => {
+                let graphemes: Vec<&str> = text[..offset].graphemes(true).collect();
+                let mut i = graphemes.len();
+                while i > 0 && !is_word(graphemes[i - 1]) {
+                    i -= 1;
+                }
+                while i > 0 && is_word(graphemes[i - 1]) {
+                    i -= 1;
+                }
+                at(node, bounds[i])
+            }
+            Self::WordRight => {
+                let graphemes: Vec<&str> = text[offset..].graphemes(true).collect();
+                let mut i = 0;
+                while i < graphemes.len() && !is_word(graphemes[i]) {
+                    i += 1;
+                }
+                while i < graphemes.len() && is_word(graphemes[i]) {
+                    i += 1;
+                }
+                at(node, bounds[column + i])
+            }
+            Self::LineStart => at(node, 0),
+            Self::LineEnd => at(node, text.len()),
+            Self::Up if node == 0 => at(node, 0),
+            Self::Down if node == last => at(node, text.len()),
+            Self::Up | Self::Down => {
+                let target = if self == Self::Up { node - 1 } else { node + 1 };
+                let target_bounds = grapheme_boundaries(&offset_text(&document.nodes[target]));
+                at(target, target_bounds[column.min(target_bounds.len() - 1)])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> Document {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Hello, wörld");
+        doc.add_paragraph_with_text("e\u{301}🇯🇵!");
+        doc.add_code_block("fn main() {}", "rust");
+        doc
+    }
+
+    fn apply(motion: CursorMotion, node: usize, offset: usize) -> (usize, usize) {
+        position_key(&motion.apply(&document(), &Position::new(vec![node], offset)))
+    }
+
+    #[test]
+    fn test_grapheme_moves() {
+        // "é" is two code points and the flag two regional indicators
+        assert_eq!(apply(CursorMotion::Right, 1, 0), (1, 3));
+        assert_eq!(apply(CursorMotion::Right, 1, 3), (1, 11));
+        assert_eq!(apply(CursorMotion::Left, 1, 11), (1, 3));
+        // Offsets inside a cluster snap back to its start
+        assert_eq!(apply(CursorMotion::Left, 1, 5), (1, 0));
+
+        assert_eq!(apply(CursorMotion::Right, 0, 13), (1, 0));
+        assert_eq!(apply(CursorMotion::Left, 1, 0), (0, 13));
+        assert_eq!(apply(CursorMotion::Left, 0, 0), (0, 0));
+        assert_eq!(apply(CursorMotion::Right, 2, 12), (2, 12));
+    }
+
+    #[test]
+    fn test_word_moves() {
+        assert_eq!(apply(CursorMotion::WordRight, 0, 0), (0, 5));
+        assert_eq!(apply(CursorMotion::WordRight, 0, 5), (0, 13));
+        assert_eq!(apply(CursorMotion::WordLeft, 0, 13), (0, 7));
+        assert_eq!(apply(CursorMotion::WordLeft, 0, 7), (0, 0));
+        assert_eq!(apply(CursorMotion::WordLeft, 2, 0), (1, 12));
+        assert_eq!(apply(CursorMotion::WordRight, 0, 13), (1, 0));
+    }
+
+    #[test]
+    fn test_line_moves() {
+        assert_eq!(apply(CursorMotion::LineEnd, 0, 3), (0, 13));
+        assert_eq!(apply(CursorMotion::LineStart, 0, 3), (0, 0));
+        // Column 2 in "e\u{301}🇯🇵!" is the "!"
+        assert_eq!(apply(CursorMotion::Up, 2, 2), (1, 11));
+        assert_eq!(apply(CursorMotion::Down, 1, 11), (2, 2));
+        assert_eq!(apply(CursorMotion::Down, 0, 13), (1, 12));
+        assert_eq!(apply(CursorMotion::Up, 0, 5), (0, 0));
+        assert_eq!(apply(CursorMotion::Down, 2, 3), (2, 12));
+    }
+}
@@ -0,0 +1,223 @@
+use super::command::{Command as EditorCommand, estimate_bytes};
+use crate::{
+    ChangeSet, Comment, Document, EditError, NodeAttributes, NodeChange, Selection, TrackedChange,
+};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// A command on the undo or redo stack with its estimated memory footprint
+pub(crate) struct HistoryEntry {
+    pub(crate) command: Box<dyn EditorCommand>,
+    /// Estimated bytes retained by the command, measured when it was recorded
+    pub(crate) bytes: usize,
+    /// Whether the entry has already been through history compression
+    pub(crate) compressed: bool,
+    /// Selection before the command ran, restored by undo
+    pub(crate) selection_before: Option<Selection>,
+    /// Selection after the command ran, restored by redo
+    pub(crate) selection_after: Option<Selection>,
+    /// Node attributes before the command, if it moved or removed any,
+    /// restored by undo so removed nodes get theirs back
+    pub(crate) attributes_before: Option<BTreeMap<usize, NodeAttributes>>,
+    /// Node attributes after the command, if it moved or removed any,
+    /// restored by redo
+    pub(crate) attributes_after: Option<BTreeMap<usize, NodeAttributes>>,
+}
+
+impl HistoryEntry {
+    pub(crate) fn new(command: Box<dyn EditorCommand>) -> Self {
+        Self {
+            bytes: command.retained_bytes(),
+            command,
+            compressed: false,
+            selection_before: None,
+            selection_after: None,
+            attributes_before: None,
+            attributes_after: None,
+        }
+    }
+
+    /// Builds an entry for another command doing the same step, keeping the
+    /// selections and node attributes
+    pub(crate) fn replaced_by(&self, command: Box<dyn EditorCommand>) -> Self {
+        Self {
+            selection_before: self.selection_before.clone(),
+            selection_after: self.selection_after.clone(),
+            attributes_before: self.attributes_before.clone(),
+            attributes_after: self.attributes_after.clone(),
+            ..Self::new(command)
+        }
+    }
+}
+
+/// One undoable step as a node-level patch
+///
+/// Steps hold only the nodes that changed, the selection on either side and
+/// the comments, tracked changes and node attributes if the step changed them, so they can be persisted and
+/// replayed on a later run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryStep {
+    /// Changes that redo the step
+    pub changes: ChangeSet,
+    /// Selection before the step
+    pub selection_before: Option<Selection>,
+    /// Selection after the step
+    pub selection_after: Option<Selection>,
+    /// Comments before the step, if it changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments_before: Option<Vec<Comment>>,
+    /// Comments after the step, if it changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments_after: Option<Vec<Comment>>,
+    /// Tracked changes before the step, if it changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracked_changes_before: Option<Vec<TrackedChange>>,
+    /// Tracked changes after the step, if it changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracked_changes_after: Option<Vec<TrackedChange>>,
+    /// Node attributes before the step, if it changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_attributes_before: Option<BTreeMap<usize, NodeAttributes>>,
+    /// Node attributes after the step, if it changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_attributes_after: Option<BTreeMap<usize, NodeAttributes>>,
+}
+
+impl HistoryStep {
+    /// Builds the step that turns `before` into `after`
+    pub(crate) fn between(before: &Document, after: &Document) -> Self {
+        let mut changes = before.diff(after);
+
+        // Inline text diffs are only informational and not needed for replay
+        for change in &mut changes.changes {
+            if let NodeChange::Modify { text_changes, .. } = change {
+                *text_changes = Vec::new();
+            }
+        }
+
+        let comments_changed = before.comments != after.comments;
+        let tracked_changed = before.tracked_changes != after.tracked_changes;
+        let attributes_changed = before.node_attributes != after.node_attributes;
+        Self {
+            changes,
+            selection_before: before.selection.clone(),
+            selection_after: after.selection.clone(),
+            comments_before: comments_changed.then(|| before.comments.clone()),
+            comments_after: comments_changed.then(|| after.comments.clone()),
+            tracked_changes_before: tracked_changed.then(|| before.tracked_changes.clone()),
+            tracked_changes_after: tracked_changed.then(|| after.tracked_changes.clone()),
+            node_attributes_before: attributes_changed.then(|| before.node_attributes.clone()),
+            node_attributes_after: attributes_changed.then(|| after.node_attributes.clone()),
+        }
+    }
+
+    pub(crate) fn redo(&self, document: &mut Document) -> Result<(), EditError> {
+        document.apply_changeset(&self.changes)?;
+        document.selection = self.selection_after.clone();
+        if let Some(comments) = &self.comments_after {
+            document.comments = comments.clone();
+        }
+        if let Some(changes) = &self.tracked_changes_after {
+            document.tracked_changes = changes.clone();
+        }
+        if let Some(attributes) = &self.node_attributes_after {
+            document.node_attributes = attributes.clone();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn undo(&self, document: &mut Document) -> Result<(), EditError> {
+        document.apply_changeset(&self.changes.invert())?;
+        document.selection = self.selection_before.clone();
+        if let Some(comments) = &self.comments_before {
+            document.comments = comments.clone();
+        }
+        if let Some(changes) = &self.tracked_changes_before {
+            document.tracked_changes = changes.clone();
+        }
+        if let Some(attributes) = &self.node_attributes_before {
+            document.node_attributes = attributes.clone();
+        }
+        Ok(())
+    }
+}
+
+/// The undo and redo stacks of an editor as plain data
+///
+/// Obtained from [`Editor::history`](super::Editor::history) and restored
+/// with [`Editor::with_history`](super::Editor::with_history). Both stacks
+/// are ordered oldest first, so the last undo step is undone first and the
+/// last redo step is redone first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditHistory {
+    /// Steps that can be undone
+    pub undo: Vec<HistoryStep>,
+    /// Steps that can be redone
+    pub redo: Vec<HistoryStep>,
+}
+
+impl EditHistory {
+    /// Checks that the history fits the document it will be restored with
+    ///
+    /// Every undo step is undone and every redo step redone on a copy of the
+    /// document. Fails with [`EditErrorKind::Conflict`](crate::EditErrorKind::Conflict) if a step does not apply.
+    pub(crate) fn validate(&self, document: &Document) -> Result<(), EditError> {
+        let mut past = document.clone();
+        for step in self.undo.iter().rev() {
+            step.undo(&mut past)?;
+        }
+        let mut future = document.clone();
+        for step in self.redo.iter().rev() {
+            step.redo(&mut future)?;
+        }
+        Ok(())
+    }
+}
+
+/// A compressed history entry that replays the node-level diff of a command
+///
+/// Patches only keep the nodes that changed, so they replace commands that
+/// hold large snapshots of the document for undo.
+pub(crate) struct PatchCommand {
+    document: Rc<RefCell<Document>>,
+    step: HistoryStep,
+}
+
+impl PatchCommand {
+    pub(crate) fn new(
+        document: Rc<RefCell<Document>>,
+        before: &Document,
+        after: &Document,
+    ) -> Self {
+        Self::from_step(document, HistoryStep::between(before, after))
+    }
+
+    pub(crate) fn from_step(document: Rc<RefCell<Document>>, step: HistoryStep) -> Self {
+        Self { document, step }
+    }
+
+    pub(crate) fn step(&self) -> &HistoryStep {
+        &self.step
+    }
+}
+
+impl EditorCommand for PatchCommand {
+    fn execute(&mut self) -> Result<(), EditError> {
+        self.step.redo(&mut self.document.borrow_mut())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        self.step.undo(&mut self.document.borrow_mut())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self) + estimate_bytes(&self.step)
+    }
+}
@@ -0,0 +1,197 @@
+use super::history::HistoryStep;
+use crate::{Document, EditError};
+use serde::Serialize;
+
+/// A document state in the [`HistoryTree`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryNode {
+    /// Identifier of the node, its index in [`HistoryTree::nodes`]
+    pub id: usize,
+    /// The state this one was reached from, `None` for the root
+    pub parent: Option<usize>,
+    /// States reached from this one, oldest first
+    pub children: Vec<usize>,
+    /// The edit leading here from the parent, `None` for the root
+    pub step: Option<HistoryStep>,
+    /// Names of the checkpoints taken at this state
+    pub checkpoints: Vec<String>,
+}
+
+/// Every state an editing session went through, as a tree
+///
+/// Linear undo forgets the redo stack as soon as a new edit is made after an
+/// undo. The tree keeps that abandoned branch as a sibling of the new edit,
+/// so a UI can show all branches and check any state out again with
+/// [`Editor::checkout_history`](super::Editor::checkout_history).
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryTree {
+    nodes: Vec<HistoryNode>,
+    current: usize,
+    /// The child redo follows from each node, the one visited last
+    #[serde(skip)]
+    active: Vec<Option<usize>>,
+    /// The document at the current node
+    #[serde(skip)]
+    snapshot: Document,
+}
+
+impl HistoryTree {
+    pub(crate) fn new(document: &Document) -> Self {
+        Self {
+            nodes: vec![HistoryNode {
+                id: 0,
+                parent: None,
+                children: Vec::new(),
+                step: None,
+                checkpoints: Vec::new(),
+            }],
+            current: 0,
+            active: vec![None],
+            snapshot: document.clone(),
+        }
+    }
+
+    /// All states, the root first
+    pub fn nodes(&self) -> &[HistoryNode] {
+        &self.nodes
+    }
+
+    /// The state with the given identifier
+    pub fn node(&self, id: usize) -> Option<&HistoryNode> {
+        self.nodes.get(id)
+    }
+
+    /// Identifier of the state the document is in
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Identifiers of the states without children, i.e. the branch tips
+    pub fn leaves(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .filter(|node| node.children.is_empty())
+            .map(|node| node.id)
+            .collect()
+    }
+
+    /// Identifiers from the root down to the given state
+    pub fn path(&self, id: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut next = self.nodes.get(id).map(|node| node.id);
+        while let Some(id) = next {
+            path.push(id);
+            next = self.nodes[id].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Records an edit from the current state to `after`
+    pub(crate) fn record(&mut self, after: &Document) {
+        let id = self.nodes.len();
+        self.nodes.push(HistoryNode {
+            id,
+            parent: Some(self.current),
+            children: Vec::new(),
+            step: Some(HistoryStep::between(&self.snapshot, after)),
+            checkpoints: Vec::new(),
+        });
+        self.active.push(None);
+        self.nodes[self.current].children.push(id);
+        self.active[self.current] = Some(id);
+        self.current = id;
+        self.snapshot = after.clone();
+    }
+
+    /// Folds a further change into the edit that led to the current state
+    pub(crate) fn amend(&mut self, after: &Document) {
+        let Some(step) = &self.nodes[self.current].step else {
+            return self.record(after);
+        };
+        let mut before = self.snapshot.clone();
+        if step.undo(&mut before).is_err() {
+            return self.record(after);
+        }
+        self.nodes[self.current].step = Some(HistoryStep::between(&before, after));
+        self.snapshot = after.clone();
+    }
+
+    /// Moves to the parent state after an undo
+    pub(crate) fn undo(&mut self, after: &Document) {
+        if let Some(parent) = self.nodes[self.current].parent {
+            self.current = parent;
+            self.snapshot = after.clone();
+        }
+    }
+
+    /// Moves to the active child after a redo
+    pub(crate) fn redo(&mut self, after: &Document) {
+        if let Some(child) = self.active[self.current] {
+            self.current = child;
+            self.snapshot = after.clone();
+        }
+    }
+
+    /// Labels the current state with a checkpoint name
+    pub(crate) fn mark(&mut self, name: &str) {
+        self.unmark(name);
+        self.nodes[self.current].checkpoints.push(name.to_string());
+    }
+
+    /// Removes a checkpoint name from whichever state carries it
+    pub(crate) fn unmark(&mut self, name: &str) {
+        for node in &mut self.nodes {
+            node.checkpoints.retain(|existing| existing != name);
+        }
+    }
+
+    /// Moves the document from the current state to `target`
+    ///
+    /// Steps are undone up to the common ancestor and redone down to the
+    /// target, which becomes the active branch. Returns the steps from the
+    /// root to the target and those redo would follow from it.
+    pub(crate) fn checkout(
+        &mut self,
+        document: &mut Document,
+        target: usize,
+    ) -> Result<(Vec<HistoryStep>, Vec<HistoryStep>), EditError> {
+        if target >= self.nodes.len() {
+            return Err(EditError::index_out_of_bounds());
+        }
+        let from = self.path(self.current);
+        let to = self.path(target);
+        let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+        let mut moved = document.clone();
+        for &id in from[common..].iter().rev() {
+            self.step(id).undo(&mut moved)?;
+        }
+        for &id in &to[common..] {
+            self.step(id).redo(&mut moved)?;
+        }
+        *document = moved;
+
+        for pair in to.windows(2) {
+            self.active[pair[0]] = Some(pair[1]);
+        }
+        self.current = target;
+        self.snapshot = document.clone();
+
+        let undo = to[1..].iter().map(|&id| self.step(id).clone()).collect();
+        let mut redo = Vec::new();
+        let mut next = self.active[target];
+        while let Some(id) = next {
+            redo.push(self.step(id).clone());
+            next = self.active[id];
+        }
+        Ok((undo, redo))
+    }
+
+    fn step(&self, id: usize) -> &HistoryStep {
+        self.nodes[id]
+            .step
+            .as_ref()
+            .expect("only the root has no step")
+    }
+}
@@ -0,0 +1,390 @@
+use super::Editor;
+use crate::{Document, EditError, Node, StatsDelta, TextFormatting};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+
+/// An edit request that can be sent to a [`DocumentHub`] from any thread
+#[derive(Debug, Clone)]
+pub enum HubCommand {
+    /// Insert text at a position within a node
+    InsertText {
+        /// Index of the target node
+        node_index: usize,
+        /// Byte offset to insert at, as with [`Editor::insert_text`]
+        position: usize,
+        /// Text to insert
+        text: String,
+    },
+    /// Delete a range of text within a node
+    DeleteText {
+        /// Index of the target node
+        node_index: usize,
+        /// Start of the range as a byte offset
+        start: usize,
+        /// End of the range as a byte offset
+        end: usize,
+    },
+    /// Apply formatting to a range of text within a node
+    FormatText {
+        /// Index of the target node
+        node_index: usize,
+        /// Start of the range as a byte offset
+        start: usize,
+        /// End of the range as a byte offset
+        end: usize,
+        /// Formatting to apply
+        formatting: TextFormatting,
+    },
+    /// Insert a node at a position in the document
+    InsertNode {
+        /// Index to insert the node at
+        position: usize,
+        /// The node to insert
+        node: Node,
+    },
+    /// Delete a node from the document
+    DeleteNode {
+        /// Index of the node to delete
+        node_index: usize,
+    },
+    /// Move a node to another position
+    MoveNode {
+        /// Current index of the node
+        from_index: usize,
+        /// Target index of the node
+        to_index: usize,
+    },
+    /// Undo the most recent command
+    Undo,
+    /// Redo the most recently undone command
+    Redo,
+}
+
+/// Notification broadcast to hub subscribers after each processed command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HubEvent {
+    /// A command was applied and the document changed
+    Changed {
+        /// Id of the handle that sent the command
+        source: usize,
+        /// Document revision after the change
+        revision: u64,
+        /// Content added and removed by the command
+        delta: StatsDelta,
+        /// Top-level nodes the command changed, see
+        /// [`Editor::last_affected_range`]
+        affected: Option<Range<usize>>,
+    },
+    /// A command failed and the document was left unchanged
+    Rejected {
+        /// Id of the handle that sent the command
+        source: usize,
+        /// Description of the failure
+        error: String,
+    },
+}
+
+/// Latest published document revision, shared between the hub and its handles
+type SharedSnapshot = Arc<RwLock<(u64, Arc<Document>)>>;
+
+/// A producer handle for submitting commands to a [`DocumentHub`]
+///
+/// Handles are cheap to clone and can be moved to other threads. Clones share
+/// the id of the handle they were cloned from.
+#[derive(Debug, Clone)]
+pub struct HubHandle {
+    id: usize,
+    sender: Sender<(usize, HubCommand)>,
+    snapshot: SharedSnapshot,
+}
+
+impl HubHandle {
+    /// Returns the id reported as the source of this handle's events
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Queues a command for execution by the hub
+    pub fn send(&self, command: HubCommand) -> Result<(), EditError> {
+        self.sender
+            .send((self.id, command))
+            .map_err(|_| EditError::other("Document hub has shut down"))
+    }
+
+    /// Returns the latest published snapshot of the document
+    pub fn snapshot(&self) -> Arc<Document> {
+        self.snapshot.read().unwrap().1.clone()
+    }
+
+    /// Returns the revision of the latest published snapshot
+    pub fn revision(&self) -> u64 {
+        self.snapshot.read().unwrap().0
+    }
+}
+
+/// Owns a document and serializes edits coming from many producers
+///
+/// Producers submit [`HubCommand`]s through [`HubHandle`]s, possibly from
+/// other threads. The hub executes them one at a time on its own editor, so
+/// commands never interleave and undo history stays linear. After each
+/// command the hub publishes an immutable snapshot and notifies subscribers.
+///
+/// # Example
+/// ```
+/// use md_core::{Document, DocumentHub, HubCommand};
+///
+/// let mut hub = DocumentHub::new(Document::new());
+/// let handle = hub.handle();
+/// let events = hub.subscribe();
+///
+/// std::thread::spawn(move || {
+///     handle
+///         .send(HubCommand::InsertNode {
+///             position: 0,
+///             node: md_core::Node::paragraph("From another thread"),
+///         })
+///         .unwrap();
+/// })
+/// .join()
+/// .unwrap();
+///
+/// assert_eq!(hub.process_pending(), 1);
+/// assert!(events.try_recv().is_ok());
+/// assert_eq!(hub.snapshot().nodes.len(), 1);
+/// ```
+pub struct DocumentHub {
+    editor: Editor,
+    sender: Sender<(usize, HubCommand)>,
+    receiver: Receiver<(usize, HubCommand)>,
+    subscribers: Vec<Sender<HubEvent>>,
+    snapshot: SharedSnapshot,
+    next_handle_id: usize,
+    revision: u64,
+}
+
+impl DocumentHub {
+    /// Creates a hub that owns the given document
+    pub fn new(document: Document) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let snapshot = Arc::new(RwLock::new((0, Arc::new(document.clone()))));
+
+        Self {
+            editor: Editor::new(document),
+            sender,
+            receiver,
+            subscribers: Vec::new(),
+            snapshot,
+            next_handle_id: 0,
+            revision: 0,
+        }
+    }
+
+    /// Creates a new producer handle with a unique id
+    pub fn handle(&mut self) -> HubHandle {
+        let id = self.next_handle_id;
+        self.next_handle_id += 1;
+
+        HubHandle {
+            id,
+            sender: self.sender.clone(),
+            snapshot: self.snapshot.clone(),
+        }
+    }
+
+    /// Registers a subscriber that receives an event for every processed command
+    pub fn subscribe(&mut self) -> Receiver<HubEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Returns the latest published snapshot of the document
+    pub fn snapshot(&self) -> Arc<Document> {
+        self.snapshot.read().unwrap().1.clone()
+    }
+
+    /// Returns the current document revision
+    ///
+    /// The revision increases by one for every command that succeeds.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Executes all queued commands without blocking
+    ///
+    /// Returns the number of commands processed.
+    pub fn process_pending(&mut self) -> usize {
+        let mut processed = 0;
+        while let Ok((source, command)) = self.receiver.try_recv() {
+            self.process(source, command);
+            processed += 1;
+        }
+        processed
+    }
+
+    /// Processes commands until every handle has been dropped
+    ///
+    /// Returns the final document.
+    pub fn run(mut self) -> Document {
+        let (placeholder, _) = mpsc::channel();
+        drop(std::mem::replace(&mut self.sender, placeholder));
+
+        while let Ok((source, command)) = self.receiver.recv() {
+            self.process(source, command);
+        }
+
+        self.editor.document().borrow().clone()
+    }
+
+    /// Executes one command, then publishes the result
+    fn process(&mut self, source: usize, command: HubCommand) {
+        let editor = &mut self.editor;
+        let result = match command {
+            HubCommand::InsertText {
+                node_index,
+                position,
+                text,
+            } => editor.insert_text(node_index, position, &text),
+            HubCommand::DeleteText {
+                node_index,
+                start,
+                end,
+            } => editor.delete_text(node_index, start, end),
+            HubCommand::FormatText {
+                node_index,
+                start,
+                end,
+                formatting,
+            } => editor.format_text(node_index, start, end, formatting),
+            HubCommand::InsertNode { position, node } => {
+                editor.insert_node(position, node).map(|_| ())
+            }
+            HubCommand::DeleteNode { node_index } => editor.delete_node(node_index),
+            HubCommand::MoveNode {
+                from_index,
+                to_index,
+            } => editor.move_node(from_index, to_index),
+            HubCommand::Undo => editor.undo(),
+            HubCommand::Redo => editor.redo(),
+        };
+
+        let event = match result {
+            Ok(()) => {
+                self.revision += 1;
+                let document = Arc::new(self.editor.document().borrow().clone());
+                *self.snapshot.write().unwrap() = (self.revision, document);
+                HubEvent::Changed {
+                    source,
+                    revision: self.revision,
+                    delta: self.editor.last_delta().clone(),
+                    affected: self.editor.last_affected_range(),
+                }
+            }
+            Err(err) => HubEvent::Rejected {
+                source,
+                error: err.to_string(),
+            },
+        };
+
+        // Subscribers that hung up are dropped
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_commands_from_many_threads() {
+        let mut hub = DocumentHub::new(Document::new());
+        let events = hub.subscribe();
+
+        let producers: Vec<_> = (0..4)
+            .map(|i| {
+                let handle = hub.handle();
+                thread::spawn(move || {
+                    for j in 0..5 {
+                        handle
+                            .send(HubCommand::InsertNode {
+                                position: 0,
+                                node: Node::paragraph(format!("{}-{}", i, j)),
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        // Runs on this thread until every producer has dropped its handle
+        let document = hub.run();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        assert_eq!(document.nodes.len(), 20);
+        assert_eq!(events.iter().count(), 20);
+    }
+
+    #[test]
+    fn test_events_and_snapshots() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Hello");
+        let mut hub = DocumentHub::new(doc);
+        let writer = hub.handle();
+        let reader = hub.handle();
+        let events = hub.subscribe();
+
+        writer
+            .send(HubCommand::InsertText {
+                node_index: 0,
+                position: 5,
+                text: " world".to_string(),
+            })
+            .unwrap();
+        writer
+            .send(HubCommand::DeleteNode { node_index: 9 })
+            .unwrap();
+        assert_eq!(hub.process_pending(), 2);
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            HubEvent::Changed {
+                source: writer.id(),
+                revision: 1,
+                delta: StatsDelta {
+                    words_added: 1,
+                    characters_added: 5,
+                    ..StatsDelta::default()
+                },
+                affected: Some(0..1),
+            }
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            HubEvent::Rejected { source, .. } if source == writer.id()
+        ));
+
+        let snapshot = reader.snapshot();
+        assert_eq!(reader.revision(), 1);
+        assert_eq!(snapshot.nodes[0].plain_text(), "Hello world");
+
+        // Snapshots are immutable; later edits publish a new one
+        reader.send(HubCommand::Undo).unwrap();
+        hub.process_pending();
+        assert_eq!(snapshot.nodes[0].plain_text(), "Hello world");
+        assert_eq!(hub.snapshot().nodes[0].plain_text(), "Hello");
+        assert_eq!(hub.revision(), 2);
+    }
+
+    #[test]
+    fn test_send_after_shutdown() {
+        let mut hub = DocumentHub::new(Document::new());
+        let handle = hub.handle();
+        drop(hub);
+        assert!(handle.send(HubCommand::Undo).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{HeadingProperties, InlineNode, ListItem, ListType, Node, NodeConversionType};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(#{1,6}) $").unwrap());
+static BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[-*+] $").unwrap());
+static ORDERED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{1,9})[.)] $").unwrap());
+
+/// A Markdown shortcut that converts the paragraph it is typed at the
+/// start of
+///
+/// A rule fires when the text before the cursor is exactly its shortcut,
+/// which is then removed and the rest of the paragraph kept as the content
+/// of the new block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputRule {
+    /// `#` to `######` and a space: a heading of that level
+    Heading,
+    /// `-`, `*` or `+` and a space: a bullet list
+    BulletList,
+    /// A number, `.` or `)` and a space: an ordered list starting at that
+    /// number
+    OrderedList,
+    /// Three backticks: a code block
+    CodeBlock,
+    /// `>` and a space: a blockquote
+    BlockQuote,
+    /// A custom shortcut and the block it converts the paragraph to
+    Prefix {
+        prefix: String,
+        target: NodeConversionType,
+    },
+}
+
+impl InputRule {
+    /// Creates a rule for a custom shortcut, e.g. `[] ` for a task list
+    pub fn prefix(prefix: impl Into<String>, target: NodeConversionType) -> Self {
+        Self::Prefix {
+            prefix: prefix.into(),
+            target,
+        }
+    }
+
+    /// The conversion to make if `typed`, the text before the cursor, is
+    /// the rule's shortcut, with the start number of ordered lists
+    fn apply(&self, typed: &str) -> Option<(NodeConversionType, Option<u64>)> {
+        match self {
+            Self::Heading => {
+                let level = HEADING.captures(typed)?[1].len() as u8;
+                Some((NodeConversionType::Heading(level), None))
+            }
+            Self::BulletList => BULLET
+                .is_match(typed)
+                .then_some((NodeConversionType::List(ListType::Unordered), None)),
+            Self::OrderedList => {
+                let start: u64 = ORDERED.captures(typed)?[1].parse().ok()?;
+                Some((
+                    NodeConversionType::List(ListType::Ordered),
+                    (start != 1).then_some(start),
+                ))
+            }
+            Self::CodeBlock => {
+                (typed == "```").then(|| (NodeConversionType::CodeBlock(String::new()), None))
+            }
+            Self::BlockQuote => (typed == "> ").then_some((NodeConversionType::BlockQuote, None)),
+            Self::Prefix { prefix, target } => {
+                (!prefix.is_empty() && typed == prefix).then(|| (target.clone(), None))
+            }
+        }
+    }
+}
+
+/// An ordered set of input rules
+///
+/// Rules are tried in the order they were added and the first one whose
+/// shortcut was typed wins.
+#[derive(Debug, Clone, Default)]
+pub struct InputRules {
+    rules: Vec<InputRule>,
+}
+
+impl InputRules {
+    /// Creates an engine without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an engine with the shortcuts for headings, bullet and
+    /// ordered lists, code blocks and blockquotes
+    pub fn with_defaults() -> Self {
+        Self::new()
+            .with_rule(InputRule::Heading)
+            .with_rule(InputRule::BulletList)
+            .with_rule(InputRule::OrderedList)
+            .with_rule(InputRule::CodeBlock)
+            .with_rule(InputRule::BlockQuote)
+    }
+
+    /// Adds a rule, returning the engine
+    pub fn with_rule(mut self, rule: InputRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds a rule
+    pub fn add_rule(&mut self, rule: InputRule) {
+        self.rules.push(rule);
+    }
+
+    /// Removes all rules
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Returns the configured rules
+    pub fn rules(&self) -> &[InputRule] {
+        &self.rules
+    }
+
+    /// Finds the conversion for a paragraph whose text before the cursor
+    /// is `typed`, with the start number of ordered lists
+    pub(crate) fn find(&self, typed: &str) -> Option<(NodeConversionType, Option<u64>)> {
+        self.rules.iter().find_map(|rule| rule.apply(typed))
+    }
+}
+
+/// Turns the content of a paragraph into the block an input rule converts
+/// it to, returning the block and the path within it of the text the
+/// cursor goes to
+pub(crate) fn convert(
+    children: Vec<InlineNode>,
+    target: NodeConversionType,
+    start: Option<u64>,
+) -> (Node, Vec<usize>) {
+    match target {
+        NodeConversionType::Paragraph => (Node::Paragraph { children }, Vec::new()),
+        NodeConversionType::Heading(level) => (
+            Node::Heading {
+                level,
+                children,
+                properties: HeadingProperties::default(),
+            },
+            Vec::new(),
+        ),
+        NodeConversionType::List(list_type) => {
+            let checked = (list_type == ListType::Task).then_some(false);
+            let item = ListItem {
+                children: vec![Node::Paragraph { children }],
+                checked,
+            };
+            (
+                Node::List {
+                    list_type,
+                    items: vec![item],
+                    start,
+                    loose: false,
+                },
+                vec![0, 0],
+            )
+        }
+        NodeConversionType::CodeBlock(language) => (
+            Node::code_block(InlineNode::plain_text_of(&children), language),
+            Vec::new(),
+        ),
+        NodeConversionType::BlockQuote => (
+            Node::BlockQuote {
+                children: vec![Node::Paragraph { children }],
+            },
+            vec![0],
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_rules() {
+        let rules = InputRules::with_defaults();
+        assert_eq!(
+            rules.find("### "),
+            Some((NodeConversionType::Heading(3), None))
+        );
+        assert_eq!(rules.find("####### "), None);
+        assert_eq!(rules.find("#"), None);
+        assert_eq!(
+            rules.find("* "),
+            Some((NodeConversionType::List(ListType::Unordered), None))
+        );
+        assert_eq!(
+            rules.find("1. "),
+            Some((NodeConversionType::List(ListType::Ordered), None))
+        );
+        assert_eq!(
+            rules.find("3) "),
+            Some((NodeConversionType::List(ListType::Ordered), Some(3)))
+        );
+        assert_eq!(
+            rules.find("```"),
+            Some((NodeConversionType::CodeBlock(String::new()), None))
+        );
+        assert_eq!(
+            rules.find("> "),
+            Some((NodeConversionType::BlockQuote, None))
+        );
+        assert_eq!(rules.find("a > "), None);
+
+        let rules = InputRules::new().with_rule(InputRule::prefix(
+            "[] ",
+            NodeConversionType::List(ListType::Task),
+        ));
+        assert_eq!(
+            rules.find("[] "),
+            Some((NodeConversionType::List(ListType::Task), None))
+        );
+        assert_eq!(rules.find("# "), None);
+    }
+}
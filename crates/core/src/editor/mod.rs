@@ -1,12 +1,38 @@
+mod autocorrect;
+mod autosave;
+mod coalesce;
 mod command;
 mod commands;
+mod cursor;
+mod history;
+mod history_tree;
+mod hub;
+mod input_rules;
+mod navigation;
+mod pins;
+mod session;
+mod snippets;
 mod transaction;
 
-use crate::error::EditError;
-use crate::{Document, ListType, Node, TableAlignment, TableProperties, TextFormatting};
+use crate::error::{EditError, EditErrorKind};
+use crate::fragment::slice_node;
+use crate::mapping::{DocumentTexts, NodeChanges, PositionMapper, RangeMode};
+use crate::offsets::{check_position, offset_text};
+use crate::spellcheck::AnnotationSet;
+use crate::stats::{DocumentStats, StatsDelta, StatsTracker};
+use crate::store::{DocumentStore, StoreError};
+use crate::{
+    AltTextFix, Annotation, CalloutKind, CellValue, CleanupOptions, CodeBlockProperties,
+    CodeBlockRename, Comment, ConflictSide, Counts, Document, DocumentFragment, FigureProperties,
+    ImageInfo, InlineNode, LinkTitle, ListType, Node, NodeAttributes, OffsetKind, Orphan,
+    OutlineDrop, Position, SectionMove, Selection, SnippetRegistry, TableAlignment, TableCursor,
+    TableProperties, Terminology, TextFormatting, TrackedChange, UrlPolicy, Visibility,
+};
+use coalesce::OpenGroup;
 use command::Command as EditorCommand;
 use command::{DeleteTextCommand, MergeNodesCommand};
 use commands::AddTaskItemCommand;
+use commands::ApplyCorrectionCommand;
 use commands::ConvertNodeTypeCommand;
 use commands::CopySelectionCommand;
 use commands::CreateTOCCommand;
@@ -16,38 +42,117 @@ use commands::DeleteNodeCommand;
 use commands::DuplicateNodeCommand;
 use commands::EditTaskItemCommand;
 use commands::FindReplaceCommand;
+use commands::FixTerminologyCommand;
 use commands::FormatTextCommand;
 use commands::GroupNodesCommand;
 use commands::IndentDirection;
 use commands::IndentTaskItemCommand;
+use commands::InsertInlineCommand;
 use commands::InsertNodeCommand;
 use commands::InsertTextCommand;
+use commands::MergeAdjacentCommand;
 use commands::MoveNodeCommand;
+use commands::MoveSectionCommand;
 use commands::MoveTaskItemCommand;
 use commands::MoveTaskPositionCommand;
+use commands::RemoveHtmlCommentsCommand;
 use commands::RemoveTaskItemCommand;
+use commands::RenameSymbolCommand;
 use commands::SelectionFormatCommand;
 use commands::SelectionIndentCommand;
+use commands::SetCodeBlockPropertiesCommand;
+use commands::SetLinkTitlesCommand;
+use commands::SetVisibilityCommand;
+use commands::SetWordTargetCommand;
 use commands::TableOperation;
 use commands::TableOperationsCommand;
 use commands::ToggleTaskCommand;
+use commands::UpdateImagesCommand;
 use commands::sort_task_list::SortTaskListCommand;
+use commands::{BulkTaskAction, BulkTaskCommand};
+use commands::{CommentAction, CommentCommand};
+use commands::{ListOperation, ListOperationsCommand};
+use commands::{MultiSelectionCommand, SelectionEdit};
+use commands::{TrackChangesCommand, TrackedEdit};
+use cursor::position_key;
+pub use history::{EditHistory, HistoryStep};
+use history::{HistoryEntry, PatchCommand};
+pub use history_tree::{HistoryNode, HistoryTree};
+use navigation::NavigationHistory;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 // Export the Transaction type
+pub use autocorrect::{Autocorrect, AutocorrectRule, Correction};
+use autosave::Autosave;
+pub use autosave::AutosavePolicy;
+pub use coalesce::CoalescePolicy;
+pub use commands::MergeOptions;
 pub use commands::SortCriteria;
+pub use cursor::CursorMotion;
+pub use hub::{DocumentHub, HubCommand, HubEvent, HubHandle};
+pub use input_rules::{InputRule, InputRules};
+pub use pins::PinAnchor;
+use pins::Pins;
+pub use session::{Bookmark, EditorSession};
+use snippets::TabStops;
 pub use transaction::Transaction;
 
 /// Editor manages a document and provides operations to modify it
 pub struct Editor {
     document: Rc<RefCell<Document>>,
-    undo_stack: Vec<Box<dyn EditorCommand>>,
-    redo_stack: Vec<Box<dyn EditorCommand>>,
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
     max_history: usize,
+    history_budget: Option<usize>,
+    /// Least time between two compressions of the history for the budget
+    compression_interval: Option<Duration>,
+    /// When the history was last compressed for the budget
+    last_compression: Option<Instant>,
+    stats: StatsTracker,
+    last_delta: StatsDelta,
+    session_delta: StatsDelta,
+    autocorrect: Autocorrect,
+    input_rules: InputRules,
+    url_policy: UrlPolicy,
+    history_tree: Option<HistoryTree>,
+    checkpoints: Vec<(String, Document)>,
+    coalescing: Option<CoalescePolicy>,
+    open_group: Option<OpenGroup>,
+    bookmarks: Vec<Bookmark>,
+    navigation: NavigationHistory,
+    pins: Pins,
+    scroll_anchor: Option<Position>,
+    annotations: AnnotationSet,
+    /// Texts of the top-level nodes, to map positions through edits
+    texts: DocumentTexts,
+    /// Maps positions from before the last change to after it
+    mapper: PositionMapper,
+    /// Selection before the change being made, to tell whether the change
+    /// moved it itself
+    selection_before: Option<Selection>,
+    /// Further selections before the change being made
+    selections_before: Vec<Selection>,
+    /// Comments when the snapshot was taken
+    snapshot_comments: Vec<Comment>,
+    /// Tracked changes when the snapshot was taken
+    snapshot_changes: Vec<TrackedChange>,
+    /// Node attributes when the snapshot was taken
+    snapshot_attributes: BTreeMap<usize, NodeAttributes>,
+    /// Author of suggested edits while changes are tracked
+    track_changes: Option<String>,
+    /// Top-level nodes the last command, undo or redo changed
+    last_affected: Option<std::ops::Range<usize>>,
+    autosave: Option<Autosave>,
+    snippets: SnippetRegistry,
+    /// Tab stops of the last inserted snippet still to visit
+    tab_stops: Option<TabStops>,
 }
 
 /// Enum representing node conversion types
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NodeConversionType {
     /// Convert to paragraph
     Paragraph,
@@ -64,11 +169,45 @@ pub enum NodeConversionType {
 impl Editor {
     /// Creates a new editor instance with the given document
     pub fn new(document: Document) -> Self {
+        let texts = DocumentTexts::new(&document);
+        let snapshot_comments = document.comments.clone();
+        let snapshot_changes = document.tracked_changes.clone();
+        let snapshot_attributes = document.node_attributes.clone();
         Self {
+            stats: StatsTracker::new(&document),
             document: Rc::new(RefCell::new(document)),
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             max_history: 100, // Default history limit
+            history_budget: None,
+            compression_interval: None,
+            last_compression: None,
+            last_delta: StatsDelta::default(),
+            session_delta: StatsDelta::default(),
+            autocorrect: Autocorrect::new(),
+            input_rules: InputRules::new(),
+            url_policy: UrlPolicy::default(),
+            history_tree: None,
+            checkpoints: Vec::new(),
+            coalescing: None,
+            open_group: None,
+            bookmarks: Vec::new(),
+            navigation: NavigationHistory::default(),
+            pins: Pins::default(),
+            scroll_anchor: None,
+            annotations: AnnotationSet::default(),
+            texts,
+            mapper: PositionMapper::default(),
+            selection_before: None,
+            selections_before: Vec::new(),
+            snapshot_comments,
+            snapshot_changes,
+            snapshot_attributes,
+            track_changes: None,
+            last_affected: None,
+            autosave: None,
+            snippets: SnippetRegistry::default(),
+            tab_stops: None,
         }
     }
 
@@ -93,1782 +232,7129 @@ impl Editor {
         }
     }
 
-    /// Delete text from a specific node
-    pub fn delete_text(
-        &mut self,
-        node_index: usize,
-        start: usize,
-        end: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(DeleteTextCommand::new(
-            self.document.clone(),
-            node_index,
-            start,
-            end,
-        ));
-        self.execute_command(command)
+    /// Set a memory budget in bytes for the undo and redo history
+    ///
+    /// When the estimated memory retained by the history exceeds the budget,
+    /// uncompressed entries are first compressed into patches and then the
+    /// oldest entries are dropped. The most recent entry is always kept.
+    /// Pass `None` to only limit history by entry count.
+    pub fn set_history_budget(&mut self, budget: Option<usize>) {
+        self.history_budget = budget;
+        self.last_compression = None;
+        self.enforce_history_budget();
     }
 
-    /// Merge two adjacent nodes of the same type
-    pub fn merge_nodes(
-        &mut self,
-        first_index: usize,
-        second_index: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(MergeNodesCommand::new(
-            self.document.clone(),
-            first_index,
-            second_index,
-        ));
-        self.execute_command(command)
+    /// Limit how often the history is compressed to fit the memory budget
+    ///
+    /// Compressing replays the uncompressed entries against copies of the
+    /// document, which is too slow to do on every keystroke of a large
+    /// document. With an interval, the history may grow past the budget
+    /// until the interval has passed since the last compression, or until
+    /// it reaches twice the budget. Pass `None` to compress whenever the
+    /// history exceeds the budget.
+    pub fn set_history_compression_interval(&mut self, interval: Option<Duration>) {
+        self.compression_interval = interval;
     }
 
-    /// Format text within a paragraph
-    pub fn format_text(
-        &mut self,
-        node_index: usize,
-        start: usize,
-        end: usize,
-        formatting: TextFormatting,
-    ) -> Result<(), EditError> {
-        let command = Box::new(FormatTextCommand::new(
-            self.document.clone(),
-            node_index,
-            start,
-            end,
-            formatting,
-        ));
-        self.execute_command(command)
+    /// Get the estimated bytes of memory retained by the undo and redo history
+    pub fn history_bytes(&self) -> usize {
+        self.undo_stack
+            .iter()
+            .chain(&self.redo_stack)
+            .map(|entry| entry.bytes)
+            .sum()
     }
 
-    /// Move a node from one position to another
-    pub fn move_node(&mut self, from_index: usize, to_index: usize) -> Result<(), EditError> {
-        let command = Box::new(MoveNodeCommand::new(
-            self.document.clone(),
-            from_index,
-            to_index,
-        ));
-        self.execute_command(command)
+    /// Get the number of operations that can be undone
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
     }
 
-    /// Convert a node from one type to another
-    pub fn convert_node_type(
-        &mut self,
-        node_index: usize,
-        target_type: NodeConversionType,
-    ) -> Result<(), EditError> {
-        let command = Box::new(ConvertNodeTypeCommand::new(
-            self.document.clone(),
-            node_index,
-            target_type,
-        ));
-        self.execute_command(command)
+    /// Get the number of operations that can be redone
+    pub fn redo_len(&self) -> usize {
+        self.redo_stack.len()
     }
 
-    /// Delete a node entirely
-    pub fn delete_node(&mut self, node_index: usize) -> Result<(), EditError> {
-        let command = Box::new(DeleteNodeCommand::new(self.document.clone(), node_index));
-        self.execute_command(command)
+    /// Discard all undo and redo history
+    ///
+    /// A history tree, if enabled, restarts from the current document.
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.open_group = None;
+        if self.history_tree.is_some() {
+            self.history_tree = Some(HistoryTree::new(&self.document.borrow()));
+        }
     }
 
-    /// Find and replace text across the document
-    /// Returns the number of replacements made
-    pub fn find_replace(&mut self, find: &str, replace: &str, case_sensitive: bool) -> usize {
-        let mut fr_command =
-            FindReplaceCommand::new(self.document.clone(), find, replace, case_sensitive);
-
-        // Execute the command
-        match fr_command.execute() {
-            Ok(_) => {
-                let replacements = fr_command.replacements();
+    /// Enable or disable the history tree
+    ///
+    /// While enabled, every edit is also recorded in a [`HistoryTree`] that
+    /// keeps branches abandoned by editing after an undo. The tree starts at
+    /// the current document; disabling it discards it.
+    pub fn set_history_tree(&mut self, enabled: bool) {
+        if !enabled {
+            self.history_tree = None;
+        } else if self.history_tree.is_none() {
+            self.history_tree = Some(HistoryTree::new(&self.document.borrow()));
+        }
+    }
 
-                // Add to undo stack
-                self.undo_stack.push(Box::new(fr_command));
-                self.redo_stack.clear();
+    /// Get the history tree, if enabled
+    pub fn history_tree(&self) -> Option<&HistoryTree> {
+        self.history_tree.as_ref()
+    }
 
-                // Trim history if needed
-                if self.undo_stack.len() > self.max_history {
-                    self.undo_stack.remove(0);
-                }
+    /// Move the document to any state of the history tree
+    ///
+    /// The undo stack is rebuilt from the path to that state and the redo
+    /// stack from the branch below it that was visited last. Fails with
+    /// [`EditErrorKind::UnsupportedOperation`](crate::EditErrorKind::UnsupportedOperation) if the tree is not enabled.
+    pub fn checkout_history(&mut self, node_id: usize) -> Result<(), EditError> {
+        self.begin_change();
+        let tree = self.history_tree.as_mut().ok_or(EditError::unsupported())?;
+        let (undo, redo) = tree.checkout(&mut self.document.borrow_mut(), node_id)?;
+
+        let skip = undo.len().saturating_sub(self.max_history);
+        self.undo_stack = self.patch_entries(undo.into_iter().skip(skip));
+        self.redo_stack = self.patch_entries(redo.into_iter().rev());
+        self.record_delta(NodeChanges::default());
+        Ok(())
+    }
 
-                replacements
-            }
-            Err(_) => 0,
+    /// Remember the current document under a name
+    ///
+    /// Taking a checkpoint with an existing name replaces it.
+    pub fn checkpoint(&mut self, name: &str) {
+        let document = self.document.borrow().clone();
+        self.checkpoints.retain(|(existing, _)| existing != name);
+        self.checkpoints.push((name.to_string(), document));
+        if let Some(tree) = &mut self.history_tree {
+            tree.mark(name);
         }
     }
 
-    /// Undo the last operation
-    pub fn undo(&mut self) -> Result<(), EditError> {
-        if let Some(mut command) = self.undo_stack.pop() {
-            command.undo()?;
-            self.redo_stack.push(command);
-            Ok(())
-        } else {
-            Err(EditError::Other("Nothing to undo".to_string()))
+    /// Names of the checkpoints, oldest first
+    pub fn checkpoints(&self) -> Vec<&str> {
+        self.checkpoints
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Forget a checkpoint, returning whether it existed
+    pub fn remove_checkpoint(&mut self, name: &str) -> bool {
+        let before = self.checkpoints.len();
+        self.checkpoints.retain(|(existing, _)| existing != name);
+        if let Some(tree) = &mut self.history_tree {
+            tree.unmark(name);
         }
+        self.checkpoints.len() != before
     }
 
-    /// Redo the last undone operation
-    pub fn redo(&mut self) -> Result<(), EditError> {
-        if let Some(mut command) = self.redo_stack.pop() {
-            command.execute()?;
-            self.undo_stack.push(command);
-            Ok(())
-        } else {
-            Err(EditError::Other("Nothing to redo".to_string()))
+    /// Restore the document to a checkpoint as a single undoable step
+    ///
+    /// Edits made since the checkpoint stay in the history, so the revert
+    /// itself can be undone.
+    pub fn revert_to_checkpoint(&mut self, name: &str) -> Result<(), EditError> {
+        let target = self
+            .checkpoints
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, document)| document.clone())
+            .ok_or_else(|| EditError::other(format!("No checkpoint named '{}'", name)))?;
+
+        let current = self.document.borrow().clone();
+        if current == target {
+            return Ok(());
         }
+        let command = PatchCommand::new(self.document.clone(), &current, &target);
+        self.execute_command(Box::new(command))
     }
 
-    /// Execute a command and add it to the undo stack
-    fn execute_command(&mut self, mut command: Box<dyn EditorCommand>) -> Result<(), EditError> {
-        command.execute()?;
+    /// Wrap history steps into compressed patch entries
+    fn patch_entries(&self, steps: impl IntoIterator<Item = HistoryStep>) -> Vec<HistoryEntry> {
+        steps
+            .into_iter()
+            .map(|step| {
+                let selection_before = step.selection_before.clone();
+                let selection_after = step.selection_after.clone();
+                let patch = PatchCommand::from_step(self.document.clone(), step);
+                let mut entry = HistoryEntry::new(Box::new(patch));
+                entry.compressed = true;
+                entry.selection_before = selection_before;
+                entry.selection_after = selection_after;
+                entry
+            })
+            .collect()
+    }
 
-        self.undo_stack.push(command);
-        self.redo_stack.clear();
+    /// Export the undo and redo history as serializable steps
+    ///
+    /// Every entry is first turned into a node-level patch by replaying the
+    /// history once, and the editor keeps the patches, so the exported steps
+    /// behave exactly like the live history. Fails if a command can no longer
+    /// be undone or redone, leaving the document unchanged.
+    pub fn history(&mut self) -> Result<EditHistory, EditError> {
+        self.patch_history()?;
+
+        let steps = |stack: &[HistoryEntry]| {
+            stack
+                .iter()
+                .filter_map(|entry| {
+                    let patch = entry.command.as_any().downcast_ref::<PatchCommand>()?;
+                    Some(HistoryStep {
+                        selection_before: entry.selection_before.clone(),
+                        selection_after: entry.selection_after.clone(),
+                        ..patch.step().clone()
+                    })
+                })
+                .collect()
+        };
+        Ok(EditHistory {
+            undo: steps(&self.undo_stack),
+            redo: steps(&self.redo_stack),
+        })
+    }
 
-        // Trim history if needed
-        if self.undo_stack.len() > self.max_history {
-            self.undo_stack.remove(0);
+    /// Creates an editor that resumes a session with its undo history intact
+    ///
+    /// `history` must have been exported from an editor whose document was
+    /// `document`. Fails with [`EditErrorKind::Conflict`](crate::EditErrorKind::Conflict) if any step does not
+    /// apply to it.
+    pub fn with_history(document: Document, history: EditHistory) -> Result<Self, EditError> {
+        history.validate(&document)?;
+
+        let mut editor = Self::new(document);
+        editor.undo_stack = editor.patch_entries(history.undo);
+        editor.redo_stack = editor.patch_entries(history.redo);
+        Ok(editor)
+    }
+
+    /// Capture the selection, folds, bookmarks and scroll anchor
+    pub fn session(&self) -> EditorSession {
+        let document = self.document.borrow();
+        EditorSession {
+            content_hash: document.content_hash(),
+            selection: document.selection.clone(),
+            folded: document.folded_sections(),
+            bookmarks: self.bookmarks.clone(),
+            scroll_anchor: self.scroll_anchor.clone(),
+            history_index: self.undo_stack.len(),
         }
+    }
 
+    /// Restore a session captured with [`Editor::session`]
+    ///
+    /// Fails with [`EditErrorKind::Conflict`] if the document's content no
+    /// longer matches the one the session was taken on, leaving the editor
+    /// unchanged.
+    pub fn restore_session(&mut self, session: &EditorSession) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        if !session.matches(&document) {
+            return Err(EditError::from(EditErrorKind::Conflict)
+                .with_message("Session was taken on different content"));
+        }
+        document.selection = session.selection.clone();
+        drop(document);
+        let len = self.document.borrow().nodes.len();
+        for index in 0..len {
+            // Nodes that can't be folded are skipped like missing ones
+            let _ = self.set_folded(index, session.folded.contains(&index));
+        }
+        self.bookmarks = session.bookmarks.clone();
+        self.scroll_anchor = session.scroll_anchor.clone();
         Ok(())
     }
 
-    /// Insert text at a specific position in a node
-    pub fn insert_text(
+    /// Save the document into `store` as `id` whenever `policy` says so
+    ///
+    /// Replaces any earlier autosave target. Saves made while recording a
+    /// change can't fail the change; their errors are reported by the next
+    /// [`poll_autosave`](Self::poll_autosave).
+    pub fn enable_autosave(
         &mut self,
-        node_index: usize,
-        position: usize,
-        text: &str,
-    ) -> Result<(), EditError> {
-        let command = Box::new(InsertTextCommand::new(
-            self.document.clone(),
-            node_index,
-            position,
-            text.to_string(),
-        ));
-        self.execute_command(command)
+        store: impl DocumentStore + 'static,
+        id: impl Into<String>,
+        policy: AutosavePolicy,
+    ) {
+        self.autosave = Some(Autosave::new(Box::new(store), id.into(), policy, None));
     }
 
-    /// Insert a new node at a specific position in the document
-    pub fn insert_node(&mut self, position: usize, node: Node) -> Result<(), EditError> {
-        let command = Box::new(InsertNodeCommand::new(
-            self.document.clone(),
-            position,
-            node,
-        ));
-        self.execute_command(command)
+    /// Stop autosaving, returning the store
+    pub fn disable_autosave(&mut self) -> Option<Box<dyn DocumentStore>> {
+        self.autosave.take().map(Autosave::into_store)
     }
 
-    /// Insert a new paragraph with text at a specific position
-    pub fn insert_paragraph(&mut self, position: usize, text: &str) -> Result<(), EditError> {
-        let command = Box::new(InsertNodeCommand::new_paragraph(
-            self.document.clone(),
-            position,
-            text,
-        ));
-        self.execute_command(command)
+    /// Save the document if it changed and was then left alone for the
+    /// policy's debounce, returning the new revision
+    ///
+    /// Call this regularly, e.g. from an idle timer. Reports the failure of
+    /// an automatic save made since the last call first.
+    pub fn poll_autosave(&mut self) -> Result<Option<u64>, StoreError> {
+        match &mut self.autosave {
+            Some(autosave) => autosave.poll(&self.document.borrow(), std::time::Instant::now()),
+            None => Ok(None),
+        }
     }
 
-    /// Insert a new heading with text at a specific position
-    pub fn insert_heading(
-        &mut self,
-        position: usize,
-        level: u8,
-        text: &str,
-    ) -> Result<(), EditError> {
-        let command = Box::new(InsertNodeCommand::new_heading(
-            self.document.clone(),
-            position,
-            level,
-            text,
-        ));
-        self.execute_command(command)
+    /// Save the document into the autosave store right away, returning the
+    /// new revision, or `None` when autosave is off
+    pub fn save_now(&mut self) -> Result<Option<u64>, StoreError> {
+        match &mut self.autosave {
+            Some(autosave) => autosave.save(&self.document.borrow()).map(Some),
+            None => Ok(None),
+        }
     }
 
-    /// Insert a new code block at a specific position
-    pub fn insert_code_block(
-        &mut self,
-        position: usize,
-        code: &str,
-        language: &str,
-    ) -> Result<(), EditError> {
-        let command = Box::new(InsertNodeCommand::new_code_block(
-            self.document.clone(),
-            position,
-            code,
-            language,
-        ));
-        self.execute_command(command)
+    /// Returns true if autosave is on and changes were made since the last
+    /// save
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.autosave
+            .as_ref()
+            .is_some_and(Autosave::has_unsaved_changes)
     }
 
-    /// Duplicate a node at a specific index
-    pub fn duplicate_node(&mut self, node_index: usize) -> Result<(), EditError> {
-        let command = Box::new(DuplicateNodeCommand::new(self.document.clone(), node_index));
-        self.execute_command(command)
+    /// The revision the last autosave or recovery stored or loaded
+    pub fn last_saved_revision(&self) -> Option<u64> {
+        self.autosave.as_ref().and_then(Autosave::last_revision)
     }
 
-    /// Cut the currently selected content
-    /// Returns a vector of nodes that were cut
-    pub fn cut_selection(&mut self) -> Vec<Node> {
-        let mut cut_cmd = CutSelectionCommand::new(self.document.clone());
+    /// Recover the latest revision of `id` from `store` after a crash,
+    /// autosaving into it from then on
+    ///
+    /// Fails with [`StoreError::NotFound`] if nothing was saved as `id`.
+    pub fn recover(
+        store: impl DocumentStore + 'static,
+        id: impl Into<String>,
+        policy: AutosavePolicy,
+    ) -> Result<Self, StoreError> {
+        let id = id.into();
+        let Some(latest) = store.latest_revision(&id)? else {
+            return Err(StoreError::NotFound { id, revision: None });
+        };
+        let document = store.load(&id, Some(latest.revision))?;
+        let mut editor = Self::new(document);
+        editor.autosave = Some(Autosave::new(
+            Box::new(store),
+            id,
+            policy,
+            Some(latest.revision),
+        ));
+        Ok(editor)
+    }
 
-        match cut_cmd.execute() {
-            Ok(_) => {
-                let cut_content = cut_cmd.cut_content().to_vec();
+    /// Fold a top-level heading or code block, hiding its content in the UI
+    ///
+    /// The fold is the `collapsed` flag of the node's properties, as with
+    /// [`Document::fold_section`], so it moves with the node and is saved
+    /// with the document. Folding isn't an edit and can't be undone.
+    pub fn fold(&mut self, node_index: usize) -> Result<(), EditError> {
+        self.set_folded(node_index, true).map(|_| ())
+    }
 
-                // Add to undo stack
-                self.undo_stack.push(Box::new(cut_cmd));
-                self.redo_stack.clear();
+    /// Unfold a top-level node, returning whether it was folded
+    pub fn unfold(&mut self, node_index: usize) -> bool {
+        self.set_folded(node_index, false).unwrap_or(false)
+    }
 
-                // Trim history if needed
-                if self.undo_stack.len() > self.max_history {
-                    self.undo_stack.remove(0);
-                }
+    /// Returns true if the top-level node is folded
+    pub fn is_folded(&self, node_index: usize) -> bool {
+        self.document.borrow().is_section_folded(node_index)
+    }
 
-                cut_content
-            }
-            Err(_) => Vec::new(),
-        }
+    /// Indices of the folded top-level nodes, ascending
+    pub fn folded(&self) -> Vec<usize> {
+        self.document.borrow().folded_sections()
     }
 
-    /// Copy the currently selected content without modifying the document
-    /// Returns a vector of nodes that were copied
-    pub fn copy_selection(&mut self) -> Vec<Node> {
-        let mut copy_cmd = CopySelectionCommand::new(self.document.clone());
+    /// Set the fold flag of a node, returning whether it was folded
+    fn set_folded(&mut self, node_index: usize, folded: bool) -> Result<bool, EditError> {
+        let mut document = self.document.borrow_mut();
+        let was_folded = document.is_section_folded(node_index);
+        if folded {
+            document.fold_section(node_index)?;
+        } else {
+            document.unfold_section(node_index)?;
+        }
+        // The flag is part of the node's hash; record it so the next
+        // change doesn't take the node as edited
+        self.texts.update(
+            &document,
+            NodeChanges {
+                affected: Some(node_index..node_index + 1),
+                order: None,
+            },
+        );
+        Ok(was_folded)
+    }
 
-        match copy_cmd.execute() {
-            Ok(_) => {
-                // Since copy doesn't modify the document, we don't add it to the undo stack
-                copy_cmd.get_copied_nodes().to_vec()
-            }
-            Err(_) => Vec::new(),
+    /// Set a bookmark, replacing any bookmark with the same name
+    pub fn set_bookmark(&mut self, name: impl Into<String>, position: Position) {
+        let name = name.into();
+        match self
+            .bookmarks
+            .iter_mut()
+            .find(|bookmark| bookmark.name == name)
+        {
+            Some(bookmark) => bookmark.position = position,
+            None => self.bookmarks.push(Bookmark { name, position }),
         }
     }
 
-    /// Apply formatting to the selected text
-    pub fn format_selection(&mut self, formatting: TextFormatting) -> Result<(), EditError> {
-        let command = Box::new(SelectionFormatCommand::new(
-            self.document.clone(),
-            formatting,
-        ));
-        self.execute_command(command)
+    /// Remove a bookmark, returning whether it existed
+    pub fn remove_bookmark(&mut self, name: &str) -> bool {
+        let before = self.bookmarks.len();
+        self.bookmarks.retain(|bookmark| bookmark.name != name);
+        self.bookmarks.len() != before
     }
 
-    /// Increase the indentation of the selected content
-    pub fn indent_selection(&mut self) -> Result<(), EditError> {
-        let command = Box::new(SelectionIndentCommand::new(
-            self.document.clone(),
-            IndentDirection::Increase,
-        ));
-        self.execute_command(command)
+    /// The position of the bookmark with the given name
+    pub fn bookmark(&self, name: &str) -> Option<&Position> {
+        self.bookmarks
+            .iter()
+            .find(|bookmark| bookmark.name == name)
+            .map(|bookmark| &bookmark.position)
     }
 
-    /// Decrease the indentation of the selected content
-    pub fn unindent_selection(&mut self) -> Result<(), EditError> {
-        let command = Box::new(SelectionIndentCommand::new(
-            self.document.clone(),
-            IndentDirection::Decrease,
-        ));
-        self.execute_command(command)
+    /// All bookmarks in the order they were set
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
     }
 
-    /// Create a table of contents from document headings
+    /// Go back to where the selection was before the last jump, returning
+    /// the selection gone back to
     ///
-    /// - `position`: The position in the document where the TOC should be inserted
-    /// - `max_level`: The maximum heading level to include (1-6)
-    pub fn create_table_of_contents(
-        &mut self,
-        position: usize,
-        max_level: u8,
-    ) -> Result<(), EditError> {
-        let command = Box::new(CreateTOCCommand::new(
-            self.document.clone(),
-            position,
-            max_level,
-        ));
-        self.execute_command(command)
+    /// Selections made with the `select_*` methods that land in another
+    /// top-level node, such as following a link or a search result, are
+    /// jumps; cursor motions and edits aren't. The history is separate from
+    /// undo, and edits move its locations along with their text.
+    pub fn navigate_back(&mut self) -> Option<Selection> {
+        let mut document = self.document.borrow_mut();
+        let selection = self.navigation.back(document.selection.clone())?;
+        document.selection = Some(selection.clone());
+        Some(selection)
     }
 
-    /// Create an empty table with default alignments
+    /// Go forward again after [`navigate_back`](Self::navigate_back),
+    /// returning the selection gone to
     ///
-    /// - `position`: The position in the document where the table should be inserted
-    /// - `columns`: The number of columns in the table
-    /// - `rows`: The number of rows in the table (not including header)
-    pub fn create_table(
-        &mut self,
-        position: usize,
-        columns: usize,
-        rows: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(CreateTableCommand::new(
-            self.document.clone(),
-            position,
-            columns,
-            rows,
-        ));
-        self.execute_command(command)
+    /// A new jump drops the locations there were to go forward to.
+    pub fn navigate_forward(&mut self) -> Option<Selection> {
+        let mut document = self.document.borrow_mut();
+        let selection = self.navigation.forward(document.selection.clone())?;
+        document.selection = Some(selection.clone());
+        Some(selection)
     }
 
-    /// Create a table with custom column alignments
-    ///
-    /// - `position`: The position in the document where the table should be inserted
-    /// - `columns`: The number of columns in the table
-    /// - `rows`: The number of rows in the table (not including header)
-    /// - `alignments`: Column alignments (one per column)
-    pub fn create_table_with_alignments(
-        &mut self,
-        position: usize,
-        columns: usize,
-        rows: usize,
-        alignments: Vec<TableAlignment>,
-    ) -> Result<(), EditError> {
-        let command = Box::new(CreateTableCommand::with_alignments(
-            self.document.clone(),
-            position,
-            columns,
-            rows,
-            alignments,
-        ));
-        self.execute_command(command)
+    /// Returns true if there is a location to go back to
+    pub fn can_navigate_back(&self) -> bool {
+        self.navigation.can_go_back()
     }
 
-    /// Create a table with predefined data
-    ///
-    /// - `position`: The position in the document where the table should be inserted
-    /// - `header`: Header row cells
-    /// - `rows`: Table data (rows of cells)
-    /// - `alignments`: Optional column alignments
-    pub fn create_table_with_data(
-        &mut self,
-        position: usize,
-        header: Vec<String>,
-        rows: Vec<Vec<String>>,
-        alignments: Option<Vec<TableAlignment>>,
-    ) -> Result<(), EditError> {
-        let command = Box::new(CreateTableCommand::with_data(
-            self.document.clone(),
-            position,
-            header,
-            rows,
-            alignments,
-        ));
-        self.execute_command(command)
+    /// Returns true if there is a location to go forward to
+    pub fn can_navigate_forward(&self) -> bool {
+        self.navigation.can_go_forward()
     }
 
-    /// Create a table with custom properties
-    ///
-    /// - `position`: The position in the document where the table should be inserted
-    /// - `columns`: The number of columns in the table
-    /// - `rows`: The number of rows in the table (not including header)
-    /// - `properties`: Table styling and behavior properties
-    pub fn create_table_with_properties(
-        &mut self,
-        position: usize,
-        columns: usize,
-        rows: usize,
-        properties: TableProperties,
-    ) -> Result<(), EditError> {
-        let command = Box::new(CreateTableCommand::with_properties(
-            self.document.clone(),
-            position,
-            columns,
-            rows,
-            properties,
-        ));
-        self.execute_command(command)
+    /// Change the selection with `select`, recording the jump in the
+    /// navigation history; returns what `select` did
+    fn select_with(&mut self, select: impl FnOnce(&mut Document) -> bool) -> bool {
+        let mut document = self.document.borrow_mut();
+        let previous = document.selection.clone();
+        if !select(&mut document) {
+            return false;
+        }
+        if let (Some(previous), Some(current)) = (previous, &document.selection) {
+            self.navigation.record(previous, current);
+        }
+        true
     }
 
-    /// Create a table with predefined data and custom properties
+    /// Pin a top-level node to its distance from the start or end of the
+    /// document, replacing any pin it had
     ///
-    /// - `position`: The position in the document where the table should be inserted
-    /// - `header`: Header row cells
-    /// - `rows`: Table data (rows of cells)
-    /// - `alignments`: Optional column alignments
-    /// - `properties`: Table styling and behavior properties
-    pub fn create_table_with_data_and_properties(
-        &mut self,
-        position: usize,
-        header: Vec<String>,
-        rows: Vec<Vec<String>>,
-        alignments: Option<Vec<TableAlignment>>,
-        properties: TableProperties,
-    ) -> Result<(), EditError> {
-        let command = Box::new(CreateTableCommand::with_data_and_properties(
-            self.document.clone(),
-            position,
-            header,
-            rows,
-            alignments,
-            properties,
-        ));
-        self.execute_command(command)
+    /// [`move_node`](Self::move_node), [`move_section`](Self::move_section),
+    /// [`insert_node`](Self::insert_node) and
+    /// [`insert_paragraph`](Self::insert_paragraph) move their target out of
+    /// the pinned nodes, while [`delete_node`](Self::delete_node),
+    /// [`duplicate_node`](Self::duplicate_node) and
+    /// [`drop_outline_section`](Self::drop_outline_section) fail with
+    /// [`EditErrorKind::Pinned`] rather than move or remove one.
+    pub fn pin_node(&mut self, node_index: usize, anchor: PinAnchor) -> Result<(), EditError> {
+        let len = self.document.borrow().nodes.len();
+        if node_index >= len {
+            return Err(EditError::index_out_of_bounds().at_node(node_index));
+        }
+        self.pins.pin(node_index, len, anchor);
+        Ok(())
     }
 
-    /// Add a row to an existing table
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `row_index`: The index where the new row should be inserted (0 is first row after header)
-    pub fn add_table_row(&mut self, node_index: usize, row_index: usize) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::AddRow(row_index),
-        ));
-        self.execute_command(command)
+    /// Unpin a top-level node, returning whether it was pinned
+    pub fn unpin_node(&mut self, node_index: usize) -> bool {
+        let len = self.document.borrow().nodes.len();
+        self.pins.unpin(node_index, len)
     }
 
-    /// Remove a row from an existing table
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `row_index`: The index of the row to remove
-    pub fn remove_table_row(
-        &mut self,
-        node_index: usize,
-        row_index: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::RemoveRow(row_index),
-        ));
-        self.execute_command(command)
+    /// Returns true if the top-level node is pinned
+    pub fn is_pinned(&self, node_index: usize) -> bool {
+        self.pinned_nodes().contains(&node_index)
     }
 
-    /// Add a column to an existing table
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `column_index`: The index where the new column should be inserted
-    pub fn add_table_column(
-        &mut self,
-        node_index: usize,
-        column_index: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::AddColumn(column_index),
-        ));
-        self.execute_command(command)
+    /// Indices of the pinned top-level nodes, ascending
+    pub fn pinned_nodes(&self) -> Vec<usize> {
+        self.pins.indices(self.document.borrow().nodes.len())
     }
 
-    /// Remove a column from an existing table
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `column_index`: The index of the column to remove
-    pub fn remove_table_column(
-        &mut self,
-        node_index: usize,
-        column_index: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::RemoveColumn(column_index),
-        ));
-        self.execute_command(command)
+    /// The insertion point closest to `position` outside the pinned nodes;
+    /// positions past the end are left for the command to reject
+    fn unpinned_position(&self, position: usize) -> Result<usize, EditError> {
+        let len = self.document.borrow().nodes.len();
+        match position <= len {
+            true => self.pins.insertion_point(position, len),
+            false => Ok(position),
+        }
     }
 
-    /// Set the content of a table cell
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `row`: The row index of the cell (ignored if is_header is true)
-    /// - `column`: The column index of the cell
-    /// - `content`: The new content for the cell
-    /// - `is_header`: Whether the cell is in the header row
-    pub fn set_table_cell(
-        &mut self,
-        node_index: usize,
-        row: usize,
-        column: usize,
-        content: &str,
-        is_header: bool,
-    ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::SetCell {
-                row,
-                column,
-                content: content.to_string(),
-                is_header,
-            },
-        ));
-        self.execute_command(command)
+    /// Fails if removing the existing nodes of `range` would move or remove
+    /// a pinned node
+    fn check_pins(&self, range: std::ops::Range<usize>) -> Result<(), EditError> {
+        let len = self.document.borrow().nodes.len();
+        match range.end <= len {
+            true => self.pins.check_removal(range, len),
+            false => Ok(()),
+        }
     }
 
-    /// Set the alignment of a table column
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `column`: The column index
-    /// - `alignment`: The alignment to set
-    pub fn set_table_column_alignment(
-        &mut self,
-        node_index: usize,
-        column: usize,
-        alignment: TableAlignment,
-    ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::SetAlignment { column, alignment },
-        ));
-        self.execute_command(command)
+    /// Set the position to keep at the top of the viewport
+    pub fn set_scroll_anchor(&mut self, anchor: Option<Position>) {
+        self.scroll_anchor = anchor;
     }
 
-    /// Group multiple nodes together
-    ///
-    /// - `node_indices`: Indices of nodes to group
-    /// - `group_name`: Name or type of the group
-    pub fn group_nodes(
-        &mut self,
-        node_indices: Vec<usize>,
-        group_name: &str,
-    ) -> Result<(), EditError> {
-        let command = Box::new(GroupNodesCommand::new(
-            self.document.clone(),
-            node_indices,
-            group_name.to_string(),
-        ));
-        self.execute_command(command)
+    /// The position kept at the top of the viewport
+    pub fn scroll_anchor(&self) -> Option<&Position> {
+        self.scroll_anchor.as_ref()
     }
 
-    /// Selects all content in the document
-    pub fn select_all(&mut self) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.select_all() {
-            return Err(EditError::OperationFailed);
+    /// Replace every history entry with the patch it amounts to
+    fn patch_history(&mut self) -> Result<(), EditError> {
+        let current = self.document.borrow().clone();
+
+        // undone[k] is the document with the top k undo entries undone
+        let mut undone = vec![current.clone()];
+        let top = self.undo_stack.len();
+        for i in (0..top).rev() {
+            if let Err(err) = self.undo_stack[i].command.undo() {
+                for entry in &mut self.undo_stack[i + 1..] {
+                    entry.command.execute()?;
+                }
+                return Err(err);
+            }
+            undone.push(self.document.borrow().clone());
+        }
+        *self.document.borrow_mut() = current.clone();
+
+        // redone[k] is the document with the top k redo entries redone
+        let mut redone = vec![current.clone()];
+        let redo_top = self.redo_stack.len();
+        for i in (0..redo_top).rev() {
+            if let Err(err) = self.redo_stack[i].command.execute() {
+                for entry in &mut self.redo_stack[i + 1..] {
+                    entry.command.undo()?;
+                }
+                return Err(err);
+            }
+            redone.push(self.document.borrow().clone());
+        }
+        *self.document.borrow_mut() = current;
+
+        let document = self.document.clone();
+        let patch = |entry: &HistoryEntry, before: &Document, after: &Document| {
+            let mut entry =
+                entry.replaced_by(Box::new(PatchCommand::new(document.clone(), before, after)));
+            entry.compressed = true;
+            entry
+        };
+        for (i, entry) in self.undo_stack.iter_mut().enumerate() {
+            *entry = patch(entry, &undone[top - i], &undone[top - i - 1]);
+        }
+        for (i, entry) in self.redo_stack.iter_mut().enumerate() {
+            *entry = patch(entry, &redone[redo_top - 1 - i], &redone[redo_top - i]);
         }
         Ok(())
     }
 
-    /// Selects a specific node by index
-    pub fn select_node(&mut self, node_index: usize) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.select_node(node_index) {
-            return Err(EditError::IndexOutOfBounds);
+    /// Compress the undo history into node-level patches
+    ///
+    /// Commands that keep snapshots of whole nodes or lists for undo are replaced
+    /// by patches that only hold the nodes they changed, whenever the patch is
+    /// smaller. Undo granularity is preserved. Returns the number of bytes saved.
+    pub fn compress_history(&mut self) -> usize {
+        let Some(start) = self.undo_stack.iter().position(|entry| !entry.compressed) else {
+            return 0;
+        };
+        let before_bytes = self.history_bytes();
+
+        // Walk back through the uncompressed entries, recording the document
+        // state after each one; states[k] is the state after the k-th entry
+        // from the top
+        let mut states = vec![self.document.borrow().clone()];
+        for entry in self.undo_stack[start..].iter_mut().rev() {
+            if entry.command.undo().is_err() {
+                entry.compressed = true;
+                break;
+            }
+            states.push(self.document.borrow().clone());
         }
-        Ok(())
+        *self.document.borrow_mut() = states.last().cloned().unwrap_or_default();
+
+        // Replay forward, swapping in a patch wherever it is smaller than the
+        // command or the command fails to reproduce its original result
+        let top = self.undo_stack.len();
+        for k in (0..states.len() - 1).rev() {
+            let (before, after) = (&states[k + 1], &states[k]);
+            let entry = &mut self.undo_stack[top - 1 - k];
+            let patch = entry.replaced_by(Box::new(PatchCommand::new(
+                self.document.clone(),
+                before,
+                after,
+            )));
+
+            let replayed = patch.bytes >= entry.bytes
+                && entry.command.execute().is_ok()
+                && *self.document.borrow() == *after;
+            if !replayed {
+                *entry = patch;
+                *self.document.borrow_mut() = after.clone();
+            }
+            entry.compressed = true;
+        }
+
+        before_bytes.saturating_sub(self.history_bytes())
     }
 
-    /// Selects a range of nodes
-    pub fn select_node_range(
-        &mut self,
-        start_index: usize,
-        end_index: usize,
-    ) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.select_node_range(start_index, end_index) {
-            return Err(EditError::InvalidRange);
-        }
-        Ok(())
+    /// Merge consecutive text insertions and deletions into single undo steps
+    ///
+    /// With a policy, typing a word or holding backspace in one node yields
+    /// one undo step instead of one per keystroke. Pass `None` to give every
+    /// edit its own step again.
+    pub fn set_coalescing(&mut self, policy: Option<CoalescePolicy>) {
+        self.coalescing = policy;
+        self.open_group = None;
     }
 
-    /// Selects a specific range of text within a node
-    pub fn select_text_range(
-        &mut self,
-        node_index: usize,
-        start_offset: usize,
-        end_offset: usize,
-    ) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.select_text_range(node_index, start_offset, end_offset) {
-            return Err(EditError::InvalidRange);
-        }
-        Ok(())
+    /// Get the coalescing policy, if any
+    pub fn coalescing(&self) -> Option<&CoalescePolicy> {
+        self.coalescing.as_ref()
     }
 
-    /// Selects from one position to another across any nodes
-    pub fn select_range(
-        &mut self,
-        start_node: usize,
-        start_offset: usize,
-        end_node: usize,
-        end_offset: usize,
-    ) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.select_range(start_node, start_offset, end_node, end_offset) {
-            return Err(EditError::InvalidRange);
+    /// Make the next text edit start a new undo step
+    ///
+    /// Call this when the caret is moved or the editor loses focus.
+    pub fn break_coalescing(&mut self) {
+        self.open_group = None;
+    }
+
+    /// Merge a text edit into the open undo step if the policy allows it
+    ///
+    /// Returns the command to record as a new step otherwise.
+    fn coalesce(&mut self, command: Box<dyn EditorCommand>) -> Option<Box<dyn EditorCommand>> {
+        let Some(policy) = &self.coalescing else {
+            return Some(command);
+        };
+
+        let command = match &mut self.open_group {
+            Some(group)
+                if self.redo_stack.is_empty()
+                    && self
+                        .undo_stack
+                        .last()
+                        .is_some_and(|entry| group.is_step(entry.command.as_ref())) =>
+            {
+                let changes = node_changes(command.as_ref());
+                match group.join(command, policy) {
+                    Ok(()) => {
+                        let entry = self.undo_stack.last_mut()?;
+                        entry.bytes = entry.command.retained_bytes();
+                        entry.compressed = false;
+                        let attributes_before = self.record_delta(changes);
+                        let document = self.document.borrow();
+                        if let Some(entry) = self.undo_stack.last_mut() {
+                            entry.selection_after = document.selection.clone();
+                            // Earlier steps of the group left the attributes alone
+                            // if the entry has none yet
+                            if attributes_before.is_some() {
+                                entry.attributes_before =
+                                    entry.attributes_before.take().or(attributes_before);
+                                entry.attributes_after = Some(document.node_attributes.clone());
+                            }
+                        }
+                        drop(document);
+                        if let Some(tree) = &mut self.history_tree {
+                            tree.amend(&self.document.borrow());
+                        }
+                        self.enforce_history_budget();
+                        return None;
+                    }
+                    Err(command) => command,
+                }
+            }
+            _ => command,
+        };
+
+        match OpenGroup::start(command) {
+            Ok((group, step)) => {
+                self.open_group = Some(group);
+                Some(step)
+            }
+            Err(command) => {
+                self.open_group = None;
+                Some(command)
+            }
         }
-        Ok(())
     }
 
-    /// Collapses the current selection to its start position
-    pub fn collapse_selection_to_start(&mut self) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.collapse_selection_to_start() {
-            return Err(EditError::Other("No selection to collapse".to_string()));
+    /// Record an executed command, trimming history to the configured limits
+    fn push_history(&mut self, command: Box<dyn EditorCommand>) {
+        let Some(command) = self.coalesce(command) else {
+            return;
+        };
+        let changes = node_changes(command.as_ref());
+        let mut entry = HistoryEntry::new(command);
+        entry.selection_before = self.selection_before.clone();
+        self.redo_stack.clear();
+        entry.attributes_before = self.record_delta(changes);
+        let document = self.document.borrow();
+        entry.selection_after = document.selection.clone();
+        if entry.attributes_before.is_some() {
+            entry.attributes_after = Some(document.node_attributes.clone());
         }
-        Ok(())
+        drop(document);
+        self.undo_stack.push(entry);
+        if let Some(tree) = &mut self.history_tree {
+            tree.record(&self.document.borrow());
+        }
+
+        // Trim history if needed
+        if self.undo_stack.len() > self.max_history {
+            self.undo_stack.remove(0);
+        }
+
+        self.enforce_history_budget();
     }
 
-    /// Collapses the current selection to its end position
-    pub fn collapse_selection_to_end(&mut self) -> Result<(), EditError> {
-        let mut document = self.document.borrow_mut();
-        if !document.collapse_selection_to_end() {
-            return Err(EditError::Other("No selection to collapse".to_string()));
+    /// Compress and then drop old history until it fits the memory budget
+    fn enforce_history_budget(&mut self) {
+        let Some(budget) = self.history_budget else {
+            return;
+        };
+        let bytes = self.history_bytes();
+        if bytes <= budget {
+            return;
+        }
+        let throttled = self
+            .compression_interval
+            .zip(self.last_compression)
+            .is_some_and(|(interval, last)| last.elapsed() < interval);
+        if throttled && bytes <= budget.saturating_mul(2) {
+            return;
+        }
+
+        self.compress_history();
+        self.last_compression = Some(Instant::now());
+        while self.history_bytes() > budget && !self.redo_stack.is_empty() {
+            self.redo_stack.remove(0);
+        }
+        while self.history_bytes() > budget && self.undo_stack.len() > 1 {
+            self.undo_stack.remove(0);
         }
-        Ok(())
     }
 
-    /// Clears the current selection
-    pub fn clear_selection(&mut self) {
-        let mut document = self.document.borrow_mut();
-        document.clear_selection();
+    /// Get word counts and section progress for the current document
+    ///
+    /// Only the nodes changed since the last call are recounted.
+    pub fn stats(&mut self) -> &DocumentStats {
+        self.stats.update(&self.document.borrow())
     }
 
-    /// Returns whether there is currently a selection
-    pub fn has_selection(&self) -> bool {
-        let document = self.document.borrow();
-        document.has_selection()
+    /// Get the content change caused by the last command, undo or redo
+    pub fn last_delta(&self) -> &StatsDelta {
+        &self.last_delta
     }
 
-    /// Returns whether the current selection spans multiple nodes
-    pub fn has_multi_node_selection(&self) -> bool {
-        let document = self.document.borrow();
-        document.has_multi_node_selection()
+    /// Get the top-level nodes the last command, undo or redo changed, as
+    /// indices into the current document
+    ///
+    /// An empty range marks where nodes were removed. Frontends can use it
+    /// to scroll to and highlight the result of an edit. `None` if the last
+    /// change left the nodes as they were, e.g. when it only moved the
+    /// selection.
+    pub fn last_affected_range(&self) -> Option<std::ops::Range<usize>> {
+        self.last_affected.clone()
     }
 
-    /// Gets the currently selected text, if any
-    pub fn get_selected_text(&self) -> Option<String> {
+    /// Get the accumulated content changes since the editor was created or
+    /// the session was last reset
+    pub fn session_delta(&self) -> &StatsDelta {
+        &self.session_delta
+    }
+
+    /// Start a new writing session, clearing the accumulated changes
+    pub fn reset_session_delta(&mut self) {
+        self.session_delta = StatsDelta::default();
+    }
+
+    /// Remember the selection before a change, to tell whether the change
+    /// moved it itself
+    fn begin_change(&mut self) {
         let document = self.document.borrow();
-        document.get_selected_text()
+        self.selection_before = document.selection.clone();
+        self.selections_before = document.selections.clone();
     }
 
-    /// Begin a transaction to group multiple operations into a single atomic change.
+    /// Update the statistics after a change and remember what it did, and
+    /// move the selection and annotations along with their text
     ///
-    /// Returns a Transaction object that can be used to build up a series of operations.
-    /// The transaction is not applied to the document until it is committed and executed.
-    ///
-    /// # Example
-    /// ```
-    /// # use md_core::{Document, Editor, EditError};
-    /// # fn example() -> Result<(), EditError> {
-    /// # let doc = Document::new();
-    /// # let mut editor = Editor::new(doc);
-    /// let mut transaction = editor.begin_transaction();
-    /// transaction.insert_text(0, 0, "Hello");
-    /// editor.execute_transaction(transaction)?;
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn begin_transaction(&self) -> Transaction {
-        Transaction::new(self.document.clone())
+    /// `changes` holds the range of top-level nodes the change reported, if
+    /// any; otherwise the changed nodes are found by comparing hashes.
+    /// Returns the node attributes from before the change if it moved or
+    /// removed any.
+    fn record_delta(&mut self, changes: NodeChanges) -> Option<BTreeMap<usize, NodeAttributes>> {
+        let affected = changes.affected.clone();
+        self.mapper = self.texts.update(&self.document.borrow(), changes);
+        self.last_affected = affected.or_else(|| self.mapper.changed_nodes());
+        // Nodes outside the changed range are the ones the tracker has
+        let window = self.last_affected.clone().unwrap_or(0..0);
+        self.last_delta = self.stats.update_within(&self.document.borrow(), window);
+        self.session_delta.accumulate(&self.last_delta);
+        self.annotations.remap(&self.mapper);
+        self.navigation.remap(&self.mapper);
+        self.remap_bookmarks();
+        self.remap_selection();
+        let attributes_before = self.remap_ranges();
+        if let Some(tab_stops) = &mut self.tab_stops
+            && !tab_stops.remap(&self.mapper)
+        {
+            self.tab_stops = None;
+        }
+        if let Some(autosave) = &mut self.autosave {
+            autosave.changed(&self.document.borrow());
+        }
+        attributes_before
     }
 
-    /// Execute a transaction with a provided closure that builds the transaction.
+    /// Move bookmarks along with their text, dropping those whose node was
+    /// removed
+    fn remap_bookmarks(&mut self) {
+        self.bookmarks.retain_mut(
+            |bookmark| match self.mapper.map_position(&bookmark.position) {
+                Some(position) => {
+                    bookmark.position = position;
+                    true
+                }
+                None => false,
+            },
+        );
+    }
+
+    /// Move selections the change left alone along with their text
     ///
-    /// # Example
-    /// ```
-    /// # use md_core::{Document, Editor, TextFormatting, EditError};
-    /// # fn example() -> Result<(), EditError> {
-    /// # let doc = Document::new();
-    /// # let mut editor = Editor::new(doc);
-    /// editor.with_transaction(|mut transaction| {
-    ///     transaction
-    ///         .insert_text(0, 0, "Hello")
-    ///         .format_text(0, 0, 5, TextFormatting {
-    ///             bold: true,
-    ///             ..Default::default()
-    ///         });
-    ///     transaction
-    /// })?;
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn with_transaction<F>(&mut self, transaction_builder: F) -> Result<(), EditError>
-    where
-        F: FnOnce(Transaction) -> Transaction,
-    {
-        // Create a new transaction
-        let transaction = Transaction::new(self.document.clone());
+    /// An end whose node was removed moves to the start of the first
+    /// changed node, or the document's last node if the change removed
+    /// nodes at the end.
+    fn remap_selection(&mut self) {
+        let mut document = self.document.borrow_mut();
+        let nodes = document.nodes.len();
+        let fallback = self.mapper.changed_nodes().map_or(0, |range| range.start);
+        let map = |position: &Position| {
+            self.mapper
+                .map_position(position)
+                .unwrap_or_else(|| Position::new(vec![fallback.min(nodes - 1)], 0))
+        };
+        let map_selection = |selection: &Selection| match nodes {
+            0 => None,
+            _ if selection.is_collapsed => Some(Selection::collapsed(map(&selection.start))),
+            _ => Some(Selection::new(map(&selection.start), map(&selection.end))),
+        };
 
-        // Let the closure build the transaction
-        let transaction = transaction_builder(transaction);
+        if document.selection == self.selection_before {
+            document.selection = document.selection.as_ref().and_then(map_selection);
+        }
+        if document.selections == self.selections_before {
+            let mut selections = Vec::new();
+            for selection in document.selections.iter().filter_map(map_selection) {
+                if !selections.contains(&selection)
+                    && document.selection.as_ref() != Some(&selection)
+                {
+                    selections.push(selection);
+                }
+            }
+            document.selections = selections;
+        }
+        self.selection_before = document.selection.clone();
+        self.selections_before = document.selections.clone();
+    }
 
-        // Execute the transaction
-        self.execute_transaction(transaction)
+    /// Put back the selection an undo or redo step recorded
+    fn restore_selection(&mut self, selection: Option<Selection>) {
+        self.document.borrow_mut().selection = selection.clone();
+        self.selection_before = selection;
     }
 
-    /// Execute a transaction, updating the undo stack as a single composite command.
+    /// Put back the node attributes a history entry recorded, if any
+    fn restore_attributes(&mut self, attributes: Option<BTreeMap<usize, NodeAttributes>>) {
+        if let Some(attributes) = attributes {
+            self.document.borrow_mut().node_attributes = attributes.clone();
+            self.snapshot_attributes = attributes;
+        }
+    }
+
+    /// Map positions from before the last command, undo or redo to after it
     ///
-    /// This method commits the transaction and applies the changes to the document.
-    pub fn execute_transaction(&mut self, transaction: Transaction) -> Result<(), EditError> {
-        // Commit the transaction
-        let commands = transaction.commit()?;
+    /// The editor uses it to move the selection, annotations and comments
+    /// along with edits; anything else holding positions, such as remote
+    /// cursors or search highlights, can do the same.
+    pub fn position_mapper(&self) -> &PositionMapper {
+        &self.mapper
+    }
 
-        // Execute the committed commands
-        self.execute_transaction_commands(commands)
+    /// Move comments and tracked changes along with their text, growing or
+    /// shrinking them with edits inside, and remove those whose node was
+    /// removed
+    ///
+    /// Comments or tracked changes the change itself replaced, as comment
+    /// and tracked-change commands and history patches do, already fit the
+    /// document and are kept as they are. Node attributes move with their
+    /// nodes; returns the attributes from before if any moved or were
+    /// removed.
+    fn remap_ranges(&mut self) -> Option<BTreeMap<usize, NodeAttributes>> {
+        let mut document = self.document.borrow_mut();
+        let mut comments = std::mem::take(&mut document.comments);
+        let mut changes = std::mem::take(&mut document.tracked_changes);
+        let mapper = &self.mapper;
+        if comments == self.snapshot_comments {
+            comments.retain_mut(|comment| {
+                match mapper.map(&comment.path, &comment.range, RangeMode::Adjust) {
+                    Some((path, range)) => {
+                        comment.path = path;
+                        comment.range = range;
+                        true
+                    }
+                    None => false,
+                }
+            });
+        }
+        if changes == self.snapshot_changes {
+            changes.retain_mut(|change| {
+                match mapper.map(&change.path, &change.range, RangeMode::Adjust) {
+                    Some((path, range)) if !range.is_empty() => {
+                        change.path = path;
+                        change.range = range;
+                        true
+                    }
+                    _ => false,
+                }
+            });
+        }
+        let mut attributes_before = None;
+        if document.node_attributes == self.snapshot_attributes
+            && document
+                .node_attributes
+                .keys()
+                .any(|&index| mapper.map_index(index) != Some(index))
+        {
+            let attributes = std::mem::take(&mut document.node_attributes);
+            document.node_attributes = attributes
+                .iter()
+                .filter_map(|(&index, attributes)| {
+                    Some((mapper.map_index(index)?, attributes.clone()))
+                })
+                .collect();
+            attributes_before = Some(attributes);
+        }
+        document.comments = comments;
+        document.tracked_changes = changes;
+        self.snapshot_comments = document.comments.clone();
+        self.snapshot_changes = document.tracked_changes.clone();
+        self.snapshot_attributes = document.node_attributes.clone();
+        attributes_before
     }
 
-    /// Execute a list of commands from a transaction and add to undo stack.
+    /// Attach an annotation, such as a misspelling a spellchecker found in
+    /// one of the document's [`text_runs`](Document::text_runs)
     ///
-    /// This is a lower-level method that's used by execute_transaction.
-    pub fn execute_transaction_commands(
-        &mut self,
-        commands: Vec<Box<dyn EditorCommand>>,
-    ) -> Result<(), EditError> {
-        // If there are no commands, nothing to do
-        if commands.is_empty() {
-            return Ok(());
+    /// Edits move annotations along with their text and drop those whose
+    /// text changed. Fails if the path doesn't lead to a paragraph or
+    /// heading or the range doesn't fit its text.
+    pub fn annotate(&mut self, annotation: Annotation) -> Result<(), EditError> {
+        let path = annotation.path.clone();
+        if self.annotations.add(&self.document.borrow(), annotation) {
+            Ok(())
+        } else {
+            Err(EditError::invalid_range()
+                .at_path(path)
+                .with_message("Annotation doesn't fit a paragraph or heading"))
         }
+    }
 
-        // Create a composite command that represents all commands as one operation
-        let composite = CompositeCommand::new(commands);
+    /// All annotations, in the order they were added
+    pub fn annotations(&self) -> &[Annotation] {
+        self.annotations.annotations()
+    }
 
-        // Add to undo stack
-        self.undo_stack.push(Box::new(composite));
+    /// Annotations covering a position, e.g. to offer suggestions for the
+    /// word under the cursor
+    pub fn annotations_at(&self, position: &Position) -> Vec<&Annotation> {
+        self.annotations()
+            .iter()
+            .filter(|annotation| {
+                annotation.path == position.path
+                    && annotation.range.start <= position.offset
+                    && position.offset <= annotation.range.end
+            })
+            .collect()
+    }
 
-        // Clear redo stack since we executed a new command
-        self.redo_stack.clear();
+    /// Remove the annotations of a kind, returning how many were removed
+    pub fn clear_annotations(&mut self, kind: &str) -> usize {
+        self.annotations
+            .remove(|annotation| annotation.kind == kind)
+    }
 
-        // Trim history if needed
-        if self.undo_stack.len() > self.max_history {
-            self.undo_stack.remove(0);
-        }
+    /// Add a comment by `author` on a byte range of the paragraph or heading
+    /// at `path`, returning the comment's id
+    pub fn add_comment(
+        &mut self,
+        author: impl Into<String>,
+        path: Vec<usize>,
+        range: std::ops::Range<usize>,
+        body: impl Into<String>,
+    ) -> Result<u64, EditError> {
+        let action = CommentAction::Add {
+            author: author.into(),
+            path,
+            range,
+            body: body.into(),
+        };
+        self.execute_command(Box::new(CommentCommand::new(self.document.clone(), action)))?;
+        let document = self.document.borrow();
+        Ok(document.comments.last().map_or(0, |comment| comment.id))
+    }
 
-        Ok(())
+    /// Mark a comment resolved, or reopen it
+    pub fn resolve_comment(&mut self, id: u64, resolved: bool) -> Result<(), EditError> {
+        let action = CommentAction::Resolve { id, resolved };
+        self.execute_command(Box::new(CommentCommand::new(self.document.clone(), action)))
     }
 
-    /// Set the background color of a table cell
+    /// Delete a comment
+    pub fn delete_comment(&mut self, id: u64) -> Result<(), EditError> {
+        let action = CommentAction::Delete { id };
+        self.execute_command(Box::new(CommentCommand::new(self.document.clone(), action)))
+    }
+
+    /// Track changes by `author`, or stop tracking them with `None`
     ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `row`: The row index of the cell (0 is the first row after the header)
-    /// - `column`: The column index of the cell (0 is the first column)
-    /// - `color`: The background color in hex format (e.g., "#f5f5f5")
-    /// - `is_header`: Whether to modify a header cell or a body cell
-    pub fn set_table_cell_background(
+    /// While changes are tracked, [`insert_text`](Self::insert_text) marks
+    /// the inserted text as a suggested insertion and
+    /// [`delete_text`](Self::delete_text) marks text as a suggested deletion
+    /// instead of removing it. Typing, deleting and cutting the selection
+    /// are tracked the same way, as long as there is a single selection
+    /// within one node. Edits that can't be marked, such as pasting or
+    /// deleting a whole node, fail instead. Marks already made are kept
+    /// either way.
+    pub fn set_track_changes(&mut self, author: Option<String>) {
+        self.track_changes = author;
+    }
+
+    /// The author of suggested edits, if changes are tracked
+    pub fn track_changes(&self) -> Option<&str> {
+        self.track_changes.as_deref()
+    }
+
+    /// Accept a tracked change: an insertion stays and a deletion removes
+    /// its text
+    pub fn accept_change(&mut self, id: u64) -> Result<(), EditError> {
+        self.resolve_changes(Some(id), true)
+    }
+
+    /// Reject a tracked change: an insertion removes its text and a
+    /// deletion keeps it
+    pub fn reject_change(&mut self, id: u64) -> Result<(), EditError> {
+        self.resolve_changes(Some(id), false)
+    }
+
+    /// Accept all tracked changes as one undoable step
+    pub fn accept_all(&mut self) -> Result<(), EditError> {
+        self.resolve_changes(None, true)
+    }
+
+    /// Reject all tracked changes as one undoable step
+    pub fn reject_all(&mut self) -> Result<(), EditError> {
+        self.resolve_changes(None, false)
+    }
+
+    /// Fails if changes are tracked, for edits that can't be marked as
+    /// suggestions
+    fn check_untracked(&self) -> Result<(), EditError> {
+        match self.track_changes {
+            Some(_) => Err(EditError::unsupported()
+                .with_message("This edit can't be made while changes are tracked")),
+            None => Ok(()),
+        }
+    }
+
+    /// The node and byte range of the selection, for edits that are
+    /// tracked; only a single selection within one node can be tracked
+    fn tracked_range(&self) -> Result<(usize, usize, usize), EditError> {
+        let document = self.document.borrow();
+        let unsupported = || {
+            EditError::unsupported().with_message(
+                "Only a single selection within one node can be edited while changes are tracked",
+            )
+        };
+        let selection = document
+            .selection
+            .as_ref()
+            .filter(|_| document.selections.is_empty())
+            .ok_or_else(unsupported)?;
+        let (start, end) = (&selection.start, &selection.end);
+        if start.path.len() != 1 || start.path != end.path {
+            return Err(unsupported());
+        }
+        let (start, end) = (start.offset.min(end.offset), start.offset.max(end.offset));
+        Ok((selection.start.path[0], start, end))
+    }
+
+    fn resolve_changes(&mut self, id: Option<u64>, accept: bool) -> Result<(), EditError> {
+        let edit = TrackedEdit::Resolve { id, accept };
+        self.execute_command(Box::new(TrackChangesCommand::new(
+            self.document.clone(),
+            edit,
+        )))
+    }
+
+    /// Set or clear the word-count target of the section opened by a heading
+    pub fn set_word_target(
         &mut self,
         node_index: usize,
-        row: usize,
-        column: usize,
-        color: impl Into<String>,
-        is_header: bool,
+        target: Option<usize>,
     ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
+        let command = Box::new(SetWordTargetCommand::new(
             self.document.clone(),
             node_index,
-            TableOperation::SetCellBackground {
-                row,
-                column,
-                color: color.into(),
-                is_header,
-            },
+            target,
         ));
         self.execute_command(command)
     }
 
-    /// Set custom CSS style for a table cell
+    /// Delete text from a specific node
     ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `row`: The row index of the cell (0 is the first row after the header)
-    /// - `column`: The column index of the cell (0 is the first column)
-    /// - `style`: CSS style string (e.g., "font-weight: bold; color: red;")
-    /// - `is_header`: Whether to modify a header cell or a body cell
-    pub fn set_table_cell_style(
+    /// `start` and `end` are byte offsets; see [`Editor::delete_text_at`] for
+    /// other units.
+    pub fn delete_text(
         &mut self,
         node_index: usize,
-        row: usize,
-        column: usize,
-        style: impl Into<String>,
-        is_header: bool,
+        start: usize,
+        end: usize,
     ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
+        if let Some(author) = &self.track_changes {
+            let edit = TrackedEdit::Delete {
+                author: author.clone(),
+                node_index,
+                start,
+                end,
+            };
+            return self.execute_command(Box::new(TrackChangesCommand::new(
+                self.document.clone(),
+                edit,
+            )));
+        }
+        let command = Box::new(DeleteTextCommand::new(
             self.document.clone(),
             node_index,
-            TableOperation::SetCellStyle {
-                row,
-                column,
-                style: style.into(),
-                is_header,
-            },
+            start,
+            end,
         ));
         self.execute_command(command)
     }
 
-    /// Set the spanning of a table cell
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `row`: The row index of the cell (0 is the first row after the header)
-    /// - `column`: The column index of the cell (0 is the first column)
-    /// - `colspan`: Number of columns this cell should span
-    /// - `rowspan`: Number of rows this cell should span
-    /// - `is_header`: Whether to modify a header cell or a body cell
-    pub fn set_table_cell_span(
+    /// Delete text between two offsets counted in the given unit
+    pub fn delete_text_at(
         &mut self,
         node_index: usize,
-        row: usize,
-        column: usize,
-        colspan: u32,
-        rowspan: u32,
-        is_header: bool,
+        start: usize,
+        end: usize,
+        kind: OffsetKind,
     ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::SetCellSpan {
-                row,
-                column,
-                colspan,
-                rowspan,
-                is_header,
-            },
-        ));
-        self.execute_command(command)
+        let (start, end) = self.byte_range(node_index, start, end, kind)?;
+        self.delete_text(node_index, start, end)
     }
 
-    /// Set table properties
-    ///
-    /// - `node_index`: The index of the table node in the document
-    /// - `properties`: The table properties to set
-    pub fn set_table_properties(
-        &mut self,
+    /// Converts a pair of offsets of the given kind into byte offsets
+    fn byte_range(
+        &self,
         node_index: usize,
-        properties: TableProperties,
-    ) -> Result<(), EditError> {
-        let command = Box::new(TableOperationsCommand::new(
-            self.document.clone(),
-            node_index,
-            TableOperation::SetTableProperties(properties),
-        ));
-        self.execute_command(command)
+        start: usize,
+        end: usize,
+        kind: OffsetKind,
+    ) -> Result<(usize, usize), EditError> {
+        let document = self.document.borrow();
+        Ok((
+            document.to_byte_offset(node_index, start, kind)?,
+            document.to_byte_offset(node_index, end, kind)?,
+        ))
     }
 
-    /// Toggle the checked status of a task list item
-    pub fn toggle_task(&mut self, node_index: usize, item_index: usize) -> Result<(), EditError> {
-        let command = Box::new(ToggleTaskCommand::new(
+    /// Merge two adjacent nodes of the same type
+    pub fn merge_nodes(
+        &mut self,
+        first_index: usize,
+        second_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(MergeNodesCommand::new(
             self.document.clone(),
-            node_index,
-            item_index,
+            first_index,
+            second_index,
         ));
         self.execute_command(command)
     }
 
-    /// Increase the indentation level of a task list item
-    pub fn indent_task_item(
+    /// Format text within a paragraph
+    pub fn format_text(
         &mut self,
         node_index: usize,
-        item_index: usize,
+        start: usize,
+        end: usize,
+        formatting: TextFormatting,
     ) -> Result<(), EditError> {
-        let command = Box::new(IndentTaskItemCommand::increase_indent(
+        let command = Box::new(FormatTextCommand::new(
             self.document.clone(),
             node_index,
-            item_index,
+            start,
+            end,
+            formatting,
         ));
         self.execute_command(command)
     }
 
-    /// Decrease the indentation level of a task list item
-    pub fn dedent_task_item(
-        &mut self,
-        node_index: usize,
-        item_index: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(IndentTaskItemCommand::decrease_indent(
+    /// Move a node from one position to another
+    ///
+    /// A pinned node can't be moved, and a target among pinned nodes moves
+    /// to the nearest position outside them.
+    pub fn move_node(&mut self, from_index: usize, to_index: usize) -> Result<(), EditError> {
+        self.check_pins(from_index..from_index + 1)?;
+        let to_index = self.unpinned_position(to_index)?;
+        let command = Box::new(MoveNodeCommand::new(
             self.document.clone(),
-            node_index,
-            item_index,
+            from_index,
+            to_index,
         ));
         self.execute_command(command)
     }
 
-    /// Add a new item to a task list
-    pub fn add_task_item(
+    /// Move a heading and its section content to another position
+    ///
+    /// The section runs until the next heading of the same or a higher level.
+    /// `to_position` refers to node indices before the move, and moves out
+    /// of any pinned nodes like the target of [`move_node`](Self::move_node).
+    pub fn move_section(
         &mut self,
-        node_index: usize,
-        position: usize,
-        text: impl Into<String>,
-        checked: bool,
+        from_heading: usize,
+        to_position: usize,
     ) -> Result<(), EditError> {
-        let command = Box::new(AddTaskItemCommand::new(
+        let section = self.document.borrow().section_range(from_heading);
+        if let Some(section) = section {
+            self.check_pins(section)?;
+        }
+        let to_position = self.unpinned_position(to_position)?;
+        let command = Box::new(MoveSectionCommand::new(
             self.document.clone(),
-            node_index,
-            position,
-            text,
-            checked,
+            from_heading,
+            to_position,
         ));
         self.execute_command(command)
     }
 
-    /// Remove an item from a task list
-    pub fn remove_task_item(
+    /// Move a section dragged in an outline view to where it was dropped
+    ///
+    /// The move and level change come from [`Document::plan_outline_drop`]
+    /// and are applied as one undoable step; a drop that changes nothing
+    /// is not recorded. Returns the move made.
+    pub fn drop_outline_section(
         &mut self,
-        node_index: usize,
-        item_index: usize,
-    ) -> Result<(), EditError> {
-        let command = Box::new(RemoveTaskItemCommand::new(
+        heading_index: usize,
+        drop: OutlineDrop,
+    ) -> Result<SectionMove, EditError> {
+        let (planned, section) = {
+            let document = self.document.borrow();
+            let planned = document.plan_outline_drop(heading_index, drop)?;
+            (planned, document.section_range(heading_index))
+        };
+        if section
+            .as_ref()
+            .is_some_and(|section| planned.is_noop(section))
+        {
+            return Ok(planned);
+        }
+        if let Some(section) = section {
+            self.check_pins(section)?;
+        }
+        if self.unpinned_position(planned.to_position)? != planned.to_position {
+            return Err(EditError::pinned()
+                .at_node(heading_index)
+                .with_message("Drop target is among pinned nodes"));
+        }
+
+        let command = MoveSectionCommand::new(
             self.document.clone(),
-            node_index,
-            item_index,
-        ));
-        self.execute_command(command)
+            planned.heading_index,
+            planned.to_position,
+        )
+        .with_level_shift(planned.level_shift);
+        self.execute_command(Box::new(command))?;
+        Ok(planned)
     }
 
-    /// Edit the text content of a task list item
-    pub fn edit_task_item(
+    /// Convert a node from one type to another
+    pub fn convert_node_type(
         &mut self,
         node_index: usize,
-        item_index: usize,
-        text: impl Into<String>,
+        target_type: NodeConversionType,
     ) -> Result<(), EditError> {
-        let command = Box::new(EditTaskItemCommand::new(
+        let command = Box::new(ConvertNodeTypeCommand::new(
             self.document.clone(),
             node_index,
-            item_index,
-            text,
+            target_type,
         ));
         self.execute_command(command)
     }
 
-    /// Move a task item within a task list
-    ///
-    /// # Arguments
-    ///
-    /// * `node_index` - The index of the task list node
-    /// * `from_index` - The index of the item to move
-    /// * `to_index` - The destination index
+    /// Delete a node entirely
     ///
-    /// # Returns
+    /// Fails if the node is pinned, deleting it would move a pinned node or
+    /// changes are tracked.
+    pub fn delete_node(&mut self, node_index: usize) -> Result<(), EditError> {
+        self.check_untracked()?;
+        self.check_pins(node_index..node_index + 1)?;
+        let command = Box::new(DeleteNodeCommand::new(self.document.clone(), node_index));
+        self.execute_command(command)
+    }
+
+    /// Find and replace text across the document
+    /// Returns the number of replacements made
+    pub fn find_replace(&mut self, find: &str, replace: &str, case_sensitive: bool) -> usize {
+        let mut fr_command =
+            FindReplaceCommand::new(self.document.clone(), find, replace, case_sensitive);
+
+        // Execute the command
+        self.begin_change();
+        match fr_command.execute() {
+            Ok(_) => {
+                let replacements = fr_command.replacements();
+
+                // Add to undo stack
+                self.push_history(Box::new(fr_command));
+
+                replacements
+            }
+            Err(_) => 0,
+        }
+    }
+
+    /// Rename an identifier in code blocks, leaving prose untouched
     ///
-    /// * `Ok(())` if the operation was successful
-    /// * `Err` with appropriate error if the operation failed
-    pub fn move_task_item(
+    /// Only blocks in `language` are changed when one is given. With
+    /// `word_boundary`, occurrences inside longer identifiers are kept, and
+    /// strings and comments are skipped for languages whose syntax is known.
+    /// All blocks change in one undoable step; the report lists each block
+    /// that changed.
+    pub fn rename_in_code_blocks(
+        &mut self,
+        old: &str,
+        new: &str,
+        language: Option<&str>,
+        word_boundary: bool,
+    ) -> Result<Vec<CodeBlockRename>, EditError> {
+        let mut command =
+            RenameSymbolCommand::new(self.document.clone(), old, new, language, word_boundary);
+        self.begin_change();
+        command
+            .execute()
+            .map_err(|err| err.in_command(command.name()))?;
+
+        let report = command.report().to_vec();
+        if !report.is_empty() {
+            self.push_history(Box::new(command));
+        }
+        Ok(report)
+    }
+
+    /// Replace the rendering properties of the code block at `node_index`
+    pub fn set_code_block_properties(
         &mut self,
         node_index: usize,
-        from_index: usize,
-        to_index: usize,
+        properties: CodeBlockProperties,
     ) -> Result<(), EditError> {
-        if from_index == to_index {
-            return Ok(());
-        }
+        let command =
+            SetCodeBlockPropertiesCommand::new(self.document.clone(), node_index, properties);
+        self.execute_command(Box::new(command))
+    }
 
-        let command = Box::new(MoveTaskPositionCommand::new(
+    /// Apply a properties template to every code block in `language`,
+    /// nested ones included, as one undoable step
+    ///
+    /// The template's line numbers, theme, copy button, CSS class and style
+    /// and maximum height replace each block's; start lines, highlighted
+    /// lines and notebook outputs are kept. Returns the number of blocks that changed.
+    pub fn apply_code_block_template(
+        &mut self,
+        language: &str,
+        template: &CodeBlockProperties,
+    ) -> Result<usize, EditError> {
+        let mut command = SetCodeBlockPropertiesCommand::for_language(
             self.document.clone(),
-            node_index,
-            from_index,
-            to_index,
-        ));
-        self.execute_command(command)
+            language,
+            template.clone(),
+        );
+        self.begin_change();
+        command
+            .execute()
+            .map_err(|err| err.in_command(command.name()))?;
+
+        let changed = command.changed();
+        if changed > 0 {
+            self.push_history(Box::new(command));
+        }
+        Ok(changed)
     }
 
-    /// Move a task item up in the task list (swap with the previous item)
-    ///
-    /// # Arguments
-    ///
-    /// * `node_index` - The index of the task list node
-    /// * `item_index` - The index of the item to move up
-    ///
-    /// # Returns
+    /// Undo the last operation
+    pub fn undo(&mut self) -> Result<(), EditError> {
+        self.open_group = None;
+        self.begin_change();
+        if let Some(mut entry) = self.undo_stack.pop() {
+            entry
+                .command
+                .undo()
+                .map_err(|err| err.in_command(entry.command.name()))?;
+            self.record_delta(node_changes(entry.command.as_ref()));
+            self.restore_selection(entry.selection_before.clone());
+            self.restore_attributes(entry.attributes_before.clone());
+            self.redo_stack.push(entry);
+            if let Some(tree) = &mut self.history_tree {
+                tree.undo(&self.document.borrow());
+            }
+            Ok(())
+        } else {
+            Err(EditError::other("Nothing to undo"))
+        }
+    }
+
+    /// Redo the last undone operation
+    pub fn redo(&mut self) -> Result<(), EditError> {
+        self.open_group = None;
+        self.begin_change();
+        if let Some(mut entry) = self.redo_stack.pop() {
+            entry
+                .command
+                .execute()
+                .map_err(|err| err.in_command(entry.command.name()))?;
+            self.record_delta(node_changes(entry.command.as_ref()));
+            self.restore_selection(entry.selection_after.clone());
+            self.restore_attributes(entry.attributes_after.clone());
+            self.undo_stack.push(entry);
+            if let Some(tree) = &mut self.history_tree {
+                tree.redo(&self.document.borrow());
+            }
+            Ok(())
+        } else {
+            Err(EditError::other("Nothing to redo"))
+        }
+    }
+
+    /// Execute a command and add it to the undo stack
+    fn execute_command(&mut self, mut command: Box<dyn EditorCommand>) -> Result<(), EditError> {
+        self.begin_change();
+        command
+            .execute()
+            .map_err(|err| err.in_command(command.name()))?;
+        self.push_history(command);
+        Ok(())
+    }
+
+    /// Insert text at a specific position in a node
     ///
-    /// * `Ok(())` if the operation was successful
-    /// * `Err` with appropriate error if the operation failed (e.g., already at the top)
-    pub fn move_task_item_up(
+    /// `position` is a byte offset; see [`Editor::insert_text_at`] for other
+    /// units.
+    pub fn insert_text(
         &mut self,
         node_index: usize,
-        item_index: usize,
+        position: usize,
+        text: &str,
     ) -> Result<(), EditError> {
-        // Cannot move the first item up
-        if item_index == 0 {
-            return Err(EditError::Other("Already at the top".into()));
+        if let Some(author) = &self.track_changes {
+            let edit = TrackedEdit::Insert {
+                author: author.clone(),
+                node_index,
+                position,
+                text: text.to_string(),
+            };
+            return self.execute_command(Box::new(TrackChangesCommand::new(
+                self.document.clone(),
+                edit,
+            )));
         }
-
-        let command = Box::new(MoveTaskItemCommand::move_up(
+        let command = Box::new(InsertTextCommand::new(
             self.document.clone(),
             node_index,
-            item_index,
+            position,
+            text.to_string(),
         ));
         self.execute_command(command)
     }
 
-    /// Move a task item down in the task list (swap with the next item)
-    ///
-    /// # Arguments
-    ///
-    /// * `node_index` - The index of the task list node
-    /// * `item_index` - The index of the item to move down
-    ///
-    /// # Returns
-    ///
-    /// * `Ok(())` if the operation was successful
-    /// * `Err` with appropriate error if the operation failed (e.g., already at the bottom)
-    pub fn move_task_item_down(
+    /// Insert text at an offset counted in the given unit
+    pub fn insert_text_at(
         &mut self,
         node_index: usize,
-        item_index: usize,
+        offset: usize,
+        kind: OffsetKind,
+        text: &str,
     ) -> Result<(), EditError> {
-        // Check if this is the last item (we'll need to validate this in the command too)
-        let doc = self.document.borrow();
-        let items_len = match &doc.nodes.get(node_index) {
-            Some(Node::List { items, .. }) => items.len(),
-            _ => return Err(EditError::IndexOutOfBounds),
+        let position = self
+            .document
+            .borrow()
+            .to_byte_offset(node_index, offset, kind)?;
+        self.insert_text(node_index, position, text)
+    }
+
+    /// Insert an inline node at a byte offset in a paragraph or heading
+    ///
+    /// A text run the offset falls inside is split around the new node.
+    pub fn insert_inline(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        inline: InlineNode,
+    ) -> Result<(), EditError> {
+        let command = Box::new(
+            InsertInlineCommand::new(self.document.clone(), node_index, position, inline)
+                .with_url_policy(self.url_policy.clone()),
+        );
+        self.execute_command(command)
+    }
+
+    /// Insert a mention, such as `("octocat", "user")` or `("42", "issue")`,
+    /// at a byte offset in a paragraph or heading
+    pub fn insert_mention(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        name: &str,
+        mention_type: &str,
+    ) -> Result<(), EditError> {
+        let mention = InlineNode::Mention {
+            name: name.to_string(),
+            mention_type: mention_type.to_string(),
         };
-        drop(doc); // Release the borrow before continuing
+        self.insert_inline(node_index, position, mention)
+    }
 
-        // Cannot move the last item down
-        if item_index >= items_len - 1 {
-            return Err(EditError::Other("Already at the bottom".into()));
-        }
+    /// Insert a link at a byte offset in a paragraph or heading
+    ///
+    /// Fails with [`EditErrorKind::InvalidUrl`] if the editor's
+    /// [`UrlPolicy`] doesn't allow the URL.
+    pub fn insert_link(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        url: &str,
+        text: &str,
+    ) -> Result<(), EditError> {
+        self.insert_inline(node_index, position, InlineNode::link(url, text))
+    }
 
-        let command = Box::new(MoveTaskItemCommand::move_down(
+    /// Insert an image at a byte offset in a paragraph or heading
+    ///
+    /// Fails with [`EditErrorKind::InvalidUrl`] if the editor's
+    /// [`UrlPolicy`] doesn't allow the URL.
+    pub fn insert_image(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        url: &str,
+        alt: &str,
+    ) -> Result<(), EditError> {
+        self.insert_inline(node_index, position, InlineNode::image(url, alt))
+    }
+
+    /// Get the URL allowlist links, images, figures and embeds are checked
+    /// against on insertion
+    pub fn url_policy(&self) -> &UrlPolicy {
+        &self.url_policy
+    }
+
+    /// Replace the URL allowlist
+    pub fn set_url_policy(&mut self, policy: UrlPolicy) {
+        self.url_policy = policy;
+    }
+
+    /// Get the autocorrect rules used by `insert_text_with_autocorrect`
+    pub fn autocorrect(&self) -> &Autocorrect {
+        &self.autocorrect
+    }
+
+    /// Get mutable access to the autocorrect rules
+    pub fn autocorrect_mut(&mut self) -> &mut Autocorrect {
+        &mut self.autocorrect
+    }
+
+    /// Replace the autocorrect rules
+    pub fn set_autocorrect(&mut self, autocorrect: Autocorrect) {
+        self.autocorrect = autocorrect;
+    }
+
+    /// Insert text and then apply the first matching autocorrect rule
+    ///
+    /// The insertion and the correction are recorded as separate history
+    /// entries, so a single undo reverts the correction and keeps what the
+    /// user typed. Nothing is corrected when the document has autocorrect
+    /// disabled.
+    pub fn insert_text_with_autocorrect(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        text: &str,
+    ) -> Result<Option<Correction>, EditError> {
+        self.insert_text(node_index, position, text)?;
+
+        let cursor = position + text.len();
+        let Some((start, original, replacement)) = self.find_correction(node_index, cursor) else {
+            return Ok(None);
+        };
+
+        let command = Box::new(ApplyCorrectionCommand::new(
             self.document.clone(),
             node_index,
-            item_index,
+            start,
+            start + original.len(),
+            replacement.clone(),
         ));
-        self.execute_command(command)
+        self.execute_command(command)?;
+
+        Ok(Some(Correction {
+            node_index,
+            start,
+            original,
+            replacement,
+        }))
     }
 
-    /// Sort the items in a task list according to specified criteria
+    /// Look for a correction in the text run that ends at `cursor`
+    fn find_correction(&self, node_index: usize, cursor: usize) -> Option<(usize, String, String)> {
+        let document = self.document.borrow();
+        if !document.autocorrect_enabled() || self.autocorrect.rules().is_empty() {
+            return None;
+        }
+
+        let children = match document.nodes.get(node_index)? {
+            Node::Paragraph { children } | Node::Heading { children, .. } => children,
+            _ => return None,
+        };
+
+        let mut offset = 0;
+        for child in children {
+            let InlineNode::Text(text_node) = child else {
+                offset += 1;
+                continue;
+            };
+            let next_offset = offset + text_node.text.len();
+            if cursor > offset && cursor <= next_offset {
+                let before = text_node.text.get(..cursor - offset)?;
+                let (range, replacement) = self.autocorrect.correct(before)?;
+                return Some((offset + range.start, before[range].to_string(), replacement));
+            }
+            offset = next_offset;
+        }
+        None
+    }
+
+    /// Insert a new node at a specific position in the document
     ///
-    /// This method allows sorting task items within a task list by different criteria
-    /// such as alphabetically or by completion status.
+    /// A position among pinned nodes moves to the nearest one outside them.
+    /// Returns the index the node was inserted at.
+    pub fn insert_node(&mut self, position: usize, node: Node) -> Result<usize, EditError> {
+        let position = self.unpinned_position(position)?;
+        let command = Box::new(
+            InsertNodeCommand::new(self.document.clone(), position, node)
+                .with_url_policy(self.url_policy.clone()),
+        );
+        self.execute_command(command)?;
+        Ok(position)
+    }
+
+    /// Insert a new paragraph with text at a specific position, outside any
+    /// pinned nodes as with [`insert_node`](Self::insert_node)
+    pub fn insert_paragraph(&mut self, position: usize, text: &str) -> Result<(), EditError> {
+        let position = self.unpinned_position(position)?;
+        let command = Box::new(InsertNodeCommand::new_paragraph(
+            self.document.clone(),
+            position,
+            text,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Insert a new heading with text at a specific position
+    pub fn insert_heading(
+        &mut self,
+        position: usize,
+        level: u8,
+        text: &str,
+    ) -> Result<(), EditError> {
+        let command = Box::new(InsertNodeCommand::new_heading(
+            self.document.clone(),
+            position,
+            level,
+            text,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Insert a new code block at a specific position
+    pub fn insert_code_block(
+        &mut self,
+        position: usize,
+        code: &str,
+        language: &str,
+    ) -> Result<(), EditError> {
+        let command = Box::new(InsertNodeCommand::new_code_block(
+            self.document.clone(),
+            position,
+            code,
+            language,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Insert a captioned figure at a specific position
+    pub fn insert_figure(
+        &mut self,
+        position: usize,
+        url: &str,
+        alt: &str,
+        caption: &str,
+        properties: FigureProperties,
+    ) -> Result<(), EditError> {
+        let command = Box::new(
+            InsertNodeCommand::new(
+                self.document.clone(),
+                position,
+                Node::figure_with_properties(url, alt, caption, properties),
+            )
+            .with_url_policy(self.url_policy.clone()),
+        );
+        self.execute_command(command)
+    }
+
+    /// Insert a callout holding one paragraph of text at a specific position
     ///
-    /// # Arguments
+    /// Without a title the callout shows the kind's default title, such as
+    /// "Warning".
+    pub fn insert_callout(
+        &mut self,
+        position: usize,
+        kind: CalloutKind,
+        title: Option<&str>,
+        text: &str,
+    ) -> Result<(), EditError> {
+        let command = Box::new(InsertNodeCommand::new(
+            self.document.clone(),
+            position,
+            Node::Callout {
+                kind,
+                title: title.map(str::to_string),
+                children: vec![Node::paragraph(text)],
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Insert a paragraph after the block containing the cursor
     ///
-    /// * `node_index` - The index of the task list node in the document
-    /// * `criteria` - The criteria to use for sorting (alphabetical, unchecked first, or checked first)
+    /// Without a selection the paragraph is appended to the end of the
+    /// document. Returns the index of the new paragraph.
+    pub fn insert_paragraph_at_cursor(&mut self, text: &str) -> Result<usize, EditError> {
+        let position = self.insertion_point_after(Document::current_node_index);
+        self.insert_at_and_focus(position, Node::paragraph(text))
+    }
+
+    /// Insert a node after the last block touched by the selection
     ///
-    /// # Returns
+    /// Without a selection the node is appended to the end of the document.
+    /// Returns the index of the new node.
+    pub fn insert_node_after_selection(&mut self, node: Node) -> Result<usize, EditError> {
+        let position = self.insertion_point_after(Document::selection_end_index);
+        self.insert_at_and_focus(position, node)
+    }
+
+    /// Insert a block directly above the block containing the cursor
+    pub fn insert_block_above_current(&mut self, node: Node) -> Result<usize, EditError> {
+        let current = self.current_block()?;
+        self.insert_at_and_focus(current, node)
+    }
+
+    /// Insert a block directly below the block containing the cursor
+    pub fn insert_block_below_current(&mut self, node: Node) -> Result<usize, EditError> {
+        let current = self.current_block()?;
+        self.insert_at_and_focus(current + 1, node)
+    }
+
+    /// Index of the block holding the cursor, clamped to the document
+    fn current_block(&self) -> Result<usize, EditError> {
+        let document = self.document.borrow();
+        document
+            .current_node_index()
+            .map(|index| index.min(document.nodes.len().saturating_sub(1)))
+            .ok_or_else(|| EditError::other("No current block to insert around"))
+    }
+
+    /// Position just after the node picked from the selection, or the end of the document
+    fn insertion_point_after(&self, pick: fn(&Document) -> Option<usize>) -> usize {
+        let document = self.document.borrow();
+        let len = document.nodes.len();
+        pick(&document).map_or(len, |index| (index + 1).min(len))
+    }
+
+    /// Insert a node and move the cursor to its start, returning the index
+    /// it was inserted at
+    fn insert_at_and_focus(&mut self, position: usize, node: Node) -> Result<usize, EditError> {
+        let position = self.insert_node(position, node)?;
+        self.document.borrow_mut().selection =
+            Some(Selection::collapsed(Position::new(vec![position], 0)));
+        Ok(position)
+    }
+
+    /// Edit the alt text, titles and captions of images in one undoable step
     ///
-    /// * `Ok(())` if the operation was successful
-    /// * `Err` with appropriate error if the operation failed
+    /// The closure sees every image from [`Document::images`]; only images it
+    /// changes are written back. Returns the number of images changed.
+    pub fn update_images<F>(&mut self, mut update: F) -> Result<usize, EditError>
+    where
+        F: FnMut(&mut ImageInfo),
+    {
+        let images = self.document.borrow().images();
+        let updates: Vec<ImageInfo> = images
+            .into_iter()
+            .filter_map(|image| {
+                let mut updated = image.clone();
+                update(&mut updated);
+                (updated != image).then_some(updated)
+            })
+            .collect();
+        if updates.is_empty() {
+            return Ok(0);
+        }
+
+        let count = updates.len();
+        let command = Box::new(UpdateImagesCommand::new(self.document.clone(), updates));
+        self.execute_command(command)?;
+        Ok(count)
+    }
+
+    /// Give alt text, and captions for figures, to images that lack alt text
     ///
-    /// # Example
+    /// Pair this with [`Document::images_missing_alt`]: every fix must target
+    /// an image without alt text, and all fixes are applied as one undoable
+    /// step. Returns the number of images changed.
+    pub fn fix_missing_alt_text(&mut self, fixes: &[AltTextFix]) -> Result<usize, EditError> {
+        let missing = self.document.borrow().images_missing_alt();
+        if fixes
+            .iter()
+            .any(|fix| !missing.iter().any(|image| image.index == fix.index))
+        {
+            return Err(EditError::invalid_node());
+        }
+
+        self.update_images(|image| {
+            if let Some(fix) = fixes.iter().find(|fix| fix.index == image.index) {
+                image.alt.clone_from(&fix.alt);
+                if image.is_figure() && fix.caption.is_some() {
+                    image.caption.clone_from(&fix.caption);
+                }
+            }
+        })
+    }
+
+    /// Give links the titles found for their pages in one undoable step
     ///
-    /// ```
-    /// use md_core::{Document, Editor, ListItem, ListType, Node, SortCriteria};
+    /// Each title applies to the links to its URL in its node that
+    /// [`Document::link_title_targets`] lists: they take it as their title,
+    /// and bare URLs also as their text. Returns the number of targets that
+    /// got a title.
+    pub fn apply_link_titles(&mut self, titles: Vec<LinkTitle>) -> Result<usize, EditError> {
+        let targets = {
+            let document = self.document.borrow();
+            document.link_title_targets(0..document.nodes.len())
+        };
+        let count = targets
+            .iter()
+            .filter(|target| {
+                titles
+                    .iter()
+                    .any(|title| title.node_index == target.node_index && title.url == target.url)
+            })
+            .count();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let command = Box::new(SetLinkTitlesCommand::new(self.document.clone(), titles));
+        self.execute_command(command)?;
+        Ok(count)
+    }
+
+    /// Fetch the page titles of the links in the top-level nodes of `range`
+    /// that lack one, and apply them in one undoable step
     ///
-    /// // Create a document with a task list
-    /// let mut document = Document::new();
-    /// let items = vec![
-    ///     ListItem::task("Buy groceries", true),
-    ///     ListItem::task("Call plumber", false),
-    ///     ListItem::task("Attend meeting", false),
-    /// ];
-    /// document.nodes.push(Node::List {
-    ///     list_type: ListType::Task,
-    ///     items,
-    /// });
+    /// Pages are fetched concurrently with the limits of `options`; pages
+    /// that fail to load or have no title are skipped. Returns the number of
+    /// targets that got a title.
+    #[cfg(feature = "link-titles")]
+    pub fn fetch_link_titles(
+        &mut self,
+        range: std::ops::Range<usize>,
+        options: &crate::LinkTitleOptions,
+    ) -> Result<usize, EditError> {
+        let targets = self.document.borrow().link_title_targets(range);
+        let urls: Vec<String> = targets.iter().map(|target| target.url.clone()).collect();
+        let fetched = crate::link_titles::fetch_titles(&urls, options);
+        let titles = targets
+            .into_iter()
+            .filter_map(|target| {
+                let (_, title) = fetched.iter().find(|(url, _)| *url == target.url)?;
+                Some(LinkTitle {
+                    node_index: target.node_index,
+                    url: target.url,
+                    title: title.clone()?,
+                })
+            })
+            .collect();
+        self.apply_link_titles(titles)
+    }
+
+    /// Insert an HTML comment as a block of its own
     ///
-    /// let mut editor = Editor::new(document);
+    /// `text` goes between `<!--` and `-->` as is; it cannot contain `-->`.
+    /// Comments inside text are inserted with [`Editor::insert_inline`] and
+    /// [`InlineNode::html_comment`].
+    pub fn insert_html_comment(&mut self, position: usize, text: &str) -> Result<(), EditError> {
+        if text.contains("-->") {
+            return Err(EditError::invalid_node().with_message("Comment text contains \"-->\""));
+        }
+        self.insert_node(position, Node::html_comment(text))
+            .map(|_| ())
+    }
+
+    /// Remove HTML comments in one undoable step
     ///
-    /// // Sort the task list alphabetically
-    /// editor.sort_task_list(0, SortCriteria::Alphabetical).unwrap();
-    /// ```
-    pub fn sort_task_list(
-        &mut self,
-        node_index: usize,
-        criteria: SortCriteria,
-    ) -> Result<(), EditError> {
-        // Create a document clone to avoid borrow issues
-        let doc = self.document.borrow().clone();
+    /// Comments are addressed by their index from
+    /// [`Document::html_comments`]. Returns the number of comments removed.
+    pub fn remove_html_comments(&mut self, indices: &[usize]) -> Result<usize, EditError> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() {
+            return Ok(0);
+        }
 
-        let command = Box::new(SortTaskListCommand::new(doc, node_index, criteria));
-        self.execute_command(command)
+        let count = indices.len();
+        let command = RemoveHtmlCommentsCommand::new(self.document.clone(), indices);
+        self.execute_command(Box::new(command))?;
+        Ok(count)
     }
-}
 
-/// A command that groups multiple commands together as a single undo/redo unit
-struct CompositeCommand {
-    commands: Vec<Box<dyn EditorCommand>>,
-}
+    /// Find orphaned content and, unless only reporting, remove it
+    ///
+    /// All removals form a single undoable step. Returns the orphans found,
+    /// with node indices as they were before any removal.
+    pub fn cleanup(&mut self, options: &CleanupOptions) -> Result<Vec<Orphan>, EditError> {
+        let orphans: Vec<Orphan> = self
+            .document
+            .borrow()
+            .find_orphans()
+            .into_iter()
+            .filter(|orphan| options.kinds.contains(&orphan.kind))
+            .collect();
+
+        if options.remove && !orphans.is_empty() {
+            let mut transaction = self.begin_transaction();
+            for orphan in orphans.iter().rev() {
+                transaction.delete_node(orphan.node_index);
+            }
+            self.execute_transaction(transaction)?;
+        }
+        Ok(orphans)
+    }
 
-impl CompositeCommand {
-    fn new(commands: Vec<Box<dyn EditorCommand>>) -> Self {
-        Self { commands }
+    /// Merge consecutive compatible top-level nodes
+    ///
+    /// Repeated edits and pastes tend to leave a list split into several
+    /// lists of the same type, or one code sample spread over several code
+    /// blocks. All merges form a single undoable step; nothing is recorded
+    /// when no nodes merge. Returns the number of nodes folded into their
+    /// predecessor.
+    pub fn merge_adjacent(&mut self, options: &MergeOptions) -> Result<usize, EditError> {
+        let mut command = MergeAdjacentCommand::new(self.document.clone(), *options);
+        self.begin_change();
+        command
+            .execute()
+            .map_err(|err| err.in_command(command.name()))?;
+
+        let merged = command.merged();
+        if merged > 0 {
+            self.push_history(Box::new(command));
+        }
+        Ok(merged)
     }
-}
 
-impl EditorCommand for CompositeCommand {
-    fn execute(&mut self) -> Result<(), EditError> {
-        // All commands should already be executed by the transaction
-        Ok(())
+    /// Duplicate a node at a specific index
+    ///
+    /// Fails if the copy would move a pinned node.
+    pub fn duplicate_node(&mut self, node_index: usize) -> Result<(), EditError> {
+        if node_index < self.document.borrow().nodes.len()
+            && self.unpinned_position(node_index + 1)? != node_index + 1
+        {
+            return Err(EditError::pinned().at_node(node_index));
+        }
+        let command = Box::new(DuplicateNodeCommand::new(self.document.clone(), node_index));
+        self.execute_command(command)
     }
 
-    fn undo(&mut self) -> Result<(), EditError> {
-        // Undo in reverse order
-        for cmd in self.commands.iter_mut().rev() {
-            cmd.undo()?;
+    /// Cut the currently selected content
+    /// Returns a vector of nodes that were cut
+    ///
+    /// While changes are tracked, the selection is marked as deleted
+    /// instead, and nothing is cut if it can't be.
+    pub fn cut_selection(&mut self) -> Vec<Node> {
+        if self.track_changes.is_some() {
+            let copied = self.copy_selection();
+            return match self.delete_selection() {
+                Ok(()) => copied,
+                Err(_) => Vec::new(),
+            };
+        }
+        let mut cut_cmd = CutSelectionCommand::new(self.document.clone());
+
+        self.begin_change();
+        match cut_cmd.execute() {
+            Ok(_) => {
+                let cut_content = cut_cmd.cut_content().to_vec();
+
+                // Add to undo stack
+                self.push_history(Box::new(cut_cmd));
+
+                cut_content
+            }
+            Err(_) => Vec::new(),
         }
-        Ok(())
     }
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
+    /// Copy the currently selected content without modifying the document
+    /// Returns a vector of nodes that were copied
+    pub fn copy_selection(&mut self) -> Vec<Node> {
+        let mut copy_cmd = CopySelectionCommand::new(self.document.clone());
+
+        match copy_cmd.execute() {
+            Ok(_) => {
+                // Since copy doesn't modify the document, we don't add it to the undo stack
+                copy_cmd.get_copied_nodes().to_vec()
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Apply formatting to the selected text
+    ///
+    /// With several selections, every one of them is formatted in one
+    /// undoable step.
+    pub fn format_selection(&mut self, formatting: TextFormatting) -> Result<(), EditError> {
+        if !self.document.borrow().selections.is_empty() {
+            return self.edit_selections(SelectionEdit::Format(formatting));
+        }
+        let command = Box::new(SelectionFormatCommand::new(
+            self.document.clone(),
+            formatting,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Type text at every cursor, replacing the selected text of any
+    /// selection, in one undoable step
+    ///
+    /// Every selection must lie within one paragraph, heading or code block.
+    /// The cursors end up after the inserted text.
+    ///
+    /// With a single cursor in a paragraph, typing a Markdown shortcut of
+    /// the [input rules](Self::input_rules) at its start, such as `# `,
+    /// then converts the paragraph. There are no rules unless they are set
+    /// with [`set_input_rules`](Self::set_input_rules). The conversion is a separate history
+    /// entry, so a single undo reverts it and keeps what the user typed.
+    ///
+    /// While changes are tracked, the text is a suggested insertion after
+    /// the selected text, which is marked as deleted, and no input rules
+    /// apply.
+    pub fn insert_text_at_cursor(&mut self, text: &str) -> Result<(), EditError> {
+        if let Some(author) = self.track_changes.clone() {
+            let (index, start, end) = self.tracked_range()?;
+            let mut transaction = self.begin_transaction();
+            if start < end {
+                transaction.track_edit(TrackedEdit::Delete {
+                    author: author.clone(),
+                    node_index: index,
+                    start,
+                    end,
+                });
+            }
+            transaction.track_edit(TrackedEdit::Insert {
+                author,
+                node_index: index,
+                position: end,
+                text: text.to_string(),
+            });
+            self.execute_transaction(transaction)?;
+            let cursor = Position::new(vec![index], end + text.len());
+            self.document.borrow_mut().selection = Some(Selection::collapsed(cursor));
+            return Ok(());
+        }
+        self.edit_selections(SelectionEdit::Insert(text.to_string()))?;
+        self.apply_input_rules()
+    }
+
+    /// Get the input rules `insert_text_at_cursor` applies
+    pub fn input_rules(&self) -> &InputRules {
+        &self.input_rules
+    }
+
+    /// Get mutable access to the input rules
+    pub fn input_rules_mut(&mut self) -> &mut InputRules {
+        &mut self.input_rules
+    }
+
+    /// Replace the input rules; [`InputRules::with_defaults`] turns on the
+    /// usual Markdown shortcuts and [`InputRules::new`] turns them off
+    pub fn set_input_rules(&mut self, input_rules: InputRules) {
+        self.input_rules = input_rules;
+    }
+
+    /// Convert the paragraph at the cursor if the text before the cursor
+    /// is the shortcut of an input rule
+    fn apply_input_rules(&mut self) -> Result<(), EditError> {
+        let (index, (node, path)) = {
+            let document = self.document.borrow();
+            let Some(selection) = document.selection.as_ref() else {
+                return Ok(());
+            };
+            if !selection.is_collapsed
+                || !document.selections.is_empty()
+                || selection.start.path.len() != 1
+            {
+                return Ok(());
+            }
+            let (index, offset) = (selection.start.path[0], selection.start.offset);
+            let Some(Node::Paragraph { children }) = document.nodes.get(index) else {
+                return Ok(());
+            };
+            let Some(InlineNode::Text(first)) = children.first() else {
+                return Ok(());
+            };
+            let Some((target, start)) = first
+                .text
+                .get(..offset)
+                .and_then(|typed| self.input_rules.find(typed))
+            else {
+                return Ok(());
+            };
+            let len = offset_text(&document.nodes[index]).len();
+            let Node::Paragraph { children } = slice_node(&document.nodes[index], offset, len)
+            else {
+                unreachable!("a sliced paragraph is a paragraph");
+            };
+            (index, input_rules::convert(children, target, start))
+        };
+
+        let mut transaction = self.begin_transaction();
+        transaction.delete_node(index).insert_node(index, node);
+        self.execute_transaction(transaction)?;
+        let cursor = Position::new([vec![index], path].concat(), 0);
+        self.document.borrow_mut().selection = Some(Selection::collapsed(cursor));
+        Ok(())
+    }
+
+    /// Delete the selected text of every selection in one undoable step
+    ///
+    /// Collapsed cursors are left alone. Every selection must lie within one
+    /// paragraph, heading or code block. While changes are tracked, the
+    /// selected text is marked as deleted instead.
+    pub fn delete_selection(&mut self) -> Result<(), EditError> {
+        if self.track_changes.is_some() {
+            let (index, start, end) = self.tracked_range()?;
+            if start == end {
+                return Ok(());
+            }
+            return self.delete_text(index, start, end);
+        }
+        self.edit_selections(SelectionEdit::Delete)
+    }
+
+    fn edit_selections(&mut self, edit: SelectionEdit) -> Result<(), EditError> {
+        let command = MultiSelectionCommand::new(self.document.clone(), edit);
+        self.execute_command(Box::new(command))
+    }
+
+    /// Set the visibility of the top-level blocks the selection touches
+    ///
+    /// The blocks are taken out of any draft or hidden blocks they are in
+    /// and, unless made visible, wrapped in a single new one. The selection
+    /// moves along with its blocks.
+    pub fn set_selection_visibility(&mut self, visibility: Visibility) -> Result<(), EditError> {
+        let command = Box::new(SetVisibilityCommand::new(
+            self.document.clone(),
+            visibility,
+            false,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Like [`Editor::set_selection_visibility`], but makes the blocks
+    /// visible again if they all have `visibility` already
+    pub fn toggle_selection_visibility(&mut self, visibility: Visibility) -> Result<(), EditError> {
+        let command = Box::new(SetVisibilityCommand::new(
+            self.document.clone(),
+            visibility,
+            true,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Increase the indentation of the selected content
+    pub fn indent_selection(&mut self) -> Result<(), EditError> {
+        let command = Box::new(SelectionIndentCommand::new(
+            self.document.clone(),
+            IndentDirection::Increase,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Decrease the indentation of the selected content
+    pub fn unindent_selection(&mut self) -> Result<(), EditError> {
+        let command = Box::new(SelectionIndentCommand::new(
+            self.document.clone(),
+            IndentDirection::Decrease,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Create a table of contents from document headings
+    ///
+    /// - `position`: The position in the document where the TOC should be inserted
+    /// - `max_level`: The maximum heading level to include (1-6)
+    pub fn create_table_of_contents(
+        &mut self,
+        position: usize,
+        max_level: u8,
+    ) -> Result<(), EditError> {
+        let command = Box::new(CreateTOCCommand::new(
+            self.document.clone(),
+            position,
+            max_level,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Create an empty table with default alignments
+    ///
+    /// - `position`: The position in the document where the table should be inserted
+    /// - `columns`: The number of columns in the table
+    /// - `rows`: The number of rows in the table (not including header)
+    pub fn create_table(
+        &mut self,
+        position: usize,
+        columns: usize,
+        rows: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(CreateTableCommand::new(
+            self.document.clone(),
+            position,
+            columns,
+            rows,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Create a table with custom column alignments
+    ///
+    /// - `position`: The position in the document where the table should be inserted
+    /// - `columns`: The number of columns in the table
+    /// - `rows`: The number of rows in the table (not including header)
+    /// - `alignments`: Column alignments (one per column)
+    pub fn create_table_with_alignments(
+        &mut self,
+        position: usize,
+        columns: usize,
+        rows: usize,
+        alignments: Vec<TableAlignment>,
+    ) -> Result<(), EditError> {
+        let command = Box::new(CreateTableCommand::with_alignments(
+            self.document.clone(),
+            position,
+            columns,
+            rows,
+            alignments,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Create a table with predefined data
+    ///
+    /// - `position`: The position in the document where the table should be inserted
+    /// - `header`: Header row cells
+    /// - `rows`: Table data (rows of cells)
+    /// - `alignments`: Optional column alignments
+    pub fn create_table_with_data(
+        &mut self,
+        position: usize,
+        header: Vec<String>,
+        rows: Vec<Vec<String>>,
+        alignments: Option<Vec<TableAlignment>>,
+    ) -> Result<(), EditError> {
+        let command = Box::new(CreateTableCommand::with_data(
+            self.document.clone(),
+            position,
+            header,
+            rows,
+            alignments,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Create a table with custom properties
+    ///
+    /// - `position`: The position in the document where the table should be inserted
+    /// - `columns`: The number of columns in the table
+    /// - `rows`: The number of rows in the table (not including header)
+    /// - `properties`: Table styling and behavior properties
+    pub fn create_table_with_properties(
+        &mut self,
+        position: usize,
+        columns: usize,
+        rows: usize,
+        properties: TableProperties,
+    ) -> Result<(), EditError> {
+        let command = Box::new(CreateTableCommand::with_properties(
+            self.document.clone(),
+            position,
+            columns,
+            rows,
+            properties,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Create a table with predefined data and custom properties
+    ///
+    /// - `position`: The position in the document where the table should be inserted
+    /// - `header`: Header row cells
+    /// - `rows`: Table data (rows of cells)
+    /// - `alignments`: Optional column alignments
+    /// - `properties`: Table styling and behavior properties
+    pub fn create_table_with_data_and_properties(
+        &mut self,
+        position: usize,
+        header: Vec<String>,
+        rows: Vec<Vec<String>>,
+        alignments: Option<Vec<TableAlignment>>,
+        properties: TableProperties,
+    ) -> Result<(), EditError> {
+        let command = Box::new(CreateTableCommand::with_data_and_properties(
+            self.document.clone(),
+            position,
+            header,
+            rows,
+            alignments,
+            properties,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Add a row to an existing table
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row_index`: The index where the new row should be inserted (0 is first row after header)
+    pub fn add_table_row(&mut self, node_index: usize, row_index: usize) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::AddRow(row_index),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Remove a row from an existing table
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row_index`: The index of the row to remove
+    pub fn remove_table_row(
+        &mut self,
+        node_index: usize,
+        row_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::RemoveRow(row_index),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Add a column to an existing table
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `column_index`: The index where the new column should be inserted
+    pub fn add_table_column(
+        &mut self,
+        node_index: usize,
+        column_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::AddColumn(column_index),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Remove a column from an existing table
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `column_index`: The index of the column to remove
+    pub fn remove_table_column(
+        &mut self,
+        node_index: usize,
+        column_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::RemoveColumn(column_index),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set the content of a table cell
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: The row index of the cell (ignored if is_header is true)
+    /// - `column`: The column index of the cell
+    /// - `content`: The new content for the cell
+    /// - `is_header`: Whether the cell is in the header row
+    pub fn set_table_cell(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+        content: &str,
+        is_header: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCell {
+                row,
+                column,
+                content: content.to_string(),
+                is_header,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set the alignment of a table column
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `column`: The column index
+    /// - `alignment`: The alignment to set
+    pub fn set_table_column_alignment(
+        &mut self,
+        node_index: usize,
+        column: usize,
+        alignment: TableAlignment,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetAlignment { column, alignment },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Returns the table cell holding the cursor, if the selection is in a table
+    pub fn table_cursor(&self) -> Option<TableCursor> {
+        self.document.borrow().table_cursor()
+    }
+
+    /// Move the cursor into a table cell
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: The visual row, where 0 is the header row
+    /// - `column`: The column of the cell
+    pub fn focus_table_cell(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+    ) -> Result<TableCursor, EditError> {
+        let cursor = TableCursor::new(node_index, row, column);
+        if !self.document.borrow_mut().set_table_cursor(cursor) {
+            return Err(EditError::index_out_of_bounds());
+        }
+        Ok(cursor)
+    }
+
+    /// Move to the next table cell with Tab semantics
+    ///
+    /// Tabbing past the last cell appends an empty row and moves into its
+    /// first cell. The new row is recorded in the undo history.
+    pub fn next_table_cell(&mut self) -> Result<TableCursor, EditError> {
+        let cursor = self.current_table_cursor()?;
+        let next = self.document.borrow().next_table_cell(cursor);
+        if let Some(next) = next {
+            return self.focus_table_cell(next.node_index, next.row, next.column);
+        }
+
+        let new_row = match &self.document.borrow().nodes[cursor.node_index] {
+            Node::Table { rows, .. } => rows.len(),
+            _ => return Err(EditError::invalid_node()),
+        };
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            cursor.node_index,
+            TableOperation::AddEmptyRow(new_row),
+        ));
+        self.execute_command(command)?;
+        self.focus_table_cell(cursor.node_index, new_row + 1, 0)
+    }
+
+    /// Move to the previous table cell (Shift+Tab), staying put at the first cell
+    pub fn previous_table_cell(&mut self) -> Result<TableCursor, EditError> {
+        self.move_table_cursor(Document::previous_table_cell)
+    }
+
+    /// Move to the cell above, staying put in the top row
+    pub fn table_cell_up(&mut self) -> Result<TableCursor, EditError> {
+        self.move_table_cursor(Document::table_cell_above)
+    }
+
+    /// Move to the cell below, staying put in the bottom row
+    pub fn table_cell_down(&mut self) -> Result<TableCursor, EditError> {
+        self.move_table_cursor(Document::table_cell_below)
+    }
+
+    fn current_table_cursor(&self) -> Result<TableCursor, EditError> {
+        self.table_cursor()
+            .ok_or_else(|| EditError::other("Selection is not inside a table"))
+    }
+
+    fn move_table_cursor(
+        &mut self,
+        step: fn(&Document, TableCursor) -> Option<TableCursor>,
+    ) -> Result<TableCursor, EditError> {
+        let cursor = self.current_table_cursor()?;
+        let target = step(&self.document.borrow(), cursor).unwrap_or(cursor);
+        self.focus_table_cell(target.node_index, target.row, target.column)
+    }
+
+    /// Group multiple nodes together
+    ///
+    /// - `node_indices`: Indices of nodes to group
+    /// - `group_name`: Name or type of the group
+    pub fn group_nodes(
+        &mut self,
+        node_indices: Vec<usize>,
+        group_name: &str,
+    ) -> Result<(), EditError> {
+        let command = Box::new(GroupNodesCommand::new(
+            self.document.clone(),
+            node_indices,
+            group_name.to_string(),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Selects all content in the document
+    pub fn select_all(&mut self) -> Result<(), EditError> {
+        if !self.select_with(|document| document.select_all()) {
+            return Err(EditError::operation_failed());
+        }
+        Ok(())
+    }
+
+    /// Selects a specific node by index
+    pub fn select_node(&mut self, node_index: usize) -> Result<(), EditError> {
+        if !self.select_with(|document| document.select_node(node_index)) {
+            return Err(EditError::index_out_of_bounds());
+        }
+        Ok(())
+    }
+
+    /// Selects a range of nodes
+    pub fn select_node_range(
+        &mut self,
+        start_index: usize,
+        end_index: usize,
+    ) -> Result<(), EditError> {
+        if !self.select_with(|document| document.select_node_range(start_index, end_index)) {
+            return Err(EditError::invalid_range());
+        }
+        Ok(())
+    }
+
+    /// Selects a range of text within a node, counted in the given unit
+    ///
+    /// The stored selection always uses byte offsets.
+    pub fn select_text_range_at(
+        &mut self,
+        node_index: usize,
+        start_offset: usize,
+        end_offset: usize,
+        kind: OffsetKind,
+    ) -> Result<(), EditError> {
+        let (start, end) = self.byte_range(node_index, start_offset, end_offset, kind)?;
+        self.select_text_range(node_index, start, end)
+    }
+
+    /// Selects a specific range of text within a node
+    pub fn select_text_range(
+        &mut self,
+        node_index: usize,
+        start_offset: usize,
+        end_offset: usize,
+    ) -> Result<(), EditError> {
+        if !self.select_with(|document| {
+            document.select_text_range(node_index, start_offset, end_offset)
+        }) {
+            return Err(EditError::invalid_range());
+        }
+        Ok(())
+    }
+
+    /// Selects from one position to another across any nodes
+    pub fn select_range(
+        &mut self,
+        start_node: usize,
+        start_offset: usize,
+        end_node: usize,
+        end_offset: usize,
+    ) -> Result<(), EditError> {
+        {
+            let document = self.document.borrow();
+            check_position(&document, &Position::new(vec![start_node], start_offset))?;
+            check_position(&document, &Position::new(vec![end_node], end_offset))?;
+        }
+        if !self.select_with(|document| {
+            document.select_range(start_node, start_offset, end_node, end_offset)
+        }) {
+            return Err(EditError::invalid_range());
+        }
+        Ok(())
+    }
+
+    /// Collapses the current selection to its start position
+    pub fn collapse_selection_to_start(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        if !document.collapse_selection_to_start() {
+            return Err(EditError::other("No selection to collapse"));
+        }
+        Ok(())
+    }
+
+    /// Collapses the current selection to its end position
+    pub fn collapse_selection_to_end(&mut self) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        if !document.collapse_selection_to_end() {
+            return Err(EditError::other("No selection to collapse"));
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor, collapsing any selection
+    ///
+    /// Without a selection the cursor starts at the beginning of the
+    /// document. Moving left or right from a range collapses it to its
+    /// earlier or later edge. Returns the new cursor position.
+    pub fn move_cursor(&mut self, motion: CursorMotion) -> Result<Position, EditError> {
+        let mut document = self.document.borrow_mut();
+        if document.nodes.is_empty() {
+            return Err(EditError::index_out_of_bounds());
+        }
+
+        let position = match document.selection.take() {
+            None => motion.apply(&document, &Position::start()),
+            Some(selection) if !selection.is_collapsed => {
+                let forward = position_key(&selection.start) <= position_key(&selection.end);
+                match motion {
+                    CursorMotion::Left if forward => selection.start,
+                    CursorMotion::Right if !forward => selection.start,
+                    CursorMotion::Left | CursorMotion::Right => selection.end,
+                    _ => motion.apply(&document, &selection.end),
+                }
+            }
+            Some(selection) => motion.apply(&document, &selection.end),
+        };
+
+        document.selection = Some(Selection::collapsed(position.clone()));
+        Ok(position)
+    }
+
+    /// Moves the end of the selection, keeping its start as the anchor
+    ///
+    /// The end may move before the start, so the selection remembers which
+    /// side is being extended. Returns the updated selection.
+    pub fn extend_selection(&mut self, motion: CursorMotion) -> Result<Selection, EditError> {
+        let mut document = self.document.borrow_mut();
+        if document.nodes.is_empty() {
+            return Err(EditError::index_out_of_bounds());
+        }
+
+        let anchor = document
+            .selection
+            .as_ref()
+            .map_or_else(Position::start, |selection| selection.start.clone());
+        let focus = document
+            .selection
+            .as_ref()
+            .map_or_else(Position::start, |selection| selection.end.clone());
+        let selection = Selection::new(anchor, motion.apply(&document, &focus));
+
+        document.selection = Some(selection.clone());
+        Ok(selection)
+    }
+
+    /// Moves the cursor one grapheme to the left
+    pub fn move_cursor_left(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::Left)
+    }
+
+    /// Moves the cursor one grapheme to the right
+    pub fn move_cursor_right(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::Right)
+    }
+
+    /// Moves the cursor to the start of the current or previous word
+    pub fn move_cursor_word_left(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::WordLeft)
+    }
+
+    /// Moves the cursor to the end of the current or next word
+    pub fn move_cursor_word_right(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::WordRight)
+    }
+
+    /// Moves the cursor to the start of the current node
+    pub fn move_cursor_line_start(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::LineStart)
+    }
+
+    /// Moves the cursor to the end of the current node
+    pub fn move_cursor_line_end(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::LineEnd)
+    }
+
+    /// Moves the cursor to the same column in the previous node
+    pub fn move_cursor_up(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::Up)
+    }
+
+    /// Moves the cursor to the same column in the next node
+    pub fn move_cursor_down(&mut self) -> Result<Position, EditError> {
+        self.move_cursor(CursorMotion::Down)
+    }
+
+    /// Adds a selection, e.g. another cursor for editing in several places
+    /// at once
+    ///
+    /// Becomes the primary selection if there is none.
+    pub fn add_selection(&mut self, selection: Selection) -> Result<(), EditError> {
+        let mut document = self.document.borrow_mut();
+        for position in [&selection.start, &selection.end] {
+            if position
+                .path
+                .first()
+                .is_none_or(|index| *index >= document.nodes.len())
+            {
+                return Err(EditError::invalid_node().at_path(position.path.clone()));
+            }
+            check_position(&document, position)?;
+        }
+        document.add_selection(selection);
+        Ok(())
+    }
+
+    /// Adds a cursor at a byte offset in a top-level node
+    pub fn add_cursor(&mut self, node_index: usize, offset: usize) -> Result<(), EditError> {
+        self.add_selection(Selection::collapsed(Position::new(
+            vec![node_index],
+            offset,
+        )))
+    }
+
+    /// Returns the primary selection followed by any further selections
+    pub fn selections(&self) -> Vec<Selection> {
+        self.document.borrow().all_selections()
+    }
+
+    /// Drops all selections but the primary one
+    pub fn clear_secondary_selections(&mut self) {
+        self.document.borrow_mut().clear_secondary_selections();
+    }
+
+    /// Clears the current selection
+    pub fn clear_selection(&mut self) {
+        let mut document = self.document.borrow_mut();
+        document.clear_selection();
+    }
+
+    /// Returns whether there is currently a selection
+    pub fn has_selection(&self) -> bool {
+        let document = self.document.borrow();
+        document.has_selection()
+    }
+
+    /// Returns whether the current selection spans multiple nodes
+    pub fn has_multi_node_selection(&self) -> bool {
+        let document = self.document.borrow();
+        document.has_multi_node_selection()
+    }
+
+    /// Gets the currently selected text, if any
+    pub fn get_selected_text(&self) -> Option<String> {
+        let document = self.document.borrow();
+        document.get_selected_text()
+    }
+
+    /// Counts the words and characters of the selection, if any
+    ///
+    /// See [`Document::selection_stats`].
+    pub fn selection_stats(&self) -> Option<Counts> {
+        self.document.borrow().selection_stats()
+    }
+
+    /// Gets the selected content with its formatting and structure, if any
+    ///
+    /// See [`Document::selected_fragment`].
+    pub fn get_selected_fragment(&self) -> Option<DocumentFragment> {
+        self.document.borrow().selected_fragment()
+    }
+
+    /// Insert a fragment's blocks at a position in the document in one
+    /// undoable step
+    ///
+    /// Inline fragments are inserted as a paragraph. The position moves out
+    /// of pinned nodes as with [`insert_node`](Self::insert_node), and every
+    /// block is checked against the URL policy before any is inserted.
+    /// Returns the number of blocks inserted.
+    pub fn insert_fragment(
+        &mut self,
+        position: usize,
+        fragment: DocumentFragment,
+    ) -> Result<usize, EditError> {
+        let position = self.unpinned_position(position)?;
+        let nodes = fragment.into_nodes();
+        for node in &nodes {
+            self.url_policy.check_node(node)?;
+        }
+        let count = nodes.len();
+        let mut transaction = self.begin_transaction();
+        for (offset, node) in nodes.into_iter().enumerate() {
+            transaction.insert_node(position + offset, node);
+        }
+        self.execute_transaction(transaction)?;
+        Ok(count)
+    }
+
+    /// Resolve the merge conflict at `node_index` by keeping `side`, in one
+    /// undoable step
+    ///
+    /// Fails with [`EditErrorKind::InvalidNode`] if the node isn't a
+    /// [`Node::Conflict`].
+    pub fn resolve_conflict(
+        &mut self,
+        node_index: usize,
+        side: ConflictSide,
+    ) -> Result<(), EditError> {
+        let mut transaction = self.begin_transaction();
+        self.resolve_in(&mut transaction, node_index, side)?;
+        self.execute_transaction(transaction)
+    }
+
+    /// Resolve every top-level merge conflict by keeping `side`, in one
+    /// undoable step, returning how many were resolved
+    pub fn resolve_all_conflicts(&mut self, side: ConflictSide) -> Result<usize, EditError> {
+        let conflicts = self.document.borrow().conflicts();
+        if conflicts.is_empty() {
+            return Ok(0);
+        }
+        let mut transaction = self.begin_transaction();
+        // From the end, so resolving a conflict doesn't move the others
+        for &node_index in conflicts.iter().rev() {
+            self.resolve_in(&mut transaction, node_index, side)?;
+        }
+        self.execute_transaction(transaction)?;
+        Ok(conflicts.len())
+    }
+
+    /// Add the steps replacing a conflict by the blocks of `side` to a
+    /// transaction
+    fn resolve_in(
+        &self,
+        transaction: &mut Transaction,
+        node_index: usize,
+        side: ConflictSide,
+    ) -> Result<(), EditError> {
+        let (ours, theirs) = match self.document.borrow().nodes.get(node_index) {
+            Some(Node::Conflict { ours, theirs }) => (ours.clone(), theirs.clone()),
+            Some(_) => return Err(EditError::invalid_node().at_node(node_index)),
+            None => return Err(EditError::index_out_of_bounds().at_node(node_index)),
+        };
+        transaction.delete_node(node_index);
+        for (offset, node) in side.choose(ours, theirs).into_iter().enumerate() {
+            transaction.insert_node(node_index + offset, node);
+        }
+        Ok(())
+    }
+
+    /// Paste a fragment at the end of the selection in one undoable step
+    ///
+    /// Inline fragments go into the paragraph or heading the selection ends
+    /// in, leaving the cursor after them; block fragments, and inline ones
+    /// pasted anywhere else, are inserted after the last block the selection
+    /// touches, leaving the cursor at the start of the first. Without a
+    /// selection the blocks are appended. The selection itself is kept;
+    /// delete it first to replace it. Pasting fails while changes are
+    /// tracked.
+    pub fn paste_fragment(&mut self, fragment: DocumentFragment) -> Result<(), EditError> {
+        self.paste_fragment_at(fragment).map(|_| ())
+    }
+
+    /// Paste a fragment like [`paste_fragment`](Self::paste_fragment),
+    /// returning where its content starts, if anything was pasted
+    fn paste_fragment_at(
+        &mut self,
+        fragment: DocumentFragment,
+    ) -> Result<Option<Position>, EditError> {
+        self.check_untracked()?;
+        if fragment.is_empty() {
+            return Ok(None);
+        }
+        let len = fragment.inline_len();
+        match (fragment, self.paste_cursor()) {
+            (DocumentFragment::Inline(inlines), Some(cursor)) => {
+                let command = Box::new(
+                    InsertInlineCommand::many(
+                        self.document.clone(),
+                        cursor.path[0],
+                        cursor.offset,
+                        inlines,
+                    )
+                    .with_url_policy(self.url_policy.clone()),
+                );
+                self.execute_command(command)?;
+                self.document.borrow_mut().selection = Some(Selection::collapsed(Position::new(
+                    cursor.path.clone(),
+                    cursor.offset + len,
+                )));
+                Ok(Some(cursor))
+            }
+            (fragment, _) => {
+                let position = self.insertion_point_after(Document::selection_end_index);
+                let position = self.unpinned_position(position)?;
+                if self.insert_fragment(position, fragment)? == 0 {
+                    return Ok(None);
+                }
+                let start = Position::new(vec![position], 0);
+                self.document.borrow_mut().selection = Some(Selection::collapsed(start.clone()));
+                Ok(Some(start))
+            }
+        }
+    }
+
+    /// The end of the selection, if it is in the text of a top-level
+    /// paragraph or heading
+    fn paste_cursor(&self) -> Option<Position> {
+        let document = self.document.borrow();
+        let selection = document.selection.as_ref()?;
+        let end = std::cmp::max_by_key(&selection.start, &selection.end, |position| {
+            (position.path[0], position.offset)
+        });
+        let in_text = matches!(
+            document.nodes.get(end.path[0]),
+            Some(Node::Paragraph { .. } | Node::Heading { .. })
+        );
+        (in_text && end.path.len() == 1).then(|| end.clone())
+    }
+
+    /// Parse Markdown from the clipboard and paste it at the end of the
+    /// selection in one undoable step
+    ///
+    /// A single paragraph without a trailing newline merges into the
+    /// paragraph or heading at the cursor, like an inline fragment. Blocks
+    /// split that node at the cursor instead: a leading pasted paragraph
+    /// joins the text before the cursor, and in a paragraph the text after
+    /// the cursor joins a trailing pasted paragraph, so pasting `"a\n\nb"`
+    /// into `x|y` gives the paragraphs `xa` and `by`. The cursor ends up
+    /// after the pasted content. Anywhere else the content is pasted like
+    /// [`paste_fragment`](Self::paste_fragment).
+    pub fn paste_markdown(&mut self, markdown: &str) -> Result<(), EditError> {
+        let fragment = DocumentFragment::from_markdown(markdown)
+            .map_err(|err| EditError::other(err.to_string()))?;
+        self.smart_paste(fragment)
+    }
+
+    /// Parse HTML from the clipboard and paste it at the end of the
+    /// selection in one undoable step, like
+    /// [`paste_markdown`](Self::paste_markdown)
+    ///
+    /// Content that doesn't start with a block element and parses to a
+    /// single paragraph merges into the node at the cursor.
+    pub fn paste_html(&mut self, html: &str) -> Result<(), EditError> {
+        let fragment =
+            DocumentFragment::from_html(html).map_err(|err| EditError::other(err.to_string()))?;
+        self.smart_paste(fragment)
+    }
+
+    /// Paste a parsed clipboard payload, splitting the node at the cursor
+    /// around pasted blocks
+    fn smart_paste(&mut self, fragment: DocumentFragment) -> Result<(), EditError> {
+        self.check_untracked()?;
+        let (DocumentFragment::Blocks(pasted), Some(cursor)) = (&fragment, self.paste_cursor())
+        else {
+            return self.paste_fragment(fragment);
+        };
+        if pasted.is_empty() {
+            return Ok(());
+        }
+        let index = cursor.path[0];
+        self.check_pins(index..index + 1)?;
+        for node in pasted {
+            self.url_policy.check_node(node)?;
+        }
+        let node = self.document.borrow().nodes[index].clone();
+        let len = offset_text(&node).len();
+        let offset = cursor.offset.min(len);
+
+        let mut nodes = Vec::with_capacity(pasted.len() + 2);
+        let mut blocks = pasted.clone().into_iter().peekable();
+        if offset > 0 {
+            let mut head = slice_node(&node, 0, offset);
+            if let Node::Paragraph { children } | Node::Heading { children, .. } = &mut head
+                && let Some(Node::Paragraph { children: first }) =
+                    blocks.next_if(|block| matches!(block, Node::Paragraph { .. }))
+            {
+                children.extend(first);
+            }
+            nodes.push(head);
+        }
+        nodes.extend(blocks);
+
+        let last = nodes.len() - 1;
+        let last_len = offset_text(&nodes[last]).len();
+        let end = if offset == len {
+            Position::new(vec![index + last], last_len)
+        } else if let (Node::Paragraph { children }, Node::Paragraph { children: tail }) =
+            (&mut nodes[last], slice_node(&node, offset, len))
+        {
+            children.extend(tail);
+            Position::new(vec![index + last], last_len)
+        } else {
+            nodes.push(slice_node(&node, offset, len));
+            Position::new(vec![index + last + 1], 0)
+        };
+
+        let mut transaction = self.begin_transaction();
+        transaction.delete_node(index);
+        for (offset, node) in nodes.into_iter().enumerate() {
+            transaction.insert_node(index + offset, node);
+        }
+        self.execute_transaction(transaction)?;
+        self.document.borrow_mut().selection = Some(Selection::collapsed(end));
+        Ok(())
+    }
+
+    /// Get the snippets `insert_snippet` expands
+    pub fn snippets(&self) -> &SnippetRegistry {
+        &self.snippets
+    }
+
+    /// Get the snippets for registering or removing some
+    pub fn snippets_mut(&mut self) -> &mut SnippetRegistry {
+        &mut self.snippets
+    }
+
+    /// Expand a registered snippet with `vars` and paste it at the end of
+    /// the selection in one undoable step
+    ///
+    /// The snippet is pasted like a [`DocumentFragment`]. If it has tab
+    /// stops, the first is selected and [`next_tab_stop`](Self::next_tab_stop)
+    /// and [`prev_tab_stop`](Self::prev_tab_stop) move between them until
+    /// another snippet is inserted or the last stop is left. The stops move
+    /// along with edits, so typing over a stop's default text keeps the
+    /// later stops in place.
+    pub fn insert_snippet(&mut self, name: &str, vars: &[(&str, &str)]) -> Result<(), EditError> {
+        let expanded = self
+            .snippets
+            .get(name)
+            .ok_or_else(|| EditError::other(format!("Unknown snippet: {name}")))?
+            .expand(vars);
+        self.tab_stops = None;
+        let Some(start) = self.paste_fragment_at(expanded.fragment)? else {
+            return Ok(());
+        };
+        let stops = expanded
+            .tab_stops
+            .into_iter()
+            .map(|stop| {
+                let shift = if stop.node == 0 { start.offset } else { 0 };
+                (
+                    vec![start.path[0] + stop.node],
+                    stop.range.start + shift..stop.range.end + shift,
+                )
+            })
+            .collect();
+        self.tab_stops = TabStops::new(stops);
+        self.select_tab_stop();
+        Ok(())
+    }
+
+    /// Select the next tab stop of the last inserted snippet, returning
+    /// false once there is none left
+    pub fn next_tab_stop(&mut self) -> bool {
+        self.step_tab_stop(true)
+    }
+
+    /// Select the previous tab stop of the last inserted snippet, returning
+    /// false at the first one or without a snippet
+    pub fn prev_tab_stop(&mut self) -> bool {
+        self.step_tab_stop(false)
+    }
+
+    /// Returns true while the tab stops of an inserted snippet are visited
+    pub fn has_tab_stops(&self) -> bool {
+        self.tab_stops.is_some()
+    }
+
+    fn step_tab_stop(&mut self, forward: bool) -> bool {
+        let Some(tab_stops) = &mut self.tab_stops else {
+            return false;
+        };
+        if !tab_stops.step(forward) {
+            if forward {
+                self.tab_stops = None;
+            }
+            return false;
+        }
+        self.select_tab_stop()
+    }
+
+    /// Select the current tab stop, leaving the tab stops if it can't be
+    /// selected any more
+    fn select_tab_stop(&mut self) -> bool {
+        let Some((node_index, range)) = self.tab_stops.as_ref().map(|stops| {
+            let (path, range) = stops.current();
+            (path[0], range)
+        }) else {
+            return false;
+        };
+        // Tab stops lie in paragraphs, headings and code blocks, whose text
+        // is the offset text
+        let selected = self.select_with(|document| {
+            let fits = document.nodes.get(node_index).is_some_and(|node| {
+                let text = offset_text(node);
+                range.end <= text.len()
+                    && text.is_char_boundary(range.start)
+                    && text.is_char_boundary(range.end)
+            });
+            if fits {
+                document.selection = Some(Selection::new(
+                    Position::new(vec![node_index], range.start),
+                    Position::new(vec![node_index], range.end),
+                ));
+            }
+            fits
+        });
+        if !selected {
+            self.tab_stops = None;
+        }
+        selected
+    }
+
+    /// Begin a transaction to group multiple operations into a single atomic change.
+    ///
+    /// Returns a Transaction object that can be used to build up a series of operations.
+    /// The transaction is not applied to the document until it is committed and executed.
+    ///
+    /// # Example
+    /// ```
+    /// # use md_core::{Document, Editor, EditError};
+    /// # fn example() -> Result<(), EditError> {
+    /// # let doc = Document::new();
+    /// # let mut editor = Editor::new(doc);
+    /// let mut transaction = editor.begin_transaction();
+    /// transaction.insert_text(0, 0, "Hello");
+    /// editor.execute_transaction(transaction)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn begin_transaction(&self) -> Transaction {
+        Transaction::new(self.document.clone())
+    }
+
+    /// Execute a transaction with a provided closure that builds the transaction.
+    ///
+    /// # Example
+    /// ```
+    /// # use md_core::{Document, Editor, TextFormatting, EditError};
+    /// # fn example() -> Result<(), EditError> {
+    /// # let doc = Document::new();
+    /// # let mut editor = Editor::new(doc);
+    /// editor.with_transaction(|mut transaction| {
+    ///     transaction
+    ///         .insert_text(0, 0, "Hello")
+    ///         .format_text(0, 0, 5, TextFormatting {
+    ///             bold: true,
+    ///             ..Default::default()
+    ///         });
+    ///     transaction
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_transaction<F>(&mut self, transaction_builder: F) -> Result<(), EditError>
+    where
+        F: FnOnce(Transaction) -> Transaction,
+    {
+        // Create a new transaction
+        let transaction = Transaction::new(self.document.clone());
+
+        // Let the closure build the transaction
+        let transaction = transaction_builder(transaction);
+
+        // Execute the transaction
+        self.execute_transaction(transaction)
+    }
+
+    /// Execute a transaction, updating the undo stack as a single composite command.
+    ///
+    /// This method commits the transaction and applies the changes to the document.
+    pub fn execute_transaction(&mut self, transaction: Transaction) -> Result<(), EditError> {
+        // Commit the transaction
+        self.begin_change();
+        let commands = transaction.commit()?;
+
+        // Execute the committed commands
+        self.execute_transaction_commands(commands)
+    }
+
+    /// Execute a list of commands from a transaction and add to undo stack.
+    ///
+    /// This is a lower-level method that's used by execute_transaction.
+    pub fn execute_transaction_commands(
+        &mut self,
+        commands: Vec<Box<dyn EditorCommand>>,
+    ) -> Result<(), EditError> {
+        // If there are no commands, nothing to do
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        // Create a composite command that represents all commands as one operation
+        let composite = CompositeCommand::new(commands);
+
+        // Add to undo stack, clearing the redo stack
+        self.push_history(Box::new(composite));
+
+        Ok(())
+    }
+
+    /// Set the background color of a table cell
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: The row index of the cell (0 is the first row after the header)
+    /// - `column`: The column index of the cell (0 is the first column)
+    /// - `color`: The background color in hex format (e.g., "#f5f5f5")
+    /// - `is_header`: Whether to modify a header cell or a body cell
+    pub fn set_table_cell_background(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+        color: impl Into<String>,
+        is_header: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCellBackground {
+                row,
+                column,
+                color: color.into(),
+                is_header,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set custom CSS style for a table cell
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: The row index of the cell (0 is the first row after the header)
+    /// - `column`: The column index of the cell (0 is the first column)
+    /// - `style`: CSS style string (e.g., "font-weight: bold; color: red;")
+    /// - `is_header`: Whether to modify a header cell or a body cell
+    pub fn set_table_cell_style(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+        style: impl Into<String>,
+        is_header: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCellStyle {
+                row,
+                column,
+                style: style.into(),
+                is_header,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set the spanning of a table cell
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: The row index of the cell (0 is the first row after the header)
+    /// - `column`: The column index of the cell (0 is the first column)
+    /// - `colspan`: Number of columns this cell should span
+    /// - `rowspan`: Number of rows this cell should span
+    /// - `is_header`: Whether to modify a header cell or a body cell
+    pub fn set_table_cell_span(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+        colspan: u32,
+        rowspan: u32,
+        is_header: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCellSpan {
+                row,
+                column,
+                colspan,
+                rowspan,
+                is_header,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Merge a rectangular block of body cells into one spanning cell
+    ///
+    /// Unlike `set_table_cell_span`, the covered cells are removed from the
+    /// rows and their content is moved into the merged cell.
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `start_row`, `end_row`: The first and last body rows of the block
+    /// - `start_col`, `end_col`: The first and last grid columns of the block
+    pub fn merge_table_cells(
+        &mut self,
+        node_index: usize,
+        start_row: usize,
+        start_col: usize,
+        end_row: usize,
+        end_col: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::MergeCells {
+                start_row,
+                start_col,
+                end_row,
+                end_col,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Split a merged body cell back into single cells
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: A body row covered by the merged cell
+    /// - `column`: A grid column covered by the merged cell
+    pub fn split_table_cell(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SplitCell { row, column },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Swap the rows and columns of a table
+    ///
+    /// - `node_index`: The index of the table node in the document
+    pub fn transpose_table(&mut self, node_index: usize) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::Transpose,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Reflow a single-column table into a grid
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `columns`: The number of columns of the new grid
+    pub fn reshape_table(&mut self, node_index: usize, columns: usize) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::ReshapeToColumns(columns),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set or clear the typed value of a body cell
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `row`: The body row index
+    /// - `column`: The cell index within the row
+    /// - `value`: The typed value, or `None` to clear it
+    pub fn set_table_cell_value(
+        &mut self,
+        node_index: usize,
+        row: usize,
+        column: usize,
+        value: Option<CellValue>,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCellValue { row, column, value },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Sort the body rows of a table by a column
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `column`: The logical grid column to sort by
+    /// - `descending`: Whether to put the largest values first
+    pub fn sort_table(
+        &mut self,
+        node_index: usize,
+        column: usize,
+        descending: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SortByColumn { column, descending },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set table properties
+    ///
+    /// - `node_index`: The index of the table node in the document
+    /// - `properties`: The table properties to set
+    pub fn set_table_properties(
+        &mut self,
+        node_index: usize,
+        properties: TableProperties,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetTableProperties(properties),
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set the caption of a table, shown above it or, with `at_bottom`,
+    /// below it
+    pub fn set_table_caption(
+        &mut self,
+        node_index: usize,
+        text: impl Into<String>,
+        at_bottom: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCaption {
+                text: Some(text.into()),
+                at_bottom,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Remove the caption of a table
+    pub fn remove_table_caption(&mut self, node_index: usize) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetCaption {
+                text: None,
+                at_bottom: false,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Set or clear the summary and accessible name of a table
+    ///
+    /// - `summary`: Description of the table's contents, exported as `aria-description`
+    /// - `aria_label`: Accessible name, exported as `aria-label`
+    pub fn set_table_accessibility(
+        &mut self,
+        node_index: usize,
+        summary: Option<String>,
+        aria_label: Option<String>,
+    ) -> Result<(), EditError> {
+        let command = Box::new(TableOperationsCommand::new(
+            self.document.clone(),
+            node_index,
+            TableOperation::SetAccessibility {
+                summary,
+                aria_label,
+            },
+        ));
+        self.execute_command(command)
+    }
+
+    /// Toggle the checked status of a task list item
+    pub fn toggle_task(&mut self, node_index: usize, item_index: usize) -> Result<(), EditError> {
+        let command = Box::new(ToggleTaskCommand::new(
+            self.document.clone(),
+            node_index,
+            item_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Increase the indentation level of a task list item
+    pub fn indent_task_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(IndentTaskItemCommand::increase_indent(
+            self.document.clone(),
+            node_index,
+            item_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Decrease the indentation level of a task list item
+    pub fn dedent_task_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(IndentTaskItemCommand::decrease_indent(
+            self.document.clone(),
+            node_index,
+            item_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Add a new item to a task list
+    pub fn add_task_item(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        text: impl Into<String>,
+        checked: bool,
+    ) -> Result<(), EditError> {
+        let command = Box::new(AddTaskItemCommand::new(
+            self.document.clone(),
+            node_index,
+            position,
+            text,
+            checked,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Remove an item from a task list
+    pub fn remove_task_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        let command = Box::new(RemoveTaskItemCommand::new(
+            self.document.clone(),
+            node_index,
+            item_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Edit the text content of a task list item
+    pub fn edit_task_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+        text: impl Into<String>,
+    ) -> Result<(), EditError> {
+        let command = Box::new(EditTaskItemCommand::new(
+            self.document.clone(),
+            node_index,
+            item_index,
+            text,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Move a task item within a task list
+    ///
+    /// # Arguments
+    ///
+    /// * `node_index` - The index of the task list node
+    /// * `from_index` - The index of the item to move
+    /// * `to_index` - The destination index
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the operation was successful
+    /// * `Err` with appropriate error if the operation failed
+    pub fn move_task_item(
+        &mut self,
+        node_index: usize,
+        from_index: usize,
+        to_index: usize,
+    ) -> Result<(), EditError> {
+        if from_index == to_index {
+            return Ok(());
+        }
+
+        let command = Box::new(MoveTaskPositionCommand::new(
+            self.document.clone(),
+            node_index,
+            from_index,
+            to_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Move a task item up in the task list (swap with the previous item)
+    ///
+    /// # Arguments
+    ///
+    /// * `node_index` - The index of the task list node
+    /// * `item_index` - The index of the item to move up
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the operation was successful
+    /// * `Err` with appropriate error if the operation failed (e.g., already at the top)
+    pub fn move_task_item_up(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        // Cannot move the first item up
+        if item_index == 0 {
+            return Err(EditError::other("Already at the top"));
+        }
+
+        let command = Box::new(MoveTaskItemCommand::move_up(
+            self.document.clone(),
+            node_index,
+            item_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Move a task item down in the task list (swap with the next item)
+    ///
+    /// # Arguments
+    ///
+    /// * `node_index` - The index of the task list node
+    /// * `item_index` - The index of the item to move down
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the operation was successful
+    /// * `Err` with appropriate error if the operation failed (e.g., already at the bottom)
+    pub fn move_task_item_down(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        // Check if this is the last item (we'll need to validate this in the command too)
+        let doc = self.document.borrow();
+        let items_len = match &doc.nodes.get(node_index) {
+            Some(Node::List { items, .. }) => items.len(),
+            _ => return Err(EditError::index_out_of_bounds()),
+        };
+        drop(doc); // Release the borrow before continuing
+
+        // Cannot move the last item down
+        if item_index >= items_len - 1 {
+            return Err(EditError::other("Already at the bottom"));
+        }
+
+        let command = Box::new(MoveTaskItemCommand::move_down(
+            self.document.clone(),
+            node_index,
+            item_index,
+        ));
+        self.execute_command(command)
+    }
+
+    /// Add an item with a paragraph of text to a list of any type
+    ///
+    /// Items added to a task list start unchecked.
+    pub fn add_list_item(
+        &mut self,
+        node_index: usize,
+        position: usize,
+        text: impl Into<String>,
+    ) -> Result<(), EditError> {
+        let text = text.into();
+        self.list_operation(node_index, ListOperation::AddItem { position, text })
+    }
+
+    /// Remove an item from a list of any type
+    ///
+    /// The only item of a list cannot be removed; delete the list instead.
+    pub fn remove_list_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        self.list_operation(node_index, ListOperation::RemoveItem(item_index))
+    }
+
+    /// Replace the text of a list item, keeping its nested lists
+    pub fn edit_list_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+        text: impl Into<String>,
+    ) -> Result<(), EditError> {
+        let item = item_index;
+        let text = text.into();
+        self.list_operation(node_index, ListOperation::EditItem { item, text })
+    }
+
+    /// Move an item within a list of any type
+    pub fn move_list_item(
+        &mut self,
+        node_index: usize,
+        from_index: usize,
+        to_index: usize,
+    ) -> Result<(), EditError> {
+        let (from, to) = (from_index, to_index);
+        self.list_operation(node_index, ListOperation::MoveItem { from, to })
+    }
+
+    /// Nest a list item at the end of the item before it
+    pub fn indent_list_item(
+        &mut self,
+        node_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        self.list_operation(node_index, ListOperation::IndentItem(item_index))
+    }
+
+    /// Move the item at `item_index` of the list nested in the item at
+    /// `parent_index` out to the list itself, right after its parent
+    pub fn unindent_list_item(
+        &mut self,
+        node_index: usize,
+        parent_index: usize,
+        item_index: usize,
+    ) -> Result<(), EditError> {
+        let (parent, item) = (parent_index, item_index);
+        self.list_operation(node_index, ListOperation::UnindentItem { parent, item })
+    }
+
+    /// Convert a list between ordered, unordered and task lists
+    ///
+    /// Items become unchecked tasks when converting to a task list and lose
+    /// their checkboxes otherwise; nested lists keep their type.
+    pub fn convert_list_type(
+        &mut self,
+        node_index: usize,
+        list_type: ListType,
+    ) -> Result<(), EditError> {
+        self.list_operation(node_index, ListOperation::ConvertType(list_type))
+    }
+
+    fn list_operation(
+        &mut self,
+        node_index: usize,
+        operation: ListOperation,
+    ) -> Result<(), EditError> {
+        let command = ListOperationsCommand::new(self.document.clone(), node_index, operation);
+        self.execute_command(Box::new(command))
+    }
+
+    /// Check every task of the document in one undoable step
+    ///
+    /// Returns the number of tasks that were unchecked.
+    pub fn check_all_tasks(&mut self) -> Result<usize, EditError> {
+        let pending = self.document.borrow().task_summary().pending();
+        self.bulk_task_action(BulkTaskAction::CheckAll, pending)
+    }
+
+    /// Uncheck every task of the document in one undoable step
+    ///
+    /// Returns the number of tasks that were checked.
+    pub fn uncheck_all_tasks(&mut self) -> Result<usize, EditError> {
+        let completed = self.document.borrow().task_summary().completed;
+        self.bulk_task_action(BulkTaskAction::UncheckAll, completed)
+    }
+
+    /// Remove every checked task, with everything nested in it, in one
+    /// undoable step
+    ///
+    /// Lists left without items are removed. Returns the number of checked
+    /// tasks found.
+    pub fn remove_completed_tasks(&mut self) -> Result<usize, EditError> {
+        let completed = self.document.borrow().task_summary().completed;
+        self.bulk_task_action(BulkTaskAction::RemoveCompleted, completed)
+    }
+
+    fn bulk_task_action(
+        &mut self,
+        action: BulkTaskAction,
+        count: usize,
+    ) -> Result<usize, EditError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let command = Box::new(BulkTaskCommand::new(self.document.clone(), action));
+        self.execute_command(command)?;
+        Ok(count)
+    }
+
+    /// Replace every variant of the preferred terms of `terminology`
+    /// throughout the document in one undoable step
+    ///
+    /// Code blocks, code spans, links and URLs are left alone. Returns the
+    /// number of replacements.
+    pub fn fix_terminology(&mut self, terminology: &Terminology) -> Result<usize, EditError> {
+        let count = self.document.borrow().check_terminology(terminology).len();
+        if count == 0 {
+            return Ok(0);
+        }
+        let command = FixTerminologyCommand::new(self.document.clone(), terminology.clone());
+        self.execute_command(Box::new(command))?;
+        Ok(count)
+    }
+
+    /// Sort the items in a task list according to specified criteria
+    ///
+    /// This method allows sorting task items within a task list by different criteria
+    /// such as alphabetically or by completion status.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_index` - The index of the task list node in the document
+    /// * `criteria` - The criteria to use for sorting (alphabetical, unchecked first, or checked first)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the operation was successful
+    /// * `Err` with appropriate error if the operation failed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use md_core::{Document, Editor, ListItem, ListType, Node, SortCriteria};
+    ///
+    /// // Create a document with a task list
+    /// let mut document = Document::new();
+    /// let items = vec![
+    ///     ListItem::task("Buy groceries", true),
+    ///     ListItem::task("Call plumber", false),
+    ///     ListItem::task("Attend meeting", false),
+    /// ];
+    /// document.nodes.push(Node::List {
+    ///     list_type: ListType::Task,
+    ///     items,
+    ///     start: None,
+    ///     loose: false,
+    /// });
+    ///
+    /// let mut editor = Editor::new(document);
+    ///
+    /// // Sort the task list alphabetically
+    /// editor.sort_task_list(0, SortCriteria::Alphabetical).unwrap();
+    /// ```
+    pub fn sort_task_list(
+        &mut self,
+        node_index: usize,
+        criteria: SortCriteria,
+    ) -> Result<(), EditError> {
+        // Create a document clone to avoid borrow issues
+        let doc = self.document.borrow().clone();
+
+        let command = Box::new(SortTaskListCommand::new(doc, node_index, criteria));
+        self.execute_command(command)
+    }
+}
+
+/// What a command reported about the top-level nodes it changed
+fn node_changes(command: &dyn EditorCommand) -> NodeChanges {
+    NodeChanges {
+        affected: command.affected_range(),
+        order: command.node_order(),
+    }
+}
+
+/// A command that groups multiple commands together as a single undo/redo unit
+struct CompositeCommand {
+    commands: Vec<Box<dyn EditorCommand>>,
+}
+
+impl CompositeCommand {
+    fn new(commands: Vec<Box<dyn EditorCommand>>) -> Self {
+        Self { commands }
+    }
+}
+
+impl EditorCommand for CompositeCommand {
+    fn execute(&mut self) -> Result<(), EditError> {
+        // The transaction executes the commands the first time, so this only
+        // runs when the composite is redone
+        for cmd in self.commands.iter_mut() {
+            cmd.execute().map_err(|err| err.in_command(cmd.name()))?;
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), EditError> {
+        // Undo in reverse order
+        for cmd in self.commands.iter_mut().rev() {
+            cmd.undo()?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn retained_bytes(&self) -> usize {
+        size_of_val(self)
+            + self
+                .commands
+                .iter()
+                .map(|cmd| cmd.retained_bytes())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use super::AutosavePolicy;
+    use crate::{
+        AltTextFix, Annotation, CalloutKind, CellValue, ChangeKind, CleanupOptions,
+        CodeBlockProperties, CursorMotion, Document, DocumentFragment, EditErrorKind, EditHistory,
+        Editor, EditorSession, HtmlOptions, InlineNode, ListType, MergeOptions, Node,
+        NodeConversionType, OffsetKind, OrphanKind, Position, Selection, TableCursor,
+        TextFormatting, UrlPolicy, Visibility,
+    };
+
+    #[test]
+    fn test_delete_text() {
+        let mut doc = Document::new();
+        let index = doc.add_paragraph_with_text("Hello, world!");
+
+        let mut editor = Editor::new(doc);
+
+        // Delete "world"
+        let result = editor.delete_text(index, 7, 12);
+        assert!(result.is_ok());
+
+        // Verify changes after deletion
+        {
+            let doc = editor.document().borrow();
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "Hello, !");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify content after undo
+        {
+            let doc = editor.document().borrow();
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "Hello, world!");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_nodes() {
+        let mut doc = Document::new();
+        let p1 = doc.add_paragraph_with_text("First paragraph.");
+        let p2 = doc.add_paragraph_with_text("Second paragraph.");
+
+        let mut editor = Editor::new(doc);
+
+        // Merge paragraphs
+        let result = editor.merge_nodes(p1, p2);
+        assert!(result.is_ok());
+
+        // Verify after merge
+        {
+            let doc = editor.document().borrow();
+            // Should now have only one paragraph
+            assert_eq!(doc.nodes.len(), 1);
+
+            match &doc.nodes[0] {
+                Node::Paragraph { children } => {
+                    // Should have two text nodes now
+                    assert_eq!(children.len(), 2);
+                }
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to two paragraphs
+            assert_eq!(doc.nodes.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_format_text() {
+        let mut doc = Document::new();
+        let index = doc.add_paragraph_with_text("Hello, world!");
+
+        let mut editor = Editor::new(doc);
+
+        // Make "world" bold
+        let formatting = TextFormatting {
+            bold: true,
+            ..Default::default()
+        };
+
+        let result = editor.format_text(index, 7, 12, formatting);
+        assert!(result.is_ok());
+
+        // Verify changes after formatting
+        {
+            let doc = editor.document().borrow();
+            // Should now have three text nodes: before, formatted, after
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => {
+                    assert_eq!(children.len(), 3);
+
+                    // Check the middle node is bold
+                    match &children[1] {
+                        InlineNode::Text(text_node) => {
+                            assert_eq!(text_node.text, "world");
+                            assert!(text_node.formatting.bold);
+                        }
+                        _ => panic!("Expected Text node"),
+                    }
+                }
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to one text node
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => {
+                    assert_eq!(children.len(), 1);
+
+                    match &children[0] {
+                        InlineNode::Text(text_node) => {
+                            assert_eq!(text_node.text, "Hello, world!");
+                            assert!(!text_node.formatting.bold);
+                        }
+                        _ => panic!("Expected Text node"),
+                    }
+                }
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_delete_node() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("First paragraph.");
+        doc.add_paragraph_with_text("Second paragraph.");
+
+        let mut editor = Editor::new(doc);
+
+        // Delete first paragraph
+        let result = editor.delete_node(0);
+        assert!(result.is_ok());
+
+        // Verify changes after deletion
+        {
+            let doc = editor.document().borrow();
+            // Should now have only one paragraph
+            assert_eq!(doc.nodes.len(), 1);
+
+            match &doc.nodes[0] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "Second paragraph.");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to two paragraphs
+            assert_eq!(doc.nodes.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_move_node() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("First paragraph.");
+        doc.add_paragraph_with_text("Second paragraph.");
+        doc.add_paragraph_with_text("Third paragraph.");
+
+        let mut editor = Editor::new(doc);
+
+        // Move first paragraph to the end
+        let result = editor.move_node(0, 3);
+        assert!(result.is_ok());
+
+        // Verify changes after move
+        {
+            let doc = editor.document().borrow();
+            // Order should now be: Second, Third, First
+            match &doc.nodes[2] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "First paragraph.");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to original order
+            match &doc.nodes[0] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "First paragraph.");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert_node_type() {
+        let mut doc = Document::new();
+        let index = doc.add_paragraph_with_text("This is a paragraph");
+
+        let mut editor = Editor::new(doc);
+
+        // Convert paragraph to heading level 2
+        let result = editor.convert_node_type(index, NodeConversionType::Heading(2));
+        assert!(result.is_ok());
+
+        // Verify changes after conversion
+        {
+            let doc = editor.document().borrow();
+            // Should now be a heading
+            match &doc.nodes[index] {
+                Node::Heading {
+                    level, children, ..
+                } => {
+                    assert_eq!(*level, 2);
+                    match &children[0] {
+                        InlineNode::Text(text_node) => {
+                            assert_eq!(text_node.text, "This is a paragraph");
+                        }
+                        _ => panic!("Expected Text node"),
+                    }
+                }
+                _ => panic!("Expected Heading node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to a paragraph
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "This is a paragraph");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_text() {
+        let mut doc = Document::new();
+        let index = doc.add_paragraph_with_text("Hello world!");
+
+        let mut editor = Editor::new(doc);
+
+        // Insert text in the middle
+        let result = editor.insert_text(index, 5, ", beautiful");
+        assert!(result.is_ok());
+
+        // Verify changes after insertion
+        {
+            let doc = editor.document().borrow();
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "Hello, beautiful world!");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            match &doc.nodes[index] {
+                Node::Paragraph { children } => match &children[0] {
+                    InlineNode::Text(text_node) => {
+                        assert_eq!(text_node.text, "Hello world!");
+                    }
+                    _ => panic!("Expected Text node"),
+                },
+                _ => panic!("Expected Paragraph node"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_node() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("First paragraph.");
+
+        let mut editor = Editor::new(doc);
+
+        // Insert a new heading after the paragraph
+        let result = editor.insert_heading(1, 2, "New Heading");
+        assert!(result.is_ok());
+
+        // Verify changes after insertion
+        {
+            let doc = editor.document().borrow();
+            // Should now have two nodes
+            assert_eq!(doc.nodes.len(), 2);
+
+            // Check if the new node is a heading with the right content
+            match &doc.nodes[1] {
+                Node::Heading {
+                    level, children, ..
+                } => {
+                    assert_eq!(*level, 2);
+                    match &children[0] {
+                        InlineNode::Text(text_node) => {
+                            assert_eq!(text_node.text, "New Heading");
+                        }
+                        _ => panic!("Expected Text node"),
+                    }
+                }
+                _ => panic!("Expected Heading node"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to one paragraph
+            assert_eq!(doc.nodes.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_node() {
+        let mut doc = Document::new();
+        let p1 = doc.add_paragraph_with_text("This is a paragraph to duplicate.");
+
+        let mut editor = Editor::new(doc);
+
+        // Duplicate the paragraph
+        let result = editor.duplicate_node(p1);
+        assert!(result.is_ok());
+
+        // Verify changes after duplication
+        {
+            let doc = editor.document().borrow();
+            // Should now have two identical paragraphs
+            assert_eq!(doc.nodes.len(), 2);
+
+            // Check that both paragraphs have the same text
+            match (&doc.nodes[0], &doc.nodes[1]) {
+                (
+                    Node::Paragraph {
+                        children: children1,
+                    },
+                    Node::Paragraph {
+                        children: children2,
+                    },
+                ) => match (&children1[0], &children2[0]) {
+                    (InlineNode::Text(text_node1), InlineNode::Text(text_node2)) => {
+                        assert_eq!(text_node1.text, "This is a paragraph to duplicate.");
+                        assert_eq!(text_node2.text, "This is a paragraph to duplicate.");
+                    }
+                    _ => panic!("Expected Text nodes"),
+                },
+                _ => panic!("Expected two Paragraph nodes"),
+            }
+        }
+
+        // Test undo
+        let result = editor.undo();
+        assert!(result.is_ok());
+
+        // Verify after undo
+        {
+            let doc = editor.document().borrow();
+            // Should be back to one paragraph
+            assert_eq!(doc.nodes.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_indent_dedent_task_item() {
+        // Create a document with a task list
+        let mut doc = Document::new();
+        doc.add_task_list(vec![("Task 1", false), ("Task 2", true), ("Task 3", false)]);
+
+        let mut editor = Editor::new(doc);
+
+        // Check initial state
+        let node_id = 0;
+        println!("INITIAL STATE:");
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[node_id] {
+                println!("  Items length: {}", items.len());
+                for (i, item) in items.iter().enumerate() {
+                    println!(
+                        "  Item {}: text = {:?}, checked = {:?}",
+                        i,
+                        item.as_text(),
+                        item.checked
+                    );
+                }
+                assert_eq!(items[0].checked, Some(false));
+                assert_eq!(items[1].checked, Some(true));
+                assert_eq!(items[2].checked, Some(false));
+                assert_eq!(items.len(), 3); // Initial count at top level
+            }
+        }
+
+        // Indent task item 1 (second item)
+        println!("\nINDENTING TASK 1:");
+        editor.indent_task_item(node_id, 1).unwrap();
+
+        // Check that item was indented (became a child of item 0)
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[node_id] {
+                println!("  After indent, top items length: {}", items.len());
+                for (i, item) in items.iter().enumerate() {
+                    println!(
+                        "  Item {}: text = {:?}, checked = {:?}",
+                        i,
+                        item.as_text(),
+                        item.checked
+                    );
+
+                    // Check for nested lists
+                    for (j, child) in item.children.iter().enumerate() {
+                        if let Node::List {
+                            list_type,
+                            items: nested_items,
+                            ..
+                        } = child
+                        {
+                            println!(
+                                "    Child {}: list of type {:?} with {} items",
+                                j,
+                                list_type,
+                                nested_items.len()
+                            );
+                            for (k, nested_item) in nested_items.iter().enumerate() {
+                                println!(
+                                    "      Nested item {}: text = {:?}, checked = {:?}",
+                                    k,
+                                    nested_item.as_text(),
+                                    nested_item.checked
+                                );
+                            }
+                        }
+                    }
+                }
+
+                // Now we should have fewer items at top level
+                assert_eq!(items.len(), 2);
+
+                // First item should now have a nested task list
+                let has_nested_list = items[0].children.iter().any(|node| {
+                    matches!(node, Node::List { list_type, .. } if *list_type == ListType::Task)
+                });
+                assert!(has_nested_list, "Task 1 should have a nested task list");
+
+                // Find the nested list and check its items
+                if let Some(Node::List { items: nested_items, .. }) = items[0].children.iter().find(|node| {
+                    matches!(node, Node::List { list_type, .. } if *list_type == ListType::Task)
+                }) {
+                    assert_eq!(nested_items.len(), 1);
+                    assert_eq!(nested_items[0].checked, Some(true));
+                    assert_eq!(nested_items[0].as_text().unwrap(), "Task 2");
+                } else {
+                    panic!("Expected nested task list under Task 1");
+                }
+            }
+        }
+
+        // According to our implementation, dedent_task_item needs:
+        // - The node index of the task list
+        // - The index of the item within the nested list to dedent
+        // Since we're trying to dedent "Task 2" which is index 0 in the nested list under "Task 1"
+        println!("\nDEDENTING TASK:");
+        editor.dedent_task_item(node_id, 0).unwrap();
+
+        // Check indentation restored
+        println!("\nAFTER DEDENT:");
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[node_id] {
+                println!("  After dedent, top items length: {}", items.len());
+                for (i, item) in items.iter().enumerate() {
+                    println!(
+                        "  Item {}: text = {:?}, checked = {:?}",
+                        i,
+                        item.as_text(),
+                        item.checked
+                    );
+                }
+
+                // Should be back to having 3 top-level items
+                assert_eq!(items.len(), 3);
+                assert_eq!(items[0].as_text().unwrap(), "Task 1");
+                assert_eq!(items[1].as_text().unwrap(), "Task 2"); // This is failing
+                assert_eq!(items[2].as_text().unwrap(), "Task 3");
+            }
+        }
+
+        // Simplify the test for now - just verify we can restore to original state with undo
+        println!("\nUNDOING BOTH OPERATIONS:");
+        editor.undo().unwrap(); // Undo dedent
+        editor.undo().unwrap(); // Undo indent
+
+        // Check we're back to the original 3 items at top level
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[node_id] {
+                println!("  After undo, top items length: {}", items.len());
+                for (i, item) in items.iter().enumerate() {
+                    println!(
+                        "  Item {}: text = {:?}, checked = {:?}",
+                        i,
+                        item.as_text(),
+                        item.checked
+                    );
+                }
+
+                assert_eq!(items.len(), 3);
+                assert_eq!(items[0].as_text().unwrap(), "Task 1");
+                assert_eq!(items[1].as_text().unwrap(), "Task 2");
+                assert_eq!(items[2].as_text().unwrap(), "Task 3");
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_task_item_up_down() {
+        // Create a document with a task list
+        let mut doc = Document::new();
+        doc.add_task_list(vec![
+            ("Task 1", false),
+            ("Task 2", true),
+            ("Task 3", false),
+            ("Task 4", true),
+        ]);
+
+        let mut editor = Editor::new(doc);
+
+        // Check initial state
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[0] {
+                assert_eq!(items.len(), 4);
+                assert_eq!(items[0].as_text(), Some("Task 1"));
+                assert_eq!(items[1].as_text(), Some("Task 2"));
+                assert_eq!(items[2].as_text(), Some("Task 3"));
+                assert_eq!(items[3].as_text(), Some("Task 4"));
+            }
+        }
+
+        // Move Task 3 up (swap with Task 2)
+        editor.move_task_item_up(0, 2).unwrap();
+
+        // Verify the order after moving up
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[0] {
+                assert_eq!(items.len(), 4);
+                assert_eq!(items[0].as_text(), Some("Task 1"));
+                assert_eq!(items[1].as_text(), Some("Task 3")); // Now in position 1
+                assert_eq!(items[2].as_text(), Some("Task 2")); // Now in position 2
+                assert_eq!(items[3].as_text(), Some("Task 4"));
+            }
+        }
+
+        // Move Task 1 down (swap with Task 3)
+        editor.move_task_item_down(0, 0).unwrap();
+
+        // Verify the order after moving down
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[0] {
+                assert_eq!(items.len(), 4);
+                assert_eq!(items[0].as_text(), Some("Task 3")); // Now in position 0
+                assert_eq!(items[1].as_text(), Some("Task 1")); // Now in position 1
+                assert_eq!(items[2].as_text(), Some("Task 2"));
+                assert_eq!(items[3].as_text(), Some("Task 4"));
+            }
+        }
+
+        // Test error cases: moving first item up
+        let result = editor.move_task_item_up(0, 0);
+        assert!(result.is_err());
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Already at the top"));
+        }
+
+        // Test error cases: moving last item down
+        let result = editor.move_task_item_down(0, 3);
+        assert!(result.is_err());
+        if let Err(err) = result {
+            assert_eq!(err.message(), Some("Already at the bottom"));
+        }
+
+        // Test undo
+        editor.undo().unwrap(); // Undo "move Task 1 down"
+
+        // Verify the order after first undo
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[0] {
+                assert_eq!(items.len(), 4);
+                assert_eq!(items[0].as_text(), Some("Task 1"));
+                assert_eq!(items[1].as_text(), Some("Task 3"));
+                assert_eq!(items[2].as_text(), Some("Task 2"));
+                assert_eq!(items[3].as_text(), Some("Task 4"));
+            }
+        }
+
+        editor.undo().unwrap(); // Undo "move Task 3 up"
+
+        // Verify the order after second undo (should be back to original)
+        {
+            let doc = editor.document().borrow();
+            if let Node::List { items, .. } = &doc.nodes[0] {
+                assert_eq!(items.len(), 4);
+                assert_eq!(items[0].as_text(), Some("Task 1"));
+                assert_eq!(items[1].as_text(), Some("Task 2"));
+                assert_eq!(items[2].as_text(), Some("Task 3"));
+                assert_eq!(items[3].as_text(), Some("Task 4"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_stats_follow_edits() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Chapter One");
+        doc.add_paragraph_with_text("Once upon a time");
+        let mut editor = Editor::new(doc);
+
+        editor.set_word_target(0, Some(8)).unwrap();
+        assert_eq!(editor.stats().total_progress(), Some(0.5));
+
+        editor.insert_paragraph(2, "there lived a dragon").unwrap();
+        assert_eq!(editor.stats().sections[0].words, 8);
+        assert_eq!(editor.stats().total_progress(), Some(1.0));
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.stats().total_target(), None);
+        assert_eq!(editor.stats().words, 6);
+    }
+
+    #[test]
+    fn test_selection_relative_inserts() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("First");
+        doc.add_paragraph_with_text("Second");
+        doc.add_paragraph_with_text("Third");
+        let mut editor = Editor::new(doc);
+
+        // Without a selection, paragraphs are appended and blocks need a cursor
+        assert_eq!(editor.insert_paragraph_at_cursor("Fourth").unwrap(), 3);
+        editor.clear_selection();
+        assert!(
+            editor
+                .insert_block_above_current(Node::paragraph("x"))
+                .is_err()
+        );
+
+        editor.select_text_range(1, 0, 3).unwrap();
+        assert_eq!(
+            editor.insert_paragraph_at_cursor("After second").unwrap(),
+            2
+        );
+        assert_eq!(editor.document().borrow().current_node_index(), Some(2));
+
+        editor.select_node_range(0, 1).unwrap();
+        assert_eq!(
+            editor
+                .insert_node_after_selection(Node::heading(2, "Mid"))
+                .unwrap(),
+            2
+        );
+
+        editor.select_node(1).unwrap();
+        assert_eq!(
+            editor
+                .insert_block_above_current(Node::paragraph("Above"))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            editor
+                .insert_block_below_current(Node::paragraph("Below"))
+                .unwrap(),
+            2
+        );
+
+        let texts: Vec<String> = editor
+            .document()
+            .borrow()
+            .nodes
+            .iter()
+            .map(|node| node.plain_text())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "First",
+                "Above",
+                "Below",
+                "Second",
+                "Mid",
+                "After second",
+                "Third",
+                "Fourth"
+            ]
+        );
+
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes[2].plain_text(), "Second");
     }
-}
-
-#[cfg(test)]
-mod command_tests {
-    use crate::error::EditError;
-    use crate::{Document, Editor, InlineNode, ListType, Node, NodeConversionType, TextFormatting};
 
     #[test]
-    fn test_delete_text() {
+    fn test_table_keyboard_navigation() {
         let mut doc = Document::new();
-        let index = doc.add_paragraph_with_text("Hello, world!");
-
+        doc.nodes.push(Node::simple_table(
+            vec!["Name", "Age"],
+            vec![vec!["Alice", "30"]],
+        ));
         let mut editor = Editor::new(doc);
 
-        // Delete "world"
-        let result = editor.delete_text(index, 7, 12);
-        assert!(result.is_ok());
-
-        // Verify changes after deletion
+        assert!(editor.next_table_cell().is_err());
+        editor.focus_table_cell(0, 0, 0).unwrap();
+
+        assert_eq!(editor.next_table_cell().unwrap(), TableCursor::new(0, 0, 1));
+        assert_eq!(editor.table_cell_down().unwrap(), TableCursor::new(0, 1, 1));
+        assert_eq!(editor.table_cell_down().unwrap(), TableCursor::new(0, 1, 1));
+        assert_eq!(
+            editor.previous_table_cell().unwrap(),
+            TableCursor::new(0, 1, 0)
+        );
+        assert_eq!(editor.table_cell_up().unwrap(), TableCursor::new(0, 0, 0));
+        assert_eq!(
+            editor.previous_table_cell().unwrap(),
+            TableCursor::new(0, 0, 0)
+        );
+
+        // Tabbing past the last cell appends an empty row
+        editor.focus_table_cell(0, 1, 1).unwrap();
+        assert_eq!(editor.next_table_cell().unwrap(), TableCursor::new(0, 2, 0));
         {
             let doc = editor.document().borrow();
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "Hello, !");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
+            let Node::Table { rows, .. } = &doc.nodes[0] else {
+                panic!("Expected table");
+            };
+            assert_eq!(rows.len(), 2);
+            assert!(rows[1].iter().all(|cell| cell.content.is_empty()));
+            assert_eq!(doc.selection.as_ref().unwrap().start.path, vec![0, 2, 0]);
         }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+        // Undo puts the cursor back in the cell it was tabbed from
+        editor.undo().unwrap();
+        assert_eq!(editor.table_cursor(), Some(TableCursor::new(0, 1, 1)));
+    }
 
-        // Verify content after undo
-        {
-            let doc = editor.document().borrow();
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "Hello, world!");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+    #[test]
+    fn test_autocorrect_is_undone_separately() {
+        use crate::{Autocorrect, AutocorrectRule};
+
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("I saw teh");
+        let mut editor = Editor::new(doc);
+        editor.set_autocorrect(
+            Autocorrect::with_defaults().with_rule(AutocorrectRule::literal("teh", "the")),
+        );
+
+        let correction = editor
+            .insert_text_with_autocorrect(0, 9, " ")
+            .unwrap()
+            .unwrap();
+        assert_eq!((correction.start, correction.original.as_str()), (6, "teh"));
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "I saw the "
+        );
+
+        // The first undo only reverts the correction
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "I saw teh "
+        );
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "I saw teh"
+        );
+        editor.redo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "I saw the "
+        );
+
+        // Disabled documents keep the typed text as-is
+        editor
+            .document()
+            .borrow_mut()
+            .set_autocorrect_enabled(false);
+        assert_eq!(
+            editor.insert_text_with_autocorrect(0, 10, "(c)").unwrap(),
+            None
+        );
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "I saw the (c)"
+        );
+
+        editor.document().borrow_mut().set_autocorrect_enabled(true);
+        assert!(
+            editor
+                .insert_text_with_autocorrect(0, 13, " (c)")
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "I saw the (c) ©"
+        );
     }
 
     #[test]
-    fn test_merge_nodes() {
+    fn test_merged_cells_keep_column_alignment() {
+        use crate::{Html, Markdown, TableAlignment, Text};
+
         let mut doc = Document::new();
-        let p1 = doc.add_paragraph_with_text("First paragraph.");
-        let p2 = doc.add_paragraph_with_text("Second paragraph.");
+        doc.nodes.push(Node::simple_table(
+            vec!["A", "B", "C"],
+            vec![vec!["a1", "b1", "c1"], vec!["a2", "b2", "c2"]],
+        ));
+        let mut editor = Editor::new(doc);
+        editor
+            .set_table_column_alignment(0, 2, TableAlignment::Right)
+            .unwrap();
+        editor.merge_table_cells(0, 0, 0, 0, 1).unwrap();
+
+        let doc = editor.document().borrow().clone();
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(
+            html.contains("<tr><td colspan=\"2\">a1 b1</td><td class=\"align-right\">c1</td></tr>")
+        );
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert!(markdown.contains("| a1 b1 |  | c1 |"));
+
+        editor.split_table_cell(0, 0, 1).unwrap();
+        let doc = editor.document().borrow().clone();
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert!(markdown.contains("| a1 b1 |  | c1 |"));
+        assert!(editor.split_table_cell(0, 5, 0).is_err());
+    }
 
+    #[test]
+    fn test_move_section() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Intro");
+        doc.add_paragraph_with_text("intro text");
+        doc.add_heading(1, "Usage");
+        doc.add_heading(2, "Install");
+        doc.add_paragraph_with_text("cargo add");
         let mut editor = Editor::new(doc);
 
-        // Merge paragraphs
-        let result = editor.merge_nodes(p1, p2);
-        assert!(result.is_ok());
+        editor.move_section(2, 0).unwrap();
+        let outline = editor.document().borrow().outline();
+        assert_eq!(outline.entries[0].text, "Usage");
+        assert_eq!(outline.entries[0].children[0].node_index, 1);
+        assert_eq!(outline.entries[1].node_index, 3);
+
+        // The whole move is one history entry
+        editor.undo().unwrap();
+        let doc = editor.document().borrow().clone();
+        assert_eq!(doc.nodes[0].plain_text(), "Intro");
+        assert_eq!(doc.nodes[4].plain_text(), "cargo add");
+        assert!(editor.move_section(1, 0).is_err());
+    }
 
-        // Verify after merge
-        {
-            let doc = editor.document().borrow();
-            // Should now have only one paragraph
-            assert_eq!(doc.nodes.len(), 1);
+    #[test]
+    fn test_fix_missing_alt_text() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::Paragraph {
+            children: vec![
+                InlineNode::image("logo.png", "Logo"),
+                InlineNode::image("icon.png", ""),
+            ],
+        });
+        doc.nodes.push(Node::figure("chart.png", "", ""));
+        let mut editor = Editor::new(doc);
 
-            match &doc.nodes[0] {
-                Node::Paragraph { children } => {
-                    // Should have two text nodes now
-                    assert_eq!(children.len(), 2);
-                }
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+        let missing = editor.document().borrow().images_missing_alt();
+        assert_eq!(missing.len(), 2);
+        // Already described images are not part of the audit
+        assert!(
+            editor
+                .fix_missing_alt_text(&[AltTextFix::new(0, "Logo")])
+                .is_err()
+        );
+
+        let fixes = [
+            AltTextFix::new(missing[0].index, "Settings icon"),
+            AltTextFix::new(missing[1].index, "Revenue by month").with_caption("Revenue"),
+        ];
+        assert_eq!(editor.fix_missing_alt_text(&fixes).unwrap(), 2);
+        assert!(editor.document().borrow().images_missing_alt().is_empty());
+        assert_eq!(
+            editor.document().borrow().images()[2].caption.as_deref(),
+            Some("Revenue")
+        );
+
+        // One undo reverts the whole batch
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().images_missing_alt().len(), 2);
+
+        let changed = editor
+            .update_images(|image| image.title = Some(image.url.clone()))
+            .unwrap();
+        assert_eq!(changed, 3);
+        assert_eq!(editor.update_images(|_| {}).unwrap(), 0);
+    }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+    #[test]
+    fn test_cleanup() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Body");
+        doc.add_footnote_definition("stale", "Unused note");
+        doc.nodes.push(Node::unordered_list(vec![" "]));
+        doc.add_paragraph_with_text("End");
+        let mut editor = Editor::new(doc);
 
-        // Verify after undo
-        {
-            let doc = editor.document().borrow();
-            // Should be back to two paragraphs
-            assert_eq!(doc.nodes.len(), 2);
-        }
+        let report = editor
+            .cleanup(&CleanupOptions::new().report_only())
+            .unwrap();
+        assert_eq!(report.len(), 2);
+        assert_eq!(editor.document().borrow().nodes.len(), 4);
+
+        let removed = editor
+            .cleanup(&CleanupOptions::new().without(OrphanKind::UnreferencedFootnote))
+            .unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].node_index, 2);
+
+        let removed = editor.cleanup(&CleanupOptions::new()).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(editor.document().borrow().nodes.len(), 2);
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 4);
     }
 
     #[test]
-    fn test_format_text() {
+    fn test_stats_delta_per_command() {
         let mut doc = Document::new();
-        let index = doc.add_paragraph_with_text("Hello, world!");
-
+        doc.add_paragraph_with_text("Hello world");
         let mut editor = Editor::new(doc);
 
-        // Make "world" bold
-        let formatting = TextFormatting {
-            bold: true,
-            ..Default::default()
-        };
+        editor.insert_text(0, 11, " again").unwrap();
+        assert_eq!(editor.last_delta().words_added, 1);
+        assert_eq!(editor.last_delta().characters_added, 5);
+        assert_eq!(editor.last_delta().words_removed, 0);
+        assert!(editor.last_delta().nodes_added.is_empty());
 
-        let result = editor.format_text(index, 7, 12, formatting);
-        assert!(result.is_ok());
+        editor.insert_heading(0, 1, "Title").unwrap();
+        assert_eq!(editor.last_delta().nodes_added.get("heading"), Some(&1));
 
-        // Verify changes after formatting
-        {
-            let doc = editor.document().borrow();
-            // Should now have three text nodes: before, formatted, after
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => {
-                    assert_eq!(children.len(), 3);
+        editor.delete_node(1).unwrap();
+        assert_eq!(editor.last_delta().words_removed, 3);
+        assert_eq!(editor.last_delta().nodes_removed.get("paragraph"), Some(&1));
 
-                    // Check the middle node is bold
-                    match &children[1] {
-                        InlineNode::Text(text_node) => {
-                            assert_eq!(text_node.text, "world");
-                            assert!(text_node.formatting.bold);
-                        }
-                        _ => panic!("Expected Text node"),
-                    }
-                }
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+        editor.undo().unwrap();
+        assert_eq!(editor.last_delta().net_words(), 3);
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+        let session = editor.session_delta();
+        assert_eq!(session.words_added, 5);
+        assert_eq!(session.words_removed, 3);
+        assert_eq!(session.nodes_added.get("paragraph"), Some(&1));
 
-        // Verify after undo
-        {
-            let doc = editor.document().borrow();
-            // Should be back to one text node
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => {
-                    assert_eq!(children.len(), 1);
+        editor.reset_session_delta();
+        assert!(editor.session_delta().is_empty());
+    }
 
-                    match &children[0] {
-                        InlineNode::Text(text_node) => {
-                            assert_eq!(text_node.text, "Hello, world!");
-                            assert!(!text_node.formatting.bold);
-                        }
-                        _ => panic!("Expected Text node"),
-                    }
-                }
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+    #[test]
+    fn test_keyboard_navigation() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("One two");
+        doc.add_paragraph_with_text("Three");
+        let mut editor = Editor::new(doc);
+
+        assert_eq!(editor.move_cursor_word_right().unwrap().offset, 3);
+        assert_eq!(editor.move_cursor_line_end().unwrap().offset, 7);
+        let position = editor.move_cursor_right().unwrap();
+        assert_eq!((position.path[0], position.offset), (1, 0));
+        assert_eq!(editor.move_cursor_up().unwrap().path, vec![0]);
+
+        // Extending keeps the anchor, even when moving backwards past it
+        editor.select_text_range(0, 4, 4).unwrap();
+        editor.extend_selection(CursorMotion::WordRight).unwrap();
+        assert_eq!(editor.get_selected_text().as_deref(), Some("two"));
+        let selection = editor.extend_selection(CursorMotion::LineStart).unwrap();
+        assert_eq!((selection.start.offset, selection.end.offset), (4, 0));
+
+        // A plain move collapses the range to its edge
+        assert_eq!(editor.move_cursor_left().unwrap().offset, 0);
+        editor.select_text_range(0, 0, 3).unwrap();
+        assert_eq!(editor.move_cursor_right().unwrap().offset, 3);
+        assert!(
+            editor
+                .document()
+                .borrow()
+                .selection
+                .as_ref()
+                .unwrap()
+                .is_collapsed
+        );
+
+        assert!(Editor::new_empty().move_cursor_left().is_err());
     }
 
     #[test]
-    fn test_delete_node() {
+    fn test_rename_in_code_blocks() {
         let mut doc = Document::new();
-        doc.add_paragraph_with_text("First paragraph.");
-        doc.add_paragraph_with_text("Second paragraph.");
+        doc.add_paragraph_with_text("Call parse to read input.");
+        doc.add_code_block("let v = parse(s); // parse it\nparser(v);", "rust");
+        doc.add_code_block("v = parse(s)", "python");
+        doc.nodes.push(Node::BlockQuote {
+            children: vec![Node::code_block("parse()", "rust")],
+        });
+        let mut editor = Editor::new(doc);
+
+        let report = editor
+            .rename_in_code_blocks("parse", "decode", Some("rust"), true)
+            .unwrap();
+        assert_eq!(report.len(), 2);
+        assert_eq!((report[0].node_index, report[0].replacements), (1, 1));
+        assert_eq!(report[1].node_index, 3);
+
+        let doc = editor.document().borrow().clone();
+        assert_eq!(doc.nodes[0].plain_text(), "Call parse to read input.");
+        assert_eq!(
+            doc.nodes[1].plain_text(),
+            "let v = decode(s); // parse it\nparser(v);"
+        );
+        assert_eq!(doc.nodes[2].plain_text(), "v = parse(s)");
+        assert!(doc.nodes[3].plain_text().contains("decode()"));
+
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[1].plain_text(),
+            "let v = parse(s); // parse it\nparser(v);"
+        );
+        assert!(
+            editor
+                .rename_in_code_blocks("missing", "x", None, true)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(editor.rename_in_code_blocks("", "x", None, true).is_err());
+    }
 
+    #[test]
+    fn test_grapheme_offsets() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Hi 👋🏽 there");
         let mut editor = Editor::new(doc);
 
-        // Delete first paragraph
-        let result = editor.delete_node(0);
-        assert!(result.is_ok());
+        // Byte offsets inside the emoji are rejected instead of panicking
+        assert_eq!(
+            editor.insert_text(0, 5, "x").unwrap_err().kind(),
+            EditErrorKind::InvalidRange
+        );
+        assert!(editor.delete_text(0, 3, 6).is_err());
+        assert!(editor.select_text_range(0, 0, 4).is_err());
+
+        editor
+            .insert_text_at(0, 4, OffsetKind::Grapheme, "!")
+            .unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Hi 👋🏽! there"
+        );
+        editor
+            .delete_text_at(0, 3, 4, OffsetKind::Grapheme)
+            .unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Hi ! there"
+        );
+        editor
+            .select_text_range_at(0, 5, 10, OffsetKind::Char)
+            .unwrap();
+        let selection = editor.document().borrow().selection.clone().unwrap();
+        assert_eq!((selection.start.offset, selection.end.offset), (5, 10));
+        assert!(editor.insert_text_at(0, 11, OffsetKind::Char, "x").is_err());
+    }
 
-        // Verify changes after deletion
-        {
-            let doc = editor.document().borrow();
-            // Should now have only one paragraph
-            assert_eq!(doc.nodes.len(), 1);
+    #[test]
+    fn test_history_round_trip() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Notes");
+        doc.add_paragraph_with_text("First");
+        let mut editor = Editor::new(doc);
 
-            match &doc.nodes[0] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "Second paragraph.");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
+        editor.insert_text(1, 5, " draft").unwrap();
+        editor.format_text(1, 0, 5, TextFormatting::bold()).unwrap();
+        editor.delete_node(0).unwrap();
+        editor.undo().unwrap();
+        let expected = editor.document().borrow().clone();
+
+        let history = editor.history().unwrap();
+        assert_eq!(history.undo.len(), editor.undo_len());
+        assert_eq!(history.redo.len(), 1);
+        // Exporting leaves the live editor usable
+        assert_eq!(*editor.document().borrow(), expected);
+
+        let json = serde_json::to_string(&history).unwrap();
+        let history: EditHistory = serde_json::from_str(&json).unwrap();
+        let mut restored = Editor::with_history(expected.clone(), history).unwrap();
+
+        restored.redo().unwrap();
+        assert_eq!(restored.document().borrow().nodes.len(), 1);
+        while restored.undo_len() > 0 {
+            restored.undo().unwrap();
         }
+        assert_eq!(restored.document().borrow().nodes[1].plain_text(), "First");
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+        // A history from a different document is rejected
+        let history = editor.history().unwrap();
+        assert!(Editor::with_history(Document::new(), history).is_err());
+    }
 
-        // Verify after undo
-        {
-            let doc = editor.document().borrow();
-            // Should be back to two paragraphs
-            assert_eq!(doc.nodes.len(), 2);
+    #[test]
+    fn test_checkpoints() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Draft");
+        let mut editor = Editor::new(doc);
+
+        editor.checkpoint("before-refactor");
+        editor.insert_text(0, 5, " one").unwrap();
+        editor.insert_heading(0, 1, "Title").unwrap();
+        assert_eq!(editor.checkpoints(), ["before-refactor"]);
+
+        editor.revert_to_checkpoint("before-refactor").unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 1);
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "Draft");
+
+        // The revert is one undoable step
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[1].plain_text(),
+            "Draft one"
+        );
+        assert!(editor.revert_to_checkpoint("missing").is_err());
+        assert!(editor.remove_checkpoint("before-refactor"));
+        assert!(editor.checkpoints().is_empty());
+    }
+
+    #[test]
+    fn test_history_tree_keeps_branches() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Start");
+        let mut editor = Editor::new(doc);
+        editor.set_history_tree(true);
+
+        editor.insert_text(0, 5, " A").unwrap();
+        editor.insert_text(0, 7, " B").unwrap();
+        editor.checkpoint("ab");
+        editor.undo().unwrap();
+        // Editing after an undo drops " B" from linear redo, not from the tree
+        editor.insert_text(0, 7, " C").unwrap();
+        assert_eq!(editor.redo_len(), 0);
+
+        let tree = editor.history_tree().unwrap();
+        assert_eq!(tree.nodes().len(), 4);
+        assert_eq!(tree.nodes()[1].children, [2, 3]);
+        assert_eq!(tree.current(), 3);
+        assert_eq!(tree.leaves(), [2, 3]);
+        assert_eq!(tree.node(2).unwrap().checkpoints, ["ab"]);
+        assert_eq!(tree.path(3), [0, 1, 3]);
+
+        editor.checkout_history(2).unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Start A B"
+        );
+        assert_eq!(editor.undo_len(), 2);
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "Start");
+        assert_eq!(editor.history_tree().unwrap().current(), 0);
+
+        // Redo follows the branch that was checked out last
+        editor.redo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Start A B"
+        );
+        assert!(editor.checkout_history(9).is_err());
+    }
+
+    #[test]
+    fn test_coalesce_keystrokes() {
+        use crate::CoalescePolicy;
+        use std::time::Duration;
+
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("");
+        doc.add_paragraph_with_text("Other");
+        let mut editor = Editor::new(doc);
+        editor.set_coalescing(Some(CoalescePolicy::new(Duration::from_secs(60), 8)));
+
+        for (i, ch) in "Hello".chars().enumerate() {
+            editor.insert_text(0, i, &ch.to_string()).unwrap();
         }
+        assert_eq!(editor.undo_len(), 1);
+
+        // Backspaces form their own step
+        editor.delete_text(0, 4, 5).unwrap();
+        editor.delete_text(0, 3, 4).unwrap();
+        assert_eq!(editor.undo_len(), 2);
+
+        // A jump elsewhere, another node or the size limit start new steps
+        editor.insert_text(0, 0, ">").unwrap();
+        editor.insert_text(1, 5, "!").unwrap();
+        assert_eq!(editor.undo_len(), 4);
+        editor.insert_text(1, 6, "123456789").unwrap();
+        assert_eq!(editor.undo_len(), 5);
+
+        for _ in 0..3 {
+            editor.undo().unwrap();
+        }
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "Hel");
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "Hello");
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "");
+        editor.redo().unwrap();
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "Hello");
+
+        // A zero window never merges
+        editor.set_coalescing(Some(CoalescePolicy::new(Duration::ZERO, 8)));
+        editor.insert_text(0, 5, "a").unwrap();
+        editor.insert_text(0, 6, "b").unwrap();
+        assert_eq!(editor.undo_len(), 3);
     }
 
     #[test]
-    fn test_move_node() {
+    fn test_merge_adjacent() {
         let mut doc = Document::new();
-        doc.add_paragraph_with_text("First paragraph.");
-        doc.add_paragraph_with_text("Second paragraph.");
-        doc.add_paragraph_with_text("Third paragraph.");
+        doc.add_task_list(vec![("One", false)]);
+        doc.add_task_list(vec![("Two", true)]);
+        doc.add_paragraph_with_text("Between");
+        doc.add_code_block("a", "text");
+        doc.add_code_block("b", "text");
+        let mut editor = Editor::new(doc);
+
+        let options = MergeOptions::new().without_code_blocks();
+        assert_eq!(editor.merge_adjacent(&options).unwrap(), 1);
+        assert_eq!(editor.document().borrow().nodes.len(), 4);
+        assert_eq!(editor.merge_adjacent(&options).unwrap(), 0);
+        assert_eq!(editor.undo_len(), 1);
+
+        assert_eq!(editor.merge_adjacent(&MergeOptions::new()).unwrap(), 1);
+        assert_eq!(editor.document().borrow().nodes[2].plain_text(), "a\nb");
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 5);
+    }
 
+    #[test]
+    fn test_error_context() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Hello");
+        doc.add_task_list(vec![("Task", false)]);
         let mut editor = Editor::new(doc);
 
-        // Move first paragraph to the end
-        let result = editor.move_node(0, 3);
-        assert!(result.is_ok());
+        let err = editor.insert_text(0, 9, "!").unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::InvalidRange);
+        assert_eq!(err.node_index(), Some(0));
+        assert_eq!(err.expected(), Some(&(0..5)));
+        assert_eq!(err.actual(), Some(&(9..9)));
+        assert_eq!(err.command(), Some("InsertTextCommand"));
+        assert_eq!(
+            err.to_string(),
+            "Invalid range provided at node 0 (requested 9..9, valid 0..5) in InsertTextCommand"
+        );
+
+        let err = editor.edit_task_item(1, 3, "Other").unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::IndexOutOfBounds);
+        assert_eq!(err.path(), Some(&[1, 3][..]));
+
+        // Commands inside a transaction report the failing command itself
+        let mut transaction = editor.begin_transaction();
+        transaction.delete_node(7);
+        let err = editor.execute_transaction(transaction).unwrap_err();
+        assert_eq!(err.node_index(), Some(7));
+        assert_eq!(err.command(), Some("DeleteNodeCommand"));
+    }
 
-        // Verify changes after move
-        {
-            let doc = editor.document().borrow();
-            // Order should now be: Second, Third, First
-            match &doc.nodes[2] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "First paragraph.");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+    #[test]
+    fn test_editor_session() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Intro");
+        doc.add_paragraph_with_text("First paragraph");
+        doc.add_heading(2, "Details");
+        let mut editor = Editor::new(doc.clone());
+        editor.insert_text(1, 0, "The ").unwrap();
+        editor.document().borrow_mut().selection = Some(Selection::new(
+            Position::new(vec![1], 0),
+            Position::new(vec![1], 3),
+        ));
+        editor.fold(2).unwrap();
+        editor.fold(0).unwrap();
+        assert_eq!(
+            editor.fold(9).unwrap_err().kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
+        assert_eq!(
+            editor.fold(1).unwrap_err().kind(),
+            EditErrorKind::InvalidNode
+        );
+        assert_eq!(editor.document().borrow().folded_sections(), [0, 2]);
+        editor.set_bookmark("todo", Position::new(vec![1], 4));
+        editor.set_bookmark("todo", Position::new(vec![2], 0));
+        editor.set_scroll_anchor(Some(Position::new(vec![1], 0)));
+
+        let json = serde_json::to_string(&editor.session()).unwrap();
+        let session: EditorSession = serde_json::from_str(&json).unwrap();
+        assert_eq!(session.folded, vec![0, 2]);
+        assert_eq!(session.bookmarks.len(), 1);
+        assert_eq!(session.history_index, 1);
+
+        // A freshly loaded copy of the content takes the session, whatever
+        // was folded in it
+        let mut saved = editor.document().borrow().clone();
+        assert_eq!(saved.unfold_all(), 2);
+        let mut reopened = Editor::new(saved);
+        reopened.restore_session(&session).unwrap();
+        assert_eq!(reopened.folded(), [0, 2]);
+        assert!(reopened.is_folded(2));
+        assert!(!reopened.is_folded(1));
+        assert_eq!(reopened.bookmark("todo"), Some(&Position::new(vec![2], 0)));
+        assert_eq!(reopened.scroll_anchor(), Some(&Position::new(vec![1], 0)));
+        assert_eq!(
+            reopened.document().borrow().selection,
+            editor.document().borrow().selection
+        );
+        assert!(reopened.unfold(2));
+        assert!(reopened.remove_bookmark("todo"));
+        assert!(!reopened.remove_bookmark("todo"));
+
+        // The original content does not
+        let mut stale = Editor::new(doc);
+        let err = stale.restore_session(&session).unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::Conflict);
+        assert!(stale.folded().is_empty());
+    }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+    #[test]
+    fn test_folds_and_bookmarks_follow_edits() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Intro");
+        doc.add_heading(2, "First paragraph");
+        doc.add_heading(2, "Details");
+        let mut editor = Editor::new(doc);
+        editor.fold(1).unwrap();
+        editor.fold(2).unwrap();
+        editor.set_bookmark("first", Position::new(vec![1], 6));
+        editor.set_bookmark("details", Position::new(vec![2], 2));
+
+        editor.insert_paragraph(0, "Preface").unwrap();
+        editor.insert_text(2, 0, "The ").unwrap();
+        assert_eq!(editor.folded(), [2, 3]);
+        assert_eq!(editor.bookmark("first"), Some(&Position::new(vec![2], 10)));
+        assert_eq!(editor.bookmark("details"), Some(&Position::new(vec![3], 2)));
+
+        // Deleting a node drops its fold and bookmarks
+        editor.delete_node(2).unwrap();
+        assert_eq!(editor.folded(), [2]);
+        assert_eq!(editor.bookmark("first"), None);
+        assert_eq!(editor.bookmark("details"), Some(&Position::new(vec![2], 2)));
+
+        editor.move_node(2, 0).unwrap();
+        assert_eq!(editor.folded(), [0]);
+        assert_eq!(editor.bookmark("details"), Some(&Position::new(vec![0], 2)));
+    }
 
-        // Verify after undo
-        {
+    #[test]
+    fn test_insert_mention() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Thanks for the fix");
+        let mut editor = Editor::new(doc);
+        editor.insert_mention(0, 7, "octocat", "user").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].as_paragraph().unwrap()[..3],
+            [
+                InlineNode::text("Thanks "),
+                InlineNode::user_mention("octocat"),
+                InlineNode::text("for the fix"),
+            ]
+        );
+
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].as_paragraph().unwrap(),
+            &[InlineNode::text("Thanks for the fix")]
+        );
+        assert!(editor.insert_mention(0, 99, "42", "issue").is_err());
+    }
+
+    #[test]
+    fn test_sort_table_by_typed_values() {
+        let mut editor = Editor::new(Document::new());
+        let strings = |cells: &[&str]| cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        editor
+            .create_table_with_data(
+                0,
+                strings(&["Item", "Price"]),
+                vec![
+                    strings(&["Cake", "10"]),
+                    strings(&["Tea", "9"]),
+                    strings(&["Water", "free"]),
+                ],
+                None,
+            )
+            .unwrap();
+        editor
+            .set_table_cell_value(0, 0, 1, CellValue::number(10.0))
+            .unwrap();
+        editor
+            .set_table_cell_value(0, 1, 1, CellValue::number(9.0))
+            .unwrap();
+
+        let first_column = |editor: &Editor| -> Vec<String> {
             let doc = editor.document().borrow();
-            // Should be back to original order
-            match &doc.nodes[0] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "First paragraph.");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+            let Node::Table { rows, .. } = &doc.nodes[0] else {
+                panic!("expected a table");
+            };
+            rows.iter()
+                .map(|row| InlineNode::plain_text_of(&row[0].content))
+                .collect()
+        };
+
+        // Text comparison would put "10" before "9"
+        editor.sort_table(0, 1, false).unwrap();
+        assert_eq!(first_column(&editor), ["Tea", "Cake", "Water"]);
+        editor.sort_table(0, 1, true).unwrap();
+        assert_eq!(first_column(&editor), ["Water", "Cake", "Tea"]);
+
+        editor.undo().unwrap();
+        assert_eq!(first_column(&editor), ["Tea", "Cake", "Water"]);
+        assert!(editor.set_table_cell_value(0, 5, 1, None).is_err());
     }
 
     #[test]
-    fn test_convert_node_type() {
+    fn test_insert_callout() {
         let mut doc = Document::new();
-        let index = doc.add_paragraph_with_text("This is a paragraph");
-
+        doc.add_paragraph_with_text("Intro");
         let mut editor = Editor::new(doc);
-
-        // Convert paragraph to heading level 2
-        let result = editor.convert_node_type(index, NodeConversionType::Heading(2));
-        assert!(result.is_ok());
-
-        // Verify changes after conversion
-        {
-            let doc = editor.document().borrow();
-            // Should now be a heading
-            match &doc.nodes[index] {
-                Node::Heading { level, children } => {
-                    assert_eq!(*level, 2);
-                    match &children[0] {
-                        InlineNode::Text(text_node) => {
-                            assert_eq!(text_node.text, "This is a paragraph");
-                        }
-                        _ => panic!("Expected Text node"),
-                    }
-                }
-                _ => panic!("Expected Heading node"),
+        editor
+            .insert_callout(1, CalloutKind::Warning, Some("Careful"), "Back up first")
+            .unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[1],
+            Node::Callout {
+                kind: CalloutKind::Warning,
+                title: Some("Careful".to_string()),
+                children: vec![Node::paragraph("Back up first")],
             }
-        }
+        );
+
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 1);
+        assert!(
+            editor
+                .insert_callout(5, CalloutKind::Note, None, "Out of range")
+                .is_err()
+        );
+    }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+    #[test]
+    fn test_insert_link_checks_url_policy() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("See docs");
+        let mut editor = Editor::new(doc);
 
-        // Verify after undo
-        {
-            let doc = editor.document().borrow();
-            // Should be back to a paragraph
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "This is a paragraph");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
-        }
+        editor
+            .insert_link(0, 4, "https://example.com", "the ")
+            .unwrap();
+        let err = editor
+            .insert_link(0, 0, "javascript:alert(1)", "x")
+            .unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::InvalidUrl);
+        assert!(
+            editor
+                .insert_image(0, 0, "data:image/png;base64,AAAA", "dot")
+                .is_ok()
+        );
+        let err = editor
+            .insert_figure(1, "data:text/html,x", "x", "", Default::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::InvalidUrl);
+        assert_eq!(editor.document().borrow().nodes.len(), 1);
+
+        editor.set_url_policy(UrlPolicy::new().allow_link_scheme("javascript"));
+        assert!(editor.insert_link(0, 0, "javascript:void(0)", "x").is_ok());
     }
 
     #[test]
-    fn test_insert_text() {
+    fn test_comments_follow_edits() {
         let mut doc = Document::new();
-        let index = doc.add_paragraph_with_text("Hello world!");
-
+        doc.add_paragraph_with_text("The quick brown fox");
+        doc.add_paragraph_with_text("Jumps over the dog");
         let mut editor = Editor::new(doc);
 
-        // Insert text in the middle
-        let result = editor.insert_text(index, 5, ", beautiful");
-        assert!(result.is_ok());
-
-        // Verify changes after insertion
+        let quick = editor
+            .add_comment("ana", vec![0], 4..9, "Too fast?")
+            .unwrap();
+        let dog = editor
+            .add_comment("bo", vec![1], 15..18, "Which dog?")
+            .unwrap();
+        assert!(editor.add_comment("bo", vec![1], 15..30, "Nope").is_err());
+        assert_eq!(editor.document().borrow().comments.len(), 2);
+
+        // Typing before a range shifts it, typing inside grows it
+        editor.insert_text(0, 0, "See: ").unwrap();
+        editor.insert_text(0, 9, "very ").unwrap();
+        editor.insert_text(1, 15, "lazy ").unwrap();
         {
-            let doc = editor.document().borrow();
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "Hello, beautiful world!");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
-            }
+            let document = editor.document().borrow();
+            let quick = document.comment(quick).unwrap();
+            assert_eq!(quick.range, 9..19);
+            assert_eq!(
+                &document.nodes[0].plain_text()[quick.range.clone()],
+                "very quick"
+            );
+            assert_eq!(document.comment(dog).unwrap().range, 15..23);
         }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+        // Comments move with their node and go when it is deleted
+        editor.insert_paragraph(0, "Title").unwrap();
+        assert_eq!(
+            editor.document().borrow().comment(dog).unwrap().path,
+            vec![2]
+        );
+        editor.delete_node(2).unwrap();
+        assert!(editor.document().borrow().comment(dog).is_none());
+        editor.undo().unwrap();
+
+        editor.resolve_comment(quick, true).unwrap();
+        assert!(editor.document().borrow().comment(quick).unwrap().resolved);
+        editor.delete_comment(quick).unwrap();
+        assert!(editor.delete_comment(quick).is_err());
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        let document = editor.document().borrow();
+        let comment = document.comment(quick).unwrap();
+        assert!(!comment.resolved);
+        assert_eq!(
+            (comment.path.clone(), comment.range.clone()),
+            (vec![1], 9..19)
+        );
+    }
 
-        // Verify after undo
-        {
-            let doc = editor.document().borrow();
-            match &doc.nodes[index] {
-                Node::Paragraph { children } => match &children[0] {
-                    InlineNode::Text(text_node) => {
-                        assert_eq!(text_node.text, "Hello world!");
-                    }
-                    _ => panic!("Expected Text node"),
-                },
-                _ => panic!("Expected Paragraph node"),
+    #[test]
+    fn test_annotations_follow_edits() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Teh cat sat on teh mat");
+        doc.add_paragraph_with_text("Recieve it");
+        let mut editor = Editor::new(doc);
+
+        // A spellchecker walks the text runs and reports misspellings
+        let runs = editor.document().borrow().text_runs();
+        for run in &runs {
+            for word in ["Teh", "teh", "Recieve"] {
+                for (index, _) in run.text.match_indices(word) {
+                    let start = run.offset + index;
+                    editor
+                        .annotate(Annotation::new(
+                            run.path.clone(),
+                            start..start + word.len(),
+                            "spelling",
+                        ))
+                        .unwrap();
+                }
             }
         }
+        assert_eq!(editor.annotations().len(), 3);
+        assert!(
+            editor
+                .annotate(Annotation::new(vec![5], 0..1, "spelling"))
+                .is_err()
+        );
+
+        // Typing before a word moves it; editing a word drops it
+        editor.insert_text(0, 8, "quietly ").unwrap();
+        editor.delete_text(1, 0, 7).unwrap();
+        editor.insert_text(1, 0, "Receive").unwrap();
+        editor.insert_paragraph(0, "Title").unwrap();
+        let ranges: Vec<(Vec<usize>, std::ops::Range<usize>)> = editor
+            .annotations()
+            .iter()
+            .map(|annotation| (annotation.path.clone(), annotation.range.clone()))
+            .collect();
+        assert_eq!(ranges, vec![(vec![1], 0..3), (vec![1], 23..26)]);
+
+        let at = editor.annotations_at(&Position::new(vec![1], 24));
+        assert_eq!(at.len(), 1);
+        editor.undo().unwrap();
+        assert_eq!(editor.annotations()[1].path, vec![0]);
+        assert_eq!(editor.clear_annotations("spelling"), 2);
     }
 
     #[test]
-    fn test_insert_node() {
+    fn test_toggle_selection_visibility() {
         let mut doc = Document::new();
-        doc.add_paragraph_with_text("First paragraph.");
-
+        doc.add_paragraph_with_text("Intro");
+        doc.add_paragraph_with_text("First draft");
+        doc.add_paragraph_with_text("Second draft");
+        doc.add_paragraph_with_text("Outro");
         let mut editor = Editor::new(doc);
+        editor.document().borrow_mut().selection = Some(Selection::new(
+            Position::new(vec![1], 2),
+            Position::new(vec![2], 3),
+        ));
 
-        // Insert a new heading after the paragraph
-        let result = editor.insert_heading(1, 2, "New Heading");
-        assert!(result.is_ok());
-
-        // Verify changes after insertion
+        editor
+            .toggle_selection_visibility(Visibility::Draft)
+            .unwrap();
         {
-            let doc = editor.document().borrow();
-            // Should now have two nodes
-            assert_eq!(doc.nodes.len(), 2);
-
-            // Check if the new node is a heading with the right content
-            match &doc.nodes[1] {
-                Node::Heading { level, children } => {
-                    assert_eq!(*level, 2);
-                    match &children[0] {
-                        InlineNode::Text(text_node) => {
-                            assert_eq!(text_node.text, "New Heading");
-                        }
-                        _ => panic!("Expected Text node"),
-                    }
-                }
-                _ => panic!("Expected Heading node"),
-            }
+            let document = editor.document().borrow();
+            assert_eq!(document.nodes.len(), 3);
+            assert_eq!(document.visibility(1), Some(Visibility::Draft));
+            let selection = document.selection.as_ref().unwrap();
+            assert_eq!(selection.start, Position::new(vec![1, 0], 2));
+            assert_eq!(selection.end, Position::new(vec![1, 1], 3));
+            let html = document.to_html_with(&HtmlOptions::new()).html;
+            assert_eq!(html, "<p>Intro</p><p>Outro</p>");
         }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
-
-        // Verify after undo
+        // Hiding a draft replaces its visibility, toggling again shows it
+        editor.set_selection_visibility(Visibility::Hidden).unwrap();
+        assert_eq!(
+            editor.document().borrow().visibility(1),
+            Some(Visibility::Hidden)
+        );
+        editor
+            .toggle_selection_visibility(Visibility::Hidden)
+            .unwrap();
         {
-            let doc = editor.document().borrow();
-            // Should be back to one paragraph
-            assert_eq!(doc.nodes.len(), 1);
+            let document = editor.document().borrow();
+            assert_eq!(document.nodes.len(), 4);
+            assert_eq!(document.visibility(2), Some(Visibility::Visible));
+            assert_eq!(
+                document.selection.as_ref().unwrap().end,
+                Position::new(vec![2], 3)
+            );
         }
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().visibility(1),
+            Some(Visibility::Draft)
+        );
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 4);
+
+        editor.document().borrow_mut().selection = None;
+        assert!(
+            editor
+                .toggle_selection_visibility(Visibility::Draft)
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_duplicate_node() {
+    fn test_track_changes() {
         let mut doc = Document::new();
-        let p1 = doc.add_paragraph_with_text("This is a paragraph to duplicate.");
-
+        doc.add_paragraph_with_text("The cat sat");
         let mut editor = Editor::new(doc);
+        let text = |editor: &Editor| editor.document().borrow().nodes[0].plain_text();
+        let ranges = |editor: &Editor| {
+            let document = editor.document().borrow();
+            let changes = document.tracked_changes.iter();
+            changes
+                .map(|change| change.range.clone())
+                .collect::<Vec<_>>()
+        };
 
-        // Duplicate the paragraph
-        let result = editor.duplicate_node(p1);
-        assert!(result.is_ok());
-
-        // Verify changes after duplication
-        {
-            let doc = editor.document().borrow();
-            // Should now have two identical paragraphs
-            assert_eq!(doc.nodes.len(), 2);
+        editor.set_track_changes(Some("ana".to_string()));
+        editor.insert_text(0, 4, "big ").unwrap();
+        editor.insert_text(0, 8, "black ").unwrap();
+        editor.delete_text(0, 14, 18).unwrap();
+        assert_eq!(text(&editor), "The big black cat sat");
+        assert_eq!(ranges(&editor), vec![4..14, 14..18]);
+
+        // Deleting one's own suggestion removes it right away
+        editor.delete_text(0, 4, 8).unwrap();
+        assert_eq!(text(&editor), "The black cat sat");
+        assert_eq!(ranges(&editor), vec![4..10, 10..14]);
+        let html = editor
+            .document()
+            .borrow()
+            .to_html_with(&HtmlOptions::new())
+            .html;
+        assert_eq!(
+            html,
+            "<p>The <ins data-change-id=\"1\" data-author=\"ana\">black </ins>\
+             <del data-change-id=\"2\" data-author=\"ana\">cat </del>sat</p>"
+        );
+        editor.undo().unwrap();
+        assert_eq!(text(&editor), "The big black cat sat");
+        assert_eq!(ranges(&editor), vec![4..14, 14..18]);
+        editor.redo().unwrap();
+
+        // Untracked edits move the marks along
+        editor.set_track_changes(None);
+        editor.insert_text(0, 0, "Now ").unwrap();
+        assert_eq!(ranges(&editor), vec![8..14, 14..18]);
+
+        editor.reject_change(1).unwrap();
+        assert_eq!(text(&editor), "Now The cat sat");
+        assert_eq!(ranges(&editor), vec![8..12]);
+        editor.accept_all().unwrap();
+        assert_eq!(text(&editor), "Now The sat");
+        assert!(editor.document().borrow().tracked_changes.is_empty());
+        assert!(editor.accept_change(2).is_err());
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(text(&editor), "Now The black cat sat");
+        assert_eq!(ranges(&editor), vec![8..14, 14..18]);
+    }
 
-            // Check that both paragraphs have the same text
-            match (&doc.nodes[0], &doc.nodes[1]) {
-                (
-                    Node::Paragraph {
-                        children: children1,
-                    },
-                    Node::Paragraph {
-                        children: children2,
-                    },
-                ) => match (&children1[0], &children2[0]) {
-                    (InlineNode::Text(text_node1), InlineNode::Text(text_node2)) => {
-                        assert_eq!(text_node1.text, "This is a paragraph to duplicate.");
-                        assert_eq!(text_node2.text, "This is a paragraph to duplicate.");
-                    }
-                    _ => panic!("Expected Text nodes"),
-                },
-                _ => panic!("Expected two Paragraph nodes"),
-            }
+    #[test]
+    fn test_last_affected_range() {
+        let mut doc = Document::new();
+        for text in ["One", "Two", "Three"] {
+            doc.add_paragraph_with_text(text);
         }
+        let mut editor = Editor::new(doc);
+        assert_eq!(editor.last_affected_range(), None);
+
+        editor.insert_text(1, 3, "!").unwrap();
+        assert_eq!(editor.last_affected_range(), Some(1..2));
+        editor.insert_paragraph(3, "Four").unwrap();
+        assert_eq!(editor.last_affected_range(), Some(3..4));
+        editor.undo().unwrap();
+        assert_eq!(editor.last_affected_range(), Some(3..3));
+        editor.move_node(0, 3).unwrap();
+        assert_eq!(editor.last_affected_range(), Some(0..3));
+
+        // Commands that don't report a range fall back to comparing nodes
+        let bold = TextFormatting {
+            bold: true,
+            ..Default::default()
+        };
+        editor.format_text(1, 0, 3, bold).unwrap();
+        assert_eq!(editor.last_affected_range(), Some(1..2));
+        editor.delete_node(0).unwrap();
+        assert_eq!(editor.last_affected_range(), Some(0..0));
+        editor.undo().unwrap();
+        assert_eq!(editor.last_affected_range(), Some(0..1));
+    }
 
-        // Test undo
-        let result = editor.undo();
-        assert!(result.is_ok());
+    #[test]
+    fn test_selection_follows_edits() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Intro");
+        doc.add_paragraph_with_text("The cat sat");
+        let mut editor = Editor::new(doc);
+        let selection = |editor: &Editor| editor.document().borrow().selection.clone().unwrap();
+        let position = |index, offset| Position::new(vec![index], offset);
+
+        editor.select_text_range(1, 4, 7).unwrap();
+        editor.insert_text(1, 0, "So ").unwrap();
+        assert_eq!(
+            selection(&editor),
+            Selection::new(position(1, 7), position(1, 10))
+        );
+        editor.delete_node(0).unwrap();
+        assert_eq!(
+            selection(&editor),
+            Selection::new(position(0, 7), position(0, 10))
+        );
+        editor.undo().unwrap();
+        assert_eq!(selection(&editor).start, position(1, 7));
+
+        // A cursor stays after text typed at it
+        editor.select_text_range(1, 3, 3).unwrap();
+        editor.insert_text(1, 3, "the ").unwrap();
+        assert_eq!(selection(&editor), Selection::collapsed(position(1, 7)));
+
+        // Positions held outside the editor map the same way
+        editor.insert_paragraph(0, "Title").unwrap();
+        let mapper = editor.position_mapper();
+        assert_eq!(mapper.map_position(&position(1, 2)), Some(position(2, 2)));
+        assert_eq!(mapper.map_range(&[1], 0..3), Some((vec![2], 0..3)));
+    }
 
-        // Verify after undo
-        {
-            let doc = editor.document().borrow();
-            // Should be back to one paragraph
-            assert_eq!(doc.nodes.len(), 1);
-        }
+    #[test]
+    fn test_undo_restores_selection() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Hello world");
+        let mut editor = Editor::new(doc);
+        let selection = |editor: &Editor| editor.document().borrow().selection.clone();
+        let position = |offset| Position::new(vec![0], offset);
+
+        editor.select_text_range(0, 6, 11).unwrap();
+        editor.delete_text(0, 6, 11).unwrap();
+        editor.select_text_range(0, 6, 6).unwrap();
+        editor.insert_text(0, 6, "there").unwrap();
+        let typed = selection(&editor);
+
+        // The cursor moved away after the edits
+        editor.select_text_range(0, 0, 0).unwrap();
+        editor.undo().unwrap();
+        assert_eq!(selection(&editor), Some(Selection::collapsed(position(6))));
+        editor.undo().unwrap();
+        assert_eq!(
+            selection(&editor),
+            Some(Selection::new(position(6), position(11)))
+        );
+        editor.redo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(selection(&editor), typed);
+
+        // Compressed and exported history keeps the selections
+        editor.undo().unwrap();
+        editor.compress_history();
+        let history = editor.history().unwrap();
+        assert_eq!(
+            history.undo[0].selection_before,
+            Some(Selection::new(position(6), position(11)))
+        );
+        let mut resumed =
+            Editor::with_history(editor.document().borrow().clone(), history).unwrap();
+        resumed.undo().unwrap();
+        assert_eq!(
+            selection(&resumed),
+            Some(Selection::new(position(6), position(11)))
+        );
     }
 
     #[test]
-    fn test_indent_dedent_task_item() {
-        // Create a document with a task list
+    fn test_html_comments() {
         let mut doc = Document::new();
-        doc.add_task_list(vec![("Task 1", false), ("Task 2", true), ("Task 3", false)]);
+        doc.add_paragraph_with_text("Hello world");
+        let mut editor = Editor::new(doc);
 
+        editor.insert_html_comment(0, " TODO ").unwrap();
+        assert!(editor.insert_html_comment(0, "a --> b").is_err());
+        editor
+            .insert_inline(1, 5, InlineNode::html_comment("aside"))
+            .unwrap();
+        let comments = editor.document().borrow().html_comments();
+        assert_eq!(comments.len(), 2);
+        assert!(comments[1].inline);
+
+        assert!(editor.remove_html_comments(&[5]).is_err());
+        assert_eq!(editor.remove_html_comments(&[0, 1, 0]).unwrap(), 2);
+        assert_eq!(editor.document().borrow().nodes.len(), 1);
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Hello world"
+        );
+
+        editor.undo().unwrap();
+        let markdown = editor
+            .document()
+            .borrow()
+            .to_markdown_with(&crate::MarkdownOptions::default());
+        assert_eq!(markdown, "<!-- TODO -->\n\nHello<!--aside--> world");
+    }
+
+    #[test]
+    fn test_multiple_cursors() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("a = 1");
+        doc.add_paragraph_with_text("b = 2");
         let mut editor = Editor::new(doc);
+        let cursor = |index, offset| Selection::collapsed(Position::new(vec![index], offset));
+
+        editor.add_cursor(0, 1).unwrap();
+        editor.add_cursor(1, 1).unwrap();
+        assert!(editor.add_cursor(2, 0).is_err());
+        editor.insert_text_at_cursor("x").unwrap();
+        assert_eq!(editor.document().borrow().nodes[0].plain_text(), "ax = 1");
+        assert_eq!(editor.document().borrow().nodes[1].plain_text(), "bx = 2");
+        assert_eq!(editor.selections(), [cursor(0, 2), cursor(1, 2)]);
+
+        // Other edits move every cursor along
+        editor.insert_paragraph(0, "Title").unwrap();
+        assert_eq!(editor.selections(), [cursor(1, 2), cursor(2, 2)]);
+
+        editor.select_text_range(1, 0, 2).unwrap();
+        editor
+            .add_selection(Selection::new(
+                Position::new(vec![2], 0),
+                Position::new(vec![2], 2),
+            ))
+            .unwrap();
+        editor.clear_secondary_selections();
+        editor
+            .add_selection(Selection::new(
+                Position::new(vec![2], 0),
+                Position::new(vec![2], 2),
+            ))
+            .unwrap();
+        editor.delete_selection().unwrap();
+        assert_eq!(editor.document().borrow().nodes[1].plain_text(), " = 1");
+        assert_eq!(editor.document().borrow().nodes[2].plain_text(), " = 2");
+
+        // One undo step restores the text and every selection
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes[2].plain_text(), "bx = 2");
+        assert_eq!(editor.selections().len(), 2);
+        editor.clear_selection();
+        assert!(editor.selections().is_empty());
+    }
 
-        // Check initial state
-        let node_id = 0;
-        println!("INITIAL STATE:");
+    #[test]
+    fn test_list_item_commands() {
+        let mut doc = Document::new();
+        doc.add_unordered_list(vec!["Milk", "Eggs"]);
+        let mut editor = Editor::new(doc);
+
+        editor.add_list_item(0, 2, "Bread").unwrap();
+        editor.edit_list_item(0, 0, "Oat milk").unwrap();
+        editor.move_list_item(0, 2, 0).unwrap();
+        editor.indent_list_item(0, 2).unwrap();
+        editor.convert_list_type(0, ListType::Task).unwrap();
+        editor.toggle_task(0, 0).unwrap();
         {
             let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[node_id] {
-                println!("  Items length: {}", items.len());
-                for (i, item) in items.iter().enumerate() {
-                    println!(
-                        "  Item {}: text = {:?}, checked = {:?}",
-                        i,
-                        item.as_text(),
-                        item.checked
-                    );
-                }
-                assert_eq!(items[0].checked, Some(false));
-                assert_eq!(items[1].checked, Some(true));
-                assert_eq!(items[2].checked, Some(false));
-                assert_eq!(items.len(), 3); // Initial count at top level
-            }
+            let Node::List { items, .. } = &doc.nodes[0] else {
+                panic!("Expected list");
+            };
+            let checked: Vec<Option<bool>> = items.iter().map(|item| item.checked).collect();
+            assert_eq!(checked, [Some(true), Some(false)]);
+            assert!(matches!(
+                &items[1].children[1],
+                Node::List { list_type: ListType::Unordered, items, .. } if items[0].checked.is_none()
+            ));
+            assert_eq!(doc.nodes[0].plain_text(), "Bread\nOat milk\nEggs");
         }
 
-        // Indent task item 1 (second item)
-        println!("\nINDENTING TASK 1:");
-        editor.indent_task_item(node_id, 1).unwrap();
+        editor.unindent_list_item(0, 1, 0).unwrap();
+        editor.remove_list_item(0, 0).unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Oat milk\nEggs"
+        );
+        assert!(editor.remove_list_item(0, 5).is_err());
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert!(matches!(
+            &editor.document().borrow().nodes[0],
+            Node::List { list_type: ListType::Unordered, items, .. } if items.len() == 2
+        ));
+    }
 
-        // Check that item was indented (became a child of item 0)
-        {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[node_id] {
-                println!("  After indent, top items length: {}", items.len());
-                for (i, item) in items.iter().enumerate() {
-                    println!(
-                        "  Item {}: text = {:?}, checked = {:?}",
-                        i,
-                        item.as_text(),
-                        item.checked
-                    );
+    #[test]
+    fn test_table_caption_and_accessibility() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::simple_table(
+            vec!["Plan", "Price"],
+            vec![vec!["Pro", "$9"]],
+        ));
+        let mut editor = Editor::new(doc);
 
-                    // Check for nested lists
-                    for (j, child) in item.children.iter().enumerate() {
-                        if let Node::List {
-                            list_type,
-                            items: nested_items,
-                        } = child
-                        {
-                            println!(
-                                "    Child {}: list of type {:?} with {} items",
-                                j,
-                                list_type,
-                                nested_items.len()
-                            );
-                            for (k, nested_item) in nested_items.iter().enumerate() {
-                                println!(
-                                    "      Nested item {}: text = {:?}, checked = {:?}",
-                                    k,
-                                    nested_item.as_text(),
-                                    nested_item.checked
-                                );
-                            }
-                        }
-                    }
-                }
+        editor.set_table_caption(0, "Plans & prices", true).unwrap();
+        editor
+            .set_table_accessibility(
+                0,
+                Some("One plan per row".to_string()),
+                Some("Pricing".to_string()),
+            )
+            .unwrap();
+        let html = editor
+            .document()
+            .borrow()
+            .to_html_with(&HtmlOptions::new())
+            .html;
+        assert!(html.contains(
+            "<table class=\"bordered\" aria-label=\"Pricing\" aria-description=\"One plan per row\">"
+        ));
+        assert!(html.contains("</tbody>\n<caption>Plans &amp; prices</caption>"));
+
+        editor.remove_table_caption(0).unwrap();
+        let html = editor
+            .document()
+            .borrow()
+            .to_html_with(&HtmlOptions::new())
+            .html;
+        assert!(!html.contains("<caption>"));
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        let doc = editor.document().borrow();
+        let Node::Table { properties, .. } = &doc.nodes[0] else {
+            panic!("Expected table");
+        };
+        assert_eq!(properties.caption.as_deref(), Some("Plans & prices"));
+        assert!(properties.caption_at_bottom);
+        assert_eq!(properties.aria_label, None);
+    }
+
+    #[test]
+    fn test_apply_link_titles() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![
+            InlineNode::text("Pasted "),
+            InlineNode::autolink_url("https://example.com/post"),
+        ]);
+        let mut editor = Editor::new(doc);
 
-                // Now we should have fewer items at top level
-                assert_eq!(items.len(), 2);
+        let targets = editor.document().borrow().link_title_targets(0..1);
+        let titles = targets
+            .into_iter()
+            .map(|target| crate::LinkTitle {
+                node_index: target.node_index,
+                url: target.url,
+                title: "A Post".to_string(),
+            })
+            .collect();
+        assert_eq!(editor.apply_link_titles(titles).unwrap(), 1);
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Pasted A Post"
+        );
+        assert_eq!(editor.apply_link_titles(Vec::new()).unwrap(), 0);
+
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Pasted https://example.com/post"
+        );
+    }
 
-                // First item should now have a nested task list
-                let has_nested_list = items[0].children.iter().any(|node| {
-                    matches!(node, Node::List { list_type, .. } if *list_type == ListType::Task)
-                });
-                assert!(has_nested_list, "Task 1 should have a nested task list");
+    #[test]
+    fn test_bulk_task_commands() {
+        let doc = Document::try_from(crate::Text::<crate::Markdown>::new(
+            "- [x] Done\n- [ ] Open\n  - [x] Nested done\n\n- [x] Alone\n",
+        ))
+        .unwrap();
+        let mut editor = Editor::new(doc);
+        assert_eq!(editor.document().borrow().task_summary().completed, 3);
+
+        assert_eq!(editor.check_all_tasks().unwrap(), 1);
+        assert_eq!(editor.check_all_tasks().unwrap(), 0);
+        assert_eq!(editor.uncheck_all_tasks().unwrap(), 4);
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().task_summary().pending(), 0);
+        editor.undo().unwrap();
+
+        assert_eq!(editor.remove_completed_tasks().unwrap(), 3);
+        let tasks = editor
+            .document()
+            .borrow()
+            .find_tasks(&crate::TaskFilter::All);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].text, "Open");
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().task_summary().total, 4);
+    }
 
-                // Find the nested list and check its items
-                if let Some(Node::List { items: nested_items, .. }) = items[0].children.iter().find(|node| {
-                    matches!(node, Node::List { list_type, .. } if *list_type == ListType::Task)
-                }) {
-                    assert_eq!(nested_items.len(), 1);
-                    assert_eq!(nested_items[0].checked, Some(true));
-                    assert_eq!(nested_items[0].as_text().unwrap(), "Task 2");
-                } else {
-                    panic!("Expected nested task list under Task 1");
-                }
-            }
+    #[test]
+    fn test_pinned_nodes() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Title");
+        doc.add_paragraph_with_text("One");
+        doc.add_paragraph_with_text("Two");
+        doc.add_paragraph_with_text("Disclaimer");
+        let mut editor = Editor::new(doc);
+        editor.pin_node(0, crate::PinAnchor::Start).unwrap();
+        editor.pin_node(3, crate::PinAnchor::End).unwrap();
+        assert_eq!(
+            editor
+                .pin_node(4, crate::PinAnchor::End)
+                .unwrap_err()
+                .kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
+        let texts = |editor: &Editor| -> Vec<String> {
+            let document = editor.document().borrow();
+            document.nodes.iter().map(Node::plain_text).collect()
+        };
+
+        // Targets among pinned nodes move out of them
+        editor.insert_paragraph(4, "Three").unwrap();
+        editor.move_node(1, 0).unwrap();
+        assert_eq!(
+            texts(&editor),
+            ["Title", "One", "Two", "Three", "Disclaimer"]
+        );
+        editor.move_node(1, 5).unwrap();
+        assert_eq!(
+            texts(&editor),
+            ["Title", "Two", "Three", "One", "Disclaimer"]
+        );
+        assert_eq!(editor.pinned_nodes(), [0, 4]);
+
+        for err in [
+            editor.delete_node(4).unwrap_err(),
+            editor.move_node(0, 2).unwrap_err(),
+            editor.duplicate_node(4).unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), EditErrorKind::Pinned);
         }
+        editor.delete_node(1).unwrap();
+        editor.duplicate_node(0).unwrap();
+        assert_eq!(
+            texts(&editor),
+            ["Title", "Title", "Three", "One", "Disclaimer"]
+        );
+        assert!(editor.is_pinned(4));
+
+        assert!(editor.unpin_node(4));
+        assert!(!editor.is_pinned(4));
+        editor.delete_node(4).unwrap();
+        assert_eq!(editor.pinned_nodes(), [0]);
+    }
 
-        // According to our implementation, dedent_task_item needs:
-        // - The node index of the task list
-        // - The index of the item within the nested list to dedent
-        // Since we're trying to dedent "Task 2" which is index 0 in the nested list under "Task 1"
-        println!("\nDEDENTING TASK:");
-        editor.dedent_task_item(node_id, 0).unwrap();
+    #[test]
+    fn test_insert_and_focus_outside_pinned_nodes() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Body");
+        doc.add_paragraph_with_text("Disclaimer");
+        let mut editor = Editor::new(doc);
+        editor.pin_node(1, crate::PinAnchor::End).unwrap();
+
+        // Inserting below the disclaimer lands above it, and so does the cursor
+        editor.select_text_range(1, 0, 0).unwrap();
+        let index = editor
+            .insert_block_below_current(Node::paragraph("New"))
+            .unwrap();
+        assert_eq!(index, 1);
+        let document = editor.document().borrow();
+        assert_eq!(document.nodes[1].plain_text(), "New");
+        assert_eq!(
+            document.selection,
+            Some(Selection::collapsed(Position::new(vec![1], 0)))
+        );
+        drop(document);
+        assert_eq!(editor.insert_node(3, Node::paragraph("Last")).unwrap(), 2);
+    }
 
-        // Check indentation restored
-        println!("\nAFTER DEDENT:");
-        {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[node_id] {
-                println!("  After dedent, top items length: {}", items.len());
-                for (i, item) in items.iter().enumerate() {
-                    println!(
-                        "  Item {}: text = {:?}, checked = {:?}",
-                        i,
-                        item.as_text(),
-                        item.checked
-                    );
-                }
+    #[test]
+    fn test_navigation_history() {
+        let mut doc = Document::new();
+        for text in ["Intro", "Usage", "Details", "Index"] {
+            doc.add_paragraph_with_text(text);
+        }
+        let mut editor = Editor::new(doc);
+        let start = |editor: &Editor| {
+            let document = editor.document().borrow();
+            let start = &document.selection.as_ref().unwrap().start;
+            (start.path[0], start.offset)
+        };
+        assert_eq!(editor.navigate_back(), None);
+
+        editor.select_text_range(0, 2, 2).unwrap();
+        editor.select_text_range(0, 3, 3).unwrap();
+        assert!(!editor.can_navigate_back(), "no jump within a node");
+        editor.select_node(3).unwrap();
+        editor.move_cursor(CursorMotion::Right).unwrap();
+        editor.select_text_range(1, 1, 1).unwrap();
+
+        assert!(editor.navigate_back().is_some());
+        assert_eq!(start(&editor), (3, 5), "where the cursor was when jumping");
+        assert!(editor.navigate_back().is_some());
+        assert_eq!(start(&editor), (0, 3));
+        assert!(!editor.can_navigate_back());
+        assert!(editor.navigate_forward().is_some());
+        assert_eq!(start(&editor), (3, 5));
+        assert!(editor.can_navigate_forward());
+
+        // Edits move the locations along with their text, and undo leaves
+        // the history alone
+        editor.insert_paragraph(0, "Title").unwrap();
+        editor.undo().unwrap();
+        editor.insert_paragraph(0, "Title").unwrap();
+        assert!(editor.navigate_back().is_some());
+        assert_eq!(start(&editor), (1, 3));
+        assert!(editor.navigate_forward().is_some());
+        assert!(editor.navigate_forward().is_some());
+        assert_eq!(start(&editor), (2, 1));
+        assert_eq!(editor.navigate_forward(), None);
+    }
 
-                // Should be back to having 3 top-level items
-                assert_eq!(items.len(), 3);
-                assert_eq!(items[0].as_text().unwrap(), "Task 1");
-                assert_eq!(items[1].as_text().unwrap(), "Task 2"); // This is failing
-                assert_eq!(items[2].as_text().unwrap(), "Task 3");
+    #[test]
+    fn test_code_block_properties() {
+        let doc = Document::try_from(crate::Text::<crate::Markdown>::new(
+            "```rust\nfn a() {}\n```\n\n```python\na = 1\n```\n\n> ```Rust,ignore\n> fn b() {}\n> ```\n",
+        ))
+        .unwrap();
+        let mut editor = Editor::new(doc);
+        let properties = |editor: &Editor, path: &[usize]| {
+            let document = editor.document().borrow();
+            let mut node = &document.nodes[path[0]];
+            for &index in &path[1..] {
+                node = &node.as_blockquote().unwrap()[index];
             }
-        }
+            node.as_code_block_with_properties().unwrap().2.clone()
+        };
 
-        // Simplify the test for now - just verify we can restore to original state with undo
-        println!("\nUNDOING BOTH OPERATIONS:");
-        editor.undo().unwrap(); // Undo dedent
-        editor.undo().unwrap(); // Undo indent
+        let highlighted = CodeBlockProperties::new().with_highlight_lines(vec![1]);
+        editor
+            .set_code_block_properties(0, highlighted.clone())
+            .unwrap();
+        assert_eq!(properties(&editor, &[0]), highlighted);
+        assert_eq!(
+            editor
+                .set_code_block_properties(2, highlighted.clone())
+                .unwrap_err()
+                .kind(),
+            EditErrorKind::InvalidNode
+        );
+
+        let template = CodeBlockProperties::new()
+            .with_theme("github")
+            .with_line_numbers(true);
+        assert_eq!(
+            editor.apply_code_block_template("rust", &template).unwrap(),
+            2
+        );
+        assert_eq!(
+            editor.apply_code_block_template("rust", &template).unwrap(),
+            0
+        );
+        assert_eq!(
+            editor.apply_code_block_template("go", &template).unwrap(),
+            0
+        );
+        assert_eq!(
+            properties(&editor, &[0]),
+            template.clone().with_highlight_lines(vec![1])
+        );
+        assert_eq!(properties(&editor, &[2, 0]), template);
+        assert_eq!(properties(&editor, &[1]), CodeBlockProperties::default());
+
+        editor.undo().unwrap();
+        assert_eq!(properties(&editor, &[0]), highlighted);
+        assert_eq!(properties(&editor, &[2, 0]), CodeBlockProperties::default());
+        editor.undo().unwrap();
+        assert_eq!(properties(&editor, &[0]), CodeBlockProperties::default());
+    }
 
-        // Check we're back to the original 3 items at top level
-        {
+    #[test]
+    fn test_fix_terminology() {
+        let doc = Document::try_from(crate::Text::<crate::Markdown>::new(
+            "# Javascript tips\n\nSend an e-mail about `javascript`.\n\n- E-mail the Javascript team\n",
+        ))
+        .unwrap();
+        let mut editor = Editor::new(doc);
+        let terminology = crate::Terminology::new()
+            .prefer("JavaScript", ["javascript"])
+            .prefer("email", ["e-mail"]);
+
+        assert_eq!(editor.fix_terminology(&terminology).unwrap(), 4);
+        let markdown = editor
+            .document()
+            .borrow()
+            .to_markdown_with(&crate::MarkdownOptions::default());
+        assert_eq!(
+            markdown,
+            "# JavaScript tips\n\nSend an email about `javascript`.\n\n* Email the JavaScript team"
+        );
+        assert_eq!(editor.fix_terminology(&terminology).unwrap(), 0);
+
+        editor.undo().unwrap();
+        let doc = editor.document().borrow();
+        assert_eq!(doc.check_terminology(&terminology).len(), 4);
+    }
+
+    #[test]
+    fn test_drop_outline_section() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Guide");
+        doc.add_heading(2, "Setup");
+        doc.add_paragraph_with_text("Install it.");
+        doc.add_heading(3, "Linux");
+        doc.add_heading(1, "Reference");
+        let mut editor = Editor::new(doc);
+        let headings = |editor: &Editor| -> Vec<(u8, String)> {
             let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[node_id] {
-                println!("  After undo, top items length: {}", items.len());
-                for (i, item) in items.iter().enumerate() {
-                    println!(
-                        "  Item {}: text = {:?}, checked = {:?}",
-                        i,
-                        item.as_text(),
-                        item.checked
-                    );
-                }
+            doc.outline()
+                .iter()
+                .map(|entry| (entry.level, entry.text.clone()))
+                .collect()
+        };
 
-                assert_eq!(items.len(), 3);
-                assert_eq!(items[0].as_text().unwrap(), "Task 1");
-                assert_eq!(items[1].as_text().unwrap(), "Task 2");
-                assert_eq!(items[2].as_text().unwrap(), "Task 3");
-            }
+        let moved = editor
+            .drop_outline_section(1, crate::OutlineDrop::Into(4))
+            .unwrap();
+        assert_eq!(moved.level_shift, 0);
+        editor
+            .drop_outline_section(0, crate::OutlineDrop::After(1))
+            .unwrap();
+        assert_eq!(
+            headings(&editor),
+            [
+                (1, "Reference".to_string()),
+                (2, "Setup".to_string()),
+                (3, "Linux".to_string()),
+                (1, "Guide".to_string()),
+            ]
+        );
+        assert!(
+            editor
+                .drop_outline_section(0, crate::OutlineDrop::Into(2))
+                .is_err()
+        );
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(headings(&editor)[1], (2, "Setup".to_string()));
+        assert_eq!(editor.document().borrow().nodes[1].plain_text(), "Setup");
+    }
+
+    fn long_document() -> Document {
+        let mut doc = Document::new();
+        for i in 0..20 {
+            doc.add_heading(2, format!("Section {}", i));
+            doc.add_paragraph_with_text("lorem ipsum dolor sit amet ".repeat(20));
         }
+        doc
     }
 
     #[test]
-    fn test_move_task_item_up_down() {
-        // Create a document with a task list
-        let mut doc = Document::new();
-        doc.add_task_list(vec![
-            ("Task 1", false),
-            ("Task 2", true),
-            ("Task 3", false),
-            ("Task 4", true),
-        ]);
+    fn test_clear_history() {
+        let mut editor = Editor::new(long_document());
+        editor.insert_paragraph(0, "Intro").unwrap();
+        editor.delete_node(1).unwrap();
+        editor.undo().unwrap();
+
+        assert_eq!((editor.undo_len(), editor.redo_len()), (1, 1));
+        assert!(editor.history_bytes() > 0);
+
+        editor.clear_history();
+        assert_eq!(editor.history_bytes(), 0);
+        assert!(editor.undo().is_err());
+        assert!(editor.redo().is_err());
+    }
+
+    #[test]
+    fn test_compress_history_keeps_undo() {
+        let original = long_document();
+        let mut editor = Editor::new(original.clone());
+
+        // The TOC command snapshots every node in the document for undo
+        editor.create_table_of_contents(0, 2).unwrap();
+        editor.insert_paragraph(0, "Preface").unwrap();
+        let edited = editor.document().borrow().clone();
+
+        let before = editor.history_bytes();
+        let saved = editor.compress_history();
+        assert!(saved > 0);
+        assert_eq!(editor.history_bytes(), before - saved);
+        assert_eq!(*editor.document().borrow(), edited);
+        assert_eq!(editor.compress_history(), 0);
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes, original.nodes);
+
+        editor.redo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(editor.document().borrow().nodes, edited.nodes);
+    }
+
+    #[test]
+    fn test_history_budget() {
+        let mut editor = Editor::new(long_document());
+        for i in 0..10 {
+            editor.create_table_of_contents(0, 2).unwrap();
+            editor.insert_paragraph(0, &format!("Edit {}", i)).unwrap();
+        }
+        let unbounded = editor.history_bytes();
+
+        editor.set_history_budget(Some(unbounded / 4));
+        assert!(editor.history_bytes() <= unbounded / 4);
+        assert!(editor.undo_len() > 1);
+
+        // A budget smaller than any single entry keeps only the latest one
+        editor.set_history_budget(Some(1));
+        assert_eq!(editor.undo_len(), 1);
+        editor.undo().unwrap();
+        assert!(
+            editor.document().borrow().nodes[0]
+                .plain_text()
+                .contains("Section 0")
+        );
+    }
+
+    #[test]
+    fn test_history_compression_interval() {
+        use std::time::Duration;
+
+        let mut editor = Editor::new(long_document());
+        editor.create_table_of_contents(0, 2).unwrap();
+        let toc = editor.history_bytes();
+        editor.set_history_compression_interval(Some(Duration::from_secs(3600)));
+        editor.set_history_budget(Some(toc * 3));
+        assert_eq!(editor.history_bytes(), toc);
+
+        // The first time over the budget compresses, and the interval then
+        // lets the history grow up to twice the budget
+        for _ in 0..3 {
+            editor.create_table_of_contents(0, 2).unwrap();
+        }
+        assert!(editor.history_bytes() <= toc * 3);
+        let mut over_budget = false;
+        for _ in 0..20 {
+            editor.create_table_of_contents(0, 2).unwrap();
+            // Past twice the budget it compresses regardless
+            assert!(editor.history_bytes() <= toc * 6);
+            over_budget |= editor.history_bytes() > toc * 3;
+        }
+        assert!(over_budget);
+
+        // Without an interval the budget holds after every command
+        editor.set_history_compression_interval(None);
+        for _ in 0..10 {
+            editor.create_table_of_contents(0, 2).unwrap();
+            assert!(editor.history_bytes() <= toc * 3);
+        }
+    }
 
+    #[test]
+    fn test_paste_fragment() {
+        let doc = Document::try_from(crate::Text::<crate::Markdown>::new(
+            "Hello world\n\n```\ncode\n```\n",
+        ))
+        .unwrap();
         let mut editor = Editor::new(doc);
+        let text =
+            |editor: &Editor, index: usize| editor.document().borrow().nodes[index].plain_text();
+
+        editor.select_text_range(0, 6, 6).unwrap();
+        let DocumentFragment::Inline(mut inlines) =
+            DocumentFragment::from_markdown("**big** [new](https://a.example)").unwrap()
+        else {
+            panic!("expected inline content");
+        };
+        inlines.push(InlineNode::text(" "));
+        editor
+            .paste_fragment(DocumentFragment::Inline(inlines))
+            .unwrap();
+        assert_eq!(text(&editor, 0), "Hello big new world");
+        let cursor = editor.document().borrow().selection.clone().unwrap().end;
+        assert_eq!(
+            cursor,
+            Position::new(vec![0], 12),
+            "the link takes one offset"
+        );
+        editor.undo().unwrap();
+        assert_eq!(text(&editor, 0), "Hello world");
+
+        // Inline content pasted into a code block becomes a paragraph after it
+        editor.select_text_range(1, 0, 0).unwrap();
+        let blocks = DocumentFragment::from_markdown("# One\n\nTwo\n").unwrap();
+        editor
+            .paste_fragment(DocumentFragment::Inline(vec![InlineNode::text("Three")]))
+            .unwrap();
+        editor.paste_fragment(blocks.clone()).unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 5);
+        assert_eq!(text(&editor, 2), "Three");
+        assert_eq!(text(&editor, 3), "One");
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 2, "one step each");
+
+        assert_eq!(editor.insert_fragment(0, blocks).unwrap(), 2);
+        let extracted = editor.document().borrow().fragment(0..2);
+        assert_eq!(
+            extracted.to_markdown_with(&Default::default()),
+            "# One\n\nTwo\n"
+        );
+        let unsafe_link = DocumentFragment::Blocks(vec![
+            Node::paragraph_with_inlines(vec![InlineNode::link("https://ok.example", "ok")]),
+            Node::paragraph_with_inlines(vec![InlineNode::link("javascript:alert(1)", "x")]),
+        ]);
+        let err = editor.insert_fragment(0, unsafe_link).unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::InvalidUrl);
+        assert_eq!(editor.document().borrow().nodes.len(), 4);
+    }
 
-        // Check initial state
+    #[test]
+    fn test_autosave() {
+        use crate::{DocumentStore, MemoryStore, StoreError};
+        use std::time::Duration;
+
+        let mut editor = Editor::new_empty();
+        editor.enable_autosave(
+            MemoryStore::new(),
+            "draft",
+            AutosavePolicy::new(Some(3), Some(Duration::ZERO)),
+        );
+        assert!(!editor.has_unsaved_changes());
+        editor.insert_paragraph(0, "One").unwrap();
+        editor.insert_paragraph(1, "Two").unwrap();
+        assert!(editor.has_unsaved_changes());
+        assert_eq!(editor.last_saved_revision(), None);
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.last_saved_revision(),
+            Some(1),
+            "saved after 3 changes"
+        );
+        assert!(!editor.has_unsaved_changes());
+        assert_eq!(editor.poll_autosave().unwrap(), None, "nothing to save");
+
+        editor.redo().unwrap();
+        assert_eq!(editor.poll_autosave().unwrap(), Some(2), "debounced save");
+        assert_eq!(editor.save_now().unwrap(), Some(3));
+
+        // A crash loses the editor, but not the autosaved revisions
+        let store = editor.disable_autosave().unwrap();
+        assert_eq!(store.revisions("draft").unwrap().len(), 3);
+        let mut store_copy = MemoryStore::new();
+        store_copy
+            .save("draft", &store.load("draft", None).unwrap())
+            .unwrap();
+        let recovered = Editor::recover(store_copy, "draft", AutosavePolicy::default()).unwrap();
+        assert_eq!(recovered.document().borrow().nodes.len(), 2);
+        assert_eq!(recovered.last_saved_revision(), Some(1));
+        assert!(matches!(
+            Editor::recover(MemoryStore::new(), "draft", AutosavePolicy::default()),
+            Err(StoreError::NotFound { .. })
+        ));
+
+        let mut editor = Editor::new_empty();
+        editor.enable_autosave(
+            MemoryStore::new(),
+            "../x",
+            AutosavePolicy::new(Some(1), None),
+        );
+        editor.insert_paragraph(0, "Saved nowhere").unwrap();
+        assert!(matches!(
+            editor.poll_autosave(),
+            Err(StoreError::InvalidId(_))
+        ));
+        assert_eq!(
+            editor.save_now().unwrap_err().to_string(),
+            "invalid document id \"../x\""
+        );
+        assert_eq!(Editor::new_empty().save_now().unwrap(), None);
+    }
+
+    #[test]
+    fn test_paste_image() {
+        use crate::{Asset, AssetStore, MemoryAssetStore};
+
+        let mut editor = Editor::new_empty();
+        editor.insert_paragraph(0, "Logo: ").unwrap();
+        editor.select_text_range(0, 6, 6).unwrap();
+        let mut store = MemoryAssetStore::new();
+        let png = Asset::new("image/png", b"\x89PNG".to_vec());
+        let id = editor.paste_image(&mut store, png.clone(), "logo").unwrap();
+        assert_eq!(id, png.id());
+        assert_eq!(editor.paste_image(&mut store, png, "again").unwrap(), id);
+        assert_eq!(store.len(), 1);
+
+        let images = editor.document().borrow().images();
+        assert_eq!(images.len(), 2);
+        assert!(
+            images
+                .iter()
+                .all(|image| image.url == format!("asset:{id}"))
+        );
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert!(editor.document().borrow().images().is_empty());
+        assert!(store.contains(&id));
+
+        editor.set_url_policy(UrlPolicy::new().deny_scheme("asset"));
+        let gif = Asset::new("image/gif", b"GIF89a".to_vec());
+        assert!(editor.paste_image(&mut store, gif, "x").is_err());
+    }
+
+    #[test]
+    fn test_insert_snippet() {
+        use crate::Snippet;
+        use crate::offsets::offset_text;
+
+        let mut editor = Editor::new_empty();
+        editor.snippets_mut().register(
+            "greeting",
+            Snippet::from_markdown("Dear {{name}}, {{1:thanks}} for {{2}}.{{cursor}}").unwrap(),
+        );
+        editor.snippets_mut().register(
+            "meeting",
+            Snippet::from_markdown("## {{1:Topic}}\n\nAttendees: {{2:me}}\n").unwrap(),
+        );
+        assert_eq!(
+            editor.snippets().names().collect::<Vec<_>>(),
+            ["greeting", "meeting"]
+        );
+        assert!(editor.insert_snippet("missing", &[]).is_err());
+
+        editor.insert_paragraph(0, "Hi. ").unwrap();
+        editor.select_text_range(0, 4, 4).unwrap();
+        editor
+            .insert_snippet("greeting", &[("name", "Bo")])
+            .unwrap();
+        let selected = |editor: &Editor| {
+            let document = editor.document().borrow();
+            let selection = document.selection.clone().unwrap();
+            let text = offset_text(&document.nodes[selection.start.path[0]]);
+            text[selection.start.offset..selection.end.offset].to_string()
+        };
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Hi. Dear Bo, thanks for ."
+        );
+        assert_eq!(selected(&editor), "thanks");
+
+        // Typing over a stop keeps the later stops in place
+        editor.insert_text(0, 13, "many thanks to").unwrap();
+        editor.delete_text(0, 27, 33).unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Hi. Dear Bo, many thanks to for ."
+        );
+        assert!(editor.next_tab_stop());
+        let selection = editor.document().borrow().selection.clone().unwrap();
+        assert_eq!(selection.start, Position::new(vec![0], 32));
+        assert!(editor.prev_tab_stop());
+        assert_eq!(selected(&editor), "many thanks to");
+        assert!(!editor.prev_tab_stop());
+        assert!(editor.next_tab_stop());
+        assert!(editor.next_tab_stop(), "the cursor is the last stop");
+        let selection = editor.document().borrow().selection.clone().unwrap();
+        assert_eq!(selection.start, Position::new(vec![0], 33));
+        assert!(!editor.next_tab_stop());
+        assert!(!editor.has_tab_stops());
+
+        editor.insert_snippet("meeting", &[]).unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 3);
+        assert_eq!(selected(&editor), "Topic");
+        assert!(editor.next_tab_stop());
+        assert_eq!(selected(&editor), "me");
+        assert_eq!(
+            editor
+                .document()
+                .borrow()
+                .selection
+                .clone()
+                .unwrap()
+                .start
+                .path,
+            vec![2]
+        );
+        editor.undo().unwrap();
+        assert!(
+            !editor.has_tab_stops(),
+            "undoing the snippet drops its stops"
+        );
+        assert!(!editor.next_tab_stop());
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        use crate::{ConflictSide, Markdown, Text, merge_documents};
+
+        let doc = |markdown: &str| Document::try_from(Text::<Markdown>::new(markdown)).unwrap();
+        let base = doc("A\n\nB\n\nC\n");
+        let merged = merge_documents(&base, &doc("A1\n\nB\n\nC1\n"), &doc("A2\n\nB\n\nC2\n"));
+        assert_eq!(merged.conflicts(), vec![0, 2]);
+
+        let mut editor = Editor::new(merged.clone());
+        assert_eq!(
+            editor
+                .resolve_conflict(1, ConflictSide::Ours)
+                .unwrap_err()
+                .kind(),
+            EditErrorKind::InvalidNode
+        );
+        editor.resolve_conflict(2, ConflictSide::Both).unwrap();
+        editor.resolve_conflict(0, ConflictSide::Theirs).unwrap();
+        assert_eq!(
+            editor
+                .document()
+                .borrow()
+                .to_markdown_with(&Default::default()),
+            "A2\n\nB\n\nC1\n\nC2"
+        );
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(*editor.document().borrow(), merged);
+
+        assert_eq!(editor.resolve_all_conflicts(ConflictSide::Ours).unwrap(), 2);
+        assert_eq!(
+            editor
+                .document()
+                .borrow()
+                .to_markdown_with(&Default::default()),
+            "A1\n\nB\n\nC1"
+        );
+        assert_eq!(editor.resolve_all_conflicts(ConflictSide::Ours).unwrap(), 0);
+        editor.undo().unwrap();
+        assert_eq!(editor.document().borrow().conflicts(), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_smart_paste() {
+        let mut editor = Editor::new_empty();
+        editor.insert_paragraph(0, "Hello world").unwrap();
+        editor.select_text_range(0, 5, 5).unwrap();
+        editor.paste_markdown(", *big*").unwrap();
+        let texts = |editor: &Editor| -> Vec<String> {
+            let document = editor.document().borrow();
+            document.nodes.iter().map(Node::plain_text).collect()
+        };
+        assert_eq!(texts(&editor), ["Hello, big world"]);
+
+        editor.select_text_range(0, 6, 6).unwrap();
+        editor.paste_markdown("one\n\n- item\n\ntwo: ").unwrap();
+        assert_eq!(texts(&editor), ["Hello,one", "item", "two: big world"]);
         {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[0] {
-                assert_eq!(items.len(), 4);
-                assert_eq!(items[0].as_text(), Some("Task 1"));
-                assert_eq!(items[1].as_text(), Some("Task 2"));
-                assert_eq!(items[2].as_text(), Some("Task 3"));
-                assert_eq!(items[3].as_text(), Some("Task 4"));
-            }
+            let document = editor.document().borrow();
+            assert!(matches!(document.nodes[1], Node::List { .. }));
+            let cursor = &document.selection.as_ref().unwrap().end;
+            assert_eq!((cursor.path.as_slice(), cursor.offset), (&[2][..], 4));
         }
+        editor.undo().unwrap();
+        assert_eq!(texts(&editor), ["Hello, big world"]);
+
+        // Blocks not starting or ending with a paragraph split the node
+        editor.select_text_range(0, 6, 6).unwrap();
+        editor
+            .paste_html("<pre><code>let x = 1;</code></pre>")
+            .unwrap();
+        assert_eq!(texts(&editor), ["Hello,", "let x = 1;", " big world"]);
+        let cursor = editor.document().borrow().selection.clone().unwrap().end;
+        assert_eq!((cursor.path, cursor.offset), (vec![2], 0));
+
+        editor.select_text_range(2, 10, 10).unwrap();
+        editor.paste_html("<p>Done.</p><p>Really</p>").unwrap();
+        assert_eq!(
+            texts(&editor),
+            ["Hello,", "let x = 1;", " big worldDone.", "Really"]
+        );
+        assert!(
+            editor
+                .paste_markdown("[x](javascript:alert(1))\n\nmore\n")
+                .is_err()
+        );
+    }
 
-        // Move Task 3 up (swap with Task 2)
-        editor.move_task_item_up(0, 2).unwrap();
-
-        // Verify the order after moving up
+    #[test]
+    fn test_input_rules() {
+        let mut editor = Editor::new_empty();
+        editor.insert_paragraph(0, "Title").unwrap();
+
+        // Shortcuts are off until an app opts in
+        editor.select_text_range(0, 0, 0).unwrap();
+        editor.insert_text_at_cursor("# ").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0],
+            Node::paragraph("# Title")
+        );
+        editor.undo().unwrap();
+        editor.set_input_rules(crate::InputRules::with_defaults());
+
+        editor.select_text_range(0, 0, 0).unwrap();
+        editor.insert_text_at_cursor("#").unwrap();
+        editor.insert_text_at_cursor("# ").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0],
+            Node::heading(2, "Title")
+        );
+        editor.insert_text_at_cursor("My ").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0],
+            Node::heading(2, "My Title")
+        );
+
+        // Undo keeps the typed shortcut as text
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0],
+            Node::paragraph("## Title")
+        );
+
+        editor.insert_paragraph(1, "Second").unwrap();
+        editor.select_text_range(1, 0, 0).unwrap();
+        editor.insert_text_at_cursor("2. ").unwrap();
         {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[0] {
-                assert_eq!(items.len(), 4);
-                assert_eq!(items[0].as_text(), Some("Task 1"));
-                assert_eq!(items[1].as_text(), Some("Task 3")); // Now in position 1
-                assert_eq!(items[2].as_text(), Some("Task 2")); // Now in position 2
-                assert_eq!(items[3].as_text(), Some("Task 4"));
-            }
+            let document = editor.document().borrow();
+            let Node::List {
+                list_type,
+                items,
+                start,
+                ..
+            } = &document.nodes[1]
+            else {
+                panic!("expected a list");
+            };
+            assert_eq!((list_type, *start), (&ListType::Ordered, Some(2)));
+            assert_eq!(items[0].children, vec![Node::paragraph("Second")]);
+            let cursor = &document.selection.as_ref().unwrap().start;
+            assert_eq!((cursor.path.as_slice(), cursor.offset), (&[1, 0, 0][..], 0));
         }
 
-        // Move Task 1 down (swap with Task 3)
-        editor.move_task_item_down(0, 0).unwrap();
+        editor.insert_paragraph(2, "").unwrap();
+        editor.select_text_range(2, 0, 0).unwrap();
+        editor.insert_text_at_cursor("```").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[2],
+            Node::code_block("", "")
+        );
+        editor.insert_text_at_cursor("let x = 1;").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[2],
+            Node::code_block("let x = 1;", "")
+        );
+
+        // Shortcuts only fire at the start of a paragraph, and not at all
+        // without rules
+        editor.insert_paragraph(3, "a").unwrap();
+        editor.select_text_range(3, 1, 1).unwrap();
+        editor.insert_text_at_cursor("> ").unwrap();
+        assert_eq!(editor.document().borrow().nodes[3], Node::paragraph("a> "));
+        editor.set_input_rules(crate::InputRules::new());
+        editor.select_text_range(3, 0, 0).unwrap();
+        editor.insert_text_at_cursor("- ").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[3],
+            Node::paragraph("- a> ")
+        );
+    }
 
-        // Verify the order after moving down
+    #[test]
+    fn test_refresh_toc() {
+        let mut editor = Editor::new_empty();
+        editor.insert_heading(0, 1, "Guide").unwrap();
+        editor
+            .insert_node(1, Node::TableOfContents { max_level: 1 })
+            .unwrap();
+        editor.insert_heading(2, 2, "Install").unwrap();
+        editor
+            .insert_node(3, Node::TableOfContents { max_level: 0 })
+            .unwrap();
+        assert_eq!(editor.refresh_toc().unwrap(), 2);
         {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[0] {
-                assert_eq!(items.len(), 4);
-                assert_eq!(items[0].as_text(), Some("Task 3")); // Now in position 0
-                assert_eq!(items[1].as_text(), Some("Task 1")); // Now in position 1
-                assert_eq!(items[2].as_text(), Some("Task 2"));
-                assert_eq!(items[3].as_text(), Some("Task 4"));
-            }
+            let document = editor.document().borrow();
+            assert_eq!(document.nodes.len(), 3);
+            let Node::List { items, .. } = &document.nodes[1] else {
+                panic!("expected a list");
+            };
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].children[0].plain_text(), "Guide");
         }
+        assert_eq!(editor.refresh_toc().unwrap(), 0);
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[3],
+            Node::TableOfContents { max_level: 0 }
+        );
+    }
 
-        // Test error cases: moving first item up
-        let result = editor.move_task_item_up(0, 0);
-        assert!(result.is_err());
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Already at the top");
+    #[test]
+    fn test_node_attributes_follow_edits() {
+        let mut doc = Document::new();
+        for text in ["A", "B", "C"] {
+            doc.nodes.push(Node::paragraph(text));
         }
+        doc.set_node_attribute(1, "line", "3").unwrap();
+        doc.set_node_attribute(2, "line", "5").unwrap();
+        let mut editor = Editor::new(doc);
 
-        // Test error cases: moving last item down
-        let result = editor.move_task_item_down(0, 3);
-        assert!(result.is_err());
-        if let Err(EditError::Other(msg)) = result {
-            assert_eq!(msg, "Already at the bottom");
+        editor.insert_node(0, Node::paragraph("Intro")).unwrap();
+        editor.insert_text(2, 1, "!").unwrap();
+        let lines = |editor: &Editor| {
+            let document = editor.document().borrow();
+            (0..document.nodes.len())
+                .map(|index| document.node_attribute(index, "line").map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            lines(&editor),
+            vec![None, None, Some("3".to_string()), Some("5".to_string())]
+        );
+
+        editor.delete_node(2).unwrap();
+        assert_eq!(lines(&editor), vec![None, None, Some("5".to_string())]);
+        editor.undo().unwrap();
+        assert_eq!(
+            lines(&editor),
+            vec![None, None, Some("3".to_string()), Some("5".to_string())]
+        );
+        editor.redo().unwrap();
+        assert_eq!(lines(&editor), vec![None, None, Some("5".to_string())]);
+    }
+
+    #[test]
+    fn test_node_attributes_follow_moves() {
+        let mut doc = Document::new();
+        for text in ["A", "B", "C", "D"] {
+            doc.nodes.push(Node::paragraph(text));
         }
+        doc.set_node_attribute(0, "id", "a").unwrap();
+        doc.set_node_attribute(3, "id", "d").unwrap();
+        let mut editor = Editor::new(doc);
+        let ids = |editor: &Editor| {
+            let document = editor.document().borrow();
+            (0..document.nodes.len())
+                .map(|index| {
+                    document
+                        .node_attribute(index, "id")
+                        .unwrap_or("-")
+                        .to_string()
+                })
+                .collect::<String>()
+        };
 
-        // Test undo
-        editor.undo().unwrap(); // Undo "move Task 1 down"
+        editor.move_node(0, 2).unwrap();
+        assert_eq!(ids(&editor), "-a-d");
+        editor.move_node(3, 0).unwrap();
+        assert_eq!(ids(&editor), "d-a-");
+        editor.undo().unwrap();
+        assert_eq!(ids(&editor), "-a-d");
+        editor.undo().unwrap();
+        assert_eq!(ids(&editor), "a--d");
+        editor.redo().unwrap();
+        assert_eq!(ids(&editor), "-a-d");
+    }
 
-        // Verify the order after first undo
-        {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[0] {
-                assert_eq!(items.len(), 4);
-                assert_eq!(items[0].as_text(), Some("Task 1"));
-                assert_eq!(items[1].as_text(), Some("Task 3"));
-                assert_eq!(items[2].as_text(), Some("Task 2"));
-                assert_eq!(items[3].as_text(), Some("Task 4"));
-            }
-        }
+    #[test]
+    fn test_selections_inside_grapheme_clusters() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Hello 👋🏽 wörld");
+        let mut editor = Editor::new(doc);
 
-        editor.undo().unwrap(); // Undo "move Task 3 up"
+        // 9 is inside the hand's bytes, 10 between the hand and its tone
+        let err = editor.select_range(0, 9, 0, 12).unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::InvalidRange);
+        assert_eq!(err.path(), Some(&[0][..]));
+        assert!(editor.select_range(0, 0, 0, 10).is_err());
+        assert!(editor.document().borrow().selection.is_none());
+        assert!(editor.add_cursor(0, 9).is_err());
+        let selection = Selection::new(Position::new(vec![0], 6), Position::new(vec![0], 12));
+        assert!(editor.add_selection(selection).is_err());
+        assert!(editor.selections().is_empty());
+
+        editor.select_range(0, 6, 0, 14).unwrap();
+        assert_eq!(editor.cut_selection().len(), 1);
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "Hello  wörld"
+        );
+    }
 
-        // Verify the order after second undo (should be back to original)
-        {
-            let doc = editor.document().borrow();
-            if let Node::List { items, .. } = &doc.nodes[0] {
-                assert_eq!(items.len(), 4);
-                assert_eq!(items[0].as_text(), Some("Task 1"));
-                assert_eq!(items[1].as_text(), Some("Task 2"));
-                assert_eq!(items[2].as_text(), Some("Task 3"));
-                assert_eq!(items[3].as_text(), Some("Task 4"));
-            }
+    #[test]
+    fn test_selection_follows_moved_nodes() {
+        let mut doc = Document::new();
+        for text in ["Alpha", "Beta", "Gamma"] {
+            doc.add_paragraph_with_text(text);
         }
+        let mut editor = Editor::new(doc);
+        let position = |index, offset| Position::new(vec![index], offset);
+        let caret = |editor: &Editor| editor.document().borrow().selection.clone();
+
+        editor.select_text_range(0, 2, 2).unwrap();
+        editor.add_comment("ann", vec![0], 0..5, "Check").unwrap();
+        editor.move_node(1, 0).unwrap();
+        assert_eq!(caret(&editor), Some(Selection::collapsed(position(1, 2))));
+        assert_eq!(editor.document().borrow().comments[0].path, vec![1]);
+        assert_eq!(editor.last_affected_range(), Some(0..2));
+
+        editor.move_node(0, 3).unwrap();
+        assert_eq!(caret(&editor), Some(Selection::collapsed(position(0, 2))));
+        let mapper = editor.position_mapper();
+        assert_eq!(mapper.map_position(&position(2, 1)), Some(position(1, 1)));
+        assert_eq!(mapper.map_position(&position(0, 4)), Some(position(2, 4)));
+
+        editor.undo().unwrap();
+        let mapper = editor.position_mapper();
+        assert_eq!(mapper.map_position(&position(2, 4)), Some(position(0, 4)));
+        assert_eq!(mapper.map_position(&position(0, 2)), Some(position(1, 2)));
+    }
+
+    #[test]
+    fn test_selection_follows_moved_sections() {
+        let mut doc = Document::new();
+        doc.add_heading(2, "A");
+        doc.add_paragraph_with_text("alpha");
+        doc.add_heading(2, "B");
+        doc.add_paragraph_with_text("beta");
+        let mut editor = Editor::new(doc);
+
+        editor.select_text_range(1, 3, 3).unwrap();
+        editor.move_section(0, 4).unwrap();
+        let selection = editor.document().borrow().selection.clone().unwrap();
+        assert_eq!(selection.start, Position::new(vec![3], 3));
+
+        let outline_drop = editor
+            .drop_outline_section(2, crate::OutlineDrop::Before(0))
+            .unwrap();
+        assert_eq!(outline_drop.level_shift, 0);
+        let selection = editor.document().borrow().selection.clone().unwrap();
+        assert_eq!(selection.start, Position::new(vec![1], 3));
+    }
+
+    /// An editor tracking changes by "ana" in a paragraph with `text`
+    fn tracking_editor(text: &str) -> Editor {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text(text);
+        doc.add_paragraph_with_text("Second");
+        let mut editor = Editor::new(doc);
+        editor.set_track_changes(Some("ana".to_string()));
+        editor
+    }
+
+    fn tracked_ranges(editor: &Editor) -> Vec<(ChangeKind, std::ops::Range<usize>)> {
+        let document = editor.document().borrow();
+        let changes = document.tracked_changes.iter();
+        changes
+            .map(|change| (change.kind, change.range.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_track_changes_typing() {
+        let mut editor = tracking_editor("The cat sat");
+        editor.select_text_range(0, 4, 7).unwrap();
+        editor.insert_text_at_cursor("dog").unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "The catdog sat"
+        );
+        assert_eq!(
+            tracked_ranges(&editor),
+            vec![(ChangeKind::Deletion, 4..7), (ChangeKind::Insertion, 7..10)]
+        );
+        assert_eq!(
+            editor.document().borrow().selection,
+            Some(Selection::collapsed(Position::new(vec![0], 10)))
+        );
+
+        // Typing and its marks are one undoable step
+        editor.undo().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "The cat sat"
+        );
+        assert!(tracked_ranges(&editor).is_empty());
+
+        editor.select_range(0, 0, 1, 2).unwrap();
+        assert!(editor.insert_text_at_cursor("x").is_err());
+    }
+
+    #[test]
+    fn test_track_changes_delete_selection() {
+        let mut editor = tracking_editor("The cat sat");
+        editor.select_text_range(0, 3, 7).unwrap();
+        editor.delete_selection().unwrap();
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "The cat sat"
+        );
+        assert_eq!(tracked_ranges(&editor), vec![(ChangeKind::Deletion, 3..7)]);
+
+        editor.select_range(0, 0, 1, 2).unwrap();
+        assert!(editor.delete_selection().is_err());
+        assert_eq!(tracked_ranges(&editor), vec![(ChangeKind::Deletion, 3..7)]);
+    }
+
+    #[test]
+    fn test_track_changes_cut_and_paste() {
+        let mut editor = tracking_editor("The cat sat");
+        editor.select_text_range(0, 4, 8).unwrap();
+        let copied = editor.copy_selection();
+        assert!(!copied.is_empty());
+        assert_eq!(editor.cut_selection(), copied);
+        assert_eq!(
+            editor.document().borrow().nodes[0].plain_text(),
+            "The cat sat"
+        );
+        assert_eq!(tracked_ranges(&editor), vec![(ChangeKind::Deletion, 4..8)]);
+
+        let before = editor.document().borrow().nodes.clone();
+        let error = editor.paste_markdown("dog ").unwrap_err();
+        assert_eq!(error.kind(), EditErrorKind::UnsupportedOperation);
+        assert!(editor.paste_html("<p>dog</p>").is_err());
+        let fragment = DocumentFragment::from_markdown("dog").unwrap();
+        assert!(editor.paste_fragment(fragment).is_err());
+        assert_eq!(editor.document().borrow().nodes, before);
+    }
+
+    #[test]
+    fn test_track_changes_delete_node() {
+        let mut editor = tracking_editor("The cat sat");
+        let error = editor.delete_node(1).unwrap_err();
+        assert_eq!(error.kind(), EditErrorKind::UnsupportedOperation);
+        assert_eq!(editor.document().borrow().nodes.len(), 2);
+
+        editor.set_track_changes(None);
+        editor.delete_node(1).unwrap();
+        assert_eq!(editor.document().borrow().nodes.len(), 1);
     }
 }
@@ -0,0 +1,140 @@
+use crate::Selection;
+use crate::mapping::PositionMapper;
+
+/// Most locations kept to go back to
+const MAX_LOCATIONS: usize = 50;
+
+/// Top-level node a selection starts in
+fn top_node(selection: &Selection) -> Option<usize> {
+    selection.start.path.first().copied()
+}
+
+/// Locations the user jumped away from, to go back and forth between
+/// them like a browser
+///
+/// A jump is a selection made with the editor's `select_*` methods that
+/// lands in another top-level node; cursor motions and edits aren't jumps.
+/// Only the last location in a node is kept, so moving around a paragraph
+/// before jumping away leaves one entry. The history is separate from the
+/// undo history, and edits move the locations along with their text.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NavigationHistory {
+    back: Vec<Selection>,
+    forward: Vec<Selection>,
+}
+
+impl NavigationHistory {
+    /// Records a jump from one selection to another
+    pub(crate) fn record(&mut self, from: Selection, to: &Selection) {
+        if top_node(&from) == top_node(to) {
+            return;
+        }
+        if self.back.last().and_then(top_node) == top_node(&from) {
+            self.back.pop();
+        }
+        self.back.push(from);
+        if self.back.len() > MAX_LOCATIONS {
+            self.back.remove(0);
+        }
+        self.forward.clear();
+    }
+
+    /// The location to go back to from `current`, which becomes the next
+    /// one forward
+    pub(crate) fn back(&mut self, current: Option<Selection>) -> Option<Selection> {
+        step(&mut self.back, &mut self.forward, current)
+    }
+
+    /// The location to go forward to from `current`, which becomes the
+    /// next one back
+    pub(crate) fn forward(&mut self, current: Option<Selection>) -> Option<Selection> {
+        step(&mut self.forward, &mut self.back, current)
+    }
+
+    pub(crate) fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub(crate) fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    /// Moves the locations along with the text an edit moved, dropping
+    /// those whose node was removed
+    pub(crate) fn remap(&mut self, mapper: &PositionMapper) {
+        for locations in [&mut self.back, &mut self.forward] {
+            let mut remapped: Vec<Selection> = Vec::with_capacity(locations.len());
+            for selection in locations.drain(..) {
+                let start = mapper.map_position(&selection.start);
+                let end = mapper.map_position(&selection.end);
+                let Some((start, end)) = start.zip(end) else {
+                    continue;
+                };
+                let selection = Selection::new(start, end);
+                if remapped.last() != Some(&selection) {
+                    remapped.push(selection);
+                }
+            }
+            *locations = remapped;
+        }
+    }
+}
+
+/// Pops the next location from `from` that isn't `current`, pushing
+/// `current` onto `to`
+fn step(
+    from: &mut Vec<Selection>,
+    to: &mut Vec<Selection>,
+    current: Option<Selection>,
+) -> Option<Selection> {
+    let target = loop {
+        let target = from.pop()?;
+        if Some(&target) != current.as_ref() {
+            break target;
+        }
+    };
+    to.extend(current);
+    Some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Position;
+
+    fn at(node: usize, offset: usize) -> Selection {
+        Selection::collapsed(Position::new(vec![node], offset))
+    }
+
+    #[test]
+    fn test_navigation_history() {
+        let mut history = NavigationHistory::default();
+        history.record(at(0, 2), &at(0, 5));
+        assert!(!history.can_go_back(), "moves within a node aren't jumps");
+
+        history.record(at(0, 2), &at(4, 0));
+        history.record(at(4, 0), &at(1, 0));
+        history.record(at(1, 3), &at(7, 0));
+        assert_eq!(history.back, [at(0, 2), at(4, 0), at(1, 3)]);
+
+        assert_eq!(history.back(Some(at(7, 1))), Some(at(1, 3)));
+        assert_eq!(history.back(Some(at(1, 3))), Some(at(4, 0)));
+        assert!(history.can_go_forward());
+        assert_eq!(history.forward(Some(at(4, 0))), Some(at(1, 3)));
+
+        history.record(at(1, 3), &at(9, 0));
+        assert!(
+            !history.can_go_forward(),
+            "a new jump drops the way forward"
+        );
+        // Back in node 1 with the cursor keys, then away again: only the
+        // last location in the node is kept
+        history.record(at(1, 8), &at(12, 0));
+        assert_eq!(history.back, [at(0, 2), at(4, 0), at(1, 8)]);
+
+        for node in 10..70 {
+            history.record(at(node, 0), &at(node + 1, 0));
+        }
+        assert_eq!(history.back.len(), MAX_LOCATIONS);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::EditError;
+use std::ops::Range;
+
+/// The end of the document a pinned node keeps its distance to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinAnchor {
+    /// The node stays at the same index, e.g. a title that must come first
+    Start,
+    /// The node stays as many nodes from the end, e.g. a disclaimer that
+    /// must come last
+    End,
+}
+
+/// A top-level node kept at a fixed distance from one end of the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NodePin {
+    anchor: PinAnchor,
+    offset: usize,
+}
+
+impl NodePin {
+    /// Index of the pinned node in a document of `len` nodes
+    fn index(&self, len: usize) -> Option<usize> {
+        match self.anchor {
+            PinAnchor::Start => (self.offset < len).then_some(self.offset),
+            PinAnchor::End => len.checked_sub(self.offset + 1),
+        }
+    }
+}
+
+/// The pinned nodes of an editor
+///
+/// Pins are positions rather than node identities, so they follow every
+/// edit: nodes inserted or removed elsewhere leave a node pinned to the
+/// start or the end where it was. The node-level moves, inserts,
+/// duplicates and deletes keep clear of the pinned positions; between the
+/// last node pinned to the start and the first pinned to the end lies the
+/// free range those operations may change.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Pins {
+    pins: Vec<NodePin>,
+}
+
+impl Pins {
+    /// Pins the node at `index`, replacing any pin it had
+    pub(crate) fn pin(&mut self, index: usize, len: usize, anchor: PinAnchor) {
+        self.unpin(index, len);
+        let offset = match anchor {
+            PinAnchor::Start => index,
+            PinAnchor::End => len - 1 - index,
+        };
+        self.pins.push(NodePin { anchor, offset });
+    }
+
+    /// Unpins the node at `index`, returning whether it was pinned
+    pub(crate) fn unpin(&mut self, index: usize, len: usize) -> bool {
+        let before = self.pins.len();
+        self.pins.retain(|pin| pin.index(len) != Some(index));
+        self.pins.len() != before
+    }
+
+    /// Indices of the pinned nodes, ascending
+    pub(crate) fn indices(&self, len: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = self.pins.iter().filter_map(|pin| pin.index(len)).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    /// The nodes between the pinned ones, which may be moved or removed
+    /// without moving a pinned node
+    fn free_range(&self, len: usize) -> Range<usize> {
+        let mut free = 0..len;
+        for pin in &self.pins {
+            match (pin.anchor, pin.index(len)) {
+                (PinAnchor::Start, Some(index)) => free.start = free.start.max(index + 1),
+                (PinAnchor::End, Some(index)) => free.end = free.end.min(index),
+                (_, None) => {}
+            }
+        }
+        free
+    }
+
+    /// The insertion point closest to `position` that moves no pinned node
+    pub(crate) fn insertion_point(&self, position: usize, len: usize) -> Result<usize, EditError> {
+        let free = self.free_range(len);
+        if free.start > free.end {
+            return Err(EditError::pinned()
+                .with_message("Pinned nodes leave no room to insert")
+                .with_ranges(free.clone(), position..position));
+        }
+        Ok(position.clamp(free.start, free.end))
+    }
+
+    /// Fails if removing `range` of top-level nodes would remove or move a
+    /// pinned node
+    pub(crate) fn check_removal(&self, range: Range<usize>, len: usize) -> Result<(), EditError> {
+        let free = self.free_range(len);
+        if range.start < free.start || range.end > free.end {
+            return Err(EditError::pinned()
+                .at_node(range.start)
+                .with_ranges(free, range));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pins_follow_document_length() {
+        let mut pins = Pins::default();
+        pins.pin(0, 5, PinAnchor::Start);
+        pins.pin(4, 5, PinAnchor::End);
+        assert_eq!(pins.indices(5), [0, 4]);
+        assert_eq!(pins.indices(7), [0, 6]);
+        assert_eq!(pins.free_range(7), 1..6);
+
+        assert_eq!(pins.insertion_point(0, 5).unwrap(), 1);
+        assert_eq!(pins.insertion_point(5, 5).unwrap(), 4);
+        assert_eq!(pins.insertion_point(2, 5).unwrap(), 2);
+        assert!(pins.check_removal(1..4, 5).is_ok());
+        let err = pins.check_removal(3..5, 5).unwrap_err();
+        assert_eq!(err.kind(), crate::EditErrorKind::Pinned);
+        assert_eq!(err.expected(), Some(&(1..4)));
+
+        assert!(pins.unpin(6, 7));
+        assert!(!pins.unpin(6, 7));
+        assert_eq!(pins.indices(7), [0]);
+        pins.pin(1, 2, PinAnchor::End);
+        assert!(pins.insertion_point(0, 1).is_err(), "both pins on one node");
+    }
+}
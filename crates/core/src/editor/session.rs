@@ -0,0 +1,64 @@
+use crate::folding::collapsed_mut;
+use crate::models::fnv1a;
+use crate::{Document, Position, Selection};
+use serde::{Deserialize, Serialize};
+
+/// A named position the user can jump back to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// Name shown to the user, unique within an editor
+    pub name: String,
+    /// The marked position
+    pub position: Position,
+}
+
+/// UI state of an editor that is not part of the document
+///
+/// Obtained from [`Editor::session`](super::Editor::session) and restored
+/// with [`Editor::restore_session`](super::Editor::restore_session) once
+/// the document has been loaded again. The session records a hash of the
+/// content it was taken on, so restoring it onto a document that changed
+/// in the meantime fails instead of selecting or folding the wrong nodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditorSession {
+    /// [`Document::content_hash`] of the document the session was taken on
+    pub content_hash: String,
+    /// The selection or cursor
+    pub selection: Option<Selection>,
+    /// Indices of the folded top-level nodes, ascending
+    #[serde(default)]
+    pub folded: Vec<usize>,
+    /// Bookmarks in the order they were set
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    /// Position to keep at the top of the viewport
+    #[serde(default)]
+    pub scroll_anchor: Option<Position>,
+    /// Number of undo steps at the time, to pair the session with an
+    /// exported [`EditHistory`](super::EditHistory)
+    #[serde(default)]
+    pub history_index: usize,
+}
+
+impl EditorSession {
+    /// Returns true if the session was taken on a document with this content
+    pub fn matches(&self, document: &Document) -> bool {
+        self.content_hash == document.content_hash()
+    }
+}
+
+impl Document {
+    /// A stable hash of the document's nodes, as 16 hex digits
+    ///
+    /// Metadata, the selection and folds are not included, so only edits
+    /// to the content change the hash. The hash is the same across runs and
+    /// platforms, which makes it fit to store alongside the document.
+    pub fn content_hash(&self) -> String {
+        let mut nodes = self.nodes.clone();
+        for flag in nodes.iter_mut().filter_map(collapsed_mut) {
+            *flag = false;
+        }
+        let bytes = serde_json::to_vec(&nodes).unwrap_or_default();
+        format!("{:016x}", fnv1a(&bytes))
+    }
+}
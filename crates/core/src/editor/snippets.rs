@@ -0,0 +1,55 @@
+use crate::mapping::{PositionMapper, RangeMode};
+use std::ops::Range;
+
+/// Tab stops of an inserted snippet, by path and byte range, moved along
+/// with their text as the document is edited
+#[derive(Debug, Clone)]
+pub(crate) struct TabStops {
+    stops: Vec<(Vec<usize>, Range<usize>)>,
+    current: usize,
+}
+
+impl TabStops {
+    /// Tab stops to visit in order, starting at the first, or `None`
+    /// without any
+    pub(crate) fn new(stops: Vec<(Vec<usize>, Range<usize>)>) -> Option<Self> {
+        (!stops.is_empty()).then_some(Self { stops, current: 0 })
+    }
+
+    /// The tab stop the selection is at
+    pub(crate) fn current(&self) -> (&[usize], Range<usize>) {
+        let (path, range) = &self.stops[self.current];
+        (path, range.clone())
+    }
+
+    /// Moves to the next tab stop, or the previous one going backwards,
+    /// returning false if there is none
+    pub(crate) fn step(&mut self, forward: bool) -> bool {
+        let next = match forward {
+            true => Some(self.current + 1).filter(|&next| next < self.stops.len()),
+            false => self.current.checked_sub(1),
+        };
+        if let Some(next) = next {
+            self.current = next;
+        }
+        next.is_some()
+    }
+
+    /// Moves the tab stops through the last change, dropping those whose
+    /// node was removed, and returns false if none is left
+    pub(crate) fn remap(&mut self, mapper: &PositionMapper) -> bool {
+        let mut stops = Vec::with_capacity(self.stops.len());
+        let mut current = 0;
+        for (index, (path, range)) in self.stops.drain(..).enumerate() {
+            if let Some(stop) = mapper.map(&path, &range, RangeMode::Adjust) {
+                if index <= self.current {
+                    current = stops.len();
+                }
+                stops.push(stop);
+            }
+        }
+        self.stops = stops;
+        self.current = current;
+        !self.stops.is_empty()
+    }
+}
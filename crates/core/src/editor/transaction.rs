@@ -67,6 +67,13 @@ impl Transaction {
         self
     }
 
+    /// Make a suggested edit while changes are tracked.
+    pub(crate) fn track_edit(&mut self, edit: TrackedEdit) -> &mut Self {
+        let command = TrackChangesCommand::new(self.document.clone(), edit);
+        self.add_command(command);
+        self
+    }
+
     /// Delete text from a specific node.
     pub fn delete_text(&mut self, node_index: usize, start: usize, end: usize) -> &mut Self {
         let command = DeleteTextCommand::new(self.document.clone(), node_index, start, end);
@@ -248,9 +255,7 @@ impl Transaction {
     /// Note that this consumes the transaction, so it can only be committed once.
     pub fn commit(mut self) -> Result<Vec<Box<dyn EditorCommand>>, EditError> {
         if self.committed {
-            return Err(EditError::Other(
-                "Transaction already committed".to_string(),
-            ));
+            return Err(EditError::other("Transaction already committed"));
         }
 
         // No commands to execute
@@ -262,6 +267,7 @@ impl Transaction {
         for cmd in &mut self.commands {
             // If any command fails, roll back all previous commands
             if let Err(err) = cmd.execute() {
+                let err = err.in_command(cmd.name());
                 // Roll back all commands that were already executed
                 self.rollback();
                 return Err(err);
@@ -482,5 +488,11 @@ mod tests {
             let doc = editor.document().borrow();
             assert_eq!(doc.nodes.len(), 0);
         }
+
+        // Redo applies the whole transaction again
+        editor.redo().expect("Redo should succeed");
+        let doc = editor.document().borrow();
+        assert_eq!(doc.nodes.len(), 4);
+        assert_eq!(doc.nodes[3].plain_text(), "Another paragraph");
     }
 }
@@ -0,0 +1,503 @@
+//! EPUB 3 export of multi-chapter books
+//!
+//! Only compiled with the `epub` feature. [`write_epub`] takes the chapters
+//! of a book in reading order, each a [`Document`], and packs them into an
+//! EPUB archive: one XHTML file per chapter, a navigation document built
+//! from the chapters' heading outlines, and the local images the chapters
+//! refer to. Images with a URL scheme, such as `https://`, are left as
+//! remote resources.
+
+use crate::convert::html_escape;
+use crate::links::has_scheme;
+use crate::outline::{Outline, OutlineEntry};
+use crate::snippets::utc_timestamp;
+use crate::{Document, HtmlOptions};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Cursor, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+static VOID_ELEMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)<(area|base|br|col|embed|hr|img|input|link|meta|source|track|wbr)\b([^>]*?)\s*/?>",
+    )
+    .unwrap()
+});
+static BOOLEAN_ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s(checked|disabled|open)(\s|>|$)").unwrap());
+
+/// Title, author and identity of a book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubMetadata {
+    /// Title of the book
+    pub title: String,
+    /// Author of the book
+    pub author: Option<String>,
+    /// Language of the book as a BCP 47 tag
+    pub language: String,
+    /// Unique identifier of the book, such as an ISBN or a UUID URN
+    pub identifier: String,
+    /// Last modification time as `CCYY-MM-DDThh:mm:ssZ`, the time of export
+    /// when not set
+    pub modified: Option<String>,
+}
+
+impl EpubMetadata {
+    /// Metadata for a book in English
+    pub fn new(title: impl Into<String>, identifier: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            author: None,
+            language: "en".to_string(),
+            identifier: identifier.into(),
+            modified: None,
+        }
+    }
+
+    /// Sets the author
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Sets the language
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// Sets the last modification time
+    pub fn with_modified(mut self, modified: impl Into<String>) -> Self {
+        self.modified = Some(modified.into());
+        self
+    }
+}
+
+/// Options for [`write_epub`]
+#[derive(Debug, Clone, Default)]
+pub struct EpubOptions {
+    /// Directory relative image paths are resolved against, the current
+    /// directory when not set
+    pub image_root: Option<PathBuf>,
+    /// Options for rendering the chapters
+    pub html: HtmlOptions,
+}
+
+/// Media type of an image EPUB readers must support, by file extension
+fn image_media_type(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => return None,
+    })
+}
+
+/// Decodes `%XX` escapes, or returns `None` if an escape is malformed or
+/// the result is not UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The file a local image URL refers to, or `None` if it is malformed or
+/// points outside the image root
+///
+/// The query and fragment are dropped and escapes decoded. Absolute paths
+/// and `..` components are refused, as the URLs come from the documents.
+fn image_path(root: &Path, url: &str) -> Option<PathBuf> {
+    let end = url.find(['?', '#']).unwrap_or(url.len());
+    let path = PathBuf::from(percent_decode(&url[..end])?);
+    let inside = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    (inside && path.file_name().is_some()).then(|| root.join(path))
+}
+
+/// An image packed into the archive
+struct Resource {
+    href: String,
+    media_type: &'static str,
+    bytes: Vec<u8>,
+}
+
+/// Makes HTML output well-formed XHTML: void elements are closed and
+/// boolean attributes get values
+fn to_xhtml(html: &str) -> String {
+    let closed = VOID_ELEMENT.replace_all(html, "<$1$2 />");
+    let mut xhtml = closed.into_owned();
+    loop {
+        let expanded = BOOLEAN_ATTRIBUTE.replace_all(&xhtml, " $1=\"$1\"$2");
+        if expanded == xhtml {
+            return xhtml;
+        }
+        xhtml = expanded.into_owned();
+    }
+}
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{}.xhtml", index + 1)
+}
+
+/// Navigation entries for a chapter's headings
+fn nav_items(entries: &[OutlineEntry], file: &str) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "<li><a href=\"{file}#{}\">{}</a>{}</li>",
+                html_escape(&entry.anchor),
+                html_escape(&entry.text),
+                nav_list(&entry.children, file)
+            )
+        })
+        .collect()
+}
+
+/// Nested list of navigation entries, empty without headings
+fn nav_list(entries: &[OutlineEntry], file: &str) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    format!("<ol>{}</ol>", nav_items(entries, file))
+}
+
+fn xhtml_page(title: &str, language: &str, body: &str) -> String {
+    let language = html_escape(language);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" \
+         xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"{language}\" xml:lang=\"{language}\">\n\
+         <head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n</head>\n\
+         <body>\n{body}\n</body>\n</html>\n",
+        html_escape(title)
+    )
+}
+
+fn container_xml() -> &'static str {
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+     <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+     <rootfiles>\n\
+     <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+     </rootfiles>\n</container>\n"
+}
+
+fn package_opf(
+    metadata: &EpubMetadata,
+    chapters: usize,
+    resources: &[Resource],
+    modified: &str,
+) -> String {
+    let mut opf = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <dc:identifier id=\"book-id\">{}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n\
+         <dc:language>{}</dc:language>\n",
+        html_escape(&metadata.identifier),
+        html_escape(&metadata.title),
+        html_escape(&metadata.language)
+    );
+    if let Some(author) = &metadata.author {
+        opf.push_str(&format!(
+            "<dc:creator>{}</dc:creator>\n",
+            html_escape(author)
+        ));
+    }
+    opf.push_str(&format!(
+        "<meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n<manifest>\n\
+         <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+        html_escape(modified)
+    ));
+    for index in 0..chapters {
+        opf.push_str(&format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            index + 1,
+            chapter_file(index)
+        ));
+    }
+    for (index, resource) in resources.iter().enumerate() {
+        opf.push_str(&format!(
+            "<item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            index + 1,
+            resource.href,
+            resource.media_type
+        ));
+    }
+    opf.push_str("</manifest>\n<spine>\n");
+    for index in 0..chapters {
+        opf.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", index + 1));
+    }
+    opf.push_str("</spine>\n</package>\n");
+    opf
+}
+
+/// Packs the chapters of a book into an EPUB 3 archive
+///
+/// Each chapter becomes an XHTML file rendered with the options' HTML
+/// options, titled after its metadata title or first heading. Local images
+/// with a PNG, JPEG, GIF, SVG or WebP extension are read from the image
+/// root and embedded once each, however many chapters use them; the
+/// others keep their URL. Images that can't be read or whose path leaves
+/// the image root keep their URL too, with a warning on stderr.
+pub fn write_epub(
+    chapters: &[Document],
+    metadata: &EpubMetadata,
+    options: &EpubOptions,
+) -> io::Result<Vec<u8>> {
+    let root = options.image_root.clone().unwrap_or_default();
+    let mut resources: Vec<Resource> = Vec::new();
+    let mut embedded: HashMap<String, String> = HashMap::new();
+    let mut skipped: HashSet<String> = HashSet::new();
+    let mut pages = Vec::with_capacity(chapters.len());
+    let mut nav = String::new();
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        for image in chapter.images() {
+            let url = image.url;
+            if url.is_empty()
+                || has_scheme(&url)
+                || embedded.contains_key(&url)
+                || skipped.contains(&url)
+            {
+                continue;
+            }
+            let Some(path) = image_path(&root, &url) else {
+                eprintln!("Warning: Not embedding image {url}: not a file inside the image root");
+                skipped.insert(url);
+                continue;
+            };
+            let Some(media_type) = path.to_str().and_then(image_media_type) else {
+                continue;
+            };
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    eprintln!("Warning: Not embedding image {}: {err}", path.display());
+                    skipped.insert(url);
+                    continue;
+                }
+            };
+            let extension = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let href = format!("images/image-{}.{extension}", resources.len() + 1);
+            embedded.insert(url, href.clone());
+            resources.push(Resource {
+                href,
+                media_type,
+                bytes,
+            });
+        }
+
+        let mut chapter = chapter.clone();
+        chapter.rewrite_links(|url| embedded.get(url).cloned());
+        let outline = Outline::new(&chapter);
+        let file = chapter_file(index);
+        let title = chapter
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.title.clone())
+            .or_else(|| outline.iter().next().map(|entry| entry.text.clone()))
+            .unwrap_or_else(|| format!("Chapter {}", index + 1));
+        if outline.entries.len() == 1 {
+            // A chapter under a single top-level heading is listed by it
+            nav.push_str(&nav_items(&outline.entries, &file));
+        } else {
+            nav.push_str(&format!(
+                "<li><a href=\"{file}\">{}</a>{}</li>",
+                html_escape(&title),
+                nav_list(&outline.entries, &file)
+            ));
+        }
+
+        let body = to_xhtml(&chapter.to_html_with(&options.html).html);
+        pages.push(xhtml_page(&title, &metadata.language, &body));
+    }
+
+    let modified = metadata.modified.clone().unwrap_or_else(|| {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        utc_timestamp(seconds)
+    });
+    let nav_page = xhtml_page(
+        &metadata.title,
+        &metadata.language,
+        &format!("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>{nav}</ol>\n</nav>"),
+    );
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // The mimetype must come first and uncompressed
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(container_xml().as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package_opf(metadata, chapters.len(), &resources, &modified).as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav_page.as_bytes())?;
+    for (index, page) in pages.iter().enumerate() {
+        zip.start_file(format!("OEBPS/{}", chapter_file(index)), deflated)?;
+        zip.write_all(page.as_bytes())?;
+    }
+    for resource in &resources {
+        zip.start_file(format!("OEBPS/{}", resource.href), deflated)?;
+        zip.write_all(&resource.bytes)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut text = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn test_write_epub() {
+        let dir = std::env::temp_dir().join(format!("md-core-epub-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("img")).unwrap();
+        std::fs::write(dir.join("img/cover.png"), b"png").unwrap();
+
+        let chapters = [
+            Document::try_from(Text::<Markdown>::new(
+                "# Beginnings\n\n![Cover](img/cover.png)\n\n## Setup\n\nText.  \nMore.\n\n---\n",
+            ))
+            .unwrap(),
+            Document::try_from(Text::<Markdown>::new(
+                "Untitled start with ![again](img/cover.png) and ![remote](https://example.com/a.png).\n\n\
+                 - [x] Done\n",
+            ))
+            .unwrap(),
+        ];
+        let metadata = EpubMetadata::new("A & B", "urn:uuid:1234")
+            .with_author("Ada")
+            .with_modified("2024-01-02T03:04:05Z");
+        let options = EpubOptions {
+            image_root: Some(dir.clone()),
+            ..Default::default()
+        };
+        let bytes = write_epub(&chapters, &metadata, &options).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let first = archive.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        drop(first);
+
+        let opf = read(&mut archive, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>A &amp; B</dc:title>"));
+        assert!(opf.contains("<dc:creator>Ada</dc:creator>"));
+        assert!(opf.contains("<meta property=\"dcterms:modified\">2024-01-02T03:04:05Z</meta>"));
+        assert_eq!(opf.matches("media-type=\"image/png\"").count(), 1);
+        assert!(opf.contains("<itemref idref=\"chapter-2\"/>"));
+
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains(
+            "<ol><li><a href=\"chapter-1.xhtml#beginnings\">Beginnings</a>\
+             <ol><li><a href=\"chapter-1.xhtml#setup\">Setup</a></li></ol></li>\
+             <li><a href=\"chapter-2.xhtml\">Chapter 2</a></li></ol>"
+        ));
+
+        let first = read(&mut archive, "OEBPS/chapter-1.xhtml");
+        assert!(first.contains("src=\"images/image-1.png\""));
+        assert!(first.contains("<br />") && first.contains("<hr />"));
+        let second = read(&mut archive, "OEBPS/chapter-2.xhtml");
+        assert!(second.contains("src=\"images/image-1.png\""));
+        assert!(second.contains("src=\"https://example.com/a.png\""));
+        assert!(second.contains("checked=\"checked\""));
+        assert_eq!(read(&mut archive, "OEBPS/images/image-1.png"), "png");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_image_paths() {
+        let root = Path::new("book");
+        assert_eq!(
+            image_path(root, "img/my%20cover.png?v=2#top"),
+            Some(root.join("img/my cover.png"))
+        );
+        assert_eq!(
+            image_path(root, "./cover.png"),
+            Some(root.join("cover.png"))
+        );
+        assert_eq!(image_path(root, "../secret.png"), None);
+        assert_eq!(image_path(root, "img/%2e%2e/%2e%2e/secret.png"), None);
+        assert_eq!(image_path(root, "/etc/secret.png"), None);
+        assert_eq!(image_path(root, "%2Fetc/secret.png"), None);
+        assert_eq!(image_path(root, "bad%zz.png"), None);
+        assert_eq!(image_path(root, "#cover"), None);
+    }
+
+    #[test]
+    fn test_unresolvable_images_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("md-core-epub-skip-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("book/img")).unwrap();
+        std::fs::write(dir.join("book/img/a b.png"), b"png").unwrap();
+        std::fs::write(dir.join("secret.png"), b"secret").unwrap();
+
+        let markdown = "![ok](img/a%20b.png?v=1)\n\n![up](../secret.png)\n\n\
+                        ![absolute](/etc/passwd.png)\n\n![missing](nope.png#frag)\n";
+        let chapters = [Document::try_from(Text::<Markdown>::new(markdown)).unwrap()];
+        let options = EpubOptions {
+            image_root: Some(dir.join("book")),
+            ..Default::default()
+        };
+        let metadata = EpubMetadata::new("Book", "urn:uuid:5678");
+        let bytes = write_epub(&chapters, &metadata, &options).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let page = read(&mut archive, "OEBPS/chapter-1.xhtml");
+        assert!(page.contains("src=\"images/image-1.png\""));
+        assert!(page.contains("src=\"../secret.png\""));
+        assert!(page.contains("src=\"/etc/passwd.png\""));
+        assert!(page.contains("src=\"nope.png#frag\""));
+        assert_eq!(read(&mut archive, "OEBPS/images/image-1.png"), "png");
+        assert_eq!(
+            archive
+                .file_names()
+                .filter(|name| name.contains("images/"))
+                .count(),
+            1
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
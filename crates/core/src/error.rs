@@ -1,4 +1,6 @@
+use serde::Serialize;
 use std::fmt;
+use std::ops::Range;
 use thiserror::Error;
 /// Represents errors that can occur during parsing or serialization
 #[derive(Debug, Error)]
@@ -13,9 +15,10 @@ pub enum ParseError {
     Generic(String),
 }
 
-/// Represents errors that can occur during document editing operations
-#[derive(Debug, Error)]
-pub enum EditError {
+/// The kind of an [`EditError`], for callers that handle errors programmatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditErrorKind {
     /// The index is out of bounds
     IndexOutOfBounds,
     /// The operation is not supported for the given node type
@@ -26,22 +29,192 @@ pub enum EditError {
     InvalidNode,
     /// The operation could not be completed successfully
     OperationFailed,
-    /// Other error with a message
-    Other(String),
+    /// The document diverged from the base of a change set
+    Conflict,
+    /// A link or image URL uses a scheme that isn't allowed
+    InvalidUrl,
+    /// The operation would move or remove a pinned node
+    Pinned,
+    /// Any other failure, described by the message
+    Other,
+}
+
+/// Represents errors that can occur during document editing operations
+///
+/// Besides its [`kind`](Self::kind), an error carries whatever context the
+/// failing code knew: the path of the offending node, the range that was
+/// valid and the one that was requested, and the command that failed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct EditError {
+    kind: EditErrorKind,
+    message: Option<String>,
+    path: Option<Vec<usize>>,
+    expected: Option<Range<usize>>,
+    actual: Option<Range<usize>>,
+    command: Option<&'static str>,
+}
+
+impl From<EditErrorKind> for EditError {
+    fn from(kind: EditErrorKind) -> Self {
+        Self {
+            kind,
+            message: None,
+            path: None,
+            expected: None,
+            actual: None,
+            command: None,
+        }
+    }
+}
+
+impl EditError {
+    /// An index, usually of a node, that does not exist
+    pub fn index_out_of_bounds() -> Self {
+        EditErrorKind::IndexOutOfBounds.into()
+    }
+
+    /// An operation the node type does not support
+    pub fn unsupported() -> Self {
+        EditErrorKind::UnsupportedOperation.into()
+    }
+
+    /// An empty, reversed or out-of-bounds range
+    pub fn invalid_range() -> Self {
+        EditErrorKind::InvalidRange.into()
+    }
+
+    /// An operation attempted on a node that cannot take it
+    pub fn invalid_node() -> Self {
+        EditErrorKind::InvalidNode.into()
+    }
+
+    /// An operation that could not be completed
+    pub fn operation_failed() -> Self {
+        EditErrorKind::OperationFailed.into()
+    }
+
+    /// A document that diverged from a change set at the given node
+    pub fn conflict(node_index: usize) -> Self {
+        Self::from(EditErrorKind::Conflict).at_node(node_index)
+    }
+
+    /// A link or image URL with a disallowed scheme
+    pub fn invalid_url() -> Self {
+        EditErrorKind::InvalidUrl.into()
+    }
+
+    /// An operation that would move or remove a pinned node
+    pub fn pinned() -> Self {
+        EditErrorKind::Pinned.into()
+    }
+
+    /// Any other failure
+    pub fn other(message: impl Into<String>) -> Self {
+        Self::from(EditErrorKind::Other).with_message(message)
+    }
+
+    /// Sets the path of the offending node
+    pub fn at_path(mut self, path: Vec<usize>) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Sets the path to the top-level node at `node_index`
+    pub fn at_node(self, node_index: usize) -> Self {
+        self.at_path(vec![node_index])
+    }
+
+    /// Sets the range that was valid and the one that was requested
+    pub fn with_ranges(mut self, expected: Range<usize>, actual: Range<usize>) -> Self {
+        self.expected = Some(expected);
+        self.actual = Some(actual);
+        self
+    }
+
+    /// Sets a description of the failure
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Sets the name of the failing command, unless already set
+    pub fn in_command(mut self, command: &'static str) -> Self {
+        self.command.get_or_insert(command);
+        self
+    }
+
+    /// The kind of error
+    pub fn kind(&self) -> EditErrorKind {
+        self.kind
+    }
+
+    /// Description of the failure, if any
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Path of the offending node: its top-level index followed by child
+    /// indices, if known
+    pub fn path(&self) -> Option<&[usize]> {
+        self.path.as_deref()
+    }
+
+    /// Index of the top-level node the error concerns, if known
+    pub fn node_index(&self) -> Option<usize> {
+        self.path.as_ref().and_then(|path| path.first().copied())
+    }
+
+    /// The range of valid values, if known
+    pub fn expected(&self) -> Option<&Range<usize>> {
+        self.expected.as_ref()
+    }
+
+    /// The requested range that fell outside the valid one, if known
+    pub fn actual(&self) -> Option<&Range<usize>> {
+        self.actual.as_ref()
+    }
+
+    /// Name of the command that failed, if known
+    pub fn command(&self) -> Option<&'static str> {
+        self.command
+    }
 }
 
 impl fmt::Display for EditError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            EditError::IndexOutOfBounds => write!(f, "Index out of bounds"),
-            EditError::UnsupportedOperation => {
-                write!(f, "Operation not supported for this node type")
+        match (self.kind, &self.message) {
+            (_, Some(message)) => write!(f, "{}", message)?,
+            (EditErrorKind::IndexOutOfBounds, None) => write!(f, "Index out of bounds")?,
+            (EditErrorKind::UnsupportedOperation, None) => {
+                write!(f, "Operation not supported for this node type")?
             }
-            EditError::InvalidRange => write!(f, "Invalid range provided"),
-            EditError::InvalidNode => write!(f, "Operation attempted on invalid node"),
-            EditError::OperationFailed => write!(f, "Operation failed to complete"),
-            EditError::Other(msg) => write!(f, "{}", msg),
+            (EditErrorKind::InvalidRange, None) => write!(f, "Invalid range provided")?,
+            (EditErrorKind::InvalidNode, None) => write!(f, "Operation attempted on invalid node")?,
+            (EditErrorKind::OperationFailed, None) => write!(f, "Operation failed to complete")?,
+            (EditErrorKind::Conflict, None) => {
+                write!(f, "Document has diverged from the change set")?
+            }
+            (EditErrorKind::InvalidUrl, None) => write!(f, "URL scheme not allowed")?,
+            (EditErrorKind::Pinned, None) => write!(f, "Operation would move a pinned node")?,
+            (EditErrorKind::Other, None) => write!(f, "Edit failed")?,
+        }
+        if let Some(path) = &self.path {
+            match path.as_slice() {
+                [index] => write!(f, " at node {}", index)?,
+                path => write!(f, " at node path {:?}", path)?,
+            }
+        }
+        if let (Some(expected), Some(actual)) = (&self.expected, &self.actual) {
+            write!(
+                f,
+                " (requested {}..{}, valid {}..{})",
+                actual.start, actual.end, expected.start, expected.end
+            )?;
+        }
+        if let Some(command) = self.command {
+            write!(f, " in {}", command)?;
         }
+        Ok(())
     }
 }
 
@@ -0,0 +1,147 @@
+//! Folded sections and code blocks
+//!
+//! Fold state lives on the nodes themselves, as the `collapsed` flag of
+//! [`HeadingProperties`](crate::HeadingProperties) and
+//! [`CodeBlockProperties`](crate::CodeBlockProperties), so it is saved with
+//! the document in JSON and moves with its node through edits. A folded
+//! heading hides its section up to the next heading of the same or a
+//! higher level; how a folded code block is shown is up to the UI.
+//! [`Editor::fold`](crate::Editor::fold) sets the same flag.
+
+use crate::{Document, EditError, Node};
+use std::ops::Range;
+
+/// The fold flag of a heading or code block
+fn collapsed(node: &Node) -> Option<bool> {
+    match node {
+        Node::Heading { properties, .. } => Some(properties.collapsed),
+        Node::CodeBlock { properties, .. } => Some(properties.collapsed),
+        _ => None,
+    }
+}
+
+pub(crate) fn collapsed_mut(node: &mut Node) -> Option<&mut bool> {
+    match node {
+        Node::Heading { properties, .. } => Some(&mut properties.collapsed),
+        Node::CodeBlock { properties, .. } => Some(&mut properties.collapsed),
+        _ => None,
+    }
+}
+
+impl Document {
+    /// Sets the fold flag of the heading or code block at `node_index`,
+    /// returning the previous value
+    fn set_collapsed(&mut self, node_index: usize, value: bool) -> Result<bool, EditError> {
+        let node = self
+            .nodes
+            .get_mut(node_index)
+            .ok_or_else(|| EditError::index_out_of_bounds().at_node(node_index))?;
+        let flag = collapsed_mut(node).ok_or_else(|| {
+            EditError::invalid_node()
+                .at_node(node_index)
+                .with_message("Only headings and code blocks can be folded")
+        })?;
+        Ok(std::mem::replace(flag, value))
+    }
+
+    /// Folds the section the heading at `node_index` leads, or the code
+    /// block at `node_index`
+    ///
+    /// Fails with [`EditErrorKind::InvalidNode`](crate::EditErrorKind::InvalidNode)
+    /// for other nodes.
+    pub fn fold_section(&mut self, node_index: usize) -> Result<(), EditError> {
+        self.set_collapsed(node_index, true).map(|_| ())
+    }
+
+    /// Unfolds the heading or code block at `node_index`, returning whether
+    /// it was folded
+    pub fn unfold_section(&mut self, node_index: usize) -> Result<bool, EditError> {
+        self.set_collapsed(node_index, false)
+    }
+
+    /// Returns true if the top-level node is a folded heading or code block
+    pub fn is_section_folded(&self, node_index: usize) -> bool {
+        self.nodes
+            .get(node_index)
+            .and_then(collapsed)
+            .unwrap_or(false)
+    }
+
+    /// Indices of the folded top-level headings and code blocks, ascending
+    pub fn folded_sections(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|&index| self.is_section_folded(index))
+            .collect()
+    }
+
+    /// Unfolds every heading and code block, returning how many were folded
+    pub fn unfold_all(&mut self) -> usize {
+        self.nodes
+            .iter_mut()
+            .filter_map(collapsed_mut)
+            .map(|flag| std::mem::replace(flag, false))
+            .filter(|&was_folded| was_folded)
+            .count()
+    }
+
+    /// Ranges of the top-level nodes folded headings hide, ascending and
+    /// disjoint
+    ///
+    /// A folded heading hides its section after the heading itself; folds
+    /// inside a hidden range don't add to it.
+    pub fn hidden_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for index in self.folded_sections() {
+            if ranges.last().is_some_and(|last| index < last.end) {
+                continue;
+            }
+            if let Some(section) = self.section_range(index)
+                && section.len() > 1
+            {
+                ranges.push(index + 1..section.end);
+            }
+        }
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EditErrorKind, HtmlOptions, HtmlSectionMode, Json, Markdown, Text};
+
+    #[test]
+    fn test_fold_sections() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "# A\n\nText\n\n## B\n\n```rust\nfn main() {}\n```\n\n# C\n\nMore\n",
+        ))
+        .unwrap();
+        doc.fold_section(2).unwrap();
+        doc.fold_section(3).unwrap();
+        assert_eq!(doc.folded_sections(), vec![2, 3]);
+        assert_eq!(doc.hidden_ranges(), vec![3..4]);
+        doc.fold_section(0).unwrap();
+        assert_eq!(doc.hidden_ranges(), vec![1..4]);
+
+        let json = Text::<Json>::try_from(&doc).unwrap();
+        assert!(json.as_str().contains("\"collapsed\": true"));
+        let restored = Document::try_from(json).unwrap();
+        assert_eq!(restored.folded_sections(), vec![0, 2, 3]);
+        let options = HtmlOptions::new().with_sections(HtmlSectionMode::Details);
+        let html = doc.to_html_with(&options).html;
+        assert!(html.starts_with("<details><summary><h1 id=\"a\">A</h1></summary>"));
+        assert!(html.contains("<details open><summary><h1 id=\"c\">"));
+
+        assert!(doc.unfold_section(0).unwrap());
+        assert!(!doc.unfold_section(0).unwrap());
+        assert_eq!(doc.unfold_all(), 2);
+        assert!(doc.hidden_ranges().is_empty());
+        let json = Text::<Json>::try_from(&doc).unwrap();
+        assert!(!json.as_str().contains("collapsed"));
+
+        let err = doc.fold_section(1).unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::InvalidNode);
+        let err = doc.fold_section(9).unwrap_err();
+        assert_eq!(err.kind(), EditErrorKind::IndexOutOfBounds);
+    }
+}
@@ -0,0 +1,150 @@
+//! Footnote previews and navigation
+//!
+//! Frontends show a footnote's text when the reader hovers its reference and
+//! offer links from the reference to the definition and back. A preview
+//! bundles what that needs: the definition rendered as HTML and as plain
+//! text, its path, the paths of the blocks referring to it, and the element
+//! ids the HTML export gives both ends.
+
+use crate::convert::html::node_to_html;
+use crate::{Document, InlineNode, Node};
+use serde::Serialize;
+
+/// Everything needed to preview a footnote and navigate to and from it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FootnotePreview {
+    /// Label shared by the references and the definition
+    pub label: String,
+    /// Path to the definition: the top-level node index followed by the child
+    /// indices through any containers
+    pub path: Vec<usize>,
+    /// The definition's content rendered as HTML
+    pub html: String,
+    /// The definition's content as plain text
+    pub text: String,
+    /// Paths to the blocks referring to the footnote, in document order
+    pub references: Vec<Vec<usize>>,
+    /// Id of the definition element in the HTML export
+    pub anchor: String,
+    /// Id of the reference element in the HTML export
+    pub back_anchor: String,
+}
+
+impl FootnotePreview {
+    /// Index of the top-level node containing the definition
+    pub fn node_index(&self) -> usize {
+        self.path[0]
+    }
+}
+
+/// Returns true if the block itself, not a nested one, refers to `label`
+fn refers_to(node: &Node, label: &str) -> bool {
+    if let Node::FootnoteReference(reference) = node {
+        return reference.label == label;
+    }
+    let mut found = false;
+    for inline in node.inline_runs().into_iter().flatten() {
+        inline.walk(&mut |inline| {
+            if let InlineNode::FootnoteRef { label: used } = inline {
+                found |= used == label;
+            }
+        });
+    }
+    found
+}
+
+impl Document {
+    /// Builds the preview of the footnote with the given label
+    ///
+    /// Returns `None` if no definition carries the label.
+    pub fn footnote_preview(&self, label: &str) -> Option<FootnotePreview> {
+        let mut definition = None;
+        let mut references = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            node.walk_with_path(&mut vec![index], &mut |block, path| {
+                if let Node::FootnoteDefinition(found) = block
+                    && found.label == label
+                    && definition.is_none()
+                {
+                    definition = Some((path.to_vec(), found.clone()));
+                }
+                if refers_to(block, label) {
+                    references.push(path.to_vec());
+                }
+            });
+        }
+
+        let (path, definition) = definition?;
+        let html = definition
+            .content
+            .iter()
+            .filter_map(|node| node_to_html(node, 0).ok())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text = definition
+            .content
+            .iter()
+            .map(Node::plain_text)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Some(FootnotePreview {
+            label: label.to_string(),
+            path,
+            html,
+            text,
+            references,
+            anchor: format!("fn-{label}"),
+            back_anchor: format!("fnref-{label}"),
+        })
+    }
+
+    /// Resolves a footnote reference to the preview of its definition
+    ///
+    /// Accepts the [`InlineNode::FootnoteRef`] a frontend finds under the
+    /// pointer; any other inline node, or a dangling reference, gives `None`.
+    pub fn resolve_footnote(&self, reference: &InlineNode) -> Option<FootnotePreview> {
+        match reference {
+            InlineNode::FootnoteRef { label } => self.footnote_preview(label),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FootnoteDefinition;
+
+    #[test]
+    fn test_footnote_preview() {
+        let reference = InlineNode::FootnoteRef {
+            label: "note".to_string(),
+        };
+        let mut doc = Document::new();
+        doc.add_paragraph_with_inlines(vec![InlineNode::text("Claim"), reference.clone()]);
+        doc.nodes.push(Node::BlockQuote {
+            children: vec![Node::paragraph_with_inlines(vec![
+                InlineNode::text("Again"),
+                reference.clone(),
+            ])],
+        });
+        doc.nodes
+            .push(Node::FootnoteDefinition(FootnoteDefinition::new(
+                "note",
+                vec![Node::paragraph("See *the* source.")],
+            )));
+
+        let preview = doc.resolve_footnote(&reference).unwrap();
+        assert_eq!(preview.path, vec![2]);
+        assert_eq!(preview.node_index(), 2);
+        assert_eq!(preview.text, "See *the* source.");
+        assert_eq!(preview.html, "<p>See *the* source.</p>");
+        assert_eq!(preview.references, vec![vec![0], vec![1, 0]]);
+        assert_eq!(preview.anchor, "fn-note");
+        assert_eq!(preview.back_anchor, "fnref-note");
+
+        assert!(doc.footnote_preview("missing").is_none());
+        assert!(doc.resolve_footnote(&InlineNode::text("note")).is_none());
+    }
+}
@@ -0,0 +1,383 @@
+//! Partial content: selections, clipboard data and extracted blocks
+//!
+//! [`Document::get_selected_text`] flattens a selection to a string, which is
+//! all a plain-text clipboard can hold. [`Document::selected_fragment`] keeps
+//! the structure instead: the selected inline content when the selection
+//! stays within one paragraph or heading, or the selected blocks with the
+//! first and last trimmed to the selection. Fragments convert to and from
+//! Markdown, HTML and JSON, so copy operations can offer every clipboard
+//! format at once and paste can read any of them back, and the editor
+//! inserts them with [`Editor::insert_fragment`] and
+//! [`Editor::paste_fragment`].
+//!
+//! [`Editor::insert_fragment`]: crate::Editor::insert_fragment
+//! [`Editor::paste_fragment`]: crate::Editor::paste_fragment
+
+use crate::convert::html::inlines_to_html;
+use crate::{
+    Document, HtmlOptions, InlineNode, Markdown, MarkdownOptions, Node, ParseError, Position, Text,
+    TextNode,
+};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A piece of a document, such as the content of a selection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "content", rename_all = "snake_case")]
+pub enum DocumentFragment {
+    /// Inline content from within a single paragraph or heading
+    Inline(Vec<InlineNode>),
+    /// Whole blocks, where the first and last may be trimmed
+    Blocks(Vec<Node>),
+}
+
+impl DocumentFragment {
+    /// Returns true if the fragment holds no content
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Inline(inlines) => inlines.is_empty(),
+            Self::Blocks(nodes) => nodes.is_empty(),
+        }
+    }
+
+    /// The fragment as blocks, with inline content wrapped in a paragraph
+    pub fn into_nodes(self) -> Vec<Node> {
+        match self {
+            Self::Inline(inlines) if inlines.is_empty() => Vec::new(),
+            Self::Inline(inlines) => vec![Node::Paragraph { children: inlines }],
+            Self::Blocks(nodes) => nodes,
+        }
+    }
+
+    /// Plain text of the fragment, with blocks separated by blank lines
+    pub fn plain_text(&self) -> String {
+        match self {
+            Self::Inline(inlines) => InlineNode::plain_text_of(inlines),
+            Self::Blocks(nodes) => nodes
+                .iter()
+                .map(Node::plain_text)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
+
+    /// Number of offsets the fragment takes up once inserted into a
+    /// paragraph, counting text by bytes and other inlines as one
+    ///
+    /// Block fragments have no inline length and give 0.
+    pub fn inline_len(&self) -> usize {
+        match self {
+            Self::Inline(inlines) => inlines
+                .iter()
+                .map(|inline| match inline {
+                    InlineNode::Text(text) => text.text.len(),
+                    _ => 1,
+                })
+                .sum(),
+            Self::Blocks(_) => 0,
+        }
+    }
+
+    /// Parses a fragment from Markdown
+    ///
+    /// A single paragraph without a trailing newline is read as inline
+    /// content, the way [`to_markdown`](Self::to_markdown) writes inline
+    /// fragments; anything else gives blocks.
+    pub fn from_markdown(markdown: &str) -> Result<Self, ParseError> {
+        let document = Document::try_from(Text::<Markdown>::new(markdown))?;
+        Ok(Self::from_nodes(document.nodes, !markdown.ends_with('\n')))
+    }
+
+    /// Parses a fragment from HTML with the default options
+    pub fn from_html(html: &str) -> Result<Self, ParseError> {
+        Self::from_html_with(html, &HtmlOptions::default())
+    }
+
+    /// Parses a fragment from HTML
+    ///
+    /// Content that doesn't start with a block element and parses to a
+    /// single paragraph is read as inline content, the way
+    /// [`to_html`](Self::to_html) writes inline fragments.
+    pub fn from_html_with(html: &str, options: &HtmlOptions) -> Result<Self, ParseError> {
+        let document = Document::from_html_with(html, options)?;
+        Ok(Self::from_nodes(
+            document.nodes,
+            !html.trim_start().starts_with("<p"),
+        ))
+    }
+
+    /// Converts the fragment to JSON
+    pub fn to_json(&self) -> Result<String, ParseError> {
+        serde_json::to_string_pretty(self).map_err(|e| ParseError::Json(e.to_string()))
+    }
+
+    /// Parses a fragment from the JSON [`to_json`](Self::to_json) writes
+    pub fn from_json(json: &str) -> Result<Self, ParseError> {
+        serde_json::from_str(json).map_err(|e| ParseError::Json(e.to_string()))
+    }
+
+    /// Blocks parsed from a format, unwrapping a lone paragraph if `inline`
+    fn from_nodes(mut nodes: Vec<Node>, inline: bool) -> Self {
+        match nodes.as_mut_slice() {
+            [Node::Paragraph { children }] if inline => Self::Inline(std::mem::take(children)),
+            [] if inline => Self::Inline(Vec::new()),
+            _ => Self::Blocks(nodes),
+        }
+    }
+
+    /// Converts the fragment to Markdown with the default options
+    pub fn to_markdown(&self) -> String {
+        self.to_markdown_with(&MarkdownOptions::default())
+    }
+
+    /// Converts the fragment to Markdown
+    ///
+    /// Inline fragments are written without a trailing newline, so they can
+    /// be pasted into the middle of a line, and block fragments always end
+    /// with one.
+    pub fn to_markdown_with(&self, options: &MarkdownOptions) -> String {
+        let mut document = Document::new();
+        document.nodes = self.clone().into_nodes();
+        let markdown = document.to_markdown_with(options);
+        match self {
+            Self::Inline(_) => markdown.trim_end_matches('\n').to_string(),
+            Self::Blocks(_) if markdown.is_empty() || markdown.ends_with('\n') => markdown,
+            Self::Blocks(_) => markdown + "\n",
+        }
+    }
+
+    /// Converts the fragment to HTML with the default options
+    pub fn to_html(&self) -> String {
+        self.to_html_with(&HtmlOptions::default())
+    }
+
+    /// Converts the fragment to HTML
+    ///
+    /// Inline fragments are written without a surrounding `<p>`.
+    pub fn to_html_with(&self, options: &HtmlOptions) -> String {
+        match self {
+            Self::Inline(inlines) => inlines_to_html(inlines, options),
+            Self::Blocks(nodes) => {
+                let mut document = Document::new();
+                document.nodes = nodes.clone();
+                document.to_html_with(options).html
+            }
+        }
+    }
+}
+
+impl From<Vec<Node>> for DocumentFragment {
+    fn from(nodes: Vec<Node>) -> Self {
+        Self::Blocks(nodes)
+    }
+}
+
+impl From<Vec<InlineNode>> for DocumentFragment {
+    fn from(inlines: Vec<InlineNode>) -> Self {
+        Self::Inline(inlines)
+    }
+}
+
+/// Inline content between two offsets, splitting text runs at the edges
+///
+/// Other inlines take one offset and are kept only if wholly selected.
+fn slice_inlines(inlines: &[InlineNode], start: usize, end: usize) -> Vec<InlineNode> {
+    let mut sliced = Vec::new();
+    let mut offset = 0;
+    for inline in inlines {
+        let len = match inline {
+            InlineNode::Text(text) => text.text.len(),
+            _ => 1,
+        };
+        let next = offset + len;
+        match inline {
+            InlineNode::Text(text) if offset < end && next > start => {
+                let from = start.saturating_sub(offset);
+                let to = end.min(next) - offset;
+                if let Some(part) = text.text.get(from..to).filter(|part| !part.is_empty()) {
+                    sliced.push(InlineNode::Text(TextNode::with_formatting(
+                        part,
+                        text.formatting.clone(),
+                    )));
+                }
+            }
+            InlineNode::Text(_) => {}
+            _ if offset >= start && next <= end => sliced.push(inline.clone()),
+            _ => {}
+        }
+        offset = next;
+    }
+    sliced
+}
+
+/// A block trimmed to the part between two offsets
+///
+/// Only paragraphs, headings and code blocks are trimmed; other blocks are
+/// kept whole.
+pub(crate) fn slice_node(node: &Node, start: usize, end: usize) -> Node {
+    let mut node = node.clone();
+    match &mut node {
+        Node::Paragraph { children } | Node::Heading { children, .. } => {
+            *children = slice_inlines(children, start, end);
+        }
+        Node::CodeBlock { code, .. } => {
+            let end = end.min(code.len());
+            *code = code
+                .get(start.min(end)..end)
+                .unwrap_or_default()
+                .to_string();
+        }
+        _ => {}
+    }
+    node
+}
+
+impl Document {
+    /// Copies the top-level nodes in `range` as a block fragment, for
+    /// extracting or transcluding part of the document
+    ///
+    /// The range is clamped to the document.
+    pub fn fragment(&self, range: Range<usize>) -> DocumentFragment {
+        let end = range.end.min(self.nodes.len());
+        let start = range.start.min(end);
+        DocumentFragment::Blocks(self.nodes[start..end].to_vec())
+    }
+
+    /// Returns the selected content with its formatting and structure
+    ///
+    /// A selection within one paragraph or heading gives an
+    /// [`Inline`](DocumentFragment::Inline) fragment; any other gives the
+    /// selected blocks, with the first and last trimmed to the selection.
+    /// Selections made backwards give the same content as forward ones.
+    /// Returns `None` without a selection or when it points past the end
+    /// of the document.
+    pub fn selected_fragment(&self) -> Option<DocumentFragment> {
+        let selection = self.selection.as_ref()?;
+        let key = |position: &Position| (position.path[0], position.offset);
+        let (start, end) = if key(&selection.end) < key(&selection.start) {
+            (&selection.end, &selection.start)
+        } else {
+            (&selection.start, &selection.end)
+        };
+        let (first, last) = (start.path[0], end.path[0]);
+        if last >= self.nodes.len() {
+            return None;
+        }
+
+        if first == last {
+            return Some(match &self.nodes[first] {
+                Node::Paragraph { children } | Node::Heading { children, .. } => {
+                    DocumentFragment::Inline(slice_inlines(children, start.offset, end.offset))
+                }
+                node => DocumentFragment::Blocks(vec![slice_node(node, start.offset, end.offset)]),
+            });
+        }
+
+        let mut nodes = Vec::with_capacity(last - first + 1);
+        nodes.push(slice_node(&self.nodes[first], start.offset, usize::MAX));
+        nodes.extend(self.nodes[first + 1..last].iter().cloned());
+        nodes.push(slice_node(&self.nodes[last], 0, end.offset));
+        Some(DocumentFragment::Blocks(nodes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn parse(markdown: &str) -> Document {
+        Document::try_from(Text::<Markdown>::new(markdown)).unwrap()
+    }
+
+    #[test]
+    fn test_selected_fragment_inline() {
+        let mut doc = parse("Some **bold** and [a link](https://example.com) here\n");
+        // "me **bold** and <link> h", selected backwards
+        assert!(doc.select_range(0, 17, 0, 2));
+        let fragment = doc.selected_fragment().unwrap();
+        assert_eq!(
+            fragment,
+            DocumentFragment::Inline(vec![
+                InlineNode::text("me "),
+                InlineNode::bold_text("bold"),
+                InlineNode::text(" and "),
+                InlineNode::link("https://example.com", "a link"),
+                InlineNode::text(" h"),
+            ])
+        );
+        assert_eq!(
+            fragment.to_markdown(),
+            "me **bold** and [a link](https://example.com) h"
+        );
+        assert_eq!(
+            fragment.to_html(),
+            "me <strong>bold</strong> and <a href=\"https://example.com\">a link</a> h"
+        );
+        assert_eq!(fragment.plain_text(), "me bold and a link h");
+    }
+
+    #[test]
+    fn test_selected_fragment_blocks() {
+        let mut doc = parse("# Intro text\n\nMiddle\n\n```rust\nlet a = 1;\nlet b = 2;\n```\n");
+        assert!(doc.select_range(0, 6, 2, 10));
+        let fragment = doc.selected_fragment().unwrap();
+        let DocumentFragment::Blocks(nodes) = &fragment else {
+            panic!("expected blocks");
+        };
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0].plain_text(), "text");
+        assert!(matches!(nodes[0], Node::Heading { level: 1, .. }));
+        assert_eq!(nodes[1], Node::paragraph("Middle"));
+        assert!(matches!(&nodes[2], Node::CodeBlock { code, .. } if code == "let a = 1;"));
+        assert!(
+            fragment
+                .to_markdown()
+                .starts_with("# text\n\nMiddle\n\n```rust\n")
+        );
+        assert!(fragment.to_html().contains("<p>Middle</p>"));
+
+        doc.selection = None;
+        assert_eq!(doc.selected_fragment(), None);
+    }
+
+    #[test]
+    fn test_fragment_conversions() {
+        let inline = DocumentFragment::Inline(vec![
+            InlineNode::text("me "),
+            InlineNode::bold_text("bold"),
+            InlineNode::text(" and "),
+            InlineNode::link("https://example.com", "link"),
+        ]);
+        assert_eq!(inline.inline_len(), 13);
+        for fragment in [
+            inline.clone(),
+            parse("# Title\n\nText\n").fragment(0..5),
+            DocumentFragment::Blocks(vec![Node::paragraph("Alone")]),
+        ] {
+            assert_eq!(
+                DocumentFragment::from_markdown(&fragment.to_markdown()).unwrap(),
+                fragment
+            );
+            assert_eq!(
+                DocumentFragment::from_json(&fragment.to_json().unwrap()).unwrap(),
+                fragment
+            );
+        }
+        // HTML import spaces inline content a little differently
+        assert!(matches!(
+            DocumentFragment::from_html(&inline.to_html()).unwrap(),
+            DocumentFragment::Inline(inlines) if inlines.len() == 4
+        ));
+        let blocks = parse("# Title\n\nText\n").fragment(0..2);
+        assert_eq!(
+            DocumentFragment::from_html(&blocks.to_html()).unwrap(),
+            blocks
+        );
+        assert_eq!(
+            DocumentFragment::from_markdown("").unwrap(),
+            DocumentFragment::Inline(Vec::new())
+        );
+        assert!(DocumentFragment::from_json("[]").is_err());
+    }
+}
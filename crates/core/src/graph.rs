@@ -0,0 +1,325 @@
+//! Graph export of document structure
+//!
+//! [`Document::graph`] turns a document into nodes and edges: the heading
+//! hierarchy, `#anchor` links between sections, footnote references and
+//! links to other documents by relative path or wiki-link. The graph serializes to JSON
+//! and renders as Graphviz DOT with [`DocumentGraph::to_dot`], for note
+//! graphs and structure views in tooling.
+
+use crate::wiki_links;
+use crate::{Document, InlineNode, LinkKind, Node, OutlineEntry};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// What a graph node stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphNodeKind {
+    /// The document itself, the root of the heading hierarchy
+    Document,
+    /// A heading
+    Heading,
+    /// A footnote, defined or only referred to
+    Footnote,
+    /// Another document linked by relative path or wiki-link
+    LinkedDocument,
+}
+
+/// A node of a [`DocumentGraph`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphNode {
+    /// Identifier, unique within the graph: `root`, `h<index>` for the
+    /// heading at a top-level node index, `fn:<label>` or `doc:<path>`
+    pub id: String,
+    /// What the node stands for
+    pub kind: GraphNodeKind,
+    /// Text to show: the title, heading text, footnote label or path
+    pub label: String,
+    /// Index of the top-level heading or footnote definition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_index: Option<usize>,
+}
+
+/// What a graph edge stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphEdgeKind {
+    /// A heading's section contains the subsection
+    Section,
+    /// A section links to a heading or another document
+    Link,
+    /// A section refers to a footnote
+    Footnote,
+}
+
+/// An edge of a [`DocumentGraph`], from the section where something is
+/// found to what it points at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphEdge {
+    /// Id of the source node
+    pub from: String,
+    /// Id of the target node
+    pub to: String,
+    /// What the edge stands for
+    pub kind: GraphEdgeKind,
+}
+
+/// Structure of a document as a graph
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DocumentGraph {
+    /// Nodes, the root first and then in document order
+    pub nodes: Vec<GraphNode>,
+    /// Edges without duplicates, section edges first
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Escapes a string for a quoted DOT id or label
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl DocumentGraph {
+    fn add_node(&mut self, id: String, kind: GraphNodeKind, label: String, index: Option<usize>) {
+        if self.nodes.iter().all(|node| node.id != id) {
+            self.nodes.push(GraphNode {
+                id,
+                kind,
+                label,
+                node_index: index,
+            });
+        }
+    }
+
+    fn add_edge(&mut self, from: &str, to: String, kind: GraphEdgeKind) {
+        let edge = GraphEdge {
+            from: from.to_string(),
+            to,
+            kind,
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Adds an outline entry and its subsections below `parent`
+    fn add_section(&mut self, parent: &str, entry: &OutlineEntry) {
+        let id = format!("h{}", entry.node_index);
+        self.add_node(
+            id.clone(),
+            GraphNodeKind::Heading,
+            entry.text.clone(),
+            Some(entry.node_index),
+        );
+        self.add_edge(parent, id.clone(), GraphEdgeKind::Section);
+        for child in &entry.children {
+            self.add_section(&id, child);
+        }
+    }
+
+    /// Renders the graph in Graphviz DOT
+    ///
+    /// Sections are solid edges, links dashed and footnote references
+    /// dotted; footnotes and linked documents get their own shapes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph document {\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                GraphNodeKind::Document => "box",
+                GraphNodeKind::Heading => "ellipse",
+                GraphNodeKind::Footnote => "note",
+                GraphNodeKind::LinkedDocument => "component",
+            };
+            let _ = writeln!(
+                dot,
+                "  \"{}\" [label=\"{}\", shape={}];",
+                dot_escape(&node.id),
+                dot_escape(&node.label),
+                shape
+            );
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                GraphEdgeKind::Section => "",
+                GraphEdgeKind::Link => " [style=dashed]",
+                GraphEdgeKind::Footnote => " [style=dotted]",
+            };
+            let _ = writeln!(
+                dot,
+                "  \"{}\" -> \"{}\"{};",
+                dot_escape(&edge.from),
+                dot_escape(&edge.to),
+                style
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Document {
+    /// Builds the graph of the document's structure
+    ///
+    /// Headings hang below the root or their parent heading. Links and
+    /// footnote references start at the heading of the section they are in,
+    /// or the root before the first heading. Broken `#anchor` links and
+    /// links with a scheme are left out.
+    pub fn graph(&self) -> DocumentGraph {
+        let mut graph = DocumentGraph::default();
+        let title = self.metadata.as_ref().and_then(|m| m.title.clone());
+        graph.add_node(
+            "root".to_string(),
+            GraphNodeKind::Document,
+            title.unwrap_or_else(|| "Document".to_string()),
+            None,
+        );
+        for entry in &self.outline().entries {
+            graph.add_section("root", entry);
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let Node::FootnoteDefinition(definition) = node {
+                graph.add_node(
+                    format!("fn:{}", definition.label),
+                    GraphNodeKind::Footnote,
+                    format!("[^{}]", definition.label),
+                    Some(index),
+                );
+            }
+        }
+
+        // The section each top-level node is in
+        let mut section = "root".to_string();
+        let sections: Vec<String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                if matches!(node, Node::Heading { .. }) {
+                    section = format!("h{index}");
+                }
+                section.clone()
+            })
+            .collect();
+
+        for link in self.links().links {
+            if link.kind != LinkKind::Link {
+                continue;
+            }
+            let from = &sections[link.node_index];
+            if link.is_anchor() {
+                if let Some(target) = self.resolve_anchor(&link.url) {
+                    graph.add_edge(from, format!("h{target}"), GraphEdgeKind::Link);
+                }
+            } else if link.is_relative() {
+                let path = link.url.split(['#', '?']).next().unwrap_or_default();
+                if path.is_empty() {
+                    continue;
+                }
+                let id = format!("doc:{path}");
+                graph.add_node(
+                    id.clone(),
+                    GraphNodeKind::LinkedDocument,
+                    path.to_string(),
+                    None,
+                );
+                graph.add_edge(from, id, GraphEdgeKind::Link);
+            }
+        }
+
+        for link in self.wiki_links() {
+            let page = wiki_links::page_of(&link.target);
+            if page.is_empty() {
+                continue;
+            }
+            let id = format!("doc:{page}");
+            graph.add_node(
+                id.clone(),
+                GraphNodeKind::LinkedDocument,
+                page.to_string(),
+                None,
+            );
+            graph.add_edge(&sections[link.node_index], id, GraphEdgeKind::Link);
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut labels = Vec::new();
+            node.walk(&mut |block| {
+                if let Node::FootnoteReference(reference) = block {
+                    labels.push(reference.label.clone());
+                }
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        if let InlineNode::FootnoteRef { label } = inline {
+                            labels.push(label.clone());
+                        }
+                    });
+                }
+            });
+            for label in labels {
+                let id = format!("fn:{label}");
+                graph.add_node(
+                    id.clone(),
+                    GraphNodeKind::Footnote,
+                    format!("[^{label}]"),
+                    None,
+                );
+                graph.add_edge(&sections[index], id, GraphEdgeKind::Footnote);
+            }
+        }
+
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FootnoteDefinition, Markdown, Text};
+
+    #[test]
+    fn test_document_graph() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "Intro with a note[^1].\n\n\
+             # Guide\n\n\
+             See [usage](#usage) and [the API](api.md#types).\n\n\
+             ## Usage\n\n\
+             Back to the [guide](#guide), [nowhere](#nowhere) or [web](https://example.com).\n",
+        ))
+        .unwrap();
+        doc.nodes
+            .push(Node::FootnoteDefinition(FootnoteDefinition::new(
+                "1",
+                vec![Node::paragraph("A note.")],
+            )));
+        let graph = doc.graph();
+
+        let ids: Vec<&str> = graph.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, ["root", "h1", "h3", "fn:1", "doc:api.md"]);
+        assert_eq!(graph.nodes[3].node_index, Some(5));
+        let edges: Vec<(&str, &str, GraphEdgeKind)> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.from.as_str(), edge.to.as_str(), edge.kind))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("root", "h1", GraphEdgeKind::Section),
+                ("h1", "h3", GraphEdgeKind::Section),
+                ("h1", "h3", GraphEdgeKind::Link),
+                ("h1", "doc:api.md", GraphEdgeKind::Link),
+                ("h3", "h1", GraphEdgeKind::Link),
+                ("root", "fn:1", GraphEdgeKind::Footnote),
+            ]
+        );
+
+        let dot = graph.to_dot();
+        assert!(
+            dot.starts_with("digraph document {\n  \"root\" [label=\"Document\", shape=box];\n")
+        );
+        assert!(dot.contains("  \"h1\" -> \"doc:api.md\" [style=dashed];\n"));
+        assert!(dot.contains("  \"fn:1\" [label=\"[^1]\", shape=note];\n"));
+    }
+}
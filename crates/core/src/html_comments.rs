@@ -0,0 +1,139 @@
+//! HTML comments
+//!
+//! A `<!-- ... -->` standing alone in Markdown becomes a
+//! [`Node::HtmlComment`], and one inside text an
+//! [`InlineNode::HtmlComment`]. The text between the markers is kept
+//! verbatim, so comments survive round trips exactly. Comments are listed in
+//! reading order; the position of a comment in that list is its index, which
+//! the editor uses to remove comments.
+
+use crate::{Document, InlineNode, Node};
+use serde::Serialize;
+
+/// An HTML comment found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HtmlCommentInfo {
+    /// Position of the comment among all comments of the document
+    pub index: usize,
+    /// Index of the top-level node containing the comment
+    pub node_index: usize,
+    /// Text between `<!--` and `-->`
+    pub text: String,
+    /// Whether the comment is inside text rather than a block of its own
+    pub inline: bool,
+}
+
+/// Returns the text of `html` if it is exactly one comment
+pub(crate) fn comment_text(html: &str) -> Option<&str> {
+    let text = html.strip_prefix("<!--")?.strip_suffix("-->")?;
+    (!text.contains("-->")).then_some(text)
+}
+
+/// Removes the comments whose index is in `indices` from inline content,
+/// counting indices from `next`
+fn remove_inline_comments(inlines: &mut Vec<InlineNode>, next: &mut usize, indices: &[usize]) {
+    inlines.retain_mut(|inline| {
+        if let InlineNode::HtmlComment { .. } = inline {
+            *next += 1;
+            return !indices.contains(&(*next - 1));
+        }
+        if let InlineNode::Link { children, .. } | InlineNode::InlineFootnote { children } = inline
+        {
+            remove_inline_comments(children, next, indices);
+        }
+        true
+    });
+}
+
+/// Removes the comments whose index is in `indices` from blocks, counting
+/// indices from `next` in the order of [`Document::html_comments`]
+pub(crate) fn remove_html_comments(nodes: &mut Vec<Node>, next: &mut usize, indices: &[usize]) {
+    nodes.retain_mut(|node| {
+        if let Node::HtmlComment { .. } = node {
+            *next += 1;
+            return !indices.contains(&(*next - 1));
+        }
+        for run in node.inline_runs_mut() {
+            remove_inline_comments(run, next, indices);
+        }
+        for list in node.block_lists_mut() {
+            remove_html_comments(list, next, indices);
+        }
+        true
+    });
+}
+
+impl Document {
+    /// Lists every HTML comment of the document in reading order
+    pub fn html_comments(&self) -> Vec<HtmlCommentInfo> {
+        let mut comments = Vec::new();
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            node.walk(&mut |block| {
+                if let Node::HtmlComment { text } = block {
+                    comments.push(HtmlCommentInfo {
+                        index: comments.len(),
+                        node_index,
+                        text: text.clone(),
+                        inline: false,
+                    });
+                }
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        if let InlineNode::HtmlComment { text } = inline {
+                            comments.push(HtmlCommentInfo {
+                                index: comments.len(),
+                                node_index,
+                                text: text.clone(),
+                                inline: true,
+                            });
+                        }
+                    });
+                }
+            });
+        }
+        comments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    #[test]
+    fn test_html_comments_round_trip() {
+        let source = "<!-- TODO: intro -->\n\n\
+                      Text <!--note--> here.\n\n\
+                      > <!--\n> quoted\n> -->\n>\n> [link <!-- in link -->](https://example.com)\n\n\
+                      <div>\n<!-- not alone -->\n</div>";
+        let doc = Document::try_from(Text::<Markdown>::new(source)).unwrap();
+
+        assert_eq!(
+            doc.nodes[0],
+            Node::HtmlComment {
+                text: " TODO: intro ".to_string()
+            }
+        );
+        assert_eq!(doc.nodes[1].plain_text(), "Text  here.");
+        assert!(matches!(doc.nodes[3], Node::HtmlBlock { .. }));
+        let comments = doc.html_comments();
+        let texts: Vec<&str> = comments.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, [" TODO: intro ", "note", "\nquoted\n", " in link "]);
+        assert!(comments[1].inline && !comments[2].inline);
+        assert_eq!(comments[3].node_index, 2);
+
+        let markdown: Text<Markdown> = doc.as_ref().try_into().unwrap();
+        assert!(
+            markdown
+                .as_str()
+                .starts_with("<!-- TODO: intro -->\n\nText <!--note--> here.")
+        );
+        assert_eq!(Document::try_from(markdown).unwrap().nodes, doc.nodes);
+
+        let mut doc = doc;
+        remove_html_comments(&mut doc.nodes, &mut 0, &[0, 2, 3]);
+        let texts: Vec<String> = doc.html_comments().into_iter().map(|c| c.text).collect();
+        assert_eq!(texts, ["note"]);
+        assert_eq!(doc.nodes.len(), 3);
+    }
+}
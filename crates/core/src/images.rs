@@ -0,0 +1,202 @@
+//! Image inventory for accessibility reviews
+//!
+//! Images are listed in reading order, covering both inline images and
+//! figures. The position of an image in that list is its index, which the
+//! editor uses to address images when applying batch edits.
+
+use crate::{Document, InlineNode, Node};
+use serde::Serialize;
+
+/// An image found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageInfo {
+    /// Position of the image among all images of the document
+    pub index: usize,
+    /// Index of the top-level node containing the image
+    pub node_index: usize,
+    /// Image source
+    pub url: String,
+    /// Alternative text
+    pub alt: String,
+    /// Optional image title
+    pub title: Option<String>,
+    /// Plain text of the caption; only figures have one
+    pub caption: Option<String>,
+}
+
+impl ImageInfo {
+    /// Returns true if the image is a figure rather than an inline image
+    pub fn is_figure(&self) -> bool {
+        self.caption.is_some()
+    }
+
+    /// Returns true if the image has no meaningful alternative text
+    pub fn lacks_alt(&self) -> bool {
+        self.alt.trim().is_empty()
+    }
+}
+
+/// Alternative text, and optionally a caption, to give an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltTextFix {
+    /// Index of the image, as reported by [`Document::images`]
+    pub index: usize,
+    /// New alternative text
+    pub alt: String,
+    /// New caption; ignored for inline images, which cannot carry one
+    pub caption: Option<String>,
+}
+
+impl AltTextFix {
+    /// Creates a fix setting the alternative text of an image
+    pub fn new(index: usize, alt: impl Into<String>) -> Self {
+        Self {
+            index,
+            alt: alt.into(),
+            caption: None,
+        }
+    }
+
+    /// Also sets the caption of the figure
+    pub fn with_caption(mut self, caption: impl Into<String>) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+}
+
+impl Document {
+    /// Lists every image of the document in reading order
+    pub fn images(&self) -> Vec<ImageInfo> {
+        let mut images = Vec::new();
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            node.walk(&mut |block| {
+                if let Node::Figure {
+                    url,
+                    alt,
+                    title,
+                    caption,
+                    ..
+                } = block
+                {
+                    images.push(ImageInfo {
+                        index: images.len(),
+                        node_index,
+                        url: url.clone(),
+                        alt: alt.clone(),
+                        title: title.clone(),
+                        caption: Some(InlineNode::plain_text_of(caption)),
+                    });
+                }
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        if let InlineNode::Image { url, alt, title } = inline {
+                            images.push(ImageInfo {
+                                index: images.len(),
+                                node_index,
+                                url: url.clone(),
+                                alt: alt.clone(),
+                                title: title.clone(),
+                                caption: None,
+                            });
+                        }
+                    });
+                }
+            });
+        }
+        images
+    }
+
+    /// Lists the images without alternative text
+    pub fn images_missing_alt(&self) -> Vec<ImageInfo> {
+        self.images()
+            .into_iter()
+            .filter(ImageInfo::lacks_alt)
+            .collect()
+    }
+}
+
+/// Writes the alt text, title and caption of `updates` back into a node
+///
+/// `first_index` is the index of the first image inside the node; the index
+/// following its last image is returned.
+pub(crate) fn apply_image_updates(
+    node: &mut Node,
+    first_index: usize,
+    updates: &[ImageInfo],
+) -> usize {
+    let mut index = first_index;
+    let find = |index: usize| updates.iter().find(|update| update.index == index);
+
+    node.walk_mut(&mut |block| {
+        if let Node::Figure {
+            alt,
+            title,
+            caption,
+            ..
+        } = block
+        {
+            if let Some(update) = find(index) {
+                alt.clone_from(&update.alt);
+                title.clone_from(&update.title);
+                if let Some(text) = &update.caption
+                    && *text != InlineNode::plain_text_of(caption)
+                {
+                    *caption = vec![InlineNode::text(text.clone())];
+                }
+            }
+            index += 1;
+        }
+        for inline in block.inline_runs_mut().into_iter().flatten() {
+            inline.walk_mut(&mut |inline| {
+                if let InlineNode::Image { alt, title, .. } = inline {
+                    if let Some(update) = find(index) {
+                        alt.clone_from(&update.alt);
+                        title.clone_from(&update.title);
+                    }
+                    index += 1;
+                }
+            });
+        }
+    });
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    #[test]
+    fn test_list_images() {
+        let source = "![Logo](logo.png)\n\n![](icon.png)\n\n\
+                      > ![  ](quoted.png \"Quoted\")\n\n\
+                      | a |\n|---|\n| [![](badge.svg)](https://ci) |\n";
+        let doc = Document::try_from(Text::<Markdown>::new(source)).unwrap();
+
+        let images = doc.images();
+        let urls: Vec<&str> = images.iter().map(|image| image.url.as_str()).collect();
+        assert_eq!(urls, ["logo.png", "icon.png", "quoted.png", "badge.svg"]);
+        assert_eq!(images[2].node_index, 2);
+        assert_eq!(images[2].title.as_deref(), Some("Quoted"));
+        assert!(!images[0].is_figure());
+
+        let missing: Vec<usize> = doc
+            .images_missing_alt()
+            .iter()
+            .map(|image| image.index)
+            .collect();
+        assert_eq!(missing, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_list_figures() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::figure("chart.png", "", "Sales"));
+
+        let images = doc.images();
+        assert!(images[0].is_figure());
+        assert_eq!(images[0].caption.as_deref(), Some("Sales"));
+        assert!(images[0].lacks_alt());
+    }
+}
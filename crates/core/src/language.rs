@@ -0,0 +1,296 @@
+//! Document and passage languages
+//!
+//! The language of a document is a BCP 47 tag such as `en` or `pt-BR`,
+//! stored in the custom metadata under `lang` so it travels with the
+//! document and page templates can place it as `{{meta.lang}}`. It picks
+//! how [`Document::stats`] counts words, since Chinese, Japanese, Thai and a
+//! few others don't separate words with spaces, and
+//! [`Autocorrect::for_language`](crate::Autocorrect::for_language) adds the
+//! language's typographic conventions. Passages in another language are
+//! tagged in HTML export with
+//! [`HtmlOptions::with_node_languages`](crate::HtmlOptions::with_node_languages).
+//!
+//! With the `language-detection` feature, [`Document::detect_languages`]
+//! infers the language of the document and of its top-level blocks.
+
+use crate::{Document, DocumentMetadata};
+
+/// Custom metadata key holding the document language
+const LANGUAGE_KEY: &str = "lang";
+
+/// Lowercase primary subtag of a language tag, e.g. `zh` for `zh-Hant`
+pub(crate) fn primary_subtag(language: &str) -> String {
+    language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Returns false for languages written without spaces between words
+pub(crate) fn separates_words(language: Option<&str>) -> bool {
+    !language.is_some_and(|language| {
+        matches!(
+            primary_subtag(language).as_str(),
+            "zh" | "ja" | "th" | "lo" | "km" | "my" | "bo"
+        )
+    })
+}
+
+impl Document {
+    /// The language of the document as a BCP 47 tag, if set
+    pub fn language(&self) -> Option<&str> {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.custom_value(LANGUAGE_KEY))
+    }
+
+    /// Sets or clears the language of the document
+    pub fn set_language(&mut self, language: Option<String>) {
+        if language.is_none() && self.metadata.is_none() {
+            return;
+        }
+        self.metadata
+            .get_or_insert_with(DocumentMetadata::default)
+            .set_custom_value(LANGUAGE_KEY, language);
+    }
+}
+
+#[cfg(feature = "language-detection")]
+pub use detection::*;
+
+#[cfg(feature = "language-detection")]
+mod detection {
+    use crate::Document;
+    use serde::Serialize;
+
+    /// Least characters of prose a top-level block needs to have its
+    /// language detected on its own
+    const MIN_NODE_CHARS: usize = 40;
+
+    /// A detected language
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct LanguageGuess {
+        /// BCP 47 tag of the language
+        pub language: String,
+        /// Confidence of the detection, between 0 and 1
+        pub confidence: f64,
+    }
+
+    /// A top-level block written in another language than the document
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct NodeLanguage {
+        /// Index of the top-level node
+        pub node_index: usize,
+        /// BCP 47 tag of the language
+        pub language: String,
+        /// Confidence of the detection, between 0 and 1
+        pub confidence: f64,
+    }
+
+    /// Languages detected in a document
+    #[derive(Debug, Clone, Default, PartialEq, Serialize)]
+    pub struct LanguageReport {
+        /// Language of the document as a whole
+        pub document: Option<LanguageGuess>,
+        /// Top-level blocks in another language, in document order
+        pub nodes: Vec<NodeLanguage>,
+    }
+
+    impl LanguageReport {
+        /// Languages of the blocks by node index, for
+        /// [`HtmlOptions::with_node_languages`](crate::HtmlOptions::with_node_languages)
+        pub fn node_languages(&self) -> impl Iterator<Item = (usize, String)> + '_ {
+            self.nodes
+                .iter()
+                .map(|node| (node.node_index, node.language.clone()))
+        }
+    }
+
+    /// Two-letter code of a language where there is one, as BCP 47 prefers
+    fn language_tag(lang: whatlang::Lang) -> &'static str {
+        use whatlang::Lang::*;
+        match lang {
+            Epo => "eo",
+            Eng => "en",
+            Rus => "ru",
+            Cmn => "zh",
+            Spa => "es",
+            Por => "pt",
+            Ita => "it",
+            Ben => "bn",
+            Fra => "fr",
+            Deu => "de",
+            Ukr => "uk",
+            Kat => "ka",
+            Ara => "ar",
+            Hin => "hi",
+            Jpn => "ja",
+            Heb => "he",
+            Yid => "yi",
+            Pol => "pl",
+            Amh => "am",
+            Jav => "jv",
+            Kor => "ko",
+            Nob => "nb",
+            Dan => "da",
+            Swe => "sv",
+            Fin => "fi",
+            Tur => "tr",
+            Nld => "nl",
+            Hun => "hu",
+            Ces => "cs",
+            Ell => "el",
+            Bul => "bg",
+            Bel => "be",
+            Mar => "mr",
+            Kan => "kn",
+            Ron => "ro",
+            Slv => "sl",
+            Hrv => "hr",
+            Srp => "sr",
+            Mkd => "mk",
+            Lit => "lt",
+            Lav => "lv",
+            Est => "et",
+            Tam => "ta",
+            Vie => "vi",
+            Urd => "ur",
+            Tha => "th",
+            Guj => "gu",
+            Uzb => "uz",
+            Pan => "pa",
+            Aze => "az",
+            Ind => "id",
+            Tel => "te",
+            Pes => "fa",
+            Mal => "ml",
+            Ori => "or",
+            Mya => "my",
+            Nep => "ne",
+            Sin => "si",
+            Khm => "km",
+            Tuk => "tk",
+            Aka => "ak",
+            Zul => "zu",
+            Sna => "sn",
+            Afr => "af",
+            Lat => "la",
+            Slk => "sk",
+            Cat => "ca",
+            Tgl => "tl",
+            Hye => "hy",
+        }
+    }
+
+    /// Detects the language of a text, if the detection is reliable
+    pub fn detect_language(text: &str) -> Option<LanguageGuess> {
+        let info = whatlang::detect(text)?;
+        info.is_reliable().then(|| LanguageGuess {
+            language: language_tag(info.lang()).to_string(),
+            confidence: info.confidence(),
+        })
+    }
+
+    impl Document {
+        /// Detects the language of the document and of the top-level blocks
+        /// written in another one
+        ///
+        /// Only prose is considered, as in [`Document::text_runs`]. Blocks
+        /// with too little text to tell are assumed to be in the document
+        /// language.
+        pub fn detect_languages(&self) -> LanguageReport {
+            let mut texts: Vec<String> = vec![String::new(); self.nodes.len()];
+            for run in self.text_runs() {
+                let text = &mut texts[run.path[0]];
+                text.push_str(&run.text);
+                text.push('\n');
+            }
+
+            let document = detect_language(&texts.concat());
+            let nodes = texts
+                .iter()
+                .enumerate()
+                .filter(|(_, text)| text.chars().count() >= MIN_NODE_CHARS)
+                .filter_map(|(node_index, text)| {
+                    let guess = detect_language(text)?;
+                    let same = document
+                        .as_ref()
+                        .is_some_and(|document| document.language == guess.language);
+                    (!same).then_some(NodeLanguage {
+                        node_index,
+                        language: guess.language,
+                        confidence: guess.confidence,
+                    })
+                })
+                .collect();
+            LanguageReport { document, nodes }
+        }
+
+        /// Detects the languages and stores the document language in the
+        /// metadata, keeping the language set before if detection fails
+        pub fn detect_and_set_language(&mut self) -> LanguageReport {
+            let report = self.detect_languages();
+            if let Some(document) = &report.document {
+                self.set_language(Some(document.language.clone()));
+            }
+            report
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_language() {
+        let mut doc = Document::new();
+        assert_eq!(doc.language(), None);
+        doc.set_language(None);
+        assert!(doc.metadata.is_none());
+
+        doc.set_language(Some("pt-BR".to_string()));
+        assert_eq!(doc.language(), Some("pt-BR"));
+        let metadata = doc.metadata.as_ref().unwrap();
+        assert_eq!(metadata.custom_value("lang"), Some("pt-BR"));
+        doc.set_language(None);
+        assert_eq!(doc.language(), None);
+    }
+
+    #[test]
+    fn test_separates_words() {
+        assert!(separates_words(None));
+        assert!(separates_words(Some("en-GB")));
+        assert!(!separates_words(Some("zh-Hant")));
+        assert!(!separates_words(Some("JA")));
+    }
+
+    #[cfg(feature = "language-detection")]
+    #[test]
+    fn test_detect_languages() {
+        use crate::{Markdown, Text};
+
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "# A short guide\n\n\
+             This guide explains how the editor keeps your documents safe while you write.\n\n\
+             Each change is saved in the background, so nothing is lost when the power fails.\n\n\
+             Every document you open is remembered, and you can always return to where you were.\n\n\
+             Settings are kept with the document itself, so they follow it to other computers.\n\n\
+             Ceci est un paragraphe écrit en français pour vérifier la détection.\n\n\
+             `code`\n",
+        ))
+        .unwrap();
+
+        let report = doc.detect_and_set_language();
+        assert_eq!(report.document.unwrap().language, "en");
+        assert_eq!(doc.language(), Some("en"));
+        let nodes: Vec<(usize, String)> = report
+            .nodes
+            .iter()
+            .map(|n| (n.node_index, n.language.clone()))
+            .collect();
+        assert_eq!(nodes, [(5, "fr".to_string())]);
+        assert_eq!(detect_language("?"), None);
+    }
+}
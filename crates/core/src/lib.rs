@@ -8,14 +8,100 @@ It allows representing, manipulating, and serializing hierarchical markdown docu
 - Hierarchical document representation with blocks and inline elements
 - Rich text formatting (bold, italic, code, strikethrough)
 - Support for lists (ordered, unordered, tasks), code blocks, tables, and more
+- Typed table cell values (numbers, currencies, dates) for sorting, column aggregates and locale-aware HTML rendering
+- Callouts (note, tip, warning) parsed from GitHub alerts and `:::` containers, rendered as admonition blocks
 - Cursor state and selection tracking
+- Byte, character or grapheme-cluster offsets for text edits and selections
 - Document metadata handling
-- Serialization and deserialization to/from JSON
+- Pandoc-style `[@key]` citations with a CSL-JSON bibliography, labelled author-date and followed by a generated references section in HTML and Markdown
+- Per-node attributes for application data, kept through JSON round trips and edits and exported as HTML `data-*` attributes
+- Source byte and line ranges of top-level nodes recorded on Markdown import, for mapping findings back to the source
+- Serialization and deserialization to/from JSON, with a schema version and migrations upgrading older payloads on load
+- Compact MessagePack-based binary encoding for autosave and IPC, versioned and migrated like JSON (`binary` feature)
 - Conversion to/from markdown and HTML formats
+- Pluggable format converters with a registry, usable through the same `Text<T>` conversions
+- Reports of the nodes an export to Markdown or plain text would degrade
+- Raw inline and block HTML kept verbatim, with optional escaping in HTML export
+- HTML comments kept as their own nodes through round trips, with listing, insertion and removal
+- Allowlist sanitization of raw HTML and URLs for untrusted documents
+- URL scheme allowlists enforced on link and image insertion and on HTML import
+- Safe mode for untrusted documents, removing raw HTML, script URLs, external images (or proxying them), custom styles and event handlers on export and import, with a report of what was removed
+- Standalone HTML pages from user templates with title, metadata, table of contents and body (`minijinja` feature for Jinja templates)
+- Per-node-type HTML renderer overrides for custom markup and attributes
+- Collapsible heading sections in HTML export (`<details>` or data attributes) reflecting the editor's folds
+- Incremental HTML rendering that re-renders only changed top-level nodes
+- Ordered lists renumbered from their start number on Markdown export, as `3. 4. 5.` or repeated `3.` markers
+- Tight and loose lists kept through Markdown and HTML round trips
+- Reference-style links kept through Markdown round trips, or written inline or numbered on export
+- Optional server-side code highlighting in HTML export (`syntax-highlighting` feature)
+- ANSI-styled terminal previews with box-drawn tables, task checkboxes and highlighted code (`ansi` feature)
+- EPUB 3 books from ordered chapter documents, with a table of contents from their headings and embedded local images (`epub` feature)
+- AsciiDoc import and export with admonitions, spanning table cells and callout lists (`asciidoc` feature)
+- Org-mode import and export, with TODO headlines as task lists and `#+BEGIN_SRC` blocks, tables and links
+- Jupyter notebook import, with code cells as code blocks carrying their execution counts and outputs
+- reStructuredText import of sections, lists, grid and simple tables, and Sphinx directives as callouts and code blocks
+- Math in HTML export as raw TeX, MathJax-ready delimiters, MathML or KaTeX HTML (`katex` feature), with diagnostics for invalid TeX
 - Editing operations (insert, split, etc.)
 - Fluent builder API for document creation
+- Visitor traits for read-only and mutating tree traversal
 - Simplified selection API with helper methods
+- Multiple cursors and selections, with typing, deletion and formatting at all of them as one undoable step
+- Adding, removing, editing, moving, nesting and converting items of any list type
+- Checklist progress per list, task queries, and checking, unchecking or removing completed tasks in bulk
+- Undoable table captions, summaries and ARIA labels, exported to HTML
+- Document fragments for selections, clipboards and extracted blocks, converting to and from Markdown, HTML and JSON and pasted or inserted by the editor in one undoable step
+- Smart paste of Markdown and HTML, merging inline content into the paragraph at the cursor and splitting it around pasted blocks
+- Markdown shortcuts typed at the start of a paragraph (`# `, `- `, `1. `, ```` ``` ````, `> `) converting it as a separate undo step, with configurable input rules
 - Transaction support for atomic operations
+- Typed edit errors carrying the failing node path, ranges and command
+- Serializable undo and redo history for resuming editing sessions
+- Undo and redo restoring the selection from before and after each step
+- Editor sessions (selection, folds, bookmarks, scroll anchor) keyed by a content hash
+- Folded sections and code blocks stored on the nodes, saved in JSON and rendered collapsed in sectioned HTML
+- Pasted images stored once as content-addressed assets behind `asset:` URLs, embedded or rewritten on export
+- Three-way merging of documents into conflict blocks, resolved in the editor by choosing a side
+- Snippets with variables, built-in dates and tab stops, expanded at the cursor with navigation between the stops
+- Autosave into pluggable document stores (filesystem or memory) with numbered revisions, after a number of changes or a pause, and recovery of the latest revision
+- Back and forward navigation through the selection's recent jumps, separate from undo
+- Nodes pinned to the start or end of the document, kept in place by moves, inserts and deletes
+- Named checkpoints and an optional branching history tree
+- Coalescing of keystroke-level edits into single undo steps
+- Pluggable autocorrect rules applied as separately undoable edits
+- Structural document diffing with word- or grapheme-level text changes, HTML rendering and compact edit patches
+- Unified text diffs over the canonical Markdown for logs and CI checks
+- Review comments on text ranges that follow edits and are saved with the document
+- Suggested-edits mode recording insertions and deletions for review, with accept and reject and `<ins>`/`<del>` in HTML export
+- Prose text runs for spellcheckers, with annotations that follow edits
+- Preferred terminology checks over prose, with an undoable fix-all that skips code and URLs
+- Position mapping across edits for remote cursors and highlights, keeping the selection in place
+- Word counts and per-section progress towards word targets
+- Word and character counts with reading time for node ranges and the current selection
+- Document language in metadata, driving word segmentation, autocorrect typography and HTML `lang` attributes (`language-detection` feature to infer it)
+- Per-command and per-session deltas of words and nodes
+- Ranges of the nodes each command, undo or redo changed, for scrolling to and highlighting edits
+- Heading outline with anchors, breadcrumbs and whole-section moves
+- Managed tables of contents expanded from the outline on export, round-tripped through Markdown comment markers and materialized as static lists on demand
+- Outline drag and drop of sections before, after or into other sections, adjusting heading levels
+- Splitting documents into one document per section and joining documents, keeping footnotes, anchor links and link references intact
+- GitHub-style heading anchors and intra-document link checking
+- Footnote previews with definition paths and back-references
+- Page titles for pasted links and untitled links, applied as one undoable update (`link-titles` feature to fetch them)
+- Graph export of headings, internal links, footnote references and linked documents as JSON or Graphviz DOT
+- Image inventory with batch alt-text and caption fixes
+- Link reports and URL rewriting for site generators
+- Composable link rewriters (base URL, extension mapping, anchor normalization) for exports
+- Opt-in `@user` and `#123` mention parsing, with resolvers linking mentions in HTML export
+- Opt-in `#tag` parsing with configurable syntax and nested tags, aggregated per document and rendered as tag spans or links in HTML
+- `[[Page]]` and `[[Page|Alias]]` wiki-links, listed for backlink indexing, linked through a resolver in HTML export and shown in the document graph
+- Draft and hidden blocks kept out of HTML and published Markdown, round-tripped as Markdown comment markers
+- Custom fenced blocks and `:name[...]` inline directives kept through round trips, with per-kind HTML renderers
+- Orphaned content detection with atomic cleanup
+- Merging of adjacent lists, blockquotes and code blocks left split by edits
+- Structured code block reports for documentation tooling
+- Identifier renames across code blocks that skip strings and comments
+- Undoable code block properties, set per block or from a template for every block in a language
+- Doctest-style verification of code examples (rustc, JSON, custom verifiers)
+- Command hub for serialized multi-producer editing with snapshots
 
 ## Basic Example
 
@@ -128,12 +214,129 @@ The document is organized as a tree structure:
 See the module documentation for more details on individual components.
 */
 
+mod anchors;
+mod assets;
+mod attributes;
+mod citations;
+mod cleanup;
+mod code_blocks;
+mod comments;
 mod convert;
+mod custom;
+mod degradation;
+mod diff;
 mod editor;
+#[cfg(feature = "epub")]
+mod epub;
 mod error;
+mod folding;
+mod footnotes;
+mod fragment;
+mod graph;
+mod html_comments;
+mod images;
+mod language;
+mod link_titles;
+mod links;
+mod mapping;
+mod mentions;
+pub mod merge;
 mod models;
-
-pub use convert::{Html, Json, Markdown, Text};
+mod offsets;
+mod outline;
+mod safe_mode;
+mod snippets;
+mod source_positions;
+mod spellcheck;
+mod split;
+mod stats;
+mod store;
+mod tags;
+mod tasks;
+mod terminology;
+mod toc;
+mod track_changes;
+mod urls;
+mod verify;
+mod visibility;
+mod visit;
+mod wiki_links;
+
+pub use anchors::{InternalLink, InternalLinkReport};
+pub use assets::{ASSET_SCHEME, Asset, AssetStore, MemoryAssetStore, asset_id, asset_url};
+pub use attributes::NodeAttributes;
+pub use citations::{BibliographyEntry, CslName};
+pub use cleanup::{CleanupOptions, Orphan, OrphanKind};
+pub use code_blocks::{CodeBlockInfo, CodeBlockRename, CodeBlockReport};
+pub use comments::Comment;
+#[cfg(feature = "asciidoc")]
+pub use convert::AsciiDoc;
+#[cfg(feature = "ansi")]
+pub use convert::ansi::AnsiOptions;
+pub use convert::format::{Capabilities, ConverterRegistry, FormatConverter};
+#[cfg(feature = "syntax-highlighting")]
+pub use convert::highlight::{DEFAULT_HIGHLIGHT_THEME, highlight_themes};
+pub use convert::html::{
+    HtmlOptions, HtmlOutput, HtmlRenderCache, HtmlSectionMode, MathDiagnostic, RenderedNode,
+};
+pub use convert::json::{JsonMigrations, SCHEMA_VERSION};
+pub use convert::locale::{Locale, LocaleProvider};
+pub use convert::markdown::{LinkStyle, ListNumbering, MarkdownOptions};
+#[cfg(feature = "katex")]
+pub use convert::math::KatexRenderer;
+pub use convert::math::{HtmlMathMode, MathMlRenderer, MathRenderer};
+pub use convert::render::{NodeRenderer, RendererRegistry};
+pub use convert::sanitize::HtmlSanitizer;
+#[cfg(feature = "minijinja")]
+pub use convert::template::JinjaTemplate;
+pub use convert::template::{HtmlPage, HtmlTemplate, PageTemplate};
+pub use convert::{Html, Ipynb, Json, Markdown, Org, Rst, Text};
+pub use custom::{CustomRenderer, Directives};
+pub use degradation::{DegradationReport, DegradedNode, ExportTarget, Feature};
+pub use diff::{
+    ChangeSet, DiffGranularity, NodeChange, TextChange, TextEdit, apply_text_edits, diff_documents,
+    diff_text, diff_text_with, text_edits, to_text_diff, to_text_diff_with,
+};
 pub use editor::*;
-pub use error::{EditError, ParseError};
+#[cfg(feature = "epub")]
+pub use epub::{EpubMetadata, EpubOptions, write_epub};
+pub use error::{EditError, EditErrorKind, ParseError};
+pub use footnotes::FootnotePreview;
+pub use fragment::DocumentFragment;
+pub use graph::{DocumentGraph, GraphEdge, GraphEdgeKind, GraphNode, GraphNodeKind};
+pub use html_comments::HtmlCommentInfo;
+pub use images::{AltTextFix, ImageInfo};
+#[cfg(feature = "language-detection")]
+pub use language::{LanguageGuess, LanguageReport, NodeLanguage, detect_language};
+#[cfg(feature = "link-titles")]
+pub use link_titles::LinkTitleOptions;
+pub use link_titles::{LinkTitle, LinkTitleTarget, page_title};
+pub use links::{
+    BaseUrl, ExtensionMap, LinkInfo, LinkKind, LinkPipeline, LinkReport, LinkRewriter,
+    NormalizeAnchors,
+};
+pub use mapping::PositionMapper;
+pub use mentions::{MentionResolver, MentionUrls};
+pub use merge::{ConflictSide, MergeOutcome, merge_documents};
 pub use models::*;
+pub use offsets::OffsetKind;
+pub use outline::{Breadcrumb, Outline, OutlineDrop, OutlineEntry, SectionMove, slugify};
+pub use safe_mode::{RemovalKind, RemovedContent, SafeMode, SafetyReport};
+pub use snippets::{ExpandedSnippet, Snippet, SnippetRegistry, TabStop};
+pub use source_positions::SourceSpan;
+pub use spellcheck::{Annotation, TextRun};
+pub use stats::{
+    Counts, DocumentStats, READING_WORDS_PER_MINUTE, SectionStats, StatsDelta, StatsTracker,
+};
+pub use store::{DocumentStore, FileStore, MemoryStore, RevisionInfo, StoreError};
+pub use tags::{TagInfo, TagOptions};
+pub use tasks::{TaskFilter, TaskInfo, TaskListSummary, TaskSummary};
+pub use terminology::{TermViolation, Terminology};
+pub use track_changes::{ChangeKind, TrackedChange};
+pub use urls::UrlPolicy;
+pub use verify::{
+    BlockVerification, CodeVerifier, Diagnostic, JsonVerifier, RustcVerifier, Severity,
+    VerificationReport, Verifier,
+};
+pub use visit::{VisitControl, Visitor, VisitorMut};
+pub use wiki_links::{WikiLinkInfo, WikiLinkResolver};
@@ -0,0 +1,373 @@
+//! Page titles for pasted links
+//!
+//! A pasted URL becomes an autolink, or a link whose text is the URL itself.
+//! [`Document::link_title_targets`] lists such links to `http(s)` pages, and
+//! links without a title, so their page `<title>` can be looked up;
+//! [`page_title`] reads it from fetched HTML. The editor applies the results
+//! as one undoable update: links get the page title as their title, and as
+//! their text when they had none of their own.
+//!
+//! With the `link-titles` feature, `Editor::fetch_link_titles` does the
+//! fetching too, on a bounded number of threads with a timeout per request.
+//! It blocks until the pages are in: the editor is single-threaded and the
+//! crate has no async runtime to hand the requests to. Applications with an
+//! event loop or their own HTTP client fetch the targets themselves, async
+//! or not, and call `Editor::apply_link_titles` with the results.
+
+use crate::{Document, InlineNode, Node};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::LazyLock;
+
+static TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<title(?:\s[^>]*)?>(.*?)</title\s*>").unwrap());
+
+/// A link whose page title is worth looking up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkTitleTarget {
+    /// Index of the top-level node containing the link
+    pub node_index: usize,
+    /// URL of the page
+    pub url: String,
+    /// Whether the link has no text of its own, being a bare URL
+    pub needs_text: bool,
+}
+
+/// The title found for a page, to apply to the links to it in one node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkTitle {
+    /// Index of the top-level node containing the links
+    pub node_index: usize,
+    /// URL of the page
+    pub url: String,
+    /// Title of the page
+    pub title: String,
+}
+
+/// Options for fetching page titles
+#[cfg(feature = "link-titles")]
+#[derive(Debug, Clone)]
+pub struct LinkTitleOptions {
+    /// Time allowed for each request, including reading the page
+    pub timeout: std::time::Duration,
+    /// Most requests in flight at once
+    pub max_concurrency: usize,
+    /// Most bytes of each page read while looking for the title
+    pub max_bytes: u64,
+}
+
+#[cfg(feature = "link-titles")]
+impl Default for LinkTitleOptions {
+    fn default() -> Self {
+        Self {
+            timeout: std::time::Duration::from_secs(5),
+            max_concurrency: 4,
+            max_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// Returns the text of the `<title>` element of an HTML page, with entities
+/// decoded and whitespace collapsed, if it is not empty
+pub fn page_title(html: &str) -> Option<String> {
+    let raw = TITLE.captures(html)?.get(1)?.as_str();
+    let title = raw
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    (!title.is_empty()).then_some(title)
+}
+
+fn is_web_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Returns the target for a link or autolink, if it lacks a title
+fn target(inline: &InlineNode, node_index: usize) -> Option<LinkTitleTarget> {
+    let (url, needs_text) = match inline {
+        InlineNode::Link {
+            url,
+            title,
+            children,
+            ..
+        } => {
+            let text = InlineNode::plain_text_of(children);
+            let needs_text = text.trim().is_empty() || text == *url;
+            if title.is_some() && !needs_text {
+                return None;
+            }
+            (url, needs_text)
+        }
+        InlineNode::AutoLink {
+            url,
+            is_email: false,
+        } => (url, true),
+        _ => return None,
+    };
+    is_web_url(url).then(|| LinkTitleTarget {
+        node_index,
+        url: url.clone(),
+        needs_text,
+    })
+}
+
+/// Applies the titles found for a node's links to its inline content,
+/// returning how many links changed
+fn apply_inline_titles(
+    inlines: &mut [InlineNode],
+    node_index: usize,
+    titles: &[LinkTitle],
+) -> usize {
+    let mut changed = 0;
+    for inline in inlines {
+        let Some(found) = target(inline, node_index) else {
+            if let InlineNode::Link { children, .. } | InlineNode::InlineFootnote { children } =
+                inline
+            {
+                changed += apply_inline_titles(children, node_index, titles);
+            }
+            continue;
+        };
+        let Some(title) = titles
+            .iter()
+            .find(|title| title.node_index == node_index && title.url == found.url)
+        else {
+            continue;
+        };
+        let text = if found.needs_text {
+            vec![InlineNode::text(title.title.clone())]
+        } else {
+            match inline {
+                InlineNode::Link { children, .. } => std::mem::take(children),
+                _ => unreachable!("only links have text of their own"),
+            }
+        };
+        let reference = match inline {
+            InlineNode::Link { reference, .. } => reference.take(),
+            _ => None,
+        };
+        *inline = InlineNode::Link {
+            url: found.url,
+            title: Some(title.title.clone()),
+            children: text,
+            reference,
+        };
+        changed += 1;
+    }
+    changed
+}
+
+/// Applies titles to the links of a top-level node, returning how many
+/// links changed
+pub(crate) fn apply_link_titles(node: &mut Node, node_index: usize, titles: &[LinkTitle]) -> usize {
+    let mut changed = 0;
+    node.walk_mut(&mut |block| {
+        for run in block.inline_runs_mut() {
+            changed += apply_inline_titles(run, node_index, titles);
+        }
+    });
+    changed
+}
+
+impl Document {
+    /// Lists the links to web pages in the top-level nodes of `range` that
+    /// lack a title, or text other than their URL
+    ///
+    /// Links to the same URL within one node are listed once.
+    pub fn link_title_targets(&self, range: Range<usize>) -> Vec<LinkTitleTarget> {
+        let mut targets: Vec<LinkTitleTarget> = Vec::new();
+        let end = range.end.min(self.nodes.len());
+        for node_index in range.start.min(end)..end {
+            self.nodes[node_index].walk(&mut |block| {
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        let Some(found) = target(inline, node_index) else {
+                            return;
+                        };
+                        match targets.iter_mut().find(|target| {
+                            target.node_index == node_index && target.url == found.url
+                        }) {
+                            Some(existing) => existing.needs_text |= found.needs_text,
+                            None => targets.push(found),
+                        }
+                    });
+                }
+            });
+        }
+        targets
+    }
+}
+
+/// Fetches the title of every distinct URL, `None` for pages that failed to
+/// load or have no title
+///
+/// Requests run on at most `max_concurrency` threads at once, and no more
+/// threads than there are distinct URLs. Blocks until all requests are done
+/// or timed out.
+#[cfg(feature = "link-titles")]
+pub(crate) fn fetch_titles(
+    urls: &[String],
+    options: &LinkTitleOptions,
+) -> Vec<(String, Option<String>)> {
+    use std::io::Read;
+    use std::sync::Mutex;
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(options.timeout))
+        .build()
+        .into();
+    let fetch = |url: &str| -> Option<String> {
+        let mut response = agent.get(url).call().ok()?;
+        let mut bytes = Vec::new();
+        response
+            .body_mut()
+            .as_reader()
+            .take(options.max_bytes)
+            .read_to_end(&mut bytes)
+            .ok()?;
+        page_title(&String::from_utf8_lossy(&bytes))
+    };
+
+    let mut distinct: Vec<&String> = Vec::new();
+    for url in urls {
+        if !distinct.contains(&url) {
+            distinct.push(url);
+        }
+    }
+    let workers = options.max_concurrency.clamp(1, distinct.len().max(1));
+    let queue = Mutex::new(distinct.into_iter());
+    let results = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let Some(url) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let title = fetch(url);
+                    results.lock().unwrap().push((url.clone(), title));
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    #[test]
+    fn test_page_title() {
+        let html = "<html><head><TITLE lang=\"en\">\n  Rust &amp; Markdown\n</TITLE></head></html>";
+        assert_eq!(page_title(html).as_deref(), Some("Rust & Markdown"));
+        assert_eq!(page_title("<title>  </title>"), None);
+        assert_eq!(page_title("<p>No title</p>"), None);
+    }
+
+    #[test]
+    fn test_link_title_targets_and_apply() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "See <https://a.example> and [docs](https://b.example).\n\n\
+             [https://c.example](https://c.example), [titled](https://d.example \"D\") \
+             and [local](guide.md).\n",
+        ))
+        .unwrap();
+
+        let targets = doc.link_title_targets(0..5);
+        let found: Vec<(usize, &str, bool)> = targets
+            .iter()
+            .map(|t| (t.node_index, t.url.as_str(), t.needs_text))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (0, "https://a.example", true),
+                (0, "https://b.example", false),
+                (1, "https://c.example", true),
+            ]
+        );
+        assert_eq!(doc.link_title_targets(1..2).len(), 1);
+
+        let titles = vec![
+            LinkTitle {
+                node_index: 0,
+                url: "https://a.example".to_string(),
+                title: "Site A".to_string(),
+            },
+            LinkTitle {
+                node_index: 0,
+                url: "https://b.example".to_string(),
+                title: "Site B".to_string(),
+            },
+        ];
+        assert_eq!(apply_link_titles(&mut doc.nodes[0], 0, &titles), 2);
+        let Node::Paragraph { children } = &doc.nodes[0] else {
+            panic!("Expected paragraph");
+        };
+        assert_eq!(
+            children[1],
+            InlineNode::link_with_title("https://a.example", "Site A", "Site A")
+        );
+        assert_eq!(
+            children[3],
+            InlineNode::link_with_title("https://b.example", "Site B", "docs")
+        );
+        assert!(doc.link_title_targets(0..1).is_empty());
+    }
+
+    #[cfg(feature = "link-titles")]
+    #[test]
+    fn test_fetch_titles() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let read = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..read]);
+                let (status, body) = if request.starts_with("GET /page ") {
+                    ("200 OK", "<html><title>Local Page</title></html>")
+                } else {
+                    ("404 Not Found", "")
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: text/html\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let page = format!("{base}/page");
+        let missing = format!("{base}/missing");
+        let urls = [page.clone(), missing.clone(), page.clone()];
+        let options = LinkTitleOptions {
+            max_concurrency: 8,
+            ..Default::default()
+        };
+        let mut fetched = fetch_titles(&urls, &options);
+        fetched.sort();
+        server.join().unwrap();
+        assert_eq!(
+            fetched,
+            [(missing, None), (page, Some("Local Page".to_string()))]
+        );
+    }
+}
@@ -0,0 +1,536 @@
+//! Link inventory and URL rewriting
+//!
+//! Static site generators and link checkers need every URL a document refers
+//! to. [`Document::links`] lists links, autolinks and images (including
+//! figures) with the top-level node they appear in, and
+//! [`Document::rewrite_links`] maps them in place, e.g. to turn relative
+//! `.md` paths into `.html` routes.
+//!
+//! Rewrites that recur across exports implement [`LinkRewriter`]. The
+//! built-in [`BaseUrl`], [`ExtensionMap`] and [`NormalizeAnchors`] chain in
+//! a [`LinkPipeline`], which the exports apply through
+//! [`HtmlOptions::with_link_rewriter`](crate::HtmlOptions::with_link_rewriter)
+//! and [`MarkdownOptions::with_link_rewriter`](crate::MarkdownOptions::with_link_rewriter),
+//! and [`Document::rewrite_links_with`] applies to an imported document.
+
+use crate::{Document, InlineNode, Node, slugify};
+use serde::Serialize;
+use std::fmt;
+use std::rc::Rc;
+
+/// The kind of element a URL was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// An inline `[text](url)` link
+    Link,
+    /// A bare URL or email address
+    AutoLink,
+    /// An inline image or a figure
+    Image,
+}
+
+/// A URL found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkInfo {
+    /// Where the URL was found
+    pub kind: LinkKind,
+    /// The URL as written
+    pub url: String,
+    /// Link text, or alt text for images
+    pub text: String,
+    /// Index of the top-level node containing the URL
+    pub node_index: usize,
+}
+
+impl LinkInfo {
+    /// Returns true if the URL points at an anchor in the same document
+    pub fn is_anchor(&self) -> bool {
+        self.url.starts_with('#')
+    }
+
+    /// Returns true if the URL has a scheme such as `https:` or `mailto:`
+    pub fn is_absolute(&self) -> bool {
+        self.kind == LinkKind::AutoLink || has_scheme(&self.url)
+    }
+
+    /// Returns true if the URL is a path relative to the document
+    pub fn is_relative(&self) -> bool {
+        !self.is_absolute() && !self.is_anchor() && !self.url.starts_with('/')
+    }
+}
+
+/// Returns true if the URL starts with `scheme:`
+pub(crate) fn has_scheme(url: &str) -> bool {
+    match url.split_once(':') {
+        Some((scheme, _)) => {
+            scheme
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => url.starts_with("//"),
+    }
+}
+
+/// Splits a URL into its path and the `?query#fragment` suffix
+fn split_suffix(url: &str) -> (&str, &str) {
+    url.split_at(url.find(['?', '#']).unwrap_or(url.len()))
+}
+
+/// Maps URLs found in a document to the URLs to emit
+///
+/// Closures taking a URL and returning `Option<String>` are rewriters too.
+pub trait LinkRewriter {
+    /// Returns the new URL, or `None` to leave the URL unchanged
+    fn rewrite(&self, url: &str) -> Option<String>;
+}
+
+impl<F> LinkRewriter for F
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn rewrite(&self, url: &str) -> Option<String> {
+        self(url)
+    }
+}
+
+/// Resolves relative and root-relative paths against a base URL
+///
+/// With the base `https://example.com/docs/`, `guide.md` becomes
+/// `https://example.com/docs/guide.md` and `/about` becomes
+/// `https://example.com/about`. Anchors and absolute URLs are left alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseUrl {
+    /// Scheme and host, e.g. `https://example.com`
+    origin: String,
+    /// Directory paths are resolved against, with a trailing `/`
+    directory: String,
+}
+
+impl BaseUrl {
+    /// Creates a rewriter resolving against `base`
+    ///
+    /// A base without a trailing `/` names a document, so paths resolve
+    /// against its directory.
+    pub fn new(base: &str) -> Self {
+        let path_start = match base.find("://") {
+            Some(scheme_end) => base[scheme_end + 3..]
+                .find('/')
+                .map_or(base.len(), |i| scheme_end + 3 + i),
+            None => 0,
+        };
+        let (origin, path) = base.split_at(path_start);
+        let path = split_suffix(path).0;
+        let directory = match path.rfind('/') {
+            Some(i) => &path[..=i],
+            None => "/",
+        };
+        Self {
+            origin: origin.to_string(),
+            directory: directory.to_string(),
+        }
+    }
+}
+
+/// Removes `.` and `..` segments from an absolute path
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut parts = path.split('/').skip(1).peekable();
+    while let Some(part) = parts.next() {
+        let last = parts.peek().is_none();
+        match part {
+            "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => {
+                segments.push(part);
+                continue;
+            }
+        }
+        // A trailing `.` or `..` still names a directory
+        if last {
+            segments.push("");
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+impl LinkRewriter for BaseUrl {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        if url.is_empty() || url.starts_with('#') || has_scheme(url) {
+            return None;
+        }
+        let (path, suffix) = split_suffix(url);
+        let absolute = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("{}{}", self.directory, path)
+        };
+        Some(format!(
+            "{}{}{}",
+            self.origin,
+            remove_dot_segments(&absolute),
+            suffix
+        ))
+    }
+}
+
+/// Replaces the file extension of relative and root-relative paths
+///
+/// Keeps any query and fragment, so `../install.md#linux` becomes
+/// `../install.html#linux` with [`ExtensionMap::markdown_to_html`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionMap {
+    from: String,
+    to: String,
+}
+
+impl ExtensionMap {
+    /// Maps paths ending in `from` to end in `to`, both including the dot
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Maps `.md` files to `.html` pages, as static site generators publish them
+    pub fn markdown_to_html() -> Self {
+        Self::new(".md", ".html")
+    }
+}
+
+impl LinkRewriter for ExtensionMap {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        if has_scheme(url) {
+            return None;
+        }
+        let (path, suffix) = split_suffix(url);
+        let stem = path.strip_suffix(self.from.as_str())?;
+        (!stem.is_empty() && !stem.ends_with('/')).then(|| format!("{}{}{}", stem, self.to, suffix))
+    }
+}
+
+/// Rewrites the fragment of anchors and relative links as a heading anchor
+///
+/// `#Getting Started` becomes `#getting-started`, matching the ids the
+/// HTML export gives headings (see [`slugify`]). Fragments of absolute URLs
+/// belong to other sites and are left alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeAnchors;
+
+impl LinkRewriter for NormalizeAnchors {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        if has_scheme(url) {
+            return None;
+        }
+        let (path, fragment) = url.split_once('#')?;
+        let anchor = slugify(&fragment.replace("%20", " "));
+        (anchor != fragment).then(|| format!("{}#{}", path, anchor))
+    }
+}
+
+/// Rewriters applied one after another, each to the previous one's output
+///
+/// The order is the order of [`LinkPipeline::then`] calls, so
+/// `ExtensionMap` before `BaseUrl` maps `guide.md` to
+/// `https://example.com/docs/guide.html`.
+#[derive(Clone, Default)]
+pub struct LinkPipeline {
+    rewriters: Vec<Rc<dyn LinkRewriter>>,
+}
+
+impl fmt::Debug for LinkPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinkPipeline")
+            .field("rewriters", &self.rewriters.len())
+            .finish()
+    }
+}
+
+impl LinkPipeline {
+    /// Creates an empty pipeline, which leaves every URL unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a rewriter to run after the ones already added
+    pub fn then(mut self, rewriter: impl LinkRewriter + 'static) -> Self {
+        self.rewriters.push(Rc::new(rewriter));
+        self
+    }
+
+    /// Number of rewriters in the pipeline
+    pub fn len(&self) -> usize {
+        self.rewriters.len()
+    }
+
+    /// Returns true if the pipeline has no rewriters
+    pub fn is_empty(&self) -> bool {
+        self.rewriters.is_empty()
+    }
+}
+
+impl LinkRewriter for LinkPipeline {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        let mut rewritten: Option<String> = None;
+        for rewriter in &self.rewriters {
+            let current = rewritten.as_deref().unwrap_or(url);
+            if let Some(next) = rewriter.rewrite(current) {
+                rewritten = Some(next);
+            }
+        }
+        rewritten.filter(|new_url| new_url != url)
+    }
+}
+
+/// Every URL of a document in reading order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LinkReport {
+    /// The URLs, in document order
+    pub links: Vec<LinkInfo>,
+}
+
+impl LinkReport {
+    /// Number of URLs found
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Returns true if the document refers to no URLs
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// URLs found in elements of the given kind
+    pub fn with_kind(&self, kind: LinkKind) -> impl Iterator<Item = &LinkInfo> {
+        self.links.iter().filter(move |link| link.kind == kind)
+    }
+
+    /// Distinct URLs, sorted
+    pub fn urls(&self) -> Vec<&str> {
+        let mut urls: Vec<&str> = self.links.iter().map(|link| link.url.as_str()).collect();
+        urls.sort_unstable();
+        urls.dedup();
+        urls
+    }
+}
+
+impl Document {
+    /// Lists every link, autolink and image URL with its position
+    pub fn links(&self) -> LinkReport {
+        let mut links = Vec::new();
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            node.walk(&mut |block| {
+                if let Node::Figure { url, alt, .. } = block {
+                    links.push(LinkInfo {
+                        kind: LinkKind::Image,
+                        url: url.clone(),
+                        text: alt.clone(),
+                        node_index,
+                    });
+                }
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        let (kind, url, text) = match inline {
+                            InlineNode::Link { url, children, .. } => {
+                                (LinkKind::Link, url, InlineNode::plain_text_of(children))
+                            }
+                            InlineNode::AutoLink { url, .. } => {
+                                (LinkKind::AutoLink, url, url.clone())
+                            }
+                            InlineNode::Image { url, alt, .. } => {
+                                (LinkKind::Image, url, alt.clone())
+                            }
+                            _ => return,
+                        };
+                        links.push(LinkInfo {
+                            kind,
+                            url: url.clone(),
+                            text,
+                            node_index,
+                        });
+                    });
+                }
+            });
+        }
+        LinkReport { links }
+    }
+
+    /// Rewrites link, autolink and image URLs in place
+    ///
+    /// The closure returns the new URL, or `None` to leave a URL unchanged.
+    /// Returns the number of URLs changed.
+    pub fn rewrite_links<F>(&mut self, mut rewrite: F) -> usize
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut changed = 0;
+        let mut apply = |url: &mut String| {
+            if let Some(new_url) = rewrite(url)
+                && new_url != *url
+            {
+                *url = new_url;
+                changed += 1;
+            }
+        };
+
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| {
+                if let Node::Figure { url, .. } = block {
+                    apply(url);
+                }
+                for inline in block.inline_runs_mut().into_iter().flatten() {
+                    inline.walk_mut(&mut |inline| match inline {
+                        InlineNode::Link { url, .. }
+                        | InlineNode::AutoLink { url, .. }
+                        | InlineNode::Image { url, .. } => apply(url),
+                        _ => {}
+                    });
+                }
+            });
+        }
+        changed
+    }
+
+    /// Rewrites link, autolink and image URLs in place with a [`LinkRewriter`]
+    ///
+    /// Returns the number of URLs changed.
+    pub fn rewrite_links_with(&mut self, rewriter: &dyn LinkRewriter) -> usize {
+        self.rewrite_links(|url| rewriter.rewrite(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    const SOURCE: &str = "[Guide](guide.md) and <https://example.com>\n\n\
+                          - See [install](../install.md#linux)\n\n\
+                          ![Logo](img/logo.png)\n\n\
+                          | Link |\n|---|\n| [top](#top) |\n";
+
+    #[test]
+    fn test_link_report() {
+        let doc = Document::try_from(Text::<Markdown>::new(SOURCE)).unwrap();
+        let report = doc.links();
+
+        let urls: Vec<&str> = report.links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "guide.md",
+                "https://example.com",
+                "../install.md#linux",
+                "img/logo.png",
+                "#top"
+            ]
+        );
+        assert_eq!(report.links[0].text, "Guide");
+        assert_eq!(report.links[1].kind, LinkKind::AutoLink);
+        assert_eq!(report.links[2].node_index, 1);
+        assert_eq!(report.with_kind(LinkKind::Image).count(), 1);
+
+        let relative: Vec<&str> = report
+            .links
+            .iter()
+            .filter(|link| link.is_relative())
+            .map(|link| link.url.as_str())
+            .collect();
+        assert_eq!(
+            relative,
+            ["guide.md", "../install.md#linux", "img/logo.png"]
+        );
+        assert!(report.links[4].is_anchor());
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(SOURCE)).unwrap();
+        doc.nodes.push(Node::figure("img/chart.png", "Chart", ""));
+
+        let changed = doc.rewrite_links(|url| {
+            if let Some(path) = url.strip_prefix("img/") {
+                return Some(format!("/static/{}", path));
+            }
+            url.contains(".md").then(|| url.replacen(".md", ".html", 1))
+        });
+        assert_eq!(changed, 4);
+        assert_eq!(
+            doc.links().urls(),
+            [
+                "#top",
+                "../install.html#linux",
+                "/static/chart.png",
+                "/static/logo.png",
+                "guide.html",
+                "https://example.com"
+            ]
+        );
+        assert_eq!(doc.rewrite_links(|url| Some(url.to_string())), 0);
+    }
+
+    #[test]
+    fn test_link_rewriters() {
+        let base = BaseUrl::new("https://example.com/docs/intro.html");
+        assert_eq!(
+            base.rewrite("guide.md").as_deref(),
+            Some("https://example.com/docs/guide.md")
+        );
+        assert_eq!(
+            base.rewrite("../install.md#linux").as_deref(),
+            Some("https://example.com/install.md#linux")
+        );
+        assert_eq!(
+            base.rewrite("/about?lang=en").as_deref(),
+            Some("https://example.com/about?lang=en")
+        );
+        assert_eq!(base.rewrite("#top"), None);
+        assert_eq!(base.rewrite("mailto:me@example.com"), None);
+
+        let extensions = ExtensionMap::markdown_to_html();
+        assert_eq!(
+            extensions.rewrite("../install.md#linux").as_deref(),
+            Some("../install.html#linux")
+        );
+        assert_eq!(extensions.rewrite("https://example.com/a.md"), None);
+        assert_eq!(extensions.rewrite("notes.mdx"), None);
+
+        assert_eq!(
+            NormalizeAnchors.rewrite("#Getting%20Started").as_deref(),
+            Some("#getting-started")
+        );
+        assert_eq!(NormalizeAnchors.rewrite("#top"), None);
+
+        let pipeline = LinkPipeline::new()
+            .then(NormalizeAnchors)
+            .then(ExtensionMap::markdown_to_html())
+            .then(BaseUrl::new("https://example.com/docs/"));
+        assert_eq!(pipeline.len(), 3);
+        assert_eq!(
+            pipeline.rewrite("setup.md#First Steps").as_deref(),
+            Some("https://example.com/docs/setup.html#first-steps")
+        );
+
+        // Order matters: the base URL makes the path absolute, so the
+        // extension map no longer applies
+        let reversed = LinkPipeline::new()
+            .then(BaseUrl::new("https://example.com/docs/"))
+            .then(ExtensionMap::markdown_to_html());
+        assert_eq!(
+            reversed.rewrite("setup.md").as_deref(),
+            Some("https://example.com/docs/setup.md")
+        );
+
+        let mut doc = Document::try_from(Text::<Markdown>::new(SOURCE)).unwrap();
+        let changed = doc.rewrite_links_with(&ExtensionMap::markdown_to_html());
+        assert_eq!(changed, 2);
+        assert_eq!(doc.links().links[0].url, "guide.html");
+    }
+}
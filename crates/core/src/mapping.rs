@@ -0,0 +1,551 @@
+//! Following positions and text ranges through document changes
+//!
+//! Selections, annotations, comments and tracked changes point at byte
+//! offsets in a node's offset text. When the document changes, a
+//! [`PositionMapper`] compares the top-level nodes before and after by
+//! content hash: nodes outside the changed stretch keep their offsets,
+//! shifted to their new index, and offsets in changed nodes are mapped
+//! through the common prefix and suffix of the old and new text. Only the
+//! nodes a command reports as affected are hashed again, and commands that
+//! reorder nodes report the new order, which comparing nodes can't tell
+//! from an edit.
+
+use crate::offsets::offset_text;
+use crate::{Document, Node, Position, Selection};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// How a range that an edit touches is mapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RangeMode {
+    /// The range is dropped, as its text needs to be looked at again
+    Strict,
+    /// The range grows or shrinks with the edit
+    Adjust,
+}
+
+/// Offset texts of the nodes in a top-level node, by path below it
+type NodeTexts = HashMap<Vec<usize>, String>;
+
+fn node_texts(node: &Node) -> NodeTexts {
+    let mut texts = HashMap::new();
+    node.walk_with_path(&mut vec![0], &mut |node, path| {
+        texts.insert(path[1..].to_vec(), offset_text(node));
+    });
+    texts
+}
+
+/// Returns true if `range` fits the text of the paragraph or heading at
+/// `path`
+pub(crate) fn range_fits(document: &Document, path: &[usize], range: &Range<usize>) -> bool {
+    let Some(node) = path.first().and_then(|&index| document.nodes.get(index)) else {
+        return false;
+    };
+    let mut fits = false;
+    node.walk_with_path(&mut vec![path[0]], &mut |node, node_path| {
+        if node_path == path && matches!(node, Node::Paragraph { .. } | Node::Heading { .. }) {
+            let text = offset_text(node);
+            fits = range.start <= range.end
+                && range.end <= text.len()
+                && text.is_char_boundary(range.start)
+                && text.is_char_boundary(range.end);
+        }
+    });
+    fits
+}
+
+/// The lengths of the common prefix and suffix of two texts, counting
+/// only the ends known to be unchanged
+fn common_ends(old: &str, new: &str, keep_start: bool, keep_end: bool) -> (usize, usize) {
+    let prefix: usize = match keep_start {
+        true => old
+            .chars()
+            .zip(new.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum(),
+        false => 0,
+    };
+    let suffix: usize = match keep_end {
+        true => old
+            .chars()
+            .rev()
+            .zip(new.chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum::<usize>()
+            .min(old.len().min(new.len()) - prefix),
+        false => 0,
+    };
+    (prefix, suffix)
+}
+
+/// Maps a range from `old` to `new` text
+///
+/// With `keep_start` the texts' common prefix is known to be unchanged, and
+/// with `keep_end` their common suffix; the edit replaced what lies between.
+fn map_range(
+    old: &str,
+    new: &str,
+    range: &Range<usize>,
+    keep_start: bool,
+    keep_end: bool,
+    mode: RangeMode,
+) -> Option<Range<usize>> {
+    let (prefix, suffix) = common_ends(old, new, keep_start, keep_end);
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    let map = |offset: usize, end: bool| {
+        if offset <= prefix {
+            Some(offset)
+        } else if offset >= old_end {
+            Some(offset - old_end + new_end)
+        } else {
+            match mode {
+                RangeMode::Strict => None,
+                RangeMode::Adjust if end => Some(new_end),
+                RangeMode::Adjust => Some(prefix),
+            }
+        }
+    };
+
+    match mode {
+        RangeMode::Strict if keep_start && range.end <= prefix => Some(range.clone()),
+        RangeMode::Strict if keep_end && range.start >= old_end => {
+            Some(map(range.start, false)?..map(range.end, true)?)
+        }
+        RangeMode::Strict => None,
+        RangeMode::Adjust => {
+            let start = map(range.start, false)?;
+            Some(start..map(range.end, true)?.max(start))
+        }
+    }
+}
+
+/// Maps an offset from `old` to `new` text, as [`map_range`] does
+///
+/// An offset where text was inserted moves past it, and one inside
+/// replaced text moves to the end of the replacement, or gives `None` with
+/// `strict`.
+fn map_offset(
+    old: &str,
+    new: &str,
+    offset: usize,
+    keep_start: bool,
+    keep_end: bool,
+    strict: bool,
+) -> Option<usize> {
+    let (prefix, suffix) = common_ends(old, new, keep_start, keep_end);
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    if offset < prefix || (offset == prefix && old_end > prefix) {
+        Some(offset)
+    } else if offset >= old_end {
+        Some(offset - old_end + new_end)
+    } else {
+        (!strict).then_some(new_end)
+    }
+}
+
+/// Top-level nodes a command reordered, as old index to new index
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NodeMoves {
+    /// First index of the reordered stretch
+    start: usize,
+    /// New index of each node of the stretch, in old order
+    targets: Vec<usize>,
+}
+
+impl NodeMoves {
+    fn apply(&self, index: usize) -> usize {
+        index
+            .checked_sub(self.start)
+            .and_then(|offset| self.targets.get(offset))
+            .copied()
+            .unwrap_or(index)
+    }
+}
+
+/// What a change reported about the top-level nodes it touched
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct NodeChanges {
+    /// Nodes that may have changed, as indices after the change; nodes
+    /// outside are taken as unchanged without comparing them
+    pub(crate) affected: Option<Range<usize>>,
+    /// For each node in `affected`, the index it had before the change,
+    /// when the change reordered them
+    pub(crate) order: Option<Vec<usize>>,
+}
+
+/// Hashes and texts of a document's top-level nodes, kept up to date
+/// across changes to map positions from one version to the next
+///
+/// Texts of unchanged nodes are kept, so only the changed nodes are read
+/// again.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DocumentTexts {
+    hashes: Vec<u64>,
+    texts: Vec<Rc<NodeTexts>>,
+}
+
+/// Number of equal leading items
+fn common_prefix(a: impl Iterator<Item = u64>, b: impl Iterator<Item = u64>) -> usize {
+    a.zip(b).take_while(|(a, b)| a == b).count()
+}
+
+impl DocumentTexts {
+    pub(crate) fn new(document: &Document) -> Self {
+        Self {
+            hashes: document.nodes.iter().map(Node::content_hash).collect(),
+            texts: document
+                .nodes
+                .iter()
+                .map(|node| Rc::new(node_texts(node)))
+                .collect(),
+        }
+    }
+
+    /// Applies a reordering of the nodes in `range`, returning the moves
+    /// if `order` is a permutation of the range
+    fn reorder(&mut self, range: &Range<usize>, order: &[usize]) -> Option<NodeMoves> {
+        let mut targets = vec![usize::MAX; range.len()];
+        if range.end > self.hashes.len() || order.len() != range.len() {
+            return None;
+        }
+        for (offset, &old) in order.iter().enumerate() {
+            let slot = targets.get_mut(old.checked_sub(range.start)?)?;
+            if *slot != usize::MAX {
+                return None;
+            }
+            *slot = range.start + offset;
+        }
+        let hashes: Vec<u64> = order.iter().map(|&old| self.hashes[old]).collect();
+        let texts: Vec<Rc<NodeTexts>> = order.iter().map(|&old| self.texts[old].clone()).collect();
+        self.hashes.splice(range.clone(), hashes);
+        self.texts.splice(range.clone(), texts);
+        Some(NodeMoves {
+            start: range.start,
+            targets,
+        })
+    }
+
+    /// Moves to a new version of the document, returning the mapper from
+    /// the recorded version to it
+    ///
+    /// Without an affected range every node is hashed and compared.
+    pub(crate) fn update(&mut self, document: &Document, changes: NodeChanges) -> PositionMapper {
+        // Moved nodes keep their text, so positions follow them
+        let moves = match (&changes.affected, &changes.order) {
+            (Some(range), Some(order)) => self.reorder(range, order).map(Rc::new),
+            _ => None,
+        };
+
+        let (old_len, new_len) = (self.hashes.len(), document.nodes.len());
+        let (before, after) = match changes.affected {
+            Some(range)
+                if range.start <= range.end
+                    && range.end <= new_len
+                    && range.start + (new_len - range.end) <= old_len =>
+            {
+                (range.start, new_len - range.end)
+            }
+            _ => (0, 0),
+        };
+        let window: Vec<u64> = document.nodes[before..new_len - after]
+            .iter()
+            .map(Node::content_hash)
+            .collect();
+        let old_window = &self.hashes[before..old_len - after];
+        let prefix = before + common_prefix(old_window.iter().copied(), window.iter().copied());
+        let suffix = after
+            + common_prefix(
+                old_window.iter().rev().copied(),
+                window.iter().rev().copied(),
+            )
+            .min(old_window.len().min(window.len()) - (prefix - before));
+        let (old_end, new_end) = (old_len - suffix, new_len - suffix);
+
+        let changed: Vec<Rc<NodeTexts>> = document.nodes[prefix..new_end]
+            .iter()
+            .map(|node| Rc::new(node_texts(node)))
+            .collect();
+        let removed = self
+            .texts
+            .splice(prefix..old_end, changed.iter().cloned())
+            .collect();
+        self.hashes.splice(before..old_len - after, window);
+        PositionMapper {
+            moves,
+            prefix,
+            old_end,
+            new_end,
+            old: removed,
+            new: changed,
+        }
+    }
+}
+
+/// Maps positions and ranges from before a change to after it
+///
+/// An [`Editor`](crate::Editor) keeps the mapper of its last command, undo
+/// or redo, see [`Editor::position_mapper`](crate::Editor::position_mapper),
+/// for anything holding positions outside the document, such as
+/// collaborators' cursors or search highlights. The default mapper leaves
+/// positions as they are.
+#[derive(Debug, Clone, Default)]
+pub struct PositionMapper {
+    /// Top-level nodes moved before the rest of the change
+    moves: Option<Rc<NodeMoves>>,
+    /// Unchanged top-level nodes at the start
+    prefix: usize,
+    /// End of the changed nodes before the change
+    old_end: usize,
+    /// End of the changed nodes after the change
+    new_end: usize,
+    /// Texts of the changed nodes before the change
+    old: Vec<Rc<NodeTexts>>,
+    /// Texts of the changed nodes after the change
+    new: Vec<Rc<NodeTexts>>,
+}
+
+impl PositionMapper {
+    /// Top-level nodes that changed, as indices into the new document, or
+    /// `None` if none did
+    pub(crate) fn changed_nodes(&self) -> Option<Range<usize>> {
+        (self.old_end > self.prefix || self.new_end > self.prefix)
+            .then_some(self.prefix..self.new_end)
+    }
+
+    /// Index of a top-level node after the moves, before the rest of the
+    /// change
+    fn moved(&self, index: usize) -> usize {
+        self.moves
+            .as_ref()
+            .map_or(index, |moves| moves.apply(index))
+    }
+
+    /// Maps the index of a top-level node, or returns `None` if the node was
+    /// removed or replaced
+    ///
+    /// The first changed node keeps its index, as a node edited in place or
+    /// split does, unless the change only inserted nodes before it.
+    pub(crate) fn map_index(&self, index: usize) -> Option<usize> {
+        let index = self.moved(index);
+        if index < self.prefix
+            || (index == self.prefix && self.old_end > self.prefix && self.new_end > self.prefix)
+        {
+            Some(index)
+        } else if index >= self.old_end {
+            Some(index - self.old_end + self.new_end)
+        } else {
+            None
+        }
+    }
+
+    /// Maps a value located in the node at `path` with `map`, which gets the
+    /// old and new text and whether their start and end are unchanged
+    ///
+    /// The first changed top-level node keeps its start and the last its
+    /// end, so a position in a split paragraph follows the part holding it.
+    fn map_in<T: Clone>(
+        &self,
+        path: &[usize],
+        value: T,
+        map: impl Fn(&str, &str, T, bool, bool) -> Option<T>,
+    ) -> Option<(Vec<usize>, T)> {
+        let index = self.moved(*path.first()?);
+        let mut path = path.to_vec();
+        path[0] = index;
+        if index < self.prefix {
+            return Some((path, value));
+        }
+        if index >= self.old_end {
+            path[0] = index - self.old_end + self.new_end;
+            return Some((path, value));
+        }
+        let old_text = self.old[index - self.prefix].get(&path[1..])?;
+
+        let single = self.old_end - self.prefix == 1 && self.new_end - self.prefix == 1;
+        let mut candidates = Vec::new();
+        if index == self.prefix && self.new_end > self.prefix {
+            candidates.push((self.prefix, true, single));
+        }
+        if index + 1 == self.old_end && self.new_end > self.prefix && !single {
+            candidates.push((self.new_end - 1, false, true));
+        }
+        for (target, keep_start, keep_end) in candidates {
+            path[0] = target;
+            let Some(new_text) = self.new[target - self.prefix].get(&path[1..]) else {
+                continue;
+            };
+            if let Some(value) = map(old_text, new_text, value.clone(), keep_start, keep_end) {
+                return Some((path, value));
+            }
+        }
+        None
+    }
+
+    /// Maps a range, returning its new path and range, or `None` if it was
+    /// removed with its node or, in strict mode, touched by the edit
+    pub(crate) fn map(
+        &self,
+        path: &[usize],
+        range: &Range<usize>,
+        mode: RangeMode,
+    ) -> Option<(Vec<usize>, Range<usize>)> {
+        self.map_in(
+            path,
+            range.clone(),
+            |old, new, range, keep_start, keep_end| {
+                map_range(old, new, &range, keep_start, keep_end, mode)
+            },
+        )
+    }
+
+    /// Maps a position, or returns `None` if its node was removed
+    ///
+    /// A position where text was inserted moves past the text, and one
+    /// inside replaced text moves to the end of the replacement.
+    pub fn map_position(&self, position: &Position) -> Option<Position> {
+        // Prefer the part of a split node whose unchanged text holds the
+        // position
+        let map = |strict| {
+            self.map_in(
+                &position.path,
+                position.offset,
+                |old, new, offset, start, end| map_offset(old, new, offset, start, end, strict),
+            )
+        };
+        let (path, offset) = map(true).or_else(|| map(false))?;
+        Some(Position::new(path, offset))
+    }
+
+    /// Maps a byte range of the node at `path`, growing or shrinking it with
+    /// edits inside, or returns `None` if its node was removed
+    pub fn map_range(
+        &self,
+        path: &[usize],
+        range: Range<usize>,
+    ) -> Option<(Vec<usize>, Range<usize>)> {
+        self.map(path, &range, RangeMode::Adjust)
+    }
+
+    /// Maps both ends of a selection, or returns `None` if the node of
+    /// either end was removed
+    pub fn map_selection(&self, selection: &Selection) -> Option<Selection> {
+        let start = self.map_position(&selection.start)?;
+        if selection.is_collapsed {
+            return Some(Selection::collapsed(start));
+        }
+        Some(Selection::new(start, self.map_position(&selection.end)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_range() {
+        use RangeMode::{Adjust, Strict};
+
+        // "wrold" is at 6..11 and "foo" at 12..15
+        let old = "Hello wrold foo";
+        let map =
+            |new: &str, range: Range<usize>, mode| map_range(old, new, &range, true, true, mode);
+        assert_eq!(map("Hi, Hello wrold foo", 12..15, Strict), Some(16..19));
+        assert_eq!(map("Hello wrold foo!", 6..11, Strict), Some(6..11));
+        assert_eq!(map("Hello world foo", 6..11, Strict), None);
+        assert_eq!(map("Hello world foo", 6..11, Adjust), Some(6..11));
+        assert_eq!(map("Hello wrld foo", 0..15, Adjust), Some(0..14));
+        assert_eq!(map("Hello foo", 6..11, Adjust), Some(6..6));
+        assert_eq!(
+            map_range(old, "old foo", &(12..15), false, true, Strict),
+            Some(4..7)
+        );
+        assert_eq!(
+            map_range(old, "Hello wr", &(6..11), true, false, Adjust),
+            Some(6..8)
+        );
+    }
+
+    #[test]
+    fn test_position_mapper() {
+        let mut doc = Document::new();
+        for text in ["One", "Hello world", "Three"] {
+            doc.add_paragraph_with_text(text);
+        }
+        let mut texts = DocumentTexts::new(&doc);
+        let position = |index, offset| Position::new(vec![index], offset);
+
+        doc.nodes[1] = Node::paragraph("Hello, world");
+        let mapper = texts.update(&doc, NodeChanges::default());
+        assert_eq!(mapper.changed_nodes(), Some(1..2));
+        assert_eq!(mapper.map_position(&position(0, 2)), Some(position(0, 2)));
+        assert_eq!(mapper.map_position(&position(1, 4)), Some(position(1, 4)));
+        assert_eq!(mapper.map_position(&position(1, 5)), Some(position(1, 6)));
+        assert_eq!(mapper.map_position(&position(1, 11)), Some(position(1, 12)));
+        assert_eq!(mapper.map_range(&[1], 0..11), Some((vec![1], 0..12)));
+
+        doc.nodes.remove(0);
+        let mapper = texts.update(&doc, NodeChanges::default());
+        assert_eq!(mapper.changed_nodes(), Some(0..0));
+        assert_eq!(mapper.map_position(&position(0, 2)), None);
+        assert_eq!(mapper.map_position(&position(1, 6)), Some(position(0, 6)));
+        assert_eq!(mapper.map_position(&position(2, 3)), Some(position(1, 3)));
+
+        // Splitting a paragraph keeps positions with the part holding them
+        doc.nodes[0] = Node::paragraph("Hello,");
+        doc.nodes.insert(1, Node::paragraph(" world"));
+        let mapper = texts.update(&doc, NodeChanges::default());
+        assert_eq!(mapper.map_position(&position(0, 3)), Some(position(0, 3)));
+        assert_eq!(mapper.map_position(&position(0, 9)), Some(position(1, 3)));
+        assert!(
+            texts
+                .update(&doc, NodeChanges::default())
+                .changed_nodes()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_reported_node_changes() {
+        let mut doc = Document::new();
+        for text in ["One", "Two", "Three", "Four"] {
+            doc.add_paragraph_with_text(text);
+        }
+        let mut texts = DocumentTexts::new(&doc);
+        let position = |index, offset| Position::new(vec![index], offset);
+        let changes = |affected, order| NodeChanges {
+            affected: Some(affected),
+            order,
+        };
+
+        // A wider range than the edit is narrowed to the nodes that changed
+        doc.nodes[1] = Node::paragraph("Two!");
+        let mapper = texts.update(&doc, changes(0..3, None));
+        assert_eq!(mapper.changed_nodes(), Some(1..2));
+        assert_eq!(mapper.map_position(&position(2, 3)), Some(position(2, 3)));
+
+        // Reordered nodes take their positions along
+        doc.nodes[1..4].rotate_left(1);
+        let mapper = texts.update(&doc, changes(1..4, Some(vec![2, 3, 1])));
+        assert_eq!(mapper.changed_nodes(), None);
+        assert_eq!(mapper.map_position(&position(1, 4)), Some(position(3, 4)));
+        assert_eq!(mapper.map_position(&position(3, 2)), Some(position(2, 2)));
+        assert_eq!(mapper.map_index(0), Some(0));
+
+        // Moves combine with edits of the moved nodes
+        doc.nodes.swap(0, 1);
+        doc.nodes[1] = Node::paragraph("Uno");
+        let mapper = texts.update(&doc, changes(0..2, Some(vec![1, 0])));
+        assert_eq!(mapper.changed_nodes(), Some(1..2));
+        assert_eq!(mapper.map_position(&position(1, 2)), Some(position(0, 2)));
+        assert_eq!(mapper.map_position(&position(0, 3)), Some(position(1, 3)));
+        assert!(
+            texts
+                .update(&doc, NodeChanges::default())
+                .changed_nodes()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Mention parsing and link resolution
+//!
+//! [`InlineNode::Mention`] stores who or what is mentioned, but not where it
+//! lives: the same `@octocat` may point at a GitHub profile in one app and
+//! at an internal directory in another. A [`MentionResolver`] passed to
+//! [`HtmlOptions::with_mention_resolver`](crate::HtmlOptions::with_mention_resolver)
+//! supplies the URL at export time, and [`MentionUrls`] covers the common
+//! case of one URL template per mention type.
+//!
+//! Markdown has no mention syntax, so `@user` and `#123` are only turned
+//! into mentions when asked to, with [`Document::parse_mentions`] or
+//! [`MarkdownOptions::with_mentions`](crate::MarkdownOptions::with_mentions).
+
+use crate::{Document, InlineNode, TextNode};
+use std::collections::BTreeMap;
+
+/// Maps mentions to the URL they link to
+///
+/// Closures taking the name and mention type and returning `Option<String>`
+/// are resolvers too.
+pub trait MentionResolver {
+    /// Returns the URL for the mention, or `None` to render it unlinked
+    fn resolve(&self, name: &str, mention_type: &str) -> Option<String>;
+}
+
+impl<F> MentionResolver for F
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    fn resolve(&self, name: &str, mention_type: &str) -> Option<String> {
+        self(name, mention_type)
+    }
+}
+
+/// Resolves mentions with one URL template per mention type
+///
+/// `{name}` in a template is replaced with the mentioned name, so
+/// `https://github.com/{name}` links `@octocat` to its profile. Mention
+/// types without a template stay unlinked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MentionUrls {
+    templates: BTreeMap<String, String>,
+}
+
+impl MentionUrls {
+    /// Creates a resolver without templates
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Links mentions of `mention_type` with `template`
+    pub fn with_template(
+        mut self,
+        mention_type: impl Into<String>,
+        template: impl Into<String>,
+    ) -> Self {
+        self.templates.insert(mention_type.into(), template.into());
+        self
+    }
+
+    /// Links user mentions with `template`
+    pub fn users(self, template: impl Into<String>) -> Self {
+        self.with_template("user", template)
+    }
+
+    /// Links issue mentions with `template`
+    pub fn issues(self, template: impl Into<String>) -> Self {
+        self.with_template("issue", template)
+    }
+}
+
+impl MentionResolver for MentionUrls {
+    fn resolve(&self, name: &str, mention_type: &str) -> Option<String> {
+        let template = self.templates.get(mention_type)?;
+        Some(template.replace("{name}", name))
+    }
+}
+
+/// Finds `@user` and `#123` patterns, as `(start, end, mention)` with byte
+/// offsets covering the sigil
+///
+/// A sigil only starts a mention at the start of the text or after a
+/// character that can't be part of a word, URL or email address.
+fn find_mentions(text: &str) -> Vec<(usize, usize, InlineNode)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut mentions = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, sigil)) = chars.next() {
+        let boundary = previous.is_none_or(|c| !is_word(c) && !matches!(c, '@' | '#' | '/' | '&'));
+        previous = Some(sigil);
+        if !boundary || !matches!(sigil, '@' | '#') {
+            continue;
+        }
+
+        let rest = &text[start + 1..];
+        let (name, mention) = if sigil == '@' {
+            // Usernames may contain hyphens, but not end in one
+            let len = rest
+                .find(|c: char| !(is_word(c) || c == '-'))
+                .unwrap_or(rest.len());
+            let name = rest[..len].trim_end_matches('-');
+            if !name.starts_with(|c: char| c.is_alphanumeric()) {
+                continue;
+            }
+            (name, InlineNode::user_mention(name))
+        } else {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if len == 0 || rest[len..].starts_with(is_word) {
+                continue;
+            }
+            (&rest[..len], InlineNode::issue_mention(&rest[..len]))
+        };
+
+        let end = start + 1 + name.len();
+        mentions.push((start, end, mention));
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+        previous = text[..end].chars().next_back();
+    }
+    mentions
+}
+
+/// Splits mentions out of the text runs of `inlines`, returning how many
+/// were found
+///
+/// Code and link text are left alone.
+fn split_mentions(inlines: &mut Vec<InlineNode>) -> usize {
+    let mut found = 0;
+    let mut result = Vec::with_capacity(inlines.len());
+    for mut inline in std::mem::take(inlines) {
+        match &mut inline {
+            InlineNode::Text(TextNode { text, formatting }) if !formatting.code => {
+                let mentions = find_mentions(text);
+                if mentions.is_empty() {
+                    result.push(inline);
+                    continue;
+                }
+                let mut last = 0;
+                for (start, end, mention) in mentions {
+                    if start > last {
+                        result.push(InlineNode::Text(TextNode::with_formatting(
+                            &text[last..start],
+                            formatting.clone(),
+                        )));
+                    }
+                    result.push(mention);
+                    last = end;
+                    found += 1;
+                }
+                if last < text.len() {
+                    result.push(InlineNode::Text(TextNode::with_formatting(
+                        &text[last..],
+                        formatting.clone(),
+                    )));
+                }
+            }
+            InlineNode::InlineFootnote { children } => {
+                found += split_mentions(children);
+                result.push(inline);
+            }
+            _ => result.push(inline),
+        }
+    }
+    *inlines = result;
+    found
+}
+
+impl Document {
+    /// Turns `@user` and `#123` patterns in text into mentions
+    ///
+    /// Code, code blocks and link text are skipped, as are email addresses
+    /// and patterns inside words. Returns the number of mentions created.
+    pub fn parse_mentions(&mut self) -> usize {
+        let mut found = 0;
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| {
+                for inlines in block.inline_runs_mut() {
+                    found += split_mentions(inlines);
+                }
+            });
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Node, Text};
+
+    #[test]
+    fn test_parse_mentions() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "Thanks @octo-cat, see #42 and **@bold**.\n\n\
+             Mail me@example.com, not #42a, `@code` or [@link](https://example.com).\n\n\
+             - @alice-: fixes #7\n",
+        ))
+        .unwrap();
+        assert_eq!(doc.parse_mentions(), 5);
+
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap()[..4],
+            [
+                InlineNode::text("Thanks "),
+                InlineNode::user_mention("octo-cat"),
+                InlineNode::text(", see "),
+                InlineNode::issue_mention("42"),
+            ]
+        );
+        assert!(
+            doc.nodes[0]
+                .as_paragraph()
+                .unwrap()
+                .contains(&InlineNode::user_mention("bold"))
+        );
+        assert!(
+            !doc.nodes[1]
+                .as_paragraph()
+                .unwrap()
+                .iter()
+                .any(|inline| matches!(inline, InlineNode::Mention { .. }))
+        );
+        let Node::List { items, .. } = &doc.nodes[2] else {
+            panic!("expected a list");
+        };
+        assert_eq!(
+            items[0].children[0].as_paragraph().unwrap()[..3],
+            [
+                InlineNode::user_mention("alice"),
+                InlineNode::text("-: fixes "),
+                InlineNode::issue_mention("7"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mention_urls() {
+        let urls = MentionUrls::new()
+            .users("https://github.com/{name}")
+            .issues("https://github.com/org/repo/issues/{name}");
+        assert_eq!(
+            urls.resolve("octocat", "user").as_deref(),
+            Some("https://github.com/octocat")
+        );
+        assert_eq!(
+            urls.resolve("42", "issue").as_deref(),
+            Some("https://github.com/org/repo/issues/42")
+        );
+        assert_eq!(urls.resolve("ops", "team"), None);
+    }
+}
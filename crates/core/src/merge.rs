@@ -0,0 +1,545 @@
+//! Three-way merging of documents
+//!
+//! Two versions edited from a common base are merged block by block, the
+//! way version control merges lines: blocks are matched against the base,
+//! and between the blocks both versions kept, a region changed on one side
+//! only takes that side's blocks. A region both sides changed differently
+//! becomes a [`Node::Conflict`] holding both, unless the regions have as
+//! many blocks and can be merged block by block, leaving conflicts only
+//! where both sides changed the same block.
+//!
+//! [`three_way`] merges two documents and reports attributes both sides
+//! changed; [`merge_documents`] returns just the merged document.
+//! Conflicts are resolved by choosing a side, e.g. with
+//! [`Editor::resolve_conflict`](crate::Editor::resolve_conflict).
+
+use crate::{Document, Node, NodeAttributes};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// The side of a conflict to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictSide {
+    /// Keep our blocks
+    Ours,
+    /// Keep their blocks
+    Theirs,
+    /// Keep our blocks followed by theirs
+    Both,
+}
+
+impl ConflictSide {
+    /// The blocks of a conflict this side keeps
+    pub(crate) fn choose(self, ours: Vec<Node>, theirs: Vec<Node>) -> Vec<Node> {
+        match self {
+            Self::Ours => ours,
+            Self::Theirs => theirs,
+            Self::Both => ours.into_iter().chain(theirs).collect(),
+        }
+    }
+}
+
+/// The result of [`three_way`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeOutcome {
+    /// The merged document
+    pub document: Document,
+    /// Indices of the merged top-level nodes whose attributes both sides
+    /// changed differently; they keep our attributes
+    pub attribute_conflicts: Vec<usize>,
+}
+
+/// Merges the changes `ours` and `theirs` made to `base`
+///
+/// The merged document has the blocks of both sides, with a
+/// [`Node::Conflict`] wherever they changed the same blocks differently.
+/// Node attributes are merged node by node the same way, following each
+/// node to its merged index; a node whose attributes both sides changed
+/// differently keeps ours and is reported in
+/// [`MergeOutcome::attribute_conflicts`]. Conflict nodes have no attributes.
+/// Metadata is merged as a whole, preferring ours when both sides changed
+/// it; comments, tracked changes and the bibliography are ours, and the
+/// selection is cleared.
+pub fn three_way(base: &Document, ours: &Document, theirs: &Document) -> MergeOutcome {
+    let metadata = if ours.metadata == base.metadata {
+        theirs.metadata.clone()
+    } else {
+        ours.metadata.clone()
+    };
+    let mut merge = Merge::new(&base.nodes, &ours.nodes, &theirs.nodes);
+    merge.run(&ours.nodes, &theirs.nodes);
+
+    let mut node_attributes = BTreeMap::new();
+    let mut attribute_conflicts = Vec::new();
+    for (index, origin) in merge.origins.iter().enumerate() {
+        let Some(origin) = origin else {
+            continue;
+        };
+        let base_attributes = attributes(base, origin.base);
+        let our_attributes = attributes(ours, origin.ours);
+        let their_attributes = attributes(theirs, origin.theirs);
+        let merged = if our_attributes == their_attributes || their_attributes == base_attributes {
+            our_attributes
+        } else if our_attributes == base_attributes {
+            their_attributes
+        } else {
+            attribute_conflicts.push(index);
+            our_attributes
+        };
+        if let Some(merged) = merged {
+            node_attributes.insert(index, merged.clone());
+        }
+    }
+
+    MergeOutcome {
+        document: Document {
+            nodes: merge.nodes,
+            selection: None,
+            selections: Vec::new(),
+            metadata,
+            comments: ours.comments.clone(),
+            tracked_changes: ours.tracked_changes.clone(),
+            node_attributes,
+            bibliography: ours.bibliography.clone(),
+        },
+        attribute_conflicts,
+    }
+}
+
+/// The attributes of the top-level node at `index`, if it has any
+fn attributes(document: &Document, index: Option<usize>) -> Option<&NodeAttributes> {
+    index
+        .and_then(|index| document.node_attributes.get(&index))
+        .filter(|attributes| !attributes.is_empty())
+}
+
+/// Merges the changes `ours` and `theirs` made to `base`, like
+/// [`three_way`] without reporting attribute conflicts
+pub fn merge_documents(base: &Document, ours: &Document, theirs: &Document) -> Document {
+    three_way(base, ours, theirs).document
+}
+
+/// For each node of `base`, the index of the node of `other` it is matched
+/// with by a longest common subsequence
+fn matches(base: &[Node], other: &[Node]) -> Vec<Option<usize>> {
+    let (n, m) = (base.len(), other.len());
+    // lcs[i][j] holds the LCS length of base[i..] and other[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if base[i] == other[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut matched = vec![None; n];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            matched[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matched
+}
+
+/// A change one side made: the base blocks in `base` replaced by its
+/// blocks in `nodes`
+#[derive(Debug, Clone)]
+struct Hunk {
+    base: Range<usize>,
+    nodes: Range<usize>,
+}
+
+impl Hunk {
+    /// Returns true if both hunks change the same base blocks, or insert
+    /// at the same place
+    fn overlaps(&self, range: &Range<usize>) -> bool {
+        (self.base.start < range.end && range.start < self.base.end)
+            || (self.base.start == range.start && self.base.is_empty() && range.is_empty())
+    }
+}
+
+/// The changes that turn the base into a side of `len` blocks, given the
+/// side's block matched with each base block, in order
+fn hunks(matched: &[Option<usize>], len: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut b, mut o) = (0, 0);
+    let matched = matched
+        .iter()
+        .enumerate()
+        .filter_map(|(i, j)| Some((i, (*j)?)))
+        .chain([(matched.len(), len)]);
+    for (i, j) in matched {
+        if b < i || o < j {
+            hunks.push(Hunk {
+                base: b..i,
+                nodes: o..j,
+            });
+        }
+        (b, o) = (i + 1, j + 1);
+    }
+    hunks
+}
+
+/// Where a merged block came from, as its index in the base and each side
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    base: Option<usize>,
+    ours: Option<usize>,
+    theirs: Option<usize>,
+}
+
+/// The blocks a side has in place of some base blocks, each with the index
+/// of the base block it kept, if any, and its own index
+struct SideBlocks {
+    nodes: Vec<Node>,
+    origins: Vec<(Option<usize>, usize)>,
+}
+
+/// A merge in progress, with the merged blocks so far
+struct Merge<'a> {
+    base: &'a [Node],
+    /// For each base block, the block of ours it was kept as
+    our_matches: Vec<Option<usize>>,
+    /// For each base block, the block of theirs it was kept as
+    their_matches: Vec<Option<usize>>,
+    nodes: Vec<Node>,
+    /// Where each merged block came from; conflicts come from neither side
+    origins: Vec<Option<Origin>>,
+}
+
+impl<'a> Merge<'a> {
+    fn new(base: &'a [Node], ours: &[Node], theirs: &[Node]) -> Self {
+        Self {
+            base,
+            our_matches: matches(base, ours),
+            their_matches: matches(base, theirs),
+            nodes: Vec::new(),
+            origins: Vec::new(),
+        }
+    }
+
+    /// Keeps the base blocks in `range`, which neither side changed
+    fn keep_base(&mut self, range: Range<usize>) {
+        for index in range {
+            self.nodes.push(self.base[index].clone());
+            self.origins.push(Some(Origin {
+                base: Some(index),
+                ours: self.our_matches[index],
+                theirs: self.their_matches[index],
+            }));
+        }
+    }
+
+    /// The blocks a side has in place of the base blocks in `range`, given
+    /// its hunks within the range
+    fn side_blocks(
+        &self,
+        side: &[Node],
+        matched: &[Option<usize>],
+        hunks: &[Hunk],
+        range: &Range<usize>,
+    ) -> SideBlocks {
+        let mut blocks = SideBlocks {
+            nodes: Vec::new(),
+            origins: Vec::new(),
+        };
+        let kept = |blocks: &mut SideBlocks, range: Range<usize>| {
+            for index in range {
+                blocks.nodes.push(self.base[index].clone());
+                blocks
+                    .origins
+                    .push((Some(index), matched[index].unwrap_or_default()));
+            }
+        };
+        let mut position = range.start;
+        for hunk in hunks {
+            kept(&mut blocks, position..hunk.base.start);
+            blocks.nodes.extend_from_slice(&side[hunk.nodes.clone()]);
+            blocks
+                .origins
+                .extend(hunk.nodes.clone().map(|index| (None, index)));
+            position = hunk.base.end;
+        }
+        kept(&mut blocks, position..range.end);
+        blocks
+    }
+
+    fn run(&mut self, ours: &[Node], theirs: &[Node]) {
+        let mut our_hunks = hunks(&self.our_matches, ours.len()).into_iter().peekable();
+        let mut their_hunks = hunks(&self.their_matches, theirs.len())
+            .into_iter()
+            .peekable();
+        let mut position = 0;
+        loop {
+            // The next change, grown with the changes of both sides
+            // overlapping it
+            let mut range = match (our_hunks.peek(), their_hunks.peek()) {
+                (None, None) => {
+                    self.keep_base(position..self.base.len());
+                    return;
+                }
+                (Some(our), Some(their)) if their.base.start < our.base.start => their.base.clone(),
+                (Some(our), _) => our.base.clone(),
+                (None, Some(their)) => their.base.clone(),
+            };
+            let (mut our_group, mut their_group) = (Vec::new(), Vec::new());
+            loop {
+                if let Some(hunk) = our_hunks.next_if(|hunk| hunk.overlaps(&range)) {
+                    range = range.start.min(hunk.base.start)..range.end.max(hunk.base.end);
+                    our_group.push(hunk);
+                } else if let Some(hunk) = their_hunks.next_if(|hunk| hunk.overlaps(&range)) {
+                    range = range.start.min(hunk.base.start)..range.end.max(hunk.base.end);
+                    their_group.push(hunk);
+                } else {
+                    break;
+                }
+            }
+
+            self.keep_base(position..range.start);
+            position = range.end;
+            let our_blocks = self.side_blocks(ours, &self.our_matches, &our_group, &range);
+            let their_blocks = self.side_blocks(theirs, &self.their_matches, &their_group, &range);
+            self.merge_region(range, our_blocks, their_blocks);
+        }
+    }
+
+    /// Takes the blocks one side has in place of the base blocks in
+    /// `range`, finding the other side's block for each base block
+    ///
+    /// Blocks replacing as many base blocks are taken as edited in place.
+    fn take(&mut self, range: Range<usize>, blocks: SideBlocks, is_ours: bool) {
+        let in_place = blocks.nodes.len() == range.len();
+        for (offset, (node, (base, index))) in
+            blocks.nodes.into_iter().zip(blocks.origins).enumerate()
+        {
+            let base = base.or(in_place.then_some(range.start + offset));
+            let (ours, theirs) = if is_ours {
+                (Some(index), base.and_then(|base| self.their_matches[base]))
+            } else {
+                (base.and_then(|base| self.our_matches[base]), Some(index))
+            };
+            self.nodes.push(node);
+            self.origins.push(Some(Origin { base, ours, theirs }));
+        }
+    }
+
+    /// Merges the base blocks in `range`, which both sides changed
+    fn merge_region(&mut self, range: Range<usize>, ours: SideBlocks, theirs: SideBlocks) {
+        let base = &self.base[range.clone()];
+        if ours.nodes == theirs.nodes {
+            for ((node, (our_base, our_index)), (their_base, their_index)) in
+                ours.nodes.into_iter().zip(ours.origins).zip(theirs.origins)
+            {
+                self.nodes.push(node);
+                self.origins.push(Some(Origin {
+                    base: our_base.or(their_base),
+                    ours: Some(our_index),
+                    theirs: Some(their_index),
+                }));
+            }
+        } else if theirs.nodes == base {
+            self.take(range, ours, true);
+        } else if ours.nodes == base {
+            self.take(range, theirs, false);
+        } else if base.len() == ours.nodes.len() && base.len() == theirs.nodes.len() {
+            // Both sides changed blocks in place; only the blocks both
+            // changed differently conflict, with neighbouring conflicts kept
+            // together
+            let blocks = ours
+                .nodes
+                .into_iter()
+                .zip(ours.origins)
+                .zip(theirs.nodes.into_iter().zip(theirs.origins));
+            for (index, ((ours, (_, our_index)), (theirs, (_, their_index)))) in range.zip(blocks) {
+                let base = &self.base[index];
+                let node = if ours == theirs || theirs == *base {
+                    ours
+                } else if ours == *base {
+                    theirs
+                } else {
+                    if let Some(Node::Conflict {
+                        ours: our_blocks,
+                        theirs: their_blocks,
+                    }) = self.nodes.last_mut()
+                    {
+                        our_blocks.push(ours);
+                        their_blocks.push(theirs);
+                    } else {
+                        self.nodes.push(Node::Conflict {
+                            ours: vec![ours],
+                            theirs: vec![theirs],
+                        });
+                        self.origins.push(None);
+                    }
+                    continue;
+                };
+                self.nodes.push(node);
+                self.origins.push(Some(Origin {
+                    base: Some(index),
+                    ours: Some(our_index),
+                    theirs: Some(their_index),
+                }));
+            }
+        } else {
+            self.nodes.push(Node::Conflict {
+                ours: ours.nodes,
+                theirs: theirs.nodes,
+            });
+            self.origins.push(None);
+        }
+    }
+}
+
+/// Conflict markers around the text of both sides, as version control
+/// writes them
+pub(crate) fn conflict_markers(ours: &str, theirs: &str) -> String {
+    let mut text = String::from("<<<<<<< ours\n");
+    for side in [ours, "=======", theirs] {
+        if !side.is_empty() {
+            text.push_str(side);
+            text.push('\n');
+        }
+    }
+    text.push_str(">>>>>>> theirs");
+    text
+}
+
+impl Document {
+    /// Indices of the top-level conflicts a merge left
+    pub fn conflicts(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, Node::Conflict { .. }))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DocumentMetadata, Markdown, Text};
+
+    fn doc(markdown: &str) -> Document {
+        Document::try_from(Text::<Markdown>::new(markdown)).unwrap()
+    }
+
+    #[test]
+    fn test_merge_documents() {
+        let base = doc("# Title\n\nOne\n\nTwo\n\nThree\n");
+        let ours = doc("# Title\n\nOne!\n\nTwo\n\nThree\n\nFour\n");
+        let theirs = doc("# New title\n\nOne\n\nThree\n");
+        let merged = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged, doc("# New title\n\nOne!\n\nThree\n\nFour\n"));
+        assert!(merged.conflicts().is_empty());
+
+        let mut ours = doc("# Title\n\nUno\n\nDos\n\nThree\n");
+        ours.metadata = Some(DocumentMetadata {
+            title: Some("Ours".to_string()),
+            ..Default::default()
+        });
+        let theirs = doc("# Title\n\nEins\n\nTwo\n\nThree\n");
+        let merged = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts(), vec![1]);
+        assert_eq!(
+            merged.nodes[1],
+            Node::Conflict {
+                ours: vec![Node::paragraph("Uno")],
+                theirs: vec![Node::paragraph("Eins")],
+            }
+        );
+        assert_eq!(merged.nodes[2], Node::paragraph("Dos"));
+        assert_eq!(merged.metadata, ours.metadata);
+        assert_eq!(
+            merged.to_markdown_with(&Default::default()),
+            "# Title\n\n<<<<<<< ours\nUno\n=======\nEins\n>>>>>>> theirs\n\nDos\n\nThree"
+        );
+
+        // Regions of different lengths conflict as a whole
+        let ours = doc("# Title\n\nOne\n\nThree\n");
+        let theirs = doc("# Title\n\nOne\n\nTwo and a half\n\nMore\n\nThree\n");
+        let merged = merge_documents(&base, &ours, &theirs);
+        assert_eq!(
+            merged.nodes[2],
+            Node::Conflict {
+                ours: Vec::new(),
+                theirs: vec![Node::paragraph("Two and a half"), Node::paragraph("More")],
+            }
+        );
+        // Both sides adding different blocks at the same place conflict
+        let ours = doc("# Title\n\nOne\n\nTwo\n\nThree\n\nMine\n");
+        let theirs = doc("# Title\n\nOne\n\nTwo\n\nThree\n\nYours\n");
+        let merged = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts(), vec![4]);
+        let html = crate::Text::<crate::Html>::try_from(&merged).unwrap();
+        assert!(html.as_str().contains(
+            "<div class=\"merge-conflict\"><div class=\"conflict-ours\"><p>Mine</p></div>\
+             <div class=\"conflict-theirs\"><p>Yours</p></div></div>"
+        ));
+        assert_eq!(
+            ConflictSide::Both.choose(vec![Node::paragraph("a")], vec![Node::paragraph("b")]),
+            vec![Node::paragraph("a"), Node::paragraph("b")]
+        );
+    }
+
+    #[test]
+    fn test_three_way_node_attributes() {
+        let mut base = doc("# Title\n\nOne\n\nTwo\n\nThree\n");
+        base.set_node_attribute(1, "id", "one").unwrap();
+        base.set_node_attribute(2, "id", "two").unwrap();
+        base.set_node_attribute(3, "id", "three").unwrap();
+
+        // Ours inserts a block before the attributed ones and retags two of
+        // them; theirs edits "Two" in place and tags the title
+        let mut ours = doc("# Title\n\nNew\n\nOne\n\nTwo\n\nThree\n");
+        ours.set_node_attribute(2, "id", "one").unwrap();
+        ours.set_node_attribute(3, "id", "2").unwrap();
+        ours.set_node_attribute(4, "id", "3").unwrap();
+        let mut theirs = doc("# Title\n\nOne\n\nTwo!\n\nThree\n");
+        theirs.set_node_attribute(0, "id", "title").unwrap();
+        theirs.set_node_attribute(1, "id", "one").unwrap();
+        theirs.set_node_attribute(2, "id", "two").unwrap();
+        theirs.set_node_attribute(3, "id", "three").unwrap();
+
+        let outcome = three_way(&base, &ours, &theirs);
+        let merged = &outcome.document;
+        assert_eq!(
+            merged.nodes,
+            doc("# Title\n\nNew\n\nOne\n\nTwo!\n\nThree\n").nodes
+        );
+        let ids: Vec<Option<&str>> = (0..merged.nodes.len())
+            .map(|index| merged.node_attribute(index, "id"))
+            .collect();
+        assert_eq!(
+            ids,
+            [Some("title"), None, Some("one"), Some("2"), Some("3")]
+        );
+        assert!(outcome.attribute_conflicts.is_empty());
+
+        // Both sides changing the same node's attributes conflict, keeping ours
+        theirs.set_node_attribute(3, "id", "drei").unwrap();
+        let outcome = three_way(&base, &ours, &theirs);
+        assert_eq!(outcome.attribute_conflicts, vec![4]);
+        assert_eq!(outcome.document.node_attribute(4, "id"), Some("3"));
+
+        // Conflicting blocks don't carry attributes
+        let mut theirs = doc("# Title\n\nUno\n\nTwo\n\nThree\n");
+        theirs.set_node_attribute(1, "id", "one").unwrap();
+        let ours = doc("# Title\n\nEins\n\nTwo\n\nThree\n");
+        let outcome = three_way(&base, &ours, &theirs);
+        assert_eq!(outcome.document.conflicts(), vec![1]);
+        assert!(!outcome.document.node_attributes.contains_key(&1));
+        assert_eq!(merge_documents(&base, &ours, &theirs), outcome.document);
+    }
+}
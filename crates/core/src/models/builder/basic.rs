@@ -151,6 +151,17 @@ impl DocumentBuilder {
         self
     }
 
+    /// Adds a captioned figure to the document
+    pub fn figure(
+        mut self,
+        url: impl Into<String>,
+        alt: impl Into<String>,
+        caption: impl Into<String>,
+    ) -> Self {
+        self.document.nodes.push(Node::figure(url, alt, caption));
+        self
+    }
+
     /// Adds a math block to the document
     pub fn math_block(mut self, math: impl Into<String>) -> Self {
         self.document.nodes.push(Node::math_block(math));
@@ -220,7 +231,9 @@ mod tests {
 
         // Check title
         match &doc.nodes[0] {
-            Node::Heading { level, children } => {
+            Node::Heading {
+                level, children, ..
+            } => {
                 assert_eq!(*level, 1);
                 assert_eq!(children.len(), 1);
                 match &children[0] {
@@ -289,7 +302,9 @@ mod tests {
 
         // Check lists
         match &doc.nodes[6] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 assert_eq!(*list_type, ListType::Unordered);
                 assert_eq!(items.len(), 3);
             }
@@ -1,3 +1,4 @@
+use crate::offsets::check_offset;
 use crate::{Document, InlineNode, Node, Position, Selection, TextNode};
 
 /// Extension methods for Document to help with selections
@@ -135,6 +136,12 @@ impl Document {
             }
         }
 
+        // Neither end may split a grapheme cluster
+        let node = &self.nodes[node_index];
+        if check_offset(node, start_offset).is_err() || check_offset(node, end_offset).is_err() {
+            return false;
+        }
+
         let start = Position::new(vec![node_index], start_offset);
         let end = Position::new(vec![node_index], end_offset);
 
@@ -183,9 +190,38 @@ impl Document {
         }
     }
 
-    /// Clear the current selection
+    /// Clear the current selection, including any further selections
     pub fn clear_selection(&mut self) {
         self.selection = None;
+        self.selections.clear();
+    }
+
+    /// Adds a selection, e.g. another cursor
+    ///
+    /// Becomes the primary selection if there is none; selections already
+    /// present are not added again.
+    pub fn add_selection(&mut self, selection: Selection) {
+        if self.selection.is_none() {
+            self.selection = Some(selection);
+        } else if self.selection.as_ref() != Some(&selection)
+            && !self.selections.contains(&selection)
+        {
+            self.selections.push(selection);
+        }
+    }
+
+    /// Returns the primary selection followed by the further selections
+    pub fn all_selections(&self) -> Vec<Selection> {
+        self.selection
+            .iter()
+            .chain(&self.selections)
+            .cloned()
+            .collect()
+    }
+
+    /// Keeps only the primary selection
+    pub fn clear_secondary_selections(&mut self) {
+        self.selections.clear();
     }
 
     /// Returns true if there is an active selection
@@ -195,11 +231,25 @@ impl Document {
 
     /// Returns true if there is an active selection that spans multiple nodes
     pub fn has_multi_node_selection(&self) -> bool {
-        if let Some(selection) = &self.selection {
-            selection.start.path[0] != selection.end.path[0]
-        } else {
-            false
-        }
+        self.selection
+            .as_ref()
+            .is_some_and(|selection| selection.start.path.first() != selection.end.path.first())
+    }
+
+    /// Returns the index of the first node touched by the selection
+    pub fn current_node_index(&self) -> Option<usize> {
+        let selection = self.selection.as_ref()?;
+        let start = selection.start.path.first().copied()?;
+        let end = selection.end.path.first().copied()?;
+        Some(start.min(end))
+    }
+
+    /// Returns the index of the last node touched by the selection
+    pub fn selection_end_index(&self) -> Option<usize> {
+        let selection = self.selection.as_ref()?;
+        let start = selection.start.path.first().copied()?;
+        let end = selection.end.path.first().copied()?;
+        Some(start.max(end))
     }
 
     /// Returns the selected text as a string, if possible
@@ -370,4 +420,25 @@ mod tests {
         doc.select_node_range(0, 1);
         assert!(doc.has_multi_node_selection());
     }
+
+    #[test]
+    fn test_selection_node_indices() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("First paragraph");
+        doc.add_paragraph_with_text("Second paragraph");
+        doc.add_paragraph_with_text("Third paragraph");
+        assert_eq!(doc.current_node_index(), None);
+
+        // Backwards selections are normalised
+        doc.select_range(2, 3, 1, 0);
+        assert_eq!(doc.current_node_index(), Some(1));
+        assert_eq!(doc.selection_end_index(), Some(2));
+        assert!(doc.has_multi_node_selection());
+
+        // A selection without a node path touches no node
+        doc.selection = Some(Selection::collapsed(Position::new(vec![], 0)));
+        assert_eq!(doc.current_node_index(), None);
+        assert_eq!(doc.selection_end_index(), None);
+        assert!(!doc.has_multi_node_selection());
+    }
 }
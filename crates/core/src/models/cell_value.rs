@@ -0,0 +1,353 @@
+use super::{InlineNode, Node, TableCell, table_cell_columns};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A calendar date, serialized as `YYYY-MM-DD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CellDate {
+    /// The year
+    pub year: i32,
+    /// The month, from 1 to 12
+    pub month: u8,
+    /// The day of the month, from 1
+    pub day: u8,
+}
+
+impl CellDate {
+    /// Creates a date, or `None` if the day doesn't exist
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        (1..=days)
+            .contains(&day)
+            .then_some(Self { year, month, day })
+    }
+
+    /// Parses an ISO 8601 `YYYY-MM-DD` date
+    pub fn parse(date: &str) -> Option<Self> {
+        let mut parts = date.splitn(3, '-');
+        let year = parts.next()?;
+        let (month, day) = (parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        Self::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+}
+
+impl fmt::Display for CellDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl TryFrom<String> for CellDate {
+    type Error = String;
+
+    fn try_from(date: String) -> Result<Self, Self::Error> {
+        Self::parse(&date).ok_or_else(|| format!("invalid date: {date}"))
+    }
+}
+
+impl From<CellDate> for String {
+    fn from(date: CellDate) -> Self {
+        date.to_string()
+    }
+}
+
+/// A typed value stored alongside a table cell's display text
+///
+/// Sorting and aggregates use the value, so `9` sorts before `10` and
+/// `03/01/2024` compares as a date however the cell displays it. With a
+/// [`LocaleProvider`](crate::LocaleProvider), HTML export also formats the
+/// value for the reader's locale instead of showing the stored text.
+///
+/// Numbers are never NaN, which is what makes the type `Eq`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CellValue {
+    /// A plain number
+    Number {
+        /// The number
+        value: f64,
+    },
+    /// An amount of money
+    Currency {
+        /// The amount
+        amount: f64,
+        /// ISO 4217 currency code, e.g. `"EUR"`
+        currency: String,
+    },
+    /// A calendar date
+    Date {
+        /// The date
+        date: CellDate,
+    },
+}
+
+impl Eq for CellValue {}
+
+impl CellValue {
+    /// Creates a number, or `None` for NaN
+    pub fn number(value: f64) -> Option<Self> {
+        (!value.is_nan()).then_some(Self::Number { value })
+    }
+
+    /// Creates an amount of money, or `None` for NaN
+    pub fn currency(amount: f64, currency: impl Into<String>) -> Option<Self> {
+        (!amount.is_nan()).then(|| Self::Currency {
+            amount,
+            currency: currency.into(),
+        })
+    }
+
+    /// Creates a date value
+    pub fn date(date: CellDate) -> Self {
+        Self::Date { date }
+    }
+
+    /// Compares two values of the same kind
+    ///
+    /// Returns `None` for values of different kinds, including amounts in
+    /// different currencies.
+    pub fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Number { value: a }, Self::Number { value: b }) => a.partial_cmp(b),
+            (
+                Self::Currency {
+                    amount: a,
+                    currency: x,
+                },
+                Self::Currency {
+                    amount: b,
+                    currency: y,
+                },
+            ) if x == y => a.partial_cmp(b),
+            (Self::Date { date: a }, Self::Date { date: b }) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    /// Sort rank of the kind, so mixed columns group by kind
+    fn kind_rank(&self) -> (u8, &str) {
+        match self {
+            Self::Number { .. } => (0, ""),
+            Self::Currency { currency, .. } => (1, currency),
+            Self::Date { .. } => (2, ""),
+        }
+    }
+}
+
+/// Orders table cells for sorting
+///
+/// Cells with a typed value come first, ordered by value and grouped by
+/// kind. The rest follow, ordered by their text without regard to case.
+pub(crate) fn compare_cells(a: &TableCell, b: &TableCell) -> Ordering {
+    match (&a.value, &b.value) {
+        (Some(x), Some(y)) => x
+            .compare(y)
+            .unwrap_or_else(|| x.kind_rank().cmp(&y.kind_rank())),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => {
+            let x = InlineNode::plain_text_of(&a.content);
+            let y = InlineNode::plain_text_of(&b.content);
+            x.to_lowercase().cmp(&y.to_lowercase()).then(x.cmp(&y))
+        }
+    }
+}
+
+/// A formula computed over the typed values of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    /// Total of numbers or of amounts in one currency
+    Sum,
+    /// Mean of numbers or of amounts in one currency
+    Average,
+    /// Smallest value
+    Min,
+    /// Largest value
+    Max,
+    /// Number of cells holding a typed value
+    Count,
+}
+
+impl Aggregate {
+    /// Computes the formula over `values`
+    ///
+    /// Returns `None` when there are no values, or when they can't be
+    /// combined: sums of dates, or amounts in different currencies.
+    pub fn apply<'a>(self, values: impl IntoIterator<Item = &'a CellValue>) -> Option<CellValue> {
+        let values: Vec<&CellValue> = values.into_iter().collect();
+        if self == Self::Count {
+            return CellValue::number(values.len() as f64);
+        }
+        let (first, rest) = values.split_first()?;
+
+        match self {
+            Self::Min | Self::Max => {
+                let mut best = *first;
+                for value in rest {
+                    let ordering = value.compare(best)?;
+                    if (self == Self::Min && ordering.is_lt())
+                        || (self == Self::Max && ordering.is_gt())
+                    {
+                        best = value;
+                    }
+                }
+                Some(best.clone())
+            }
+            _ => {
+                let mut total = 0.0;
+                for value in &values {
+                    total += match (first, value) {
+                        (CellValue::Number { .. }, CellValue::Number { value }) => *value,
+                        (
+                            CellValue::Currency { currency, .. },
+                            CellValue::Currency {
+                                amount,
+                                currency: other,
+                            },
+                        ) if currency == other => *amount,
+                        _ => return None,
+                    };
+                }
+                if self == Self::Average {
+                    total /= values.len() as f64;
+                }
+                match first {
+                    CellValue::Currency { currency, .. } => {
+                        CellValue::currency(total, currency.clone())
+                    }
+                    _ => CellValue::number(total),
+                }
+            }
+        }
+    }
+}
+
+impl TableCell {
+    /// Creates a cell holding a typed value, displayed in the default
+    /// [`Locale`](crate::Locale)
+    pub fn typed(value: CellValue) -> Self {
+        Self::text(crate::LocaleProvider::format(
+            &crate::Locale::default(),
+            &value,
+        ))
+        .with_value(value)
+    }
+
+    /// Sets the typed value, leaving the display text as it is
+    pub fn with_value(mut self, value: CellValue) -> Self {
+        self.value = Some(value);
+        self
+    }
+}
+
+impl Node {
+    /// Computes a formula over the typed values in a table body column
+    ///
+    /// `column` is a logical grid column, so merged cells count once in the
+    /// column they start in. Cells without a typed value are skipped.
+    /// Returns `None` for other nodes and where [`Aggregate::apply`] does.
+    pub fn table_aggregate(&self, column: usize, aggregate: Aggregate) -> Option<CellValue> {
+        let Node::Table { rows, .. } = self else {
+            return None;
+        };
+        let columns = table_cell_columns(rows);
+        let values = rows.iter().zip(&columns).flat_map(|(row, row_columns)| {
+            row.iter()
+                .zip(row_columns)
+                .filter(|&(_, &c)| c == column)
+                .filter_map(|(cell, _)| cell.value.as_ref())
+        });
+        aggregate.apply(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TableAlignment;
+
+    fn number(value: f64) -> CellValue {
+        CellValue::number(value).unwrap()
+    }
+
+    #[test]
+    fn test_cell_date() {
+        let date = CellDate::parse("2024-02-29").unwrap();
+        assert_eq!((date.year, date.month, date.day), (2024, 2, 29));
+        assert_eq!(CellDate::parse("2023-02-29"), None);
+        assert_eq!(CellDate::parse("2024-2-1"), None);
+
+        let value = CellValue::date(date);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"type":"date","date":"2024-02-29"}"#);
+        assert_eq!(serde_json::from_str::<CellValue>(&json).unwrap(), value);
+        assert!(
+            serde_json::from_str::<CellValue>(r#"{"type":"date","date":"2024-13-01"}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_compare_cells() {
+        let mut cells = [
+            TableCell::text("banana"),
+            TableCell::text("10").with_value(number(10.0)),
+            TableCell::text("Apple"),
+            TableCell::text("9").with_value(number(9.0)),
+        ];
+        cells.sort_by(compare_cells);
+        let texts: Vec<String> = cells
+            .iter()
+            .map(|cell| InlineNode::plain_text_of(&cell.content))
+            .collect();
+        assert_eq!(texts, ["9", "10", "Apple", "banana"]);
+    }
+
+    #[test]
+    fn test_table_aggregate() {
+        let price = |amount: f64| TableCell::typed(CellValue::currency(amount, "USD").unwrap());
+        let table = Node::Table {
+            header: vec![TableCell::header("Item"), TableCell::header("Price")],
+            rows: vec![
+                vec![TableCell::text("Tea"), price(3.5)],
+                vec![TableCell::text("Cake"), price(4.25)],
+                vec![TableCell::text("Water"), TableCell::text("free")],
+            ],
+            alignments: vec![TableAlignment::None; 2],
+            properties: Default::default(),
+        };
+
+        assert_eq!(
+            table.table_aggregate(1, Aggregate::Sum),
+            CellValue::currency(7.75, "USD")
+        );
+        assert_eq!(
+            table.table_aggregate(1, Aggregate::Max),
+            CellValue::currency(4.25, "USD")
+        );
+        assert_eq!(
+            table.table_aggregate(1, Aggregate::Count),
+            CellValue::number(2.0)
+        );
+        assert_eq!(table.table_aggregate(0, Aggregate::Sum), None);
+
+        let mixed = [number(1.0), CellValue::currency(2.0, "EUR").unwrap()];
+        assert_eq!(Aggregate::Sum.apply(&mixed), None);
+        assert_eq!(
+            Aggregate::Average.apply(&[number(1.0), number(2.0)]),
+            CellValue::number(1.5)
+        );
+    }
+}
@@ -1,5 +1,9 @@
-use crate::{InlineNode, Node, Selection, TextNode};
+use crate::{
+    BibliographyEntry, Comment, InlineNode, Node, NodeAttributes, Selection, TextNode,
+    TrackedChange,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::ops::{Deref, DerefMut};
 
 /// The main document structure, containing a list of block nodes
@@ -11,9 +15,24 @@ pub struct Document {
     /// Optional selection state
     #[serde(skip_serializing_if = "Option::is_none")]
     pub selection: Option<Selection>,
+    /// Further selections besides the primary one, for multiple cursors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selections: Vec<Selection>,
     /// Document metadata
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<DocumentMetadata>,
+    /// Review comments on ranges of text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<Comment>,
+    /// Suggested insertions and deletions awaiting review
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracked_changes: Vec<TrackedChange>,
+    /// Application-defined attributes of top-level nodes, by node index
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub node_attributes: BTreeMap<usize, NodeAttributes>,
+    /// Entries citations refer to, as CSL-JSON items
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bibliography: Vec<BibliographyEntry>,
 }
 
 /// Contains metadata about the document
@@ -33,6 +52,31 @@ pub struct DocumentMetadata {
     pub custom: Vec<(String, String)>,
 }
 
+/// Custom metadata key holding the per-document autocorrect setting
+const AUTOCORRECT_KEY: &str = "autocorrect";
+
+impl DocumentMetadata {
+    /// Returns the value of a custom metadata entry
+    pub fn custom_value(&self, key: &str) -> Option<&str> {
+        self.custom
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets a custom metadata entry, or removes it when `value` is None
+    pub fn set_custom_value(&mut self, key: &str, value: Option<String>) {
+        match (self.custom.iter().position(|(k, _)| k == key), value) {
+            (Some(index), Some(value)) => self.custom[index].1 = value,
+            (Some(index), None) => {
+                self.custom.remove(index);
+            }
+            (None, Some(value)) => self.custom.push((key.to_string(), value)),
+            (None, None) => {}
+        }
+    }
+}
+
 impl Document {
     /// Creates a new empty document
     pub fn new() -> Self {
@@ -51,6 +95,28 @@ impl Document {
         doc
     }
 
+    /// Whether autocorrect applies to this document
+    ///
+    /// Enabled unless turned off with `set_autocorrect_enabled`; the setting
+    /// is stored in the custom metadata so it travels with the document.
+    pub fn autocorrect_enabled(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.custom_value(AUTOCORRECT_KEY))
+            != Some("off")
+    }
+
+    /// Enables or disables autocorrect for this document
+    pub fn set_autocorrect_enabled(&mut self, enabled: bool) {
+        let value = (!enabled).then(|| "off".to_string());
+        if value.is_none() && self.metadata.is_none() {
+            return;
+        }
+        self.metadata
+            .get_or_insert_with(DocumentMetadata::default)
+            .set_custom_value(AUTOCORRECT_KEY, value);
+    }
+
     /// Adds a heading to the document
     pub fn add_heading(&mut self, level: u8, text: impl Into<String>) -> usize {
         let index = self.nodes.len();
@@ -153,6 +219,18 @@ impl Document {
         index
     }
 
+    /// Adds a captioned figure to the document
+    pub fn add_figure(
+        &mut self,
+        url: impl Into<String>,
+        alt: impl Into<String>,
+        caption: impl Into<String>,
+    ) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node::figure(url, alt, caption));
+        index
+    }
+
     /// Adds a definition list to the document
     pub fn add_definition_list(&mut self, items: Vec<(String, Vec<String>)>) -> usize {
         let index = self.nodes.len();
@@ -394,6 +472,21 @@ impl Document {
                 Node::FootnoteDefinition { .. } => "FootnoteDefinition".to_string(),
                 Node::DefinitionList { .. } => "DefinitionList".to_string(),
                 Node::MathBlock { .. } => "MathBlock".to_string(),
+                Node::Embed { provider, .. } => format!("Embed ({})", provider),
+                Node::Figure { url, .. } => format!("Figure ({})", url),
+                Node::HtmlBlock { .. } => "HtmlBlock".to_string(),
+                Node::HtmlComment { .. } => "HtmlComment".to_string(),
+                Node::Callout { kind, .. } => format!("Callout ({})", kind.as_str()),
+                Node::VisibilityBlock { visibility, .. } => {
+                    format!("VisibilityBlock ({})", visibility.as_str())
+                }
+                Node::Custom { kind, .. } => format!("Custom ({})", kind),
+                Node::Conflict { ours, theirs } => {
+                    format!("Conflict ({} ours, {} theirs)", ours.len(), theirs.len())
+                }
+                Node::TableOfContents { max_level } => {
+                    format!("TableOfContents (up to level {})", max_level)
+                }
                 Node::TempListItem(_) => "TempListItem (Internal)".to_string(),
                 Node::TempTableCell(_) => "TempTableCell (Internal)".to_string(),
             };
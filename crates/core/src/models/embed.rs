@@ -0,0 +1,273 @@
+use crate::{Document, InlineNode, Node};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static YOUTUBE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^https?://(?:www\.)?(?:youtube\.com/(?:watch\?v=|embed/)|youtu\.be/)([A-Za-z0-9_-]+)",
+    )
+    .unwrap()
+});
+static VIMEO: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^https?://(?:www\.)?vimeo\.com/(\d+)").unwrap());
+static GIST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^https?://gist\.github\.com/([\w-]+/[0-9a-f]+)").unwrap());
+
+/// Metadata describing how a media URL should be embedded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbedInfo {
+    /// Name of the media provider (e.g., "youtube")
+    pub provider: String,
+    /// Pre-rendered embed HTML, if the provider supplies one
+    pub html: Option<String>,
+    /// Aspect ratio of the embedded media (e.g., "16:9")
+    pub aspect_ratio: Option<String>,
+}
+
+/// Resolves media URLs into embed metadata, mirroring an oEmbed lookup
+///
+/// Implementations can call out to a real oEmbed endpoint or recognize
+/// URLs locally. Returning `None` means the URL is not embeddable.
+pub trait EmbedResolver {
+    /// Resolve a URL into embed metadata
+    fn resolve(&self, url: &str) -> Option<EmbedInfo>;
+}
+
+/// Built-in resolver for YouTube, Vimeo and GitHub Gist URLs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultEmbedResolver;
+
+/// Inline style that stretches an iframe over its responsive container
+const IFRAME_STYLE: &str = "position:absolute;top:0;left:0;width:100%;height:100%;border:0;";
+
+impl EmbedResolver for DefaultEmbedResolver {
+    fn resolve(&self, url: &str) -> Option<EmbedInfo> {
+        if let Some(caps) = YOUTUBE.captures(url) {
+            return Some(EmbedInfo {
+                provider: "youtube".to_string(),
+                html: Some(format!(
+                    "<iframe src=\"https://www.youtube.com/embed/{}\" style=\"{}\" allowfullscreen></iframe>",
+                    &caps[1], IFRAME_STYLE
+                )),
+                aspect_ratio: Some("16:9".to_string()),
+            });
+        }
+
+        if let Some(caps) = VIMEO.captures(url) {
+            return Some(EmbedInfo {
+                provider: "vimeo".to_string(),
+                html: Some(format!(
+                    "<iframe src=\"https://player.vimeo.com/video/{}\" style=\"{}\" allowfullscreen></iframe>",
+                    &caps[1], IFRAME_STYLE
+                )),
+                aspect_ratio: Some("16:9".to_string()),
+            });
+        }
+
+        if let Some(caps) = GIST.captures(url) {
+            return Some(EmbedInfo {
+                provider: "gist".to_string(),
+                html: Some(format!(
+                    "<script src=\"https://gist.github.com/{}.js\"></script>",
+                    &caps[1]
+                )),
+                aspect_ratio: None,
+            });
+        }
+
+        None
+    }
+}
+
+/// Returns the URL if the inline content is nothing but a bare URL or autolink
+pub(crate) fn bare_url(children: &[InlineNode]) -> Option<&str> {
+    match children {
+        [InlineNode::Text(text)] if text.formatting == Default::default() => {
+            let url = text.text.trim();
+            if (url.starts_with("http://") || url.starts_with("https://"))
+                && !url.contains(char::is_whitespace)
+            {
+                Some(url)
+            } else {
+                None
+            }
+        }
+        [
+            InlineNode::AutoLink {
+                url,
+                is_email: false,
+            },
+        ] => Some(url),
+        _ => None,
+    }
+}
+
+/// Converts a paragraph holding a bare provider URL into an embed node
+pub(crate) fn paragraph_to_embed(node: &Node, resolver: &dyn EmbedResolver) -> Option<Node> {
+    let url = bare_url(node.as_paragraph()?)?;
+    let info = resolver.resolve(url)?;
+    Some(Node::embed(url, info))
+}
+
+/// Converts an aspect ratio such as "16:9" into a CSS padding percentage
+pub(crate) fn aspect_ratio_padding(aspect_ratio: &str) -> Option<f64> {
+    let (width, height) = aspect_ratio.split_once(':')?;
+    let width: f64 = width.trim().parse().ok()?;
+    let height: f64 = height.trim().parse().ok()?;
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+    Some(height / width * 100.0)
+}
+
+impl Document {
+    /// Resolves embeds in the document using the given resolver
+    ///
+    /// Top-level paragraphs that consist of a single URL recognized by the
+    /// resolver are converted into embed nodes, and existing embeds without
+    /// HTML are filled in. Returns the number of nodes that were updated.
+    pub fn resolve_embeds(&mut self, resolver: &dyn EmbedResolver) -> usize {
+        let mut updated = 0;
+
+        for node in self.nodes.iter_mut() {
+            if let Some(embed) = paragraph_to_embed(node, resolver) {
+                *node = embed;
+                updated += 1;
+                continue;
+            }
+
+            if let Node::Embed {
+                url,
+                provider,
+                html,
+                aspect_ratio,
+            } = node
+            {
+                if html.is_some() {
+                    continue;
+                }
+                if let Some(info) = resolver.resolve(url) {
+                    *provider = info.provider;
+                    *html = info.html;
+                    *aspect_ratio = info.aspect_ratio;
+                    updated += 1;
+                }
+            }
+        }
+
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, Markdown, Text};
+
+    struct AudioResolver;
+
+    impl EmbedResolver for AudioResolver {
+        fn resolve(&self, url: &str) -> Option<EmbedInfo> {
+            url.ends_with(".mp3").then(|| EmbedInfo {
+                provider: "audio".to_string(),
+                html: Some(format!("<audio src=\"{}\" controls></audio>", url)),
+                aspect_ratio: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_default_resolver_providers() {
+        let resolver = DefaultEmbedResolver;
+
+        let youtube = resolver
+            .resolve("https://www.youtube.com/watch?v=dQw4w9WgXcQ")
+            .unwrap();
+        assert_eq!(youtube.provider, "youtube");
+        assert!(
+            youtube
+                .html
+                .unwrap()
+                .contains("youtube.com/embed/dQw4w9WgXcQ")
+        );
+
+        let short = resolver.resolve("https://youtu.be/dQw4w9WgXcQ").unwrap();
+        assert_eq!(short.provider, "youtube");
+
+        let vimeo = resolver.resolve("https://vimeo.com/76979871").unwrap();
+        assert_eq!(vimeo.provider, "vimeo");
+        assert_eq!(vimeo.aspect_ratio.as_deref(), Some("16:9"));
+
+        let gist = resolver
+            .resolve("https://gist.github.com/octocat/6cad326836d38bd3a7ae")
+            .unwrap();
+        assert_eq!(gist.provider, "gist");
+        assert!(gist.aspect_ratio.is_none());
+
+        assert!(resolver.resolve("https://example.com/page").is_none());
+    }
+
+    #[test]
+    fn test_parse_bare_url_into_embed() {
+        let markdown = "Intro\n\nhttps://www.youtube.com/watch?v=dQw4w9WgXcQ\n\nOutro";
+        let doc = Document::try_from(Text::<Markdown>::new(markdown)).unwrap();
+
+        assert_eq!(doc.nodes.len(), 3);
+        let (url, provider) = doc.nodes[1].as_embed().unwrap();
+        assert_eq!(url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(provider, "youtube");
+
+        // Plain links stay paragraphs
+        let doc = Document::try_from(Text::<Markdown>::new("https://example.com")).unwrap();
+        assert!(doc.nodes[0].as_paragraph().is_some());
+    }
+
+    #[test]
+    fn test_embed_rendering() {
+        let mut doc = Document::new();
+        let info = DefaultEmbedResolver
+            .resolve("https://vimeo.com/76979871")
+            .unwrap();
+        doc.nodes
+            .push(Node::embed("https://vimeo.com/76979871", info));
+
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(html.contains("class=\"embed embed-vimeo\""));
+        assert!(html.contains("padding-bottom:56.25%"));
+        assert!(html.contains("<iframe src=\"https://player.vimeo.com/video/76979871\""));
+
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert_eq!(markdown.as_str(), "<https://vimeo.com/76979871>");
+
+        let reparsed = Document::try_from(markdown).unwrap();
+        assert_eq!(reparsed, doc);
+    }
+
+    #[test]
+    fn test_resolve_embeds_with_custom_resolver() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("https://example.com/song.mp3");
+        doc.add_paragraph_with_text("Not a link");
+        doc.nodes.push(Node::Embed {
+            url: "https://example.com/intro.mp3".to_string(),
+            provider: String::new(),
+            html: None,
+            aspect_ratio: None,
+        });
+
+        assert_eq!(doc.resolve_embeds(&AudioResolver), 2);
+        assert_eq!(
+            doc.nodes[0].as_embed(),
+            Some(("https://example.com/song.mp3", "audio"))
+        );
+        assert!(doc.nodes[1].as_paragraph().is_some());
+        assert_eq!(doc.nodes[2].as_embed().unwrap().1, "audio");
+    }
+
+    #[test]
+    fn test_aspect_ratio_padding() {
+        assert_eq!(aspect_ratio_padding("16:9"), Some(56.25));
+        assert_eq!(aspect_ratio_padding("4:3"), Some(75.0));
+        assert_eq!(aspect_ratio_padding("wide"), None);
+    }
+}
@@ -77,6 +77,10 @@ pub enum InlineNode {
         title: Option<String>,
         /// The link's content/children
         children: Vec<InlineNode>,
+        /// Label of the definition a reference-style `[text][label]` link
+        /// uses, kept so Markdown export can write it back the same way
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reference: Option<String>,
     },
 
     /// An inline image
@@ -129,6 +133,35 @@ pub enum InlineNode {
         mention_type: String,
     },
 
+    /// A hashtag categorizing the text, written `#tag`
+    #[serde(rename = "tag")]
+    Tag {
+        /// Name of the tag, without `#`
+        name: String,
+    },
+
+    /// A link to another note by name, written `[[Page]]` or
+    /// `[[Page|Alias]]`
+    #[serde(rename = "wiki_link")]
+    WikiLink {
+        /// Name of the linked page, optionally with a `#heading`
+        target: String,
+        /// Text shown instead of the target
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alias: Option<String>,
+    },
+
+    /// A citation of a bibliography entry, written `[@key]` or
+    /// `[@key, p. 12]`
+    #[serde(rename = "citation")]
+    Citation {
+        /// Key of the cited entry in the document's bibliography
+        key: String,
+        /// Where in the entry, e.g. a page or chapter
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        locator: Option<String>,
+    },
+
     /// Inline math expression
     #[serde(rename = "math")]
     Math {
@@ -143,6 +176,29 @@ pub enum InlineNode {
         shortcode: String,
     },
 
+    /// Raw inline HTML such as `<kbd>`, kept verbatim
+    #[serde(rename = "html")]
+    Html {
+        /// The HTML source of the tag
+        html: String,
+    },
+
+    /// An HTML comment inside text, `<!--text-->`
+    #[serde(rename = "html_comment")]
+    HtmlComment {
+        /// Text between `<!--` and `-->`, verbatim
+        text: String,
+    },
+
+    /// An inline directive handled by an extension, written `:kind[data]`
+    #[serde(rename = "custom")]
+    Custom {
+        /// Name of the extension, e.g. `"kbd"`
+        kind: String,
+        /// Content of the directive, interpreted by the extension
+        data: String,
+    },
+
     /// Hard break
     HardBreak,
 
@@ -182,6 +238,7 @@ impl InlineNode {
             url: url.into(),
             title: None,
             children: vec![Self::text(text)],
+            reference: None,
         }
     }
 
@@ -195,6 +252,21 @@ impl InlineNode {
             url: url.into(),
             title: Some(title.into()),
             children: vec![Self::text(text)],
+            reference: None,
+        }
+    }
+
+    /// Creates a reference-style link using the definition with the given label
+    pub fn reference_link(
+        url: impl Into<String>,
+        label: impl Into<String>,
+        text: impl Into<String>,
+    ) -> Self {
+        Self::Link {
+            url: url.into(),
+            title: None,
+            children: vec![Self::text(text)],
+            reference: Some(label.into()),
         }
     }
 
@@ -266,6 +338,43 @@ impl InlineNode {
         }
     }
 
+    /// Creates a hashtag from a name without `#`
+    pub fn tag(name: impl Into<String>) -> Self {
+        Self::Tag { name: name.into() }
+    }
+
+    /// Creates a wiki-link to the page with the given name
+    pub fn wiki_link(target: impl Into<String>) -> Self {
+        Self::WikiLink {
+            target: target.into(),
+            alias: None,
+        }
+    }
+
+    /// Creates a wiki-link showing `alias` instead of the page name
+    pub fn wiki_link_with_alias(target: impl Into<String>, alias: impl Into<String>) -> Self {
+        Self::WikiLink {
+            target: target.into(),
+            alias: Some(alias.into()),
+        }
+    }
+
+    /// Creates a citation of the bibliography entry with the given key
+    pub fn citation(key: impl Into<String>) -> Self {
+        Self::Citation {
+            key: key.into(),
+            locator: None,
+        }
+    }
+
+    /// Creates a citation of a place in a bibliography entry, e.g. a page
+    pub fn citation_at(key: impl Into<String>, locator: impl Into<String>) -> Self {
+        Self::Citation {
+            key: key.into(),
+            locator: Some(locator.into()),
+        }
+    }
+
     /// Creates an inline math expression
     pub fn math(math: impl Into<String>) -> Self {
         Self::Math { math: math.into() }
@@ -278,6 +387,11 @@ impl InlineNode {
         }
     }
 
+    /// Creates an HTML comment inside text
+    pub fn html_comment(text: impl Into<String>) -> Self {
+        Self::HtmlComment { text: text.into() }
+    }
+
     /// Creates a hard break
     pub fn hard_break() -> Self {
         Self::HardBreak
@@ -289,4 +403,55 @@ impl InlineNode {
             _ => None,
         }
     }
+
+    /// Returns the plain text content of this inline node, without formatting
+    pub fn plain_text(&self) -> String {
+        match self {
+            Self::Text(text) => text.text.clone(),
+            Self::Link { children, .. } | Self::InlineFootnote { children } => {
+                Self::plain_text_of(children)
+            }
+            Self::Image { alt, .. } => alt.clone(),
+            Self::CodeSpan { code } => code.clone(),
+            Self::AutoLink { url, .. } => url.clone(),
+            Self::FootnoteRef { label } => label.clone(),
+            Self::Mention { name, .. } => name.clone(),
+            Self::Tag { name } => format!("#{name}"),
+            Self::WikiLink { target, alias } => alias.clone().unwrap_or_else(|| target.clone()),
+            Self::Citation { key, locator } => {
+                crate::citations::citation_markdown(key, locator.as_deref())
+            }
+            Self::Math { math } => math.clone(),
+            Self::Emoji { shortcode } => shortcode.clone(),
+            Self::Html { .. } | Self::HtmlComment { .. } => String::new(),
+            Self::Custom { data, .. } => data.clone(),
+            Self::HardBreak => "\n".to_string(),
+            Self::SoftBreak => " ".to_string(),
+        }
+    }
+
+    /// Calls `f` on this node and every inline nested inside it, parents first
+    pub(crate) fn walk(&self, f: &mut dyn FnMut(&InlineNode)) {
+        f(self);
+        if let Self::Link { children, .. } | Self::InlineFootnote { children } = self {
+            for child in children {
+                child.walk(f);
+            }
+        }
+    }
+
+    /// Mutable counterpart of [`InlineNode::walk`]
+    pub(crate) fn walk_mut(&mut self, f: &mut dyn FnMut(&mut InlineNode)) {
+        f(self);
+        if let Self::Link { children, .. } | Self::InlineFootnote { children } = self {
+            for child in children {
+                child.walk_mut(f);
+            }
+        }
+    }
+
+    /// Returns the concatenated plain text of a sequence of inline nodes
+    pub fn plain_text_of(inlines: &[InlineNode]) -> String {
+        inlines.iter().map(|inline| inline.plain_text()).collect()
+    }
 }
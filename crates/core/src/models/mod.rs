@@ -1,19 +1,28 @@
 mod builder;
+mod cell_value;
 mod document;
+pub(crate) mod embed;
 mod formatting;
 mod inline;
 mod node;
 mod selection;
+mod table_cursor;
 
 pub use builder::DocumentBuilder;
+pub(crate) use cell_value::compare_cells;
+pub use cell_value::{Aggregate, CellDate, CellValue};
 pub use document::*;
+pub use embed::{DefaultEmbedResolver, EmbedInfo, EmbedResolver};
 pub use formatting::TextFormatting;
 pub use inline::{InlineNode, TextNode};
 pub use node::{
-    CodeBlockProperties, DefinitionItem, FootnoteDefinition, FootnoteReference, ListItem, ListType,
-    Node, TableAlignment, TableCell, TableProperties,
+    CalloutKind, CodeBlockProperties, CodeOutput, CodeOutputKind, DefinitionItem, FigureAlignment,
+    FigureProperties, FootnoteDefinition, FootnoteReference, HeadingProperties, ListItem, ListType,
+    Node, TableAlignment, TableCell, TableProperties, Visibility,
 };
+pub(crate) use node::{fnv1a, table_cell_columns};
 pub use selection::{Position, Selection};
+pub use table_cursor::TableCursor;
 
 // Public serialization functions are now in crate::convert
 // pub use serialization::{from_html, from_json, from_markdown, to_html, to_json, to_markdown}; // Removed old export
@@ -40,7 +49,9 @@ mod test {
         assert_eq!(doc.nodes.len(), 1);
 
         match &doc.nodes[0] {
-            Node::Heading { level, children } => {
+            Node::Heading {
+                level, children, ..
+            } => {
                 assert_eq!(*level, 1);
                 assert_eq!(children.len(), 1);
 
@@ -128,7 +139,9 @@ mod test {
         assert_eq!(idx, 0);
 
         match &doc.nodes[0] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 assert_eq!(*list_type, ListType::Unordered);
                 assert_eq!(items.len(), 3);
 
@@ -152,7 +165,9 @@ mod test {
         assert_eq!(idx, 1);
 
         match &doc.nodes[1] {
-            Node::List { list_type, items } => {
+            Node::List {
+                list_type, items, ..
+            } => {
                 assert_eq!(*list_type, ListType::Task);
                 assert_eq!(items.len(), 2);
 
@@ -196,6 +211,7 @@ mod test {
                 url,
                 title,
                 children,
+                ..
             } => {
                 assert_eq!(url, "https://example.com");
                 assert_eq!(title, None);
@@ -240,7 +256,9 @@ mod test {
         assert_eq!(doc.nodes.len(), doc2.nodes.len());
 
         match &doc2.nodes[0] {
-            Node::Heading { level, children } => {
+            Node::Heading {
+                level, children, ..
+            } => {
                 assert_eq!(*level, 1);
 
                 match &children[0] {
@@ -277,7 +295,7 @@ mod test {
 
         let html = Text::<Html>::try_from(&doc).unwrap();
 
-        assert!(html.contains("<h1>Test Document</h1>"));
+        assert!(html.contains("<h1 id=\"test-document\">Test Document</h1>"));
         assert!(html.contains("<p>This is a paragraph</p>"));
         // The document wrapper is not included in the current implementation
     }
@@ -324,6 +342,7 @@ mod test {
                 Node::Heading {
                     level: node_level,
                     children,
+                    ..
                 } => {
                     // Check level
                     assert_eq!(*node_level, level as u8);
@@ -352,7 +371,10 @@ mod test {
         let html = Text::<Html>::try_from(&doc).unwrap();
         for level in 1..=6 {
             // Check correct HTML tags
-            assert!(html.contains(&format!("<h{}>Heading Level {}</h{}>", level, level, level)));
+            assert!(html.contains(&format!(
+                "<h{} id=\"heading-level-{}\">Heading Level {}</h{}>",
+                level, level, level, level
+            )));
         }
 
         // Test JSON roundtrip
@@ -368,6 +390,7 @@ mod test {
                 Node::Heading {
                     level: node_level,
                     children,
+                    ..
                 } => {
                     assert_eq!(*node_level, level as u8);
 
@@ -592,7 +615,10 @@ mod test {
         let item1_children = &item1.children;
         assert_eq!(item1_children.len(), 2); // The paragraph and the nested list
 
-        if let Node::List { list_type, items } = &item1_children[1] {
+        if let Node::List {
+            list_type, items, ..
+        } = &item1_children[1]
+        {
             assert_eq!(*list_type, ListType::Ordered);
             assert_eq!(items.len(), 2);
             assert_eq!(items[0].as_text(), Some("Subitem 1"));
@@ -604,7 +630,10 @@ mod test {
         let item3_children = &item3.children;
         assert_eq!(item3_children.len(), 2); // The paragraph and the nested list
 
-        if let Node::List { list_type, items } = &item3_children[1] {
+        if let Node::List {
+            list_type, items, ..
+        } = &item3_children[1]
+        {
             assert_eq!(*list_type, ListType::Ordered);
             assert_eq!(items.len(), 2);
             assert_eq!(items[0].as_text(), Some("Subitem 3"));
@@ -1,8 +1,8 @@
-use crate::InlineNode;
+use crate::{CellValue, EmbedInfo, InlineNode};
 use serde::{Deserialize, Serialize};
 
 /// Alignment options for table columns
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum TableAlignment {
     /// Left-aligned column
@@ -12,6 +12,7 @@ pub enum TableAlignment {
     /// Right-aligned column
     Right,
     /// Default alignment
+    #[default]
     None,
     /// Text justify alignment
     Justify,
@@ -23,12 +24,6 @@ pub enum TableAlignment {
     Bottom,
 }
 
-impl Default for TableAlignment {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 /// Properties for table styling and behavior
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TableProperties {
@@ -56,6 +51,12 @@ pub struct TableProperties {
     /// Whether the caption should be displayed at the bottom
     #[serde(default = "default_false")]
     pub caption_at_bottom: bool,
+    /// Description of the table's contents for assistive technology
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Accessible name of the table, for tables without a caption
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aria_label: Option<String>,
 }
 
 fn default_true() -> bool {
@@ -77,6 +78,8 @@ impl Default for TableProperties {
             style: None,
             caption: None,
             caption_at_bottom: false,
+            summary: None,
+            aria_label: None,
         }
     }
 }
@@ -129,6 +132,18 @@ impl TableProperties {
         self.caption_at_bottom = at_bottom;
         self
     }
+
+    /// Set the description of the table's contents
+    pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    /// Set the accessible name of the table
+    pub fn with_aria_label(mut self, aria_label: impl Into<String>) -> Self {
+        self.aria_label = Some(aria_label.into());
+        self
+    }
 }
 
 /// Type of list: ordered, unordered, or task
@@ -212,6 +227,71 @@ pub struct TableCell {
         skip_serializing_if = "is_default_is_header"
     )]
     pub is_header: bool,
+    /// Typed value behind the display text, used for sorting, aggregates
+    /// and locale-aware rendering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<CellValue>,
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms unlike the std hasher
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The text of an HTML fragment with its tags removed
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Logical grid column where each cell of the given rows starts
+///
+/// Cells covered by a colspan or by a rowspan from an earlier row are not
+/// stored in the rows, so a cell's position in its row can differ from the
+/// column it occupies. This follows the HTML table layout rules.
+pub(crate) fn table_cell_columns(rows: &[Vec<TableCell>]) -> Vec<Vec<usize>> {
+    // Number of further rows each column stays occupied by a rowspan
+    let mut pending: Vec<u32> = Vec::new();
+
+    rows.iter()
+        .map(|row| {
+            let mut column = 0;
+            let starts = row
+                .iter()
+                .map(|cell| {
+                    while pending.get(column).is_some_and(|rows| *rows > 0) {
+                        column += 1;
+                    }
+                    let start = column;
+                    let colspan = cell.colspan.max(1) as usize;
+                    if pending.len() < start + colspan {
+                        pending.resize(start + colspan, 0);
+                    }
+                    for rows in &mut pending[start..start + colspan] {
+                        // The current row is counted off below with the others
+                        *rows = cell.rowspan.max(1);
+                    }
+                    column += colspan;
+                    start
+                })
+                .collect();
+            for rows in &mut pending {
+                *rows = rows.saturating_sub(1);
+            }
+            starts
+        })
+        .collect()
 }
 
 fn default_span() -> u32 {
@@ -241,6 +321,7 @@ impl TableCell {
             css_class: None,
             style: None,
             is_header: false,
+            value: None,
         }
     }
 
@@ -254,6 +335,7 @@ impl TableCell {
             css_class: None,
             style: None,
             is_header: false,
+            value: None,
         }
     }
 
@@ -267,6 +349,7 @@ impl TableCell {
             css_class: None,
             style: None,
             is_header: false,
+            value: None,
         }
     }
 
@@ -280,6 +363,7 @@ impl TableCell {
             css_class: None,
             style: None,
             is_header: false,
+            value: None,
         }
     }
 
@@ -293,6 +377,7 @@ impl TableCell {
             css_class: None,
             style: None,
             is_header: false,
+            value: None,
         }
     }
 
@@ -306,6 +391,7 @@ impl TableCell {
             css_class: None,
             style: None,
             is_header: true,
+            value: None,
         }
     }
 
@@ -344,6 +430,7 @@ impl Default for TableCell {
             css_class: None,
             style: None,
             is_header: false,
+            value: None,
         }
     }
 }
@@ -461,6 +548,62 @@ pub struct CodeBlockProperties {
     /// Maximum height before scrolling (e.g., "500px")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_height: Option<String>,
+
+    /// Execution count of a notebook cell, shown as `In [n]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_count: Option<u32>,
+
+    /// Outputs of a notebook cell from its last run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<CodeOutput>,
+
+    /// Whether the code is folded in the UI
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapsed: bool,
+}
+
+/// Where the output of a notebook cell came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeOutputKind {
+    /// Text written to standard output
+    Stdout,
+    /// Text written to standard error
+    Stderr,
+    /// The value of the cell's last expression
+    Result,
+    /// Rich output displayed while the cell ran, such as a plot
+    Display,
+    /// The traceback of an error the cell raised
+    Error,
+}
+
+/// One output of a notebook cell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeOutput {
+    /// Where the output came from
+    pub kind: CodeOutputKind,
+    /// MIME type of the data, such as `text/plain`, `text/html` or
+    /// `image/png`
+    pub mime_type: String,
+    /// The output: text, or base64 for binary image types
+    pub data: String,
+}
+
+impl CodeOutput {
+    /// Creates a `text/plain` output
+    pub fn text(kind: CodeOutputKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            mime_type: "text/plain".to_string(),
+            data: text.into(),
+        }
+    }
+
+    /// Returns true if the data is base64 rather than text
+    pub fn is_binary(&self) -> bool {
+        self.mime_type.starts_with("image/") && self.mime_type != "image/svg+xml"
+    }
 }
 
 /// Returns the default starting line number (1)
@@ -479,6 +622,9 @@ impl Default for CodeBlockProperties {
             css_class: None,
             style: None,
             max_height: None,
+            execution_count: None,
+            outputs: Vec::new(),
+            collapsed: false,
         }
     }
 }
@@ -536,6 +682,131 @@ impl CodeBlockProperties {
         self.max_height = Some(height.into());
         self
     }
+
+    /// Set the notebook execution count
+    pub fn with_execution_count(mut self, count: u32) -> Self {
+        self.execution_count = Some(count);
+        self
+    }
+
+    /// Set the notebook cell outputs
+    pub fn with_outputs(mut self, outputs: Vec<CodeOutput>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+}
+
+/// Optional attributes attached to a heading
+///
+/// In markdown these are written as a trailing attribute block, e.g.
+/// `## Chapter One {target=2000}`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct HeadingProperties {
+    /// Word-count target for the section opened by this heading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_target: Option<usize>,
+    /// Whether the section is folded in the UI
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapsed: bool,
+}
+
+impl HeadingProperties {
+    /// Create a new default HeadingProperties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the word-count target for the section
+    pub fn with_word_target(mut self, target: usize) -> Self {
+        self.word_target = Some(target);
+        self
+    }
+
+    /// Returns true if no attributes are set
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Horizontal alignment of a figure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FigureAlignment {
+    /// Default placement
+    #[default]
+    None,
+    /// Floated to the left
+    Left,
+    /// Centered
+    Center,
+    /// Floated to the right
+    Right,
+}
+
+impl FigureAlignment {
+    /// Returns the lowercase name used in markdown attributes and CSS classes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Left => "left",
+            Self::Center => "center",
+            Self::Right => "right",
+        }
+    }
+}
+
+/// Display attributes of a figure image
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FigureProperties {
+    /// Display width in pixels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// Display height in pixels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Horizontal alignment
+    #[serde(default)]
+    pub alignment: FigureAlignment,
+    /// Whether the browser should defer loading the image
+    #[serde(default)]
+    pub lazy_loading: bool,
+}
+
+impl FigureProperties {
+    /// Create a new default FigureProperties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the display size in pixels
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    /// Set the display width in pixels, keeping the aspect ratio
+    pub fn with_width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Set the horizontal alignment
+    pub fn with_alignment(mut self, alignment: FigureAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Set whether the image is lazily loaded
+    pub fn with_lazy_loading(mut self, lazy: bool) -> Self {
+        self.lazy_loading = lazy;
+        self
+    }
+
+    /// Returns true if no attributes are set
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
 }
 
 /// Represents a block-level node in the document
@@ -549,6 +820,9 @@ pub enum Node {
         level: u8,
         /// Inline content of the heading
         children: Vec<InlineNode>,
+        /// Heading attributes such as section word targets
+        #[serde(default, skip_serializing_if = "HeadingProperties::is_empty")]
+        properties: HeadingProperties,
     },
 
     /// A paragraph
@@ -565,6 +839,12 @@ pub enum Node {
         list_type: ListType,
         /// List items
         items: Vec<ListItem>,
+        /// Number of the first item of an ordered list, when not 1
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<u64>,
+        /// Whether items are separated by blank lines in Markdown
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        loose: bool,
     },
 
     /// A code block
@@ -635,6 +915,108 @@ pub enum Node {
         math: String,
     },
 
+    /// Embedded rich media (video, gist, etc.) resolved from a URL
+    #[serde(rename = "embed")]
+    Embed {
+        /// The original media URL
+        url: String,
+        /// Name of the media provider (e.g., "youtube")
+        provider: String,
+        /// Pre-rendered embed HTML, if resolved
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        html: Option<String>,
+        /// Aspect ratio of the media (e.g., "16:9")
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aspect_ratio: Option<String>,
+    },
+
+    /// A block-level image with a caption
+    #[serde(rename = "figure")]
+    Figure {
+        /// URL of the image
+        url: String,
+        /// Alternative text of the image
+        alt: String,
+        /// Optional image title
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Inline content of the caption
+        #[serde(default)]
+        caption: Vec<InlineNode>,
+        /// Size, alignment and loading attributes
+        #[serde(default, skip_serializing_if = "FigureProperties::is_empty")]
+        properties: FigureProperties,
+    },
+
+    /// A block of raw HTML, kept verbatim
+    #[serde(rename = "html_block")]
+    HtmlBlock {
+        /// The HTML source
+        html: String,
+    },
+
+    /// An HTML comment on a line of its own, `<!--text-->`
+    #[serde(rename = "html_comment")]
+    HtmlComment {
+        /// Text between `<!--` and `-->`, verbatim
+        text: String,
+    },
+
+    /// A callout (admonition) such as a note or warning
+    #[serde(rename = "callout")]
+    Callout {
+        /// What kind of callout this is
+        kind: CalloutKind,
+        /// Title shown instead of the kind's default title
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Content of the callout
+        children: Vec<Node>,
+    },
+
+    /// Blocks that are only published in some exports, such as drafts
+    #[serde(rename = "visibility_block")]
+    VisibilityBlock {
+        /// Who gets to see the blocks
+        visibility: Visibility,
+        /// The blocks
+        children: Vec<Node>,
+    },
+
+    /// A block handled by an extension, such as a ```` ```mermaid ```` diagram
+    #[serde(rename = "custom")]
+    Custom {
+        /// Name of the extension, e.g. `"mermaid"`
+        kind: String,
+        /// Source of the block, interpreted by the extension
+        data: String,
+    },
+
+    /// Blocks that two merged versions of the document changed in different
+    /// ways, until one side is chosen
+    ///
+    /// Text exports write both sides between version control conflict
+    /// markers, which are not read back as a conflict.
+    #[serde(rename = "conflict")]
+    Conflict {
+        /// The blocks as our version has them
+        ours: Vec<Node>,
+        /// The blocks as their version has them
+        theirs: Vec<Node>,
+    },
+
+    /// A table of contents kept up to date with the headings
+    ///
+    /// Markdown and HTML exports expand it into a list of links to the
+    /// headings up to `max_level` at the time of the export; Markdown
+    /// wraps the list in `<!-- toc:N -->` ... `<!-- /toc -->` comment
+    /// markers so it is read back as a managed table of contents.
+    #[serde(rename = "table_of_contents")]
+    TableOfContents {
+        /// Deepest heading level listed (1-6)
+        max_level: u8,
+    },
+
     /// Temporary variants for parsing stack
     #[doc(hidden)]
     TempListItem(ListItem),
@@ -642,6 +1024,102 @@ pub enum Node {
     TempTableCell(TableCell),
 }
 
+/// The kind of a callout, matching the GitHub alert types
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CalloutKind {
+    /// Information worth noticing
+    #[default]
+    Note,
+    /// Advice for doing things better
+    Tip,
+    /// Information needed to succeed
+    Important,
+    /// Something that needs immediate attention
+    Warning,
+    /// Risks or negative outcomes of an action
+    Caution,
+}
+
+impl CalloutKind {
+    /// Every kind, in the order GitHub documents them
+    pub const ALL: [CalloutKind; 5] = [
+        Self::Note,
+        Self::Tip,
+        Self::Important,
+        Self::Warning,
+        Self::Caution,
+    ];
+
+    /// Looks up a kind by name, case-insensitively
+    ///
+    /// Accepts the common admonition aliases of other tools, such as
+    /// `info`, `hint` and `danger`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "note" | "info" | "abstract" | "summary" => Self::Note,
+            "tip" | "hint" | "success" => Self::Tip,
+            "important" | "question" => Self::Important,
+            "warning" | "attention" => Self::Warning,
+            "caution" | "danger" | "error" | "bug" | "failure" => Self::Caution,
+            _ => return None,
+        })
+    }
+
+    /// Lowercase name, as used in serialized documents and CSS classes
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Note => "note",
+            Self::Tip => "tip",
+            Self::Important => "important",
+            Self::Warning => "warning",
+            Self::Caution => "caution",
+        }
+    }
+
+    /// Title shown when a callout has none of its own
+    pub fn default_title(self) -> &'static str {
+        match self {
+            Self::Note => "Note",
+            Self::Tip => "Tip",
+            Self::Important => "Important",
+            Self::Warning => "Warning",
+            Self::Caution => "Caution",
+        }
+    }
+}
+
+/// Whether blocks are published
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Shown in every export
+    #[default]
+    Visible,
+    /// Work in progress, kept out of exports unless drafts are included
+    Draft,
+    /// Kept in the source only, such as notes to self
+    Hidden,
+}
+
+impl Visibility {
+    /// Lowercase name, as used in serialized documents and Markdown markers
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Visible => "visible",
+            Self::Draft => "draft",
+            Self::Hidden => "hidden",
+        }
+    }
+
+    /// Looks up a visibility by its lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Visible, Self::Draft, Self::Hidden]
+            .into_iter()
+            .find(|visibility| visibility.as_str() == name)
+    }
+}
+
 /// Type alias for table components
 pub type TableComponents<'a> = (
     &'a Vec<TableCell>,
@@ -657,6 +1135,7 @@ impl Node {
         Self::Heading {
             level,
             children: vec![InlineNode::text(text)],
+            properties: HeadingProperties::default(),
         }
     }
 
@@ -711,6 +1190,8 @@ impl Node {
         Self::List {
             list_type: ListType::Unordered,
             items: list_items,
+            start: None,
+            loose: false,
         }
     }
 
@@ -724,6 +1205,8 @@ impl Node {
         Self::List {
             list_type: ListType::Ordered,
             items: list_items,
+            start: None,
+            loose: false,
         }
     }
 
@@ -737,6 +1220,8 @@ impl Node {
         Self::List {
             list_type: ListType::Task,
             items: list_items,
+            start: None,
+            loose: false,
         }
     }
 
@@ -883,6 +1368,58 @@ impl Node {
         Self::MathBlock { math: math.into() }
     }
 
+    /// Creates an embed node from a URL and resolved embed metadata
+    pub fn embed(url: impl Into<String>, info: EmbedInfo) -> Self {
+        Self::Embed {
+            url: url.into(),
+            provider: info.provider,
+            html: info.html,
+            aspect_ratio: info.aspect_ratio,
+        }
+    }
+
+    /// Creates a figure with a plain-text caption
+    pub fn figure(
+        url: impl Into<String>,
+        alt: impl Into<String>,
+        caption: impl Into<String>,
+    ) -> Self {
+        let caption = caption.into();
+        Self::Figure {
+            url: url.into(),
+            alt: alt.into(),
+            title: None,
+            caption: if caption.is_empty() {
+                Vec::new()
+            } else {
+                vec![InlineNode::text(caption)]
+            },
+            properties: FigureProperties::default(),
+        }
+    }
+
+    /// Creates a figure with a caption and display attributes
+    pub fn figure_with_properties(
+        url: impl Into<String>,
+        alt: impl Into<String>,
+        caption: impl Into<String>,
+        properties: FigureProperties,
+    ) -> Self {
+        let mut figure = Self::figure(url, alt, caption);
+        if let Self::Figure {
+            properties: props, ..
+        } = &mut figure
+        {
+            *props = properties;
+        }
+        figure
+    }
+
+    /// Creates an HTML comment block
+    pub fn html_comment(text: impl Into<String>) -> Self {
+        Self::HtmlComment { text: text.into() }
+    }
+
     /// Creates a new group node
     pub fn group(name: impl Into<String>, children: Vec<Node>) -> Self {
         Self::Group {
@@ -894,7 +1431,9 @@ impl Node {
     /// Returns this node as a heading if it is one
     pub fn as_heading(&self) -> Option<(u8, &Vec<InlineNode>)> {
         match self {
-            Self::Heading { level, children } => Some((*level, children)),
+            Self::Heading {
+                level, children, ..
+            } => Some((*level, children)),
             _ => None,
         }
     }
@@ -910,7 +1449,9 @@ impl Node {
     /// Returns this node as a list if it is one
     pub fn as_list(&self) -> Option<(&ListType, &Vec<ListItem>)> {
         match self {
-            Self::List { list_type, items } => Some((list_type, items)),
+            Self::List {
+                list_type, items, ..
+            } => Some((list_type, items)),
             _ => None,
         }
     }
@@ -944,7 +1485,7 @@ impl Node {
     }
 
     /// Returns this node as a table if it is one
-    pub fn as_table(&self) -> Option<TableComponents> {
+    pub fn as_table(&self) -> Option<TableComponents<'_>> {
         match self {
             Node::Table {
                 header,
@@ -988,11 +1529,99 @@ impl Node {
         }
     }
 
+    /// Returns the URL and provider if this node is an embed
+    pub fn as_embed(&self) -> Option<(&str, &str)> {
+        match self {
+            Self::Embed { url, provider, .. } => Some((url, provider)),
+            _ => None,
+        }
+    }
+
+    /// Returns the URL and caption if this node is a figure
+    pub fn as_figure(&self) -> Option<(&str, &Vec<InlineNode>)> {
+        match self {
+            Self::Figure { url, caption, .. } => Some((url, caption)),
+            _ => None,
+        }
+    }
+
     /// Returns whether this node is a thematic break
     pub fn is_thematic_break(&self) -> bool {
         matches!(self, Self::ThematicBreak)
     }
 
+    /// Returns the plain text content of this node and its descendants
+    ///
+    /// Block children are separated by newlines and table cells by tabs.
+    pub fn plain_text(&self) -> String {
+        fn join_nodes(nodes: &[Node]) -> String {
+            nodes
+                .iter()
+                .map(|node| node.plain_text())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        fn row_text(cells: &[TableCell]) -> String {
+            cells
+                .iter()
+                .map(|cell| InlineNode::plain_text_of(&cell.content))
+                .collect::<Vec<_>>()
+                .join("\t")
+        }
+
+        match self {
+            Self::Heading { children, .. } | Self::Paragraph { children } => {
+                InlineNode::plain_text_of(children)
+            }
+            Self::List { items, .. } => items
+                .iter()
+                .map(|item| join_nodes(&item.children))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Self::CodeBlock { code, .. } => code.clone(),
+            Self::BlockQuote { children } | Self::Group { children, .. } => join_nodes(children),
+            Self::ThematicBreak => String::new(),
+            Self::Table { header, rows, .. } => std::iter::once(header)
+                .chain(rows.iter())
+                .map(|row| row_text(row))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Self::FootnoteReference(reference) => reference.label.clone(),
+            Self::FootnoteDefinition(definition) => join_nodes(&definition.content),
+            Self::DefinitionList { items } => items
+                .iter()
+                .map(|item| {
+                    let mut parts = vec![InlineNode::plain_text_of(&item.term)];
+                    parts.extend(item.descriptions.iter().map(|desc| join_nodes(desc)));
+                    parts.join("\n")
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Self::MathBlock { math } => math.clone(),
+            Self::Embed { url, .. } => url.clone(),
+            Self::Figure { caption, .. } => InlineNode::plain_text_of(caption),
+            Self::HtmlBlock { html } => strip_tags(html),
+            Self::HtmlComment { .. } => String::new(),
+            Self::Callout {
+                title, children, ..
+            } => match title {
+                Some(title) => format!("{}\n{}", title, join_nodes(children)),
+                None => join_nodes(children),
+            },
+            Self::VisibilityBlock { children, .. } => join_nodes(children),
+            Self::Custom { data, .. } => data.clone(),
+            Self::Conflict { ours, theirs } => [join_nodes(ours), join_nodes(theirs)]
+                .into_iter()
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Self::TableOfContents { .. } => String::new(),
+            Self::TempListItem(item) => join_nodes(&item.children),
+            Self::TempTableCell(cell) => InlineNode::plain_text_of(&cell.content),
+        }
+    }
+
     /// Returns the group components if this node is a group
     pub fn as_group(&self) -> Option<(&str, &Vec<Node>)> {
         match self {
@@ -1000,4 +1629,200 @@ impl Node {
             _ => None,
         }
     }
+    /// A hash of the node's content, stable across runs and platforms
+    ///
+    /// Equal nodes hash equally, so the hash identifies a node's content
+    /// between edits, e.g. to cache its rendering.
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(&serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// Returns the serialized type tag of the node, e.g. `"code_block"`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Heading { .. } => "heading",
+            Self::Paragraph { .. } => "paragraph",
+            Self::List { .. } => "list",
+            Self::CodeBlock { .. } => "code_block",
+            Self::BlockQuote { .. } => "blockquote",
+            Self::ThematicBreak => "thematic_break",
+            Self::Table { .. } => "table",
+            Self::Group { .. } => "group",
+            Self::FootnoteReference(_) => "footnote_reference",
+            Self::FootnoteDefinition(_) => "footnote_definition",
+            Self::DefinitionList { .. } => "definition_list",
+            Self::MathBlock { .. } => "math_block",
+            Self::Embed { .. } => "embed",
+            Self::Figure { .. } => "figure",
+            Self::HtmlBlock { .. } => "html_block",
+            Self::HtmlComment { .. } => "html_comment",
+            Self::Callout { .. } => "callout",
+            Self::VisibilityBlock { .. } => "visibility_block",
+            Self::Custom { .. } => "custom",
+            Self::Conflict { .. } => "conflict",
+            Self::TableOfContents { .. } => "table_of_contents",
+            Self::TempListItem(_) => "list_item",
+            Self::TempTableCell(_) => "table_cell",
+        }
+    }
+
+    /// Returns the block nodes nested directly inside this node
+    pub(crate) fn child_blocks(&self) -> Vec<&Node> {
+        match self {
+            Self::List { items, .. } => items.iter().flat_map(|item| &item.children).collect(),
+            Self::BlockQuote { children }
+            | Self::Group { children, .. }
+            | Self::Callout { children, .. }
+            | Self::VisibilityBlock { children, .. } => children.iter().collect(),
+            Self::FootnoteDefinition(definition) => definition.content.iter().collect(),
+            Self::DefinitionList { items } => items
+                .iter()
+                .flat_map(|item| item.descriptions.iter().flatten())
+                .collect(),
+            Self::Conflict { ours, theirs } => ours.iter().chain(theirs).collect(),
+            Self::TempListItem(item) => item.children.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mutable counterpart of [`Node::child_blocks`]
+    pub(crate) fn child_blocks_mut(&mut self) -> Vec<&mut Node> {
+        match self {
+            Self::List { items, .. } => items
+                .iter_mut()
+                .flat_map(|item| &mut item.children)
+                .collect(),
+            Self::BlockQuote { children }
+            | Self::Group { children, .. }
+            | Self::Callout { children, .. }
+            | Self::VisibilityBlock { children, .. } => children.iter_mut().collect(),
+            Self::FootnoteDefinition(definition) => definition.content.iter_mut().collect(),
+            Self::DefinitionList { items } => items
+                .iter_mut()
+                .flat_map(|item| item.descriptions.iter_mut().flatten())
+                .collect(),
+            Self::Conflict { ours, theirs } => ours.iter_mut().chain(theirs).collect(),
+            Self::TempListItem(item) => item.children.iter_mut().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the lists of blocks nested directly inside this node, for
+    /// inserting or removing blocks
+    pub(crate) fn block_lists_mut(&mut self) -> Vec<&mut Vec<Node>> {
+        match self {
+            Self::List { items, .. } => items.iter_mut().map(|item| &mut item.children).collect(),
+            Self::BlockQuote { children }
+            | Self::Group { children, .. }
+            | Self::Callout { children, .. }
+            | Self::VisibilityBlock { children, .. } => vec![children],
+            Self::FootnoteDefinition(definition) => vec![&mut definition.content],
+            Self::DefinitionList { items } => items
+                .iter_mut()
+                .flat_map(|item| item.descriptions.iter_mut())
+                .collect(),
+            Self::Conflict { ours, theirs } => vec![ours, theirs],
+            Self::TempListItem(item) => vec![&mut item.children],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the inline runs owned by this node itself, excluding nested blocks
+    pub(crate) fn inline_runs(&self) -> Vec<&Vec<InlineNode>> {
+        match self {
+            Self::Heading { children, .. } | Self::Paragraph { children } => vec![children],
+            Self::Table { header, rows, .. } => std::iter::once(header)
+                .chain(rows)
+                .flatten()
+                .map(|cell| &cell.content)
+                .collect(),
+            Self::DefinitionList { items } => items.iter().map(|item| &item.term).collect(),
+            Self::Figure { caption, .. } => vec![caption],
+            Self::TempTableCell(cell) => vec![&cell.content],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Mutable counterpart of [`Node::inline_runs`]
+    pub(crate) fn inline_runs_mut(&mut self) -> Vec<&mut Vec<InlineNode>> {
+        match self {
+            Self::Heading { children, .. } | Self::Paragraph { children } => vec![children],
+            Self::Table { header, rows, .. } => std::iter::once(header)
+                .chain(rows.iter_mut())
+                .flatten()
+                .map(|cell| &mut cell.content)
+                .collect(),
+            Self::DefinitionList { items } => items.iter_mut().map(|item| &mut item.term).collect(),
+            Self::Figure { caption, .. } => vec![caption],
+            Self::TempTableCell(cell) => vec![&mut cell.content],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Calls `f` on this node and every nested block, parents first
+    pub(crate) fn walk(&self, f: &mut dyn FnMut(&Node)) {
+        f(self);
+        for child in self.child_blocks() {
+            child.walk(f);
+        }
+    }
+
+    /// Mutable counterpart of [`Node::walk`]
+    pub(crate) fn walk_mut(&mut self, f: &mut dyn FnMut(&mut Node)) {
+        f(self);
+        for child in self.child_blocks_mut() {
+            child.walk_mut(f);
+        }
+    }
+
+    /// Calls `f` with this node and every block nested inside it, parents
+    /// first, along with each block's path
+    ///
+    /// `path` is the path of this node. Children extend it with their index,
+    /// list items with the item and child index, and definition lists with
+    /// the item, description and child index.
+    pub(crate) fn walk_with_path(&self, path: &mut Vec<usize>, f: &mut dyn FnMut(&Node, &[usize])) {
+        f(self, path);
+        let mut visit = |children: &[Node], path: &mut Vec<usize>| {
+            for (i, child) in children.iter().enumerate() {
+                path.push(i);
+                child.walk_with_path(path, f);
+                path.pop();
+            }
+        };
+
+        match self {
+            Self::BlockQuote { children }
+            | Self::Group { children, .. }
+            | Self::Callout { children, .. }
+            | Self::VisibilityBlock { children, .. } => visit(children, path),
+            Self::FootnoteDefinition(definition) => visit(&definition.content, path),
+            Self::List { items, .. } => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(i);
+                    visit(&item.children, path);
+                    path.pop();
+                }
+            }
+            Self::DefinitionList { items } => {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(i);
+                    for (j, description) in item.descriptions.iter().enumerate() {
+                        path.push(j);
+                        visit(description, path);
+                        path.pop();
+                    }
+                    path.pop();
+                }
+            }
+            Self::Conflict { ours, theirs } => {
+                for (i, side) in [ours, theirs].into_iter().enumerate() {
+                    path.push(i);
+                    visit(side, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
 }
@@ -0,0 +1,186 @@
+use crate::{Document, InlineNode, Node, Position, Selection};
+use serde::{Deserialize, Serialize};
+
+/// The logical "current cell" of a table, used for keyboard navigation
+///
+/// Rows are counted visually: row 0 is the header row and body row `n` is
+/// row `n + 1`. In the document selection a cell is addressed by the path
+/// `[node_index, row, column]`, with the offset inside the cell's text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TableCursor {
+    /// Index of the table node in the document
+    pub node_index: usize,
+    /// Visual row, where 0 is the header row
+    pub row: usize,
+    /// Column within the row
+    pub column: usize,
+}
+
+impl TableCursor {
+    /// Creates a cursor at the given cell
+    pub fn new(node_index: usize, row: usize, column: usize) -> Self {
+        Self {
+            node_index,
+            row,
+            column,
+        }
+    }
+
+    /// Returns true if the cursor is in the header row
+    pub fn is_header(&self) -> bool {
+        self.row == 0
+    }
+
+    /// Index into the table's body rows, or None for the header row
+    pub fn body_row(&self) -> Option<usize> {
+        self.row.checked_sub(1)
+    }
+
+    fn at(self, row: usize, column: usize) -> Self {
+        Self::new(self.node_index, row, column)
+    }
+}
+
+/// Table navigation helpers for Document
+impl Document {
+    /// Returns the table cell holding the start of the selection, if any
+    pub fn table_cursor(&self) -> Option<TableCursor> {
+        let selection = self.selection.as_ref()?;
+        let [node_index, row, column] = selection.start.path[..] else {
+            return None;
+        };
+        let cursor = TableCursor::new(node_index, row, column);
+        self.table_cell_content(cursor).map(|_| cursor)
+    }
+
+    /// Moves the selection into a table cell, placing the caret after its text
+    pub fn set_table_cursor(&mut self, cursor: TableCursor) -> bool {
+        let Some(content) = self.table_cell_content(cursor) else {
+            return false;
+        };
+        let offset = InlineNode::plain_text_of(content).len();
+        self.selection = Some(Selection::collapsed(Position::new(
+            vec![cursor.node_index, cursor.row, cursor.column],
+            offset,
+        )));
+        true
+    }
+
+    /// The cell after the cursor in reading order, or None past the last cell
+    pub fn next_table_cell(&self, cursor: TableCursor) -> Option<TableCursor> {
+        let widths = self.table_row_widths(cursor.node_index)?;
+        if cursor.column + 1 < *widths.get(cursor.row)? {
+            return Some(cursor.at(cursor.row, cursor.column + 1));
+        }
+        (cursor.row + 1..widths.len())
+            .find(|&row| widths[row] > 0)
+            .map(|row| cursor.at(row, 0))
+    }
+
+    /// The cell before the cursor in reading order, or None before the first cell
+    pub fn previous_table_cell(&self, cursor: TableCursor) -> Option<TableCursor> {
+        let widths = self.table_row_widths(cursor.node_index)?;
+        if cursor.column > 0 {
+            return Some(cursor.at(cursor.row, cursor.column - 1));
+        }
+        (0..cursor.row)
+            .rev()
+            .find(|&row| widths[row] > 0)
+            .map(|row| cursor.at(row, widths[row] - 1))
+    }
+
+    /// The cell in the row above, keeping the column where the row allows
+    pub fn table_cell_above(&self, cursor: TableCursor) -> Option<TableCursor> {
+        let widths = self.table_row_widths(cursor.node_index)?;
+        (0..cursor.row)
+            .rev()
+            .find(|&row| widths[row] > 0)
+            .map(|row| cursor.at(row, cursor.column.min(widths[row] - 1)))
+    }
+
+    /// The cell in the row below, keeping the column where the row allows
+    pub fn table_cell_below(&self, cursor: TableCursor) -> Option<TableCursor> {
+        let widths = self.table_row_widths(cursor.node_index)?;
+        (cursor.row + 1..widths.len())
+            .find(|&row| widths[row] > 0)
+            .map(|row| cursor.at(row, cursor.column.min(widths[row] - 1)))
+    }
+
+    /// Number of cells in each visual row of a table, header first
+    fn table_row_widths(&self, node_index: usize) -> Option<Vec<usize>> {
+        match self.nodes.get(node_index)? {
+            Node::Table { header, rows, .. } => Some(
+                std::iter::once(header.len())
+                    .chain(rows.iter().map(Vec::len))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn table_cell_content(&self, cursor: TableCursor) -> Option<&[InlineNode]> {
+        let Node::Table { header, rows, .. } = self.nodes.get(cursor.node_index)? else {
+            return None;
+        };
+        let row = match cursor.body_row() {
+            None => header,
+            Some(row) => rows.get(row)?,
+        };
+        row.get(cursor.column).map(|cell| cell.content.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_document() -> Document {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("Intro");
+        doc.nodes.push(Node::simple_table(
+            vec!["Name", "Age"],
+            vec![vec!["Alice", "30"], vec!["Bob", "25"]],
+        ));
+        doc
+    }
+
+    #[test]
+    fn test_table_cursor_selection_mapping() {
+        let mut doc = table_document();
+        assert_eq!(doc.table_cursor(), None);
+
+        assert!(doc.set_table_cursor(TableCursor::new(1, 1, 0)));
+        let selection = doc.selection.as_ref().unwrap();
+        assert_eq!(selection.start.path, vec![1, 1, 0]);
+        assert_eq!(selection.start.offset, "Alice".len());
+        assert_eq!(doc.table_cursor(), Some(TableCursor::new(1, 1, 0)));
+
+        assert!(!doc.set_table_cursor(TableCursor::new(1, 3, 0)));
+        assert!(!doc.set_table_cursor(TableCursor::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_table_cell_movement() {
+        let doc = table_document();
+        let first = TableCursor::new(1, 0, 0);
+        let last = TableCursor::new(1, 2, 1);
+
+        assert_eq!(doc.next_table_cell(first), Some(TableCursor::new(1, 0, 1)));
+        assert_eq!(
+            doc.next_table_cell(TableCursor::new(1, 0, 1)),
+            Some(TableCursor::new(1, 1, 0))
+        );
+        assert_eq!(doc.next_table_cell(last), None);
+
+        assert_eq!(doc.previous_table_cell(first), None);
+        assert_eq!(
+            doc.previous_table_cell(TableCursor::new(1, 2, 0)),
+            Some(TableCursor::new(1, 1, 1))
+        );
+
+        assert_eq!(doc.table_cell_above(first), None);
+        assert_eq!(doc.table_cell_above(last), Some(TableCursor::new(1, 1, 1)));
+        assert_eq!(doc.table_cell_below(first), Some(TableCursor::new(1, 1, 0)));
+        assert_eq!(doc.table_cell_below(last), None);
+    }
+}
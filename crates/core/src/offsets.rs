@@ -0,0 +1,220 @@
+//! Conversion between byte, character and grapheme offsets
+//!
+//! Editing commands and selections address text by byte offset into a node's
+//! offset text: the concatenated text runs of a paragraph or heading, where
+//! every other inline takes a single offset, or the code of a code block.
+//! User interfaces usually count characters or grapheme clusters instead, so
+//! an emoji with a skin tone or a letter with a combining accent is one step.
+//! The helpers here convert between the three and reject offsets that would
+//! split a cluster.
+
+use crate::{Document, EditError, InlineNode, Node, Position};
+use serde::{Deserialize, Serialize};
+use std::iter;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Stand-in for inline nodes other than text, which take up one offset
+const OBJECT_PLACEHOLDER: char = '\u{1a}';
+
+/// The unit an offset into a node's text is counted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetKind {
+    /// UTF-8 bytes, the unit used by the document model
+    #[default]
+    Byte,
+    /// Unicode scalar values
+    Char,
+    /// Extended grapheme clusters, i.e. user-perceived characters
+    Grapheme,
+}
+
+/// Text of a node as offsets see it
+///
+/// Paragraphs and headings concatenate their text runs, with every other
+/// inline taking a single offset. Code blocks use their code. Other nodes
+/// have no text and only offer offset 0.
+pub(crate) fn offset_text(node: &Node) -> String {
+    match node {
+        Node::Paragraph { children } | Node::Heading { children, .. } => children
+            .iter()
+            .map(|child| match child {
+                InlineNode::Text(text_node) => text_node.text.clone(),
+                _ => OBJECT_PLACEHOLDER.to_string(),
+            })
+            .collect(),
+        Node::CodeBlock { code, .. } => code.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Grapheme cluster boundaries of `text`, including 0 and its length
+pub(crate) fn grapheme_boundaries(text: &str) -> Vec<usize> {
+    text.grapheme_indices(true)
+        .map(|(offset, _)| offset)
+        .chain(iter::once(text.len()))
+        .collect()
+}
+
+/// Returns true if `offset` lies inside `text` but splits a grapheme cluster
+fn splits_grapheme(text: &str, offset: usize) -> bool {
+    offset < text.len() && grapheme_boundaries(text).binary_search(&offset).is_err()
+}
+
+/// Rejects a byte offset that falls inside a grapheme cluster of the node
+///
+/// Offsets past the end of the text are left for the caller to judge, as
+/// commands differ in how they treat them.
+pub(crate) fn check_offset(node: &Node, offset: usize) -> Result<(), EditError> {
+    if splits_grapheme(&offset_text(node), offset) {
+        Err(EditError::invalid_range()
+            .with_message(format!("Offset {} splits a grapheme cluster", offset)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Rejects a position whose offset falls inside a grapheme cluster of the
+/// node its path leads to
+///
+/// Paths that lead nowhere are left for the caller to judge.
+pub(crate) fn check_position(document: &Document, position: &Position) -> Result<(), EditError> {
+    let Some(node) = position
+        .path
+        .first()
+        .and_then(|&index| document.nodes.get(index))
+    else {
+        return Ok(());
+    };
+    let mut result = Ok(());
+    node.walk_with_path(&mut vec![position.path[0]], &mut |block, path| {
+        if path == position.path.as_slice() {
+            result = check_offset(block, position.offset);
+        }
+    });
+    result.map_err(|err| err.at_path(position.path.clone()))
+}
+
+fn to_byte(text: &str, offset: usize, kind: OffsetKind) -> Option<usize> {
+    match kind {
+        OffsetKind::Byte => {
+            (offset <= text.len() && !splits_grapheme(text, offset)).then_some(offset)
+        }
+        OffsetKind::Char => text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(iter::once(text.len()))
+            .nth(offset)
+            .filter(|&byte| !splits_grapheme(text, byte)),
+        OffsetKind::Grapheme => grapheme_boundaries(text).get(offset).copied(),
+    }
+}
+
+fn text_length(text: &str, kind: OffsetKind) -> usize {
+    match kind {
+        OffsetKind::Byte => text.len(),
+        OffsetKind::Char => text.chars().count(),
+        OffsetKind::Grapheme => text.graphemes(true).count(),
+    }
+}
+
+fn from_byte(text: &str, byte: usize, kind: OffsetKind) -> Option<usize> {
+    if byte > text.len() || splits_grapheme(text, byte) {
+        return None;
+    }
+    match kind {
+        OffsetKind::Byte => Some(byte),
+        OffsetKind::Char => Some(text[..byte].chars().count()),
+        OffsetKind::Grapheme => grapheme_boundaries(text).binary_search(&byte).ok(),
+    }
+}
+
+impl Document {
+    /// Converts an offset of the given kind into a byte offset
+    ///
+    /// Fails with [`EditErrorKind::IndexOutOfBounds`](crate::EditErrorKind::IndexOutOfBounds) for a missing node and
+    /// [`EditErrorKind::InvalidRange`](crate::EditErrorKind::InvalidRange) if the offset is past the end of the text
+    /// or inside a grapheme cluster.
+    pub fn to_byte_offset(
+        &self,
+        node_index: usize,
+        offset: usize,
+        kind: OffsetKind,
+    ) -> Result<usize, EditError> {
+        let node = self
+            .nodes
+            .get(node_index)
+            .ok_or(EditError::index_out_of_bounds().at_node(node_index))?;
+        let text = offset_text(node);
+        to_byte(&text, offset, kind).ok_or_else(|| {
+            EditError::invalid_range()
+                .at_node(node_index)
+                .with_ranges(0..text_length(&text, kind), offset..offset)
+        })
+    }
+
+    /// Converts a byte offset into an offset of the given kind
+    pub fn from_byte_offset(
+        &self,
+        node_index: usize,
+        byte: usize,
+        kind: OffsetKind,
+    ) -> Result<usize, EditError> {
+        let node = self
+            .nodes
+            .get(node_index)
+            .ok_or(EditError::index_out_of_bounds().at_node(node_index))?;
+        let text = offset_text(node);
+        from_byte(&text, byte, kind).ok_or_else(|| {
+            EditError::invalid_range()
+                .at_node(node_index)
+                .with_ranges(0..text.len(), byte..byte)
+        })
+    }
+
+    /// Length of a node's text in the given unit
+    pub fn text_length(&self, node_index: usize, kind: OffsetKind) -> Result<usize, EditError> {
+        let node = self
+            .nodes
+            .get(node_index)
+            .ok_or(EditError::index_out_of_bounds().at_node(node_index))?;
+        Ok(text_length(&offset_text(node), kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_conversion() {
+        let mut doc = Document::new();
+        // "é" is e + combining accent, the flag is two regional indicators
+        doc.add_paragraph_with_text("e\u{301}🇯🇵 ok");
+
+        assert_eq!(doc.text_length(0, OffsetKind::Byte).unwrap(), 14);
+        assert_eq!(doc.text_length(0, OffsetKind::Char).unwrap(), 7);
+        assert_eq!(doc.text_length(0, OffsetKind::Grapheme).unwrap(), 5);
+
+        assert_eq!(doc.to_byte_offset(0, 2, OffsetKind::Grapheme).unwrap(), 11);
+        assert_eq!(doc.to_byte_offset(0, 2, OffsetKind::Char).unwrap(), 3);
+        assert_eq!(
+            doc.from_byte_offset(0, 11, OffsetKind::Grapheme).unwrap(),
+            2
+        );
+        assert_eq!(doc.from_byte_offset(0, 11, OffsetKind::Char).unwrap(), 4);
+
+        // Offsets inside a cluster or past the end are rejected
+        assert!(doc.to_byte_offset(0, 1, OffsetKind::Char).is_err());
+        assert!(doc.to_byte_offset(0, 7, OffsetKind::Byte).is_err());
+        let err = doc.to_byte_offset(0, 6, OffsetKind::Grapheme).unwrap_err();
+        assert_eq!(err.path(), Some(&[0][..]));
+        assert_eq!(
+            (err.expected(), err.actual()),
+            (Some(&(0..5)), Some(&(6..6)))
+        );
+        assert!(doc.from_byte_offset(0, 1, OffsetKind::Grapheme).is_err());
+        let err = doc.to_byte_offset(1, 0, OffsetKind::Byte).unwrap_err();
+        assert_eq!(err.path(), Some(&[1][..]));
+    }
+}
@@ -0,0 +1,420 @@
+//! Heading outline of a document
+//!
+//! The outline nests headings by level: a heading owns every following heading
+//! of a deeper level until the next heading of the same or a higher level.
+//! Each entry carries a GitHub-style anchor so outlines can link into the
+//! rendered document.
+
+use crate::{Document, EditError, Node};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A heading in the outline, with the headings nested under it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutlineEntry {
+    /// Heading level (1-6)
+    pub level: u8,
+    /// Plain text of the heading
+    pub text: String,
+    /// Index of the heading node in the document
+    pub node_index: usize,
+    /// Anchor slug, unique within the document
+    pub anchor: String,
+    /// Headings nested under this one
+    pub children: Vec<OutlineEntry>,
+}
+
+/// The heading tree of a document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Outline {
+    /// Top-level entries in document order
+    pub entries: Vec<OutlineEntry>,
+}
+
+impl Outline {
+    /// Builds the outline of a document
+    pub fn new(document: &Document) -> Self {
+        let mut entries: Vec<OutlineEntry> = Vec::new();
+        // Path of child indices from the roots to the most recent entry
+        let mut open: Vec<usize> = Vec::new();
+
+        for (node_index, anchor) in document.heading_anchors() {
+            let node = &document.nodes[node_index];
+            let Node::Heading { level, .. } = node else {
+                continue;
+            };
+            let entry = OutlineEntry {
+                level: *level,
+                text: node.plain_text(),
+                anchor,
+                node_index,
+                children: Vec::new(),
+            };
+
+            // Close entries that are not shallower than the new heading
+            let mut siblings = &mut entries;
+            let mut depth = 0;
+            while depth < open.len() && siblings[open[depth]].level < entry.level {
+                siblings = &mut siblings[open[depth]].children;
+                depth += 1;
+            }
+            open.truncate(depth);
+            open.push(siblings.len());
+            siblings.push(entry);
+        }
+
+        Self { entries }
+    }
+
+    /// Iterates over all entries depth-first, in document order
+    pub fn iter(&self) -> impl Iterator<Item = &OutlineEntry> {
+        let mut stack: Vec<&OutlineEntry> = self.entries.iter().rev().collect();
+        std::iter::from_fn(move || {
+            let entry = stack.pop()?;
+            stack.extend(entry.children.iter().rev());
+            Some(entry)
+        })
+    }
+
+    /// Finds the entry for the heading at `node_index`
+    pub fn find(&self, node_index: usize) -> Option<&OutlineEntry> {
+        self.iter().find(|entry| entry.node_index == node_index)
+    }
+}
+
+/// Where a section dragged in an outline view is dropped, relative to the
+/// heading at a node index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineDrop {
+    /// Right before the heading, at its level
+    Before(usize),
+    /// Right after the heading's whole section, at its level
+    After(usize),
+    /// At the end of the heading's section, one level deeper
+    Into(usize),
+}
+
+/// The section move an outline drop implies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SectionMove {
+    /// Index of the dragged heading
+    pub heading_index: usize,
+    /// Node index, before the move, the section goes to
+    pub to_position: usize,
+    /// Change of level of every heading in the section
+    pub level_shift: i8,
+}
+
+impl SectionMove {
+    /// Returns true if the move leaves the document as it is
+    pub fn is_noop(&self, section: &Range<usize>) -> bool {
+        self.level_shift == 0
+            && (self.to_position == section.start || self.to_position == section.end)
+    }
+}
+
+/// A heading enclosing a node, as shown in a breadcrumb trail
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Breadcrumb {
+    /// Heading level (1-6)
+    pub level: u8,
+    /// Plain text of the heading
+    pub text: String,
+    /// Index of the heading node in the document
+    pub node_index: usize,
+    /// Anchor slug of the heading
+    pub anchor: String,
+}
+
+/// Generates GitHub-compatible heading slugs, numbering repeats
+#[derive(Debug, Default)]
+pub(crate) struct Slugger {
+    seen: HashMap<String, usize>,
+}
+
+impl Slugger {
+    /// Returns the slug for the text, suffixed with `-1`, `-2`, ... on repeats
+    pub(crate) fn slug(&mut self, text: &str) -> String {
+        let base = slugify(text);
+        let mut slug = base.clone();
+        if let Some(&count) = self.seen.get(&base) {
+            let mut suffix = count;
+            loop {
+                suffix += 1;
+                slug = format!("{}-{}", base, suffix);
+                if !self.seen.contains_key(&slug) {
+                    break;
+                }
+            }
+            self.seen.insert(base, suffix);
+        }
+        self.seen.insert(slug.clone(), 0);
+        slug
+    }
+}
+
+/// Converts heading text to a GitHub-style anchor slug
+///
+/// Letters are lowercased, spaces become hyphens, and punctuation other than
+/// hyphens and underscores is dropped.
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Document {
+    /// Returns the heading tree of the document
+    pub fn outline(&self) -> Outline {
+        Outline::new(self)
+    }
+
+    /// Returns the headings enclosing a node, outermost first
+    ///
+    /// For a heading, the trail holds its ancestors but not the heading
+    /// itself. Returns an empty trail for nodes before the first heading or
+    /// out of range.
+    pub fn breadcrumb_for(&self, node_index: usize) -> Vec<Breadcrumb> {
+        let mut trail: Vec<Breadcrumb> = Vec::new();
+        if node_index >= self.nodes.len() {
+            return trail;
+        }
+        let own_level = match &self.nodes[node_index] {
+            Node::Heading { level, .. } => *level,
+            _ => u8::MAX,
+        };
+
+        for (index, anchor) in self.heading_anchors() {
+            if index >= node_index {
+                break;
+            }
+            let node = &self.nodes[index];
+            let Node::Heading { level, .. } = node else {
+                continue;
+            };
+            trail.retain(|crumb| crumb.level < *level);
+            trail.push(Breadcrumb {
+                level: *level,
+                text: node.plain_text(),
+                node_index: index,
+                anchor,
+            });
+        }
+
+        trail.retain(|crumb| crumb.level < own_level);
+        trail
+    }
+
+    /// Returns the anchor of every top-level heading, in document order
+    pub(crate) fn heading_anchors(&self) -> Vec<(usize, String)> {
+        let mut slugs = Slugger::default();
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, Node::Heading { .. }))
+            .map(|(index, node)| (index, slugs.slug(&node.plain_text())))
+            .collect()
+    }
+
+    /// Works out the section move for dropping the section of the heading
+    /// at `heading_index` in an outline view
+    ///
+    /// The section keeps its shape: all its headings change level by as
+    /// much as the dragged one. Fails if either end is not a heading, if the
+    /// target lies in the dragged section itself, or if a heading would
+    /// leave levels 1 to 6.
+    pub fn plan_outline_drop(
+        &self,
+        heading_index: usize,
+        drop: OutlineDrop,
+    ) -> Result<SectionMove, EditError> {
+        let range = self
+            .section_range(heading_index)
+            .ok_or(EditError::invalid_node().at_node(heading_index))?;
+        let (OutlineDrop::Before(target) | OutlineDrop::After(target) | OutlineDrop::Into(target)) =
+            drop;
+        let (Some(target_range), Some(Node::Heading { level, .. })) =
+            (self.section_range(target), self.nodes.get(target))
+        else {
+            return Err(EditError::invalid_node().at_node(target));
+        };
+        if range.contains(&target) {
+            return Err(EditError::invalid_range()
+                .at_node(target)
+                .with_message("Cannot drop a section into itself or one of its subsections"));
+        }
+
+        let (to_position, new_level) = match drop {
+            OutlineDrop::Before(_) => (target, *level),
+            OutlineDrop::After(_) => (target_range.end, *level),
+            OutlineDrop::Into(_) => (target_range.end, level + 1),
+        };
+        let Node::Heading {
+            level: old_level, ..
+        } = &self.nodes[heading_index]
+        else {
+            unreachable!("sections start with a heading");
+        };
+        let level_shift = new_level as i8 - *old_level as i8;
+        let deepest = self.nodes[range.clone()]
+            .iter()
+            .filter_map(|node| match node {
+                Node::Heading { level, .. } => Some(*level as i8 + level_shift),
+                _ => None,
+            })
+            .max()
+            .unwrap_or_default();
+        if deepest > 6 {
+            return Err(EditError::unsupported()
+                .at_node(heading_index)
+                .with_message("Headings cannot be nested deeper than level 6"));
+        }
+
+        Ok(SectionMove {
+            heading_index,
+            to_position,
+            level_shift,
+        })
+    }
+
+    /// Returns the nodes making up the section opened by a heading
+    ///
+    /// The section runs from the heading up to, but not including, the next
+    /// heading of the same or a higher level.
+    pub fn section_range(&self, heading_index: usize) -> Option<Range<usize>> {
+        let Node::Heading { level, .. } = self.nodes.get(heading_index)? else {
+            return None;
+        };
+        let end = self.nodes[heading_index + 1..]
+            .iter()
+            .position(|node| matches!(node, Node::Heading { level: l, .. } if l <= level))
+            .map_or(self.nodes.len(), |offset| heading_index + 1 + offset);
+        Some(heading_index..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book() -> Document {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Book");
+        doc.add_heading(2, "Intro");
+        doc.add_paragraph_with_text("Hello");
+        doc.add_heading(3, "Why?");
+        doc.add_heading(2, "Usage");
+        doc.add_heading(2, "Intro");
+        doc.add_heading(1, "Appendix");
+        doc
+    }
+
+    #[test]
+    fn test_outline_tree() {
+        let outline = book().outline();
+
+        assert_eq!(outline.entries.len(), 2);
+        let book = &outline.entries[0];
+        assert_eq!(book.children.len(), 3);
+        assert_eq!(book.children[0].children[0].text, "Why?");
+        assert_eq!(book.children[0].children[0].node_index, 3);
+
+        let anchors: Vec<&str> = outline.iter().map(|entry| entry.anchor.as_str()).collect();
+        assert_eq!(
+            anchors,
+            ["book", "intro", "why", "usage", "intro-1", "appendix"]
+        );
+        assert_eq!(outline.find(4).unwrap().text, "Usage");
+    }
+
+    #[test]
+    fn test_outline_starts_below_top_level() {
+        let mut doc = Document::new();
+        doc.add_heading(2, "First");
+        doc.add_heading(1, "Second");
+        doc.add_heading(3, "Nested");
+
+        let outline = doc.outline();
+        assert_eq!(outline.entries.len(), 2);
+        assert_eq!(outline.entries[1].children[0].text, "Nested");
+    }
+
+    #[test]
+    fn test_breadcrumb_for() {
+        let doc = book();
+        let texts = |index| -> Vec<String> {
+            doc.breadcrumb_for(index)
+                .into_iter()
+                .map(|crumb| crumb.text)
+                .collect()
+        };
+
+        assert_eq!(texts(2), ["Book", "Intro"]);
+        assert_eq!(texts(3), ["Book", "Intro"]);
+        assert_eq!(texts(4), ["Book"]);
+        assert!(texts(0).is_empty());
+        assert!(texts(6).is_empty());
+        assert!(texts(42).is_empty());
+        assert_eq!(doc.breadcrumb_for(5)[0].anchor, "book");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("API v2.0 — notes"), "api-v20--notes");
+        assert_eq!(slugify("snake_case and-dash"), "snake_case-and-dash");
+
+        let mut slugger = Slugger::default();
+        assert_eq!(slugger.slug("A"), "a");
+        assert_eq!(slugger.slug("A"), "a-1");
+        assert_eq!(slugger.slug("A-1"), "a-1-1");
+        assert_eq!(slugger.slug("A"), "a-2");
+    }
+
+    #[test]
+    fn test_plan_outline_drop() {
+        let doc = book();
+        let plan = |heading, drop| doc.plan_outline_drop(heading, drop);
+
+        // "Why?" (level 3) dropped before "Usage" becomes a level 2 sibling
+        let moved = plan(3, OutlineDrop::Before(4)).unwrap();
+        assert_eq!((moved.to_position, moved.level_shift), (4, -1));
+        // "Usage" dropped into "Appendix" ends the document at level 2
+        let moved = plan(4, OutlineDrop::Into(6)).unwrap();
+        assert_eq!((moved.to_position, moved.level_shift), (7, 0));
+        // "Intro" after "Book" ends up at level 1 after the whole book
+        let moved = plan(1, OutlineDrop::After(0)).unwrap();
+        assert_eq!((moved.to_position, moved.level_shift), (6, -1));
+        let moved = plan(6, OutlineDrop::Before(0)).unwrap();
+        assert!(!moved.is_noop(&doc.section_range(6).unwrap()));
+        assert!(plan(1, OutlineDrop::Before(4)).unwrap().is_noop(&(1..4)));
+
+        assert!(plan(0, OutlineDrop::Into(3)).is_err());
+        assert!(plan(2, OutlineDrop::Before(0)).is_err());
+        assert!(plan(0, OutlineDrop::Before(2)).is_err());
+
+        let mut deep = Document::new();
+        deep.add_heading(5, "A");
+        deep.add_heading(6, "A.1");
+        deep.add_heading(5, "B");
+        assert!(deep.plan_outline_drop(0, OutlineDrop::Into(2)).is_err());
+        assert!(deep.plan_outline_drop(2, OutlineDrop::Into(0)).is_ok());
+    }
+
+    #[test]
+    fn test_section_range() {
+        let doc = book();
+        assert_eq!(doc.section_range(0), Some(0..6));
+        assert_eq!(doc.section_range(1), Some(1..4));
+        assert_eq!(doc.section_range(6), Some(6..7));
+        assert_eq!(doc.section_range(2), None);
+    }
+}
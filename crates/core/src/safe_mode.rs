@@ -0,0 +1,478 @@
+//! Safe mode for untrusted documents
+//!
+//! Sanitizing raw HTML is not enough to show a document from an untrusted
+//! source: links can run `javascript:` URLs, images loading from other
+//! servers tell them who reads the document, and CSS classes and styles
+//! restyle the page around it. A [`SafeMode`] profile removes all of these
+//! from a document with [`Document::make_safe`], before HTML export with
+//! [`HtmlOptions::with_safe_mode`] and after import with
+//! [`Document::from_untrusted_html`], and reports what it removed so the
+//! reader can be told.
+
+use crate::convert::sanitize::{HtmlSanitizer, start_tags, url_scheme};
+use crate::urls::figure_text;
+use crate::{ASSET_SCHEME, Document, HtmlOptions, InlineNode, Node, ParseError, UrlPolicy};
+use serde::Serialize;
+
+/// What safe mode removes from untrusted documents
+///
+/// Raw HTML blocks, tags and comments, pre-rendered embed HTML and HTML
+/// outputs of notebook cells, URLs the [`UrlPolicy`] doesn't allow, and CSS
+/// classes, styles and colors of tables, cells and code blocks are always
+/// removed. Images loading from
+/// another server are removed too, unless they are loaded through an image
+/// proxy or explicitly allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SafeMode {
+    policy: UrlPolicy,
+    image_proxy: Option<String>,
+    external_images: bool,
+}
+
+impl SafeMode {
+    /// Safe mode with the default [`UrlPolicy`], removing external images
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads external images through `prefix`, followed by the
+    /// percent-encoded image URL, e.g. `https://proxy.example/?url=`
+    pub fn with_image_proxy(mut self, prefix: impl Into<String>) -> Self {
+        self.image_proxy = Some(prefix.into());
+        self
+    }
+
+    /// Keeps external images loading from their own URL
+    pub fn allow_external_images(mut self) -> Self {
+        self.external_images = true;
+        self
+    }
+
+    /// Checks link and image URLs with `policy` instead of the default one
+    pub fn with_url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether an image URL loads from another server
+    fn is_external(url: &str) -> bool {
+        url.starts_with("//")
+            || url_scheme(url).is_some_and(|(scheme, _)| scheme != "data" && scheme != ASSET_SCHEME)
+    }
+
+    /// The URL an external image is loaded from, or `None` to remove it
+    fn image_url(&self, url: &str, index: usize, report: &mut SafetyReport) -> Option<String> {
+        if self.external_images || !Self::is_external(url) {
+            return Some(url.to_string());
+        }
+        match &self.image_proxy {
+            Some(prefix) => {
+                report.proxied_images += 1;
+                Some(format!("{prefix}{}", encode_component(url)))
+            }
+            None => {
+                report.push(RemovalKind::ExternalImage, Some(index), url);
+                None
+            }
+        }
+    }
+
+    /// Makes a top-level node safe, or returns `None` if nothing of it is
+    /// left
+    pub(crate) fn node(&self, node: Node, index: usize, report: &mut SafetyReport) -> Option<Node> {
+        let mut nodes = vec![node];
+        self.blocks(&mut nodes, index, report);
+        nodes.pop()
+    }
+
+    fn blocks(&self, nodes: &mut Vec<Node>, index: usize, report: &mut SafetyReport) {
+        nodes.retain_mut(|node| {
+            match node {
+                Node::HtmlBlock { html } => {
+                    report.raw_html(index, html);
+                    return false;
+                }
+                Node::HtmlComment { text } => {
+                    report.push(RemovalKind::RawHtml, Some(index), &format!("<!--{text}-->"));
+                    return false;
+                }
+                _ => {}
+            }
+            self.block(node, index, report);
+            for list in node.block_lists_mut() {
+                self.blocks(list, index, report);
+            }
+            true
+        });
+    }
+
+    /// Makes a block safe, but not its nested blocks
+    fn block(&self, block: &mut Node, index: usize, report: &mut SafetyReport) {
+        let mut urls = Vec::new();
+        self.policy.sanitize_block(block, &mut urls);
+        for url in urls {
+            report.push(RemovalKind::UnsafeUrl, Some(index), &url);
+        }
+
+        match block {
+            Node::Embed { html, .. } => {
+                if let Some(html) = html.take() {
+                    report.raw_html(index, &html);
+                }
+            }
+            Node::Figure { url, .. } => match self.image_url(url, index, report) {
+                Some(safe) => *url = safe,
+                None => *block = figure_text(block),
+            },
+            Node::Table {
+                header,
+                rows,
+                properties,
+                ..
+            } => {
+                report.styles(index, properties.css_class.take(), properties.style.take());
+                for cell in header.iter_mut().chain(rows.iter_mut().flatten()) {
+                    if let Some(color) = cell.background_color.take() {
+                        report.push(
+                            RemovalKind::Style,
+                            Some(index),
+                            &format!("background-color: {color}"),
+                        );
+                    }
+                    report.styles(index, cell.css_class.take(), cell.style.take());
+                }
+            }
+            Node::CodeBlock { properties, .. } => {
+                report.styles(index, properties.css_class.take(), properties.style.take());
+                properties.outputs.retain(|output| {
+                    let raw = matches!(output.mime_type.as_str(), "text/html" | "image/svg+xml");
+                    if raw {
+                        report.raw_html(index, &output.data);
+                    }
+                    !raw
+                });
+            }
+            _ => {}
+        }
+        for inlines in block.inline_runs_mut() {
+            self.inlines(inlines, index, report);
+        }
+    }
+
+    fn inlines(&self, inlines: &mut Vec<InlineNode>, index: usize, report: &mut SafetyReport) {
+        let mut result = Vec::with_capacity(inlines.len());
+        for mut inline in std::mem::take(inlines) {
+            match &mut inline {
+                InlineNode::Html { html } => {
+                    report.raw_html(index, html);
+                    continue;
+                }
+                InlineNode::HtmlComment { text } => {
+                    report.push(RemovalKind::RawHtml, Some(index), &format!("<!--{text}-->"));
+                    continue;
+                }
+                InlineNode::Image { url, alt, .. } => match self.image_url(url, index, report) {
+                    Some(safe) => *url = safe,
+                    None => {
+                        if !alt.is_empty() {
+                            result.push(InlineNode::text(alt.as_str()));
+                        }
+                        continue;
+                    }
+                },
+                InlineNode::Link { children, .. } | InlineNode::InlineFootnote { children } => {
+                    self.inlines(children, index, report);
+                }
+                _ => {}
+            }
+            result.push(inline);
+        }
+        *inlines = result;
+    }
+}
+
+/// Percent-encodes everything but unreserved URL characters
+fn encode_component(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Kind of content safe mode removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalKind {
+    /// Raw HTML blocks, tags and comments, pre-rendered embeds, and tags of
+    /// imported HTML the sanitizer doesn't allow
+    RawHtml,
+    /// A link or image URL with a scheme the URL policy doesn't allow, such
+    /// as `javascript:`
+    UnsafeUrl,
+    /// An image loading from another server
+    ExternalImage,
+    /// A CSS class, inline style or background color
+    Style,
+    /// An event handler attribute such as `onclick`, in raw or imported
+    /// HTML
+    EventAttribute,
+}
+
+/// A piece of content safe mode removed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RemovedContent {
+    /// What kind of content it was
+    pub kind: RemovalKind,
+    /// Index of the top-level node it was removed from, or `None` for
+    /// imported HTML removed before parsing
+    pub node_index: Option<usize>,
+    /// The removed HTML, URL, style or attribute
+    pub content: String,
+}
+
+/// What safe mode removed from a document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SafetyReport {
+    /// Removed content, in document order
+    pub removed: Vec<RemovedContent>,
+    /// Number of external images now loaded through the image proxy
+    pub proxied_images: usize,
+}
+
+impl SafetyReport {
+    /// Whether nothing was removed
+    pub fn is_clean(&self) -> bool {
+        self.removed.is_empty()
+    }
+
+    /// Number of removed pieces of content of `kind`
+    pub fn count(&self, kind: RemovalKind) -> usize {
+        self.removed.iter().filter(|item| item.kind == kind).count()
+    }
+
+    fn push(&mut self, kind: RemovalKind, node_index: Option<usize>, content: &str) {
+        self.removed.push(RemovedContent {
+            kind,
+            node_index,
+            content: content.to_string(),
+        });
+    }
+
+    /// Reports removed raw HTML along with the event handlers it had
+    fn raw_html(&mut self, index: usize, html: &str) {
+        self.push(RemovalKind::RawHtml, Some(index), html);
+        for (_, attributes) in start_tags(html) {
+            for (name, value) in attributes {
+                if name.starts_with("on") {
+                    self.push(
+                        RemovalKind::EventAttribute,
+                        Some(index),
+                        &attribute(&name, value.as_deref()),
+                    );
+                }
+            }
+        }
+    }
+
+    fn styles(&mut self, index: usize, class: Option<String>, style: Option<String>) {
+        if let Some(class) = class {
+            self.push(
+                RemovalKind::Style,
+                Some(index),
+                &attribute("class", Some(&class)),
+            );
+        }
+        if let Some(style) = style {
+            self.push(
+                RemovalKind::Style,
+                Some(index),
+                &attribute("style", Some(&style)),
+            );
+        }
+    }
+}
+
+/// An attribute as written in HTML
+fn attribute(name: &str, value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("{name}=\"{value}\""),
+        None => name.to_string(),
+    }
+}
+
+impl Document {
+    /// Removes what `mode` doesn't allow in an untrusted document,
+    /// reporting what was removed
+    ///
+    /// Raw HTML blocks and comments are dropped, disallowed links keep
+    /// their text and removed images their alt text or caption. Works on
+    /// documents from any importer.
+    pub fn make_safe(&mut self, mode: &SafeMode) -> SafetyReport {
+        let mut report = SafetyReport::default();
+        let nodes = std::mem::take(&mut self.nodes);
+        for (index, node) in nodes.into_iter().enumerate() {
+            self.nodes.extend(mode.node(node, index, &mut report));
+        }
+        report
+    }
+
+    /// Imports untrusted HTML in safe mode
+    ///
+    /// The HTML is sanitized with the default [`HtmlSanitizer`] before
+    /// parsing; the tags it drops, event handler and style attributes, and
+    /// disallowed URLs are reported without a node index. The parsed
+    /// document is then made safe with [`make_safe`](Self::make_safe).
+    pub fn from_untrusted_html(
+        html: &str,
+        mode: &SafeMode,
+    ) -> Result<(Document, SafetyReport), ParseError> {
+        let sanitizer = HtmlSanitizer::default();
+        let mut report = SafetyReport::default();
+        for (tag, attributes) in start_tags(html) {
+            if !sanitizer.allows_tag(&tag) {
+                report.push(RemovalKind::RawHtml, None, &format!("<{tag}>"));
+            }
+            for (name, value) in attributes {
+                let content = attribute(&name, value.as_deref());
+                if name.starts_with("on") {
+                    report.push(RemovalKind::EventAttribute, None, &content);
+                } else if name == "style" {
+                    report.push(RemovalKind::Style, None, &content);
+                } else if matches!(name.as_str(), "href" | "src")
+                    && let Some(url) = value.filter(|url| !sanitizer.allows_url(url))
+                {
+                    report.push(RemovalKind::UnsafeUrl, None, &url);
+                }
+            }
+        }
+
+        let options = HtmlOptions::new()
+            .with_sanitizer(sanitizer)
+            .with_url_policy(mode.policy.clone());
+        let mut document = Document::from_html_with(html, &options)?;
+        let safe = document.make_safe(mode);
+        report.removed.extend(safe.removed);
+        report.proxied_images += safe.proxied_images;
+        Ok((document, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, TableCell, Text};
+
+    #[test]
+    fn test_make_safe() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "# Notes\n\nSee [this](javascript:alert(1)) and <b onclick=\"steal()\">bold</b> \
+             ![logo](https://tracker.example/logo.png) ![local](img/a.png)\n\n\
+             <div onmouseover=\"x()\">block</div>\n\n<!-- note -->\n\n```rust\nfn a() {}\n```\n",
+        ))
+        .unwrap();
+        let Node::CodeBlock { properties, .. } = &mut doc.nodes[4] else {
+            panic!("expected a code block");
+        };
+        properties.style = Some("position: fixed".to_string());
+        let mut cell = TableCell::text("x");
+        cell.background_color = Some("#f00".to_string());
+        doc.nodes.push(Node::Table {
+            header: Vec::new(),
+            rows: vec![vec![cell]],
+            alignments: Vec::new(),
+            properties: Default::default(),
+        });
+
+        let report = doc.make_safe(&SafeMode::new());
+        assert_eq!(
+            doc.nodes.len(),
+            4,
+            "raw HTML blocks and comments are dropped"
+        );
+        let paragraph = doc.nodes[1].as_paragraph().unwrap();
+        assert_eq!(
+            InlineNode::plain_text_of(paragraph),
+            "See this and bold logo local"
+        );
+        assert_eq!(
+            paragraph.last(),
+            Some(&InlineNode::image("img/a.png", "local"))
+        );
+        let removed: Vec<(RemovalKind, Option<usize>, &str)> = report
+            .removed
+            .iter()
+            .map(|item| (item.kind, item.node_index, item.content.as_str()))
+            .collect();
+        assert_eq!(
+            removed,
+            [
+                (RemovalKind::UnsafeUrl, Some(1), "javascript:alert(1)"),
+                (RemovalKind::RawHtml, Some(1), "<b onclick=\"steal()\">"),
+                (RemovalKind::EventAttribute, Some(1), "onclick=\"steal()\""),
+                (RemovalKind::RawHtml, Some(1), "</b>"),
+                (
+                    RemovalKind::ExternalImage,
+                    Some(1),
+                    "https://tracker.example/logo.png"
+                ),
+                (
+                    RemovalKind::RawHtml,
+                    Some(2),
+                    "<div onmouseover=\"x()\">block</div>"
+                ),
+                (RemovalKind::EventAttribute, Some(2), "onmouseover=\"x()\""),
+                (RemovalKind::RawHtml, Some(3), "<!-- note -->"),
+                (RemovalKind::Style, Some(4), "style=\"position: fixed\""),
+                (RemovalKind::Style, Some(5), "background-color: #f00"),
+            ]
+        );
+        assert_eq!(report.count(RemovalKind::RawHtml), 4);
+
+        let mut doc = Document::new();
+        doc.nodes
+            .push(Node::paragraph_with_inlines(vec![InlineNode::image(
+                "https://cdn.example/a b.png",
+                "a",
+            )]));
+        let report = doc.make_safe(&SafeMode::new().with_image_proxy("/proxy?url="));
+        assert!(report.is_clean());
+        assert_eq!(report.proxied_images, 1);
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap(),
+            &[InlineNode::image(
+                "/proxy?url=https%3A%2F%2Fcdn.example%2Fa%20b.png",
+                "a"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_safe_html_export_and_import() {
+        let doc = Document::try_from(Text::<Markdown>::new(
+            "Hi <script>alert(1)</script> [x](javascript:void(0))\n",
+        ))
+        .unwrap();
+        let output = doc.to_html_with(&HtmlOptions::new().with_safe_mode(SafeMode::new()));
+        assert_eq!(output.html, "<p>Hi alert(1) x</p>");
+        assert_eq!(output.safety.count(RemovalKind::RawHtml), 2);
+        assert_eq!(output.safety.count(RemovalKind::UnsafeUrl), 1);
+
+        let (doc, report) = Document::from_untrusted_html(
+            "<p style=\"color:red\" onclick=\"x()\">Hi <a href=\"javascript:x()\">there</a>\
+             <img src=\"https://t.example/p.gif\" alt=\"pixel\"></p><script>steal()</script>",
+            &SafeMode::new(),
+        )
+        .unwrap();
+        assert_eq!(doc.nodes[0].plain_text(), "Hi there pixel");
+        assert_eq!(report.count(RemovalKind::EventAttribute), 1);
+        assert_eq!(report.count(RemovalKind::Style), 1);
+        assert_eq!(report.count(RemovalKind::UnsafeUrl), 1);
+        assert_eq!(report.count(RemovalKind::RawHtml), 1, "the script tag");
+        assert_eq!(report.count(RemovalKind::ExternalImage), 1);
+    }
+}
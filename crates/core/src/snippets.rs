@@ -0,0 +1,368 @@
+//! Snippets with variables and tab stops
+//!
+//! A snippet is a piece of a document, usually written in Markdown, with
+//! placeholders in its text:
+//!
+//! - `{{name}}` is replaced by the value of the variable `name`. `date` and
+//!   `time` default to the current UTC date (`2024-05-01`) and time
+//!   (`09:30`); unknown variables are kept as written.
+//! - `{{1}}`, `{{2:default text}}` are tab stops, visited in the order of
+//!   their numbers, holding their default text if any.
+//! - `{{cursor}}` is where the cursor ends up after the last tab stop.
+//!
+//! Tab stops are only found in the text of top-level paragraphs, headings
+//! and code blocks, where the editor can select them; elsewhere they are
+//! replaced by their default text. When a number is used more than once,
+//! its first use is the tab stop.
+
+use crate::{DocumentFragment, InlineNode, Node, ParseError};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*(?:(cursor)|(\d+)(?::([^{}]*))?|([A-Za-z_][\w.-]*))\s*\}\}").unwrap()
+});
+
+/// A named piece of content to insert, with variables and tab stops
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    fragment: DocumentFragment,
+}
+
+/// A tab stop of an expanded snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    /// Index of the node within the expanded fragment; always 0 for inline
+    /// fragments
+    pub node: usize,
+    /// Byte range of the stop's text in the node, empty without default
+    /// text
+    pub range: Range<usize>,
+}
+
+/// A snippet with its variables replaced and its tab stops located
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandedSnippet {
+    /// Content to insert
+    pub fragment: DocumentFragment,
+    /// Tab stops in the order to visit them, ending with the `{{cursor}}`
+    /// if the snippet has one
+    pub tab_stops: Vec<TabStop>,
+}
+
+/// A placeholder found in a text, by the order it is visited in: numbered
+/// tab stops first, then the cursor
+type FoundStop = ((bool, usize), Range<usize>);
+
+impl Snippet {
+    /// Creates a snippet from a fragment
+    pub fn new(fragment: impl Into<DocumentFragment>) -> Self {
+        Self {
+            fragment: fragment.into(),
+        }
+    }
+
+    /// Parses a snippet from Markdown, which is inline content if it is a
+    /// single paragraph without a trailing newline
+    pub fn from_markdown(markdown: &str) -> Result<Self, ParseError> {
+        Ok(Self::new(DocumentFragment::from_markdown(markdown)?))
+    }
+
+    /// The snippet's content, placeholders included
+    pub fn fragment(&self) -> &DocumentFragment {
+        &self.fragment
+    }
+
+    /// Replaces the variables with `vars` and the built-in `date` and
+    /// `time`, and locates the tab stops
+    pub fn expand(&self, vars: &[(&str, &str)]) -> ExpandedSnippet {
+        let timestamp = utc_timestamp(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+        );
+        let mut values = BTreeMap::from([
+            ("date", timestamp[..10].to_string()),
+            ("time", timestamp[11..16].to_string()),
+        ]);
+        values.extend(vars.iter().map(|&(name, value)| (name, value.to_string())));
+
+        let mut fragment = self.fragment.clone();
+        let mut stops: Vec<(usize, FoundStop)> = Vec::new();
+        match &mut fragment {
+            DocumentFragment::Inline(inlines) => {
+                for found in expand_run(inlines, &values) {
+                    stops.push((0, found));
+                }
+            }
+            DocumentFragment::Blocks(nodes) => {
+                for (index, node) in nodes.iter_mut().enumerate() {
+                    for found in expand_node(node, &values) {
+                        stops.push((index, found));
+                    }
+                }
+            }
+        }
+
+        stops.sort_by_key(|(_, (order, _))| *order);
+        stops.dedup_by_key(|(_, (order, _))| *order);
+        ExpandedSnippet {
+            fragment,
+            tab_stops: stops
+                .into_iter()
+                .map(|(node, (_, range))| TabStop { node, range })
+                .collect(),
+        }
+    }
+}
+
+/// Replaces the placeholders of `text`, returning the new text and where
+/// its tab stops ended up
+fn expand_text(text: &str, values: &BTreeMap<&str, String>) -> (String, Vec<FoundStop>) {
+    let mut expanded = String::with_capacity(text.len());
+    let mut stops = Vec::new();
+    let mut last = 0;
+    for captures in PLACEHOLDER.captures_iter(text) {
+        let whole = captures.get(0).unwrap();
+        expanded.push_str(&text[last..whole.start()]);
+        last = whole.end();
+        let start = expanded.len();
+        let order = match (captures.get(1), captures.get(2), captures.get(4)) {
+            (Some(_), _, _) => (true, 0),
+            (_, Some(number), _) => {
+                expanded.push_str(captures.get(3).map_or("", |text| text.as_str()));
+                (false, number.as_str().parse().unwrap_or(usize::MAX))
+            }
+            (_, _, Some(name)) => {
+                let value = values.get(name.as_str()).map(String::as_str);
+                expanded.push_str(value.unwrap_or(whole.as_str()));
+                continue;
+            }
+            _ => unreachable!("the placeholder pattern has three alternatives"),
+        };
+        stops.push((order, start..expanded.len()));
+    }
+    expanded.push_str(&text[last..]);
+    (expanded, stops)
+}
+
+/// Expands the placeholders of a top-level node, returning its tab stops
+fn expand_node(node: &mut Node, values: &BTreeMap<&str, String>) -> Vec<FoundStop> {
+    match node {
+        Node::Paragraph { children } | Node::Heading { children, .. } => {
+            expand_run(children, values)
+        }
+        Node::CodeBlock { code, .. } => {
+            let (expanded, stops) = expand_text(code, values);
+            *code = expanded;
+            stops
+        }
+        _ => {
+            node.walk_mut(&mut |block| {
+                if let Node::CodeBlock { code, .. } = block {
+                    *code = expand_text(code, values).0;
+                }
+                for inlines in block.inline_runs_mut() {
+                    expand_run(inlines, values);
+                }
+            });
+            Vec::new()
+        }
+    }
+}
+
+/// Expands the placeholders of a paragraph's or heading's inline content,
+/// returning the tab stops found in its text runs by offset
+fn expand_run(inlines: &mut Vec<InlineNode>, values: &BTreeMap<&str, String>) -> Vec<FoundStop> {
+    merge_text(inlines);
+    let mut stops = Vec::new();
+    let mut offset = 0;
+    for inline in inlines {
+        match inline {
+            InlineNode::Text(text) => {
+                let (expanded, found) = expand_text(&text.text, values);
+                text.text = expanded;
+                stops.extend(
+                    found
+                        .into_iter()
+                        .map(|(order, range)| (order, range.start + offset..range.end + offset)),
+                );
+                offset += text.text.len();
+            }
+            _ => {
+                inline.walk_mut(&mut |inline| match inline {
+                    InlineNode::Text(text) => text.text = expand_text(&text.text, values).0,
+                    InlineNode::Link { url, .. } | InlineNode::Image { url, .. } => {
+                        *url = expand_text(url, values).0;
+                    }
+                    _ => {}
+                });
+                offset += 1;
+            }
+        }
+    }
+    stops
+}
+
+/// Joins adjacent text nodes of the same formatting, so placeholders the
+/// Markdown parser split are found whole
+fn merge_text(inlines: &mut Vec<InlineNode>) {
+    let mut merged: Vec<InlineNode> = Vec::with_capacity(inlines.len());
+    for inline in inlines.drain(..) {
+        if let (Some(InlineNode::Text(last)), InlineNode::Text(text)) = (merged.last_mut(), &inline)
+            && last.formatting == text.formatting
+        {
+            last.text.push_str(&text.text);
+            continue;
+        }
+        merged.push(inline);
+    }
+    *inlines = merged;
+}
+
+/// Formats seconds since the Unix epoch as `CCYY-MM-DDThh:mm:ssZ`
+pub(crate) fn utc_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+    // Civil date from days since the epoch, after Howard Hinnant
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Snippets by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnippetRegistry {
+    snippets: BTreeMap<String, Snippet>,
+}
+
+impl SnippetRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a snippet, returning the one it replaces
+    pub fn register(&mut self, name: impl Into<String>, snippet: Snippet) -> Option<Snippet> {
+        self.snippets.insert(name.into(), snippet)
+    }
+
+    /// Removes a snippet, returning it if it was registered
+    pub fn remove(&mut self, name: &str) -> Option<Snippet> {
+        self.snippets.remove(name)
+    }
+
+    /// The snippet with the given name
+    pub fn get(&self, name: &str) -> Option<&Snippet> {
+        self.snippets.get(name)
+    }
+
+    /// Names of the registered snippets, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.snippets.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_snippet() {
+        let snippet = Snippet::from_markdown(
+            "# {{title}}\n\nBy {{2:author}} on {{date}}, see [{{link_text}}]({{url}}) and {{1}}{{cursor}}\n\n```\nlet {{1:x}} = {{3:0}};\n```\n",
+        )
+        .unwrap();
+        let expanded = snippet.expand(&[
+            ("title", "Notes"),
+            ("link_text", "docs"),
+            ("url", "https://example.com"),
+        ]);
+        let DocumentFragment::Blocks(nodes) = &expanded.fragment else {
+            panic!("expected blocks");
+        };
+        assert_eq!(nodes[0].plain_text(), "Notes");
+        let text = crate::offsets::offset_text(&nodes[1]);
+        assert!(text.starts_with("By author on 20"), "{text}");
+        assert!(text.ends_with(", see \u{1a} and "), "{text}");
+        let Node::Paragraph { children } = &nodes[1] else {
+            panic!("expected a paragraph");
+        };
+        assert!(matches!(
+            &children[1],
+            InlineNode::Link { url, children, .. }
+                if url == "https://example.com" && InlineNode::plain_text_of(children) == "docs"
+        ));
+        let Node::CodeBlock { code, .. } = &nodes[2] else {
+            panic!("expected a code block");
+        };
+        assert_eq!(code, "let x = 0;");
+
+        let end = text.len();
+        assert_eq!(
+            expanded.tab_stops,
+            vec![
+                TabStop {
+                    node: 1,
+                    range: end..end
+                },
+                TabStop {
+                    node: 1,
+                    range: 3..9
+                },
+                TabStop {
+                    node: 2,
+                    range: 8..9
+                },
+                TabStop {
+                    node: 1,
+                    range: end..end
+                },
+            ]
+        );
+
+        let inline = Snippet::from_markdown("Hi {{ name }}, {{1:there}}").unwrap();
+        let expanded = inline.expand(&[("name", "Ann")]);
+        assert_eq!(expanded.fragment.to_markdown().trim_end(), "Hi Ann, there");
+        assert_eq!(
+            expanded.tab_stops,
+            vec![TabStop {
+                node: 0,
+                range: 8..13
+            }]
+        );
+        assert_eq!(
+            Snippet::from_markdown("{{unknown}}")
+                .unwrap()
+                .expand(&[])
+                .fragment,
+            DocumentFragment::Inline(vec![InlineNode::text("{{unknown}}")])
+        );
+    }
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400 + 3_661), "2000-02-29T01:01:01Z");
+    }
+}
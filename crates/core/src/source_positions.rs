@@ -0,0 +1,111 @@
+//! Source positions of parsed nodes
+//!
+//! With [`MarkdownOptions::with_source_positions`](crate::MarkdownOptions::with_source_positions),
+//! Markdown import records the byte and line range each top-level node was
+//! read from as [node attributes](crate::NodeAttributes), so tools can map
+//! lint findings or diffs back to the source text. Being attributes, the
+//! positions are saved in JSON and follow their nodes through edits; a
+//! node the editor replaces loses them.
+
+use crate::Document;
+use std::ops::Range;
+
+/// Attribute holding the byte offset a node starts at
+const START: &str = "source-start";
+/// Attribute holding the byte offset a node ends at
+const END: &str = "source-end";
+/// Attribute holding the line a node starts on
+const LINE: &str = "source-line";
+/// Attribute holding the line a node ends on
+const END_LINE: &str = "source-end-line";
+
+/// Where a top-level node came from in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// Byte range in the source, without trailing whitespace
+    pub bytes: Range<usize>,
+    /// First line, starting from 1
+    pub line: usize,
+    /// Last line, starting from 1
+    pub end_line: usize,
+}
+
+impl Document {
+    /// Stores the source byte ranges of the top-level nodes as attributes
+    pub(crate) fn record_source_spans(&mut self, source: &str, spans: Vec<Range<usize>>) {
+        for (index, span) in spans.into_iter().enumerate().take(self.nodes.len()) {
+            let end = span.start + source[span.clone()].trim_end().len();
+            let line = source[..span.start].matches('\n').count() + 1;
+            let end_line = line + source[span.start..end].matches('\n').count();
+            let attributes = self.node_attributes.entry(index).or_default();
+            attributes.insert(START.to_string(), span.start.to_string());
+            attributes.insert(END.to_string(), end.to_string());
+            attributes.insert(LINE.to_string(), line.to_string());
+            attributes.insert(END_LINE.to_string(), end_line.to_string());
+        }
+    }
+
+    /// Returns where the top-level node at `node_index` came from in the
+    /// source, if it was parsed with source positions
+    pub fn source_span(&self, node_index: usize) -> Option<SourceSpan> {
+        let value = |name| self.node_attribute(node_index, name)?.parse().ok();
+        Some(SourceSpan {
+            bytes: value(START)?..value(END)?,
+            line: value(LINE)?,
+            end_line: value(END_LINE)?,
+        })
+    }
+
+    /// Returns the top-level node whose source spans `line`, starting
+    /// from 1
+    pub fn node_at_source_line(&self, line: usize) -> Option<usize> {
+        (0..self.nodes.len()).find(|&index| {
+            self.source_span(index)
+                .is_some_and(|span| (span.line..=span.end_line).contains(&line))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, MarkdownOptions, Node, Text};
+
+    #[test]
+    fn test_source_positions() {
+        let markdown = "# Title\n\nSome *text*\nwrapped\n\n::: note\nInside\n:::\n\n\
+                        <!-- toc:2 -->\n\n* [Title](#title)\n\n<!-- /toc -->\n\n```\ncode\n```\n";
+        let options = MarkdownOptions::new().with_source_positions();
+        let doc = Document::from_markdown_with(markdown, &options).unwrap();
+        assert_eq!(doc.nodes.len(), 5);
+        let span = |index| doc.source_span(index).unwrap();
+        assert_eq!(
+            span(0),
+            SourceSpan {
+                bytes: 0..7,
+                line: 1,
+                end_line: 1
+            }
+        );
+        assert_eq!(&markdown[span(1).bytes], "Some *text*\nwrapped");
+        assert_eq!((span(1).line, span(1).end_line), (3, 4));
+        assert_eq!(&markdown[span(2).bytes], "::: note\nInside\n:::");
+        assert_eq!(doc.nodes[3], Node::TableOfContents { max_level: 2 });
+        assert_eq!(
+            &markdown[span(3).bytes],
+            "<!-- toc:2 -->\n\n* [Title](#title)\n\n<!-- /toc -->"
+        );
+        assert_eq!(&markdown[span(4).bytes], "```\ncode\n```");
+        assert_eq!(doc.node_at_source_line(4), Some(1));
+        assert_eq!(doc.node_at_source_line(5), None);
+
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(html.as_str().starts_with(
+            "<h1 id=\"title\" data-source-end=\"7\" data-source-end-line=\"1\" \
+             data-source-line=\"1\" data-source-start=\"0\">"
+        ));
+        let plain = Document::try_from(Text::<crate::Markdown>::new(markdown)).unwrap();
+        assert_eq!(plain.nodes, doc.nodes);
+        assert_eq!(plain.source_span(0), None);
+    }
+}
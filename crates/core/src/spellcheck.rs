@@ -0,0 +1,262 @@
+//! Plumbing for spellcheckers and other prose linters
+//!
+//! [`Document::text_runs`] extracts the prose of a document as contiguous
+//! runs, leaving out code, math, URLs and other inlines a checker should
+//! not see. Each run records where it starts in its node, so a checker's
+//! findings can be attached back to the document as [`Annotation`]s with
+//! [`Editor::annotate`](crate::Editor::annotate). The editor moves
+//! annotations along as edits shift the text around them and drops those
+//! whose text was edited, so they can be checked again. No dictionary is
+//! included; that is up to the application.
+
+use crate::mapping::{PositionMapper, RangeMode, range_fits};
+use crate::{Document, InlineNode, Node};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A stretch of prose in a paragraph or heading
+///
+/// Text runs with different formatting join into one run, so a word
+/// partly in bold is still one word.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TextRun {
+    /// Path to the paragraph or heading: the top-level node index followed
+    /// by the child indices through any containers
+    pub path: Vec<usize>,
+    /// Byte offset of the run in the node's text, as used by editing
+    /// commands
+    pub offset: usize,
+    /// The prose
+    pub text: String,
+}
+
+impl TextRun {
+    /// Byte range of the run in the node's text
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.text.len()
+    }
+}
+
+/// Returns true if a whitespace-separated word looks like a URL or an email
+/// address
+fn is_url_like(word: &str) -> bool {
+    word.contains("://")
+        || word.starts_with("www.")
+        || word
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'))
+}
+
+/// Collects text runs from inline content
+struct RunBuilder<'a> {
+    path: &'a [usize],
+    runs: Vec<TextRun>,
+    current: Option<TextRun>,
+}
+
+impl RunBuilder<'_> {
+    fn push(&mut self, offset: usize, text: &str) {
+        match &mut self.current {
+            Some(run) if run.offset + run.text.len() == offset => run.text.push_str(text),
+            _ => {
+                self.finish();
+                self.current = Some(TextRun {
+                    path: self.path.to_vec(),
+                    offset,
+                    text: text.to_string(),
+                });
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(run) = self.current.take()
+            && !run.text.trim().is_empty()
+        {
+            self.runs.push(run);
+        }
+    }
+
+    /// Adds prose text starting at `offset`, leaving out URL-like words
+    fn text(&mut self, offset: usize, text: &str) {
+        let mut start = 0;
+        for (index, word) in text.split_whitespace().map(|word| {
+            let index = word.as_ptr() as usize - text.as_ptr() as usize;
+            (index, word)
+        }) {
+            if is_url_like(word) {
+                if index > start {
+                    self.push(offset + start, &text[start..index]);
+                }
+                self.finish();
+                start = index + word.len();
+            }
+        }
+        if start < text.len() {
+            self.push(offset + start, &text[start..]);
+        }
+    }
+}
+
+/// Text runs of a paragraph's or heading's inline content
+pub(crate) fn inline_runs(path: &[usize], children: &[InlineNode]) -> Vec<TextRun> {
+    let mut builder = RunBuilder {
+        path,
+        runs: Vec::new(),
+        current: None,
+    };
+    let mut offset = 0;
+    for child in children {
+        match child {
+            InlineNode::Text(text) if !text.formatting.code => {
+                builder.text(offset, &text.text);
+                offset += text.text.len();
+            }
+            InlineNode::Text(text) => {
+                builder.finish();
+                offset += text.text.len();
+            }
+            _ => {
+                builder.finish();
+                offset += 1;
+            }
+        }
+    }
+    builder.finish();
+    builder.runs
+}
+
+impl Document {
+    /// Returns the prose of every paragraph and heading, including nested
+    /// ones, in document order
+    ///
+    /// Code blocks, code spans, math, links, images and other non-text
+    /// inlines end a run, and words that look like URLs or email addresses
+    /// are left out. Table cells are not included, as editing commands
+    /// can't address their text.
+    pub fn text_runs(&self) -> Vec<TextRun> {
+        let mut runs = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            node.walk_with_path(&mut vec![index], &mut |node, path| {
+                if let Node::Paragraph { children } | Node::Heading { children, .. } = node {
+                    runs.extend(inline_runs(path, children));
+                }
+            });
+        }
+        runs
+    }
+}
+
+/// A transient mark on a range of text, such as a misspelling
+///
+/// Annotations are not part of the document: they are neither saved nor
+/// undone, and an editor drops them when the text they cover is edited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Path to the paragraph or heading, as in [`TextRun::path`]
+    pub path: Vec<usize>,
+    /// Byte range in the node's text
+    pub range: Range<usize>,
+    /// What the annotation marks, e.g. `"spelling"` or `"grammar"`
+    pub kind: String,
+    /// Replacements to offer, best first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+impl Annotation {
+    /// Creates an annotation without suggestions
+    pub fn new(path: Vec<usize>, range: Range<usize>, kind: impl Into<String>) -> Self {
+        Self {
+            path,
+            range,
+            kind: kind.into(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Sets the replacements to offer
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+}
+
+/// Annotations of an editor, with what they need to follow edits
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AnnotationSet {
+    annotations: Vec<Annotation>,
+}
+
+impl AnnotationSet {
+    pub(crate) fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Adds an annotation, or returns false if it doesn't fit the document
+    pub(crate) fn add(&mut self, document: &Document, annotation: Annotation) -> bool {
+        if !range_fits(document, &annotation.path, &annotation.range) {
+            return false;
+        }
+        self.annotations.push(annotation);
+        true
+    }
+
+    /// Removes the annotations for which `remove` returns true, returning
+    /// how many were removed
+    pub(crate) fn remove(&mut self, remove: impl Fn(&Annotation) -> bool) -> usize {
+        let before = self.annotations.len();
+        self.annotations.retain(|annotation| !remove(annotation));
+        before - self.annotations.len()
+    }
+
+    /// Moves annotations to where their text is after a change, dropping
+    /// those whose text changed
+    pub(crate) fn remap(&mut self, mapper: &PositionMapper) {
+        self.annotations.retain_mut(|annotation| {
+            match mapper.map(&annotation.path, &annotation.range, RangeMode::Strict) {
+                Some((path, range)) => {
+                    annotation.path = path;
+                    annotation.range = range;
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn run(path: Vec<usize>, offset: usize, text: &str) -> TextRun {
+        TextRun {
+            path,
+            offset,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_text_runs() {
+        let doc = Document::try_from(Text::<Markdown>::new(
+            "Helo **wor**ld, see `cde` and www.exmple.com now\n\n\
+             ```\nnot prose\n```\n\n\
+             - Item with [a link](https://example.com) here\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            doc.text_runs(),
+            vec![
+                run(vec![0], 0, "Helo world, see "),
+                run(vec![0], 17, " and "),
+                run(vec![0], 36, " now"),
+                run(vec![2, 0, 0], 0, "Item with "),
+                run(vec![2, 0, 0], 11, " here"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,507 @@
+//! Splitting and joining documents
+//!
+//! [`Document::split_at_headings`] cuts a long document into one document
+//! per section, as static sites and books publish chapters as pages of
+//! their own, and [`Document::concat`] joins documents into one, such as
+//! notes collected for printing. Both keep cross-references working:
+//! footnote definitions go with the parts referring to them, `#anchor`
+//! links to a heading that ends up in another part point at that part, and
+//! footnote and link reference labels that clash between joined documents
+//! are renamed.
+
+use crate::cleanup::footnote_references;
+use crate::convert::markdown::normalize_label;
+use crate::{Document, InlineNode, Node};
+use std::collections::{HashMap, HashSet};
+
+/// `label-2`, `label-3`, ... whichever is first not in `taken`
+fn unique_label(label: &str, taken: &HashSet<String>) -> String {
+    (2..)
+        .map(|n| format!("{label}-{n}"))
+        .find(|candidate| !taken.contains(&normalize_label(candidate)))
+        .unwrap_or_default()
+}
+
+/// Rewrites the URLs of the `#anchor` links in `nodes`
+///
+/// `rewrite` takes the anchor without `#` and returns the new URL, or `None`
+/// to leave the link unchanged.
+fn rewrite_anchor_links(nodes: &mut [Node], rewrite: &dyn Fn(&str) -> Option<String>) {
+    for node in nodes {
+        node.walk_mut(&mut |block| {
+            for inline in block.inline_runs_mut().into_iter().flatten() {
+                inline.walk_mut(&mut |inline| {
+                    if let InlineNode::Link { url, .. } = inline
+                        && let Some(anchor) = url.strip_prefix('#')
+                        && let Some(new_url) = rewrite(anchor)
+                    {
+                        *url = new_url;
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Renames footnote labels in references and definitions alike
+fn rename_footnotes(nodes: &mut [Node], renames: &HashMap<String, String>) {
+    let rename = |label: &mut String| {
+        if let Some(new_label) = renames.get(label.as_str()) {
+            *label = new_label.clone();
+        }
+    };
+    for node in nodes {
+        node.walk_mut(&mut |block| {
+            match block {
+                Node::FootnoteReference(reference) => rename(&mut reference.label),
+                Node::FootnoteDefinition(definition) => rename(&mut definition.label),
+                _ => {}
+            }
+            for inline in block.inline_runs_mut().into_iter().flatten() {
+                inline.walk_mut(&mut |inline| {
+                    if let InlineNode::FootnoteRef { label } = inline {
+                        rename(label);
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// A link reference definition implied by reference-style links
+struct LinkDefinition {
+    label: String,
+    url: String,
+    title: Option<String>,
+}
+
+/// The reference definitions the links in `nodes` use, by normalized label
+/// in order of first use
+fn link_definitions(nodes: &[Node]) -> Vec<(String, LinkDefinition)> {
+    let mut definitions: Vec<(String, LinkDefinition)> = Vec::new();
+    for node in nodes {
+        node.walk(&mut |block| {
+            for inline in block.inline_runs().into_iter().flatten() {
+                inline.walk(&mut |inline| {
+                    let InlineNode::Link {
+                        url,
+                        title,
+                        reference: Some(label),
+                        ..
+                    } = inline
+                    else {
+                        return;
+                    };
+                    let key = normalize_label(label);
+                    if !definitions.iter().any(|(existing, _)| *existing == key) {
+                        definitions.push((
+                            key,
+                            LinkDefinition {
+                                label: label.clone(),
+                                url: url.clone(),
+                                title: title.clone(),
+                            },
+                        ));
+                    }
+                });
+            }
+        });
+    }
+    definitions
+}
+
+/// Renames the labels of reference-style links, keyed by normalized label
+fn rename_link_references(nodes: &mut [Node], renames: &HashMap<String, String>) {
+    for node in nodes {
+        node.walk_mut(&mut |block| {
+            for inline in block.inline_runs_mut().into_iter().flatten() {
+                inline.walk_mut(&mut |inline| {
+                    if let InlineNode::Link {
+                        reference: Some(label),
+                        ..
+                    } = inline
+                        && let Some(new_label) = renames.get(&normalize_label(label))
+                    {
+                        *label = new_label.clone();
+                    }
+                });
+            }
+        });
+    }
+}
+
+impl Document {
+    /// Splits the document into one document per section led by a
+    /// top-level heading of `level` or higher
+    ///
+    /// Parts after the first are linked as `part-2.md`, `part-3.md`, ...;
+    /// see [`Document::split_at_headings_with`].
+    pub fn split_at_headings(&self, level: u8) -> Vec<Document> {
+        self.split_at_headings_with(level, |part| format!("part-{}.md", part + 1))
+    }
+
+    /// Splits the document into one document per section led by a
+    /// top-level heading of `level` or higher, with `part_url` giving the
+    /// URL of each part by its index
+    ///
+    /// Content before the first such heading becomes a part of its own.
+    /// Every part keeps the metadata and bibliography, along with the
+    /// attributes, comments and tracked changes of its nodes. Footnote
+    /// definitions are moved to the end of each part referring to them,
+    /// while unreferenced ones stay where they are. `#anchor` links to
+    /// headings in another part become `{part_url}#anchor`, and anchors are
+    /// renumbered for repeated headings that no longer share a document.
+    pub fn split_at_headings_with<F>(&self, level: u8, part_url: F) -> Vec<Document>
+    where
+        F: Fn(usize) -> String,
+    {
+        let is_definition = |index: usize| matches!(self.nodes[index], Node::FootnoteDefinition(_));
+        let mut starts = vec![0];
+        starts.extend((1..self.nodes.len()).filter(|&index| {
+            matches!(self.nodes[index], Node::Heading { level: found, .. } if found <= level)
+        }));
+        // Definitions alone before the first heading aren't worth a part
+        if starts.len() > 1 && (0..starts[1]).all(is_definition) {
+            starts.remove(1);
+        }
+        starts.push(self.nodes.len());
+
+        let referenced = footnote_references(&self.nodes);
+        let definitions: Vec<(usize, &crate::FootnoteDefinition)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| match node {
+                Node::FootnoteDefinition(definition) => Some((index, definition)),
+                _ => None,
+            })
+            .collect();
+
+        let mut parts = Vec::new();
+        let mut maps = Vec::new();
+        for range in starts.windows(2).map(|pair| pair[0]..pair[1]) {
+            if range.is_empty() {
+                continue;
+            }
+            let mut part = Document::new();
+            part.metadata = self.metadata.clone();
+            part.bibliography = self.bibliography.clone();
+            let mut map = vec![None; self.nodes.len()];
+            for index in range.clone().filter(|&index| !is_definition(index)) {
+                map[index] = Some(part.nodes.len());
+                part.nodes.push(self.nodes[index].clone());
+            }
+
+            // Definitions can refer to further footnotes, which come along
+            let mut labels = footnote_references(&part.nodes);
+            loop {
+                let wanted: Vec<(usize, &crate::FootnoteDefinition)> = definitions
+                    .iter()
+                    .filter(|(index, definition)| {
+                        map[*index].is_none()
+                            && (labels.contains(&definition.label)
+                                || (range.contains(index)
+                                    && !referenced.contains(&definition.label)))
+                    })
+                    .copied()
+                    .collect();
+                if wanted.is_empty() {
+                    break;
+                }
+                for (index, definition) in wanted {
+                    map[index] = Some(part.nodes.len());
+                    part.nodes
+                        .push(Node::FootnoteDefinition(definition.clone()));
+                    labels.extend(footnote_references(&definition.content));
+                }
+            }
+
+            self.copy_annotations(&map, &mut part);
+            parts.push(part);
+            maps.push(map);
+        }
+
+        // Where each heading ended up: its old anchor to its part and anchor
+        let mut targets: HashMap<String, (usize, String)> = HashMap::new();
+        let anchors: Vec<Vec<(usize, String)>> =
+            parts.iter().map(|part| part.heading_anchors()).collect();
+        for (index, anchor) in self.heading_anchors() {
+            let found = maps.iter().enumerate().find_map(|(part, map)| {
+                let new_index = map[index]?;
+                anchors[part]
+                    .iter()
+                    .find(|(heading, _)| *heading == new_index)
+                    .map(|(_, new_anchor)| (part, new_anchor.clone()))
+            });
+            if let Some(found) = found {
+                targets.entry(anchor).or_insert(found);
+            }
+        }
+        for (index, part) in parts.iter_mut().enumerate() {
+            rewrite_anchor_links(&mut part.nodes, &|anchor| {
+                let (target, new_anchor) = targets.get(anchor)?;
+                Some(if *target == index {
+                    format!("#{new_anchor}")
+                } else {
+                    format!("{}#{new_anchor}", part_url(*target))
+                })
+            });
+        }
+        parts
+    }
+
+    /// Joins documents into one, in order
+    ///
+    /// The metadata is the first document's that has any, and bibliography
+    /// entries are kept once per id. A footnote definition repeating one
+    /// already joined under the same label is dropped; with different
+    /// content, the later footnote is renamed to `label-2` and so on. Link
+    /// reference labels already used for another URL are renamed the same
+    /// way. `#anchor` links follow their headings as anchors are
+    /// renumbered, and node attributes, comments and tracked changes move
+    /// with their nodes, with later ids shifted past earlier ones.
+    pub fn concat(documents: impl IntoIterator<Item = Document>) -> Document {
+        let mut joined = Document::new();
+        for document in documents {
+            joined.append_document(document);
+        }
+        joined
+    }
+
+    /// Appends another document's nodes, as [`Document::concat`] joins them
+    fn append_document(&mut self, mut document: Document) {
+        if self.metadata.is_none() {
+            self.metadata = document.metadata.take();
+        }
+        for entry in std::mem::take(&mut document.bibliography) {
+            if self.bibliography_entry(&entry.id).is_none() {
+                self.bibliography.push(entry);
+            }
+        }
+
+        // Footnotes
+        let defined: HashMap<&str, &[Node]> = self
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::FootnoteDefinition(definition) => {
+                    Some((definition.label.as_str(), definition.content.as_slice()))
+                }
+                _ => None,
+            })
+            .collect();
+        let mut taken: HashSet<String> = footnote_references(&self.nodes)
+            .into_iter()
+            .chain(footnote_references(&document.nodes))
+            .chain(defined.keys().map(|label| label.to_string()))
+            .map(|label| normalize_label(&label))
+            .collect();
+        let mut renames = HashMap::new();
+        let mut duplicates = HashSet::new();
+        for (index, node) in document.nodes.iter().enumerate() {
+            let Node::FootnoteDefinition(definition) = node else {
+                continue;
+            };
+            match defined.get(definition.label.as_str()) {
+                Some(content) if *content == definition.content.as_slice() => {
+                    duplicates.insert(index);
+                }
+                Some(_) if !renames.contains_key(&definition.label) => {
+                    let label = unique_label(&definition.label, &taken);
+                    taken.insert(normalize_label(&label));
+                    renames.insert(definition.label.clone(), label);
+                }
+                _ => {}
+            }
+        }
+        rename_footnotes(&mut document.nodes, &renames);
+
+        // Link reference definitions
+        let ours = link_definitions(&self.nodes);
+        let theirs = link_definitions(&document.nodes);
+        let mut taken: HashSet<String> = ours
+            .iter()
+            .chain(&theirs)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut renames = HashMap::new();
+        for (key, definition) in &theirs {
+            let clashes = ours.iter().any(|(existing, ours)| {
+                existing == key && (ours.url != definition.url || ours.title != definition.title)
+            });
+            if clashes {
+                let label = unique_label(&definition.label, &taken);
+                taken.insert(normalize_label(&label));
+                renames.insert(key.clone(), label);
+            }
+        }
+        rename_link_references(&mut document.nodes, &renames);
+
+        let anchors = document.heading_anchors();
+        let offset = self.nodes.len();
+        let mut map = vec![None; document.nodes.len()];
+        for (index, node) in std::mem::take(&mut document.nodes).into_iter().enumerate() {
+            if !duplicates.contains(&index) {
+                map[index] = Some(self.nodes.len());
+                self.nodes.push(node);
+            }
+        }
+        document.copy_annotations(&map, self);
+
+        let joined = self.heading_anchors();
+        let targets: HashMap<String, String> = anchors
+            .into_iter()
+            .filter_map(|(index, anchor)| {
+                let new_index = map[index]?;
+                let (_, new_anchor) = joined.iter().find(|(heading, _)| *heading == new_index)?;
+                Some((anchor, new_anchor.clone()))
+            })
+            .collect();
+        rewrite_anchor_links(&mut self.nodes[offset..], &|anchor| {
+            targets
+                .get(anchor)
+                .map(|new_anchor| format!("#{new_anchor}"))
+        });
+    }
+
+    /// Copies the attributes, comments and tracked changes of this
+    /// document's nodes into `target`, where node `i` became node `map[i]`
+    ///
+    /// Comment and tracked change ids are shifted past those in `target`.
+    fn copy_annotations(&self, map: &[Option<usize>], target: &mut Document) {
+        let moved = |index: usize| map.get(index).copied().flatten();
+        for (&index, attributes) in &self.node_attributes {
+            if let Some(new_index) = moved(index) {
+                target.node_attributes.insert(new_index, attributes.clone());
+            }
+        }
+
+        let offset = target.comments.iter().map(|c| c.id + 1).max().unwrap_or(0);
+        for comment in &self.comments {
+            if let Some(new_index) = moved(comment.path[0]) {
+                let mut comment = comment.clone();
+                comment.id += offset;
+                comment.path[0] = new_index;
+                target.comments.push(comment);
+            }
+        }
+        let offset = target
+            .tracked_changes
+            .iter()
+            .map(|c| c.id + 1)
+            .max()
+            .unwrap_or(0);
+        for change in &self.tracked_changes {
+            if let Some(new_index) = moved(change.path[0]) {
+                let mut change = change.clone();
+                change.id += offset;
+                change.path[0] = new_index;
+                target.tracked_changes.push(change);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DocumentMetadata, Markdown, Text};
+
+    #[test]
+    fn test_split_at_headings() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "Intro[^a].\n\n\
+             # One\n\nSee [two](#two) and [setup](#setup).\n\n## Setup\n\nText[^b].\n\n\
+             # Two\n\nBack to [one](#one)[^a] and [setup](#setup-1).\n\n## Setup\n",
+        ))
+        .unwrap();
+        doc.add_footnote_definition("a", "Note A.");
+        doc.add_footnote_definition("b", "Note B.");
+        doc.add_footnote_definition("c", "Unused.");
+        doc.metadata = Some(DocumentMetadata {
+            title: Some("Book".to_string()),
+            ..Default::default()
+        });
+        doc.set_node_attribute(6, "line", "14").unwrap();
+
+        let parts = doc.split_at_headings(1);
+        assert_eq!(parts.len(), 3);
+        assert!(
+            parts
+                .iter()
+                .all(|part| part.metadata.as_ref().unwrap().title.as_deref() == Some("Book"))
+        );
+        let labels = |part: &Document| -> Vec<String> {
+            part.nodes
+                .iter()
+                .filter_map(|node| match node {
+                    Node::FootnoteDefinition(definition) => Some(definition.label.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(labels(&parts[0]), ["a"]);
+        assert_eq!(labels(&parts[1]), ["b"]);
+        assert_eq!(labels(&parts[2]), ["a", "c"]);
+        assert_eq!(parts[2].node_attribute(1, "line"), Some("14"));
+
+        let urls = |part: &Document| -> Vec<String> {
+            part.links()
+                .links
+                .into_iter()
+                .map(|link| link.url)
+                .collect()
+        };
+        assert_eq!(urls(&parts[1]), ["part-3.md#two", "#setup"]);
+        assert_eq!(urls(&parts[2]), ["part-2.md#one", "#setup"]);
+
+        let chapters = doc.split_at_headings_with(2, |part| format!("ch{part}.html"));
+        assert_eq!(chapters.len(), 5);
+        assert_eq!(urls(&chapters[1]), ["ch3.html#two", "ch2.html#setup"]);
+        assert!(Document::new().split_at_headings(1).is_empty());
+    }
+
+    #[test]
+    fn test_concat() {
+        let mut first = Document::try_from(Text::<Markdown>::new(
+            "# Intro\n\nSee [site][home][^1] and [docs][d][^n].\n\n\
+             [home]: https://a.example\n[d]: https://docs.example\n",
+        ))
+        .unwrap();
+        first.add_footnote_definition("1", "First.");
+        first.add_footnote_definition("n", "Shared.");
+        let mut second = Document::try_from(Text::<Markdown>::new(
+            "# Intro\n\nBack to [top](#intro), [site][Home][^1] and [docs][d][^n].\n\n\
+             [home]: https://b.example\n[d]: https://docs.example\n",
+        ))
+        .unwrap();
+        second.add_footnote_definition("1", "Second.");
+        second.add_footnote_definition("n", "Shared.");
+        second.add_comment("ann", vec![1], 0..4, "Check").unwrap();
+        let mut first_with_comment = first.clone();
+        first_with_comment
+            .add_comment("bob", vec![0], 0..5, "Title?")
+            .unwrap();
+
+        let joined = Document::concat([first_with_comment, second]);
+        assert_eq!(joined.nodes.len(), 7);
+        let markdown = Text::<Markdown>::try_from(&joined).unwrap();
+        let markdown = markdown.as_str();
+        assert!(markdown.contains("Back to [top](#intro-1), [site][Home-2][^1-2]"));
+        assert!(markdown.contains("[docs][d][^n]."));
+        assert!(markdown.contains("[home]: https://a.example"));
+        assert!(markdown.contains("[Home-2]: https://b.example"));
+        assert!(markdown.contains("[^1-2]: Second."));
+        assert_eq!(markdown.matches("[^n]: Shared.").count(), 1);
+        assert_eq!(joined.check_internal_links().links[0].target, Some(4));
+
+        assert_eq!(joined.comments.len(), 2);
+        assert_eq!(joined.comments[1].path, vec![5]);
+        assert_ne!(joined.comments[0].id, joined.comments[1].id);
+
+        let parts = joined.split_at_headings(1);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(Document::concat(parts).nodes.len(), 7);
+        assert_eq!(Document::concat([first.clone()]), first);
+    }
+}
@@ -0,0 +1,636 @@
+//! Word counts and writing progress
+//!
+//! Statistics are computed per top-level node and rolled up into sections, where
+//! a section starts at a heading and runs until the next heading of the same or
+//! a higher level. Headings can carry a word target, which turns the section
+//! counts into progress figures for longform writing. Counts and reading time
+//! are also available for a range of nodes or the current selection.
+
+use crate::language::separates_words;
+use crate::{Document, EditError, InlineNode, Node};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Reading speed assumed by [`Counts::reading_time`], in words per minute
+pub const READING_WORDS_PER_MINUTE: u32 = 200;
+
+/// Word and character counts for a piece of content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counts {
+    /// Number of whitespace-separated words
+    pub words: usize,
+    /// Number of characters, excluding whitespace
+    pub characters: usize,
+}
+
+impl Counts {
+    /// Counts the words and characters in a block node
+    pub fn of_node(node: &Node) -> Self {
+        Self::of_text(&node.plain_text())
+    }
+
+    /// Counts the words and characters in a block node written in
+    /// `language`, a BCP 47 tag
+    pub fn of_node_in(node: &Node, language: Option<&str>) -> Self {
+        Self::of_text_in(&node.plain_text(), language)
+    }
+
+    /// Counts the words and characters in a string
+    pub fn of_text(text: &str) -> Self {
+        Self::of_text_in(text, None)
+    }
+
+    /// Counts the words and characters in a string written in `language`
+    ///
+    /// Languages written without spaces between words, such as Chinese,
+    /// Japanese and Thai, are split into words by Unicode word boundaries,
+    /// which gives one word per ideograph. Others count whitespace-separated
+    /// words.
+    pub fn of_text_in(text: &str, language: Option<&str>) -> Self {
+        let words = if separates_words(language) {
+            text.split_whitespace().count()
+        } else {
+            text.unicode_words().count()
+        };
+        Self {
+            words,
+            characters: text.chars().filter(|c| !c.is_whitespace()).count(),
+        }
+    }
+
+    /// Estimated time to read the words at [`READING_WORDS_PER_MINUTE`]
+    pub fn reading_time(&self) -> Duration {
+        self.reading_time_at(READING_WORDS_PER_MINUTE)
+    }
+
+    /// Estimated time to read the words at `words_per_minute`, rounded to
+    /// whole seconds
+    pub fn reading_time_at(&self, words_per_minute: u32) -> Duration {
+        let seconds = self.words as f64 * 60.0 / f64::from(words_per_minute.max(1));
+        Duration::from_secs(seconds.round() as u64)
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            words: self.words + other.words,
+            characters: self.characters + other.characters,
+        }
+    }
+}
+
+/// Statistics for a single section of the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionStats {
+    /// Index of the heading that opens the section
+    pub heading_index: usize,
+    /// Level of the opening heading
+    pub level: u8,
+    /// Plain text of the opening heading
+    pub title: String,
+    /// Words in the section body, including nested sections
+    pub words: usize,
+    /// Word-count target set on the heading, if any
+    pub word_target: Option<usize>,
+}
+
+impl SectionStats {
+    /// Returns the fraction of the word target reached, if a target is set
+    ///
+    /// The value exceeds 1.0 once the target has been passed.
+    pub fn progress(&self) -> Option<f64> {
+        match self.word_target {
+            Some(0) => Some(1.0),
+            Some(target) => Some(self.words as f64 / target as f64),
+            None => None,
+        }
+    }
+}
+
+/// Word counts and per-section progress for a document
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DocumentStats {
+    /// Total words in the document
+    pub words: usize,
+    /// Total non-whitespace characters in the document
+    pub characters: usize,
+    /// Statistics for every section, in document order
+    pub sections: Vec<SectionStats>,
+}
+
+impl DocumentStats {
+    /// Computes statistics for a document from scratch
+    ///
+    /// Words are counted as the document language says.
+    pub fn compute(document: &Document) -> Self {
+        let language = document.language();
+        let counts: Vec<Counts> = document
+            .nodes
+            .iter()
+            .map(|node| Counts::of_node_in(node, language))
+            .collect();
+        Self::from_counts(&document.nodes, &counts)
+    }
+
+    /// Builds statistics from precomputed per-node counts
+    fn from_counts(nodes: &[Node], counts: &[Counts]) -> Self {
+        let mut sections = Vec::new();
+
+        for (index, node) in nodes.iter().enumerate() {
+            if let Node::Heading {
+                level,
+                children,
+                properties,
+            } = node
+            {
+                let end = nodes[index + 1..]
+                    .iter()
+                    .position(|next| matches!(next, Node::Heading { level: l, .. } if l <= level))
+                    .map_or(nodes.len(), |offset| index + 1 + offset);
+
+                sections.push(SectionStats {
+                    heading_index: index,
+                    level: *level,
+                    title: InlineNode::plain_text_of(children),
+                    words: counts[index + 1..end].iter().map(|c| c.words).sum(),
+                    word_target: properties.word_target,
+                });
+            }
+        }
+
+        Self {
+            words: counts.iter().map(|c| c.words).sum(),
+            characters: counts.iter().map(|c| c.characters).sum(),
+            sections,
+        }
+    }
+
+    /// Returns the section opened by the heading at `heading_index`
+    pub fn section(&self, heading_index: usize) -> Option<&SectionStats> {
+        self.sections
+            .iter()
+            .find(|section| section.heading_index == heading_index)
+    }
+
+    /// Returns the targeted sections that are not nested inside another targeted section
+    fn outermost_targets(&self) -> impl Iterator<Item = &SectionStats> {
+        let mut enclosing: Option<u8> = None;
+        let mut outermost = Vec::new();
+
+        for section in &self.sections {
+            if let Some(level) = enclosing
+                && section.level <= level
+            {
+                enclosing = None;
+            }
+            if enclosing.is_none() && section.word_target.is_some() {
+                enclosing = Some(section.level);
+                outermost.push(section);
+            }
+        }
+
+        outermost.into_iter()
+    }
+
+    /// Returns the sum of all section targets, if any section has one
+    ///
+    /// Targets on sections nested inside a targeted section are not added
+    /// again, since the outer target already covers them.
+    pub fn total_target(&self) -> Option<usize> {
+        self.outermost_targets()
+            .filter_map(|section| section.word_target)
+            .reduce(|a, b| a + b)
+    }
+
+    /// Returns the overall progress towards the section targets
+    pub fn total_progress(&self) -> Option<f64> {
+        let target = self.total_target()?;
+        if target == 0 {
+            return Some(1.0);
+        }
+        let words: usize = self.outermost_targets().map(|section| section.words).sum();
+        Some(words as f64 / target as f64)
+    }
+}
+
+/// Change in content caused by an edit
+///
+/// Words are compared as multisets, so retyping a word inside a paragraph
+/// counts as one word removed and one added rather than the whole paragraph.
+/// Nodes count as added or removed only when the number of nodes of their
+/// type changes; editing a node in place changes neither.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StatsDelta {
+    /// Words that appeared
+    pub words_added: usize,
+    /// Words that disappeared
+    pub words_removed: usize,
+    /// Non-whitespace characters in the words that appeared
+    pub characters_added: usize,
+    /// Non-whitespace characters in the words that disappeared
+    pub characters_removed: usize,
+    /// Top-level nodes added, by node type
+    pub nodes_added: BTreeMap<&'static str, usize>,
+    /// Top-level nodes removed, by node type
+    pub nodes_removed: BTreeMap<&'static str, usize>,
+}
+
+impl StatsDelta {
+    /// Computes the delta between two runs of top-level nodes
+    pub fn between(before: &[Node], after: &[Node]) -> Self {
+        let mut delta = Self::default();
+
+        let before_text: Vec<String> = before.iter().map(Node::plain_text).collect();
+        let after_text: Vec<String> = after.iter().map(Node::plain_text).collect();
+        let mut words: HashMap<&str, isize> = HashMap::new();
+        for word in before_text.iter().flat_map(|text| text.split_whitespace()) {
+            *words.entry(word).or_default() -= 1;
+        }
+        for word in after_text.iter().flat_map(|text| text.split_whitespace()) {
+            *words.entry(word).or_default() += 1;
+        }
+        for (word, change) in words {
+            let characters = word.chars().count() * change.unsigned_abs();
+            if change > 0 {
+                delta.words_added += change as usize;
+                delta.characters_added += characters;
+            } else {
+                delta.words_removed += change.unsigned_abs();
+                delta.characters_removed += characters;
+            }
+        }
+
+        let mut types: BTreeMap<&'static str, isize> = BTreeMap::new();
+        for node in before {
+            *types.entry(node.type_name()).or_default() -= 1;
+        }
+        for node in after {
+            *types.entry(node.type_name()).or_default() += 1;
+        }
+        for (name, change) in types {
+            if change > 0 {
+                delta.nodes_added.insert(name, change as usize);
+            } else if change < 0 {
+                delta.nodes_removed.insert(name, change.unsigned_abs());
+            }
+        }
+
+        delta
+    }
+
+    /// Returns true if the edit changed no words and no node counts
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Net change in the word count
+    pub fn net_words(&self) -> isize {
+        self.words_added as isize - self.words_removed as isize
+    }
+
+    /// Adds another delta to this one, e.g. to total a writing session
+    pub fn accumulate(&mut self, other: &Self) {
+        self.words_added += other.words_added;
+        self.words_removed += other.words_removed;
+        self.characters_added += other.characters_added;
+        self.characters_removed += other.characters_removed;
+        for (name, count) in &other.nodes_added {
+            *self.nodes_added.entry(name).or_default() += count;
+        }
+        for (name, count) in &other.nodes_removed {
+            *self.nodes_removed.entry(name).or_default() += count;
+        }
+    }
+}
+
+/// Keeps document statistics up to date across edits
+///
+/// The tracker caches per-node counts. On update it skips the unchanged
+/// nodes at the start and end of the document and recounts only the
+/// region in between, which is usually the single node being edited.
+#[derive(Debug, Clone, Default)]
+pub struct StatsTracker {
+    nodes: Vec<Node>,
+    counts: Vec<Counts>,
+    language: Option<String>,
+    stats: DocumentStats,
+}
+
+impl StatsTracker {
+    /// Creates a tracker primed with the statistics for `document`
+    pub fn new(document: &Document) -> Self {
+        let mut tracker = Self::default();
+        tracker.update(document);
+        tracker
+    }
+
+    /// Returns the statistics as of the last update
+    pub fn stats(&self) -> &DocumentStats {
+        &self.stats
+    }
+
+    /// Brings the statistics up to date with `document`
+    pub fn update(&mut self, document: &Document) -> &DocumentStats {
+        self.update_with_delta(document);
+        &self.stats
+    }
+
+    /// Brings the statistics up to date and returns what changed since the
+    /// last update
+    pub fn update_with_delta(&mut self, document: &Document) -> StatsDelta {
+        self.update_within(document, 0..document.nodes.len())
+    }
+
+    /// Like [`update_with_delta`](Self::update_with_delta), but only compares
+    /// the top-level nodes in `window`, an index range into `document`
+    ///
+    /// The nodes outside the window must be the ones of the last update, as
+    /// for the range a command reports as affected. A window that does not
+    /// fit the cached nodes compares the whole document.
+    pub(crate) fn update_within(
+        &mut self,
+        document: &Document,
+        window: Range<usize>,
+    ) -> StatsDelta {
+        let nodes = &document.nodes;
+        let language = document.language();
+        if self.language.as_deref() != language {
+            // Words are counted differently now
+            self.language = language.map(str::to_string);
+            self.counts = self
+                .nodes
+                .iter()
+                .map(|node| Counts::of_node_in(node, language))
+                .collect();
+            self.stats = DocumentStats::from_counts(&self.nodes, &self.counts);
+        }
+
+        let (old_len, new_len) = (self.nodes.len(), nodes.len());
+        let (before, after) = match window {
+            window
+                if window.start <= window.end
+                    && window.end <= new_len
+                    && window.start + (new_len - window.end) <= old_len =>
+            {
+                (window.start, new_len - window.end)
+            }
+            _ => (0, 0),
+        };
+        let cached = &self.nodes[before..old_len - after];
+        let current = &nodes[before..new_len - after];
+        let prefix = before
+            + cached
+                .iter()
+                .zip(current)
+                .take_while(|(cached, node)| cached == node)
+                .count();
+        let max_suffix = cached.len().min(current.len()) - (prefix - before);
+        let suffix = after
+            + cached
+                .iter()
+                .rev()
+                .zip(current.iter().rev())
+                .take(max_suffix)
+                .take_while(|(cached, node)| cached == node)
+                .count();
+
+        let (old_end, new_end) = (old_len - suffix, new_len - suffix);
+        if prefix == old_end && prefix == new_end {
+            return StatsDelta::default();
+        }
+
+        let delta = StatsDelta::between(&self.nodes[prefix..old_end], &nodes[prefix..new_end]);
+        self.counts.splice(
+            prefix..old_end,
+            nodes[prefix..new_end]
+                .iter()
+                .map(|node| Counts::of_node_in(node, language)),
+        );
+        self.nodes
+            .splice(prefix..old_end, nodes[prefix..new_end].iter().cloned());
+
+        self.stats = DocumentStats::from_counts(&self.nodes, &self.counts);
+        delta
+    }
+}
+
+impl Document {
+    /// Computes word counts and section progress for the document
+    pub fn stats(&self) -> DocumentStats {
+        DocumentStats::compute(self)
+    }
+
+    /// Counts the words and characters of the top-level nodes in `range`
+    ///
+    /// The range is clamped to the document.
+    pub fn stats_for_range(&self, range: Range<usize>) -> Counts {
+        let end = range.end.min(self.nodes.len());
+        self.nodes[range.start.min(end)..end]
+            .iter()
+            .map(Counts::of_node)
+            .fold(Counts::default(), Counts::add)
+    }
+
+    /// Counts the words and characters of the selected text, if there is a
+    /// selection
+    ///
+    /// A collapsed selection counts as empty.
+    pub fn selection_stats(&self) -> Option<Counts> {
+        let fragment = self.selected_fragment()?;
+        Some(Counts::of_text(&fragment.plain_text()))
+    }
+
+    /// Sets or clears the word-count target on the heading at `heading_index`
+    pub fn set_word_target(
+        &mut self,
+        heading_index: usize,
+        target: Option<usize>,
+    ) -> Result<(), EditError> {
+        match self.nodes.get_mut(heading_index) {
+            Some(Node::Heading { properties, .. }) => {
+                properties.word_target = target;
+                Ok(())
+            }
+            Some(_) => Err(EditError::unsupported()),
+            None => Err(EditError::index_out_of_bounds()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EditErrorKind, Markdown, Text};
+
+    fn novel() -> Document {
+        let markdown = "# Part One {target=10}\n\n\
+                        ## Chapter 1 {target=4}\n\n\
+                        It was a dark night.\n\n\
+                        ## Chapter 2\n\n\
+                        Rain fell.\n\n\
+                        # Part Two {target=6}\n\n\
+                        Dawn came slowly over the hills.";
+        Document::try_from(Text::<Markdown>::new(markdown)).unwrap()
+    }
+
+    #[test]
+    fn test_heading_target_round_trip() {
+        let doc = novel();
+        assert_eq!(doc.stats().sections[0].word_target, Some(10));
+        assert_eq!(doc.nodes[0].as_heading().unwrap().1.len(), 1);
+        assert_eq!(doc.stats().sections[0].title, "Part One");
+
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert!(markdown.starts_with("# Part One {target=10}"));
+        assert_eq!(Document::try_from(markdown).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_section_progress() {
+        let stats = novel().stats();
+
+        assert_eq!(stats.words, 21);
+        assert_eq!(stats.sections.len(), 4);
+
+        let part_one = stats.section(0).unwrap();
+        // Includes both chapters along with their headings
+        assert_eq!(part_one.words, 11);
+        assert_eq!(part_one.progress(), Some(1.1));
+
+        let chapter_one = stats.section(1).unwrap();
+        assert_eq!(chapter_one.words, 5);
+        assert_eq!(chapter_one.progress(), Some(1.25));
+
+        assert_eq!(stats.section(3).unwrap().progress(), None);
+        let part_two = stats.section(5).unwrap();
+        assert_eq!(part_two.progress(), Some(1.0));
+
+        // Chapter targets are nested under Part One and do not add to the total
+        assert_eq!(stats.total_target(), Some(16));
+        assert_eq!(stats.total_progress(), Some(17.0 / 16.0));
+    }
+
+    #[test]
+    fn test_range_and_selection_stats() {
+        let doc = novel();
+        let chapter_one = doc.stats_for_range(1..3);
+        assert_eq!(chapter_one.words, 7);
+        assert_eq!(chapter_one.characters, 24);
+        assert_eq!(doc.stats_for_range(5..100).words, 8);
+        assert_eq!(doc.stats_for_range(9..12), Counts::default());
+
+        let mut doc = doc;
+        assert_eq!(doc.selection_stats(), None);
+        // "a dark night"
+        assert!(doc.select_range(2, 7, 2, 19));
+        assert_eq!(
+            doc.selection_stats(),
+            Some(Counts {
+                words: 3,
+                characters: 10
+            })
+        );
+        assert!(doc.select_range(1, 8, 3, 7));
+        assert_eq!(doc.selection_stats().unwrap().words, 7);
+    }
+
+    #[test]
+    fn test_word_segmentation_by_language() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("我们喜欢写作 and more");
+        assert_eq!(doc.stats().words, 3);
+        let mut tracker = StatsTracker::new(&doc);
+
+        doc.set_language(Some("zh-CN".to_string()));
+        assert_eq!(doc.stats().words, 8);
+        assert_eq!(tracker.update(&doc).words, 8);
+        assert_eq!(
+            Counts::of_text_in("すし と てんぷら", Some("ja")).words,
+            Counts::of_text_in("すしとてんぷら", Some("ja")).words
+        );
+    }
+
+    #[test]
+    fn test_reading_time() {
+        let counts = Counts::of_text(&"word ".repeat(500));
+        assert_eq!(counts.reading_time(), Duration::from_secs(150));
+        assert_eq!(counts.reading_time_at(250), Duration::from_secs(120));
+        assert_eq!(Counts::default().reading_time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_set_word_target() {
+        let mut doc = Document::new();
+        doc.add_heading(1, "Draft");
+        doc.add_paragraph_with_text("One two three");
+        assert_eq!(doc.stats().total_target(), None);
+
+        doc.set_word_target(0, Some(6)).unwrap();
+        assert_eq!(doc.stats().total_progress(), Some(0.5));
+
+        assert_eq!(
+            doc.set_word_target(1, Some(6)).unwrap_err().kind(),
+            EditErrorKind::UnsupportedOperation
+        );
+        assert_eq!(
+            doc.set_word_target(5, None).unwrap_err().kind(),
+            EditErrorKind::IndexOutOfBounds
+        );
+    }
+
+    #[test]
+    fn test_tracker_matches_full_recount() {
+        let mut doc = novel();
+        let mut tracker = StatsTracker::new(&doc);
+        assert_eq!(tracker.stats(), &doc.stats());
+
+        doc.nodes[2] = Node::paragraph("It was a dark and stormy night.");
+        assert_eq!(tracker.update(&doc), &doc.stats());
+        assert_eq!(tracker.stats().section(1).unwrap().words, 7);
+
+        doc.nodes.insert(4, Node::paragraph("Thunder rolled."));
+        doc.nodes.remove(0);
+        assert_eq!(tracker.update(&doc), &doc.stats());
+
+        doc.nodes.clear();
+        assert_eq!(tracker.update(&doc), &DocumentStats::default());
+    }
+
+    #[test]
+    fn test_tracker_delta() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("the quick brown fox");
+        let mut tracker = StatsTracker::new(&doc);
+
+        doc.nodes[0] = Node::paragraph("the slow brown fox");
+        doc.add_code_block("let x = 1;", "rust");
+        let delta = tracker.update_with_delta(&doc);
+        assert_eq!((delta.words_added, delta.words_removed), (5, 1));
+        assert_eq!(delta.characters_removed, 5);
+        assert_eq!(delta.nodes_added.get("code_block"), Some(&1));
+        assert!(delta.nodes_removed.is_empty());
+
+        assert!(tracker.update_with_delta(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_tracker_window() {
+        let mut doc = novel();
+        let mut tracker = StatsTracker::new(&doc);
+
+        doc.nodes[2] = Node::paragraph("It was a dark and stormy night.");
+        doc.nodes.insert(3, Node::paragraph("Thunder rolled."));
+        let delta = tracker.update_within(&doc, 2..4);
+        assert_eq!((delta.words_added, delta.words_removed), (4, 0));
+        assert_eq!(tracker.stats(), &doc.stats());
+
+        // Nothing changed outside the window
+        assert!(tracker.update_within(&doc, 0..0).is_empty());
+
+        // A window that does not fit compares the whole document
+        doc.nodes.remove(0);
+        tracker.update_within(&doc, 5..9);
+        assert_eq!(tracker.stats(), &doc.stats());
+    }
+}
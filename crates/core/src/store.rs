@@ -0,0 +1,388 @@
+//! Document storage with revisions
+//!
+//! A [`DocumentStore`] keeps numbered revisions of documents by id, so an
+//! app can save, list and reload them and an editor can autosave into one
+//! (see [`Editor::enable_autosave`](crate::Editor::enable_autosave)).
+//! [`FileStore`] keeps each revision as a JSON file and [`MemoryStore`]
+//! keeps them in memory, for tests and short-lived sessions. Revisions are
+//! stored as versioned JSON, so they load across releases.
+
+use crate::{Document, Json, ParseError, Text};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Errors from a [`DocumentStore`]
+#[derive(Debug, Error)]
+pub enum StoreError {
+    /// The document or revision doesn't exist
+    #[error("document {id} has no revision {revision:?}")]
+    NotFound {
+        /// Id of the document
+        id: String,
+        /// The revision asked for, or `None` for the latest
+        revision: Option<u64>,
+    },
+    /// The id can't name a document, e.g. because it is empty or holds a
+    /// path separator
+    #[error("invalid document id {0:?}")]
+    InvalidId(String),
+    /// Reading or writing storage failed
+    #[error("storage failed: {0}")]
+    Io(#[from] io::Error),
+    /// A stored revision couldn't be read or the document written
+    #[error("stored document is invalid: {0}")]
+    Format(#[from] ParseError),
+}
+
+/// A stored revision of a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevisionInfo {
+    /// Revision number, counting up from 1 for each document
+    pub revision: u64,
+    /// When the revision was saved, in seconds since the Unix epoch
+    pub saved_at: u64,
+    /// Size of the stored revision in bytes
+    pub bytes: u64,
+}
+
+/// Storage keeping numbered revisions of documents by id
+pub trait DocumentStore {
+    /// Saves the document as its next revision, returning the revision
+    /// number
+    fn save(&mut self, id: &str, document: &Document) -> Result<u64, StoreError>;
+
+    /// Loads a revision of a document, or its latest with `None`
+    fn load(&self, id: &str, revision: Option<u64>) -> Result<Document, StoreError>;
+
+    /// Ids of the stored documents, sorted
+    fn list(&self) -> Result<Vec<String>, StoreError>;
+
+    /// Revisions of a document, oldest first, or none if it isn't stored
+    fn revisions(&self, id: &str) -> Result<Vec<RevisionInfo>, StoreError>;
+
+    /// The latest revision of a document, if it is stored
+    fn latest_revision(&self, id: &str) -> Result<Option<RevisionInfo>, StoreError> {
+        Ok(self.revisions(id)?.pop())
+    }
+}
+
+/// Fails for ids that would escape or can't name a directory
+fn check_id(id: &str) -> Result<(), StoreError> {
+    let invalid = id.is_empty()
+        || id == "."
+        || id == ".."
+        || id.starts_with('.')
+        || id.contains(['/', '\\', '\0']);
+    match invalid {
+        true => Err(StoreError::InvalidId(id.to_string())),
+        false => Ok(()),
+    }
+}
+
+fn encode(document: &Document) -> Result<String, StoreError> {
+    Ok(Text::<Json>::try_from(document)?.into_inner())
+}
+
+fn decode(json: &str) -> Result<Document, StoreError> {
+    Ok(Document::try_from(Text::<Json>::new(json))?)
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Revisions kept in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    documents: BTreeMap<String, Vec<(RevisionInfo, String)>>,
+    max_revisions: Option<usize>,
+}
+
+impl MemoryStore {
+    /// An empty store keeping every revision
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps only the latest `max` revisions of each document
+    pub fn with_max_revisions(mut self, max: usize) -> Self {
+        self.max_revisions = Some(max.max(1));
+        self
+    }
+}
+
+impl DocumentStore for MemoryStore {
+    fn save(&mut self, id: &str, document: &Document) -> Result<u64, StoreError> {
+        check_id(id)?;
+        let json = encode(document)?;
+        let revisions = self.documents.entry(id.to_string()).or_default();
+        let info = RevisionInfo {
+            revision: revisions.last().map_or(1, |(info, _)| info.revision + 1),
+            saved_at: unix_seconds(SystemTime::now()),
+            bytes: json.len() as u64,
+        };
+        revisions.push((info, json));
+        if let Some(max) = self.max_revisions {
+            let excess = revisions.len().saturating_sub(max);
+            revisions.drain(..excess);
+        }
+        Ok(info.revision)
+    }
+
+    fn load(&self, id: &str, revision: Option<u64>) -> Result<Document, StoreError> {
+        let revisions = self
+            .documents
+            .get(id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let found = match revision {
+            Some(revision) => revisions.iter().find(|(info, _)| info.revision == revision),
+            None => revisions.last(),
+        };
+        match found {
+            Some((_, json)) => decode(json),
+            None => Err(StoreError::NotFound {
+                id: id.to_string(),
+                revision,
+            }),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.documents.keys().cloned().collect())
+    }
+
+    fn revisions(&self, id: &str) -> Result<Vec<RevisionInfo>, StoreError> {
+        Ok(self
+            .documents
+            .get(id)
+            .map(|revisions| revisions.iter().map(|(info, _)| *info).collect())
+            .unwrap_or_default())
+    }
+}
+
+/// Revisions kept as JSON files under a directory
+///
+/// Each document gets a directory named after its id, holding one
+/// `<revision>.json` file per revision. Revisions are written to a
+/// temporary file first and renamed into place, so a crash while saving
+/// leaves the previous revisions intact.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    root: PathBuf,
+    max_revisions: Option<usize>,
+}
+
+impl FileStore {
+    /// A store under `root`, which is created on the first save
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_revisions: None,
+        }
+    }
+
+    /// Keeps only the latest `max` revisions of each document, deleting
+    /// older files after each save
+    pub fn with_max_revisions(mut self, max: usize) -> Self {
+        self.max_revisions = Some(max.max(1));
+        self
+    }
+
+    /// The directory the store keeps its documents in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn revision_path(&self, id: &str, revision: u64) -> PathBuf {
+        self.root.join(id).join(format!("{revision:010}.json"))
+    }
+}
+
+impl DocumentStore for FileStore {
+    fn save(&mut self, id: &str, document: &Document) -> Result<u64, StoreError> {
+        check_id(id)?;
+        let json = encode(document)?;
+        let revisions = self.revisions(id)?;
+        let revision = revisions.last().map_or(1, |info| info.revision + 1);
+
+        fs::create_dir_all(self.root.join(id))?;
+        let path = self.revision_path(id, revision);
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, json)?;
+        fs::rename(&temporary, &path)?;
+
+        if let Some(max) = self.max_revisions {
+            let excess = (revisions.len() + 1).saturating_sub(max);
+            for old in &revisions[..excess.min(revisions.len())] {
+                fs::remove_file(self.revision_path(id, old.revision))?;
+            }
+        }
+        Ok(revision)
+    }
+
+    fn load(&self, id: &str, revision: Option<u64>) -> Result<Document, StoreError> {
+        check_id(id)?;
+        let revision = match revision {
+            Some(revision) => Some(revision),
+            None => self.latest_revision(id)?.map(|info| info.revision),
+        };
+        let not_found = || StoreError::NotFound {
+            id: id.to_string(),
+            revision,
+        };
+        let path = self.revision_path(id, revision.ok_or_else(not_found)?);
+        match fs::read_to_string(path) {
+            Ok(json) => decode(&json),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(not_found()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut ids = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir()
+                && let Some(id) = entry.file_name().to_str()
+                && check_id(id).is_ok()
+            {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn revisions(&self, id: &str) -> Result<Vec<RevisionInfo>, StoreError> {
+        check_id(id)?;
+        let entries = match fs::read_dir(self.root.join(id)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut revisions = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(revision) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|stem| stem.parse().ok())
+            else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            revisions.push(RevisionInfo {
+                revision,
+                saved_at: metadata.modified().map_or(0, unix_seconds),
+                bytes: metadata.len(),
+            });
+        }
+        revisions.sort_by_key(|info| info.revision);
+        Ok(revisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_store(store: &mut dyn DocumentStore) {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("First");
+        assert_eq!(store.save("notes", &doc).unwrap(), 1);
+        doc.add_paragraph_with_text("Second");
+        assert_eq!(store.save("notes", &doc).unwrap(), 2);
+        store.save("todo", &Document::new()).unwrap();
+
+        assert_eq!(store.list().unwrap(), ["notes", "todo"]);
+        assert_eq!(store.load("notes", None).unwrap(), doc);
+        assert_eq!(store.load("notes", Some(1)).unwrap().nodes.len(), 1);
+        let revisions = store.revisions("notes").unwrap();
+        assert_eq!(
+            revisions
+                .iter()
+                .map(|info| info.revision)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert!(revisions[1].bytes > revisions[0].bytes);
+        assert_eq!(
+            store
+                .latest_revision("notes")
+                .unwrap()
+                .map(|info| info.revision),
+            Some(2)
+        );
+
+        assert!(matches!(
+            store.load("notes", Some(7)),
+            Err(StoreError::NotFound {
+                revision: Some(7),
+                ..
+            })
+        ));
+        assert!(matches!(
+            store.load("missing", None),
+            Err(StoreError::NotFound { revision: None, .. })
+        ));
+        assert!(store.revisions("missing").unwrap().is_empty());
+        for id in ["", "../escape", "a/b", ".hidden"] {
+            assert!(matches!(
+                store.save(id, &doc),
+                Err(StoreError::InvalidId(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&mut MemoryStore::new());
+
+        let mut store = MemoryStore::new().with_max_revisions(2);
+        for _ in 0..4 {
+            store.save("doc", &Document::new()).unwrap();
+        }
+        let revisions = store.revisions("doc").unwrap();
+        assert_eq!(
+            revisions
+                .iter()
+                .map(|info| info.revision)
+                .collect::<Vec<_>>(),
+            [3, 4]
+        );
+    }
+
+    #[test]
+    fn test_file_store() {
+        let root = std::env::temp_dir().join(format!("md-core-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        check_store(&mut FileStore::new(&root));
+        assert!(root.join("notes").join("0000000002.json").is_file());
+
+        let mut store = FileStore::new(&root).with_max_revisions(2);
+        store.save("notes", &Document::new()).unwrap();
+        let revisions = store.revisions("notes").unwrap();
+        assert_eq!(
+            revisions
+                .iter()
+                .map(|info| info.revision)
+                .collect::<Vec<_>>(),
+            [2, 3]
+        );
+        assert!(!root.join("notes").join("0000000001.json").exists());
+        fs::remove_dir_all(&root).unwrap();
+        assert!(store.list().unwrap().is_empty());
+    }
+}
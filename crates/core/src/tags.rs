@@ -0,0 +1,291 @@
+//! Hashtags
+//!
+//! `#tag` tokens in text are turned into [`InlineNode::Tag`] when asked to,
+//! with [`Document::parse_tags`] or
+//! [`MarkdownOptions::with_tags`](crate::MarkdownOptions::with_tags), as
+//! note-taking apps use them to categorize notes. [`TagOptions`] sets what
+//! counts as a tag; by default these are `#` and a name of letters,
+//! digits, `_`, `-` and `/` for nested tags such as `#project/alpha`, with
+//! at least one character that isn't a digit so `#123` stays free for
+//! issue mentions. [`Document::tags`] aggregates the tags of a note.
+
+use crate::{Document, InlineNode, TextNode};
+use serde::Serialize;
+
+/// What counts as a `#tag`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagOptions {
+    nested: bool,
+    numeric: bool,
+}
+
+impl Default for TagOptions {
+    fn default() -> Self {
+        Self {
+            nested: true,
+            numeric: false,
+        }
+    }
+}
+
+impl TagOptions {
+    /// The default tag syntax
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ends tags at `/` instead of allowing nested tags
+    pub fn without_nesting(mut self) -> Self {
+        self.nested = false;
+        self
+    }
+
+    /// Allows tags of digits only, such as `#2024`
+    ///
+    /// These would otherwise be issue mentions, so don't combine this with
+    /// mention parsing.
+    pub fn with_numeric(mut self) -> Self {
+        self.numeric = true;
+        self
+    }
+
+    /// The length of the tag name at the start of `text`, or 0 if there is
+    /// none
+    fn name_len(&self, text: &str) -> usize {
+        let is_name =
+            |c: char| c.is_alphanumeric() || matches!(c, '_' | '-') || (self.nested && c == '/');
+        let len = text.find(|c: char| !is_name(c)).unwrap_or(text.len());
+        // Tags don't end in a separator, so `#a/` or `#todo-` leave it out
+        let name = text[..len].trim_end_matches(['-', '/']);
+        let valid = name.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            && (self.numeric || name.chars().any(|c| !c.is_ascii_digit()));
+        if valid { name.len() } else { 0 }
+    }
+}
+
+/// A tag and where it is used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagInfo {
+    /// Name of the tag, without `#`
+    pub name: String,
+    /// Number of times the tag is used
+    pub count: usize,
+    /// Indices of the top-level nodes using the tag, ascending and without
+    /// duplicates
+    pub node_indices: Vec<usize>,
+}
+
+impl TagInfo {
+    /// The tags a nested tag falls under, e.g. `project` for
+    /// `project/alpha`
+    pub fn parents(&self) -> Vec<&str> {
+        self.name
+            .match_indices('/')
+            .map(|(index, _)| &self.name[..index])
+            .collect()
+    }
+}
+
+/// Finds `#tag` patterns, as `(start, end, tag)` with byte offsets covering
+/// the `#`
+///
+/// A `#` only starts a tag at the start of the text or after whitespace or
+/// punctuation other than `#`, `&`, `/` and `:`, so URLs, entities and
+/// anchors aren't tags.
+fn find_tags(text: &str, options: &TagOptions) -> Vec<(usize, usize, InlineNode)> {
+    let mut tags = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let boundary = previous
+            .is_none_or(|p| !(p.is_alphanumeric() || matches!(p, '_' | '#' | '&' | '/' | ':')));
+        previous = Some(c);
+        if c != '#' || !boundary {
+            continue;
+        }
+        let len = options.name_len(&text[start + 1..]);
+        if len == 0 {
+            continue;
+        }
+        let end = start + 1 + len;
+        tags.push((start, end, InlineNode::tag(&text[start + 1..end])));
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+        previous = text[..end].chars().next_back();
+    }
+    tags
+}
+
+/// Splits tags out of the text runs of `inlines`, returning how many were
+/// found
+///
+/// Code and link text are left alone.
+fn split_tags(inlines: &mut Vec<InlineNode>, options: &TagOptions) -> usize {
+    let mut found = 0;
+    let mut result = Vec::with_capacity(inlines.len());
+    for mut inline in std::mem::take(inlines) {
+        match &mut inline {
+            InlineNode::Text(TextNode { text, formatting }) if !formatting.code => {
+                let tags = find_tags(text, options);
+                if tags.is_empty() {
+                    result.push(inline);
+                    continue;
+                }
+                let mut last = 0;
+                for (start, end, tag) in tags {
+                    if start > last {
+                        result.push(InlineNode::Text(TextNode::with_formatting(
+                            &text[last..start],
+                            formatting.clone(),
+                        )));
+                    }
+                    result.push(tag);
+                    last = end;
+                    found += 1;
+                }
+                if last < text.len() {
+                    result.push(InlineNode::Text(TextNode::with_formatting(
+                        &text[last..],
+                        formatting.clone(),
+                    )));
+                }
+            }
+            InlineNode::InlineFootnote { children } => {
+                found += split_tags(children, options);
+                result.push(inline);
+            }
+            _ => result.push(inline),
+        }
+    }
+    *inlines = result;
+    found
+}
+
+impl Document {
+    /// Turns `#tag` patterns in text into tags
+    ///
+    /// Code, code blocks and link text are skipped, as are patterns inside
+    /// words and URLs. Returns the number of tags created.
+    pub fn parse_tags(&mut self, options: &TagOptions) -> usize {
+        let mut found = 0;
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| {
+                for inlines in block.inline_runs_mut() {
+                    found += split_tags(inlines, options);
+                }
+            });
+        }
+        found
+    }
+
+    /// The tags used in the document, in the order they are first used
+    ///
+    /// Tags differing only in case are counted together under the
+    /// spelling used first.
+    pub fn tags(&self) -> Vec<TagInfo> {
+        let mut tags: Vec<TagInfo> = Vec::new();
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            node.walk(&mut |block| {
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        let InlineNode::Tag { name } = inline else {
+                            return;
+                        };
+                        match tags
+                            .iter_mut()
+                            .find(|tag| tag.name.to_lowercase() == name.to_lowercase())
+                        {
+                            Some(tag) => {
+                                tag.count += 1;
+                                if tag.node_indices.last() != Some(&node_index) {
+                                    tag.node_indices.push(node_index);
+                                }
+                            }
+                            None => tags.push(TagInfo {
+                                name: name.clone(),
+                                count: 1,
+                                node_indices: vec![node_index],
+                            }),
+                        }
+                    });
+                }
+            });
+        }
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HtmlOptions, Markdown, MarkdownOptions, Node, Text};
+
+    #[test]
+    fn test_parse_tags() {
+        let markdown = "Planning #project/alpha and #Todo-.\n\n\
+                        Not #123, a#b, `#code`, [#link](#anchor) or https://x.io/#frag.\n\n\
+                        - #todo again, #2024 #project\n";
+        let options = MarkdownOptions::new().with_tags(TagOptions::new());
+        let doc = Document::from_markdown_with(markdown, &options).unwrap();
+        assert_eq!(
+            doc.nodes[0],
+            Node::Paragraph {
+                children: vec![
+                    InlineNode::text("Planning "),
+                    InlineNode::tag("project/alpha"),
+                    InlineNode::text(" and "),
+                    InlineNode::tag("Todo"),
+                    InlineNode::text("-."),
+                ]
+            }
+        );
+        assert!(
+            !doc.nodes[1]
+                .as_paragraph()
+                .unwrap()
+                .iter()
+                .any(|inline| matches!(inline, InlineNode::Tag { .. }))
+        );
+
+        let tags = doc.tags();
+        let summary: Vec<(&str, usize, &[usize])> = tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.count, tag.node_indices.as_slice()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("project/alpha", 1, &[0][..]),
+                ("Todo", 2, &[0, 2][..]),
+                ("project", 1, &[2][..]),
+            ]
+        );
+        assert_eq!(tags[0].parents(), vec!["project"]);
+
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert!(
+            markdown
+                .as_str()
+                .starts_with("Planning #project/alpha and #Todo-.")
+        );
+
+        let html = doc
+            .to_html_with(&HtmlOptions::new().with_tag_url("/tags/{tag}"))
+            .html;
+        assert!(html.starts_with(
+            "<p>Planning <a class=\"tag\" href=\"/tags/project/alpha\">#project/alpha</a>"
+        ));
+        let html = Text::<crate::Html>::try_from(&doc).unwrap();
+        assert!(
+            html.as_str()
+                .contains("<span class=\"tag\" data-tag=\"Todo\">#Todo</span>")
+        );
+
+        let mut flat = Document::try_from(Text::<Markdown>::new("#a/b #2024")).unwrap();
+        let options = TagOptions::new().without_nesting().with_numeric();
+        assert_eq!(flat.parse_tags(&options), 2);
+        assert_eq!(
+            flat.nodes[0].as_paragraph().unwrap()[..2],
+            [InlineNode::tag("a"), InlineNode::text("/b ")]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Checklist progress and task queries
+//!
+//! A task is a list item with a checkbox, wherever the list is: at the top
+//! level, nested in another item or inside a quote. [`Document::task_summary`]
+//! counts them per list for progress bars, and [`Document::find_tasks`] lists
+//! them with their paths. The editor checks, unchecks or removes tasks in
+//! bulk as single undoable steps.
+
+use crate::{Document, ListItem, Node};
+use serde::Serialize;
+
+/// A task found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskInfo {
+    /// Path to the list: the top-level node index followed by the item and
+    /// child indices through any containers
+    pub list_path: Vec<usize>,
+    /// Index of the item in its list
+    pub item_index: usize,
+    /// Text of the item, without nested lists
+    pub text: String,
+    /// Whether the task is checked
+    pub checked: bool,
+    /// Number of lists the task's list is nested in
+    pub depth: usize,
+}
+
+/// Task counts of one list, not counting its nested lists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskListSummary {
+    /// Path to the list
+    pub path: Vec<usize>,
+    /// Number of tasks in the list
+    pub total: usize,
+    /// Number of checked tasks in the list
+    pub completed: usize,
+}
+
+/// Task counts of the whole document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TaskSummary {
+    /// Number of tasks
+    pub total: usize,
+    /// Number of checked tasks
+    pub completed: usize,
+    /// Counts of every list holding tasks, in document order
+    pub lists: Vec<TaskListSummary>,
+}
+
+impl TaskSummary {
+    /// Number of unchecked tasks
+    pub fn pending(&self) -> usize {
+        self.total - self.completed
+    }
+
+    /// Share of checked tasks between 0 and 1, or 1 without tasks
+    pub fn progress(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.completed as f64 / total as f64,
+        }
+    }
+}
+
+/// Which tasks [`Document::find_tasks`] returns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskFilter {
+    /// Every task
+    All,
+    /// Checked tasks
+    Completed,
+    /// Unchecked tasks
+    Pending,
+    /// Tasks whose text contains the string, ignoring case
+    Containing(String),
+}
+
+impl TaskFilter {
+    fn matches(&self, task: &TaskInfo) -> bool {
+        match self {
+            Self::All => true,
+            Self::Completed => task.checked,
+            Self::Pending => !task.checked,
+            Self::Containing(text) => task.text.to_lowercase().contains(&text.to_lowercase()),
+        }
+    }
+}
+
+/// A change [`apply_task_action`] makes to every task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TaskAction {
+    Check,
+    Uncheck,
+    RemoveCompleted,
+}
+
+/// Text of an item, leaving out its nested lists
+fn item_text(item: &ListItem) -> String {
+    item.children
+        .iter()
+        .filter(|child| !matches!(child, Node::List { .. }))
+        .map(Node::plain_text)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Applies `action` to the tasks in `nodes` and everything nested in them,
+/// returning how many tasks changed or were removed
+///
+/// Removing a task removes what is nested in it too; lists left without
+/// items are removed as well.
+pub(crate) fn apply_task_action(nodes: &mut Vec<Node>, action: TaskAction) -> usize {
+    let mut changed = 0;
+    nodes.retain_mut(|node| {
+        if let Node::List { items, .. } = node {
+            match action {
+                TaskAction::RemoveCompleted => {
+                    let before = items.len();
+                    items.retain(|item| item.checked != Some(true));
+                    changed += before - items.len();
+                    if before > 0 && items.is_empty() {
+                        return false;
+                    }
+                }
+                TaskAction::Check | TaskAction::Uncheck => {
+                    let checked = action == TaskAction::Check;
+                    for item in items.iter_mut() {
+                        if item.checked == Some(!checked) {
+                            item.checked = Some(checked);
+                            changed += 1;
+                        }
+                    }
+                }
+            }
+        }
+        for list in node.block_lists_mut() {
+            changed += apply_task_action(list, action);
+        }
+        true
+    });
+    changed
+}
+
+impl Document {
+    /// Lists the tasks matching `filter` in document order
+    pub fn find_tasks(&self, filter: &TaskFilter) -> Vec<TaskInfo> {
+        let mut tasks = Vec::new();
+        // Lists are visited before those nested in them
+        let mut list_paths: Vec<Vec<usize>> = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            node.walk_with_path(&mut vec![index], &mut |block, path| {
+                let Node::List { items, .. } = block else {
+                    return;
+                };
+                let depth = list_paths
+                    .iter()
+                    .filter(|outer| path.starts_with(outer))
+                    .count();
+                list_paths.push(path.to_vec());
+                for (item_index, item) in items.iter().enumerate() {
+                    let Some(checked) = item.checked else {
+                        continue;
+                    };
+                    let task = TaskInfo {
+                        list_path: path.to_vec(),
+                        item_index,
+                        text: item_text(item),
+                        checked,
+                        depth,
+                    };
+                    if filter.matches(&task) {
+                        tasks.push(task);
+                    }
+                }
+            });
+        }
+        tasks
+    }
+
+    /// Counts the tasks of the document, in total and per list
+    pub fn task_summary(&self) -> TaskSummary {
+        let mut summary = TaskSummary::default();
+        for task in self.find_tasks(&TaskFilter::All) {
+            summary.total += 1;
+            summary.completed += usize::from(task.checked);
+            match summary.lists.last_mut() {
+                Some(list) if list.path == task.list_path => {
+                    list.total += 1;
+                    list.completed += usize::from(task.checked);
+                }
+                _ => summary.lists.push(TaskListSummary {
+                    path: task.list_path,
+                    total: 1,
+                    completed: usize::from(task.checked),
+                }),
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn document() -> Document {
+        Document::try_from(Text::<Markdown>::new(
+            "- [x] Write draft\n\
+             - [ ] Review\n  - [x] Spelling\n  - [ ] Links\n\n\
+             Notes\n\n\
+             > - [ ] Publish draft\n",
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_task_summary() {
+        let summary = document().task_summary();
+        assert_eq!(
+            (summary.total, summary.completed, summary.pending()),
+            (5, 2, 3)
+        );
+        assert_eq!(summary.progress(), 0.4);
+        let lists: Vec<(Vec<usize>, usize, usize)> = summary
+            .lists
+            .into_iter()
+            .map(|list| (list.path, list.total, list.completed))
+            .collect();
+        assert_eq!(
+            lists,
+            [(vec![0], 2, 1), (vec![0, 1, 1], 2, 1), (vec![2, 0], 1, 0),]
+        );
+        assert_eq!(Document::new().task_summary().progress(), 1.0);
+    }
+
+    #[test]
+    fn test_find_tasks() {
+        let doc = document();
+        let pending = doc.find_tasks(&TaskFilter::Pending);
+        let found: Vec<(&str, usize, usize)> = pending
+            .iter()
+            .map(|task| (task.text.as_str(), task.item_index, task.depth))
+            .collect();
+        assert_eq!(
+            found,
+            [("Review", 1, 0), ("Links", 1, 1), ("Publish draft", 0, 0)]
+        );
+
+        let drafts = doc.find_tasks(&TaskFilter::Containing("DRAFT".to_string()));
+        assert_eq!(drafts.len(), 2);
+        assert_eq!(doc.find_tasks(&TaskFilter::Completed).len(), 2);
+    }
+
+    #[test]
+    fn test_apply_task_action() {
+        let mut doc = document();
+        assert_eq!(apply_task_action(&mut doc.nodes, TaskAction::Check), 3);
+        assert_eq!(doc.task_summary().pending(), 0);
+        assert_eq!(apply_task_action(&mut doc.nodes, TaskAction::Uncheck), 5);
+
+        let mut doc = document();
+        assert_eq!(
+            apply_task_action(&mut doc.nodes, TaskAction::RemoveCompleted),
+            2
+        );
+        let texts: Vec<String> = doc
+            .find_tasks(&TaskFilter::All)
+            .into_iter()
+            .map(|task| task.text)
+            .collect();
+        assert_eq!(texts, ["Review", "Links", "Publish draft"]);
+
+        apply_task_action(&mut doc.nodes, TaskAction::Check);
+        apply_task_action(&mut doc.nodes, TaskAction::RemoveCompleted);
+        assert_eq!(doc.nodes.len(), 2, "emptied lists are removed");
+    }
+}
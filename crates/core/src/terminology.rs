@@ -0,0 +1,269 @@
+//! Consistent terminology across a document
+//!
+//! A [`Terminology`] lists preferred terms with the variants to replace,
+//! such as "JavaScript" for "Javascript" or "email" for "e-mail".
+//! [`Document::check_terminology`] finds the variants in the prose of the
+//! document as whole words, ignoring case, and the editor replaces them all
+//! as one undoable step. Only the prose of [`Document::text_runs`] is
+//! checked, so code blocks, code spans, links and URLs are left alone.
+
+use crate::spellcheck::inline_runs;
+use crate::{Document, InlineNode, Node};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::ops::Range;
+
+/// A preferred term with the variants to replace by it
+#[derive(Debug, Clone)]
+struct TermRule {
+    preferred: String,
+    pattern: Regex,
+}
+
+/// Preferred terms to enforce across a document
+#[derive(Debug, Clone, Default)]
+pub struct Terminology {
+    rules: Vec<TermRule>,
+}
+
+impl Terminology {
+    /// Creates an empty terminology
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prefers `preferred` over each of `variants`
+    ///
+    /// Variants match as whole words in any case, so `"javascript"` also
+    /// catches "Javascript" and "JAVASCRIPT"; text already spelled exactly
+    /// as preferred is not a violation. A variant starting with a capital
+    /// letter where the preferred term starts lowercase, as at the start of
+    /// a sentence, is replaced by the capitalized preferred term.
+    pub fn prefer<I, S>(mut self, preferred: impl Into<String>, variants: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut variants: Vec<String> = variants
+            .into_iter()
+            .map(|variant| variant.as_ref().trim().to_string())
+            .filter(|variant| !variant.is_empty())
+            .collect();
+        if variants.is_empty() {
+            return self;
+        }
+        // Longer variants first, so "e-mail address" wins over "e-mail"
+        variants.sort_by_key(|variant| std::cmp::Reverse(variant.len()));
+        let alternatives: Vec<String> = variants.iter().map(|v| regex::escape(v)).collect();
+        let pattern = RegexBuilder::new(&alternatives.join("|"))
+            .case_insensitive(true)
+            .build()
+            .expect("escaped variants form a valid pattern");
+        self.rules.push(TermRule {
+            preferred: preferred.into(),
+            pattern,
+        });
+        self
+    }
+
+    /// Returns true if no term was added
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Violations in a stretch of prose starting at `offset` of its node
+    fn violations_in(&self, path: &[usize], offset: usize, text: &str) -> Vec<TermViolation> {
+        let mut found = Vec::new();
+        for rule in &self.rules {
+            for matched in rule.pattern.find_iter(text) {
+                if !is_word_boundary(text, matched.start(), matched.end()) {
+                    continue;
+                }
+                let replacement = match_case(matched.as_str(), &rule.preferred);
+                if replacement == matched.as_str() {
+                    continue;
+                }
+                found.push(TermViolation {
+                    path: path.to_vec(),
+                    range: offset + matched.start()..offset + matched.end(),
+                    found: matched.as_str().to_string(),
+                    preferred: replacement,
+                });
+            }
+        }
+        found
+    }
+
+    /// Violations in a paragraph's or heading's inline content, in order
+    /// and without overlaps
+    fn violations(&self, path: &[usize], children: &[InlineNode]) -> Vec<TermViolation> {
+        let mut found: Vec<TermViolation> = inline_runs(path, children)
+            .iter()
+            .flat_map(|run| self.violations_in(path, run.offset, &run.text))
+            .collect();
+        found.sort_by_key(|violation| (violation.range.start, usize::MAX - violation.range.end));
+        let mut end = 0;
+        found.retain(|violation| {
+            let keep = violation.range.start >= end;
+            if keep {
+                end = violation.range.end;
+            }
+            keep
+        });
+        found
+    }
+}
+
+/// A variant of a preferred term found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TermViolation {
+    /// Path to the paragraph or heading, as in
+    /// [`TextRun::path`](crate::TextRun::path)
+    pub path: Vec<usize>,
+    /// Byte range of the variant in the node's text
+    pub range: Range<usize>,
+    /// The text as found
+    pub found: String,
+    /// The text to replace it with
+    pub preferred: String,
+}
+
+/// Returns true if the match neither starts nor ends inside a word
+fn is_word_boundary(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    !text[..start].chars().next_back().is_some_and(is_word)
+        && !text[end..].chars().next().is_some_and(is_word)
+}
+
+/// Capitalizes a lowercase preferred term when the text found was
+/// capitalized
+fn match_case(found: &str, preferred: &str) -> String {
+    let found_upper = found.chars().next().is_some_and(char::is_uppercase);
+    let mut chars = preferred.chars();
+    match chars.next() {
+        Some(first) if found_upper && first.is_lowercase() => {
+            first.to_uppercase().chain(chars).collect()
+        }
+        _ => preferred.to_string(),
+    }
+}
+
+/// Replaces `range` of the offset text of inline content with `replacement`
+///
+/// A range spanning differently formatted text keeps the formatting of
+/// where it starts.
+fn replace_range(children: &mut Vec<InlineNode>, range: &Range<usize>, replacement: &str) {
+    let mut offset = 0;
+    let mut replaced = false;
+    for child in children.iter_mut() {
+        let InlineNode::Text(text_node) = child else {
+            offset += 1;
+            continue;
+        };
+        let next_offset = offset + text_node.text.len();
+        let start = range.start.max(offset);
+        let end = range.end.min(next_offset);
+        if start < end {
+            let with = if replaced { "" } else { replacement };
+            text_node
+                .text
+                .replace_range(start - offset..end - offset, with);
+            replaced = true;
+        }
+        offset = next_offset;
+        if offset >= range.end {
+            break;
+        }
+    }
+    children
+        .retain(|child| !matches!(child, InlineNode::Text(text_node) if text_node.text.is_empty()));
+}
+
+/// Replaces the variants in a top-level node and everything nested in it,
+/// returning how many were replaced
+pub(crate) fn apply_terminology(node: &mut Node, terminology: &Terminology) -> usize {
+    let mut replaced = 0;
+    node.walk_mut(&mut |block| {
+        if let Node::Paragraph { children } | Node::Heading { children, .. } = block {
+            let violations = terminology.violations(&[], children);
+            for violation in violations.iter().rev() {
+                replace_range(children, &violation.range, &violation.preferred);
+            }
+            replaced += violations.len();
+        }
+    });
+    replaced
+}
+
+impl Document {
+    /// Finds the variants of preferred terms in the prose of every
+    /// paragraph and heading, in document order
+    pub fn check_terminology(&self, terminology: &Terminology) -> Vec<TermViolation> {
+        let mut violations = Vec::new();
+        if terminology.is_empty() {
+            return violations;
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            node.walk_with_path(&mut vec![index], &mut |node, path| {
+                if let Node::Paragraph { children } | Node::Heading { children, .. } = node {
+                    violations.extend(terminology.violations(path, children));
+                }
+            });
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    fn terminology() -> Terminology {
+        Terminology::new()
+            .prefer("JavaScript", ["javascript"])
+            .prefer("email", ["e-mail", "e mail"])
+    }
+
+    #[test]
+    fn test_check_terminology() {
+        let doc = Document::try_from(Text::<Markdown>::new(
+            "# Javascript and JavaScript\n\n\
+             E-mail us about `javascript` or [javascript](https://javascript.info).\n\n\
+             > - Send an e-mail, not an e-mailer\n\n\
+             ```\njavascript\n```\n",
+        ))
+        .unwrap();
+
+        let violations = doc.check_terminology(&terminology());
+        let found: Vec<(Vec<usize>, Range<usize>, &str, &str)> = violations
+            .iter()
+            .map(|v| {
+                (
+                    v.path.clone(),
+                    v.range.clone(),
+                    v.found.as_str(),
+                    v.preferred.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (vec![0], 0..10, "Javascript", "JavaScript"),
+                (vec![1], 0..6, "E-mail", "Email"),
+                (vec![2, 0, 0, 0], 8..14, "e-mail", "email"),
+            ]
+        );
+        assert!(doc.check_terminology(&Terminology::new()).is_empty());
+    }
+
+    #[test]
+    fn test_apply_terminology() {
+        let mut doc =
+            Document::try_from(Text::<Markdown>::new("Use *Java*script via e-mail.\n")).unwrap();
+        assert_eq!(apply_terminology(&mut doc.nodes[0], &terminology()), 2);
+        assert_eq!(doc.nodes[0].plain_text(), "Use JavaScript via email.");
+        assert!(doc.check_terminology(&terminology()).is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! Managed tables of contents
+//!
+//! A [`Node::TableOfContents`] holds no entries of its own: exports expand
+//! it from the outline at the time they run, so it never goes stale the way
+//! the list [`Editor::create_table_of_contents`] writes does. Markdown
+//! writes the expanded list between `<!-- toc:N -->` and `<!-- /toc -->`
+//! comment markers, which are read back as a managed table of contents,
+//! dropping the list. [`Editor::refresh_toc`] turns managed tables of
+//! contents into static lists when a copy to edit by hand is wanted.
+
+use crate::{Document, EditError, Editor, InlineNode, ListItem, ListType, Node, OutlineEntry};
+
+/// The comment opening a table of contents of headings up to `max_level`
+/// in Markdown, or closing one without a level
+pub(crate) fn marker(max_level: Option<u8>) -> String {
+    match max_level {
+        Some(max_level) => format!("<!-- toc:{max_level} -->"),
+        None => "<!-- /toc -->".to_string(),
+    }
+}
+
+/// Reads a comment marker, returning the level of an opening marker, or
+/// `None` for a closing one
+fn parse_marker(node: &Node) -> Option<Option<u8>> {
+    let Node::HtmlComment { text } = node else {
+        return None;
+    };
+    match text.trim() {
+        "/toc" => Some(None),
+        "toc" => Some(Some(6)),
+        text => {
+            let level: u8 = text.strip_prefix("toc:")?.trim().parse().ok()?;
+            Some(Some(level.clamp(1, 6)))
+        }
+    }
+}
+
+/// Returns true for the comment closing a table of contents
+pub(crate) fn is_closing_marker(node: &Node) -> bool {
+    parse_marker(node) == Some(None)
+}
+
+/// Replaces the blocks between matching comment markers, at any depth,
+/// with managed tables of contents
+///
+/// Markers without a match are kept as comments.
+pub(crate) fn wrap_markers(nodes: &mut Vec<Node>) {
+    let mut wrapped = Vec::with_capacity(nodes.len());
+    let mut rest = std::mem::take(nodes).into_iter();
+    while let Some(mut node) = rest.next() {
+        let Some(Some(max_level)) = parse_marker(&node) else {
+            for list in node.block_lists_mut() {
+                wrap_markers(list);
+            }
+            wrapped.push(node);
+            continue;
+        };
+        let remaining = rest.as_slice();
+        match remaining
+            .iter()
+            .position(|node| parse_marker(node) == Some(None))
+        {
+            Some(end) => {
+                rest.nth(end);
+                wrapped.push(Node::TableOfContents { max_level });
+            }
+            None => wrapped.push(node),
+        }
+    }
+    *nodes = wrapped;
+}
+
+/// A nested list linking to the entries up to `max_level`, or `None` if
+/// there are none
+pub(crate) fn entries_list(entries: &[OutlineEntry], max_level: u8) -> Option<Node> {
+    let items: Vec<ListItem> = entries
+        .iter()
+        .filter(|entry| entry.level <= max_level)
+        .map(|entry| {
+            let link = InlineNode::link(format!("#{}", entry.anchor), entry.text.as_str());
+            let mut children = vec![Node::Paragraph {
+                children: vec![link],
+            }];
+            children.extend(entries_list(&entry.children, max_level));
+            ListItem::new(children)
+        })
+        .collect();
+    (!items.is_empty()).then_some(Node::List {
+        list_type: ListType::Unordered,
+        items,
+        start: None,
+        loose: false,
+    })
+}
+
+impl Document {
+    /// The list of links to the headings up to `max_level` a table of
+    /// contents expands to, or `None` if there are no such headings
+    pub fn table_of_contents(&self, max_level: u8) -> Option<Node> {
+        entries_list(&self.outline().entries, max_level)
+    }
+}
+
+impl Editor {
+    /// Replace every top-level managed table of contents with the static
+    /// list it currently expands to, in one undoable step, returning how
+    /// many were replaced
+    ///
+    /// Tables of contents without any heading to list are removed.
+    pub fn refresh_toc(&mut self) -> Result<usize, EditError> {
+        let replacements: Vec<(usize, Option<Node>)> = {
+            let document = self.document().borrow();
+            document
+                .nodes
+                .iter()
+                .enumerate()
+                .filter_map(|(index, node)| match node {
+                    Node::TableOfContents { max_level } => {
+                        Some((index, document.table_of_contents(*max_level)))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        if replacements.is_empty() {
+            return Ok(0);
+        }
+
+        let mut transaction = self.begin_transaction();
+        for (index, list) in replacements.iter().rev() {
+            transaction.delete_node(*index);
+            if let Some(list) = list {
+                transaction.insert_node(*index, list.clone());
+            }
+        }
+        self.execute_transaction(transaction)?;
+        Ok(replacements.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Html, Markdown, Text};
+
+    #[test]
+    fn test_table_of_contents() {
+        let mut doc = Document::try_from(Text::<Markdown>::new(
+            "# Guide\n\n## Install\n\n### From source\n\n## Usage\n",
+        ))
+        .unwrap();
+        doc.nodes.insert(1, Node::TableOfContents { max_level: 2 });
+
+        let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+        assert!(markdown.as_str().contains(
+            "<!-- toc:2 -->\n\n* [Guide](#guide)\n  * [Install](#install)\n  * [Usage](#usage)\n\n<!-- /toc -->"
+        ));
+        let reparsed = Document::try_from(markdown).unwrap();
+        assert_eq!(reparsed.nodes, doc.nodes);
+
+        // The table of contents follows the headings
+        doc.nodes[2] = Node::heading(2, "Setup");
+        let html = Text::<Html>::try_from(&doc).unwrap();
+        assert!(html.as_str().contains(
+            "<nav class=\"table-of-contents\"><ul><li><a href=\"#guide\">Guide</a><ul>\
+             <li><a href=\"#setup\">Setup</a></li>"
+        ));
+        assert!(!html.as_str().contains("From source</a>"));
+
+        let empty = Document::try_from(Text::<Markdown>::new(
+            "<!-- toc -->\n\n<!-- /toc -->\n\n<!-- toc:2 -->\n\nText\n",
+        ))
+        .unwrap();
+        assert_eq!(empty.nodes[0], Node::TableOfContents { max_level: 6 });
+        assert!(matches!(empty.nodes[1], Node::HtmlComment { .. }));
+        assert_eq!(empty.table_of_contents(6), None);
+    }
+}
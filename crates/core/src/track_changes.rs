@@ -0,0 +1,306 @@
+//! Suggested edits
+//!
+//! With [`Editor::set_track_changes`](crate::Editor::set_track_changes) on,
+//! inserted text is marked as a [`TrackedChange`] insertion and deleted
+//! text stays in place, marked as a deletion, until a reviewer accepts or
+//! rejects the change. Marks are saved with the document, follow later
+//! edits like comments do, and render as `<ins>` and `<del>` in HTML.
+
+use crate::offsets::offset_text;
+use crate::{Document, EditError, InlineNode, Node, TextNode};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// What a tracked change suggests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// The text was added
+    Insertion,
+    /// The text is to be removed
+    Deletion,
+}
+
+impl ChangeKind {
+    /// The HTML element marking the change
+    pub(crate) fn tag(self) -> &'static str {
+        match self {
+            Self::Insertion => "ins",
+            Self::Deletion => "del",
+        }
+    }
+}
+
+/// A suggested insertion or deletion of a range of text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedChange {
+    /// Identifier, unique within the document
+    pub id: u64,
+    /// Whether the text was inserted or is to be deleted
+    pub kind: ChangeKind,
+    /// Who made the change
+    pub author: String,
+    /// Path to the paragraph or heading, as in [`Comment::path`](crate::Comment::path)
+    pub path: Vec<usize>,
+    /// Byte range of the text in the node
+    pub range: Range<usize>,
+}
+
+/// Maps an offset through replacing `start..end` with `inserted` bytes
+///
+/// Text inserted at a range's start or end stays outside the range.
+fn shift_offset(offset: usize, start: usize, end: usize, inserted: usize, is_end: bool) -> usize {
+    if offset < start || (is_end && offset == start) {
+        offset
+    } else if offset >= end {
+        offset - (end - start) + inserted
+    } else if is_end {
+        start
+    } else {
+        start + inserted
+    }
+}
+
+/// Removes a byte range from the text of a paragraph, heading or code
+/// block; non-text inlines count as one byte, as in editing commands
+fn delete_range(node: &mut Node, range: &Range<usize>) -> Result<(), EditError> {
+    let children = match node {
+        Node::Paragraph { children } | Node::Heading { children, .. } => children,
+        Node::CodeBlock { code, .. } if range.end <= code.len() => {
+            code.replace_range(range.clone(), "");
+            return Ok(());
+        }
+        _ => return Err(EditError::unsupported()),
+    };
+    let mut offset = 0;
+    children.retain_mut(|child| {
+        let start = offset;
+        match child {
+            InlineNode::Text(text) => {
+                offset += text.text.len();
+                let from = range.start.clamp(start, offset) - start;
+                let to = range.end.clamp(start, offset) - start;
+                text.text.replace_range(from..to, "");
+                !text.text.is_empty()
+            }
+            _ => {
+                offset += 1;
+                !(range.start <= start && start < range.end)
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Splits the text runs of inline content at `offset`, so a run starts
+/// there
+pub(crate) fn split_inlines_at(children: &mut Vec<InlineNode>, offset: usize) {
+    let mut start = 0;
+    for index in 0..children.len() {
+        let InlineNode::Text(text) = &children[index] else {
+            start += 1;
+            continue;
+        };
+        let end = start + text.text.len();
+        if start < offset && offset < end && text.text.is_char_boundary(offset - start) {
+            let tail = TextNode {
+                text: text.text[offset - start..].to_string(),
+                formatting: text.formatting.clone(),
+            };
+            if let InlineNode::Text(text) = &mut children[index] {
+                text.text.truncate(offset - start);
+            }
+            children.insert(index + 1, InlineNode::Text(tail));
+            return;
+        }
+        start = end;
+    }
+}
+
+impl Document {
+    /// Returns the tracked change with an id
+    pub fn tracked_change(&self, id: u64) -> Option<&TrackedChange> {
+        self.tracked_changes.iter().find(|change| change.id == id)
+    }
+
+    /// Tracked changes in the top-level node at `index`, ordered by range
+    pub(crate) fn tracked_changes_in(&self, index: usize) -> Vec<&TrackedChange> {
+        let mut changes: Vec<&TrackedChange> = self
+            .tracked_changes
+            .iter()
+            .filter(|change| change.path == [index] && !change.range.is_empty())
+            .collect();
+        changes.sort_by_key(|change| (change.range.start, change.range.end));
+        changes
+    }
+
+    /// Moves the tracked changes in a top-level node along with replacing
+    /// `start..end` of its text with `inserted` bytes
+    fn shift_tracked_changes(&mut self, index: usize, start: usize, end: usize, inserted: usize) {
+        for change in &mut self.tracked_changes {
+            if change.path == [index] {
+                change.range = shift_offset(change.range.start, start, end, inserted, false)
+                    ..shift_offset(change.range.end, start, end, inserted, true);
+            }
+        }
+        self.tracked_changes
+            .retain(|change| !change.range.is_empty() || change.path != [index]);
+    }
+
+    fn next_change_id(&self) -> u64 {
+        self.tracked_changes
+            .iter()
+            .map(|change| change.id)
+            .max()
+            .map_or(1, |id| id + 1)
+    }
+
+    /// Records text inserted at `position` of a top-level node by `author`,
+    /// after the text itself was inserted
+    ///
+    /// Joins an insertion of the same author the text continues.
+    pub(crate) fn track_insertion(
+        &mut self,
+        index: usize,
+        position: usize,
+        len: usize,
+        author: &str,
+    ) {
+        self.shift_tracked_changes(index, position, position, len);
+        let adjacent = self.tracked_changes.iter_mut().find(|change| {
+            change.path == [index]
+                && change.kind == ChangeKind::Insertion
+                && change.author == author
+                && (change.range.end == position || change.range.start == position + len)
+        });
+        match adjacent {
+            Some(change) => {
+                change.range =
+                    change.range.start.min(position)..change.range.end.max(position + len)
+            }
+            None => {
+                let id = self.next_change_id();
+                self.tracked_changes.push(TrackedChange {
+                    id,
+                    kind: ChangeKind::Insertion,
+                    author: author.to_string(),
+                    path: vec![index],
+                    range: position..position + len,
+                });
+            }
+        }
+    }
+
+    /// Marks `range` of a top-level node as deleted by `author`
+    ///
+    /// Text inside the author's own pending insertion is deleted right away
+    /// instead, as there is nothing to review. Overlapping deletions of the
+    /// same author are joined.
+    pub(crate) fn track_deletion(
+        &mut self,
+        index: usize,
+        range: Range<usize>,
+        author: &str,
+    ) -> Result<(), EditError> {
+        let Some(node) = self.nodes.get_mut(index) else {
+            return Err(EditError::index_out_of_bounds().at_node(index));
+        };
+        let length = offset_text(node).len();
+        if range.start >= range.end || range.end > length {
+            return Err(EditError::invalid_range()
+                .at_node(index)
+                .with_ranges(0..length, range));
+        }
+
+        let own_insertion = self.tracked_changes.iter().any(|change| {
+            change.path == [index]
+                && change.kind == ChangeKind::Insertion
+                && change.author == author
+                && change.range.start <= range.start
+                && range.end <= change.range.end
+        });
+        if own_insertion {
+            delete_range(node, &range).map_err(|err| err.at_node(index))?;
+            self.shift_tracked_changes(index, range.start, range.end, 0);
+            return Ok(());
+        }
+
+        let mut merged = range;
+        self.tracked_changes.retain(|change| {
+            let joins = change.path == [index]
+                && change.kind == ChangeKind::Deletion
+                && change.author == author
+                && change.range.start <= merged.end
+                && merged.start <= change.range.end;
+            if joins {
+                merged = merged.start.min(change.range.start)..merged.end.max(change.range.end);
+            }
+            !joins
+        });
+        let id = self.next_change_id();
+        self.tracked_changes.push(TrackedChange {
+            id,
+            kind: ChangeKind::Deletion,
+            author: author.to_string(),
+            path: vec![index],
+            range: merged,
+        });
+        Ok(())
+    }
+
+    /// Accepts or rejects a tracked change, applying or undoing its edit
+    pub(crate) fn resolve_tracked_change(
+        &mut self,
+        id: u64,
+        accept: bool,
+    ) -> Result<(), EditError> {
+        let Some(position) = self.tracked_changes.iter().position(|c| c.id == id) else {
+            return Err(EditError::other(format!("No tracked change with id {id}")));
+        };
+        let change = self.tracked_changes.remove(position);
+        let removes_text = match change.kind {
+            ChangeKind::Insertion => !accept,
+            ChangeKind::Deletion => accept,
+        };
+        if !removes_text {
+            return Ok(());
+        }
+        let index = change.path[0];
+        let Some(node) = self.nodes.get_mut(index).filter(|_| change.path.len() == 1) else {
+            return Err(EditError::invalid_node().at_path(change.path));
+        };
+        delete_range(node, &change.range).map_err(|err| err.at_node(index))?;
+        self.shift_tracked_changes(index, change.range.start, change.range.end, 0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_and_resolve() {
+        let mut doc = Document::new();
+        doc.add_paragraph_with_text("The cat sat");
+
+        doc.track_deletion(0, 4..8, "ana").unwrap();
+        doc.nodes[0] = Node::paragraph("The big cat sat");
+        doc.track_insertion(0, 4, 4, "bo");
+        assert_eq!(doc.tracked_change(1).unwrap().range, 8..12);
+        assert_eq!(doc.tracked_change(2).unwrap().range, 4..8);
+        assert!(doc.track_deletion(0, 10..40, "bo").is_err());
+
+        // Deleting inside one's own insertion removes the text
+        doc.track_deletion(0, 4..5, "bo").unwrap();
+        assert_eq!(doc.nodes[0].plain_text(), "The ig cat sat");
+        assert_eq!(doc.tracked_change(2).unwrap().range, 4..7);
+
+        doc.resolve_tracked_change(1, true).unwrap();
+        assert_eq!(doc.nodes[0].plain_text(), "The ig sat");
+        doc.resolve_tracked_change(2, false).unwrap();
+        assert_eq!(doc.nodes[0].plain_text(), "The sat");
+        assert!(doc.tracked_changes.is_empty());
+        assert!(doc.resolve_tracked_change(2, false).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! URL scheme allowlists for links and images
+//!
+//! A link to `javascript:alert(1)` is as much a script as a `<script>` tag.
+//! A [`UrlPolicy`] lists the URL schemes links and images may use, so
+//! editor commands can reject dangerous URLs with
+//! [`EditErrorKind::InvalidUrl`](crate::EditErrorKind::InvalidUrl) and
+//! imported HTML can have them removed with [`Document::sanitize_urls`].
+//!
+//! Relative URLs, fragments and paths have no scheme and are always allowed.
+
+use crate::convert::sanitize::url_scheme;
+use crate::{Document, EditError, InlineNode, Node};
+use std::collections::BTreeSet;
+
+/// Allowed URL schemes for links and for images
+///
+/// By default links may use `http`, `https`, `mailto` and `tel`, and images
+/// `http`, `https`, `data` and [`asset`](crate::ASSET_SCHEME). `data:` URLs are only accepted for images,
+/// and only with an `image/` media type, so `data:text/html` is rejected
+/// even where `data` is allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlPolicy {
+    link_schemes: BTreeSet<String>,
+    image_schemes: BTreeSet<String>,
+}
+
+impl Default for UrlPolicy {
+    fn default() -> Self {
+        let set = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        Self {
+            link_schemes: set(&["http", "https", "mailto", "tel"]),
+            image_schemes: set(&["http", "https", "data", "asset"]),
+        }
+    }
+}
+
+impl UrlPolicy {
+    /// A policy with the default schemes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows links with the given scheme, e.g. `"ftp"`
+    pub fn allow_link_scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.link_schemes.insert(scheme.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Allows images with the given scheme
+    pub fn allow_image_scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.image_schemes
+            .insert(scheme.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Removes a scheme from both allowlists
+    pub fn deny_scheme(mut self, scheme: &str) -> Self {
+        let scheme = scheme.to_ascii_lowercase();
+        self.link_schemes.remove(&scheme);
+        self.image_schemes.remove(&scheme);
+        self
+    }
+
+    /// Returns true if a link may point at the URL
+    pub fn allows_link(&self, url: &str) -> bool {
+        match url_scheme(url) {
+            Some((scheme, _)) => scheme != "data" && self.link_schemes.contains(&scheme),
+            None => true,
+        }
+    }
+
+    /// Returns true if an image may load from the URL
+    pub fn allows_image(&self, url: &str) -> bool {
+        match url_scheme(url) {
+            Some((scheme, rest)) if scheme == "data" => {
+                self.image_schemes.contains(&scheme)
+                    && rest.to_ascii_lowercase().starts_with("image/")
+            }
+            Some((scheme, _)) => self.image_schemes.contains(&scheme),
+            None => true,
+        }
+    }
+
+    /// Checks a link URL
+    pub fn check_link(&self, url: &str) -> Result<(), EditError> {
+        if self.allows_link(url) {
+            Ok(())
+        } else {
+            Err(EditError::invalid_url().with_message(format!("Link URL not allowed: {url}")))
+        }
+    }
+
+    /// Checks an image URL
+    pub fn check_image(&self, url: &str) -> Result<(), EditError> {
+        if self.allows_image(url) {
+            Ok(())
+        } else {
+            Err(EditError::invalid_url().with_message(format!("Image URL not allowed: {url}")))
+        }
+    }
+
+    /// Checks every URL in an inline node and its children
+    pub fn check_inline(&self, inline: &InlineNode) -> Result<(), EditError> {
+        let mut result = Ok(());
+        inline.walk(&mut |inline| {
+            if result.is_err() {
+                return;
+            }
+            result = match inline {
+                InlineNode::Link { url, .. } => self.check_link(url),
+                InlineNode::AutoLink { url, is_email } if !is_email => self.check_link(url),
+                InlineNode::Image { url, .. } => self.check_image(url),
+                _ => Ok(()),
+            };
+        });
+        result
+    }
+
+    /// Checks every URL in a block, its nested blocks and their inline
+    /// content
+    pub fn check_node(&self, node: &Node) -> Result<(), EditError> {
+        let mut result = Ok(());
+        node.walk(&mut |block| {
+            if result.is_err() {
+                return;
+            }
+            result = match block {
+                Node::Figure { url, .. } => self.check_image(url),
+                Node::Embed { url, .. } => self.check_link(url),
+                _ => Ok(()),
+            };
+            for inline in block.inline_runs().into_iter().flatten() {
+                if result.is_ok() {
+                    result = self.check_inline(inline);
+                }
+            }
+        });
+        result
+    }
+
+    /// Removes disallowed URLs from inline content, adding them to
+    /// `removed`
+    ///
+    /// Links are replaced by their content, autolinks by their text and
+    /// images by their alt text.
+    fn sanitize_inlines(&self, inlines: &mut Vec<InlineNode>, removed: &mut Vec<String>) {
+        let mut result = Vec::with_capacity(inlines.len());
+        for mut inline in std::mem::take(inlines) {
+            match &mut inline {
+                InlineNode::Link { url, children, .. } => {
+                    self.sanitize_inlines(children, removed);
+                    if !self.allows_link(url) {
+                        removed.push(std::mem::take(url));
+                        result.append(children);
+                        continue;
+                    }
+                }
+                InlineNode::AutoLink { url, is_email } if !*is_email && !self.allows_link(url) => {
+                    result.push(InlineNode::text(url.as_str()));
+                    removed.push(std::mem::take(url));
+                    continue;
+                }
+                InlineNode::Image { url, alt, .. } if !self.allows_image(url) => {
+                    removed.push(std::mem::take(url));
+                    if !alt.is_empty() {
+                        result.push(InlineNode::text(alt.as_str()));
+                    }
+                    continue;
+                }
+                InlineNode::InlineFootnote { children } => {
+                    self.sanitize_inlines(children, removed);
+                }
+                _ => {}
+            }
+            result.push(inline);
+        }
+        *inlines = result;
+    }
+
+    /// Removes disallowed URLs from a block and its inline content, but not
+    /// from nested blocks, adding them to `removed`
+    pub(crate) fn sanitize_block(&self, block: &mut Node, removed: &mut Vec<String>) {
+        match block {
+            Node::Figure { url, .. } if !self.allows_image(url) => {
+                removed.push(url.clone());
+                *block = figure_text(block);
+            }
+            Node::Embed { url, .. } if !self.allows_link(url) => {
+                removed.push(url.clone());
+                *block = Node::paragraph(url.as_str());
+            }
+            _ => {}
+        }
+        for inlines in block.inline_runs_mut() {
+            self.sanitize_inlines(inlines, removed);
+        }
+    }
+}
+
+/// The paragraph a removed figure leaves: its caption, or its alt text
+/// without one
+pub(crate) fn figure_text(figure: &mut Node) -> Node {
+    let children = match figure {
+        Node::Figure { alt, caption, .. } if caption.is_empty() => {
+            vec![InlineNode::text(alt.as_str())]
+        }
+        Node::Figure { caption, .. } => std::mem::take(caption),
+        _ => Vec::new(),
+    };
+    Node::Paragraph { children }
+}
+
+impl Document {
+    /// Removes links, images, figures and embeds whose URL `policy` doesn't
+    /// allow, returning how many were removed
+    ///
+    /// Links keep their text and images their alt text; figures become a
+    /// paragraph with their caption, and embeds a paragraph with their URL
+    /// as text. HTML import applies this with the default policy, or the one
+    /// set with
+    /// [`HtmlOptions::with_url_policy`](crate::HtmlOptions::with_url_policy).
+    pub fn sanitize_urls(&mut self, policy: &UrlPolicy) -> usize {
+        let mut removed = Vec::new();
+        for node in &mut self.nodes {
+            node.walk_mut(&mut |block| policy.sanitize_block(block, &mut removed));
+        }
+        removed.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EditErrorKind;
+
+    #[test]
+    fn test_url_policy() {
+        let policy = UrlPolicy::new();
+        assert!(policy.allows_link("https://example.com"));
+        assert!(policy.allows_link("../guide.md#intro"));
+        assert!(policy.allows_link("mailto:me@example.com"));
+        assert!(!policy.allows_link("javascript:alert(1)"));
+        assert!(!policy.allows_link(" JaVa\tScript:alert(1)"));
+        assert!(!policy.allows_link("jav&#x61;script:alert(1)"));
+        assert!(!policy.allows_link("javascript&#58alert(1)"));
+        assert!(!policy.allows_link("javascript&colon;alert(1)"));
+        assert!(!policy.allows_link("data:image/png;base64,AAAA"));
+
+        assert!(policy.allows_image("data:image/png;base64,AAAA"));
+        assert!(!policy.allows_image("data:text/html,<script>alert(1)</script>"));
+        assert!(!policy.allows_image("mailto:me@example.com"));
+
+        let error = policy.check_link("vbscript:msgbox").unwrap_err();
+        assert_eq!(error.kind(), EditErrorKind::InvalidUrl);
+        assert_eq!(error.to_string(), "Link URL not allowed: vbscript:msgbox");
+
+        let custom = UrlPolicy::new()
+            .allow_link_scheme("ftp")
+            .deny_scheme("data");
+        assert!(custom.allows_link("ftp://example.com/file"));
+        assert!(!custom.allows_image("data:image/png;base64,AAAA"));
+    }
+
+    #[test]
+    fn test_sanitize_urls() {
+        let mut doc = Document::new();
+        doc.nodes.push(Node::paragraph_with_inlines(vec![
+            InlineNode::link("javascript:alert(1)", "click"),
+            InlineNode::text(" and "),
+            InlineNode::link("https://example.com", "safe"),
+            InlineNode::image("data:text/html,x", "logo"),
+        ]));
+        doc.nodes.push(Node::BlockQuote {
+            children: vec![Node::figure_with_properties(
+                "javascript:x",
+                "chart",
+                "",
+                Default::default(),
+            )],
+        });
+
+        assert_eq!(doc.sanitize_urls(&UrlPolicy::default()), 3);
+        assert_eq!(
+            doc.nodes[0].as_paragraph().unwrap(),
+            &[
+                InlineNode::text("click"),
+                InlineNode::text(" and "),
+                InlineNode::link("https://example.com", "safe"),
+                InlineNode::text("logo"),
+            ]
+        );
+        assert_eq!(
+            doc.nodes[1],
+            Node::BlockQuote {
+                children: vec![Node::paragraph("chart")],
+            }
+        );
+    }
+}
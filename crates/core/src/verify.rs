@@ -0,0 +1,516 @@
+//! Doctest-style verification of code examples
+//!
+//! A [`Verifier`] runs a set of registered [`CodeVerifier`]s over the code
+//! blocks reported by [`Document::code_blocks`] and collects the diagnostics
+//! per block, so documentation repositories can fail CI when an example no
+//! longer compiles or parses.
+//!
+//! Blocks whose info string carries the `ignore` attribute are skipped.
+
+use crate::{CodeBlockInfo, Document};
+use regex::Regex;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static MAIN_FN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bfn\s+main\s*\(").unwrap());
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The example is broken
+    Error,
+    /// The example works but deserves attention
+    Warning,
+}
+
+/// A problem found in a code block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// Human-readable description
+    pub message: String,
+    /// 1-based line within the code block, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+impl Diagnostic {
+    /// Creates an error diagnostic
+    pub fn error(message: impl Into<String>, line: Option<usize>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            line,
+        }
+    }
+
+    /// Creates a warning diagnostic
+    pub fn warning(message: impl Into<String>, line: Option<usize>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            line,
+        }
+    }
+}
+
+/// Checks code blocks of particular languages
+pub trait CodeVerifier {
+    /// Returns true if this verifier should check the block
+    fn accepts(&self, block: &CodeBlockInfo) -> bool;
+
+    /// Checks the block and returns the problems found
+    fn verify(&self, block: &CodeBlockInfo) -> Vec<Diagnostic>;
+}
+
+/// Verifies that `json` blocks contain valid JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonVerifier;
+
+impl CodeVerifier for JsonVerifier {
+    fn accepts(&self, block: &CodeBlockInfo) -> bool {
+        block.language == "json"
+    }
+
+    fn verify(&self, block: &CodeBlockInfo) -> Vec<Diagnostic> {
+        match serde_json::from_str::<serde_json::Value>(&block.code) {
+            Ok(_) => Vec::new(),
+            Err(err) => vec![Diagnostic::error(err.to_string(), Some(err.line()))],
+        }
+    }
+}
+
+/// Verifies that `rust` blocks type-check with `rustc`
+///
+/// Like rustdoc, examples without a `fn main` are wrapped in one. Blocks
+/// marked `compile_fail` must fail to compile instead. A compiler run that
+/// takes longer than the timeout is killed and reported as an error, as is
+/// a compiler that can't be started unless
+/// [`skip_if_unavailable`](Self::skip_if_unavailable) is set.
+#[derive(Debug, Clone)]
+pub struct RustcVerifier {
+    rustc: PathBuf,
+    edition: String,
+    timeout: Duration,
+    skip_if_unavailable: bool,
+}
+
+/// Why the compiler couldn't check an example
+enum CheckError {
+    /// The compiler couldn't be started
+    Unavailable(String),
+    /// The compiler timed out or couldn't be waited for
+    Failed(String),
+}
+
+impl Default for RustcVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RustcVerifier {
+    /// Uses `rustc` from the `PATH` with the 2021 edition and a 30 second
+    /// timeout
+    pub fn new() -> Self {
+        Self {
+            rustc: PathBuf::from("rustc"),
+            edition: "2021".to_string(),
+            timeout: Duration::from_secs(30),
+            skip_if_unavailable: false,
+        }
+    }
+
+    /// Uses a specific compiler binary
+    pub fn with_rustc(mut self, rustc: impl Into<PathBuf>) -> Self {
+        self.rustc = rustc.into();
+        self
+    }
+
+    /// Checks examples against the given edition
+    pub fn with_edition(mut self, edition: impl Into<String>) -> Self {
+        self.edition = edition.into();
+        self
+    }
+
+    /// Sets the time allowed for checking one example
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Reports a compiler that can't be started as a warning instead of an
+    /// error, for environments where examples are only checked when a
+    /// toolchain happens to be installed
+    pub fn skip_if_unavailable(mut self, skip: bool) -> Self {
+        self.skip_if_unavailable = skip;
+        self
+    }
+
+    /// Runs the compiler on the source and returns the error lines of its output
+    fn check(&self, source: &str) -> Result<Vec<String>, CheckError> {
+        // Metadata output is enough to type-check; keep it out of the way
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        let output_path = std::env::temp_dir().join(format!(
+            "md-core-verify-{}-{}.rmeta",
+            std::process::id(),
+            RUNS.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut child = Command::new(&self.rustc)
+            .args(["--edition", &self.edition])
+            .args(["--crate-type", "bin", "--crate-name", "example"])
+            .args(["--emit=metadata", "--error-format=short", "-o"])
+            .arg(&output_path)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| {
+                CheckError::Unavailable(format!("failed to run {}: {}", self.rustc.display(), err))
+            })?;
+
+        // Read stderr on its own thread so a full pipe can't stall the
+        // compiler while we wait for it
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut text = String::new();
+                let _ = stderr.read_to_string(&mut text);
+                text
+            })
+        });
+        // Write stdin on its own thread too, so a compiler that stops
+        // reading can't block us before the timeout is checked
+        let stdin = child.stdin.take().map(|mut stdin| {
+            let source = source.to_string();
+            std::thread::spawn(move || {
+                // A compiler that exits early closes the pipe; its status
+                // says why
+                let _ = stdin.write_all(source.as_bytes());
+            })
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            match child
+                .try_wait()
+                .map_err(|err| CheckError::Failed(err.to_string()))?
+            {
+                Some(status) => break status,
+                None if Instant::now() >= deadline => {
+                    // Killing the compiler closes its pipes, which ends the
+                    // writer and reader threads
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    let _ = std::fs::remove_file(&output_path);
+                    return Err(CheckError::Failed(format!(
+                        "{} timed out after {:?}",
+                        self.rustc.display(),
+                        self.timeout
+                    )));
+                }
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        if let Some(writer) = stdin {
+            let _ = writer.join();
+        }
+        let stderr = stderr
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        let _ = std::fs::remove_file(&output_path);
+
+        if status.success() {
+            return Ok(Vec::new());
+        }
+        Ok(stderr
+            .lines()
+            .filter(|line| is_error_line(line))
+            .filter(|line| !line.starts_with("error: aborting"))
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Whether a short-format rustc line reports an error, as opposed to a
+/// warning or note that merely mentions one
+fn is_error_line(line: &str) -> bool {
+    ["error:", "error["]
+        .iter()
+        .any(|prefix| line.starts_with(prefix) || line.contains(&format!(": {prefix}")))
+}
+
+/// Splits a short-format rustc line (`<anon>:3:5: error[E0425]: ...`)
+fn parse_rustc_line(line: &str, line_offset: usize) -> Diagnostic {
+    let mut parts = line.splitn(4, ':');
+    let (file, line_no, column, rest) = (parts.next(), parts.next(), parts.next(), parts.next());
+    match (
+        file,
+        line_no.and_then(|l| l.parse::<usize>().ok()),
+        column,
+        rest,
+    ) {
+        (Some(_), Some(line_no), Some(_), Some(rest)) => Diagnostic::error(
+            rest.trim(),
+            Some(line_no.saturating_sub(line_offset).max(1)),
+        ),
+        _ => Diagnostic::error(line.trim(), None),
+    }
+}
+
+impl CodeVerifier for RustcVerifier {
+    fn accepts(&self, block: &CodeBlockInfo) -> bool {
+        matches!(block.language.as_str(), "rust" | "rs")
+    }
+
+    fn verify(&self, block: &CodeBlockInfo) -> Vec<Diagnostic> {
+        let (source, line_offset) = if MAIN_FN.is_match(&block.code) {
+            (block.code.clone(), 0)
+        } else {
+            (format!("fn main() {{\n{}\n}}\n", block.code), 1)
+        };
+
+        let errors = match self.check(&source) {
+            Ok(errors) => errors,
+            Err(CheckError::Unavailable(err)) if self.skip_if_unavailable => {
+                return vec![Diagnostic::warning(err, None)];
+            }
+            Err(CheckError::Unavailable(err) | CheckError::Failed(err)) => {
+                return vec![Diagnostic::error(err, None)];
+            }
+        };
+
+        if block.has_attribute("compile_fail") {
+            if errors.is_empty() {
+                return vec![Diagnostic::error(
+                    "example marked compile_fail compiled successfully",
+                    None,
+                )];
+            }
+            return Vec::new();
+        }
+
+        errors
+            .iter()
+            .map(|line| parse_rustc_line(line, line_offset))
+            .collect()
+    }
+}
+
+/// The diagnostics found for one code block
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockVerification {
+    /// The verified block
+    pub block: CodeBlockInfo,
+    /// Problems found, empty if the block passed
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BlockVerification {
+    /// Returns true if no errors were found
+    pub fn passed(&self) -> bool {
+        !self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+/// Results of verifying every checked block of a document
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VerificationReport {
+    /// One entry per block that a verifier accepted, in document order
+    pub results: Vec<BlockVerification>,
+}
+
+impl VerificationReport {
+    /// Returns true if every verified block passed
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(BlockVerification::passed)
+    }
+
+    /// The blocks with errors
+    pub fn failures(&self) -> impl Iterator<Item = &BlockVerification> {
+        self.results.iter().filter(|result| !result.passed())
+    }
+}
+
+/// A set of code verifiers to run over a document
+#[derive(Default)]
+pub struct Verifier {
+    verifiers: Vec<Box<dyn CodeVerifier>>,
+}
+
+impl Verifier {
+    /// Creates a verifier without any checks registered
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a code verifier, returning the set
+    pub fn with(mut self, verifier: impl CodeVerifier + 'static) -> Self {
+        self.register(verifier);
+        self
+    }
+
+    /// Registers a code verifier
+    pub fn register(&mut self, verifier: impl CodeVerifier + 'static) {
+        self.verifiers.push(Box::new(verifier));
+    }
+
+    /// Verifies every code block of the document
+    pub fn verify(&self, document: &Document) -> VerificationReport {
+        let results = document
+            .code_blocks()
+            .blocks
+            .into_iter()
+            .filter(|block| !block.has_attribute("ignore"))
+            .filter_map(|block| {
+                let mut accepted = false;
+                let mut diagnostics = Vec::new();
+                for verifier in self.verifiers.iter().filter(|v| v.accepts(&block)) {
+                    accepted = true;
+                    diagnostics.extend(verifier.verify(&block));
+                }
+                accepted.then_some(BlockVerification { block, diagnostics })
+            })
+            .collect();
+
+        VerificationReport { results }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rustc_available() -> bool {
+        Command::new("rustc").arg("--version").output().is_ok()
+    }
+
+    #[test]
+    fn test_json_verifier() {
+        let mut doc = Document::new();
+        doc.add_code_block("{\"ok\": true}", "json");
+        doc.add_code_block("{\n  \"ok\" true\n}", "json");
+        doc.add_code_block("{oops", "json,ignore");
+        doc.add_code_block("not checked", "yaml");
+
+        let report = Verifier::new().with(JsonVerifier).verify(&doc);
+        assert_eq!(report.results.len(), 2);
+        assert!(report.results[0].passed());
+
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].block.path, vec![1]);
+        assert_eq!(failures[0].diagnostics[0].line, Some(2));
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn test_rustc_verifier() {
+        if !rustc_available() {
+            return;
+        }
+
+        let mut doc = Document::new();
+        doc.add_code_block("let x: u32 = 1;\nprintln!(\"{}\", x);", "rust");
+        doc.add_code_block("let x: u32 = \"one\";", "rust");
+        doc.add_code_block("let x: u32 = \"one\";", "rust,compile_fail");
+
+        let report = Verifier::new().with(RustcVerifier::new()).verify(&doc);
+        assert!(report.results[0].passed());
+        assert!(report.results[2].passed());
+
+        let failed = &report.results[1];
+        assert!(!failed.passed());
+        assert_eq!(failed.diagnostics[0].line, Some(1));
+        assert!(failed.diagnostics[0].message.contains("mismatched types"));
+    }
+
+    #[test]
+    fn test_rustc_unavailable() {
+        let mut doc = Document::new();
+        doc.add_code_block("let x = 1;", "rust");
+        let missing = RustcVerifier::new().with_rustc("/nonexistent/rustc");
+
+        // A missing compiler fails CI unless skipping was asked for
+        let report = Verifier::new().with(missing.clone()).verify(&doc);
+        assert!(!report.is_ok());
+        assert!(
+            report.results[0].diagnostics[0]
+                .message
+                .contains("failed to run")
+        );
+
+        let report = Verifier::new()
+            .with(missing.skip_if_unavailable(true))
+            .verify(&doc);
+        assert!(report.is_ok());
+        assert_eq!(report.results[0].diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_parse_rustc_line() {
+        let diagnostic = parse_rustc_line("<anon>:3:5: error[E0425]: cannot find value `y`", 1);
+        assert_eq!(diagnostic.line, Some(2));
+        assert_eq!(diagnostic.message, "error[E0425]: cannot find value `y`");
+
+        let diagnostic = parse_rustc_line("error: linking failed", 0);
+        assert_eq!(diagnostic.line, None);
+    }
+
+    #[test]
+    fn test_rustc_error_lines() {
+        assert!(is_error_line(
+            "<anon>:3:5: error[E0425]: cannot find value `y`"
+        ));
+        assert!(is_error_line("<anon>:1:1: error: expected item"));
+        assert!(is_error_line("error: linking failed"));
+        assert!(!is_error_line(
+            "<anon>:2:9: warning: unused variable: `error_count`"
+        ));
+        assert!(!is_error_line(
+            "<anon>:4:1: note: see the error handling docs"
+        ));
+
+        assert!(MAIN_FN.is_match("fn main() {}"));
+        assert!(MAIN_FN.is_match("pub fn main () {}"));
+        assert!(!MAIN_FN.is_match("fn main_loop() {}"));
+        assert!(!MAIN_FN.is_match("// no fn mainly here"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rustc_verifier_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script =
+            std::env::temp_dir().join(format!("md-core-slow-rustc-{}", std::process::id()));
+        std::fs::write(&script, "#!/bin/sh\nexec sleep 10\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // More source than a pipe holds, which the compiler never reads
+        let mut doc = Document::new();
+        doc.add_code_block("let x = 1;\n".repeat(100_000), "rust");
+        let verifier = RustcVerifier::new()
+            .with_rustc(&script)
+            .with_timeout(Duration::from_millis(100));
+        let started = Instant::now();
+        let report = Verifier::new().with(verifier).verify(&doc);
+        std::fs::remove_file(&script).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let diagnostic = &report.results[0].diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert!(diagnostic.message.contains("timed out"));
+    }
+}
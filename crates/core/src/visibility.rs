@@ -0,0 +1,165 @@
+//! Draft and hidden blocks
+//!
+//! A [`Node::VisibilityBlock`] holds blocks that only some exports include.
+//! HTML export leaves out hidden blocks, and draft blocks unless
+//! [`HtmlOptions::with_drafts`](crate::HtmlOptions::with_drafts) is set.
+//! Markdown keeps every block, wrapped in `<!-- draft -->` ...
+//! `<!-- /draft -->` comment markers, so the visibility survives a round
+//! trip and stays invisible when the Markdown is rendered elsewhere.
+
+use crate::{Document, Node, Visibility};
+
+/// The comment opening or closing a block of a visibility in Markdown
+pub(crate) fn marker(visibility: Visibility, closing: bool) -> String {
+    let slash = if closing { "/" } else { "" };
+    format!("<!-- {}{} -->", slash, visibility.as_str())
+}
+
+/// Reads a comment marker, returning the visibility and whether it closes
+/// a block
+fn parse_marker(node: &Node) -> Option<(Visibility, bool)> {
+    let Node::HtmlComment { text } = node else {
+        return None;
+    };
+    let name = text.trim();
+    let (name, closing) = match name.strip_prefix('/') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    match Visibility::from_name(name)? {
+        Visibility::Visible => None,
+        visibility => Some((visibility, closing)),
+    }
+}
+
+/// Wraps the blocks between matching comment markers, at any depth, in
+/// visibility blocks
+///
+/// Markers without a match are kept as comments.
+pub(crate) fn wrap_markers(nodes: &mut Vec<Node>) {
+    // Open markers with the blocks before them
+    let mut open: Vec<(Visibility, Node, Vec<Node>)> = Vec::new();
+    let mut current = Vec::new();
+    for mut node in nodes.drain(..) {
+        match parse_marker(&node) {
+            Some((visibility, false)) => {
+                open.push((visibility, node, std::mem::take(&mut current)));
+            }
+            Some((visibility, true)) if open.last().is_some_and(|(v, ..)| *v == visibility) => {
+                let (_, _, before) = open.pop().expect("checked above");
+                let children = std::mem::replace(&mut current, before);
+                current.push(Node::VisibilityBlock {
+                    visibility,
+                    children,
+                });
+            }
+            _ => {
+                for list in node.block_lists_mut() {
+                    wrap_markers(list);
+                }
+                current.push(node);
+            }
+        }
+    }
+    while let Some((_, marker, before)) = open.pop() {
+        let after = std::mem::replace(&mut current, before);
+        current.push(marker);
+        current.extend(after);
+    }
+    *nodes = current;
+}
+
+/// Removes the blocks `keep` rejects, at any depth
+fn retain_blocks(nodes: &mut Vec<Node>, keep: &dyn Fn(&Node) -> bool) {
+    nodes.retain(|node| keep(node));
+    for node in nodes {
+        for list in node.block_lists_mut() {
+            retain_blocks(list, keep);
+        }
+    }
+}
+
+/// Returns true if an export includes blocks of a visibility
+pub(crate) fn is_published(visibility: Visibility, include_drafts: bool) -> bool {
+    match visibility {
+        Visibility::Visible => true,
+        Visibility::Draft => include_drafts,
+        Visibility::Hidden => false,
+    }
+}
+
+impl Document {
+    /// Returns a copy of the document without hidden blocks, and without
+    /// draft blocks unless `include_drafts` is set
+    pub fn published(&self, include_drafts: bool) -> Document {
+        let mut document = self.clone();
+        retain_blocks(&mut document.nodes, &|node| match node {
+            Node::VisibilityBlock { visibility, .. } => is_published(*visibility, include_drafts),
+            _ => true,
+        });
+        document
+    }
+
+    /// Returns the visibility of the top-level node at `index`
+    ///
+    /// Nodes outside visibility blocks are visible.
+    pub fn visibility(&self, index: usize) -> Option<Visibility> {
+        self.nodes.get(index).map(|node| match node {
+            Node::VisibilityBlock { visibility, .. } => *visibility,
+            _ => Visibility::Visible,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HtmlOptions, Markdown, MarkdownOptions, Text};
+
+    #[test]
+    fn test_visibility_round_trip() {
+        let markdown = "Intro\n\n<!-- draft -->\n\nWork in progress\n\n\
+                        <!-- hidden -->\n\nNote to self\n\n<!-- /hidden -->\n\n\
+                        <!-- /draft -->\n\n> <!-- hidden -->\n>\n> Quoted note\n>\n> <!-- /hidden -->\n\n\
+                        <!-- draft -->\n\nUnclosed";
+        let doc = Document::try_from(Text::<Markdown>::new(markdown)).unwrap();
+        assert_eq!(doc.nodes.len(), 5);
+        assert_eq!(
+            doc.nodes[1],
+            Node::VisibilityBlock {
+                visibility: Visibility::Draft,
+                children: vec![
+                    Node::paragraph("Work in progress"),
+                    Node::VisibilityBlock {
+                        visibility: Visibility::Hidden,
+                        children: vec![Node::paragraph("Note to self")],
+                    },
+                ],
+            }
+        );
+        assert_eq!(doc.visibility(1), Some(Visibility::Draft));
+        assert!(matches!(
+            &doc.nodes[2],
+            Node::BlockQuote { children } if matches!(children[0], Node::VisibilityBlock { .. })
+        ));
+        assert!(matches!(doc.nodes[3], Node::HtmlComment { .. }));
+
+        let md: Text<Markdown> = doc.as_ref().try_into().unwrap();
+        assert!(
+            md.as_str()
+                .contains("<!-- draft -->\n\nWork in progress\n\n<!-- hidden -->")
+        );
+        let reparsed = Document::try_from(md).unwrap();
+        assert_eq!(reparsed.nodes, doc.nodes);
+
+        let published = doc.to_markdown_with(&MarkdownOptions::new().for_publishing(false));
+        assert!(!published.contains("Work in progress"));
+        assert!(!published.contains("Quoted note"));
+
+        let html = doc.to_html_with(&HtmlOptions::new()).html;
+        assert!(html.starts_with("<p>Intro</p><blockquote></blockquote>"));
+        let html = doc.to_html_with(&HtmlOptions::new().with_drafts()).html;
+        assert!(html.contains("<div data-visibility=\"draft\"><p>Work in progress</p></div>"));
+        assert!(!html.contains("Note to self"));
+    }
+}
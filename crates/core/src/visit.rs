@@ -0,0 +1,228 @@
+//! Visitor-based traversal of the document tree
+//!
+//! [`Document::walk`] and [`Document::walk_mut`] visit every block in reading
+//! order, parents before children. For each block the inline content it owns
+//! (paragraph text, heading text, table cells, definition terms, figure
+//! captions) is visited before any nested blocks, and inline containers such
+//! as links are visited before their children.
+
+use crate::{Document, InlineNode, Node};
+
+/// What a traversal should do after visiting a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisitControl {
+    /// Visit the node's children, then carry on
+    #[default]
+    Continue,
+    /// Carry on without visiting the node's children
+    SkipChildren,
+    /// End the traversal
+    Stop,
+}
+
+/// Read-only visitor for [`Document::walk`]
+///
+/// Both methods default to [`VisitControl::Continue`], so implementors only
+/// override the ones they need.
+pub trait Visitor {
+    /// Called for every block node
+    fn visit_node(&mut self, _node: &Node) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Called for every inline node
+    fn visit_inline(&mut self, _inline: &InlineNode) -> VisitControl {
+        VisitControl::Continue
+    }
+}
+
+/// Mutating visitor for [`Document::walk_mut`]
+///
+/// Children are visited after their parent returns, so a visitor may replace
+/// a node's children and the traversal will descend into the new ones.
+pub trait VisitorMut {
+    /// Called for every block node
+    fn visit_node(&mut self, _node: &mut Node) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Called for every inline node
+    fn visit_inline(&mut self, _inline: &mut InlineNode) -> VisitControl {
+        VisitControl::Continue
+    }
+}
+
+/// Returns false once the traversal has been stopped
+fn walk_node(node: &Node, visitor: &mut dyn Visitor) -> bool {
+    match visitor.visit_node(node) {
+        VisitControl::Stop => return false,
+        VisitControl::SkipChildren => return true,
+        VisitControl::Continue => {}
+    }
+    node.inline_runs()
+        .into_iter()
+        .flatten()
+        .all(|inline| walk_inline(inline, visitor))
+        && node
+            .child_blocks()
+            .into_iter()
+            .all(|child| walk_node(child, visitor))
+}
+
+fn walk_inline(inline: &InlineNode, visitor: &mut dyn Visitor) -> bool {
+    match visitor.visit_inline(inline) {
+        VisitControl::Stop => return false,
+        VisitControl::SkipChildren => return true,
+        VisitControl::Continue => {}
+    }
+    match inline {
+        InlineNode::Link { children, .. } | InlineNode::InlineFootnote { children } => {
+            children.iter().all(|child| walk_inline(child, visitor))
+        }
+        _ => true,
+    }
+}
+
+fn walk_node_mut(node: &mut Node, visitor: &mut dyn VisitorMut) -> bool {
+    match visitor.visit_node(node) {
+        VisitControl::Stop => return false,
+        VisitControl::SkipChildren => return true,
+        VisitControl::Continue => {}
+    }
+    node.inline_runs_mut()
+        .into_iter()
+        .flatten()
+        .all(|inline| walk_inline_mut(inline, visitor))
+        && node
+            .child_blocks_mut()
+            .into_iter()
+            .all(|child| walk_node_mut(child, visitor))
+}
+
+fn walk_inline_mut(inline: &mut InlineNode, visitor: &mut dyn VisitorMut) -> bool {
+    match visitor.visit_inline(inline) {
+        VisitControl::Stop => return false,
+        VisitControl::SkipChildren => return true,
+        VisitControl::Continue => {}
+    }
+    match inline {
+        InlineNode::Link { children, .. } | InlineNode::InlineFootnote { children } => children
+            .iter_mut()
+            .all(|child| walk_inline_mut(child, visitor)),
+        _ => true,
+    }
+}
+
+impl Document {
+    /// Visits every node of the document in reading order
+    ///
+    /// Returns false if the visitor stopped the traversal early.
+    pub fn walk(&self, visitor: &mut impl Visitor) -> bool {
+        self.nodes.iter().all(|node| walk_node(node, visitor))
+    }
+
+    /// Visits every node of the document in reading order, allowing changes
+    ///
+    /// Returns false if the visitor stopped the traversal early.
+    pub fn walk_mut(&mut self, visitor: &mut impl VisitorMut) -> bool {
+        self.nodes
+            .iter_mut()
+            .all(|node| walk_node_mut(node, visitor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Markdown, Text};
+
+    const SOURCE: &str = "# Intro\n\n\
+                          See [the *docs*](https://docs.rs) for more.\n\n\
+                          > Quoted [link](https://quoted.example)\n\n\
+                          - Item with [another](https://item.example)\n\n\
+                          ## Next\n\n\
+                          Trailing [link](https://late.example)\n";
+
+    #[derive(Default)]
+    struct LinkCollector {
+        urls: Vec<String>,
+        skip_quotes: bool,
+        stop_at_second_heading: bool,
+        headings: usize,
+    }
+
+    impl Visitor for LinkCollector {
+        fn visit_node(&mut self, node: &Node) -> VisitControl {
+            match node {
+                Node::BlockQuote { .. } if self.skip_quotes => VisitControl::SkipChildren,
+                Node::Heading { .. } => {
+                    self.headings += 1;
+                    if self.stop_at_second_heading && self.headings == 2 {
+                        VisitControl::Stop
+                    } else {
+                        VisitControl::Continue
+                    }
+                }
+                _ => VisitControl::Continue,
+            }
+        }
+
+        fn visit_inline(&mut self, inline: &InlineNode) -> VisitControl {
+            if let InlineNode::Link { url, .. } = inline {
+                self.urls.push(url.clone());
+            }
+            VisitControl::Continue
+        }
+    }
+
+    fn document() -> Document {
+        Document::try_from(Text::<Markdown>::new(SOURCE)).unwrap()
+    }
+
+    #[test]
+    fn test_walk_visits_nested_content() {
+        let mut collector = LinkCollector::default();
+        assert!(document().walk(&mut collector));
+        assert_eq!(collector.urls.len(), 4);
+        assert_eq!(collector.urls[1], "https://quoted.example");
+    }
+
+    #[test]
+    fn test_walk_skip_and_stop() {
+        let mut collector = LinkCollector {
+            skip_quotes: true,
+            stop_at_second_heading: true,
+            ..Default::default()
+        };
+        assert!(!document().walk(&mut collector));
+        assert_eq!(collector.urls, ["https://docs.rs", "https://item.example"]);
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        struct Shout;
+
+        impl VisitorMut for Shout {
+            fn visit_node(&mut self, node: &mut Node) -> VisitControl {
+                if matches!(node, Node::Heading { .. }) {
+                    VisitControl::SkipChildren
+                } else {
+                    VisitControl::Continue
+                }
+            }
+
+            fn visit_inline(&mut self, inline: &mut InlineNode) -> VisitControl {
+                if let InlineNode::Text(text) = inline {
+                    text.text = text.text.to_uppercase();
+                }
+                VisitControl::Continue
+            }
+        }
+
+        let mut doc = document();
+        assert!(doc.walk_mut(&mut Shout));
+        assert_eq!(doc.nodes[0].plain_text(), "Intro");
+        assert_eq!(doc.nodes[1].plain_text(), "SEE THE DOCS FOR MORE.");
+        assert!(doc.nodes[3].plain_text().contains("ITEM WITH ANOTHER"));
+    }
+}
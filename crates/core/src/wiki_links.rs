@@ -0,0 +1,148 @@
+//! Wiki-links between notes
+//!
+//! `[[Page Name]]` and `[[Page Name|Alias]]` are parsed from Markdown into
+//! [`InlineNode::WikiLink`], which keeps the target as written. Like
+//! mentions, a wiki-link doesn't know where its page lives: a
+//! [`WikiLinkResolver`] passed to
+//! [`HtmlOptions::with_wiki_link_resolver`](crate::HtmlOptions::with_wiki_link_resolver)
+//! supplies the URL at export time. [`Document::wiki_links`] lists the
+//! links of a note, for building a backlink index across notes.
+
+use crate::{Document, InlineNode};
+use serde::Serialize;
+
+/// Maps wiki-link targets to the URL they link to
+///
+/// Closures taking the target and returning `Option<String>` are resolvers
+/// too.
+pub trait WikiLinkResolver {
+    /// Returns the URL for the target, or `None` to render it unlinked
+    fn resolve(&self, target: &str) -> Option<String>;
+}
+
+impl<F> WikiLinkResolver for F
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn resolve(&self, target: &str) -> Option<String> {
+        self(target)
+    }
+}
+
+/// A wiki-link found in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WikiLinkInfo {
+    /// The target as written, e.g. `Page Name#Section`
+    pub target: String,
+    /// Text shown instead of the target, if any
+    pub alias: Option<String>,
+    /// Index of the top-level node containing the link
+    pub node_index: usize,
+}
+
+impl WikiLinkInfo {
+    /// The linked page, without a `#heading` part
+    pub fn page(&self) -> &str {
+        page_of(&self.target)
+    }
+}
+
+/// The page of a wiki-link target, without a `#heading` part
+pub(crate) fn page_of(target: &str) -> &str {
+    target.split('#').next().unwrap_or_default().trim()
+}
+
+/// The text a wiki-link is written as in Markdown
+pub(crate) fn wiki_link_markdown(target: &str, alias: Option<&str>) -> String {
+    match alias {
+        Some(alias) => format!("[[{target}|{alias}]]"),
+        None => format!("[[{target}]]"),
+    }
+}
+
+impl Document {
+    /// Lists every wiki-link in document order
+    pub fn wiki_links(&self) -> Vec<WikiLinkInfo> {
+        let mut links = Vec::new();
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            node.walk(&mut |block| {
+                for inline in block.inline_runs().into_iter().flatten() {
+                    inline.walk(&mut |inline| {
+                        if let InlineNode::WikiLink { target, alias } = inline {
+                            links.push(WikiLinkInfo {
+                                target: target.clone(),
+                                alias: alias.clone(),
+                                node_index,
+                            });
+                        }
+                    });
+                }
+            });
+        }
+        links
+    }
+
+    /// The distinct pages the document links to, in the order they are
+    /// first linked
+    pub fn linked_pages(&self) -> Vec<String> {
+        let mut pages: Vec<String> = Vec::new();
+        for link in self.wiki_links() {
+            let page = link.page();
+            if !page.is_empty() && !pages.iter().any(|p| p == page) {
+                pages.push(page.to_string());
+            }
+        }
+        pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GraphEdgeKind, HtmlOptions, Markdown, Node, Text};
+
+    #[test]
+    fn test_wiki_links() {
+        let markdown = "# Notes\n\nSee [[Rust Traits]] and [[Rust Traits#Objects|trait objects]], \
+                        or [[Missing]].";
+        let doc = Document::try_from(Text::<Markdown>::new(markdown)).unwrap();
+        assert_eq!(
+            doc.nodes[1],
+            Node::Paragraph {
+                children: vec![
+                    InlineNode::text("See "),
+                    InlineNode::wiki_link("Rust Traits"),
+                    InlineNode::text(" and "),
+                    InlineNode::wiki_link_with_alias("Rust Traits#Objects", "trait objects"),
+                    InlineNode::text(", or "),
+                    InlineNode::wiki_link("Missing"),
+                    InlineNode::text("."),
+                ]
+            }
+        );
+        let links = doc.wiki_links();
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[1].page(), "Rust Traits");
+        assert_eq!(links[1].node_index, 1);
+        assert_eq!(doc.linked_pages(), vec!["Rust Traits", "Missing"]);
+
+        let exported = Text::<Markdown>::try_from(&doc).unwrap();
+        assert_eq!(exported.as_str(), markdown);
+
+        let options = HtmlOptions::new().with_wiki_link_resolver(|target: &str| {
+            (page_of(target) != "Missing").then(|| format!("/notes/{}", target.replace(' ', "-")))
+        });
+        let html = doc.to_html_with(&options).html;
+        assert!(html.contains(
+            "See <a class=\"wiki-link\" href=\"/notes/Rust-Traits\">Rust Traits</a> and \
+             <a class=\"wiki-link\" href=\"/notes/Rust-Traits#Objects\">trait objects</a>, \
+             or <span class=\"wiki-link\">Missing</span>."
+        ));
+
+        let graph = doc.graph();
+        assert!(graph.edges.iter().any(|edge| edge.from == "h0"
+            && edge.to == "doc:Rust Traits"
+            && edge.kind == GraphEdgeKind::Link));
+        assert!(graph.nodes.iter().any(|node| node.id == "doc:Missing"));
+    }
+}
@@ -8,7 +8,9 @@ fn test_add_task_item_integration() {
 
     // Verify initial state
     match &doc.nodes[task_list_idx] {
-        Node::List { list_type, items } => {
+        Node::List {
+            list_type, items, ..
+        } => {
             assert_eq!(*list_type, ListType::Task);
             assert_eq!(items.len(), 2);
             assert_eq!(items[0].checked, Some(false));
@@ -14,6 +14,8 @@ fn test_edit_task_item_integration() {
     let list = Node::List {
         list_type: ListType::Task,
         items,
+        start: None,
+        loose: false,
     };
 
     document.nodes.push(list);
@@ -108,6 +110,8 @@ fn test_edit_task_item_integration() {
     let list = Node::List {
         list_type: ListType::Task,
         items,
+        start: None,
+        loose: false,
     };
 
     document.nodes.push(list);
@@ -0,0 +1,92 @@
+use md_core::{
+    Document, Editor, FigureAlignment, FigureProperties, Html, Json, Markdown, Node, Text,
+};
+
+fn figure_document() -> Document {
+    let mut editor = Editor::new(Document::with_title("Gallery"));
+    editor
+        .insert_figure(
+            1,
+            "https://example.com/sunset.jpg",
+            "Sunset over the bay",
+            "Evening light, June 2024",
+            FigureProperties::new()
+                .with_size(640, 480)
+                .with_alignment(FigureAlignment::Center)
+                .with_lazy_loading(true),
+        )
+        .unwrap();
+    editor.document().borrow().clone()
+}
+
+#[test]
+fn test_insert_figure_with_undo() {
+    let mut editor = Editor::new(Document::new());
+    editor
+        .insert_figure(
+            0,
+            "cat.png",
+            "A cat",
+            "Our cat",
+            FigureProperties::default(),
+        )
+        .unwrap();
+
+    {
+        let doc = editor.document().borrow();
+        let (url, caption) = doc.nodes[0].as_figure().unwrap();
+        assert_eq!(url, "cat.png");
+        assert_eq!(caption.len(), 1);
+        assert_eq!(doc.nodes[0].plain_text(), "Our cat");
+    }
+
+    editor.undo().unwrap();
+    assert!(editor.document().borrow().nodes.is_empty());
+}
+
+#[test]
+fn test_figure_to_html() {
+    let doc = figure_document();
+    let html = Text::<Html>::try_from(&doc).unwrap();
+
+    assert!(html.contains("<figure class=\"figure figure-center\">"));
+    assert!(html.contains(
+        "<img src=\"https://example.com/sunset.jpg\" alt=\"Sunset over the bay\" width=\"640\" height=\"480\" loading=\"lazy\">"
+    ));
+    assert!(html.contains("<figcaption>Evening light, June 2024</figcaption></figure>"));
+}
+
+#[test]
+fn test_figure_markdown_round_trip() {
+    let doc = figure_document();
+    let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+
+    assert!(markdown.contains(
+        "![Sunset over the bay](https://example.com/sunset.jpg){width=640 height=480 align=center loading=lazy}\n*Evening light, June 2024*"
+    ));
+    assert_eq!(Document::try_from(markdown).unwrap().nodes, doc.nodes);
+
+    // Attribute-only figures and plain captions round-trip too
+    let mut doc = Document::new();
+    doc.nodes.push(Node::figure_with_properties(
+        "a.png",
+        "A",
+        "",
+        FigureProperties::new().with_width(100),
+    ));
+    doc.add_figure("b.png", "B", "Second figure");
+    let markdown = Text::<Markdown>::try_from(&doc).unwrap();
+    assert_eq!(Document::try_from(markdown).unwrap().nodes, doc.nodes);
+
+    // An image on its own stays an inline image
+    let doc = Document::try_from(Text::<Markdown>::new("![alt](c.png)")).unwrap();
+    assert!(doc.nodes[0].as_paragraph().is_some());
+}
+
+#[test]
+fn test_figure_json_round_trip() {
+    let doc = figure_document();
+    let json = Text::<Json>::try_from(&doc).unwrap();
+    assert!(json.contains("\"type\": \"figure\"") || json.contains("\"type\":\"figure\""));
+    assert_eq!(Document::try_from(json).unwrap(), doc);
+}
@@ -14,6 +14,8 @@ fn test_move_task_item_integration() {
     let list = Node::List {
         list_type: ListType::Task,
         items,
+        start: None,
+        loose: false,
     };
 
     document.nodes.push(list);
@@ -9,7 +9,9 @@ fn test_toggle_task_integration() {
 
     // Verify initial state
     match &doc.nodes[task_list_idx] {
-        Node::List { list_type, items } => {
+        Node::List {
+            list_type, items, ..
+        } => {
             assert_eq!(*list_type, ListType::Task);
             assert_eq!(items.len(), 3);
             assert_eq!(items[0].checked, Some(false));